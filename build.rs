@@ -0,0 +1,24 @@
+fn main() {
+    #[cfg(feature = "llama")]
+    build_llama_shim();
+}
+
+/// Compile the llama.cpp shim and link libllama
+///
+/// `LLAMA_CPP_DIR` points at a llama.cpp install prefix with `include/llama.h`
+/// and `lib/libllama.*`; without it the system search paths are used.
+#[cfg(feature = "llama")]
+fn build_llama_shim() {
+    println!("cargo:rerun-if-changed=src/llm/embedded/llama_shim.c");
+    println!("cargo:rerun-if-env-changed=LLAMA_CPP_DIR");
+
+    let mut build = cc::Build::new();
+    build.file("src/llm/embedded/llama_shim.c");
+    if let Ok(dir) = std::env::var("LLAMA_CPP_DIR") {
+        let dir = std::path::Path::new(&dir);
+        build.include(dir.join("include"));
+        println!("cargo:rustc-link-search=native={}", dir.join("lib").display());
+    }
+    build.compile("nexa_llama_shim");
+    println!("cargo:rustc-link-lib=dylib=llama");
+}
@@ -1,8 +1,9 @@
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
-use utoipa::ToSchema;
+use crate::llm::GuardrailConfig;
 
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "api-docs", derive(utoipa::ToSchema))]
 pub struct Task {
     pub id: String,
     pub title: String,
@@ -17,16 +18,20 @@ pub struct Task {
     pub priority: i32,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "api-docs", derive(utoipa::ToSchema))]
 pub enum TaskStatus {
     Pending,
     InProgress,
     Completed,
     Failed,
     Cancelled,
+    /// Was in progress when the server stopped unexpectedly
+    Interrupted,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "api-docs", derive(utoipa::ToSchema))]
 pub struct Agent {
     pub id: String,
     pub name: String,
@@ -34,9 +39,17 @@ pub struct Agent {
     pub status: AgentStatus,
     pub current_task: Option<String>,
     pub last_heartbeat: DateTime<Utc>,
+    /// Sent as the system message of every completion run for this agent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+    /// Screen prompts and responses of this agent's completions
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[cfg_attr(feature = "api-docs", schema(value_type = Vec<Object>))]
+    pub guardrails: Vec<GuardrailConfig>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "api-docs", derive(utoipa::ToSchema))]
 pub enum AgentStatus {
     Idle,
     Busy,
@@ -79,9 +92,21 @@ impl Agent {
             status: AgentStatus::Idle,
             current_task: None,
             last_heartbeat: Utc::now(),
+            system_prompt: None,
+            guardrails: Vec::new(),
         }
     }
 
+    pub fn with_system_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.system_prompt = Some(prompt.into());
+        self
+    }
+
+    pub fn with_guardrail(mut self, guardrail: GuardrailConfig) -> Self {
+        self.guardrails.push(guardrail);
+        self
+    }
+
     pub fn update_heartbeat(&mut self) {
         self.last_heartbeat = Utc::now();
     }
@@ -0,0 +1,183 @@
+//! API Authentication
+//!
+//! Static API keys guarding the REST API:
+//! - Keys are random tokens shown once; the config keeps only their SHA-256 hash
+//! - Each key has a role, checked against the route by the authorization layer
+//! - Requests present a key as `Authorization: Bearer <key>` or `X-API-Key: <key>`
+//! - Bearer tokens from an OpenID Connect issuer are accepted too, when one is configured
+//! - The API stays open while neither is configured, and the probes always are
+
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderMap, HeaderValue};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use uuid::Uuid;
+use crate::api::ApiError;
+use crate::api::jwt::JwtValidator;
+use crate::api::rbac::Role;
+use crate::config::{ApiKeyConfig, Config, ConfigService};
+use crate::error::NexaError;
+use tracing::debug;
+
+/// Header carrying an API key, as an alternative to a bearer token
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Environment variable with the key the CLI presents to the daemon
+pub const API_KEY_ENV: &str = "NEXA_API_KEY";
+
+/// Paths served without a key, so orchestrators can probe the daemon
+pub(crate) const OPEN_PATHS: &[&str] = &["/healthz", "/readyz", "/startupz"];
+
+/// SHA-256 of a key, as stored in the config
+pub fn hash_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+/// The configured key matching `key`, if any
+pub fn authenticate<'a>(keys: &'a [ApiKeyConfig], key: &str) -> Option<&'a ApiKeyConfig> {
+    let hash = hash_key(key);
+    keys.iter().find(|k| constant_time_eq(k.hash.as_bytes(), hash.as_bytes()))
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Add a key to the config file, returning the key itself
+///
+/// The key cannot be recovered afterwards; only its hash is written.
+pub fn create_key(config_path: &PathBuf, id: &str, role: Role) -> Result<String, NexaError> {
+    let key = format!("nexa_{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+    let entry = ApiKeyConfig { id: id.to_string(), hash: hash_key(&key), role };
+    Config::edit(config_path, |document| {
+        let keys = api_keys(document)?;
+        if keys.iter().any(|k| k.get("id").and_then(|id| id.as_str()) == Some(entry.id.as_str())) {
+            return Err(NexaError::invalid_input(format!("API key '{}' already exists", entry.id)));
+        }
+        keys.push(serde_json::to_value(&entry)?);
+        Ok(())
+    })?;
+    Ok(key)
+}
+
+/// Remove a key from the config file
+pub fn revoke_key(config_path: &PathBuf, id: &str) -> Result<(), NexaError> {
+    Config::edit(config_path, |document| {
+        let keys = api_keys(document)?;
+        let before = keys.len();
+        keys.retain(|k| k.get("id").and_then(|id| id.as_str()) != Some(id));
+        if keys.len() == before {
+            return Err(NexaError::not_found(format!("API key '{}' not found", id)));
+        }
+        Ok(())
+    })
+}
+
+/// The `api.keys` list of a config document, created when missing
+fn api_keys(document: &mut serde_json::Value) -> Result<&mut Vec<serde_json::Value>, NexaError> {
+    let invalid = || NexaError::config("The `api` section of the config file is not a mapping");
+    let api = document.as_object_mut().ok_or_else(invalid)?
+        .entry("api").or_insert_with(|| serde_json::json!({}))
+        .as_object_mut().ok_or_else(invalid)?;
+    api.entry("keys").or_insert_with(|| serde_json::json!([]))
+        .as_array_mut()
+        .ok_or_else(|| NexaError::config("`api.keys` in the config file is not a list"))
+}
+
+/// Caller of an authenticated request, available to handlers as an extension
+#[derive(Debug, Clone, PartialEq)]
+pub struct Principal {
+    /// API key id, or token subject
+    pub id: String,
+    pub role: Role,
+}
+
+/// Authenticates API requests with the keys and issuer currently configured
+#[derive(Clone)]
+pub(crate) struct Authenticator {
+    config: ConfigService,
+    jwt: JwtValidator,
+}
+
+impl Authenticator {
+    pub(crate) fn new(config: ConfigService) -> Self {
+        Self { config, jwt: JwtValidator::new() }
+    }
+
+    /// Who made a request presenting `headers`, or why they may not
+    ///
+    /// Bearer tokens shaped like a JWT are validated as one when an issuer is configured.
+    async fn authenticate(&self, headers: &HeaderMap) -> Result<Principal, NexaError> {
+        let api = self.config.current().api;
+        let header = |name| headers.get(name).and_then(|value: &HeaderValue| value.to_str().ok());
+        let bearer = header(header::AUTHORIZATION.as_str()).and_then(|value| value.strip_prefix("Bearer ")).map(str::trim);
+
+        if let (Some(token), Some(jwt)) = (bearer, &api.jwt) {
+            if token.split('.').count() == 3 {
+                let identity = self.jwt.validate(jwt, token).await?;
+                let role = identity.role.ok_or_else(|| {
+                    NexaError::forbidden(format!("'{}' has none of the roles mapped in `api.jwt.roles`", identity.subject))
+                })?;
+                return Ok(Principal { id: identity.subject, role });
+            }
+        }
+        let presented = bearer.or_else(|| header(API_KEY_HEADER).map(str::trim))
+            .ok_or_else(|| NexaError::unauthorized("An API key or token is required"))?;
+        let key = authenticate(&api.keys, presented)
+            .ok_or_else(|| NexaError::unauthorized("Invalid API key"))?;
+        Ok(Principal { id: key.id.clone(), role: key.role })
+    }
+}
+
+/// Refuse requests without a valid key or token while any is configured
+///
+/// The caller is left in the request's extensions for the authorization layer.
+pub(crate) async fn require_auth(State(auth): State<Authenticator>, mut request: Request, next: Next) -> Response {
+    let api = auth.config.current().api;
+    if (api.keys.is_empty() && api.jwt.is_none()) || OPEN_PATHS.contains(&request.uri().path()) {
+        return next.run(request).await;
+    }
+
+    let principal = match auth.authenticate(request.headers()).await {
+        Ok(principal) => principal,
+        Err(e) => return ApiError(e).into_response(),
+    };
+    debug!("{} {} as '{}'", request.method(), request.uri().path(), principal.id);
+    request.extensions_mut().insert(principal);
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_api_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.yml");
+        std::fs::write(&path, "llm:\n  server_url: ${NEXA_TEST_API_AUTH_URL}\n").unwrap();
+
+        let key = create_key(&path, "ci", Role::Viewer).unwrap();
+        assert!(create_key(&path, "ci", Role::Admin).is_err());
+        create_key(&path, "ops", Role::Admin).unwrap();
+
+        // Only the hash is written, and the rest of the file is kept as it was
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(!contents.contains(&key));
+        assert!(contents.contains("${NEXA_TEST_API_AUTH_URL}"));
+
+        std::env::set_var("NEXA_TEST_API_AUTH_URL", "http://localhost:1234");
+        let keys = Config::load(&path).unwrap().api.keys;
+        let ci = authenticate(&keys, &key).unwrap();
+        assert_eq!(ci.id, "ci");
+        assert_eq!(ci.role, Role::Viewer);
+        assert_eq!(keys[1].role, Role::Admin);
+        assert!(authenticate(&keys, "nexa_guess").is_none());
+
+        revoke_key(&path, "ci").unwrap();
+        assert!(matches!(revoke_key(&path, "ci"), Err(NexaError::NotFound(_))));
+        assert_eq!(Config::load(&path).unwrap().api.keys.len(), 1);
+    }
+}
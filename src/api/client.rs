@@ -0,0 +1,106 @@
+use std::time::Duration;
+use reqwest::{Client, RequestBuilder};
+use serde::de::DeserializeOwned;
+use crate::api::{DrainRequest, ErrorBody, SetLogLevelRequest};
+use crate::error::NexaError;
+use crate::logging::LogLevels;
+use crate::mcp::DrainStatus;
+
+/// HTTP client for the REST API of a running daemon
+#[derive(Debug, Clone)]
+pub struct ApiClient {
+    base_url: String,
+    http: Client,
+    api_key: Option<String>,
+}
+
+impl ApiClient {
+    pub fn new(base_url: impl Into<String>) -> Result<Self, NexaError> {
+        let http = Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()?;
+        Ok(Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            http,
+            api_key: None,
+        })
+    }
+
+    /// Present `key` with every request
+    pub fn with_api_key(mut self, key: impl Into<String>) -> Self {
+        self.api_key = Some(key.into());
+        self
+    }
+
+    /// Base URL of the daemon API
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Get the daemon's current log levels
+    pub async fn log_levels(&self) -> Result<LogLevels, NexaError> {
+        self.send(self.http.get(self.url("/api/log-level"))).await
+    }
+
+    /// Set the log level of a target in the daemon
+    pub async fn set_log_level(&self, target: &str, level: &str) -> Result<LogLevels, NexaError> {
+        let request = SetLogLevelRequest {
+            target: target.to_string(),
+            level: level.to_string(),
+        };
+        self.send(self.http.put(self.url("/api/log-level")).json(&request)).await
+    }
+
+    /// Remove a target's log level override in the daemon
+    pub async fn reset_log_level(&self, target: &str) -> Result<LogLevels, NexaError> {
+        self.send(self.http.delete(self.url(&format!("/api/log-level/{}", target)))).await
+    }
+
+    /// Start draining the daemon for maintenance
+    pub async fn drain(&self, timeout_secs: u64) -> Result<DrainStatus, NexaError> {
+        self.send(self.http.post(self.url("/api/drain")).json(&DrainRequest { timeout_secs })).await
+    }
+
+    /// Progress of the daemon's drain
+    pub async fn drain_status(&self) -> Result<DrainStatus, NexaError> {
+        self.send(self.http.get(self.url("/api/drain"))).await
+    }
+
+    /// Take the daemon out of maintenance, returning the drain that was ended
+    pub async fn resume(&self) -> Result<Option<DrainStatus>, NexaError> {
+        self.send(self.http.delete(self.url("/api/drain"))).await
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    async fn send<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T, NexaError> {
+        let request = match &self.api_key {
+            Some(key) => request.bearer_auth(key),
+            None => request,
+        };
+        let response = request.send().await.map_err(|e| {
+            if e.is_connect() || e.is_timeout() {
+                NexaError::unavailable(format!("Daemon API unreachable at {}: {}", self.base_url, e))
+            } else {
+                NexaError::from(e)
+            }
+        })?;
+
+        let status = response.status();
+        if status.is_success() {
+            return response.json().await
+                .map_err(|e| NexaError::invalid_response(format!("Failed to parse API response: {}", e)));
+        }
+
+        let text = response.text().await.unwrap_or_default();
+        match serde_json::from_str::<serde_json::Value>(&text)
+            .ok()
+            .and_then(|v| serde_json::from_value::<ErrorBody>(v.get("error")?.clone()).ok())
+        {
+            Some(body) => Err(NexaError::from_code(&body.code, body.message)),
+            None => Err(NexaError::invalid_response(format!("API returned {}: {}", status, text))),
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! Live Events
+//!
+//! WebSocket stream of the daemon's event bus, so dashboards need not poll:
+//! - `GET /api/events` upgrades to a WebSocket sending each event as a JSON text frame
+//! - Events are typed by `kind`: server state, agent status, task progress, alerts and the lifecycle events
+//! - `?kinds=agent_status_changed,alert` limits the stream to those kinds
+//! - Clients falling behind skip the events they missed rather than slowing the bus
+//! - Authenticated and authorized like any other `GET` route
+
+use axum::body::Body;
+use axum::extract::{Query, Request, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::Response;
+use futures::{SinkExt, StreamExt};
+use hyper_util::rt::TokioIo;
+use serde::Deserialize;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::broadcast;
+use tokio_tungstenite::WebSocketStream;
+use tungstenite::handshake::derive_accept_key;
+use tungstenite::protocol::{Message, Role};
+use crate::api::ApiError;
+use crate::error::NexaError;
+use crate::events::{Event, EventKind};
+use crate::mcp::ServerControl;
+use tracing::{debug, warn};
+
+/// Query of `GET /api/events`
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct EventsQuery {
+    /// Comma-separated kinds to stream; every kind when missing
+    pub kinds: Option<String>,
+}
+
+impl EventsQuery {
+    fn kinds(&self) -> Result<Vec<EventKind>, NexaError> {
+        self.kinds.iter()
+            .flat_map(|kinds| kinds.split(','))
+            .map(str::trim)
+            .filter(|kind| !kind.is_empty())
+            .map(|kind| {
+                serde_json::from_value(serde_json::Value::String(kind.to_string()))
+                    .map_err(|_| NexaError::invalid_input(format!("Unknown event kind '{}'", kind)))
+            })
+            .collect()
+    }
+}
+
+/// Upgrade to a WebSocket streaming the events published from now on
+pub(crate) async fn stream_events(
+    State(server): State<ServerControl>,
+    Query(query): Query<EventsQuery>,
+    mut request: Request,
+) -> Result<Response, ApiError> {
+    let kinds = query.kinds()?;
+    let accept = accept_key(request.headers())?;
+    let upgrade = hyper1::upgrade::on(&mut request);
+    // Subscribe before answering so no event published after the handshake is missed
+    let events = server.events().subscribe();
+    tokio::spawn(async move {
+        match upgrade.await {
+            Ok(upgraded) => {
+                let socket = WebSocketStream::from_raw_socket(TokioIo::new(upgraded), Role::Server, None).await;
+                forward(socket, events, kinds).await;
+            }
+            Err(e) => debug!("Event stream upgrade failed: {}", e),
+        }
+    });
+
+    Response::builder()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .header(header::CONNECTION, "upgrade")
+        .header(header::UPGRADE, "websocket")
+        .header(header::SEC_WEBSOCKET_ACCEPT, accept)
+        .body(Body::empty())
+        .map_err(|e| ApiError(NexaError::system(format!("Failed to build upgrade response: {}", e))))
+}
+
+/// `Sec-WebSocket-Accept` answering a WebSocket handshake, or why the request is not one
+fn accept_key(headers: &HeaderMap) -> Result<String, NexaError> {
+    let header = |name| headers.get(name).and_then(|value| value.to_str().ok()).unwrap_or_default();
+    let upgrading = header(header::CONNECTION).split(',').any(|token| token.trim().eq_ignore_ascii_case("upgrade"))
+        && header(header::UPGRADE).eq_ignore_ascii_case("websocket");
+    if !upgrading {
+        return Err(NexaError::invalid_input("/api/events is a WebSocket; send an upgrade request"));
+    }
+    if header(header::SEC_WEBSOCKET_VERSION) != "13" {
+        return Err(NexaError::invalid_input("Only WebSocket version 13 is supported"));
+    }
+    let key = headers.get(header::SEC_WEBSOCKET_KEY)
+        .ok_or_else(|| NexaError::invalid_input("Missing Sec-WebSocket-Key"))?;
+    Ok(derive_accept_key(key.as_bytes()))
+}
+
+/// Send the events of the wanted kinds until the client goes away
+async fn forward<S>(mut socket: WebSocketStream<S>, mut events: broadcast::Receiver<Event>, kinds: Vec<EventKind>)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    loop {
+        tokio::select! {
+            event = events.recv() => {
+                let event = match event {
+                    Ok(event) => event,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("Event stream client skipped {} event(s)", skipped);
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                if !kinds.is_empty() && !kinds.contains(&event.kind) {
+                    continue;
+                }
+                let Ok(text) = serde_json::to_string(&event) else { continue };
+                if let Err(e) = socket.send(Message::Text(text)).await {
+                    debug!("Event stream client went away: {}", e);
+                    break;
+                }
+            }
+            // Pings are answered as they are read; anything else from the client is ignored
+            message = socket.next() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+    let _ = socket.close(None).await;
+}
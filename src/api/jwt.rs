@@ -0,0 +1,420 @@
+//! JWT Authentication
+//!
+//! Bearer tokens issued by an OpenID Connect provider, for teams signing in through SSO:
+//! - Signing keys come from the issuer's JWKS, found through its discovery document
+//! - Keys are fetched again hourly, and when a token names an unknown key
+//! - Tokens must be signed with RS256/384/512 or ES256/384, current, from the issuer
+//!   and for the audience
+//! - The roles in a configurable claim map to API roles
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use openssl::bn::BigNum;
+use openssl::ec::{EcGroup, EcKey};
+use openssl::ecdsa::EcdsaSig;
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::pkey::{PKey, Public};
+use openssl::rsa::Rsa;
+use openssl::sign::Verifier;
+use parking_lot::RwLock;
+use serde::Deserialize;
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use crate::api::rbac::Role;
+use crate::config::JwtConfig;
+use crate::error::NexaError;
+use tracing::{debug, warn};
+
+/// How long fetched signing keys are used before fetching them again
+const JWKS_MAX_AGE: Duration = Duration::from_secs(3600);
+
+/// Least time between fetches prompted by tokens naming unknown keys
+const JWKS_MIN_REFRESH: Duration = Duration::from_secs(60);
+
+/// Caller identified by a valid token
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenIdentity {
+    /// `sub` claim
+    pub subject: String,
+    /// Roles listed in the roles claim
+    pub roles: Vec<String>,
+    /// Highest API role the listed roles map to
+    pub role: Option<Role>,
+}
+
+/// Signature algorithms accepted in tokens
+#[derive(Debug, Clone, Copy)]
+enum Algorithm {
+    Rs256,
+    Rs384,
+    Rs512,
+    Es256,
+    Es384,
+}
+
+impl Algorithm {
+    fn parse(alg: &str) -> Result<Self, NexaError> {
+        match alg {
+            "RS256" => Ok(Self::Rs256),
+            "RS384" => Ok(Self::Rs384),
+            "RS512" => Ok(Self::Rs512),
+            "ES256" => Ok(Self::Es256),
+            "ES384" => Ok(Self::Es384),
+            _ => Err(NexaError::unauthorized(format!("Unsupported token algorithm '{}'", alg))),
+        }
+    }
+
+    fn digest(self) -> MessageDigest {
+        match self {
+            Self::Rs256 | Self::Es256 => MessageDigest::sha256(),
+            Self::Rs384 | Self::Es384 => MessageDigest::sha384(),
+            Self::Rs512 => MessageDigest::sha512(),
+        }
+    }
+
+    /// Size of each of the two halves of an ECDSA signature
+    fn ec_half(self) -> Option<usize> {
+        match self {
+            Self::Es256 => Some(32),
+            Self::Es384 => Some(48),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Header {
+    alg: String,
+    #[serde(default)]
+    kid: Option<String>,
+}
+
+/// One key of a JWKS document
+#[derive(Debug, Deserialize)]
+struct Jwk {
+    kty: String,
+    #[serde(default)]
+    kid: Option<String>,
+    #[serde(default, rename = "use")]
+    usage: Option<String>,
+    #[serde(default)]
+    n: Option<String>,
+    #[serde(default)]
+    e: Option<String>,
+    #[serde(default)]
+    crv: Option<String>,
+    #[serde(default)]
+    x: Option<String>,
+    #[serde(default)]
+    y: Option<String>,
+}
+
+impl Jwk {
+    fn public_key(&self) -> Result<PKey<Public>, Box<dyn std::error::Error>> {
+        let component = |value: &Option<String>, name: &str| -> Result<BigNum, Box<dyn std::error::Error>> {
+            let value = value.as_deref().ok_or_else(|| format!("missing '{}'", name))?;
+            Ok(BigNum::from_slice(&URL_SAFE_NO_PAD.decode(value)?)?)
+        };
+        match self.kty.as_str() {
+            "RSA" => Ok(PKey::from_rsa(Rsa::from_public_components(component(&self.n, "n")?, component(&self.e, "e")?)?)?),
+            "EC" => {
+                let curve = match self.crv.as_deref() {
+                    Some("P-256") => Nid::X9_62_PRIME256V1,
+                    Some("P-384") => Nid::SECP384R1,
+                    crv => return Err(format!("unsupported curve {:?}", crv).into()),
+                };
+                let group = EcGroup::from_curve_name(curve)?;
+                let (x, y) = (component(&self.x, "x")?, component(&self.y, "y")?);
+                let key = EcKey::from_public_key_affine_coordinates(&group, &x, &y)?;
+                Ok(PKey::from_ec_key(key)?)
+            }
+            kty => Err(format!("unsupported key type '{}'", kty).into()),
+        }
+    }
+}
+
+/// Signing keys fetched from a JWKS endpoint
+struct KeySet {
+    /// Issuer or JWKS endpoint configured when the keys were fetched
+    source: String,
+    keys: Vec<(Option<String>, PKey<Public>)>,
+    fetched_at: Instant,
+}
+
+impl KeySet {
+    /// Keys a token naming `kid` may be signed with
+    fn matching(&self, kid: Option<&str>) -> Vec<PKey<Public>> {
+        self.keys.iter()
+            .filter(|(id, _)| kid.is_none() || id.as_deref() == kid)
+            .map(|(_, key)| key.clone())
+            .collect()
+    }
+}
+
+/// Validates bearer tokens against the signing keys of their issuer
+#[derive(Clone)]
+pub struct JwtValidator {
+    http: reqwest::Client,
+    keys: Arc<RwLock<Option<Arc<KeySet>>>>,
+}
+
+impl JwtValidator {
+    pub fn new() -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .unwrap_or_default();
+        Self { http, keys: Arc::new(RwLock::new(None)) }
+    }
+
+    /// Check a token's signature and claims, returning who it identifies
+    ///
+    /// Invalid tokens are unauthorized errors; an unreachable issuer is unavailable.
+    pub async fn validate(&self, config: &JwtConfig, token: &str) -> Result<TokenIdentity, NexaError> {
+        let mut parts = token.split('.');
+        let (Some(header), Some(payload), Some(signature), None) = (parts.next(), parts.next(), parts.next(), parts.next()) else {
+            return Err(NexaError::unauthorized("Malformed token"));
+        };
+        let signed = &token[..header.len() + 1 + payload.len()];
+        let header: Header = serde_json::from_slice(&decode(header)?)
+            .map_err(|e| NexaError::unauthorized(format!("Malformed token header: {}", e)))?;
+        let algorithm = Algorithm::parse(&header.alg)?;
+        let signature = decode(signature)?;
+
+        let keys = self.keys_for(config, header.kid.as_deref()).await?;
+        if !keys.iter().any(|key| verify(algorithm, key, signed.as_bytes(), &signature)) {
+            return Err(NexaError::unauthorized("Invalid token signature"));
+        }
+
+        let claims: Value = serde_json::from_slice(&decode(payload)?)
+            .map_err(|e| NexaError::unauthorized(format!("Malformed token claims: {}", e)))?;
+        check_claims(config, &claims, chrono::Utc::now().timestamp())?;
+
+        let subject = claims.get("sub").and_then(Value::as_str).unwrap_or_default().to_string();
+        let roles = roles(&claims, &config.roles_claim);
+        let role = roles.iter().filter_map(|role| config.roles.get(role)).copied().max();
+        Ok(TokenIdentity { subject, roles, role })
+    }
+
+    /// Keys of the issuer a token naming `kid` may be signed with, fetching them as needed
+    async fn keys_for(&self, config: &JwtConfig, kid: Option<&str>) -> Result<Vec<PKey<Public>>, NexaError> {
+        let cached = self.keys.read().clone()
+            .filter(|set| set.source == source(config));
+        if let Some(set) = &cached {
+            let keys = set.matching(kid);
+            let age = set.fetched_at.elapsed();
+            if age < JWKS_MAX_AGE && (!keys.is_empty() || age < JWKS_MIN_REFRESH) {
+                return Ok(keys);
+            }
+        }
+
+        let set = Arc::new(self.fetch(config).await?);
+        *self.keys.write() = Some(set.clone());
+        Ok(set.matching(kid))
+    }
+
+    async fn fetch(&self, config: &JwtConfig) -> Result<KeySet, NexaError> {
+        let url = match &config.jwks_url {
+            Some(url) => url.clone(),
+            None => {
+                let discovery = format!("{}/.well-known/openid-configuration", config.issuer.trim_end_matches('/'));
+                let document: Value = self.get(&discovery).await?;
+                document.get("jwks_uri").and_then(Value::as_str)
+                    .ok_or_else(|| NexaError::invalid_response(format!("{} has no jwks_uri", discovery)))?
+                    .to_string()
+            }
+        };
+
+        #[derive(Deserialize)]
+        struct Document {
+            keys: Vec<Jwk>,
+        }
+        let document: Document = self.get(&url).await?;
+        let mut keys = Vec::new();
+        for jwk in document.keys.iter().filter(|jwk| jwk.usage.as_deref().unwrap_or("sig") == "sig") {
+            match jwk.public_key() {
+                Ok(key) => keys.push((jwk.kid.clone(), key)),
+                Err(e) => warn!("Skipping signing key {:?} of {}: {}", jwk.kid, url, e),
+            }
+        }
+        debug!("Fetched {} signing keys from {}", keys.len(), url);
+        Ok(KeySet { source: source(config), keys, fetched_at: Instant::now() })
+    }
+
+    async fn get<T: serde::de::DeserializeOwned>(&self, url: &str) -> Result<T, NexaError> {
+        let response = self.http.get(url).send().await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| NexaError::unavailable(format!("Failed to fetch {}: {}", url, e)))?;
+        response.json().await
+            .map_err(|e| NexaError::invalid_response(format!("Invalid response from {}: {}", url, e)))
+    }
+}
+
+impl Default for JwtValidator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn source(config: &JwtConfig) -> String {
+    config.jwks_url.clone().unwrap_or_else(|| config.issuer.clone())
+}
+
+fn decode(part: &str) -> Result<Vec<u8>, NexaError> {
+    URL_SAFE_NO_PAD.decode(part)
+        .map_err(|e| NexaError::unauthorized(format!("Malformed token: {}", e)))
+}
+
+fn verify(algorithm: Algorithm, key: &PKey<Public>, signed: &[u8], signature: &[u8]) -> bool {
+    // ECDSA signatures are the two halves side by side in tokens, DER for OpenSSL
+    let signature = match algorithm.ec_half() {
+        Some(half) if signature.len() == 2 * half => {
+            let (r, s) = signature.split_at(half);
+            let der = BigNum::from_slice(r)
+                .and_then(|r| Ok((r, BigNum::from_slice(s)?)))
+                .and_then(|(r, s)| EcdsaSig::from_private_components(r, s))
+                .and_then(|sig| sig.to_der());
+            match der {
+                Ok(der) => der,
+                Err(_) => return false,
+            }
+        }
+        Some(_) => return false,
+        None => signature.to_vec(),
+    };
+    Verifier::new(algorithm.digest(), key)
+        .and_then(|mut verifier| {
+            verifier.update(signed)?;
+            verifier.verify(&signature)
+        })
+        .unwrap_or(false)
+}
+
+/// Check expiry, issuer and audience of a token at `now` (seconds since the epoch)
+fn check_claims(config: &JwtConfig, claims: &Value, now: i64) -> Result<(), NexaError> {
+    let leeway = config.leeway as i64;
+    let exp = claims.get("exp").and_then(Value::as_i64)
+        .ok_or_else(|| NexaError::unauthorized("Token has no expiry"))?;
+    if now > exp + leeway {
+        return Err(NexaError::unauthorized("Token expired"));
+    }
+    if claims.get("nbf").and_then(Value::as_i64).is_some_and(|nbf| now + leeway < nbf) {
+        return Err(NexaError::unauthorized("Token is not valid yet"));
+    }
+    if claims.get("iss").and_then(Value::as_str) != Some(config.issuer.as_str()) {
+        return Err(NexaError::unauthorized("Token is from another issuer"));
+    }
+    if let Some(audience) = &config.audience {
+        let matches = match claims.get("aud") {
+            Some(Value::String(aud)) => aud == audience,
+            Some(Value::Array(auds)) => auds.iter().any(|aud| aud.as_str() == Some(audience.as_str())),
+            _ => false,
+        };
+        if !matches {
+            return Err(NexaError::unauthorized("Token is for another audience"));
+        }
+    }
+    Ok(())
+}
+
+/// Roles listed in `claim`: a list, or a space separated string like `scope`
+fn roles(claims: &Value, claim: &str) -> Vec<String> {
+    let value = claim.split('.').try_fold(claims, |value, key| value.get(key));
+    match value {
+        Some(Value::Array(roles)) => roles.iter().filter_map(Value::as_str).map(String::from).collect(),
+        Some(Value::String(roles)) => roles.split_whitespace().map(String::from).collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openssl::pkey::Private;
+    use openssl::sign::Signer;
+    use serde_json::json;
+
+    fn encode(bytes: &[u8]) -> String {
+        URL_SAFE_NO_PAD.encode(bytes)
+    }
+
+    fn sign(alg: &str, kid: &str, key: &PKey<Private>, claims: &Value) -> String {
+        let header = encode(json!({ "alg": alg, "kid": kid, "typ": "JWT" }).to_string().as_bytes());
+        let signed = format!("{}.{}", header, encode(claims.to_string().as_bytes()));
+        let mut signer = Signer::new(MessageDigest::sha256(), key).unwrap();
+        signer.update(signed.as_bytes()).unwrap();
+        let mut signature = signer.sign_to_vec().unwrap();
+        if alg == "ES256" {
+            let sig = EcdsaSig::from_der(&signature).unwrap();
+            signature = [sig.r().to_vec_padded(32).unwrap(), sig.s().to_vec_padded(32).unwrap()].concat();
+        }
+        format!("{}.{}", signed, encode(&signature))
+    }
+
+    #[tokio::test]
+    async fn test_validate_tokens() {
+        let rsa = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let ec = EcKey::generate(&group).unwrap();
+        let (mut x, mut y) = (BigNum::new().unwrap(), BigNum::new().unwrap());
+        let mut ctx = openssl::bn::BigNumContext::new().unwrap();
+        ec.public_key().affine_coordinates(&group, &mut x, &mut y, &mut ctx).unwrap();
+        let ec = PKey::from_ec_key(ec).unwrap();
+
+        // An issuer serving its discovery document and signing keys
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let issuer = format!("http://{}", listener.local_addr().unwrap());
+        let discovery = json!({ "issuer": issuer, "jwks_uri": format!("{}/jwks", issuer) });
+        let jwks = json!({ "keys": [
+            { "kty": "RSA", "kid": "rsa-1", "use": "sig", "n": encode(&rsa.rsa().unwrap().n().to_vec()), "e": encode(&rsa.rsa().unwrap().e().to_vec()) },
+            { "kty": "EC", "kid": "ec-1", "crv": "P-256", "x": encode(&x.to_vec_padded(32).unwrap()), "y": encode(&y.to_vec_padded(32).unwrap()) },
+        ]});
+        let router = axum::Router::new()
+            .route("/.well-known/openid-configuration", axum::routing::get(move || async move { axum::Json(discovery) }))
+            .route("/jwks", axum::routing::get(move || async move { axum::Json(jwks) }));
+        let server = tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+        let config = JwtConfig {
+            issuer: issuer.clone(),
+            audience: Some("nexa".to_string()),
+            jwks_url: None,
+            roles_claim: "realm_access.roles".to_string(),
+            roles: [
+                ("viewer".to_string(), Role::Viewer),
+                ("operator".to_string(), Role::Operator),
+            ].into_iter().collect(),
+            leeway: 60,
+        };
+        let now = chrono::Utc::now().timestamp();
+        let claims = json!({
+            "iss": issuer, "aud": ["nexa", "account"], "sub": "alice", "exp": now + 300,
+            "realm_access": { "roles": ["operator", "viewer", "offline_access"] },
+        });
+        let validator = JwtValidator::new();
+
+        let identity = validator.validate(&config, &sign("RS256", "rsa-1", &rsa, &claims)).await.unwrap();
+        assert_eq!(identity.subject, "alice");
+        assert_eq!(identity.role, Some(Role::Operator));
+        let identity = validator.validate(&config, &sign("ES256", "ec-1", &ec, &claims)).await.unwrap();
+        assert_eq!(identity.roles.len(), 3);
+
+        // Signed by another key, or under another key's id
+        let other = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let forged = validator.validate(&config, &sign("RS256", "rsa-1", &other, &claims)).await;
+        assert!(matches!(forged, Err(NexaError::Unauthorized(_))));
+        assert!(validator.validate(&config, &sign("RS256", "rsa-2", &other, &claims)).await.is_err());
+
+        // Claims that do not hold
+        let mut expired = claims.clone();
+        expired["exp"] = json!(now - 120);
+        assert!(validator.validate(&config, &sign("RS256", "rsa-1", &rsa, &expired)).await.unwrap_err().to_string().contains("expired"));
+        let mut elsewhere = claims.clone();
+        elsewhere["aud"] = json!("billing");
+        assert!(validator.validate(&config, &sign("RS256", "rsa-1", &rsa, &elsewhere)).await.is_err());
+        assert!(validator.validate(&config, "not.a.token").await.is_err());
+
+        server.abort();
+    }
+}
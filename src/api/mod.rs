@@ -1,185 +1,842 @@
-use utoipa::OpenApi;
-use crate::agent::{Agent, AgentStatus, Task};
-use crate::monitoring::SystemMetrics;
-use std::collections::HashMap;
+//! REST API
+//!
+//! HTTP control surface of the running daemon:
+//! - API key and JWT authentication
+//! - Role-based authorization per route
+//! - Rate limiting per client address and per key
+//! - Liveness, readiness and startup probes, with the status of each component
+//! - Runtime log level control
+//! - Ollama model management
+//! - Completions from the daemon's LLM providers, optionally streamed as Server-Sent Events
+//! - Registered agents, paginated, filtered and sorted
+//! - Creating, listing, cancelling, reassigning and completing tasks
+//! - Draining the server for maintenance
+//! - Live events over a WebSocket
+//! - JSON error responses derived from `NexaError`
+//! - A trace per request, continuing the caller's `traceparent`
+//! - Client for talking to a daemon from the CLI
+//! - OpenAPI documentation (`api-docs` feature)
+
+pub mod auth;
+pub mod client;
+pub mod events;
+pub mod jwt;
+pub mod pagination;
+pub mod rate_limit;
+pub mod rbac;
+#[cfg(feature = "api-docs")]
+pub mod docs;
+
+pub use client::ApiClient;
+#[cfg(feature = "api-docs")]
+pub use docs::*;
+
+use std::net::SocketAddr;
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::sse::{Event as SseEvent, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpListener;
 use chrono::{DateTime, Utc};
+use crate::agent::{Agent, AgentStatus, Task, TaskStatus};
+use crate::error::NexaError;
+use crate::llm::{LLMClient, LLMSupervisor, OllamaClient};
+use crate::llm::ollama::{ModelDetails, RunningModel};
+use crate::logging::{self, LogLevels};
+use crate::mcp::{ComponentStatus, DrainStatus, ServerControl};
+use crate::mcp::trace::TraceContext;
+use tracing::{error, info, warn};
 
-/// Agent registration request
-#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
-pub struct RegisterAgentRequest {
-    /// Agent information
-    pub agent: Agent,
+/// Error body returned by every failing endpoint
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorBody {
+    pub code: String,
+    pub message: String,
 }
 
-/// Task assignment request
-#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
-pub struct TaskAssignmentRequest {
-    /// Task information
-    pub task: Task,
-    /// Target agent ID
-    pub agent_id: String,
-    /// Optional deadline
+/// Wrapper turning a `NexaError` into an HTTP response
+#[derive(Debug)]
+pub struct ApiError(pub NexaError);
+
+impl From<NexaError> for ApiError {
+    fn from(err: NexaError) -> Self {
+        Self(err)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = StatusCode::from_u16(self.0.http_status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let body = ErrorBody {
+            code: self.0.code().to_string(),
+            message: self.0.message(),
+        };
+        (status, Json(serde_json::json!({ "error": body }))).into_response()
+    }
+}
+
+type ApiResult<T> = Result<Json<T>, ApiError>;
+
+/// W3C header carrying the trace of a request
+pub const TRACEPARENT: &str = "traceparent";
+
+/// Request to change the log level of a target
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetLogLevelRequest {
+    /// Module path or tracing target, or `default` for the global level
+    pub target: String,
+    /// trace, debug, info, warn, error or off
+    pub level: String,
+}
+
+/// Request to drain the server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DrainRequest {
+    /// How long to wait for the tasks in flight, in seconds
+    #[serde(default = "default_drain_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_drain_timeout() -> u64 {
+    300
+}
+
+/// Request to pull a model into Ollama
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PullModelRequest {
+    pub model: String,
+}
+
+/// Request to complete a prompt with the daemon's LLM providers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatRequest {
+    pub prompt: String,
+}
+
+/// Completion of a `ChatRequest`, and the `done` event of a streamed one
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatResponse {
+    pub response: String,
+}
+
+/// Whether to stream a response as Server-Sent Events
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct StreamQuery {
+    #[serde(default)]
+    pub stream: bool,
+}
+
+/// Request to create a task, optionally assigning it at once
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateTaskRequest {
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub steps: Vec<String>,
+    /// Capabilities the task needs; the `label` tasks are filtered by
+    #[serde(default)]
+    pub requirements: Vec<String>,
+    #[serde(default)]
     pub deadline: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub estimated_duration: i64,
+    #[serde(default)]
+    pub priority: i32,
+    /// Agent to assign the task to
+    #[serde(default)]
+    pub agent_id: Option<String>,
 }
 
-/// Status update request
-#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
-pub struct StatusUpdateRequest {
-    /// Agent ID
+/// Request to assign a task to another agent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssignTaskRequest {
     pub agent_id: String,
-    /// Current status
-    pub status: AgentStatus,
-    /// Optional metrics
-    pub metrics: Option<HashMap<String, String>>,
-}
-
-/// Agent query request
-#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
-pub struct AgentQueryRequest {
-    /// Required capability
-    pub capability: String,
-    /// Additional requirements
-    pub requirements: Option<HashMap<String, String>>,
-}
-
-#[derive(OpenApi)]
-#[openapi(
-    paths(
-        ws_connect,
-        register_agent,
-        assign_task,
-        update_status,
-        query_agents,
-        get_metrics
-    ),
-    components(
-        schemas(
-            Agent,
-            AgentStatus,
-            Task,
-            SystemMetrics,
-            RegisterAgentRequest,
-            TaskAssignmentRequest,
-            StatusUpdateRequest,
-            AgentQueryRequest
-        )
-    ),
-    tags(
-        (name = "Agents", description = "Agent management operations"),
-        (name = "Tasks", description = "Task management operations"),
-        (name = "System", description = "System monitoring and control"),
-        (name = "Metrics", description = "Resource and performance metrics")
-    ),
-    modifiers(&SecurityAddon)
-)]
-pub struct ApiDoc;
-
-struct SecurityAddon;
-
-impl utoipa::Modify for SecurityAddon {
-    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
-        if let Some(components) = openapi.components.as_mut() {
-            components.add_security_scheme(
-                "bearer_auth",
-                utoipa::openapi::security::SecurityScheme::Http(
-                    utoipa::openapi::security::Http::new(
-                        utoipa::openapi::security::HttpAuthScheme::Bearer
-                    )
-                ),
-            );
+}
+
+/// Request to move a task on, such as reporting it completed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskStatusRequest {
+    /// in_progress, completed or failed
+    pub status: String,
+}
+
+/// A task as assigned, and whether its agent was connected to receive it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskAssignment {
+    pub task: Task,
+    pub delivered: bool,
+}
+
+/// REST API server bound to a running `ServerControl`
+#[derive(Clone)]
+pub struct ApiServer {
+    server: ServerControl,
+    auth: auth::Authenticator,
+    limiter: rate_limit::RateLimiter,
+    auto_port: bool,
+}
+
+impl ApiServer {
+    pub fn new(server: ServerControl) -> Self {
+        let auth = auth::Authenticator::new(server.config_service().clone());
+        let limiter = rate_limit::RateLimiter::new(server.config_service().clone());
+        Self { server, auth, limiter, auto_port: false }
+    }
+
+    /// Fall back to an ephemeral port when the requested one is busy
+    pub fn with_auto_port(mut self, enabled: bool) -> Self {
+        self.auto_port = enabled;
+        self
+    }
+
+    /// Build the API router
+    pub fn router(&self) -> Router {
+        Router::new()
+            .route("/healthz", get(healthz))
+            .route("/readyz", get(readyz))
+            .route("/startupz", get(startupz))
+            .route("/api/log-level", get(get_log_levels).put(set_log_level))
+            .route("/api/log-level/:target", axum::routing::delete(reset_log_level))
+            .route("/api/agents", get(list_agents))
+            .route("/api/tasks", get(list_tasks).post(create_task))
+            .route("/api/tasks/:id", get(get_task))
+            .route("/api/tasks/:id/cancel", axum::routing::post(cancel_task))
+            .route("/api/tasks/:id/assign", axum::routing::post(reassign_task))
+            .route("/api/tasks/:id/status", axum::routing::put(set_task_status))
+            .route("/api/drain", get(drain_status).post(drain).delete(resume))
+            .route("/api/events", get(events::stream_events))
+            .route("/api/chat", axum::routing::post(chat))
+            .route("/api/ollama/pull", axum::routing::post(pull_ollama_model))
+            .route("/api/ollama/ps", get(running_ollama_models))
+            .route("/api/ollama/models/*model", get(show_ollama_model).delete(delete_ollama_model))
+            .route_layer(axum::middleware::from_fn_with_state(self.server.config_service().clone(), rbac::authorize))
+            .layer(axum::middleware::from_fn_with_state(self.limiter.clone(), rate_limit::limit_by_key))
+            .layer(axum::middleware::from_fn_with_state(self.auth.clone(), auth::require_auth))
+            .layer(axum::middleware::from_fn_with_state(self.limiter.clone(), rate_limit::limit_by_ip))
+            .layer(axum::middleware::from_fn(trace_request))
+            .with_state(self.server.clone())
+    }
+
+    /// Bind the API and serve it in the background
+    pub async fn start(&self, addr: &str) -> Result<(SocketAddr, tokio::task::JoinHandle<()>), NexaError> {
+        let listener = match TcpListener::bind(addr).await {
+            Ok(listener) => listener,
+            Err(e) if e.kind() == std::io::ErrorKind::AddrInUse && self.auto_port => {
+                let host = addr.rsplit_once(':').map(|(host, _)| host).unwrap_or(addr);
+                warn!("{} is in use, binding the API to an ephemeral port", addr);
+                TcpListener::bind(format!("{}:0", host)).await
+                    .map_err(|e| NexaError::server(format!("Failed to bind API to {}:0: {}", host, e)))?
+            }
+            Err(e) => return Err(NexaError::server(format!("Failed to bind API to {}: {}", addr, e))),
+        };
+        let local_addr = listener.local_addr()?;
+        // Client addresses are needed to rate limit by IP
+        let router = self.router().into_make_service_with_connect_info::<SocketAddr>();
+
+        let handle = tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, router).await {
+                error!("API server error: {}", e);
+            }
+        });
+
+        info!("API server listening on {}", local_addr);
+        Ok((local_addr, handle))
+    }
+}
+
+/// Handle a request within its trace, answering with the `traceparent` of its span
+///
+/// The trace continues the one of the request's `traceparent` header, or is new.
+async fn trace_request(request: Request, next: Next) -> Response {
+    let trace = request.headers().get(TRACEPARENT)
+        .and_then(|header| header.to_str().ok())
+        .and_then(TraceContext::from_traceparent)
+        .map_or_else(TraceContext::new, |parent| parent.child());
+    let traceparent = HeaderValue::from_str(&trace.traceparent());
+    let mut response = trace.scope(next.run(request)).await;
+    if let Ok(traceparent) = traceparent {
+        response.headers_mut().insert(TRACEPARENT, traceparent);
+    }
+    response
+}
+
+/// Readiness probe body
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Readiness {
+    pub ready: bool,
+    pub leader: bool,
+    /// False while the node is outside cluster quorum and read-only
+    #[serde(default = "default_quorum")]
+    pub quorum: bool,
+    /// What readiness depends on; ready when all of them are
+    #[serde(default)]
+    pub components: Vec<ComponentStatus>,
+}
+
+/// Startup probe body
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Startup {
+    pub started: bool,
+    #[serde(default)]
+    pub components: Vec<ComponentStatus>,
+}
+
+fn default_quorum() -> bool {
+    true
+}
+
+/// Liveness: the process is up and serving requests
+async fn healthz() -> Json<serde_json::Value> {
+    Json(serde_json::json!({ "status": "ok" }))
+}
+
+/// Readiness: the MCP server, message processor and storage are up and the server is not shutting down
+async fn readyz(State(server): State<ServerControl>) -> (StatusCode, Json<Readiness>) {
+    let components = server.components().await;
+    let readiness = Readiness {
+        ready: components.iter().all(|component| component.ready),
+        leader: server.is_leader(),
+        quorum: server.has_quorum(),
+        components,
+    };
+    let status = if readiness.ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(readiness))
+}
+
+/// Startup: the server has finished starting, so liveness and readiness apply
+async fn startupz(State(server): State<ServerControl>) -> (StatusCode, Json<Startup>) {
+    let startup = Startup {
+        started: server.is_started(),
+        components: server.components().await,
+    };
+    let status = if startup.started { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(startup))
+}
+
+async fn get_log_levels() -> ApiResult<LogLevels> {
+    Ok(Json(logging::current_levels()?))
+}
+
+async fn set_log_level(Json(request): Json<SetLogLevelRequest>) -> ApiResult<LogLevels> {
+    Ok(Json(logging::set_level(&request.target, &request.level)?))
+}
+
+async fn reset_log_level(Path(target): Path<String>) -> ApiResult<LogLevels> {
+    Ok(Json(logging::reset_level(&target)?))
+}
+
+/// Fields agents may be sorted by
+const AGENT_SORT_FIELDS: &[&str] = &["id", "name", "status", "last_heartbeat"];
+
+/// List registered agents, a page at a time
+///
+/// `status` is one of idle, busy, offline or error, and `label` a capability agents must have.
+async fn list_agents(
+    State(server): State<ServerControl>,
+    Query(query): Query<pagination::ListQuery>,
+) -> ApiResult<pagination::Page<Agent>> {
+    let status = query.status.as_deref()
+        .map(|wanted| parse_status(
+            wanted,
+            &[AgentStatus::Idle, AgentStatus::Busy, AgentStatus::Offline, AgentStatus::Error],
+            "idle, busy, offline or error",
+        ))
+        .transpose()?;
+    let agents: Vec<Agent> = server.registry.list_agents().await.into_iter()
+        .filter(|agent| status.is_none_or(|status| agent.status == status))
+        .filter(|agent| query.label.as_ref().is_none_or(|label| agent.capabilities.contains(label)))
+        .collect();
+    Ok(Json(query.paginate(agents, AGENT_SORT_FIELDS, "name")?))
+}
+
+/// The status among `statuses` named `wanted`, ignoring case and underscores
+fn parse_status<T: std::fmt::Debug + Copy>(wanted: &str, statuses: &[T], expected: &str) -> Result<T, NexaError> {
+    let name = wanted.replace('_', "");
+    statuses.iter().copied()
+        .find(|status| format!("{:?}", status).eq_ignore_ascii_case(&name))
+        .ok_or_else(|| NexaError::invalid_input(format!("Unknown status '{}'; expected {}", wanted, expected)))
+}
+
+/// Fields tasks may be sorted by
+const TASK_SORT_FIELDS: &[&str] = &["id", "title", "status", "priority", "created_at", "deadline"];
+
+const TASK_STATUSES: &[TaskStatus] = &[
+    TaskStatus::Pending,
+    TaskStatus::InProgress,
+    TaskStatus::Completed,
+    TaskStatus::Failed,
+    TaskStatus::Cancelled,
+    TaskStatus::Interrupted,
+];
+
+/// List tasks, a page at a time, oldest first unless sorted otherwise
+///
+/// `label` is a requirement tasks must have.
+async fn list_tasks(
+    State(server): State<ServerControl>,
+    Query(query): Query<pagination::ListQuery>,
+) -> ApiResult<pagination::Page<Task>> {
+    let status = query.status.as_deref()
+        .map(|wanted| parse_status(wanted, TASK_STATUSES, "pending, in_progress, completed, failed, cancelled or interrupted"))
+        .transpose()?;
+    let tasks: Vec<Task> = server.registry.list_tasks().await?.into_iter()
+        .filter(|task| status.is_none_or(|status| task.status == status))
+        .filter(|task| query.label.as_ref().is_none_or(|label| task.requirements.contains(label)))
+        .collect();
+    Ok(Json(query.paginate(tasks, TASK_SORT_FIELDS, "created_at")?))
+}
+
+/// Create a task, assigning it first when the request names an agent
+async fn create_task(
+    State(server): State<ServerControl>,
+    Json(request): Json<CreateTaskRequest>,
+) -> Result<(StatusCode, Json<Task>), ApiError> {
+    if request.title.trim().is_empty() {
+        return Err(NexaError::invalid_input("A task needs a title").into());
+    }
+    if let Some(agent_id) = &request.agent_id {
+        server.registry.get_agent(agent_id).await?;
+    }
+    let task = Task::new(
+        request.title,
+        request.description,
+        request.steps,
+        request.requirements,
+        request.deadline,
+        request.estimated_duration,
+        request.priority,
+    );
+    server.registry.add_task(task.clone()).await?;
+    if let Some(agent_id) = &request.agent_id {
+        server.assign_task(&task.id, agent_id).await?;
+    }
+    Ok((StatusCode::CREATED, Json(server.registry.get_task(&task.id).await?)))
+}
+
+async fn get_task(State(server): State<ServerControl>, Path(id): Path<String>) -> ApiResult<Task> {
+    Ok(Json(server.registry.get_task(&id).await?))
+}
+
+/// Cancel an unfinished task, freeing its agent
+async fn cancel_task(State(server): State<ServerControl>, Path(id): Path<String>) -> ApiResult<Task> {
+    Ok(Json(server.registry.set_task_status(&id, TaskStatus::Cancelled).await?))
+}
+
+/// Assign an unfinished task to another agent
+async fn reassign_task(
+    State(server): State<ServerControl>,
+    Path(id): Path<String>,
+    Json(request): Json<AssignTaskRequest>,
+) -> ApiResult<TaskAssignment> {
+    let delivered = server.reassign_task(&id, &request.agent_id).await?;
+    Ok(Json(TaskAssignment { task: server.registry.get_task(&id).await?, delivered }))
+}
+
+/// Report progress on a task: started, completed or failed
+async fn set_task_status(
+    State(server): State<ServerControl>,
+    Path(id): Path<String>,
+    Json(request): Json<TaskStatusRequest>,
+) -> ApiResult<Task> {
+    let statuses = [TaskStatus::InProgress, TaskStatus::Completed, TaskStatus::Failed];
+    let status = parse_status(&request.status, &statuses, "in_progress, completed or failed")?;
+    Ok(Json(server.registry.set_task_status(&id, status).await?))
+}
+
+/// Start draining, answering with the progress so far
+async fn drain(
+    State(server): State<ServerControl>,
+    Json(request): Json<DrainRequest>,
+) -> Result<(StatusCode, Json<DrainStatus>), ApiError> {
+    let status = server.drain(std::time::Duration::from_secs(request.timeout_secs)).await?;
+    Ok((StatusCode::ACCEPTED, Json(status)))
+}
+
+async fn drain_status(State(server): State<ServerControl>) -> ApiResult<DrainStatus> {
+    let status = server.drain_status().await
+        .ok_or_else(|| NexaError::not_found("The server is not draining"))?;
+    Ok(Json(status))
+}
+
+/// Leave maintenance, answering with the drain that was ended, if any
+async fn resume(State(server): State<ServerControl>) -> ApiResult<Option<DrainStatus>> {
+    Ok(Json(server.resume().await?))
+}
+
+/// The running LLM supervisor, or the configured provider alone before the server starts one
+async fn llm(server: &ServerControl) -> Result<LLMSupervisor, NexaError> {
+    match server.llm_supervisor().await {
+        Some(supervisor) => Ok(supervisor),
+        None => Ok(LLMSupervisor::new(vec![LLMClient::new(server.config_service().current().llm)?])),
+    }
+}
+
+/// Complete a prompt, or with `?stream=true` send the text as it is generated
+///
+/// Streams are Server-Sent Events: a `token` event per chunk of text, then a `done`
+/// event with the whole completion, or an `error` event if the providers fail.
+async fn chat(
+    State(server): State<ServerControl>,
+    Query(query): Query<StreamQuery>,
+    Json(request): Json<ChatRequest>,
+) -> Result<Response, ApiError> {
+    use futures::StreamExt;
+
+    let llm = llm(&server).await?;
+    if !query.stream {
+        let response = llm.complete(&request.prompt).await?;
+        return Ok(Json(ChatResponse { response }).into_response());
+    }
+    let chunks = llm.complete_stream(&request.prompt);
+    let events = futures::stream::unfold((chunks, Some(String::new())), |(mut chunks, completion)| async move {
+        let mut completion = completion?;
+        let (event, completion) = match chunks.next().await {
+            Some(Ok(text)) => {
+                completion.push_str(&text);
+                (SseEvent::default().event("token").json_data(serde_json::json!({ "text": text })), Some(completion))
+            }
+            Some(Err(e)) => {
+                let body = ErrorBody { code: e.code().to_string(), message: e.message() };
+                (SseEvent::default().event("error").json_data(serde_json::json!({ "error": body })), None)
+            }
+            None => (SseEvent::default().event("done").json_data(ChatResponse { response: completion }), None),
+        };
+        Some((event, (chunks, completion)))
+    });
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()).into_response())
+}
+
+fn ollama(server: &ServerControl) -> Result<OllamaClient, NexaError> {
+    OllamaClient::new(&server.config_service().current().llm)
+}
+
+/// Pull a model, streaming progress as NDJSON; a failure ends the stream with an `error` line
+async fn pull_ollama_model(
+    State(server): State<ServerControl>,
+    Json(request): Json<PullModelRequest>,
+) -> Result<Response, ApiError> {
+    use futures::StreamExt;
+
+    let progress = ollama(&server)?.pull(&request.model).await?;
+    let lines = progress.map(|event| {
+        let line = match event {
+            Ok(progress) => serde_json::to_string(&progress)?,
+            Err(e) => serde_json::to_string(&serde_json::json!({
+                "error": ErrorBody { code: e.code().to_string(), message: e.message() }
+            }))?,
+        };
+        Ok::<_, serde_json::Error>(line + "\n")
+    });
+    Ok((
+        [(axum::http::header::CONTENT_TYPE, "application/x-ndjson")],
+        axum::body::Body::from_stream(lines),
+    ).into_response())
+}
+
+async fn running_ollama_models(State(server): State<ServerControl>) -> ApiResult<Vec<RunningModel>> {
+    Ok(Json(ollama(&server)?.running().await?))
+}
+
+async fn show_ollama_model(State(server): State<ServerControl>, Path(model): Path<String>) -> ApiResult<ModelDetails> {
+    Ok(Json(ollama(&server)?.show(&model).await?))
+}
+
+async fn delete_ollama_model(State(server): State<ServerControl>, Path(model): Path<String>) -> Result<StatusCode, ApiError> {
+    ollama(&server)?.delete(&model).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_error_response() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let server = ServerControl::new(temp_dir.path().join("nexa.pid"), temp_dir.path().join("nexa.sock"));
+        let (addr, handle) = ApiServer::new(server).start("127.0.0.1:0").await.unwrap();
+
+        // Logging is not initialized in the test process
+        let client = ApiClient::new(format!("http://{}", addr)).unwrap();
+        let err = client.log_levels().await.unwrap_err();
+        assert!(matches!(err, NexaError::System(_)));
+
+        let response = reqwest::Client::new()
+            .put(format!("http://{}/api/log-level", addr))
+            .body("not json")
+            .send()
+            .await
+            .unwrap();
+        assert!(response.status().is_client_error());
+
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_probes() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let server = ServerControl::new(temp_dir.path().join("nexa.pid"), temp_dir.path().join("nexa.sock"));
+        let (addr, handle) = ApiServer::new(server.clone()).start("127.0.0.1:0").await.unwrap();
+        let http = reqwest::Client::new();
+
+        let health = http.get(format!("http://{}/healthz", addr)).send().await.unwrap();
+        assert_eq!(health.status(), 200);
+
+        // The MCP server was never started, so the replica is not ready
+        let ready = http.get(format!("http://{}/readyz", addr)).send().await.unwrap();
+        assert_eq!(ready.status(), 503);
+        let body: Readiness = ready.json().await.unwrap();
+        assert!(!body.ready);
+        assert!(body.leader);
+        assert!(body.quorum);
+        let components: Vec<(&str, bool)> = body.components.iter().map(|c| (c.name.as_str(), c.ready)).collect();
+        assert_eq!(components, vec![("mcp_server", false), ("message_processor", false), ("storage", true)]);
+        assert_eq!(body.components[0].detail.as_deref(), Some("server state is stopped"));
+
+        // Nor has it finished starting
+        let startup = http.get(format!("http://{}/startupz", addr)).send().await.unwrap();
+        assert_eq!(startup.status(), 503);
+        assert!(!startup.json::<Startup>().await.unwrap().started);
+
+        // Requests continue the caller's trace, in a span of their own
+        let parent = TraceContext::new();
+        let traced = http.get(format!("http://{}/healthz", addr))
+            .header(TRACEPARENT, parent.traceparent())
+            .send().await.unwrap();
+        let trace = TraceContext::from_traceparent(traced.headers()[TRACEPARENT].to_str().unwrap()).unwrap();
+        assert_eq!(trace.trace_id, parent.trace_id);
+        assert_ne!(trace.span_id, parent.span_id);
+
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_api_key_auth() {
+        use crate::api::auth::hash_key;
+        use crate::api::rbac::Role;
+        use crate::config::{ApiKeyConfig, RouteRole};
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let server = ServerControl::new(temp_dir.path().join("nexa.pid"), temp_dir.path().join("nexa.sock"));
+        let mut config = server.config_service().current();
+        config.api.keys = vec![
+            ApiKeyConfig { id: "viewer".to_string(), hash: hash_key("nexa_viewer"), role: Role::Viewer },
+            ApiKeyConfig { id: "operator".to_string(), hash: hash_key("nexa_operator"), role: Role::Operator },
+            ApiKeyConfig { id: "ops".to_string(), hash: hash_key("nexa_ops"), role: Role::Admin },
+        ];
+        server.config_service().update(config);
+        let (addr, handle) = ApiServer::new(server.clone()).start("127.0.0.1:0").await.unwrap();
+        let http = reqwest::Client::new();
+
+        // Probes stay open
+        let health = http.get(format!("http://{}/healthz", addr)).send().await.unwrap();
+        assert_eq!(health.status(), 200);
+
+        let anonymous = ApiClient::new(format!("http://{}", addr)).unwrap();
+        assert!(matches!(anonymous.drain_status().await, Err(NexaError::Unauthorized(_))));
+        let wrong = anonymous.clone().with_api_key("nexa_guess");
+        assert!(matches!(wrong.drain_status().await, Err(NexaError::Unauthorized(_))));
+
+        // Viewers get past authentication, but may not change anything
+        let viewer = anonymous.clone().with_api_key("nexa_viewer");
+        assert!(matches!(viewer.drain_status().await, Err(NexaError::NotFound(_))));
+        assert!(matches!(viewer.resume().await, Err(NexaError::Forbidden(_))));
+
+        // Draining is for admins, here with a key in a header of its own
+        let operator = anonymous.clone().with_api_key("nexa_operator");
+        assert!(matches!(operator.resume().await, Err(NexaError::Forbidden(_))));
+        let ops = http.delete(format!("http://{}/api/drain", addr))
+            .header(auth::API_KEY_HEADER, "nexa_ops")
+            .send().await.unwrap();
+        assert!(![401, 403].contains(&ops.status().as_u16()));
+
+        // Unless the config lets operators drain too
+        let mut config = server.config_service().current();
+        config.server.route_roles = vec![RouteRole { path: "/api/drain".to_string(), method: None, role: Role::Operator }];
+        server.config_service().update(config);
+        assert!(!matches!(operator.resume().await, Err(NexaError::Forbidden(_))));
+
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_event_stream() {
+        use crate::events::EventKind;
+        use futures::StreamExt;
+        use tokio_tungstenite::tungstenite::Message;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let server = ServerControl::new(temp_dir.path().join("nexa.pid"), temp_dir.path().join("nexa.sock"));
+        let (addr, handle) = ApiServer::new(server.clone()).start("127.0.0.1:0").await.unwrap();
+
+        // Plain requests are refused, as are unknown kinds
+        let plain = reqwest::get(format!("http://{}/api/events", addr)).await.unwrap();
+        assert_eq!(plain.status(), 400);
+        assert!(tokio_tungstenite::connect_async(format!("ws://{}/api/events?kinds=nonsense", addr)).await.is_err());
+
+        let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{}/api/events?kinds=alert", addr)).await.unwrap();
+        server.events().publish(EventKind::ServerStarted, serde_json::json!({}));
+        let alert = server.events().publish(EventKind::Alert, serde_json::json!({ "message": "Disk filling up" }));
+
+        // Only the kinds asked for arrive
+        let Some(Ok(Message::Text(text))) = socket.next().await else { panic!("expected an event") };
+        let received: crate::events::Event = serde_json::from_str(&text).unwrap();
+        assert_eq!(received, alert);
+
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_chat_stream() {
+        let sse = crate::llm::test_utils::start_streaming_server(concat!(
+            "data: {\"choices\":[{\"delta\":{\"content\":\"Hel\"}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\"lo\"}}]}\n\n",
+            "data: [DONE]\n\n",
+        )).await;
+        let temp_dir = tempfile::tempdir().unwrap();
+        let server = ServerControl::new(temp_dir.path().join("nexa.pid"), temp_dir.path().join("nexa.sock"));
+        let mut config = server.config_service().current();
+        config.llm = crate::llm::LLMConfig::with_lmstudio_server(sse);
+        server.config_service().update(config);
+        let (addr, handle) = ApiServer::new(server).start("127.0.0.1:0").await.unwrap();
+
+        let response = reqwest::Client::new().post(format!("http://{}/api/chat?stream=true", addr))
+            .json(&ChatRequest { prompt: "hi".to_string() })
+            .send().await.unwrap();
+        assert_eq!(response.headers()["content-type"], "text/event-stream");
+        let body = response.text().await.unwrap();
+        let events: Vec<(&str, &str)> = body.split("\n\n")
+            .filter_map(|event| event.strip_prefix("event: ")?.split_once("\ndata: "))
+            .collect();
+        assert_eq!(events, vec![
+            ("token", r#"{"text":"Hel"}"#),
+            ("token", r#"{"text":"lo"}"#),
+            ("done", r#"{"response":"Hello"}"#),
+        ]);
+
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_list_agents() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let server = ServerControl::new(temp_dir.path().join("nexa.pid"), temp_dir.path().join("nexa.sock"));
+        for (name, capabilities) in [("charlie", vec!["code"]), ("alpha", vec!["code", "web"]), ("bravo", vec!["web"])] {
+            let agent = Agent::new(name.to_string(), capabilities.into_iter().map(String::from).collect());
+            server.registry.register(agent).await.unwrap();
+        }
+        let bravo = server.registry.list_agents().await.into_iter().find(|agent| agent.name == "bravo").unwrap();
+        server.registry.update_status(&bravo.id, AgentStatus::Busy).await.unwrap();
+        let (addr, handle) = ApiServer::new(server).start("127.0.0.1:0").await.unwrap();
+        let list = |query: &'static str| async move {
+            let response = reqwest::get(format!("http://{}/api/agents{}", addr, query)).await.unwrap();
+            let status = response.status();
+            (status, response.json::<serde_json::Value>().await.unwrap())
+        };
+        let names = |page: &serde_json::Value| page["items"].as_array().unwrap().iter()
+            .map(|agent| agent["name"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>();
+
+        let (_, page) = list("").await;
+        assert_eq!(names(&page), vec!["alpha", "bravo", "charlie"]);
+        assert_eq!(page["total"], 3);
+
+        let (_, page) = list("?sort=-name&limit=2&page=1").await;
+        assert_eq!(names(&page), vec!["charlie", "bravo"]);
+        assert_eq!(page["total"], 3);
+
+        let (_, page) = list("?label=code&status=idle").await;
+        assert_eq!(names(&page), vec!["alpha", "charlie"]);
+        let (_, page) = list("?status=BUSY").await;
+        assert_eq!(names(&page), vec!["bravo"]);
+
+        let (status, body) = list("?status=asleep").await;
+        assert_eq!(status, 400);
+        assert_eq!(body["error"]["code"], "invalid_input");
+
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_tasks() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let server = ServerControl::new(temp_dir.path().join("nexa.pid"), temp_dir.path().join("nexa.sock"));
+        let (first, second) = (Agent::new("first".to_string(), vec![]), Agent::new("second".to_string(), vec![]));
+        for agent in [&first, &second] {
+            server.registry.register(agent.clone()).await.unwrap();
         }
-        
-        openapi.info.title = "Nexa Utils API".to_string();
-        openapi.info.version = "1.0.0".to_string();
-        openapi.info.description = Some("Multi-agent Control Protocol (MCP) Implementation".to_string());
-    }
-}
-
-/// WebSocket connection endpoint
-#[utoipa::path(
-    get,
-    path = "/ws",
-    tag = "System",
-    responses(
-        (status = 101, description = "WebSocket handshake successful"),
-        (status = 400, description = "Invalid request"),
-        (status = 500, description = "Server error")
-    ),
-    security(("bearer_auth" = []))
-)]
-pub async fn ws_connect() {}
-
-/// Register a new agent
-#[utoipa::path(
-    post,
-    path = "/agents/register",
-    tag = "Agents",
-    request_body = RegisterAgentRequest,
-    responses(
-        (status = 200, description = "Agent registered successfully"),
-        (status = 400, description = "Invalid request"),
-        (status = 500, description = "Server error")
-    ),
-    security(("bearer_auth" = []))
-)]
-pub async fn register_agent() {}
-
-/// Assign a task to an agent
-#[utoipa::path(
-    post,
-    path = "/tasks/assign",
-    tag = "Tasks",
-    request_body = TaskAssignmentRequest,
-    responses(
-        (status = 200, description = "Task assigned successfully"),
-        (status = 400, description = "Invalid request"),
-        (status = 404, description = "Agent not found"),
-        (status = 500, description = "Server error")
-    ),
-    security(("bearer_auth" = []))
-)]
-pub async fn assign_task() {}
-
-/// Update agent status
-#[utoipa::path(
-    post,
-    path = "/agents/status",
-    tag = "Agents",
-    request_body = StatusUpdateRequest,
-    responses(
-        (status = 200, description = "Status updated successfully"),
-        (status = 400, description = "Invalid request"),
-        (status = 404, description = "Agent not found"),
-        (status = 500, description = "Server error")
-    ),
-    security(("bearer_auth" = []))
-)]
-pub async fn update_status() {}
-
-/// Query agents by capability
-#[utoipa::path(
-    post,
-    path = "/agents/query",
-    tag = "Agents",
-    request_body = AgentQueryRequest,
-    responses(
-        (status = 200, description = "Query successful", body = Vec<Agent>),
-        (status = 400, description = "Invalid request"),
-        (status = 500, description = "Server error")
-    ),
-    security(("bearer_auth" = []))
-)]
-pub async fn query_agents() {}
-
-/// Get system metrics
-#[utoipa::path(
-    get,
-    path = "/metrics",
-    tag = "Metrics",
-    responses(
-        (status = 200, description = "Metrics retrieved successfully", body = SystemMetrics),
-        (status = 500, description = "Server error")
-    ),
-    security(("bearer_auth" = []))
-)]
-pub async fn get_metrics() {} 
+        let (addr, handle) = ApiServer::new(server.clone()).start("127.0.0.1:0").await.unwrap();
+        let http = reqwest::Client::new();
+        let url = |path: &str| format!("http://{}/api/tasks{}", addr, path);
+
+        let created = http.post(url("")).json(&serde_json::json!({ "title": "Review", "requirements": ["code"] }))
+            .send().await.unwrap();
+        assert_eq!(created.status(), 201);
+        let review: Task = created.json().await.unwrap();
+        let assigned: Task = http.post(url("")).json(&serde_json::json!({ "title": "Write docs", "agent_id": first.id }))
+            .send().await.unwrap().json().await.unwrap();
+        assert_eq!(assigned.assigned_agent, Some(first.id.clone()));
+        let unknown = http.post(url("")).json(&serde_json::json!({ "title": "Lost", "agent_id": "nobody" })).send().await.unwrap();
+        assert_eq!(unknown.status(), 404);
+
+        let page: pagination::Page<Task> = http.get(url("?label=code&status=pending")).send().await.unwrap().json().await.unwrap();
+        assert_eq!(page.total, 1);
+        assert_eq!(page.items[0].id, review.id);
+        let fetched: Task = http.get(url(&format!("/{}", review.id))).send().await.unwrap().json().await.unwrap();
+        assert_eq!(fetched.title, "Review");
+
+        // Reassigned to an agent that is not connected, so not delivered yet
+        let moved: TaskAssignment = http.post(url(&format!("/{}/assign", assigned.id)))
+            .json(&AssignTaskRequest { agent_id: second.id.clone() })
+            .send().await.unwrap().json().await.unwrap();
+        assert_eq!(moved.task.assigned_agent, Some(second.id.clone()));
+        assert!(!moved.delivered);
+
+        // Reported complete, after which it can no longer be cancelled
+        let completed: Task = http.put(url(&format!("/{}/status", assigned.id)))
+            .json(&TaskStatusRequest { status: "completed".to_string() })
+            .send().await.unwrap().json().await.unwrap();
+        assert_eq!(completed.status, TaskStatus::Completed);
+        let late = http.post(url(&format!("/{}/cancel", assigned.id))).send().await.unwrap();
+        assert_eq!(late.status(), 400);
+
+        let cancelled: Task = http.post(url(&format!("/{}/cancel", review.id))).send().await.unwrap().json().await.unwrap();
+        assert_eq!(cancelled.status, TaskStatus::Cancelled);
+        let missing = http.get(url("/nothing")).send().await.unwrap();
+        assert_eq!(missing.status(), 404);
+
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let server = ServerControl::new(temp_dir.path().join("nexa.pid"), temp_dir.path().join("nexa.sock"));
+        let mut config = server.config_service().current();
+        config.api.rate_limit.per_ip = Some(2);
+        server.config_service().update(config);
+        let (addr, handle) = ApiServer::new(server).start("127.0.0.1:0").await.unwrap();
+        let http = reqwest::Client::new();
+
+        for _ in 0..2 {
+            let response = http.get(format!("http://{}/api/drain", addr)).send().await.unwrap();
+            assert_eq!(response.status(), 404);
+        }
+        let limited = http.get(format!("http://{}/api/drain", addr)).send().await.unwrap();
+        assert_eq!(limited.status(), 429);
+        assert_eq!(limited.headers()["retry-after"], "30");
+        let client = ApiClient::new(format!("http://{}", addr)).unwrap();
+        assert!(matches!(client.drain_status().await, Err(NexaError::RateLimited(_))));
+
+        // Probes are never limited
+        let health = http.get(format!("http://{}/healthz", addr)).send().await.unwrap();
+        assert_eq!(health.status(), 200);
+
+        handle.abort();
+    }
+}
@@ -0,0 +1,138 @@
+//! List Pagination
+//!
+//! Query parameters and response envelope shared by the list endpoints:
+//! - `page` counts from 1; `limit` defaults to 50 and is capped at 500
+//! - `sort` names a field, descending with a leading `-`
+//! - `status` and `label` filters, applied by each endpoint to its own items
+//! - `total` counts every item matching the filters, across all pages
+
+use std::cmp::Ordering;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::error::NexaError;
+
+/// Items per page when the query does not say
+pub const DEFAULT_LIMIT: usize = 50;
+
+/// Most items a single page may hold
+pub const MAX_LIMIT: usize = 500;
+
+/// Query of a list endpoint
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ListQuery {
+    pub page: Option<usize>,
+    pub limit: Option<usize>,
+    /// Field to sort by, descending with a leading `-`
+    pub sort: Option<String>,
+    /// Only items in this status
+    pub status: Option<String>,
+    /// Only items carrying this label
+    pub label: Option<String>,
+}
+
+/// One page of a list, with the count of every matching item
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Items matching the filters, on every page
+    pub total: usize,
+    pub page: usize,
+    pub limit: usize,
+}
+
+impl ListQuery {
+    /// Sort the filtered `items` as asked, or by `default_sort`, and take the requested page
+    ///
+    /// Only `sortable` fields may be sorted by; items tied on the field keep the order of their `id`.
+    pub fn paginate<T: Serialize>(&self, items: Vec<T>, sortable: &[&str], default_sort: &str) -> Result<Page<T>, NexaError> {
+        let page = self.page.unwrap_or(1);
+        if page == 0 {
+            return Err(NexaError::invalid_input("Pages count from 1"));
+        }
+        let limit = self.limit.unwrap_or(DEFAULT_LIMIT);
+        if limit == 0 || limit > MAX_LIMIT {
+            return Err(NexaError::invalid_input(format!("limit must be between 1 and {}", MAX_LIMIT)));
+        }
+        let sort = self.sort.as_deref().unwrap_or(default_sort);
+        let (field, descending) = match sort.strip_prefix('-') {
+            Some(field) => (field, true),
+            None => (sort, false),
+        };
+        if !sortable.contains(&field) {
+            return Err(NexaError::invalid_input(format!(
+                "Cannot sort by '{}'; expected one of {}", field, sortable.join(", ")
+            )));
+        }
+
+        let mut keyed: Vec<(Value, Value, T)> = items.into_iter()
+            .map(|item| {
+                let value = serde_json::to_value(&item).unwrap_or_default();
+                (value["id"].clone(), value[field].clone(), item)
+            })
+            .collect();
+        keyed.sort_by(|a, b| compare(&a.0, &b.0));
+        keyed.sort_by(|a, b| {
+            let order = compare(&a.1, &b.1);
+            if descending { order.reverse() } else { order }
+        });
+
+        let total = keyed.len();
+        let items = keyed.into_iter()
+            .skip((page - 1).saturating_mul(limit))
+            .take(limit)
+            .map(|(_, _, item)| item)
+            .collect();
+        Ok(Page { items, total, page, limit })
+    }
+}
+
+/// Order of two JSON values of a field: numbers by value, anything else by its text, missing ones first
+fn compare(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => {
+            a.as_f64().partial_cmp(&b.as_f64()).unwrap_or(Ordering::Equal)
+        }
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        (Value::Null, Value::Null) => Ordering::Equal,
+        (Value::Null, _) => Ordering::Less,
+        (_, Value::Null) => Ordering::Greater,
+        (a, b) => a.to_string().cmp(&b.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_paginate() {
+        let items: Vec<Value> = (1..=7)
+            .map(|n| json!({ "id": format!("agent-{}", n), "name": format!("worker {}", n % 3), "priority": n }))
+            .collect();
+        let query = |page, limit, sort: &str| ListQuery {
+            page: Some(page),
+            limit: Some(limit),
+            sort: Some(sort.to_string()),
+            ..Default::default()
+        };
+        let ids = |page: Page<Value>| page.items.iter().map(|item| item["id"].as_str().unwrap().to_string()).collect::<Vec<_>>();
+
+        let page = query(2, 3, "priority").paginate(items.clone(), &["name", "priority"], "name").unwrap();
+        assert_eq!((page.total, page.page, page.limit), (7, 2, 3));
+        assert_eq!(ids(page), vec!["agent-4", "agent-5", "agent-6"]);
+
+        // Descending, and ties in the order of their ids
+        let page = query(1, 3, "-name").paginate(items.clone(), &["name", "priority"], "name").unwrap();
+        assert_eq!(ids(page), vec!["agent-2", "agent-5", "agent-1"]);
+
+        // Past the last page is empty but still counts everything
+        let page = query(4, 3, "priority").paginate(items.clone(), &["name", "priority"], "name").unwrap();
+        assert!(page.items.is_empty());
+        assert_eq!(page.total, 7);
+
+        assert!(query(1, 3, "secret").paginate(items.clone(), &["name"], "name").is_err());
+        assert!(query(0, 3, "name").paginate(items.clone(), &["name"], "name").is_err());
+        assert!(query(1, MAX_LIMIT + 1, "name").paginate(items, &["name"], "name").is_err());
+    }
+}
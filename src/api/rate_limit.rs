@@ -0,0 +1,150 @@
+//! API Rate Limiting
+//!
+//! Token buckets keeping any one caller from flooding the REST API:
+//! - One bucket per client IP address, checked before authentication
+//! - One bucket per API key or token subject, checked after it
+//! - Each holds a minute's allowance as a burst and refills continuously
+//! - Requests finding their bucket empty get `429` with `Retry-After`
+//! - The probes are never limited
+
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::{header, HeaderValue};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use crate::api::auth::{Principal, OPEN_PATHS};
+use crate::api::ApiError;
+use crate::config::ConfigService;
+use crate::error::NexaError;
+use tracing::debug;
+
+/// Buckets kept before idle ones are dropped
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// A bucket holding up to a minute's allowance, refilled continuously
+#[derive(Debug)]
+struct Bucket {
+    available: f64,
+    refilled: Instant,
+}
+
+impl Bucket {
+    fn full(per_minute: u32, now: Instant) -> Self {
+        Self { available: per_minute.max(1) as f64, refilled: now }
+    }
+
+    /// Take one request from the bucket, or how long until one is available
+    fn try_take(&mut self, per_minute: u32, now: Instant) -> Result<(), Duration> {
+        let capacity = per_minute.max(1) as f64;
+        let per_second = capacity / 60.0;
+        let elapsed = now.saturating_duration_since(self.refilled).as_secs_f64();
+        self.available = (self.available + elapsed * per_second).min(capacity);
+        self.refilled = now;
+        if self.available >= 1.0 {
+            self.available -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.available) / per_second))
+        }
+    }
+
+    /// Whether the bucket would be full by `now`, so dropping it changes nothing
+    fn is_idle(&self, per_minute: u32, now: Instant) -> bool {
+        now.saturating_duration_since(self.refilled) >= Duration::from_secs(60)
+            || self.available >= per_minute.max(1) as f64
+    }
+}
+
+/// Buckets of the callers of one kind, by caller
+#[derive(Debug, Default)]
+struct Buckets(Mutex<HashMap<String, Bucket>>);
+
+impl Buckets {
+    fn try_take(&self, caller: &str, per_minute: u32, now: Instant) -> Result<(), Duration> {
+        let mut buckets = self.0.lock();
+        if buckets.len() >= PRUNE_THRESHOLD {
+            buckets.retain(|_, bucket| !bucket.is_idle(per_minute, now));
+        }
+        buckets.entry(caller.to_string())
+            .or_insert_with(|| Bucket::full(per_minute, now))
+            .try_take(per_minute, now)
+    }
+}
+
+/// Per-IP and per-key buckets of the API, limited as currently configured
+#[derive(Clone)]
+pub(crate) struct RateLimiter {
+    config: ConfigService,
+    by_ip: Arc<Buckets>,
+    by_key: Arc<Buckets>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(config: ConfigService) -> Self {
+        Self { config, by_ip: Arc::default(), by_key: Arc::default() }
+    }
+}
+
+/// Refuse requests from client addresses over `api.rate_limit.per_ip`
+pub(crate) async fn limit_by_ip(State(limiter): State<RateLimiter>, request: Request, next: Next) -> Response {
+    let per_minute = limiter.config.current().api.rate_limit.per_ip;
+    let client = request.extensions().get::<ConnectInfo<SocketAddr>>().map(|info| info.0.ip());
+    if let (Some(per_minute), Some(ip)) = (per_minute, client) {
+        if !OPEN_PATHS.contains(&request.uri().path()) {
+            if let Err(wait) = limiter.by_ip.try_take(&ip.to_string(), per_minute, Instant::now()) {
+                debug!("Rate limited requests from {}", ip);
+                return too_many_requests(format!("Too many requests from {}", ip), wait);
+            }
+        }
+    }
+    next.run(request).await
+}
+
+/// Refuse requests from API keys and token subjects over `api.rate_limit.per_key`
+pub(crate) async fn limit_by_key(State(limiter): State<RateLimiter>, request: Request, next: Next) -> Response {
+    let per_minute = limiter.config.current().api.rate_limit.per_key;
+    let principal = request.extensions().get::<Principal>();
+    if let (Some(per_minute), Some(principal)) = (per_minute, principal) {
+        if let Err(wait) = limiter.by_key.try_take(&principal.id, per_minute, Instant::now()) {
+            debug!("Rate limited requests as '{}'", principal.id);
+            return too_many_requests(format!("Too many requests as '{}'", principal.id), wait);
+        }
+    }
+    next.run(request).await
+}
+
+fn too_many_requests(message: String, wait: Duration) -> Response {
+    let mut response = ApiError(NexaError::rate_limited(message)).into_response();
+    let seconds = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+    response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(seconds.max(1)));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buckets() {
+        let start = Instant::now();
+        let buckets = Buckets::default();
+
+        // A minute's allowance as a burst, then one request per refill
+        for _ in 0..60 {
+            assert!(buckets.try_take("10.0.0.1", 60, start).is_ok());
+        }
+        assert_eq!(buckets.try_take("10.0.0.1", 60, start), Err(Duration::from_secs(1)));
+        assert!(buckets.try_take("10.0.0.1", 60, start + Duration::from_secs(1)).is_ok());
+
+        // Refused requests do not push the wait further out
+        assert!(buckets.try_take("10.0.0.1", 60, start + Duration::from_secs(1)).is_err());
+        assert!(buckets.try_take("10.0.0.1", 60, start + Duration::from_secs(2)).is_ok());
+
+        // Callers have buckets of their own
+        assert!(buckets.try_take("10.0.0.2", 60, start).is_ok());
+    }
+}
@@ -0,0 +1,112 @@
+//! Role-Based Authorization
+//!
+//! What callers of the API may do, by the role of their key or token:
+//! - `viewer` reads: status, agents, log levels, drain progress, models
+//! - `operator` also changes what runs: agents, tasks, workflows, models
+//! - `admin` also controls the daemon itself: log levels and draining
+//!
+//! Every route needs a role by default; `server.route_roles` in the config
+//! overrides it per route and method.
+
+use axum::extract::{MatchedPath, Request, State};
+use axum::http::Method;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use crate::api::auth::Principal;
+use crate::api::ApiError;
+use crate::config::{ConfigService, RouteRole};
+use crate::error::NexaError;
+
+/// Role of an API caller; each role may do everything the ones before it may
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Viewer,
+    Operator,
+    Admin,
+}
+
+impl Role {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Viewer => "viewer",
+            Self::Operator => "operator",
+            Self::Admin => "admin",
+        }
+    }
+}
+
+impl std::str::FromStr for Role {
+    type Err = NexaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "viewer" => Ok(Self::Viewer),
+            "operator" => Ok(Self::Operator),
+            "admin" => Ok(Self::Admin),
+            _ => Err(NexaError::invalid_input(format!("Unknown role '{}'; expected viewer, operator or admin", s))),
+        }
+    }
+}
+
+/// Routes only admins may change unless configured otherwise
+const ADMIN_ROUTES: &[&str] = &["/api/log-level", "/api/log-level/:target", "/api/drain"];
+
+/// Least role allowed to call `method` on `route`, as the route is declared in the router
+pub fn required_role(rules: &[RouteRole], method: &Method, route: &str) -> Role {
+    let rule = rules.iter().find(|rule| {
+        rule.path == route && rule.method.as_ref().is_none_or(|m| m.eq_ignore_ascii_case(method.as_str()))
+    });
+    if let Some(rule) = rule {
+        return rule.role;
+    }
+    match *method {
+        Method::GET | Method::HEAD | Method::OPTIONS => Role::Viewer,
+        _ if ADMIN_ROUTES.contains(&route) => Role::Admin,
+        _ => Role::Operator,
+    }
+}
+
+/// Refuse requests from callers whose role is below the one their route needs
+///
+/// Requests carry no principal only while the API is open, and are let through.
+pub(crate) async fn authorize(State(config): State<ConfigService>, request: Request, next: Next) -> Response {
+    let Some(principal) = request.extensions().get::<Principal>() else {
+        return next.run(request).await;
+    };
+    let route = request.extensions().get::<MatchedPath>().map_or(request.uri().path(), MatchedPath::as_str);
+    let required = required_role(&config.current().server.route_roles, request.method(), route);
+    if principal.role < required {
+        let err = NexaError::forbidden(format!(
+            "'{}' is {} but {} {} needs {}",
+            principal.id, principal.role.name(), request.method(), route, required.name()
+        ));
+        return ApiError(err).into_response();
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_required_role() {
+        assert_eq!(required_role(&[], &Method::GET, "/api/drain"), Role::Viewer);
+        assert_eq!(required_role(&[], &Method::POST, "/api/drain"), Role::Admin);
+        assert_eq!(required_role(&[], &Method::POST, "/api/ollama/pull"), Role::Operator);
+        assert!(Role::Admin > Role::Operator && Role::Operator > Role::Viewer);
+
+        // Configured rules win, for their method or for all of them
+        let rules = vec![
+            RouteRole { path: "/api/ollama/pull".to_string(), method: Some("post".to_string()), role: Role::Admin },
+            RouteRole { path: "/api/log-level".to_string(), method: None, role: Role::Operator },
+        ];
+        assert_eq!(required_role(&rules, &Method::POST, "/api/ollama/pull"), Role::Admin);
+        assert_eq!(required_role(&rules, &Method::GET, "/api/ollama/ps"), Role::Viewer);
+        assert_eq!(required_role(&rules, &Method::PUT, "/api/log-level"), Role::Operator);
+        assert_eq!(required_role(&rules, &Method::GET, "/api/log-level"), Role::Operator);
+    }
+}
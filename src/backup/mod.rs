@@ -0,0 +1,333 @@
+//! Runtime Backups
+//!
+//! Snapshots of persisted data with retention:
+//! - Agents, the registry journal, workflows, tasks, runtime state and config copied into a snapshot directory
+//! - Token usage totals recorded alongside the data
+//! - Scheduled snapshots while the server runs, and snapshots before migrations
+//! - Retention of the newest snapshots only
+//! - Restore of a snapshot by id or path
+
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::error::NexaError;
+use crate::mcp::ServerControl;
+use crate::migrations::DataKind;
+use crate::mcp::registry::REGISTRY_JOURNAL_FILE;
+use crate::recovery::STATE_FILE;
+use tracing::{debug, error, info};
+
+/// Directory holding snapshots, relative to the runtime directory
+pub const BACKUP_DIR: &str = "backups";
+/// Manifest describing a snapshot
+pub const MANIFEST_FILE: &str = "manifest.json";
+/// Token usage totals captured with a snapshot
+pub const TOKENS_FILE: &str = "tokens.json";
+
+/// Description of a snapshot on disk
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotInfo {
+    /// Snapshot id, also the name of its directory
+    pub id: String,
+    /// Time the snapshot was taken
+    pub created_at: DateTime<Utc>,
+    /// Why the snapshot was taken (scheduled, manual, pre-migration, pre-restore)
+    pub reason: String,
+    /// Files contained in the snapshot
+    pub files: Vec<String>,
+}
+
+/// Creates, prunes and restores snapshots of the runtime directory
+#[derive(Debug, Clone)]
+pub struct BackupManager {
+    runtime_dir: PathBuf,
+    backup_dir: PathBuf,
+    config_path: Option<PathBuf>,
+    retention: usize,
+}
+
+impl BackupManager {
+    pub fn new(runtime_dir: impl Into<PathBuf>) -> Self {
+        let runtime_dir = runtime_dir.into();
+        Self {
+            backup_dir: runtime_dir.join(BACKUP_DIR),
+            runtime_dir,
+            config_path: None,
+            retention: 7,
+        }
+    }
+
+    /// Also back up the configuration file at the given path
+    pub fn with_config_path(mut self, path: PathBuf) -> Self {
+        self.config_path = Some(path);
+        self
+    }
+
+    /// Number of snapshots to keep
+    pub fn with_retention(mut self, retention: usize) -> Self {
+        self.retention = retention.max(1);
+        self
+    }
+
+    /// Directory snapshots are written to
+    pub fn backup_dir(&self) -> &Path {
+        &self.backup_dir
+    }
+
+    /// Snapshot the data files in the runtime directory
+    pub fn snapshot(&self, reason: &str) -> Result<SnapshotInfo, NexaError> {
+        self.create(reason, None)
+    }
+
+    /// Snapshot the data files together with a running server's token usage
+    pub async fn snapshot_server(&self, server: &ServerControl, reason: &str) -> Result<SnapshotInfo, NexaError> {
+        let usage = serde_json::to_value(server.total_token_usage().await)?;
+        self.create(reason, Some(usage))
+    }
+
+    /// List snapshots, oldest first
+    pub fn list(&self) -> Result<Vec<SnapshotInfo>, NexaError> {
+        let entries = match fs::read_dir(&self.backup_dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(NexaError::system(format!("Failed to read {:?}: {}", self.backup_dir, e))),
+        };
+
+        let mut snapshots: Vec<SnapshotInfo> = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| read_manifest(&entry.path()).ok())
+            .collect();
+        snapshots.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.id.cmp(&b.id)));
+        Ok(snapshots)
+    }
+
+    /// Delete all but the newest `retention` snapshots, returning the removed ids
+    pub fn prune(&self) -> Result<Vec<String>, NexaError> {
+        let snapshots = self.list()?;
+        let excess = snapshots.len().saturating_sub(self.retention);
+        let mut removed = Vec::new();
+        for snapshot in snapshots.into_iter().take(excess) {
+            fs::remove_dir_all(self.backup_dir.join(&snapshot.id))
+                .map_err(|e| NexaError::system(format!("Failed to remove snapshot {}: {}", snapshot.id, e)))?;
+            debug!("Pruned snapshot {}", snapshot.id);
+            removed.push(snapshot.id);
+        }
+        Ok(removed)
+    }
+
+    /// Restore a snapshot, given by id or by path
+    ///
+    /// The current data is snapshotted first so a restore can be undone.
+    pub fn restore(&self, from: &str) -> Result<SnapshotInfo, NexaError> {
+        let dir = if Path::new(from).join(MANIFEST_FILE).exists() {
+            PathBuf::from(from)
+        } else {
+            self.backup_dir.join(from)
+        };
+        let snapshot = read_manifest(&dir)
+            .map_err(|_| NexaError::invalid_input(format!("No snapshot found at '{}'", from)))?;
+        // A crafted manifest must not write outside the runtime directory, so check every name first
+        if let Some(file) = snapshot.files.iter().find(|file| !is_plain_file_name(file)) {
+            return Err(NexaError::invalid_input(format!(
+                "Snapshot {} names '{}', which is not a plain file name", snapshot.id, file
+            )));
+        }
+
+        let undo = self.snapshot("pre-restore")?;
+        info!("Saved current data as snapshot {} before restoring {}", undo.id, snapshot.id);
+
+        for file in &snapshot.files {
+            let Some(target) = self.target_path(file) else {
+                continue;
+            };
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)
+                    .map_err(|e| NexaError::system(format!("Failed to create {:?}: {}", parent, e)))?;
+            }
+            fs::copy(dir.join(file), &target)
+                .map_err(|e| NexaError::system(format!("Failed to restore {}: {}", file, e)))?;
+        }
+
+        info!("Restored snapshot {} ({} file(s))", snapshot.id, snapshot.files.len());
+        Ok(snapshot)
+    }
+
+    /// Snapshot on a fixed interval until the task is aborted
+    pub fn start_schedule(self, server: ServerControl, interval: Duration) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            // The first tick completes immediately; wait a full interval instead
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                // Replicas sharing a runtime volume leave scheduled snapshots to the leader
+                if !server.is_leader() {
+                    continue;
+                }
+                let result = match self.snapshot_server(&server, "scheduled").await {
+                    Ok(_) => self.prune().map(|_| ()),
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = result {
+                    error!("Scheduled backup failed: {}", e);
+                }
+            }
+        })
+    }
+
+    fn create(&self, reason: &str, token_usage: Option<serde_json::Value>) -> Result<SnapshotInfo, NexaError> {
+        let created_at = Utc::now();
+        let base_id = created_at.format("%Y%m%dT%H%M%S%.3fZ").to_string();
+        let mut id = base_id.clone();
+        let mut suffix = 1;
+        while self.backup_dir.join(&id).exists() {
+            id = format!("{}-{}", base_id, suffix);
+            suffix += 1;
+        }
+
+        let dir = self.backup_dir.join(&id);
+        fs::create_dir_all(&dir)
+            .map_err(|e| NexaError::system(format!("Failed to create snapshot directory {:?}: {}", dir, e)))?;
+
+        let mut files = Vec::new();
+        for (name, source) in self.sources() {
+            if source.exists() {
+                fs::copy(&source, dir.join(&name))
+                    .map_err(|e| NexaError::system(format!("Failed to back up {:?}: {}", source, e)))?;
+                files.push(name);
+            }
+        }
+        if let Some(usage) = token_usage {
+            fs::write(dir.join(TOKENS_FILE), serde_json::to_string_pretty(&usage)?)
+                .map_err(|e| NexaError::system(format!("Failed to write token usage: {}", e)))?;
+            files.push(TOKENS_FILE.to_string());
+        }
+
+        let snapshot = SnapshotInfo {
+            id,
+            created_at,
+            reason: reason.to_string(),
+            files,
+        };
+        fs::write(dir.join(MANIFEST_FILE), serde_json::to_string_pretty(&snapshot)?)
+            .map_err(|e| NexaError::system(format!("Failed to write snapshot manifest: {}", e)))?;
+
+        info!("Created {} snapshot {} ({} file(s))", reason, snapshot.id, snapshot.files.len());
+        Ok(snapshot)
+    }
+
+    /// Files to back up, by name inside the snapshot
+    fn sources(&self) -> Vec<(String, PathBuf)> {
+        let mut sources: Vec<(String, PathBuf)> = [DataKind::Agents, DataKind::Workflows, DataKind::Tasks]
+            .iter()
+            .map(|kind| kind.file_name())
+            .chain([REGISTRY_JOURNAL_FILE, STATE_FILE])
+            .map(|name| (name.to_string(), self.runtime_dir.join(name)))
+            .collect();
+        if let Some(path) = &self.config_path {
+            sources.push((DataKind::Config.file_name().to_string(), path.clone()));
+        }
+        sources
+    }
+
+    /// Where a snapshot file is restored to; token usage is kept for reference only
+    fn target_path(&self, file: &str) -> Option<PathBuf> {
+        if file == TOKENS_FILE {
+            None
+        } else if file == DataKind::Config.file_name() {
+            self.config_path.clone()
+        } else {
+            Some(self.runtime_dir.join(file))
+        }
+    }
+}
+
+/// Whether `name` is a single file name, neither absolute nor leading out of its directory
+fn is_plain_file_name(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    matches!((components.next(), components.next()), (Some(Component::Normal(_)), None))
+}
+
+fn read_manifest(dir: &Path) -> Result<SnapshotInfo, NexaError> {
+    let contents = fs::read_to_string(dir.join(MANIFEST_FILE))
+        .map_err(|e| NexaError::system(format!("Failed to read snapshot manifest in {:?}: {}", dir, e)))?;
+    serde_json::from_str(&contents)
+        .map_err(|e| NexaError::system(format!("Invalid snapshot manifest in {:?}: {}", dir, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_prune_restore() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config_path = temp_dir.path().join("config").join("config.yml");
+        fs::create_dir_all(config_path.parent().unwrap()).unwrap();
+        fs::write(&config_path, "version: 1\n").unwrap();
+        let agents = temp_dir.path().join(DataKind::Agents.file_name());
+        fs::write(&agents, r#"{"version": 1, "data": ["agent-1"]}"#).unwrap();
+
+        let manager = BackupManager::new(temp_dir.path())
+            .with_config_path(config_path.clone())
+            .with_retention(2);
+        let first = manager.snapshot("manual").unwrap();
+        assert_eq!(first.files, vec!["agents.json".to_string(), "config.yml".to_string()]);
+
+        fs::write(&agents, r#"{"version": 1, "data": []}"#).unwrap();
+        fs::write(&config_path, "version: 1\nserver:\n  port: 9090\n").unwrap();
+        manager.snapshot("manual").unwrap();
+        manager.snapshot("manual").unwrap();
+
+        // Only the newest snapshots are kept
+        assert_eq!(manager.prune().unwrap(), vec![first.id.clone()]);
+        assert_eq!(manager.list().unwrap().len(), 2);
+
+        // Restoring by path brings back the old contents and saves the current ones
+        let restore_dir = temp_dir.path().join("restore-me");
+        fs::create_dir_all(&restore_dir).unwrap();
+        fs::write(restore_dir.join("agents.json"), r#"{"version": 1, "data": ["agent-1"]}"#).unwrap();
+        fs::write(restore_dir.join("config.yml"), "version: 1\n").unwrap();
+        fs::write(restore_dir.join(MANIFEST_FILE), serde_json::to_string(&first).unwrap()).unwrap();
+
+        manager.restore(restore_dir.to_str().unwrap()).unwrap();
+        assert!(fs::read_to_string(&agents).unwrap().contains("agent-1"));
+        assert_eq!(fs::read_to_string(&config_path).unwrap(), "version: 1\n");
+        assert!(manager.list().unwrap().iter().any(|s| s.reason == "pre-restore"));
+
+        assert!(manager.restore("does-not-exist").is_err());
+    }
+
+    #[test]
+    fn test_restore_rejects_traversal() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let runtime_dir = temp_dir.path().join("runtime");
+        fs::create_dir_all(&runtime_dir).unwrap();
+        let manager = BackupManager::new(&runtime_dir);
+
+        let crafted = temp_dir.path().join("crafted");
+        fs::create_dir_all(crafted.join("nested")).unwrap();
+        fs::write(crafted.join("agents.json"), "{}").unwrap();
+        let escaped = temp_dir.path().join("escaped.json");
+        let absolute = escaped.to_string_lossy().into_owned();
+        for name in ["../escaped.json", absolute.as_str(), "nested/agents.json", ".", ""] {
+            let snapshot = SnapshotInfo {
+                id: "crafted".to_string(),
+                created_at: Utc::now(),
+                reason: "manual".to_string(),
+                files: vec!["agents.json".to_string(), name.to_string()],
+            };
+            fs::write(crafted.join(MANIFEST_FILE), serde_json::to_string(&snapshot).unwrap()).unwrap();
+
+            let err = manager.restore(crafted.to_str().unwrap()).unwrap_err();
+            assert!(matches!(err, NexaError::InvalidInput(_)), "{:?} was restored", name);
+        }
+
+        // Nothing was written, not even the files named before the bad one
+        assert!(!escaped.exists());
+        assert!(!runtime_dir.join(DataKind::Agents.file_name()).exists());
+        assert!(manager.list().unwrap().is_empty());
+    }
+}
@@ -1,15 +1,22 @@
 use nexa_core::cli::run;
-use std::error::Error;
+use nexa_core::logging;
+use nexa_core::NexaError;
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
-    // Initialize tracing for logging
-    tracing_subscriber::fmt()
-        .with_max_level(tracing::Level::TRACE)
-        .init();
+async fn main() {
+    // Initialize tracing with a filter the daemon can adjust at runtime;
+    // stdout carries the protocol when serving MCP over stdio
+    let init = if std::env::args().any(|arg| arg == "--stdio") { logging::init_to_stderr } else { logging::init };
+    if let Err(e) = init("trace") {
+        eprintln!("Error: {}", e);
+    }
 
-    // Run CLI handler
-    run().await?;
-
-    Ok(())
-}
\ No newline at end of file
+    // Run CLI handler, mapping errors to exit codes
+    if let Err(e) = run().await {
+        eprintln!("Error: {}", e);
+        let code = e.downcast_ref::<NexaError>()
+            .map(NexaError::exit_code)
+            .unwrap_or(1);
+        std::process::exit(code);
+    }
+}
@@ -2,14 +2,33 @@
 //! 
 //! Provides command-line interface functionality for:
 //! - Starting/stopping the MCP server
+//! - Preflight checks before startup
 //! - Monitoring system status
 //! - Managing agents
+//! - Managing provider secrets
+//! - Validating configuration
+//! - Adjusting log levels at runtime
+//! - Managing REST API keys
+//! - Creating and restoring backups
+//! - Serving the Model Context Protocol over stdio
 
 use clap::{Parser, Subcommand};
 use tracing::{error, info};
-use crate::mcp::ServerControl;
+use crate::api::ApiClient;
+use crate::api::auth::{self, API_KEY_ENV};
+use crate::api::rbac::Role;
+use crate::mcp::{DrainPhase, ServerControl};
 use std::path::PathBuf;
+use crate::config::Config;
 use crate::error::NexaError;
+use crate::llm::{LLMAvailability, LLMClient, LLMConfig, LMStudioClient, OllamaClient, ServerType};
+use crate::secrets::SecretStore;
+use crate::startup::StartupManager;
+use crate::migrations::Migrator;
+use crate::recovery::{CrashRecovery, StateJournal};
+use crate::discovery::{self, Discovery};
+use crate::mcp::stdio;
+use crate::backup::BackupManager;
 use sysinfo;
 use std::process;
 use ctrlc;
@@ -21,6 +40,9 @@ use nix::libc;
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 pub struct Cli {
+    /// Config profile to apply (overrides NEXA_PROFILE)
+    #[arg(long, global = true)]
+    profile: Option<String>,
     #[command(subcommand)]
     command: Commands,
 }
@@ -33,11 +55,188 @@ enum Commands {
     Stop,
     /// Get server status
     Status,
+    /// Manage provider secrets
+    Secret {
+        #[command(subcommand)]
+        action: SecretCommands,
+    },
+    /// Inspect and validate configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommands,
+    },
+    /// Manage REST API keys
+    Apikey {
+        #[command(subcommand)]
+        action: ApiKeyCommands,
+    },
+    /// Refuse new connections and task assignments, and wait for the tasks in flight
+    Drain {
+        /// Seconds to wait for the tasks in flight
+        #[arg(long, default_value_t = 300)]
+        timeout: u64,
+    },
+    /// Accept connections and task assignments again after a drain
+    Resume,
+    /// Adjust log levels in the running server
+    LogLevel {
+        #[command(subcommand)]
+        action: LogLevelCommands,
+    },
+    /// Manage snapshots of the runtime directory
+    Backup {
+        #[command(subcommand)]
+        action: BackupCommands,
+    },
+    /// Restore a snapshot (the server must be stopped)
+    Restore {
+        /// Snapshot id or directory
+        #[arg(long)]
+        from: String,
+    },
+    /// Inspect LLM provider models and manage Ollama and LM Studio models
+    Models {
+        #[command(subcommand)]
+        action: ModelCommands,
+    },
+    /// Serve agents and tools to Model Context Protocol hosts
+    Mcp {
+        #[command(subcommand)]
+        action: McpCommands,
+    },
+}
+
+#[derive(Subcommand)]
+enum McpCommands {
+    /// Serve MCP; over WebSocket by starting the server, or over stdin and stdout
+    Serve {
+        /// Speak MCP on stdin and stdout, for hosts that launch servers as subprocesses
+        #[arg(long)]
+        stdio: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ModelCommands {
+    /// List the models a provider offers
+    List {
+        /// lmstudio, ollama, anthropic or gemini (defaults to the configured provider)
+        provider: Option<String>,
+    },
+    /// Download a model into Ollama
+    Pull {
+        /// Model name (e.g. llama3.2:3b)
+        model: String,
+    },
+    /// Show an installed Ollama model
+    Show {
+        /// Model name
+        model: String,
+    },
+    /// Remove a model from Ollama
+    Rm {
+        /// Model name
+        model: String,
+    },
+    /// List the models Ollama has loaded into memory
+    Ps,
+    /// Load a model into LM Studio
+    Load {
+        /// Model key (e.g. qwen2.5-7b-instruct)
+        model: String,
+        /// Unload every other model first to free memory
+        #[arg(long)]
+        exclusive: bool,
+    },
+    /// Unload a model from LM Studio
+    Unload {
+        /// Model key
+        model: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum BackupCommands {
+    /// Take a snapshot now
+    Create,
+    /// List snapshots
+    List,
+}
+
+#[derive(Subcommand)]
+enum LogLevelCommands {
+    /// Set the level of a module (or `default` for all modules)
+    Set {
+        /// Module path or tracing target (e.g. mcp::server)
+        target: String,
+        /// trace, debug, info, warn, error or off
+        level: String,
+    },
+    /// Show the current log levels
+    Show,
+    /// Remove a module's level override
+    Reset {
+        /// Module path or tracing target
+        target: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// Check a config file against the schema
+    Validate {
+        /// Config file path (defaults to ~/.config/nexa/config.yml)
+        path: Option<PathBuf>,
+    },
+    /// Print the config JSON Schema
+    Schema,
+}
+
+#[derive(Subcommand)]
+enum ApiKeyCommands {
+    /// Create a key and print it; it cannot be shown again
+    Create {
+        /// Name identifying the key
+        id: String,
+        /// viewer, operator or admin
+        #[arg(long, default_value = "viewer")]
+        role: Role,
+    },
+    /// Revoke a key
+    Revoke {
+        /// Name of the key
+        id: String,
+    },
+    /// List keys and their roles
+    List,
+}
+
+#[derive(Subcommand)]
+enum SecretCommands {
+    /// Store a secret, read from stdin or typed at a prompt without echo
+    Set {
+        /// Secret name (e.g. OPENAI_API_KEY)
+        name: String,
+        /// Secret value; visible in shell history and the process list, so prefer stdin
+        #[arg(long)]
+        value: Option<String>,
+    },
+    /// Print a secret
+    Get {
+        /// Secret name
+        name: String,
+    },
+    /// Remove a secret
+    Rm {
+        /// Secret name
+        name: String,
+    },
 }
 
 pub struct CliHandler {
     pid_file: PathBuf,
     server: ServerControl,
+    secrets: SecretStore,
 }
 
 impl CliHandler {
@@ -47,12 +246,36 @@ impl CliHandler {
             pid_file.clone(),
             PathBuf::from("/tmp/nexa.sock"),
         );
-        Self { pid_file, server }
+        let secrets = SecretStore::open_default();
+        Self { pid_file, server, secrets }
     }
 
     pub fn new_with_paths(pid_file: PathBuf, socket_path: PathBuf) -> Self {
         let server = ServerControl::new(pid_file.clone(), socket_path);
-        Self { pid_file, server }
+        let secrets = SecretStore::open_default();
+        Self { pid_file, server, secrets }
+    }
+
+    /// Create a handler using the runtime directory of a config profile
+    pub fn with_profile(profile: Option<String>) -> Result<Self, NexaError> {
+        let config_path = Config::get_config_path();
+        let config = if config_path.exists() {
+            Config::load_profile(&config_path, profile.as_deref())?
+        } else if let Some(name) = &profile {
+            return Err(NexaError::config(format!(
+                "Config profile '{}' requested but {:?} does not exist", name, config_path
+            )));
+        } else {
+            Config::default()
+        };
+
+        let handler = Self::new_with_paths(
+            config.runtime_dir.join("nexa.pid"),
+            config.runtime_dir.join("nexa.sock"),
+        );
+        handler.server.config_service().set_profile(profile);
+        handler.server.config_service().update(config);
+        Ok(handler)
     }
 
     pub async fn is_server_running(&self) -> bool {
@@ -60,6 +283,10 @@ impl CliHandler {
         if let Ok(pid_str) = fs::read_to_string(&self.pid_file) {
             if let Ok(pid) = pid_str.trim().parse::<i32>() {
                 if unsafe { libc::kill(pid, 0) } == 0 {
+                    // A server owned by another process is running if its PID is alive
+                    if pid as u32 != process::id() {
+                        return true;
+                    }
                     // PID exists and process is running, now check if server is bound
                     // Wait up to 1 second for the server to be ready
                     return tokio::time::timeout(
@@ -78,6 +305,61 @@ impl CliHandler {
             return Ok(());
         }
 
+        // Load configuration and watch it for changes
+        let config_path = Config::get_config_path();
+        if config_path.exists() {
+            self.server.config_service().reload(&config_path)?;
+            self.server.config_service().watch_file(config_path.clone(), std::time::Duration::from_secs(5));
+        }
+
+        // Run preflight checks before touching the PID file
+        let bind_addr = match addr {
+            Some(addr) => addr.to_string(),
+            None if config_path.exists() => {
+                let server = self.server.config_service().current().server;
+                format!("{}:{}", server.host, server.port)
+            }
+            None => self.server.get_server_config().await?.bind_addr,
+        };
+        let runtime_dir = self.pid_file.parent().unwrap_or(&self.pid_file).to_path_buf();
+        let report = StartupManager::new(runtime_dir.clone(), self.pid_file.clone())
+            .with_bind_addr(bind_addr.clone())
+            .with_auto_port(self.server.get_server_config().await?.auto_port)
+            .with_config_path(config_path.clone())
+            .with_llm_config(self.server.config_service().current().llm)
+            .run_preflight()
+            .await;
+        println!("{}", report);
+        if !report.is_go() {
+            return Err(NexaError::system("Preflight checks failed"));
+        }
+
+        // Upgrade older on-disk formats before anything reads them
+        let migrator = Migrator::new(&runtime_dir).with_config_path(config_path);
+        if !migrator.pending()?.is_empty() {
+            let snapshot = self.backups().snapshot("pre-migration")?;
+            println!("Saved snapshot {} before migrating", snapshot.id);
+        }
+        for record in migrator.run()? {
+            println!("Migrated {} from v{} to v{} (backup: {})", record.kind, record.from, record.to, record.backup.display());
+        }
+
+        // Reconcile state left behind by a crashed run
+        let recovery = CrashRecovery::new(&runtime_dir, self.pid_file.clone()).recover(&self.server).await?;
+        if recovery.crashed {
+            println!(
+                "Recovered from unclean shutdown: {} task(s) interrupted, {} message(s) requeued",
+                recovery.interrupted_tasks.len(),
+                recovery.requeued_messages
+            );
+        }
+
+        // Bring back the agents known to the previous run
+        let restored = self.server.registry.open(&runtime_dir).await?;
+        if restored > 0 {
+            println!("Restored {} agent(s), offline until they register again", restored);
+        }
+
         // Write PID file first
         fs::create_dir_all(self.pid_file.parent().unwrap_or(&self.pid_file))
             .map_err(|e| NexaError::system(format!("Failed to create parent directory: {}", e)))?;
@@ -87,7 +369,13 @@ impl CliHandler {
 
         // Setup signal handler for cleanup
         let pid_file = self.pid_file.clone();
+        let state_file = self.server.state_file();
+        let runtime_dir = self.server.runtime_dir();
         ctrlc::set_handler(move || {
+            if let Err(e) = StateJournal::mark_clean_shutdown(&state_file) {
+                eprintln!("Failed to record clean shutdown: {}", e);
+            }
+            Discovery::remove(&runtime_dir);
             if let Err(e) = fs::remove_file(&pid_file) {
                 eprintln!("Failed to remove PID file: {}", e);
             }
@@ -95,9 +383,9 @@ impl CliHandler {
         })?;
 
         info!("Starting Nexa Core server");
-        
+
         // Start the server
-        if let Err(e) = self.server.start(addr).await {
+        if let Err(e) = self.server.start(Some(&bind_addr)).await {
             // Clean up PID file on error
             let _ = fs::remove_file(&self.pid_file);
             return Err(e);
@@ -106,6 +394,35 @@ impl CliHandler {
         Ok(())
     }
 
+    /// Wait for SIGTERM, then stop gracefully within the configured grace period
+    ///
+    /// Readiness turns false first so load balancers stop routing to this replica.
+    pub async fn wait_for_termination(&self) -> Result<(), NexaError> {
+        let mut sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
+        sigterm.recv().await;
+
+        let grace = std::time::Duration::from_secs(self.server.config_service().current().server.shutdown_grace_period);
+        info!("Received SIGTERM, shutting down within {}s", grace.as_secs());
+        self.server.begin_shutdown();
+        match tokio::time::timeout(grace, self.server.stop()).await {
+            Ok(Ok(())) => {
+                if let Err(e) = StateJournal::mark_clean_shutdown(&self.server.state_file()) {
+                    error!("Failed to record clean shutdown: {}", e);
+                }
+            }
+            Ok(Err(e)) => error!("Failed to stop server gracefully: {}", e),
+            Err(_) => error!("Graceful shutdown exceeded {}s", grace.as_secs()),
+        }
+
+        Discovery::remove(&self.server.runtime_dir());
+        // The server removes its PID file when it stops in time
+        match fs::remove_file(&self.pid_file) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => error!("Failed to remove PID file: {}", e),
+            _ => {}
+        }
+        Ok(())
+    }
+
     pub async fn stop(&self) -> Result<(), NexaError> {
         if !self.is_server_running().await {
             println!("Server is not running");
@@ -177,9 +494,34 @@ impl CliHandler {
         } else {
             let pid = fs::read_to_string(&self.pid_file)
                 .map_err(|e| NexaError::system(format!("Failed to read PID file: {}", e)))?;
-            status.push_str(&format!("Server is running on 0.0.0.0:8080\n"));
+            match Discovery::read(&self.server.runtime_dir()) {
+                Some(discovery) => {
+                    status.push_str(&format!("Server is running on {}\n", discovery.ws_addr));
+                    if let Some(api_addr) = discovery.api_addr {
+                        status.push_str(&format!("API: {}\n", api_addr));
+                    }
+                }
+                None => status.push_str("Server is running (address unknown)\n"),
+            }
             status.push_str(&format!("PID: {}\n", pid.trim()));
 
+            // Surface profile and degraded mode from the daemon's runtime state
+            let state = StateJournal::load(&self.server.state_file()).ok().flatten();
+            let profile = state.as_ref()
+                .map(|s| s.profile.clone())
+                .unwrap_or_else(|| self.server.config_service().profile());
+            status.push_str(&format!("Profile: {}\n", profile.as_deref().unwrap_or("default")));
+
+            if let Some(state) = state {
+                if let Some(LLMAvailability::Degraded { since, reason }) = state.llm_availability {
+                    status.push_str(&format!(
+                        "\n⚠️  DEGRADED: LLM providers unavailable since {} ({})\n",
+                        since.format("%Y-%m-%d %H:%M:%S UTC"), reason
+                    ));
+                    status.push_str(&format!("  Queued LLM requests: {}\n", state.queued_llm_requests));
+                }
+            }
+
             // Add server metrics if available
             if let Ok(metrics) = self.server.get_metrics().await {
                 status.push_str(&format!("\nServer Metrics:\n"));
@@ -197,16 +539,422 @@ impl CliHandler {
     pub fn get_pid_file_path(&self) -> &PathBuf {
         &self.pid_file
     }
+
+    pub fn secret_set(&self, name: &str, value: &str) -> Result<(), NexaError> {
+        self.secrets.set(name, value)?;
+        println!("Secret '{}' stored", name);
+        Ok(())
+    }
+
+    pub fn secret_get(&self, name: &str) -> Result<(), NexaError> {
+        match self.secrets.get(name)? {
+            Some(value) => println!("{}", value),
+            None => return Err(NexaError::not_found(format!("Secret '{}' not found", name))),
+        }
+        Ok(())
+    }
+
+    pub fn secret_rm(&self, name: &str) -> Result<(), NexaError> {
+        if !self.secrets.remove(name)? {
+            return Err(NexaError::not_found(format!("Secret '{}' not found", name)));
+        }
+        println!("Secret '{}' removed", name);
+        Ok(())
+    }
+
+    pub fn config_validate(&self, path: Option<PathBuf>) -> Result<(), NexaError> {
+        let path = path.unwrap_or_else(Config::get_config_path);
+        let issues = Config::validate_file(&path)?;
+        if issues.is_empty() {
+            println!("{} is valid", path.display());
+            return Ok(());
+        }
+
+        for issue in &issues {
+            println!("  {}", issue);
+        }
+        Err(NexaError::config(format!("{} has {} configuration error(s)", path.display(), issues.len())))
+    }
+
+    pub fn config_schema(&self) -> Result<(), NexaError> {
+        println!("{}", serde_json::to_string_pretty(&Config::json_schema())?);
+        Ok(())
+    }
+
+    pub fn apikey_create(&self, id: &str, role: Role) -> Result<(), NexaError> {
+        let key = auth::create_key(&Config::get_config_path(), id, role)?;
+        println!("{}", key);
+        eprintln!("API key '{}' created; store it now, it cannot be shown again", id);
+        Ok(())
+    }
+
+    pub fn apikey_revoke(&self, id: &str) -> Result<(), NexaError> {
+        auth::revoke_key(&Config::get_config_path(), id)?;
+        println!("API key '{}' revoked", id);
+        Ok(())
+    }
+
+    pub fn apikey_list(&self) -> Result<(), NexaError> {
+        let keys = self.server.config_service().current().api.keys;
+        if keys.is_empty() {
+            println!("No API keys; the API is open");
+        }
+        for key in keys {
+            println!("{:<24} {}", key.id, key.role.name());
+        }
+        Ok(())
+    }
+
+    fn backups(&self) -> BackupManager {
+        BackupManager::new(self.server.runtime_dir())
+            .with_config_path(Config::get_config_path())
+            .with_retention(self.server.config_service().current().backup.retention)
+    }
+
+    pub fn backup_create(&self) -> Result<(), NexaError> {
+        let backups = self.backups();
+        let snapshot = backups.snapshot("manual")?;
+        backups.prune()?;
+        println!("Created snapshot {} ({} file(s))", snapshot.id, snapshot.files.len());
+        Ok(())
+    }
+
+    pub fn backup_list(&self) -> Result<(), NexaError> {
+        let backups = self.backups();
+        let snapshots = backups.list()?;
+        if snapshots.is_empty() {
+            println!("No snapshots in {}", backups.backup_dir().display());
+        }
+        for snapshot in snapshots {
+            println!("{}  {:<13} {}", snapshot.id, snapshot.reason, snapshot.files.join(", "));
+        }
+        Ok(())
+    }
+
+    pub async fn restore(&self, from: &str) -> Result<(), NexaError> {
+        if self.is_server_running().await {
+            return Err(NexaError::invalid_input("Stop the server before restoring a snapshot"));
+        }
+        let snapshot = self.backups().restore(from)?;
+        println!("Restored snapshot {} taken {} ({})", snapshot.id, snapshot.created_at, snapshot.reason);
+        Ok(())
+    }
+
+    /// List models of the configured provider, or of another one with its default settings
+    pub async fn list_models(&self, provider: Option<&str>) -> Result<(), NexaError> {
+        let configured = self.server.config_service().current().llm;
+        let config = match provider.map(str::parse::<ServerType>).transpose()? {
+            Some(server_type) if server_type != configured.server_type => {
+                LLMConfig::for_server_type(server_type)
+            }
+            _ => configured,
+        };
+        let models = LLMClient::new(config.clone())?.list_models().await?;
+        if models.is_empty() {
+            println!("No models available from {}", config.server_url);
+        }
+        for model in models {
+            println!("{}", model);
+        }
+        Ok(())
+    }
+
+    /// Ollama server of the configured `llm` section, or a local one for other providers
+    fn ollama(&self) -> Result<OllamaClient, NexaError> {
+        OllamaClient::new(&self.server.config_service().current().llm)
+    }
+
+    /// Pull a model into Ollama, printing download progress
+    pub async fn model_pull(&self, model: &str) -> Result<(), NexaError> {
+        use futures::TryStreamExt;
+        use std::io::Write;
+
+        let mut progress = self.ollama()?.pull(model).await?;
+        let mut status = String::new();
+        while let Some(event) = progress.try_next().await? {
+            if event.status != status {
+                if !status.is_empty() {
+                    println!();
+                }
+                status = event.status.clone();
+            }
+            match event.fraction() {
+                Some(fraction) => print!("\r{} {:>5.1}%", status, fraction * 100.0),
+                None => print!("\r{}", status),
+            }
+            std::io::stdout().flush()?;
+        }
+        println!();
+        Ok(())
+    }
+
+    pub async fn model_show(&self, model: &str) -> Result<(), NexaError> {
+        let details = self.ollama()?.show(model).await?;
+        println!("Family:       {}", details.details.family);
+        println!("Parameters:   {}", details.details.parameter_size);
+        println!("Quantization: {}", details.details.quantization_level);
+        println!("Format:       {}", details.details.format);
+        if !details.parameters.is_empty() {
+            println!("\n{}", details.parameters.trim_end());
+        }
+        Ok(())
+    }
+
+    pub async fn model_rm(&self, model: &str) -> Result<(), NexaError> {
+        self.ollama()?.delete(model).await?;
+        println!("Model '{}' removed", model);
+        Ok(())
+    }
+
+    pub async fn model_ps(&self) -> Result<(), NexaError> {
+        let running = self.ollama()?.running().await?;
+        if running.is_empty() {
+            println!("No models loaded");
+        }
+        for model in running {
+            let until = model.expires_at
+                .map(|at| at.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+                .unwrap_or_default();
+            println!(
+                "{:<32} {:>8.1} MB  {:>8.1} MB VRAM  {}",
+                model.name,
+                model.size as f64 / 1024.0 / 1024.0,
+                model.size_vram as f64 / 1024.0 / 1024.0,
+                until
+            );
+        }
+        Ok(())
+    }
+
+    /// LM Studio server of the configured `llm` section, or a local one for other providers
+    fn lmstudio(&self) -> Result<LMStudioClient, NexaError> {
+        LMStudioClient::new(&self.server.config_service().current().llm)
+    }
+
+    pub async fn model_load(&self, model: &str, exclusive: bool) -> Result<(), NexaError> {
+        let lmstudio = self.lmstudio()?;
+        if exclusive {
+            for unloaded in lmstudio.switch_to(model).await? {
+                println!("Model '{}' unloaded", unloaded);
+            }
+        } else {
+            lmstudio.load(model).await?;
+        }
+        println!("Model '{}' loaded", model);
+        Ok(())
+    }
+
+    pub async fn model_unload(&self, model: &str) -> Result<(), NexaError> {
+        self.lmstudio()?.unload(model).await?;
+        println!("Model '{}' unloaded", model);
+        Ok(())
+    }
+
+    /// Serve MCP on stdin and stdout
+    ///
+    /// Relays to the running server, sharing its agents and tools; otherwise serves
+    /// the tools of this process. Nothing but protocol messages is printed.
+    pub async fn mcp_serve_stdio(&self) -> Result<(), NexaError> {
+        let input = tokio::io::BufReader::new(tokio::io::stdin());
+        let output = tokio::io::stdout();
+        if self.is_server_running().await {
+            if let Some(discovery) = Discovery::read(&self.server.runtime_dir()) {
+                let url = format!("ws://{}", discovery::connectable(discovery.ws_addr));
+                info!("Relaying MCP on stdio to {}", url);
+                return stdio::relay(&url, input, output).await;
+            }
+        }
+
+        let config_path = Config::get_config_path();
+        if config_path.exists() {
+            self.server.config_service().reload(&config_path)?;
+        }
+        self.server.load_tools().await?;
+        info!("Serving MCP on stdio");
+        stdio::serve(&self.server.mcp_handler(), input, output).await
+    }
+
+    /// Client for the running server's REST API
+    ///
+    /// Prefers the address advertised in the discovery file over the configured one,
+    /// and presents the key in `NEXA_API_KEY`, if set.
+    fn api_client(&self) -> Result<ApiClient, NexaError> {
+        let url = match Discovery::read(&self.server.runtime_dir()).and_then(|d| d.api_url()) {
+            Some(url) => url,
+            None => {
+                let addr = self.server.config_service().current().api.bind_addr.replace("0.0.0.0", "127.0.0.1");
+                format!("http://{}", addr)
+            }
+        };
+        let client = ApiClient::new(url)?;
+        Ok(match std::env::var(API_KEY_ENV).ok().filter(|key| !key.is_empty()) {
+            Some(key) => client.with_api_key(key),
+            None => client,
+        })
+    }
+
+    async fn running_api_client(&self) -> Result<ApiClient, NexaError> {
+        if !self.is_server_running().await {
+            return Err(NexaError::unavailable("Server is not running. Start it with 'nexa start'"));
+        }
+        self.api_client()
+    }
+
+    /// Drain the running server, printing progress until it is drained or times out
+    pub async fn drain(&self, timeout: u64) -> Result<(), NexaError> {
+        let client = self.running_api_client().await?;
+        let mut status = client.drain(timeout).await?;
+        let mut reported = None;
+        while status.phase == DrainPhase::Draining {
+            if reported != Some(status.in_flight_tasks) {
+                println!("Draining: {} tasks in flight, {} connections open", status.in_flight_tasks, status.active_connections);
+                reported = Some(status.in_flight_tasks);
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+            status = client.drain_status().await?;
+        }
+        match status.phase {
+            DrainPhase::TimedOut => println!("Drain timed out with {} tasks in flight", status.in_flight_tasks),
+            _ => println!("Drained; {} connections still open, safe to stop", status.active_connections),
+        }
+        Ok(())
+    }
+
+    pub async fn resume(&self) -> Result<(), NexaError> {
+        self.running_api_client().await?.resume().await?;
+        println!("Server resumed");
+        Ok(())
+    }
+
+    pub async fn log_level_set(&self, target: &str, level: &str) -> Result<(), NexaError> {
+        let levels = self.running_api_client().await?.set_log_level(target, level).await?;
+        println!("Log filter: {}", levels);
+        Ok(())
+    }
+
+    pub async fn log_level_show(&self) -> Result<(), NexaError> {
+        let levels = self.running_api_client().await?.log_levels().await?;
+        println!("Default: {}", levels.default);
+        for (target, level) in &levels.targets {
+            println!("  {} = {}", target, level);
+        }
+        Ok(())
+    }
+
+    pub async fn log_level_reset(&self, target: &str) -> Result<(), NexaError> {
+        let levels = self.running_api_client().await?.reset_log_level(target).await?;
+        println!("Log filter: {}", levels);
+        Ok(())
+    }
+}
+
+/// Read a secret's value from a prompt without echo on a terminal, or else from stdin
+fn read_secret_value(name: &str) -> Result<String, NexaError> {
+    use std::io::{BufRead, IsTerminal, Read, Write};
+
+    let stdin = std::io::stdin();
+    let mut value = String::new();
+    if stdin.is_terminal() {
+        eprint!("Value for {}: ", name);
+        let _ = std::io::stderr().flush();
+        let _echo = EchoOff::new();
+        stdin.lock().read_line(&mut value)?;
+        eprintln!();
+    } else {
+        stdin.lock().read_to_string(&mut value)?;
+    }
+    let value = value.trim_end_matches(['\r', '\n']).to_string();
+    if value.is_empty() {
+        return Err(NexaError::invalid_input(format!("No value given for secret '{}'", name)));
+    }
+    Ok(value)
+}
+
+/// Turns terminal echo off on stdin until dropped
+struct EchoOff(Option<libc::termios>);
+
+impl EchoOff {
+    fn new() -> Self {
+        let mut termios = std::mem::MaybeUninit::<libc::termios>::uninit();
+        // SAFETY: tcgetattr fills the struct when it succeeds, and it is only read then
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, termios.as_mut_ptr()) } != 0 {
+            return Self(None);
+        }
+        let saved = unsafe { termios.assume_init() };
+        let mut silent = saved;
+        silent.c_lflag &= !libc::ECHO;
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &silent) };
+        Self(Some(saved))
+    }
+}
+
+impl Drop for EchoOff {
+    fn drop(&mut self) {
+        if let Some(saved) = &self.0 {
+            unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, saved) };
+        }
+    }
 }
 
 pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
-    let handler = CliHandler::new();
+    let handler = CliHandler::with_profile(cli.profile.or_else(Config::profile_from_env))?;
 
     match cli.command {
-        Commands::Start => handler.start(None).await?,
+        Commands::Start => {
+            handler.start(None).await?;
+            // Ctrl-C exits through its handler; SIGTERM drains within the grace period
+            handler.wait_for_termination().await?;
+        }
         Commands::Stop => handler.stop().await?,
         Commands::Status => handler.status().await?,
+        Commands::Secret { action } => match action {
+            SecretCommands::Set { name, value } => {
+                let value = match value {
+                    Some(value) => value,
+                    None => read_secret_value(&name)?,
+                };
+                handler.secret_set(&name, &value)?
+            }
+            SecretCommands::Get { name } => handler.secret_get(&name)?,
+            SecretCommands::Rm { name } => handler.secret_rm(&name)?,
+        },
+        Commands::Config { action } => match action {
+            ConfigCommands::Validate { path } => handler.config_validate(path)?,
+            ConfigCommands::Schema => handler.config_schema()?,
+        },
+        Commands::Apikey { action } => match action {
+            ApiKeyCommands::Create { id, role } => handler.apikey_create(&id, role)?,
+            ApiKeyCommands::Revoke { id } => handler.apikey_revoke(&id)?,
+            ApiKeyCommands::List => handler.apikey_list()?,
+        },
+        Commands::Drain { timeout } => handler.drain(timeout).await?,
+        Commands::Resume => handler.resume().await?,
+        Commands::LogLevel { action } => match action {
+            LogLevelCommands::Set { target, level } => handler.log_level_set(&target, &level).await?,
+            LogLevelCommands::Show => handler.log_level_show().await?,
+            LogLevelCommands::Reset { target } => handler.log_level_reset(&target).await?,
+        },
+        Commands::Backup { action } => match action {
+            BackupCommands::Create => handler.backup_create()?,
+            BackupCommands::List => handler.backup_list()?,
+        },
+        Commands::Restore { from } => handler.restore(&from).await?,
+        Commands::Models { action } => match action {
+            ModelCommands::List { provider } => handler.list_models(provider.as_deref()).await?,
+            ModelCommands::Pull { model } => handler.model_pull(&model).await?,
+            ModelCommands::Show { model } => handler.model_show(&model).await?,
+            ModelCommands::Rm { model } => handler.model_rm(&model).await?,
+            ModelCommands::Ps => handler.model_ps().await?,
+            ModelCommands::Load { model, exclusive } => handler.model_load(&model, exclusive).await?,
+            ModelCommands::Unload { model } => handler.model_unload(&model).await?,
+        },
+        Commands::Mcp { action: McpCommands::Serve { stdio: true } } => handler.mcp_serve_stdio().await?,
+        Commands::Mcp { action: McpCommands::Serve { stdio: false } } => {
+            // The WebSocket server answers MCP alongside the native protocol
+            handler.start(None).await?;
+            handler.wait_for_termination().await?;
+        }
     }
 
     Ok(())
@@ -0,0 +1,163 @@
+//! Environment variable interpolation for config values
+//!
+//! Supported forms inside any string value:
+//! - `${VAR}` - value of `VAR`, an error if it is unset
+//! - `${VAR:-default}` - value of `VAR`, or `default` if unset or empty
+//! - `$${` - a literal `${`
+//!
+//! A string that consists of a single reference is re-parsed as a YAML
+//! scalar, so `port: ${PORT}` yields a number.
+
+use serde_json::Value;
+use crate::error::NexaError;
+
+/// Resolve all `${VAR}` references in a parsed configuration document
+pub fn interpolate(value: &mut Value) -> Result<(), NexaError> {
+    interpolate_with(value, &|name| std::env::var(name).ok())
+}
+
+/// Resolve references using a custom variable lookup
+pub fn interpolate_with(value: &mut Value, lookup: &dyn Fn(&str) -> Option<String>) -> Result<(), NexaError> {
+    let missing = resolve_with(value, lookup)?;
+    if missing.is_empty() {
+        return Ok(());
+    }
+    Err(NexaError::config(format!(
+        "Missing environment variable(s): {}",
+        missing.join(", ")
+    )))
+}
+
+/// Resolve what can be resolved, returning the unset variables as `NAME (at /path)`
+pub fn unresolved(value: &mut Value) -> Result<Vec<String>, NexaError> {
+    resolve_with(value, &|name| std::env::var(name).ok())
+}
+
+fn resolve_with(value: &mut Value, lookup: &dyn Fn(&str) -> Option<String>) -> Result<Vec<String>, NexaError> {
+    let mut missing = Vec::new();
+    visit(value, "", lookup, &mut missing)?;
+    Ok(missing)
+}
+
+fn visit(
+    value: &mut Value,
+    path: &str,
+    lookup: &dyn Fn(&str) -> Option<String>,
+    missing: &mut Vec<String>,
+) -> Result<(), NexaError> {
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map.iter_mut() {
+                visit(child, &format!("{}/{}", path, key), lookup, missing)?;
+            }
+        }
+        Value::Array(items) => {
+            for (i, child) in items.iter_mut().enumerate() {
+                visit(child, &format!("{}/{}", path, i), lookup, missing)?;
+            }
+        }
+        Value::String(s) if s.contains('$') => {
+            let whole_reference = is_single_reference(s);
+            let resolved = expand(s, path, lookup, missing)?;
+            *value = if whole_reference {
+                serde_yaml::from_str::<Value>(&resolved)
+                    .ok()
+                    .filter(|v| v.is_number() || v.is_boolean())
+                    .unwrap_or(Value::String(resolved))
+            } else {
+                Value::String(resolved)
+            };
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn is_single_reference(s: &str) -> bool {
+    s.starts_with("${") && s.ends_with('}') && s[2..].find('}') == Some(s.len() - 3)
+}
+
+fn expand(
+    input: &str,
+    path: &str,
+    lookup: &dyn Fn(&str) -> Option<String>,
+    missing: &mut Vec<String>,
+) -> Result<String, NexaError> {
+    let mut out = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(pos) = rest.find('$') {
+        out.push_str(&rest[..pos]);
+        let tail = &rest[pos..];
+
+        if let Some(after) = tail.strip_prefix("$${") {
+            out.push_str("${");
+            rest = after;
+        } else if let Some(after) = tail.strip_prefix("${") {
+            let end = after.find('}').ok_or_else(|| {
+                NexaError::config(format!("Unterminated variable reference in {}: {}", display_path(path), input))
+            })?;
+            let reference = &after[..end];
+            let (name, default) = match reference.split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (reference, None),
+            };
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(NexaError::config(format!(
+                    "Invalid variable name '{}' in {}", name, display_path(path)
+                )));
+            }
+
+            match (lookup(name).filter(|v| !v.is_empty() || default.is_none()), default) {
+                (Some(v), _) => out.push_str(&v),
+                (None, Some(default)) => out.push_str(default),
+                (None, None) => missing.push(format!("{} (at {})", name, display_path(path))),
+            }
+            rest = &after[end + 1..];
+        } else {
+            out.push('$');
+            rest = &tail[1..];
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn display_path(path: &str) -> &str {
+    if path.is_empty() { "/" } else { path }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_interpolation() {
+        let lookup = |name: &str| match name {
+            "LLM_HOST" => Some("llm.internal".to_string()),
+            "PORT" => Some("9090".to_string()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        };
+
+        let mut value = json!({
+            "llm": { "server_url": "http://${LLM_HOST}:1234" },
+            "server": { "port": "${PORT}", "host": "${HOST:-0.0.0.0}" },
+            "logging": { "file": "${EMPTY:-nexa.log}", "level": "$${literal} and $5" },
+        });
+        interpolate_with(&mut value, &lookup).unwrap();
+        assert_eq!(value["llm"]["server_url"], "http://llm.internal:1234");
+        assert_eq!(value["server"]["port"], 9090);
+        assert_eq!(value["server"]["host"], "0.0.0.0");
+        assert_eq!(value["logging"]["file"], "nexa.log");
+        assert_eq!(value["logging"]["level"], "${literal} and $5");
+
+        let mut value = json!({ "a": "${MISSING_ONE}", "b": ["${MISSING_TWO}"] });
+        let err = interpolate_with(&mut value, &lookup).unwrap_err().to_string();
+        assert!(err.contains("MISSING_ONE (at /a)"));
+        assert!(err.contains("MISSING_TWO (at /b/0)"));
+
+        assert!(interpolate_with(&mut json!("${UNTERMINATED"), &lookup).is_err());
+    }
+}
@@ -2,50 +2,114 @@
 //! 
 //! Provides functionality for:
 //! - Loading/saving configuration
-//! - Configuration validation
+//! - Configuration validation against a generated JSON Schema
 //! - Hot reload support
 //! - Default configuration
+//! - Environment-specific profiles (`profile.<name>` overrides)
+//! - `${ENV_VAR}` interpolation in config values
+//! - Change notifications to subsystems
 
+pub mod interpolate;
+
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use crate::api::rbac::Role;
 use crate::error::NexaError;
+use crate::events::EventKind;
+use crate::llm::{LLMConfig, ModelEntry};
+use crate::mcp::client::McpServerConfig;
+use crate::mcp::server::Transport;
 use std::fs;
-use tracing::debug;
+use tokio::sync::watch;
+use tracing::{debug, error, info};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub struct ServerConfig {
     /// Server listening address
     pub host: String,
     /// Server listening port
+    #[schemars(range(max = 65535))]
     pub port: u16,
     /// Maximum number of concurrent connections
     #[serde(default = "default_max_connections")]
+    #[schemars(range(min = 1))]
     pub max_connections: u32,
     /// Connection timeout in seconds
     #[serde(default = "default_connection_timeout")]
+    #[schemars(range(min = 1))]
     pub connection_timeout: u64,
+    /// Bind to an ephemeral port when the configured port is busy
+    #[serde(default = "default_auto_port")]
+    pub auto_port: bool,
+    /// Time allowed for a graceful stop after SIGTERM (seconds)
+    #[serde(default = "default_shutdown_grace_period")]
+    pub shutdown_grace_period: u64,
+    /// Protocol agents connect with; applies on the next start
+    #[serde(default)]
+    pub transport: Transport,
+    /// Refuse agents that do not open with a protocol version handshake
+    #[serde(default)]
+    pub require_handshake: bool,
+    /// Refuse to register agents that were not issued a token
+    #[serde(default)]
+    pub require_agent_token: bool,
+    /// Seconds between pings to agents that negotiated heartbeats
+    #[serde(default = "default_heartbeat_interval")]
+    #[schemars(range(min = 1))]
+    pub heartbeat_interval: u64,
+    /// Heartbeats an agent may miss before it is taken offline
+    #[serde(default = "default_missed_heartbeats")]
+    #[schemars(range(min = 1))]
+    pub missed_heartbeats: u32,
+    /// Roles needed for API routes, overriding the defaults
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub route_roles: Vec<RouteRole>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct RouteRole {
+    /// Route as declared by the API, e.g. `/api/ollama/models/*model`
+    pub path: String,
+    /// HTTP method; every method when unset
+    #[serde(default)]
+    pub method: Option<String>,
+    /// Least role allowed
+    pub role: Role,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub struct MonitoringConfig {
     /// CPU usage threshold percentage
     #[serde(default = "default_cpu_threshold")]
+    #[schemars(range(min = 0, max = 100))]
     pub cpu_threshold: f64,
     /// Memory usage threshold percentage
     #[serde(default = "default_memory_threshold")]
+    #[schemars(range(min = 0, max = 100))]
     pub memory_threshold: f64,
     /// Health check interval in seconds
     #[serde(default = "default_health_check_interval")]
+    #[schemars(range(min = 1))]
     pub health_check_interval: u64,
     /// Enable detailed metrics collection
     #[serde(default = "default_detailed_metrics")]
     pub detailed_metrics: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub struct LoggingConfig {
     /// Log level (trace, debug, info, warn, error)
     #[serde(default = "default_log_level")]
+    #[schemars(regex(pattern = r"^(trace|debug|info|warn|error)$"))]
     pub level: String,
     /// Log file path
     #[serde(default = "default_log_file")]
@@ -58,14 +122,224 @@ pub struct LoggingConfig {
     pub files_to_keep: u32,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct ApiConfig {
+    /// Enable the REST API server
+    #[serde(default = "default_api_enabled")]
+    pub enabled: bool,
+    /// REST API listening address
+    #[serde(default = "default_api_bind_addr")]
+    pub bind_addr: String,
+    /// Keys accepted by the API; it is open to anyone while there are none
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keys: Vec<ApiKeyConfig>,
+    /// Accept bearer tokens issued by an OpenID Connect provider
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jwt: Option<JwtConfig>,
+    #[serde(default)]
+    pub rate_limit: ApiRateLimit,
+}
+
+/// Requests a caller may make to the API per minute
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+#[schemars(deny_unknown_fields)]
+pub struct ApiRateLimit {
+    /// Requests per minute from one IP address; unlimited when unset
+    #[schemars(range(min = 1))]
+    pub per_ip: Option<u32>,
+    /// Requests per minute with one API key or token subject; unlimited when unset
+    #[schemars(range(min = 1))]
+    pub per_key: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct ApiKeyConfig {
+    /// Name identifying the key in logs and when revoking it
+    pub id: String,
+    /// SHA-256 of the key, as hex
+    pub hash: String,
+    /// What the key may do
+    pub role: Role,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct JwtConfig {
+    /// Issuer tokens must come from (`iss`)
+    pub issuer: String,
+    /// Audience tokens must be for (`aud`); any when unset
+    #[serde(default)]
+    pub audience: Option<String>,
+    /// JWKS endpoint; found through the issuer's discovery document when unset
+    #[serde(default)]
+    pub jwks_url: Option<String>,
+    /// Claim listing the caller's roles; dots descend into nested claims
+    #[serde(default = "default_jwt_roles_claim")]
+    pub roles_claim: String,
+    /// API role granted to each role listed in the claim; the highest applies
+    #[serde(default)]
+    pub roles: BTreeMap<String, Role>,
+    /// Clock skew tolerated when checking expiry (seconds)
+    #[serde(default = "default_jwt_leeway")]
+    pub leeway: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct BackupConfig {
+    /// Take scheduled snapshots while the server runs
+    #[serde(default = "default_backup_enabled")]
+    pub enabled: bool,
+    /// Interval between scheduled snapshots (seconds)
+    #[serde(default = "default_backup_interval")]
+    #[schemars(range(min = 1))]
+    pub interval: u64,
+    /// Number of snapshots to keep
+    #[serde(default = "default_backup_retention")]
+    #[schemars(range(min = 1))]
+    pub retention: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct PluginConfig {
+    /// Load WASM plugins at startup (requires the `plugins` feature)
+    #[serde(default = "default_plugins_enabled")]
+    pub enabled: bool,
+    /// Plugins directory; defaults to `plugins` in the runtime directory
+    #[serde(default)]
+    pub dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct ContainersConfig {
+    /// Host directories agents may mount, with everything below them; no mounts when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mount_roots: Vec<PathBuf>,
+    /// Honor `read_only: false` on mounts; otherwise every mount is read-only
+    #[serde(default)]
+    pub allow_writable_mounts: bool,
+    /// Honor `network: true` in limits; otherwise containers have no network
+    #[serde(default)]
+    pub allow_network: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct ClusterConfig {
+    /// Healthy members, this node included, needed to assign tasks; fewer fences the node
+    #[serde(default = "default_min_quorum_size")]
+    #[schemars(range(min = 1))]
+    pub min_quorum_size: usize,
+    /// UDP address to gossip membership on, e.g. `0.0.0.0:7946`; the node stays alone when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gossip_addr: Option<SocketAddr>,
+    /// Gossip addresses of members to join through, tried in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub seeds: Vec<SocketAddr>,
+    /// Name of the secret holding the key nodes authenticate each other with;
+    /// registry replication and message forwarding are off without it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_secret: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct KubernetesConfig {
+    /// Elect a leader through a Kubernetes Lease
+    #[serde(default)]
+    pub enabled: bool,
+    /// Name of the Lease object
+    #[serde(default = "default_lease_name")]
+    pub lease_name: String,
+    /// Namespace of the Lease; defaults to the pod's namespace
+    #[serde(default)]
+    pub namespace: Option<String>,
+    /// Seconds a lease stays valid without renewal
+    #[serde(default = "default_lease_duration")]
+    #[schemars(range(min = 1))]
+    pub lease_duration: u64,
+    /// Seconds between renewals
+    #[serde(default = "default_lease_renew_interval")]
+    #[schemars(range(min = 1))]
+    pub renew_interval: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct EventsConfig {
+    /// Append every event to `events.jsonl` in the runtime directory
+    #[serde(default = "default_event_log")]
+    pub log: bool,
+    /// Forward events to connected WebSocket clients
+    #[serde(default = "default_event_websocket")]
+    pub websocket: bool,
+    /// Endpoints receiving events as JSON POST requests
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// Event kinds to deliver; all kinds when empty
+    #[serde(default)]
+    pub events: Vec<EventKind>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub struct Config {
+    /// Config file format version
+    #[serde(default = "default_config_version")]
+    pub version: u32,
     #[serde(default)]
     pub server: ServerConfig,
     #[serde(default)]
     pub monitoring: MonitoringConfig,
     #[serde(default)]
     pub logging: LoggingConfig,
+    #[serde(default)]
+    pub llm: LLMConfig,
+    /// Providers tried in order when `llm` is unreachable or rate limited
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub llm_fallbacks: Vec<LLMConfig>,
+    /// Models the router chooses from by capability and cost
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub llm_models: Vec<ModelEntry>,
+    #[serde(default)]
+    pub api: ApiConfig,
+    #[serde(default)]
+    pub backup: BackupConfig,
+    #[serde(default)]
+    pub plugins: PluginConfig,
+    /// What the `run_container` tool may expose to containers
+    #[serde(default)]
+    pub containers: ContainersConfig,
+    /// Membership and quorum of the node's cluster (requires the `cluster` feature)
+    #[serde(default)]
+    pub cluster: ClusterConfig,
+    #[serde(default)]
+    pub kubernetes: KubernetesConfig,
+    #[serde(default)]
+    pub events: EventsConfig,
+    /// MCP servers whose tools agents may use
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mcp_servers: Vec<McpServerConfig>,
+    /// Directory for PID, socket, state and data files
+    #[serde(default = "default_runtime_dir")]
+    pub runtime_dir: PathBuf,
+    /// Named profiles whose settings override the ones above
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profile: BTreeMap<String, serde_json::Value>,
+    /// Profile applied when the configuration was loaded
+    #[serde(skip)]
+    pub active_profile: Option<String>,
 }
 
 // Default implementations
@@ -76,6 +350,14 @@ impl Default for ServerConfig {
             port: 8080,
             max_connections: default_max_connections(),
             connection_timeout: default_connection_timeout(),
+            auto_port: default_auto_port(),
+            shutdown_grace_period: default_shutdown_grace_period(),
+            transport: Transport::default(),
+            require_handshake: false,
+            require_agent_token: false,
+            heartbeat_interval: default_heartbeat_interval(),
+            missed_heartbeats: default_missed_heartbeats(),
+            route_roles: Vec::new(),
         }
     }
 }
@@ -102,19 +384,103 @@ impl Default for LoggingConfig {
     }
 }
 
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_api_enabled(),
+            bind_addr: default_api_bind_addr(),
+            keys: Vec::new(),
+            jwt: None,
+            rate_limit: ApiRateLimit::default(),
+        }
+    }
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_backup_enabled(),
+            interval: default_backup_interval(),
+            retention: default_backup_retention(),
+        }
+    }
+}
+
+impl Default for PluginConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_plugins_enabled(),
+            dir: None,
+        }
+    }
+}
+
+impl Default for ClusterConfig {
+    fn default() -> Self {
+        Self {
+            min_quorum_size: default_min_quorum_size(),
+            gossip_addr: None,
+            seeds: Vec::new(),
+            auth_secret: None,
+        }
+    }
+}
+
+impl Default for KubernetesConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            lease_name: default_lease_name(),
+            namespace: None,
+            lease_duration: default_lease_duration(),
+            renew_interval: default_lease_renew_interval(),
+        }
+    }
+}
+
+impl Default for EventsConfig {
+    fn default() -> Self {
+        Self {
+            log: default_event_log(),
+            websocket: default_event_websocket(),
+            webhooks: Vec::new(),
+        }
+    }
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
+            version: default_config_version(),
             server: ServerConfig::default(),
             monitoring: MonitoringConfig::default(),
             logging: LoggingConfig::default(),
+            llm: LLMConfig::default(),
+            llm_fallbacks: Vec::new(),
+            llm_models: Vec::new(),
+            api: ApiConfig::default(),
+            backup: BackupConfig::default(),
+            plugins: PluginConfig::default(),
+            containers: ContainersConfig::default(),
+            cluster: ClusterConfig::default(),
+            kubernetes: KubernetesConfig::default(),
+            events: EventsConfig::default(),
+            mcp_servers: Vec::new(),
+            runtime_dir: default_runtime_dir(),
+            profile: BTreeMap::new(),
+            active_profile: None,
         }
     }
 }
 
 // Default value functions
+fn default_config_version() -> u32 { crate::migrations::CURRENT_VERSION }
 fn default_max_connections() -> u32 { 1000 }
 fn default_connection_timeout() -> u64 { 30 }
+fn default_auto_port() -> bool { true }
+fn default_shutdown_grace_period() -> u64 { 25 }
+fn default_heartbeat_interval() -> u64 { 15 }
+fn default_missed_heartbeats() -> u32 { 3 }
 fn default_cpu_threshold() -> f64 { 80.0 }
 fn default_memory_threshold() -> f64 { 90.0 }
 fn default_health_check_interval() -> u64 { 30 }
@@ -123,6 +489,27 @@ fn default_log_level() -> String { "info".to_string() }
 fn default_log_file() -> String { "nexa.log".to_string() }
 fn default_max_log_size() -> u64 { 100 }
 fn default_log_files() -> u32 { 5 }
+fn default_api_enabled() -> bool { true }
+fn default_api_bind_addr() -> String { "127.0.0.1:8081".to_string() }
+fn default_jwt_roles_claim() -> String { "roles".to_string() }
+fn default_jwt_leeway() -> u64 { 60 }
+fn default_backup_enabled() -> bool { true }
+fn default_backup_interval() -> u64 { 86400 }
+fn default_backup_retention() -> usize { 7 }
+fn default_plugins_enabled() -> bool { true }
+fn default_min_quorum_size() -> usize { 1 }
+fn default_lease_name() -> String { "nexa-leader".to_string() }
+fn default_lease_duration() -> u64 { 15 }
+fn default_lease_renew_interval() -> u64 { 5 }
+fn default_event_log() -> bool { true }
+fn default_event_websocket() -> bool { true }
+fn default_runtime_dir() -> PathBuf { PathBuf::from("/tmp") }
+
+/// Environment variable selecting the config profile
+pub const PROFILE_ENV: &str = "NEXA_PROFILE";
+
+/// Environment variable overriding the config file path (e.g. a mounted ConfigMap)
+pub const CONFIG_ENV: &str = "NEXA_CONFIG";
 
 impl Config {
     /// Load configuration from file
@@ -134,11 +521,57 @@ impl Config {
             return Ok(config);
         }
 
+        let mut value = Self::read_document(path)?;
+        interpolate::interpolate(&mut value)?;
+        serde_json::from_value(value)
+            .map_err(|e| NexaError::config(format!("Failed to parse config file: {}", e)))
+    }
+
+    /// Read a config file as a generic document
+    fn read_document(path: &PathBuf) -> Result<serde_json::Value, NexaError> {
         let contents = fs::read_to_string(path)
             .map_err(|e| NexaError::config(format!("Failed to read config file: {}", e)))?;
+        let value = match serde_yaml::from_str(&contents)
+            .map_err(|e| NexaError::config(format!("Failed to parse config file: {}", e)))?
+        {
+            serde_json::Value::Null => serde_json::Value::Object(Default::default()),
+            value => value,
+        };
+        Ok(value)
+    }
 
-        serde_yaml::from_str(&contents)
-            .map_err(|e| NexaError::config(format!("Failed to parse config file: {}", e)))
+    /// Load configuration from file with a profile applied on top
+    pub fn load_profile(path: &PathBuf, profile: Option<&str>) -> Result<Self, NexaError> {
+        let config = Self::load(path)?;
+        match profile {
+            Some(name) => config.with_profile(name),
+            None => Ok(config),
+        }
+    }
+
+    /// Apply the overrides of a named profile
+    pub fn with_profile(self, name: &str) -> Result<Self, NexaError> {
+        let overrides = self.profile.get(name).cloned().ok_or_else(|| {
+            let available: Vec<_> = self.profile.keys().map(String::as_str).collect();
+            NexaError::config(format!(
+                "Unknown config profile '{}' (available: {})",
+                name,
+                if available.is_empty() { "none".to_string() } else { available.join(", ") }
+            ))
+        })?;
+
+        let mut value = serde_json::to_value(&self)?;
+        merge_values(&mut value, overrides);
+        let mut config: Config = serde_json::from_value(value)
+            .map_err(|e| NexaError::config(format!("Invalid settings in profile '{}': {}", name, e)))?;
+        config.active_profile = Some(name.to_string());
+        debug!("Applied config profile {}", name);
+        Ok(config)
+    }
+
+    /// Profile selected through the environment, if any
+    pub fn profile_from_env() -> Option<String> {
+        std::env::var(PROFILE_ENV).ok().filter(|p| !p.is_empty())
     }
 
     /// Save configuration to file
@@ -155,8 +588,34 @@ impl Config {
         Ok(())
     }
 
+    /// Change a config file in place, keeping `${ENV_VAR}` references as written
+    pub fn edit<F>(path: &PathBuf, edit: F) -> Result<(), NexaError>
+    where
+        F: FnOnce(&mut serde_json::Value) -> Result<(), NexaError>,
+    {
+        let mut document = if path.exists() {
+            Self::read_document(path)?
+        } else {
+            serde_json::Value::Object(Default::default())
+        };
+        edit(&mut document)?;
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| NexaError::config(format!("Failed to create config directory: {}", e)))?;
+        }
+        let contents = serde_yaml::to_string(&document)
+            .map_err(|e| NexaError::config(format!("Failed to serialize config: {}", e)))?;
+        fs::write(path, contents)
+            .map_err(|e| NexaError::config(format!("Failed to write config file: {}", e)))?;
+        Ok(())
+    }
+
     /// Get configuration file path
     pub fn get_config_path() -> PathBuf {
+        if let Some(path) = std::env::var_os(CONFIG_ENV).filter(|p| !p.is_empty()) {
+            return PathBuf::from(path);
+        }
         let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
         PathBuf::from(home).join(".config").join("nexa").join("config.yml")
     }
@@ -165,4 +624,243 @@ impl Config {
     pub fn reset() -> Self {
         Self::default()
     }
-}
\ No newline at end of file
+
+    /// Generate the JSON Schema for the configuration file
+    pub fn json_schema() -> serde_json::Value {
+        serde_json::to_value(schemars::schema_for!(Config)).unwrap_or_default()
+    }
+
+    /// Check a configuration file against the JSON Schema
+    ///
+    /// Returns one message per violation (unknown keys, type mismatches,
+    /// out-of-range values); an empty list means the file is valid.
+    pub fn validate_file(path: &PathBuf) -> Result<Vec<String>, NexaError> {
+        let mut instance = Self::read_document(path)?;
+        let missing = interpolate::unresolved(&mut instance)?;
+
+        let schema = Self::json_schema();
+        let validator = jsonschema::JSONSchema::compile(&schema)
+            .map_err(|e| NexaError::config(format!("Invalid config schema: {}", e)))?;
+
+        let format_issue = |e: jsonschema::ValidationError| {
+            let path = e.instance_path.to_string();
+            let path = if path.is_empty() { "/".to_string() } else { path };
+            format!("{}: {}", path, e)
+        };
+        let mut issues: Vec<String> = match validator.validate(&instance) {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors.map(format_issue).collect(),
+        };
+        issues.extend(missing.into_iter().map(|var| format!("missing environment variable {}", var)));
+
+        // Check every profile as it would be applied, skipping issues inherited from the base
+        let base_issues = issues.clone();
+        if let Some(profiles) = instance.get("profile").and_then(|p| p.as_object()) {
+            for (name, overrides) in profiles {
+                let mut merged = instance.clone();
+                merge_values(&mut merged, overrides.clone());
+                let profile_issues: Vec<String> = match validator.validate(&merged) {
+                    Ok(()) => Vec::new(),
+                    Err(errors) => errors.map(format_issue).collect(),
+                };
+                issues.extend(profile_issues
+                    .into_iter()
+                    .filter(|issue| !base_issues.contains(issue))
+                    .map(|issue| format!("profile {}: {}", name, issue)));
+            }
+        }
+        debug!("Validated {:?}: {} issue(s)", path, issues.len());
+        Ok(issues)
+    }
+}
+
+/// Recursively overlay `overrides` onto `base`; non-object values replace
+fn merge_values(base: &mut serde_json::Value, overrides: serde_json::Value) {
+    match (base, overrides) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                if key == "profile" {
+                    continue;
+                }
+                match base.get_mut(&key) {
+                    Some(existing) => merge_values(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
+
+/// Distributes configuration updates to subscribed subsystems
+#[derive(Debug, Clone)]
+pub struct ConfigService {
+    tx: Arc<watch::Sender<Config>>,
+    profile: Arc<parking_lot::RwLock<Option<String>>>,
+}
+
+impl ConfigService {
+    pub fn new(config: Config) -> Self {
+        let profile = config.active_profile.clone();
+        let (tx, _) = watch::channel(config);
+        Self {
+            tx: Arc::new(tx),
+            profile: Arc::new(parking_lot::RwLock::new(profile)),
+        }
+    }
+
+    /// Select the profile applied on every reload
+    pub fn set_profile(&self, profile: Option<String>) {
+        *self.profile.write() = profile;
+    }
+
+    /// Profile applied on reload, if any
+    pub fn profile(&self) -> Option<String> {
+        self.profile.read().clone()
+    }
+
+    /// Get a snapshot of the current configuration
+    pub fn current(&self) -> Config {
+        self.tx.borrow().clone()
+    }
+
+    /// Subscribe to configuration updates
+    pub fn subscribe(&self) -> watch::Receiver<Config> {
+        self.tx.subscribe()
+    }
+
+    /// Publish a new configuration to all subscribers
+    pub fn update(&self, config: Config) {
+        self.tx.send_replace(config);
+        debug!("Published configuration update to {} subscribers", self.tx.receiver_count());
+    }
+
+    /// Reload configuration from file and publish it
+    pub fn reload(&self, path: &PathBuf) -> Result<(), NexaError> {
+        let config = Config::load_profile(path, self.profile().as_deref())?;
+        self.update(config);
+        info!("Reloaded configuration from {:?}", path);
+        Ok(())
+    }
+
+    /// Poll a configuration file and reload it whenever it changes
+    pub fn watch_file(&self, path: PathBuf, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let service = self.clone();
+        tokio::spawn(async move {
+            let modified = |path: &PathBuf| -> Option<SystemTime> {
+                fs::metadata(path).and_then(|m| m.modified()).ok()
+            };
+            let mut last_modified = modified(&path);
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let current = modified(&path);
+                if current.is_some() && current != last_modified {
+                    last_modified = current;
+                    if let Err(e) = service.reload(&path) {
+                        error!("Failed to reload configuration: {}", e);
+                    }
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("config.yml");
+
+        Config::default().save(&path).unwrap();
+        assert!(Config::validate_file(&path).unwrap().is_empty());
+
+        fs::write(&path, "server:\n  host: 127.0.0.1\n  port: 70000\nmonitoring:\n  cpu_threshold: 150\n  health_check_interval: soon\nunknown_section: true\n").unwrap();
+        let issues = Config::validate_file(&path).unwrap();
+        assert!(issues.iter().any(|i| i.starts_with("/server/port")));
+        assert!(issues.iter().any(|i| i.starts_with("/monitoring/cpu_threshold")));
+        assert!(issues.iter().any(|i| i.starts_with("/monitoring/health_check_interval")));
+        assert!(issues.iter().any(|i| i.contains("unknown_section")));
+
+        fs::write(&path, "cluster:\n  min_quorum_size: 2\n  gossip_addr: 0.0.0.0:7946\n  seeds: [10.0.0.2:7946]\n").unwrap();
+        assert!(Config::validate_file(&path).unwrap().is_empty());
+        let cluster = Config::load(&path).unwrap().cluster;
+        assert_eq!(cluster.gossip_addr, Some("0.0.0.0:7946".parse().unwrap()));
+        assert_eq!(cluster.seeds, vec!["10.0.0.2:7946".parse::<SocketAddr>().unwrap()]);
+        fs::write(&path, "cluster:\n  gossip_addr: somewhere\n").unwrap();
+        assert!(Config::load(&path).is_err());
+
+        fs::write(&path, "server:\n  host: ${NEXA_TEST_UNSET_HOST}\n  port: ${NEXA_TEST_UNSET_PORT:-8080}\n").unwrap();
+        let issues = Config::validate_file(&path).unwrap();
+        assert_eq!(issues, vec!["missing environment variable NEXA_TEST_UNSET_HOST (at /server/host)"]);
+        assert!(matches!(Config::load(&path), Err(NexaError::Config(_))));
+    }
+
+    #[test]
+    fn test_profiles() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("config.yml");
+        fs::write(&path, "\
+server:
+  host: 127.0.0.1
+  port: 8080
+profile:
+  dev:
+    runtime_dir: /tmp/nexa-dev
+    server:
+      port: 9090
+  prod:
+    monitoring:
+      cpu_threshold: 150
+").unwrap();
+
+        let base = Config::load(&path).unwrap();
+        assert_eq!(base.server.port, 8080);
+        assert_eq!(base.active_profile, None);
+
+        let dev = Config::load_profile(&path, Some("dev")).unwrap();
+        assert_eq!(dev.server.port, 9090);
+        assert_eq!(dev.server.host, "127.0.0.1");
+        assert_eq!(dev.runtime_dir, PathBuf::from("/tmp/nexa-dev"));
+        assert_eq!(dev.active_profile.as_deref(), Some("dev"));
+
+        assert!(matches!(Config::load_profile(&path, Some("staging")), Err(NexaError::Config(_))));
+
+        let issues = Config::validate_file(&path).unwrap();
+        assert_eq!(issues.len(), 1);
+        assert!(issues[0].starts_with("profile prod: /monitoring/cpu_threshold"));
+    }
+
+    #[tokio::test]
+    async fn test_config_service_notifies_subscribers() {
+        let service = ConfigService::new(Config::default());
+        let mut rx = service.subscribe();
+
+        let mut config = service.current();
+        config.monitoring.cpu_threshold = 50.0;
+        service.update(config);
+
+        rx.changed().await.unwrap();
+        assert_eq!(rx.borrow().monitoring.cpu_threshold, 50.0);
+        assert_eq!(service.current().monitoring.cpu_threshold, 50.0);
+    }
+
+    #[tokio::test]
+    async fn test_config_service_reload() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("config.yml");
+
+        let mut config = Config::default();
+        config.server.max_connections = 42;
+        config.save(&path).unwrap();
+
+        let service = ConfigService::new(Config::default());
+        service.reload(&path).unwrap();
+        assert_eq!(service.current().server.max_connections, 42);
+    }
+}
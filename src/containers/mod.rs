@@ -0,0 +1,377 @@
+//! Container Execution
+//!
+//! Runs commands in Docker containers so agents can test generated code in isolation:
+//! - Image pull, create, start, wait and cleanup through the Docker API
+//! - CPU, memory and process limits, no network unless requested
+//! - Bind mounts only below the operator's `containers.mount_roots`
+//! - Read-only mounts and no network unless the operator's config allows them
+//! - Wall-clock timeout that kills the container
+//! - Captured stdout and stderr as the result
+//! - Exposed to agents as the `run_container` tool
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use bollard::container::{
+    Config, CreateContainerOptions, LogOutput, LogsOptions, RemoveContainerOptions, WaitContainerOptions,
+};
+use bollard::image::CreateImageOptions;
+use bollard::models::HostConfig;
+use bollard::Docker;
+use futures::future::BoxFuture;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::config::ContainersConfig;
+use crate::error::NexaError;
+use crate::tools::{Tool, ToolSpec};
+use tracing::{debug, info, warn};
+
+/// A host directory mounted into the container
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Mount {
+    pub source: String,
+    pub target: String,
+    #[serde(default = "default_read_only")]
+    pub read_only: bool,
+}
+
+fn default_read_only() -> bool { true }
+
+/// Resource caps for a container run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResourceLimits {
+    /// CPU cores
+    #[serde(default = "default_cpus")]
+    pub cpus: f64,
+    /// Memory limit in MiB
+    #[serde(default = "default_memory_mb")]
+    pub memory_mb: u64,
+    /// Maximum number of processes
+    #[serde(default = "default_pids")]
+    pub pids: i64,
+    /// Wall-clock limit in seconds
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    /// Allow network access
+    #[serde(default)]
+    pub network: bool,
+}
+
+fn default_cpus() -> f64 { 1.0 }
+fn default_memory_mb() -> u64 { 512 }
+fn default_pids() -> i64 { 256 }
+fn default_timeout_secs() -> u64 { 300 }
+
+impl Default for ResourceLimits {
+    fn default() -> Self {
+        Self {
+            cpus: default_cpus(),
+            memory_mb: default_memory_mb(),
+            pids: default_pids(),
+            timeout_secs: default_timeout_secs(),
+            network: false,
+        }
+    }
+}
+
+/// What to run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContainerSpec {
+    pub image: String,
+    #[serde(default)]
+    pub cmd: Vec<String>,
+    #[serde(default)]
+    pub mounts: Vec<Mount>,
+    #[serde(default)]
+    pub limits: ResourceLimits,
+}
+
+/// Result of a container run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContainerOutput {
+    pub exit_code: i64,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Runs containers through the local Docker daemon, within the operator's policy
+#[derive(Debug, Clone)]
+pub struct ContainerRunner {
+    docker: Docker,
+    policy: ContainersConfig,
+}
+
+impl ContainerRunner {
+    /// Connect using `DOCKER_HOST` or the platform's default socket
+    pub fn connect(policy: ContainersConfig) -> Result<Self, NexaError> {
+        let docker = Docker::connect_with_local_defaults()
+            .map_err(|e| NexaError::unavailable(format!("Failed to connect to Docker: {}", e)))?;
+        Ok(Self { docker, policy })
+    }
+
+    /// Pull the image if needed, run the command to completion and remove the container
+    ///
+    /// Specs mounting anything outside the allowed roots are rejected; writable mounts
+    /// and network access are turned off unless the policy allows them.
+    pub async fn run(&self, spec: &ContainerSpec) -> Result<ContainerOutput, NexaError> {
+        let spec = &confine(spec, &self.policy)?;
+        self.pull(&spec.image).await?;
+
+        let name = format!("nexa-{}", uuid::Uuid::new_v4());
+        let container = self.docker
+            .create_container(Some(CreateContainerOptions { name: name.as_str(), platform: None }), container_config(spec))
+            .await
+            .map_err(docker_error)?;
+        debug!("Created container {} for {}", container.id, spec.image);
+
+        let result = self.run_created(&container.id, spec).await;
+        let remove = RemoveContainerOptions { force: true, ..Default::default() };
+        if let Err(e) = self.docker.remove_container(&container.id, Some(remove)).await {
+            warn!("Failed to remove container {}: {}", container.id, e);
+        }
+        result
+    }
+
+    async fn run_created(&self, id: &str, spec: &ContainerSpec) -> Result<ContainerOutput, NexaError> {
+        self.docker.start_container::<String>(id, None).await.map_err(docker_error)?;
+
+        let timeout = Duration::from_secs(spec.limits.timeout_secs.max(1));
+        let mut wait = self.docker.wait_container(id, None::<WaitContainerOptions<String>>);
+        let exit_code = match tokio::time::timeout(timeout, wait.next()).await {
+            Err(_) => {
+                // Removal with force kills it
+                return Err(NexaError::timeout(format!(
+                    "Container {} ran longer than {}s", spec.image, timeout.as_secs()
+                )));
+            }
+            Ok(Some(Ok(response))) => response.status_code,
+            Ok(Some(Err(bollard::errors::Error::DockerContainerWaitError { code, .. }))) => code,
+            Ok(Some(Err(e))) => return Err(docker_error(e)),
+            Ok(None) => return Err(NexaError::system("Docker ended the wait stream without a status")),
+        };
+
+        let mut stdout = String::new();
+        let mut stderr = String::new();
+        let options = LogsOptions::<String> { stdout: true, stderr: true, ..Default::default() };
+        let mut logs = self.docker.logs(id, Some(options));
+        while let Some(chunk) = logs.next().await {
+            match chunk.map_err(docker_error)? {
+                LogOutput::StdErr { message } => stderr.push_str(&String::from_utf8_lossy(&message)),
+                LogOutput::StdOut { message } | LogOutput::Console { message } => {
+                    stdout.push_str(&String::from_utf8_lossy(&message))
+                }
+                LogOutput::StdIn { .. } => {}
+            }
+        }
+
+        info!("Container {} exited with {}", spec.image, exit_code);
+        Ok(ContainerOutput { exit_code, stdout, stderr })
+    }
+
+    async fn pull(&self, image: &str) -> Result<(), NexaError> {
+        if self.docker.inspect_image(image).await.is_ok() {
+            return Ok(());
+        }
+        info!("Pulling image {}", image);
+        let options = CreateImageOptions { from_image: image, ..Default::default() };
+        let mut progress = self.docker.create_image(Some(options), None, None);
+        while let Some(step) = progress.next().await {
+            step.map_err(docker_error)?;
+        }
+        Ok(())
+    }
+}
+
+/// The spec as the policy allows it to run, or why it may not run at all
+fn confine(spec: &ContainerSpec, policy: &ContainersConfig) -> Result<ContainerSpec, NexaError> {
+    let mut spec = spec.clone();
+    for mount in &mut spec.mounts {
+        mount.source = allowed_source(&mount.source, &policy.mount_roots)?;
+        if !mount.read_only && !policy.allow_writable_mounts {
+            warn!("Mounting {} read-only; writable mounts are not allowed", mount.source);
+            mount.read_only = true;
+        }
+    }
+    if spec.limits.network && !policy.allow_network {
+        warn!("Running {} without network; network access is not allowed", spec.image);
+        spec.limits.network = false;
+    }
+    Ok(spec)
+}
+
+/// Resolved path of a mount source lying below one of the roots
+///
+/// Symlinks and `..` are resolved first, so neither can lead out of a root.
+fn allowed_source(source: &str, roots: &[PathBuf]) -> Result<String, NexaError> {
+    let path = Path::new(source);
+    if !path.is_absolute() {
+        return Err(NexaError::invalid_input(format!("Mount source {} must be an absolute path", source)));
+    }
+    let resolved = path.canonicalize()
+        .map_err(|e| NexaError::invalid_input(format!("Mount source {} cannot be resolved: {}", source, e)))?;
+    let allowed = roots.iter()
+        .filter_map(|root| root.canonicalize().ok())
+        .any(|root| resolved.starts_with(root));
+    if !allowed {
+        return Err(NexaError::forbidden(format!(
+            "Mount source {} is outside the allowed mount roots", source
+        )));
+    }
+    Ok(resolved.to_string_lossy().into_owned())
+}
+
+/// Docker container config for a spec
+fn container_config(spec: &ContainerSpec) -> Config<String> {
+    let limits = &spec.limits;
+    let binds = spec.mounts.iter()
+        .map(|m| format!("{}:{}:{}", m.source, m.target, if m.read_only { "ro" } else { "rw" }))
+        .collect();
+
+    Config {
+        image: Some(spec.image.clone()),
+        cmd: (!spec.cmd.is_empty()).then(|| spec.cmd.clone()),
+        attach_stdout: Some(true),
+        attach_stderr: Some(true),
+        labels: Some(HashMap::from([("nexa.managed".to_string(), "true".to_string())])),
+        host_config: Some(HostConfig {
+            nano_cpus: Some((limits.cpus * 1e9) as i64),
+            memory: Some((limits.memory_mb * 1024 * 1024) as i64),
+            // Same as memory: no swap on top of the limit
+            memory_swap: Some((limits.memory_mb * 1024 * 1024) as i64),
+            pids_limit: Some(limits.pids),
+            binds: Some(binds),
+            network_mode: (!limits.network).then(|| "none".to_string()),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+fn docker_error(e: bollard::errors::Error) -> NexaError {
+    NexaError::system(format!("Docker error: {}", e))
+}
+
+/// The `run_container` tool: runs a `ContainerSpec` and returns its `ContainerOutput`
+pub struct ContainerTool {
+    spec: ToolSpec,
+    runner: Arc<ContainerRunner>,
+}
+
+impl ContainerTool {
+    pub fn new(runner: ContainerRunner) -> Self {
+        Self {
+            spec: ToolSpec {
+                name: "run_container".to_string(),
+                description: "Run a command in an isolated Docker container and return its output".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "image": { "type": "string" },
+                        "cmd": { "type": "array", "items": { "type": "string" } },
+                        "mounts": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "source": { "type": "string" },
+                                    "target": { "type": "string" },
+                                    "read_only": { "type": "boolean" },
+                                },
+                                "required": ["source", "target"],
+                            },
+                        },
+                        "limits": { "type": "object" },
+                    },
+                    "required": ["image"],
+                }),
+            },
+            runner: Arc::new(runner),
+        }
+    }
+}
+
+impl Tool for ContainerTool {
+    fn spec(&self) -> &ToolSpec {
+        &self.spec
+    }
+
+    fn call(&self, args: Value) -> BoxFuture<'_, Result<Value, NexaError>> {
+        Box::pin(async move {
+            let spec: ContainerSpec = serde_json::from_value(args)
+                .map_err(|e| NexaError::invalid_input(format!("Invalid container spec: {}", e)))?;
+            Ok(serde_json::to_value(self.runner.run(&spec).await?)?)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_container_config() {
+        let spec: ContainerSpec = serde_json::from_value(json!({
+            "image": "python:3.12-slim",
+            "cmd": ["python", "/work/main.py"],
+            "mounts": [{ "source": "/tmp/agent", "target": "/work" }],
+            "limits": { "cpus": 0.5, "memory_mb": 256 },
+        })).unwrap();
+        assert_eq!(spec.limits.timeout_secs, 300);
+
+        let config = container_config(&spec);
+        let host = config.host_config.unwrap();
+        assert_eq!(host.nano_cpus, Some(500_000_000));
+        assert_eq!(host.memory, Some(256 * 1024 * 1024));
+        assert_eq!(host.binds, Some(vec!["/tmp/agent:/work:ro".to_string()]));
+        assert_eq!(host.network_mode.as_deref(), Some("none"));
+        assert_eq!(config.cmd.unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_container_policy() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path().join("work");
+        std::fs::create_dir_all(root.join("project")).unwrap();
+        std::fs::create_dir_all(temp_dir.path().join("secrets")).unwrap();
+        let policy = ContainersConfig { mount_roots: vec![root.clone()], ..Default::default() };
+        let spec = |source: &str, read_only: bool, network: bool| -> ContainerSpec {
+            serde_json::from_value(json!({
+                "image": "python:3.12-slim",
+                "mounts": [{ "source": source, "target": "/work", "read_only": read_only }],
+                "limits": { "network": network },
+            })).unwrap()
+        };
+        let source = root.join("project").to_string_lossy().into_owned();
+
+        // Below a root it runs, but read-only and without network
+        let confined = confine(&spec(&source, false, true), &policy).unwrap();
+        assert!(confined.mounts[0].read_only);
+        assert!(!confined.limits.network);
+
+        // Outside every root, through `..`, relative or missing: rejected
+        let outside = temp_dir.path().join("secrets").to_string_lossy().into_owned();
+        let escape = root.join("project/../../secrets").to_string_lossy().into_owned();
+        let missing = root.join("missing").to_string_lossy().into_owned();
+        for source in [outside.as_str(), escape.as_str(), "/", "work/project", missing.as_str()] {
+            assert!(confine(&spec(source, true, false), &policy).is_err(), "{} was allowed", source);
+        }
+        #[cfg(unix)]
+        {
+            let link = root.join("link");
+            std::os::unix::fs::symlink(temp_dir.path().join("secrets"), &link).unwrap();
+            assert!(confine(&spec(&link.to_string_lossy(), true, false), &policy).is_err());
+        }
+
+        // No roots, no mounts
+        assert!(confine(&spec(&source, true, false), &ContainersConfig::default()).is_err());
+
+        // Writable mounts and network only when the operator allows them
+        let open = ContainersConfig { allow_writable_mounts: true, allow_network: true, ..policy };
+        let confined = confine(&spec(&source, false, true), &open).unwrap();
+        assert!(!confined.mounts[0].read_only);
+        assert!(confined.limits.network);
+    }
+}
@@ -0,0 +1,114 @@
+//! Daemon Discovery
+//!
+//! Lets clients find a running server without guessing its ports:
+//! - Discovery file written to the runtime directory at startup
+//! - Actual WebSocket and REST API addresses, including ephemeral ports
+//! - Removal on shutdown and detection of files left by dead processes
+
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use nix::libc;
+use crate::error::NexaError;
+use tracing::debug;
+
+/// Name of the discovery file in the runtime directory
+pub const DISCOVERY_FILE: &str = "nexa.discovery";
+
+/// Addresses a running server actually bound to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Discovery {
+    /// PID of the server process
+    pub pid: u32,
+    /// WebSocket (MCP) server address
+    pub ws_addr: SocketAddr,
+    /// REST API address, if the API is enabled
+    pub api_addr: Option<SocketAddr>,
+    /// Config profile the server runs with
+    pub profile: Option<String>,
+    /// Time the server started
+    pub started_at: DateTime<Utc>,
+}
+
+impl Discovery {
+    /// Path of the discovery file in a runtime directory
+    pub fn path(runtime_dir: &Path) -> PathBuf {
+        runtime_dir.join(DISCOVERY_FILE)
+    }
+
+    /// Write the discovery file
+    pub fn write(&self, runtime_dir: &Path) -> Result<(), NexaError> {
+        let path = Self::path(runtime_dir);
+        let tmp = path.with_extension("discovery.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(self)?)
+            .map_err(|e| NexaError::system(format!("Failed to write discovery file: {}", e)))?;
+        fs::rename(&tmp, &path)
+            .map_err(|e| NexaError::system(format!("Failed to replace discovery file: {}", e)))?;
+        debug!("Wrote discovery file {:?}", path);
+        Ok(())
+    }
+
+    /// Read the discovery file of a live server
+    ///
+    /// Files left behind by a process that is no longer running are ignored.
+    pub fn read(runtime_dir: &Path) -> Option<Self> {
+        let contents = fs::read_to_string(Self::path(runtime_dir)).ok()?;
+        let discovery: Self = serde_json::from_str(&contents).ok()?;
+        let alive = discovery.pid == std::process::id()
+            || unsafe { libc::kill(discovery.pid as i32, 0) } == 0;
+        alive.then_some(discovery)
+    }
+
+    /// Remove the discovery file
+    pub fn remove(runtime_dir: &Path) {
+        let _ = fs::remove_file(Self::path(runtime_dir));
+    }
+
+    /// Loopback-reachable URL of the REST API
+    pub fn api_url(&self) -> Option<String> {
+        self.api_addr.map(|addr| format!("http://{}", connectable(addr)))
+    }
+}
+
+/// Map an unspecified bind address (0.0.0.0 / ::) to loopback so clients can connect
+pub fn connectable(mut addr: SocketAddr) -> SocketAddr {
+    if addr.ip().is_unspecified() {
+        match addr {
+            SocketAddr::V4(_) => addr.set_ip(std::net::Ipv4Addr::LOCALHOST.into()),
+            SocketAddr::V6(_) => addr.set_ip(std::net::Ipv6Addr::LOCALHOST.into()),
+        }
+    }
+    addr
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_discovery_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        assert!(Discovery::read(temp_dir.path()).is_none());
+
+        let discovery = Discovery {
+            pid: std::process::id(),
+            ws_addr: "0.0.0.0:41234".parse().unwrap(),
+            api_addr: Some("0.0.0.0:41235".parse().unwrap()),
+            profile: Some("dev".to_string()),
+            started_at: Utc::now(),
+        };
+        discovery.write(temp_dir.path()).unwrap();
+        let read = Discovery::read(temp_dir.path()).unwrap();
+        assert_eq!(read, discovery);
+        assert_eq!(read.api_url().as_deref(), Some("http://127.0.0.1:41235"));
+
+        // Files from dead processes are ignored
+        Discovery { pid: i32::MAX as u32, ..discovery }.write(temp_dir.path()).unwrap();
+        assert!(Discovery::read(temp_dir.path()).is_none());
+
+        Discovery::remove(temp_dir.path());
+        assert!(!Discovery::path(temp_dir.path()).exists());
+    }
+}
@@ -13,17 +13,20 @@ pub enum NexaError {
     Config(String),
     
     #[error("WebSocket error: {0}")]
-    WebSocket(#[from] tokio_tungstenite::tungstenite::Error),
+    WebSocket(#[from] Box<tokio_tungstenite::tungstenite::Error>),
     
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
     
     #[error("YAML error: {0}")]
-    Yaml(String),
+    Yaml(#[from] serde_yaml::Error),
     
     #[error("JSON error: {0}")]
     Json(#[from] serde_json::Error),
 
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+
     #[error("Cluster error: {0}")]
     Cluster(String),
 
@@ -32,6 +35,33 @@ pub enum NexaError {
 
     #[error("Signal handler error: {0}")]
     Signal(String),
+
+    #[error("Service unavailable: {0}")]
+    Unavailable(String),
+
+    #[error("Timed out: {0}")]
+    Timeout(String),
+
+    #[error("Invalid response: {0}")]
+    InvalidResponse(String),
+
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+
+    #[error("Not found: {0}")]
+    NotFound(String),
+
+    #[error("Cancelled: {0}")]
+    Cancelled(String),
+
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
+    #[error("Rate limited: {0}")]
+    RateLimited(String),
 }
 
 impl NexaError {
@@ -51,10 +81,6 @@ impl NexaError {
         Self::Config(msg.into())
     }
 
-    pub fn yaml(msg: impl Into<String>) -> Self {
-        Self::Yaml(msg.into())
-    }
-
     pub fn cluster(msg: impl Into<String>) -> Self {
         Self::Cluster(msg.into())
     }
@@ -66,6 +92,177 @@ impl NexaError {
     pub fn signal<S: Into<String>>(msg: S) -> Self {
         Self::Signal(msg.into())
     }
+
+    pub fn unavailable(msg: impl Into<String>) -> Self {
+        Self::Unavailable(msg.into())
+    }
+
+    pub fn timeout(msg: impl Into<String>) -> Self {
+        Self::Timeout(msg.into())
+    }
+
+    pub fn invalid_response(msg: impl Into<String>) -> Self {
+        Self::InvalidResponse(msg.into())
+    }
+
+    pub fn invalid_input(msg: impl Into<String>) -> Self {
+        Self::InvalidInput(msg.into())
+    }
+
+    pub fn not_found(msg: impl Into<String>) -> Self {
+        Self::NotFound(msg.into())
+    }
+
+    pub fn cancelled(msg: impl Into<String>) -> Self {
+        Self::Cancelled(msg.into())
+    }
+
+    pub fn unauthorized(msg: impl Into<String>) -> Self {
+        Self::Unauthorized(msg.into())
+    }
+
+    pub fn forbidden(msg: impl Into<String>) -> Self {
+        Self::Forbidden(msg.into())
+    }
+
+    pub fn rate_limited(msg: impl Into<String>) -> Self {
+        Self::RateLimited(msg.into())
+    }
+
+    /// Stable machine-readable error code
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Protocol(_) => "protocol",
+            Self::Agent(_) => "agent",
+            Self::System(_) => "system",
+            Self::Config(_) => "config",
+            Self::WebSocket(_) => "websocket",
+            Self::Io(_) => "io",
+            Self::Yaml(_) => "yaml",
+            Self::Json(_) => "json",
+            Self::Http(_) => "http",
+            Self::Cluster(_) => "cluster",
+            Self::Server(_) => "server",
+            Self::Signal(_) => "signal",
+            Self::Unavailable(_) => "unavailable",
+            Self::Timeout(_) => "timeout",
+            Self::InvalidResponse(_) => "invalid_response",
+            Self::InvalidInput(_) => "invalid_input",
+            Self::NotFound(_) => "not_found",
+            Self::Cancelled(_) => "cancelled",
+            Self::Unauthorized(_) => "unauthorized",
+            Self::Forbidden(_) => "forbidden",
+            Self::RateLimited(_) => "rate_limited",
+        }
+    }
+
+    /// Error message without the category prefix
+    pub fn message(&self) -> String {
+        match self {
+            Self::Protocol(msg) | Self::Agent(msg) | Self::System(msg) | Self::Config(msg)
+            | Self::Cluster(msg) | Self::Server(msg) | Self::Signal(msg) | Self::Unavailable(msg)
+            | Self::Timeout(msg) | Self::InvalidResponse(msg) | Self::InvalidInput(msg)
+            | Self::NotFound(msg) | Self::Cancelled(msg) | Self::Unauthorized(msg) | Self::Forbidden(msg)
+            | Self::RateLimited(msg) => msg.clone(),
+            Self::WebSocket(e) => e.to_string(),
+            Self::Io(e) => e.to_string(),
+            Self::Yaml(e) => e.to_string(),
+            Self::Json(e) => e.to_string(),
+            Self::Http(e) => e.to_string(),
+        }
+    }
+
+    /// Rebuild an error from a code and message, e.g. as reported by the API
+    ///
+    /// Codes of wrapped library errors map to `System` since their source is not available.
+    pub fn from_code(code: &str, msg: impl Into<String>) -> Self {
+        let msg = msg.into();
+        match code {
+            "protocol" => Self::Protocol(msg),
+            "agent" => Self::Agent(msg),
+            "config" | "yaml" => Self::Config(msg),
+            "cluster" => Self::Cluster(msg),
+            "server" => Self::Server(msg),
+            "signal" => Self::Signal(msg),
+            "unavailable" => Self::Unavailable(msg),
+            "timeout" => Self::Timeout(msg),
+            "invalid_response" => Self::InvalidResponse(msg),
+            "invalid_input" | "json" => Self::InvalidInput(msg),
+            "not_found" => Self::NotFound(msg),
+            "cancelled" => Self::Cancelled(msg),
+            "unauthorized" => Self::Unauthorized(msg),
+            "forbidden" => Self::Forbidden(msg),
+            "rate_limited" => Self::RateLimited(msg),
+            _ => Self::System(msg),
+        }
+    }
+
+    /// Whether the operation may succeed if retried later
+    pub fn is_retryable(&self) -> bool {
+        use std::io::ErrorKind;
+        use tokio_tungstenite::tungstenite::Error as WsError;
+
+        match self {
+            Self::Unavailable(_) | Self::Timeout(_) | Self::RateLimited(_) => true,
+            Self::Io(e) => matches!(
+                e.kind(),
+                ErrorKind::ConnectionRefused
+                    | ErrorKind::ConnectionReset
+                    | ErrorKind::ConnectionAborted
+                    | ErrorKind::TimedOut
+                    | ErrorKind::Interrupted
+                    | ErrorKind::WouldBlock
+            ),
+            Self::WebSocket(e) => matches!(**e, WsError::ConnectionClosed | WsError::AlreadyClosed | WsError::Io(_)),
+            Self::Http(e) => {
+                e.is_timeout()
+                    || e.is_connect()
+                    || e.status().is_some_and(|s| s.is_server_error() || s.as_u16() == 429)
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the error was caused by invalid input or configuration from the user
+    pub fn is_user_error(&self) -> bool {
+        matches!(
+            self,
+            Self::Config(_) | Self::Yaml(_) | Self::InvalidInput(_) | Self::NotFound(_) | Self::Unauthorized(_) | Self::Forbidden(_)
+        )
+    }
+
+    /// HTTP status code for reporting this error through the API
+    pub fn http_status(&self) -> u16 {
+        match self {
+            Self::NotFound(_) => 404,
+            Self::Unauthorized(_) => 401,
+            Self::Forbidden(_) => 403,
+            Self::RateLimited(_) => 429,
+            Self::Unavailable(_) => 503,
+            Self::Timeout(_) => 504,
+            Self::InvalidResponse(_) => 502,
+            Self::Cancelled(_) => 499, // Client closed request
+            _ if self.is_user_error() => 400,
+            _ => 500,
+        }
+    }
+
+    /// Process exit code for reporting this error from the CLI
+    pub fn exit_code(&self) -> i32 {
+        if self.is_user_error() {
+            2
+        } else if self.is_retryable() {
+            75 // EX_TEMPFAIL
+        } else {
+            1
+        }
+    }
+}
+
+impl From<tokio_tungstenite::tungstenite::Error> for NexaError {
+    fn from(err: tokio_tungstenite::tungstenite::Error) -> Self {
+        Self::WebSocket(Box::new(err))
+    }
 }
 
 impl From<ctrlc::Error> for NexaError {
@@ -74,4 +271,69 @@ impl From<ctrlc::Error> for NexaError {
     }
 }
 
-pub type Result<T> = std::result::Result<T, NexaError>; 
\ No newline at end of file
+pub type Result<T> = std::result::Result<T, NexaError>; 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_classification() {
+        let err = NexaError::unavailable("LLM server down");
+        assert_eq!(err.code(), "unavailable");
+        assert!(err.is_retryable());
+        assert!(!err.is_user_error());
+        assert_eq!(err.http_status(), 503);
+        assert_eq!(err.exit_code(), 75);
+
+        let err = NexaError::config("bad port");
+        assert!(err.is_user_error());
+        assert!(!err.is_retryable());
+        assert_eq!(err.http_status(), 400);
+        assert_eq!(err.exit_code(), 2);
+
+        let err = NexaError::from(std::io::Error::from(std::io::ErrorKind::ConnectionRefused));
+        assert!(err.is_retryable());
+        assert_eq!(NexaError::system("boom").exit_code(), 1);
+
+        let err = NexaError::cancelled("workflow aborted");
+        assert!(!err.is_retryable());
+        assert_eq!(err.http_status(), 499);
+        assert_eq!(err.exit_code(), 1);
+
+        let err = NexaError::unauthorized("invalid token");
+        assert_eq!(err.code(), "unauthorized");
+        assert_eq!(err.http_status(), 401);
+        assert_eq!(err.exit_code(), 2);
+
+        let err = NexaError::forbidden("missing scope");
+        assert_eq!(err.code(), "forbidden");
+        assert_eq!(err.http_status(), 403);
+
+        let err = NexaError::rate_limited("too many requests");
+        assert!(err.is_retryable());
+        assert_eq!(err.http_status(), 429);
+    }
+
+    #[test]
+    fn test_error_code_round_trip() {
+        let err = NexaError::not_found("agent-1");
+        let rebuilt = NexaError::from_code(err.code(), err.message());
+        assert!(matches!(rebuilt, NexaError::NotFound(ref msg) if msg == "agent-1"));
+        assert_eq!(rebuilt.to_string(), err.to_string());
+    }
+
+    #[test]
+    fn test_error_sources_preserved() {
+        use std::error::Error;
+
+        let err = NexaError::from(serde_json::from_str::<u32>("nope").unwrap_err());
+        assert!(err.source().is_some());
+
+        let err = NexaError::from(serde_yaml::from_str::<u32>("[").unwrap_err());
+        assert!(err.source().is_some());
+        assert!(err.is_user_error());
+
+        let err = NexaError::from(tokio_tungstenite::tungstenite::Error::ConnectionClosed);
+        assert!(err.is_retryable());
+    }
+}
@@ -0,0 +1,275 @@
+//! Event Notifications
+//!
+//! Lifecycle events published on a single bus so external systems need not poll:
+//! - Server started and stopped, agent failed or went offline, workflow completed, budget exceeded
+//! - Server state changes, agent status changes, task progress and alerts, for live dashboards
+//! - Pluggable sinks, each filtering the kinds it wants
+//! - Webhook sink posting every event as JSON
+//! - JSONL event log in the runtime directory
+//! - Forwarding to connected WebSocket clients through `EventBus::subscribe`, and on `/api/events`
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::broadcast;
+use crate::config::WebhookConfig;
+use crate::error::NexaError;
+use tracing::{debug, error, warn};
+
+/// Event log file, relative to the runtime directory
+pub const EVENT_LOG_FILE: &str = "events.jsonl";
+
+/// What happened
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    ServerStarted,
+    ServerStopped,
+    AgentFailed,
+    /// An agent stopped answering heartbeats
+    AgentOffline,
+    WorkflowCompleted,
+    BudgetExceeded,
+    /// The server moved between starting, running, maintenance, stopping and stopped
+    ServerStateChanged,
+    /// An agent registered, deregistered or changed status
+    AgentStatusChanged,
+    /// A task was added, assigned or moved on, as the work it belongs to progresses
+    TaskProgress,
+    /// The monitoring system raised an alert
+    Alert,
+}
+
+/// A lifecycle event
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    pub id: String,
+    pub kind: EventKind,
+    pub timestamp: DateTime<Utc>,
+    /// Kind-specific details
+    #[serde(default)]
+    pub data: Value,
+}
+
+impl Event {
+    pub fn new(kind: EventKind, data: Value) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            kind,
+            timestamp: Utc::now(),
+            data,
+        }
+    }
+}
+
+/// A destination for events
+pub trait EventSink: Send + Sync {
+    /// Name used in logs
+    fn name(&self) -> &str;
+
+    /// Whether the sink wants events of this kind
+    fn accepts(&self, _kind: EventKind) -> bool {
+        true
+    }
+
+    /// Deliver one event
+    fn deliver<'a>(&'a self, event: &'a Event) -> BoxFuture<'a, Result<(), NexaError>>;
+}
+
+/// Broadcasts events to subscribers and registered sinks
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    tx: broadcast::Sender<Event>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(1024);
+        Self { tx }
+    }
+
+    /// Publish an event; delivery to sinks happens in the background
+    pub fn publish(&self, kind: EventKind, data: Value) -> Event {
+        let event = Event::new(kind, data);
+        debug!("Publishing {:?} event {}", kind, event.id);
+        // Sending only fails when nobody is subscribed
+        let _ = self.tx.send(event.clone());
+        event
+    }
+
+    /// Receive every event published from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.tx.subscribe()
+    }
+
+    /// Deliver events to a sink until the task is aborted
+    pub fn add_sink(&self, sink: Arc<dyn EventSink>) -> tokio::task::JoinHandle<()> {
+        // Subscribe before spawning so no event published after this call is missed
+        let mut rx = self.subscribe();
+        tokio::spawn(async move {
+            loop {
+                let event = match rx.recv().await {
+                    Ok(event) => event,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("Event sink {} fell behind and skipped {} event(s)", sink.name(), skipped);
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                if !sink.accepts(event.kind) {
+                    continue;
+                }
+                if let Err(e) = sink.deliver(&event).await {
+                    error!("Event sink {} failed to deliver {}: {}", sink.name(), event.id, e);
+                }
+            }
+        })
+    }
+}
+
+/// Appends events as JSON lines to a file
+#[derive(Debug, Clone)]
+pub struct JsonlSink {
+    path: PathBuf,
+}
+
+impl JsonlSink {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl EventSink for JsonlSink {
+    fn name(&self) -> &str {
+        "event-log"
+    }
+
+    fn deliver<'a>(&'a self, event: &'a Event) -> BoxFuture<'a, Result<(), NexaError>> {
+        Box::pin(async move {
+            let line = serde_json::to_string(event)?;
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)
+                .map_err(|e| NexaError::system(format!("Failed to open {:?}: {}", self.path, e)))?;
+            writeln!(file, "{}", line)
+                .map_err(|e| NexaError::system(format!("Failed to write {:?}: {}", self.path, e)))
+        })
+    }
+}
+
+/// Posts events as JSON to an HTTP endpoint
+#[derive(Debug, Clone)]
+pub struct WebhookSink {
+    http: reqwest::Client,
+    url: String,
+    kinds: Vec<EventKind>,
+}
+
+impl WebhookSink {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            http: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap_or_default(),
+            url: url.into(),
+            kinds: Vec::new(),
+        }
+    }
+
+    /// Only deliver these kinds; all kinds when empty
+    pub fn with_kinds(mut self, kinds: Vec<EventKind>) -> Self {
+        self.kinds = kinds;
+        self
+    }
+
+    pub fn from_config(config: &WebhookConfig) -> Self {
+        Self::new(&config.url).with_kinds(config.events.clone())
+    }
+}
+
+impl EventSink for WebhookSink {
+    fn name(&self) -> &str {
+        &self.url
+    }
+
+    fn accepts(&self, kind: EventKind) -> bool {
+        self.kinds.is_empty() || self.kinds.contains(&kind)
+    }
+
+    fn deliver<'a>(&'a self, event: &'a Event) -> BoxFuture<'a, Result<(), NexaError>> {
+        Box::pin(async move {
+            let response = self.http.post(&self.url).json(event).send().await?;
+            if !response.status().is_success() {
+                return Err(NexaError::unavailable(format!(
+                    "Webhook {} returned {}", self.url, response.status()
+                )));
+            }
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::State;
+    use axum::routing::post;
+    use axum::{Json, Router};
+    use parking_lot::Mutex;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_event_sinks() {
+        let received: Arc<Mutex<Vec<Event>>> = Arc::default();
+        async fn hook(State(received): State<Arc<Mutex<Vec<Event>>>>, Json(event): Json<Event>) {
+            received.lock().push(event);
+        }
+        let router = Router::new().route("/hook", post(hook)).with_state(received.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let log_path = temp_dir.path().join(EVENT_LOG_FILE);
+        let bus = EventBus::new();
+        let mut subscriber = bus.subscribe();
+        bus.add_sink(Arc::new(JsonlSink::new(&log_path)));
+        bus.add_sink(Arc::new(
+            WebhookSink::new(format!("http://{}/hook", addr)).with_kinds(vec![EventKind::AgentFailed]),
+        ));
+
+        bus.publish(EventKind::ServerStarted, json!({ "ws_addr": "127.0.0.1:8080" }));
+        let failed = bus.publish(EventKind::AgentFailed, json!({ "agent_id": "agent-1" }));
+        assert_eq!(subscriber.recv().await.unwrap().kind, EventKind::ServerStarted);
+
+        for _ in 0..50 {
+            if received.lock().len() == 1 && std::fs::read_to_string(&log_path).map(|s| s.lines().count()).unwrap_or(0) == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        // The webhook only asked for agent failures; the log gets everything
+        assert_eq!(*received.lock(), vec![failed]);
+        let log = std::fs::read_to_string(&log_path).unwrap();
+        let kinds: Vec<EventKind> = log.lines()
+            .map(|line| serde_json::from_str::<Event>(line).unwrap().kind)
+            .collect();
+        assert_eq!(kinds, vec![EventKind::ServerStarted, EventKind::AgentFailed]);
+    }
+}
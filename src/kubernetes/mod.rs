@@ -0,0 +1,324 @@
+//! Kubernetes Integration
+//!
+//! Support for running replicas in a cluster:
+//! - Leader election through a `coordination.k8s.io/v1` Lease
+//! - In-cluster API access with the pod's service account
+//! - Lease release on shutdown for fast failover
+
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use crate::config::KubernetesConfig;
+use crate::error::NexaError;
+use tracing::{debug, error, info};
+
+/// Where Kubernetes mounts the service account credentials
+pub const SERVICE_ACCOUNT_DIR: &str = "/var/run/secrets/kubernetes.io/serviceaccount";
+
+/// Outcome of one election round
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LeaseAction {
+    Create,
+    Renew,
+    TakeOver,
+    Follow,
+}
+
+/// Competes for a Lease so that only one replica acts as leader
+#[derive(Debug, Clone)]
+pub struct LeaseElector {
+    http: reqwest::Client,
+    api_url: String,
+    token: Option<String>,
+    namespace: String,
+    name: String,
+    identity: String,
+    lease_duration: Duration,
+}
+
+impl LeaseElector {
+    pub fn new(
+        api_url: impl Into<String>,
+        namespace: impl Into<String>,
+        name: impl Into<String>,
+        identity: impl Into<String>,
+    ) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            api_url: api_url.into().trim_end_matches('/').to_string(),
+            token: None,
+            namespace: namespace.into(),
+            name: name.into(),
+            identity: identity.into(),
+            lease_duration: Duration::from_secs(15),
+        }
+    }
+
+    /// Bearer token for the API server
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// How long a lease stays valid without renewal
+    pub fn with_lease_duration(mut self, duration: Duration) -> Self {
+        self.lease_duration = duration;
+        self
+    }
+
+    /// Build an elector from the pod's service account and environment
+    pub fn in_cluster(config: &KubernetesConfig) -> Result<Self, NexaError> {
+        let host = std::env::var("KUBERNETES_SERVICE_HOST")
+            .map_err(|_| NexaError::config("Kubernetes mode is enabled but KUBERNETES_SERVICE_HOST is not set"))?;
+        let port = std::env::var("KUBERNETES_SERVICE_PORT").unwrap_or_else(|_| "443".to_string());
+        let dir = Path::new(SERVICE_ACCOUNT_DIR);
+
+        let read = |file: &str| {
+            fs::read_to_string(dir.join(file))
+                .map(|s| s.trim().to_string())
+                .map_err(|e| NexaError::config(format!("Failed to read service account {}: {}", file, e)))
+        };
+        let token = read("token")?;
+        let namespace = match &config.namespace {
+            Some(namespace) => namespace.clone(),
+            None => read("namespace")?,
+        };
+        let ca = reqwest::Certificate::from_pem(read("ca.crt")?.as_bytes())
+            .map_err(|e| NexaError::config(format!("Invalid service account CA: {}", e)))?;
+        let http = reqwest::Client::builder()
+            .add_root_certificate(ca)
+            .timeout(Duration::from_secs(10))
+            .build()?;
+
+        // Pod names are unique per replica; HOSTNAME defaults to the pod name
+        let identity = std::env::var("POD_NAME")
+            .or_else(|_| std::env::var("HOSTNAME"))
+            .unwrap_or_else(|_| uuid::Uuid::new_v4().to_string());
+
+        let mut elector = Self::new(format!("https://{}:{}", host, port), namespace, &config.lease_name, identity)
+            .with_token(token)
+            .with_lease_duration(Duration::from_secs(config.lease_duration.max(1)));
+        elector.http = http;
+        Ok(elector)
+    }
+
+    /// Identity this replica holds the lease under
+    pub fn identity(&self) -> &str {
+        &self.identity
+    }
+
+    /// Run one election round, returning whether this replica is the leader
+    pub async fn try_acquire_or_renew(&self) -> Result<bool, NexaError> {
+        let current = self.get_lease().await?;
+        let now = Utc::now();
+        let action = decide(current.as_ref(), &self.identity, now);
+        debug!("Lease {} election round: {:?}", self.name, action);
+
+        let transitions = current.as_ref()
+            .and_then(|l| l["spec"]["leaseTransitions"].as_i64())
+            .unwrap_or(0);
+        let (acquire_time, transitions) = match action {
+            LeaseAction::Follow => return Ok(false),
+            LeaseAction::Renew => (
+                current.as_ref().and_then(|l| l["spec"]["acquireTime"].as_str().map(String::from)),
+                transitions,
+            ),
+            LeaseAction::Create | LeaseAction::TakeOver => (None, transitions + i64::from(action == LeaseAction::TakeOver)),
+        };
+
+        let mut lease = json!({
+            "apiVersion": "coordination.k8s.io/v1",
+            "kind": "Lease",
+            "metadata": { "name": self.name, "namespace": self.namespace },
+            "spec": {
+                "holderIdentity": self.identity,
+                "leaseDurationSeconds": self.lease_duration.as_secs(),
+                "acquireTime": acquire_time.unwrap_or_else(|| micro_time(now)),
+                "renewTime": micro_time(now),
+                "leaseTransitions": transitions,
+            },
+        });
+
+        let request = if action == LeaseAction::Create {
+            self.http.post(self.collection_url())
+        } else {
+            // The resourceVersion makes the update fail if another replica got there first
+            lease["metadata"]["resourceVersion"] = current.as_ref()
+                .map(|l| l["metadata"]["resourceVersion"].clone())
+                .unwrap_or(Value::Null);
+            self.http.put(self.lease_url())
+        };
+
+        let response = self.authorize(request).json(&lease).send().await?;
+        match response.status() {
+            status if status.is_success() => {
+                if action != LeaseAction::Renew {
+                    info!("Acquired lease {} as {}", self.name, self.identity);
+                }
+                Ok(true)
+            }
+            StatusCode::CONFLICT => Ok(false),
+            status => Err(NexaError::unavailable(format!(
+                "Lease {} update failed with {}: {}", self.name, status, response.text().await.unwrap_or_default()
+            ))),
+        }
+    }
+
+    /// Give up the lease if this replica holds it
+    pub async fn release(&self) -> Result<(), NexaError> {
+        let Some(mut lease) = self.get_lease().await? else {
+            return Ok(());
+        };
+        if lease["spec"]["holderIdentity"].as_str() != Some(self.identity.as_str()) {
+            return Ok(());
+        }
+        lease["spec"]["holderIdentity"] = Value::Null;
+        let response = self.authorize(self.http.put(self.lease_url())).json(&lease).send().await?;
+        if response.status().is_success() {
+            info!("Released lease {}", self.name);
+        }
+        Ok(())
+    }
+
+    /// Keep competing for the lease, publishing leadership into `leader`
+    pub fn spawn(self, leader: Arc<AtomicBool>, renew_interval: Duration) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(renew_interval);
+            loop {
+                interval.tick().await;
+                let is_leader = match self.try_acquire_or_renew().await {
+                    Ok(is_leader) => is_leader,
+                    Err(e) => {
+                        error!("Leader election failed: {}", e);
+                        false
+                    }
+                };
+                if leader.swap(is_leader, Ordering::SeqCst) != is_leader {
+                    info!("{} is {} the leader", self.identity, if is_leader { "now" } else { "no longer" });
+                }
+            }
+        })
+    }
+
+    async fn get_lease(&self) -> Result<Option<Value>, NexaError> {
+        let response = self.authorize(self.http.get(self.lease_url())).send().await?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            status if status.is_success() => Ok(Some(response.json().await?)),
+            status => Err(NexaError::unavailable(format!("Failed to read lease {}: {}", self.name, status))),
+        }
+    }
+
+    fn authorize(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    fn collection_url(&self) -> String {
+        format!("{}/apis/coordination.k8s.io/v1/namespaces/{}/leases", self.api_url, self.namespace)
+    }
+
+    fn lease_url(&self) -> String {
+        format!("{}/{}", self.collection_url(), self.name)
+    }
+}
+
+fn decide(lease: Option<&Value>, identity: &str, now: DateTime<Utc>) -> LeaseAction {
+    let Some(lease) = lease else {
+        return LeaseAction::Create;
+    };
+    let spec = &lease["spec"];
+    match spec["holderIdentity"].as_str() {
+        Some(holder) if holder == identity => return LeaseAction::Renew,
+        None | Some("") => return LeaseAction::TakeOver,
+        Some(_) => {}
+    }
+
+    let expires = spec["renewTime"].as_str()
+        .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+        .map(|t| t.with_timezone(&Utc) + chrono::Duration::seconds(spec["leaseDurationSeconds"].as_i64().unwrap_or(0)));
+    match expires {
+        Some(expires) if expires > now => LeaseAction::Follow,
+        _ => LeaseAction::TakeOver,
+    }
+}
+
+fn micro_time(time: DateTime<Utc>) -> String {
+    time.format("%Y-%m-%dT%H:%M:%S%.6fZ").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::State;
+    use axum::http::StatusCode as HttpStatus;
+    use axum::routing::{get, post};
+    use axum::{Json, Router};
+    use parking_lot::Mutex;
+
+    type Store = Arc<Mutex<Option<Value>>>;
+
+    /// Minimal Lease API with resourceVersion checks
+    async fn mock_api() -> String {
+        async fn read(State(store): State<Store>) -> Result<Json<Value>, HttpStatus> {
+            store.lock().clone().map(Json).ok_or(HttpStatus::NOT_FOUND)
+        }
+        async fn create(State(store): State<Store>, Json(mut lease): Json<Value>) -> Result<Json<Value>, HttpStatus> {
+            let mut store = store.lock();
+            if store.is_some() {
+                return Err(HttpStatus::CONFLICT);
+            }
+            lease["metadata"]["resourceVersion"] = json!("1");
+            *store = Some(lease.clone());
+            Ok(Json(lease))
+        }
+        async fn replace(State(store): State<Store>, Json(mut lease): Json<Value>) -> Result<Json<Value>, HttpStatus> {
+            let mut store = store.lock();
+            let current = store.as_ref().ok_or(HttpStatus::NOT_FOUND)?;
+            let version = current["metadata"]["resourceVersion"].as_str().unwrap().parse::<u64>().unwrap();
+            if lease["metadata"]["resourceVersion"] != json!(version.to_string()) {
+                return Err(HttpStatus::CONFLICT);
+            }
+            lease["metadata"]["resourceVersion"] = json!((version + 1).to_string());
+            *store = Some(lease.clone());
+            Ok(Json(lease))
+        }
+
+        let path = "/apis/coordination.k8s.io/v1/namespaces/default/leases";
+        let router = Router::new()
+            .route(path, post(create))
+            .route(&format!("{}/:name", path), get(read).put(replace))
+            .with_state(Store::default());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_lease_election() {
+        let api = mock_api().await;
+        let a = LeaseElector::new(&api, "default", "nexa-leader", "pod-a").with_lease_duration(Duration::from_secs(1));
+        let b = LeaseElector::new(&api, "default", "nexa-leader", "pod-b").with_lease_duration(Duration::from_secs(1));
+
+        assert!(a.try_acquire_or_renew().await.unwrap());
+        assert!(!b.try_acquire_or_renew().await.unwrap());
+        assert!(a.try_acquire_or_renew().await.unwrap());
+
+        // An expired lease can be taken over
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        assert!(b.try_acquire_or_renew().await.unwrap());
+        assert!(!a.try_acquire_or_renew().await.unwrap());
+
+        // Releasing hands the lease over immediately
+        b.release().await.unwrap();
+        assert!(a.try_acquire_or_renew().await.unwrap());
+    }
+}
@@ -12,6 +12,22 @@ pub mod tokens;
 pub mod utils;
 pub mod config;
 pub mod llm;
+pub mod secrets;
+pub mod startup;
+pub mod migrations;
+pub mod recovery;
+pub mod discovery;
+pub mod backup;
+pub mod tools;
+pub mod events;
+pub mod kubernetes;
+#[cfg(feature = "plugins")]
+pub mod plugins;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+#[cfg(feature = "containers")]
+pub mod containers;
+pub mod logging;
 
 // Re-export commonly used types
 pub use agent::{Agent, AgentStatus, Task, TaskStatus};
@@ -21,9 +37,11 @@ pub use monitoring::{
     MonitoringSystem, SystemMetrics, SystemAlert, AlertLevel,
     SystemHealth, SystemStatus
 };
-pub use config::Config;
+pub use config::{Config, ConfigService};
 pub use mcp::ServerControl;
 pub use llm::{LLMClient, LLMConfig};
+pub use secrets::SecretStore;
+pub use startup::{StartupManager, CheckStatus, PreflightReport};
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,185 @@
+//! Response cache for completions
+//!
+//! - In-memory LRU with a TTL per entry
+//! - Optional on-disk layer that survives restarts
+//! - Keyed by a hash of the provider, model, generation parameters and prompt
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::debug;
+use super::{system_prompt, turns, ChatMessage, LLMConfig};
+
+/// Caching of completion responses
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+#[schemars(deny_unknown_fields)]
+pub struct CacheConfig {
+    pub enabled: bool,
+    /// Responses kept in memory
+    #[schemars(range(min = 1))]
+    pub capacity: usize,
+    /// Seconds a cached response stays valid
+    pub ttl_secs: u64,
+    /// Directory for cached responses that should outlive the process
+    pub dir: Option<PathBuf>,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            capacity: 1000,
+            ttl_secs: 3600,
+            dir: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    response: String,
+    expires_at: DateTime<Utc>,
+    #[serde(skip)]
+    last_used: u64,
+}
+
+#[derive(Debug, Default)]
+struct Lru {
+    entries: HashMap<String, Entry>,
+    clock: u64,
+}
+
+/// Completion responses shared by clones of an `LLMClient`
+#[derive(Debug, Default)]
+pub(super) struct ResponseCache {
+    lru: Mutex<Lru>,
+}
+
+impl ResponseCache {
+    /// Cached response for `key`, checking memory first and then disk
+    pub async fn get(&self, config: &CacheConfig, key: &str) -> Option<String> {
+        let now = Utc::now();
+        {
+            let mut lru = self.lru.lock();
+            lru.clock += 1;
+            let clock = lru.clock;
+            match lru.entries.get_mut(key) {
+                Some(entry) if entry.expires_at > now => {
+                    entry.last_used = clock;
+                    return Some(entry.response.clone());
+                }
+                Some(_) => {
+                    lru.entries.remove(key);
+                }
+                None => {}
+            }
+        }
+
+        let path = entry_path(config.dir.as_deref()?, key);
+        let entry: Entry = serde_json::from_slice(&tokio::fs::read(&path).await.ok()?).ok()?;
+        if entry.expires_at <= now {
+            let _ = tokio::fs::remove_file(&path).await;
+            return None;
+        }
+        let response = entry.response.clone();
+        self.insert(config, key, entry);
+        Some(response)
+    }
+
+    /// Cache `response` under `key` in memory, and on disk when configured
+    pub async fn put(&self, config: &CacheConfig, key: &str, response: &str) {
+        let entry = Entry {
+            response: response.to_string(),
+            expires_at: Utc::now() + chrono::Duration::seconds(config.ttl_secs.min(i64::MAX as u64) as i64),
+            last_used: 0,
+        };
+        if let Some(dir) = &config.dir {
+            let written = match tokio::fs::create_dir_all(dir).await {
+                Ok(()) => tokio::fs::write(entry_path(dir, key), serde_json::to_vec(&entry).unwrap_or_default()).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = written {
+                debug!("Failed to write cached LLM response to {}: {}", dir.display(), e);
+            }
+        }
+        self.insert(config, key, entry);
+    }
+
+    fn insert(&self, config: &CacheConfig, key: &str, mut entry: Entry) {
+        let mut lru = self.lru.lock();
+        lru.clock += 1;
+        entry.last_used = lru.clock;
+        lru.entries.insert(key.to_string(), entry);
+        while lru.entries.len() > config.capacity.max(1) {
+            let oldest = lru.entries.iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone());
+            match oldest {
+                Some(oldest) => lru.entries.remove(&oldest),
+                None => break,
+            };
+        }
+    }
+}
+
+/// Hash of everything that determines a completion's response
+pub(super) fn cache_key(config: &LLMConfig, messages: &[ChatMessage]) -> String {
+    let request = serde_json::json!({
+        "server_type": config.server_type,
+        "server_url": config.server_url,
+        "model": config.model,
+        "max_tokens": config.max_tokens,
+        "temperature": config.temperature,
+        "top_p": config.top_p,
+        "stop": config.stop,
+        "seed": config.seed,
+        "frequency_penalty": config.frequency_penalty,
+        "presence_penalty": config.presence_penalty,
+        "logit_bias": config.logit_bias,
+        "system": system_prompt(config, messages),
+        "messages": turns(messages).collect::<Vec<_>>(),
+    });
+    hex::encode(Sha256::digest(request.to_string().as_bytes()))
+}
+
+fn entry_path(dir: &Path, key: &str) -> PathBuf {
+    dir.join(format!("{}.json", key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_response_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = CacheConfig { enabled: true, capacity: 2, dir: Some(dir.path().to_path_buf()), ..CacheConfig::default() };
+        let cache = ResponseCache::default();
+        cache.put(&config, "a", "first").await;
+        cache.put(&config, "b", "second").await;
+        assert_eq!(cache.get(&config, "a").await.as_deref(), Some("first"));
+
+        // "b" is least recently used, so it leaves memory but stays on disk
+        cache.put(&config, "c", "third").await;
+        assert!(!cache.lru.lock().entries.contains_key("b"));
+        assert_eq!(cache.get(&config, "b").await.as_deref(), Some("second"));
+        let memory_only = CacheConfig { dir: None, ..config.clone() };
+        assert_eq!(ResponseCache::default().get(&memory_only, "b").await, None);
+
+        // Expired entries are dropped
+        let expired = CacheConfig { ttl_secs: 0, ..config };
+        cache.put(&expired, "d", "fourth").await;
+        assert_eq!(cache.get(&expired, "d").await, None);
+        assert!(!dir.path().join("d.json").exists());
+
+        let llm = LLMConfig::default();
+        let messages = [ChatMessage::user("hello")];
+        assert_eq!(cache_key(&llm, &messages), cache_key(&llm, &messages));
+        assert_ne!(cache_key(&llm, &messages), cache_key(&LLMConfig { temperature: 0.0, ..llm.clone() }, &messages));
+    }
+}
@@ -0,0 +1,104 @@
+//! Multi-turn conversations that keep their history and system prompt
+
+use serde::{Deserialize, Serialize};
+use crate::error::NexaError;
+use super::{ChatMessage, ChatRole, LLMClient};
+
+/// Chat history carried across turns, serializable so it can move between workflow steps
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Conversation {
+    system_prompt: Option<String>,
+    messages: Vec<ChatMessage>,
+}
+
+impl Conversation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use this system prompt instead of the client's configured one
+    pub fn with_system_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.system_prompt = Some(prompt.into());
+        self
+    }
+
+    pub fn system_prompt(&self) -> Option<&str> {
+        self.system_prompt.as_deref()
+    }
+
+    /// User and assistant messages so far, oldest first
+    pub fn history(&self) -> &[ChatMessage] {
+        &self.messages
+    }
+
+    /// Append a message; system messages replace the system prompt
+    pub fn push(&mut self, message: ChatMessage) {
+        match message.role {
+            ChatRole::System => self.system_prompt = Some(message.content),
+            _ => self.messages.push(message),
+        }
+    }
+
+    /// Messages to send, system prompt first
+    pub fn messages(&self) -> Vec<ChatMessage> {
+        self.system_prompt.iter()
+            .map(ChatMessage::system)
+            .chain(self.messages.iter().cloned())
+            .collect()
+    }
+
+    /// Send a user message and record the reply
+    ///
+    /// The history is left unchanged when the request fails, so the turn can be retried.
+    pub async fn send(&mut self, client: &LLMClient, message: ChatMessage) -> Result<String, NexaError> {
+        self.messages.push(message);
+        match client.complete_chat(&self.messages()).await {
+            Ok(reply) => {
+                self.messages.push(ChatMessage::assistant(reply.clone()));
+                Ok(reply)
+            }
+            Err(e) => {
+                self.messages.pop();
+                Err(e)
+            }
+        }
+    }
+
+    /// Forget the history, keeping the system prompt
+    pub fn clear(&mut self) {
+        self.messages.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::{test_utils, LLMConfig};
+
+    #[tokio::test]
+    async fn test_conversation_history() {
+        let (url, recorded) = test_utils::start_recording_server(serde_json::json!({
+            "choices": [{ "message": { "role": "assistant", "content": "Paris" } }]
+        })).await;
+        let client = LLMClient::new(LLMConfig::with_lmstudio_server(url).with_system_prompt("Configured")).unwrap();
+        let mut conversation = Conversation::new().with_system_prompt("Answer in one word.");
+
+        assert_eq!(conversation.send(&client, ChatMessage::user("Capital of France?")).await.unwrap(), "Paris");
+        conversation.send(&client, ChatMessage::user("And of Italy?")).await.unwrap();
+        assert_eq!(conversation.history().len(), 4);
+
+        let messages = recorded.lock()[1].body["messages"].clone();
+        let roles: Vec<&str> = messages.as_array().unwrap().iter().map(|m| m["role"].as_str().unwrap()).collect();
+        assert_eq!(roles, vec!["system", "user", "assistant", "user"]);
+        assert_eq!(messages[0]["content"], "Answer in one word.");
+        assert_eq!(messages[2]["content"], "Paris");
+
+        // A failed turn leaves the history as it was
+        let offline = LLMClient::new(LLMConfig::with_lmstudio_server("http://127.0.0.1:1")).unwrap();
+        assert!(conversation.send(&offline, ChatMessage::user("And of Spain?")).await.is_err());
+        assert_eq!(conversation.history().len(), 4);
+
+        let restored: Conversation = serde_json::from_str(&serde_json::to_string(&conversation).unwrap()).unwrap();
+        assert_eq!(restored, conversation);
+    }
+}
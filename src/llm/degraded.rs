@@ -0,0 +1,392 @@
+//! Degraded Mode
+//!
+//! Keeps the daemon useful while LLM providers are down:
+//! - Failover across the configured provider chain, in order
+//! - Counts of requests served by each provider
+//! - Degraded state when every provider is unreachable
+//! - Queueing of completion requests instead of failing them
+//! - Streamed completions, failing over until a provider yields text
+//! - Automatic reprocessing once a provider passes a health check
+//! - Probe latency and availability of every provider, reported to monitoring
+//! - Warmup requests so the first real task does not pay for loading a model
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use futures::{StreamExt, TryStreamExt};
+use tokio::sync::oneshot;
+use crate::error::NexaError;
+use crate::llm::{CompletionStream, LLMClient, LLMConfig};
+use crate::monitoring::{MonitoringSystem, ProviderHealth};
+use tracing::{debug, info, warn};
+
+/// Availability of the configured LLM providers
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum LLMAvailability {
+    Available,
+    Degraded {
+        since: DateTime<Utc>,
+        reason: String,
+    },
+}
+
+struct QueuedRequest {
+    prompt: String,
+    respond_to: oneshot::Sender<Result<String, NexaError>>,
+}
+
+/// Routes completions across providers and queues them while all are down
+#[derive(Clone)]
+pub struct LLMSupervisor {
+    providers: Arc<Vec<LLMClient>>,
+    availability: Arc<RwLock<LLMAvailability>>,
+    queue: Arc<Mutex<VecDeque<QueuedRequest>>>,
+    drain_lock: Arc<tokio::sync::Mutex<()>>,
+    served: Arc<Mutex<BTreeMap<String, u64>>>,
+    monitoring: Option<Arc<MonitoringSystem>>,
+}
+
+impl std::fmt::Debug for LLMSupervisor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LLMSupervisor")
+            .field("providers", &self.providers.len())
+            .field("availability", &*self.availability.read())
+            .field("queued", &self.queued())
+            .finish()
+    }
+}
+
+impl LLMSupervisor {
+    pub fn new(providers: Vec<LLMClient>) -> Self {
+        Self {
+            providers: Arc::new(providers),
+            availability: Arc::new(RwLock::new(LLMAvailability::Available)),
+            queue: Arc::new(Mutex::new(VecDeque::new())),
+            drain_lock: Arc::new(tokio::sync::Mutex::new(())),
+            served: Arc::new(Mutex::new(BTreeMap::new())),
+            monitoring: None,
+        }
+    }
+
+    /// Report provider health probes to the monitoring system
+    pub fn with_monitoring(mut self, monitoring: Arc<MonitoringSystem>) -> Self {
+        self.monitoring = Some(monitoring);
+        self
+    }
+
+    /// Current provider availability
+    pub fn availability(&self) -> LLMAvailability {
+        self.availability.read().clone()
+    }
+
+    /// Whether all providers are currently unavailable
+    pub fn is_degraded(&self) -> bool {
+        matches!(*self.availability.read(), LLMAvailability::Degraded { .. })
+    }
+
+    /// Number of requests waiting for a provider to recover
+    pub fn queued(&self) -> usize {
+        self.queue.lock().len()
+    }
+
+    /// Completed requests per provider, keyed by `<server type>/<model>`
+    pub fn served_by(&self) -> BTreeMap<String, u64> {
+        self.served.lock().clone()
+    }
+
+    /// Complete a prompt, waiting for a provider to recover if all are down
+    pub async fn complete(&self, prompt: &str) -> Result<String, NexaError> {
+        self.submit(prompt).await
+            .await
+            .map_err(|_| NexaError::system("LLM request was dropped before completion"))?
+    }
+
+    /// Submit a prompt, returning a receiver for the eventual result
+    ///
+    /// While degraded the request is queued and resolved once a provider
+    /// passes a health check; non-retryable errors are returned immediately.
+    pub async fn submit(&self, prompt: &str) -> oneshot::Receiver<Result<String, NexaError>> {
+        let (tx, rx) = oneshot::channel();
+
+        if !self.is_degraded() {
+            match self.try_providers(prompt).await {
+                Ok(response) => {
+                    let _ = tx.send(Ok(response));
+                    return rx;
+                }
+                Err(e) if !e.is_retryable() => {
+                    let _ = tx.send(Err(e));
+                    return rx;
+                }
+                Err(e) => self.enter_degraded(e.to_string()),
+            }
+        }
+
+        self.queue.lock().push_back(QueuedRequest {
+            prompt: prompt.to_string(),
+            respond_to: tx,
+        });
+        debug!("Queued LLM request while degraded ({} waiting)", self.queued());
+        rx
+    }
+
+    /// Complete a prompt, yielding text as the serving provider produces it
+    ///
+    /// Providers failing before they yield anything are failed over as with `complete`.
+    /// Streams are not queued while degraded; they fail as unavailable instead.
+    pub fn complete_stream(&self, prompt: &str) -> CompletionStream {
+        let supervisor = self.clone();
+        let prompt = prompt.to_string();
+        futures::stream::once(async move { supervisor.open_stream(&prompt).await })
+            .try_flatten()
+            .boxed()
+    }
+
+    async fn open_stream(&self, prompt: &str) -> Result<CompletionStream, NexaError> {
+        if let LLMAvailability::Degraded { reason, .. } = self.availability() {
+            return Err(NexaError::unavailable(format!("LLM providers unavailable: {}", reason)));
+        }
+        let mut last_error = NexaError::unavailable("No LLM providers configured");
+        for (index, provider) in self.providers.iter().enumerate() {
+            let mut stream = provider.complete_stream(prompt);
+            match stream.next().await {
+                Some(Err(e)) if e.is_retryable() => {
+                    debug!("LLM provider {} unavailable: {}", provider.config().server_url, e);
+                    last_error = e;
+                }
+                Some(Err(e)) => return Err(e),
+                first => {
+                    let label = provider_label(&provider.config());
+                    if index > 0 {
+                        info!("LLM stream served by fallback provider {}", label);
+                    }
+                    *self.served.lock().entry(label).or_default() += 1;
+                    return Ok(futures::stream::iter(first).chain(stream).boxed());
+                }
+            }
+        }
+        self.enter_degraded(last_error.to_string());
+        Err(last_error)
+    }
+
+    /// Probe every provider, leaving degraded mode and draining the queue on recovery
+    pub async fn check_health(&self) -> bool {
+        let probes = futures::future::join_all(self.providers.iter().map(probe)).await;
+        if let Some(monitoring) = &self.monitoring {
+            for health in &probes {
+                monitoring.record_provider_health(health.clone()).await;
+            }
+        }
+
+        if probes.iter().any(|health| health.available) {
+            self.leave_degraded();
+            self.drain_queue().await;
+            return !self.is_degraded();
+        }
+        if let Some(error) = probes.into_iter().rev().find_map(|health| health.error) {
+            self.enter_degraded(error);
+        }
+        false
+    }
+
+    /// Send every provider a minimal completion so models are loaded before real work arrives
+    pub async fn warmup(&self) {
+        futures::future::join_all(self.providers.iter().map(|provider| async move {
+            let label = provider_label(&provider.config());
+            match provider.warmup().await {
+                Ok(latency) => info!("Warmed up LLM provider {} in {:?}", label, latency),
+                Err(e) => debug!("Warmup of LLM provider {} failed: {}", label, e),
+            }
+        })).await;
+    }
+
+    /// Periodically check provider health until the task is aborted
+    pub fn start_health_checks(&self, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let supervisor = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            loop {
+                interval.tick().await;
+                supervisor.check_health().await;
+            }
+        })
+    }
+
+    async fn try_providers(&self, prompt: &str) -> Result<String, NexaError> {
+        let mut last_error = NexaError::unavailable("No LLM providers configured");
+        for (index, provider) in self.providers.iter().enumerate() {
+            match provider.complete(prompt).await {
+                Ok(response) => {
+                    let label = provider_label(&provider.config());
+                    if index > 0 {
+                        info!("LLM request served by fallback provider {}", label);
+                    }
+                    *self.served.lock().entry(label).or_default() += 1;
+                    return Ok(response);
+                }
+                Err(e) if e.is_retryable() => {
+                    debug!("LLM provider {} unavailable: {}", provider.config().server_url, e);
+                    last_error = e;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Err(last_error)
+    }
+
+    async fn drain_queue(&self) {
+        let _guard = self.drain_lock.lock().await;
+        let mut processed = 0;
+        loop {
+            let request = match self.queue.lock().pop_front() {
+                Some(request) => request,
+                None => break,
+            };
+            match self.try_providers(&request.prompt).await {
+                Err(e) if e.is_retryable() => {
+                    self.queue.lock().push_front(request);
+                    self.enter_degraded(e.to_string());
+                    break;
+                }
+                result => {
+                    let _ = request.respond_to.send(result);
+                    processed += 1;
+                }
+            }
+        }
+        if processed > 0 {
+            info!("Reprocessed {} queued LLM requests", processed);
+        }
+    }
+
+    fn enter_degraded(&self, reason: String) {
+        let mut availability = self.availability.write();
+        if *availability == LLMAvailability::Available {
+            warn!("All LLM providers unavailable, entering degraded mode: {}", reason);
+            *availability = LLMAvailability::Degraded {
+                since: Utc::now(),
+                reason,
+            };
+        }
+    }
+
+    fn leave_degraded(&self) {
+        let mut availability = self.availability.write();
+        if *availability != LLMAvailability::Available {
+            info!("LLM provider recovered, leaving degraded mode");
+            *availability = LLMAvailability::Available;
+        }
+    }
+}
+
+/// Provider name used in metrics, `<server type>/<model>`
+fn provider_label(config: &LLMConfig) -> String {
+    format!("{:?}/{}", config.server_type, config.model)
+}
+
+async fn probe(provider: &LLMClient) -> ProviderHealth {
+    let config = provider.config();
+    let started = std::time::Instant::now();
+    let result = provider.health_check().await;
+    ProviderHealth {
+        provider: provider_label(&config),
+        server_url: config.server_url,
+        available: result.is_ok(),
+        latency_ms: started.elapsed().as_millis() as u64,
+        error: result.err().map(|e| e.to_string()),
+        checked_at: Utc::now(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::test_utils::{start_mock_server, start_streaming_server};
+    use crate::llm::RetryPolicy;
+    use crate::memory::MemoryManager;
+    use crate::tokens::TokenManager;
+
+    #[tokio::test]
+    async fn test_queue_while_degraded() {
+        let client = LLMClient::new(LLMConfig::with_lmstudio_server("http://127.0.0.1:9")).unwrap();
+        let supervisor = LLMSupervisor::new(vec![client.clone()]);
+
+        let rx = supervisor.submit("hello").await;
+        assert!(supervisor.is_degraded());
+        assert_eq!(supervisor.queued(), 1);
+
+        // Still down: the request stays queued
+        assert!(!supervisor.check_health().await);
+        assert_eq!(supervisor.queued(), 1);
+
+        // Provider comes back: the queue is reprocessed
+        let addr = start_mock_server().await;
+        client.update_config(LLMConfig::with_lmstudio_server(&format!("http://{}", addr))).unwrap();
+        assert!(supervisor.check_health().await);
+        assert_eq!(supervisor.availability(), LLMAvailability::Available);
+        assert_eq!(supervisor.queued(), 0);
+
+        let response = rx.await.unwrap().unwrap();
+        assert!(response.contains("mock response"));
+    }
+
+    #[tokio::test]
+    async fn test_failover_chain() {
+        let addr = start_mock_server().await;
+        let primary = LLMConfig {
+            model: "primary".to_string(),
+            retry: RetryPolicy::none(),
+            ..LLMConfig::with_lmstudio_server("http://127.0.0.1:9")
+        };
+        let fallback = LLMConfig {
+            model: "fallback".to_string(),
+            ..LLMConfig::with_lmstudio_server(format!("http://{}", addr))
+        };
+        let supervisor = LLMSupervisor::new(vec![
+            LLMClient::new(primary).unwrap(),
+            LLMClient::new(fallback).unwrap(),
+        ]);
+
+        assert!(supervisor.complete("hello").await.unwrap().contains("mock response"));
+        supervisor.complete("again").await.unwrap();
+        assert!(!supervisor.is_degraded());
+        assert_eq!(supervisor.served_by(), BTreeMap::from([("LMStudio/fallback".to_string(), 2)]));
+
+        // Health checks probe every provider and report to monitoring
+        let memory_manager = Arc::new(MemoryManager::new());
+        let monitoring = Arc::new(MonitoringSystem::new(memory_manager.clone(), Arc::new(TokenManager::new(memory_manager))));
+        let supervisor = supervisor.with_monitoring(monitoring.clone());
+        assert!(supervisor.check_health().await);
+        let health = monitoring.provider_health().await;
+        let providers: Vec<(&str, bool)> = health.iter().map(|h| (h.provider.as_str(), h.available)).collect();
+        assert_eq!(providers, vec![("LMStudio/fallback", true), ("LMStudio/primary", false)]);
+        assert!(health[1].error.is_some());
+        assert_eq!(monitoring.get_recent_alerts(Utc::now() - chrono::Duration::minutes(1)).await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_stream_failover() {
+        let sse = start_streaming_server(concat!(
+            "data: {\"choices\":[{\"delta\":{\"content\":\"Hel\"}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\"lo\"}}]}\n\n",
+            "data: [DONE]\n\n",
+        )).await;
+        let primary = LLMConfig { retry: RetryPolicy::none(), ..LLMConfig::with_lmstudio_server("http://127.0.0.1:9") };
+        let fallback = LLMConfig { model: "fallback".to_string(), ..LLMConfig::with_lmstudio_server(sse) };
+        let supervisor = LLMSupervisor::new(vec![LLMClient::new(primary.clone()).unwrap(), LLMClient::new(fallback).unwrap()]);
+
+        let chunks: Vec<String> = supervisor.complete_stream("hi").try_collect().await.unwrap();
+        assert_eq!(chunks, vec!["Hel", "lo"]);
+        assert_eq!(supervisor.served_by(), BTreeMap::from([("LMStudio/fallback".to_string(), 1)]));
+
+        // With every provider down the stream fails, and the supervisor is degraded
+        let supervisor = LLMSupervisor::new(vec![LLMClient::new(primary).unwrap()]);
+        let mut stream = supervisor.complete_stream("hi");
+        assert!(stream.next().await.unwrap().is_err());
+        assert!(stream.next().await.is_none());
+        assert!(supervisor.is_degraded());
+    }
+}
@@ -0,0 +1,180 @@
+// Narrow C interface over llama.cpp for the `llama` feature.
+//
+// llama.cpp passes its parameter structs by value and changes their layout
+// between releases, so they are only touched here, against the installed
+// llama.h, and Rust sees plain pointers and scalars.
+
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+#include <string.h>
+#include <llama.h>
+
+struct nexa_llama {
+    struct llama_model *model;
+    const struct llama_vocab *vocab;
+};
+
+typedef int (*nexa_llama_piece_cb)(void *user, const char *piece, int32_t len);
+
+struct nexa_llama *nexa_llama_load(const char *path, int32_t n_gpu_layers) {
+    static bool initialized = false;
+    if (!initialized) {
+        llama_backend_init();
+        initialized = true;
+    }
+
+    struct llama_model_params params = llama_model_default_params();
+    params.n_gpu_layers = n_gpu_layers;
+    struct llama_model *model = llama_model_load_from_file(path, params);
+    if (model == NULL) {
+        return NULL;
+    }
+
+    struct nexa_llama *llama = malloc(sizeof(struct nexa_llama));
+    if (llama == NULL) {
+        llama_model_free(model);
+        return NULL;
+    }
+    llama->model = model;
+    llama->vocab = llama_model_get_vocab(model);
+    return llama;
+}
+
+void nexa_llama_free(struct nexa_llama *llama) {
+    if (llama != NULL) {
+        llama_model_free(llama->model);
+        free(llama);
+    }
+}
+
+int32_t nexa_llama_n_ctx_train(const struct nexa_llama *llama) {
+    return llama_model_n_ctx_train(llama->model);
+}
+
+// Render messages with the model's chat template, returning the length the
+// prompt needs (which may exceed `len`) or -1 when the model has no template
+int32_t nexa_llama_chat_prompt(
+    const struct nexa_llama *llama,
+    const char **roles,
+    const char **contents,
+    size_t n_messages,
+    char *buf,
+    int32_t len
+) {
+    const char *tmpl = llama_model_chat_template(llama->model, NULL);
+    if (tmpl == NULL) {
+        return -1;
+    }
+
+    struct llama_chat_message *messages = malloc(sizeof(struct llama_chat_message) * (n_messages ? n_messages : 1));
+    if (messages == NULL) {
+        return -1;
+    }
+    for (size_t i = 0; i < n_messages; i++) {
+        messages[i].role = roles[i];
+        messages[i].content = contents[i];
+    }
+    int32_t needed = llama_chat_apply_template(tmpl, messages, n_messages, true, buf, len);
+    free(messages);
+    return needed;
+}
+
+// Tokenize `text` into a freshly allocated array, returning the token count or -1
+static int32_t tokenize(const struct nexa_llama *llama, const char *text, llama_token **tokens) {
+    int32_t text_len = (int32_t)strlen(text);
+    int32_t n = -llama_tokenize(llama->vocab, text, text_len, NULL, 0, true, true);
+    *tokens = malloc(sizeof(llama_token) * (n > 0 ? n : 1));
+    if (*tokens == NULL) {
+        return -1;
+    }
+    if (llama_tokenize(llama->vocab, text, text_len, *tokens, n, true, true) < 0) {
+        free(*tokens);
+        return -1;
+    }
+    return n;
+}
+
+int32_t nexa_llama_count_tokens(const struct nexa_llama *llama, const char *text) {
+    llama_token *tokens = NULL;
+    int32_t n = tokenize(llama, text, &tokens);
+    free(tokens);
+    return n;
+}
+
+// Generate up to `max_tokens` tokens after `prompt`, passing each piece of
+// text to `cb` until it returns non-zero. Returns the number of tokens
+// generated, or a negative error:
+//   -1 tokenization failed, -2 the prompt does not fit in `n_ctx`,
+//   -3 context creation failed, -4 decoding failed
+int32_t nexa_llama_generate(
+    const struct nexa_llama *llama,
+    const char *prompt,
+    uint32_t n_ctx,
+    int32_t max_tokens,
+    float temperature,
+    float top_p,
+    uint32_t seed,
+    nexa_llama_piece_cb cb,
+    void *user
+) {
+    llama_token *tokens = NULL;
+    int32_t n_prompt = tokenize(llama, prompt, &tokens);
+    if (n_prompt < 0) {
+        return -1;
+    }
+    if ((uint32_t)n_prompt >= n_ctx) {
+        free(tokens);
+        return -2;
+    }
+
+    struct llama_context_params ctx_params = llama_context_default_params();
+    ctx_params.n_ctx = n_ctx;
+    ctx_params.n_batch = (uint32_t)n_prompt > ctx_params.n_batch ? (uint32_t)n_prompt : ctx_params.n_batch;
+    ctx_params.no_perf = true;
+    struct llama_context *ctx = llama_init_from_model(llama->model, ctx_params);
+    if (ctx == NULL) {
+        free(tokens);
+        return -3;
+    }
+
+    struct llama_sampler_chain_params chain_params = llama_sampler_chain_default_params();
+    chain_params.no_perf = true;
+    struct llama_sampler *sampler = llama_sampler_chain_init(chain_params);
+    if (temperature <= 0.0f) {
+        llama_sampler_chain_add(sampler, llama_sampler_init_greedy());
+    } else {
+        llama_sampler_chain_add(sampler, llama_sampler_init_top_p(top_p, 1));
+        llama_sampler_chain_add(sampler, llama_sampler_init_temp(temperature));
+        llama_sampler_chain_add(sampler, llama_sampler_init_dist(seed));
+    }
+
+    int32_t generated = 0;
+    struct llama_batch batch = llama_batch_get_one(tokens, n_prompt);
+    llama_token token;
+    char piece[256];
+    while (generated < max_tokens) {
+        if (llama_decode(ctx, batch) != 0) {
+            generated = -4;
+            break;
+        }
+        token = llama_sampler_sample(sampler, ctx, -1);
+        if (llama_vocab_is_eog(llama->vocab, token)) {
+            break;
+        }
+        generated++;
+        int32_t len = llama_token_to_piece(llama->vocab, token, piece, sizeof(piece), 0, true);
+        if (len > 0 && cb(user, piece, len) != 0) {
+            break;
+        }
+        if ((uint32_t)(n_prompt + generated) >= n_ctx) {
+            break;
+        }
+        batch = llama_batch_get_one(&token, 1);
+    }
+
+    llama_sampler_free(sampler);
+    llama_free(ctx);
+    free(tokens);
+    return generated;
+}
@@ -0,0 +1,225 @@
+//! In-process GGUF models through llama.cpp (`llama` feature)
+//!
+//! - `llm.model` is the path of a GGUF file and `server_url` is unused
+//! - A model is loaded once per path and shared by every client
+//! - Generation runs on the blocking thread pool, one llama.cpp context per request
+
+use std::collections::HashMap;
+use std::ffi::{c_char, c_int, c_void, CString};
+use std::path::{Path, PathBuf};
+use std::ptr::NonNull;
+use std::sync::{Arc, OnceLock};
+use parking_lot::Mutex;
+use tracing::info;
+use crate::error::NexaError;
+use super::{system_prompt, turns, ChatMessage, LLMConfig};
+
+/// Context size used when `llm.context_window` is unset, unless the model was trained on less
+const DEFAULT_CONTEXT: usize = 4096;
+
+mod ffi {
+    use std::ffi::{c_char, c_int, c_void};
+
+    #[repr(C)]
+    pub struct NexaLlama {
+        _private: [u8; 0],
+    }
+
+    pub type PieceCallback = unsafe extern "C" fn(user: *mut c_void, piece: *const c_char, len: i32) -> c_int;
+
+    extern "C" {
+        pub fn nexa_llama_load(path: *const c_char, n_gpu_layers: i32) -> *mut NexaLlama;
+        pub fn nexa_llama_free(llama: *mut NexaLlama);
+        pub fn nexa_llama_n_ctx_train(llama: *const NexaLlama) -> i32;
+        pub fn nexa_llama_chat_prompt(
+            llama: *const NexaLlama,
+            roles: *const *const c_char,
+            contents: *const *const c_char,
+            n_messages: usize,
+            buf: *mut c_char,
+            len: i32,
+        ) -> i32;
+        pub fn nexa_llama_count_tokens(llama: *const NexaLlama, text: *const c_char) -> i32;
+        pub fn nexa_llama_generate(
+            llama: *const NexaLlama,
+            prompt: *const c_char,
+            n_ctx: u32,
+            max_tokens: i32,
+            temperature: f32,
+            top_p: f32,
+            seed: u32,
+            cb: PieceCallback,
+            user: *mut c_void,
+        ) -> i32;
+    }
+}
+
+/// A GGUF model loaded into this process
+pub struct LlamaModel {
+    ptr: NonNull<ffi::NexaLlama>,
+    path: PathBuf,
+}
+
+// llama.cpp models are immutable once loaded; each generation gets its own context
+unsafe impl Send for LlamaModel {}
+unsafe impl Sync for LlamaModel {}
+
+impl std::fmt::Debug for LlamaModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LlamaModel").field("path", &self.path).finish_non_exhaustive()
+    }
+}
+
+impl Drop for LlamaModel {
+    fn drop(&mut self) {
+        unsafe { ffi::nexa_llama_free(self.ptr.as_ptr()) }
+    }
+}
+
+impl LlamaModel {
+    /// Load a GGUF file, offloading `gpu_layers` layers to the GPU
+    pub fn load(path: &Path, gpu_layers: u32) -> Result<Self, NexaError> {
+        if !path.is_file() {
+            return Err(NexaError::not_found(format!("GGUF model {} does not exist", path.display())));
+        }
+        let c_path = CString::new(path.to_string_lossy().as_bytes())
+            .map_err(|_| NexaError::invalid_input(format!("Invalid model path {}", path.display())))?;
+        let ptr = unsafe { ffi::nexa_llama_load(c_path.as_ptr(), gpu_layers.min(i32::MAX as u32) as i32) };
+        let ptr = NonNull::new(ptr)
+            .ok_or_else(|| NexaError::system(format!("llama.cpp failed to load {}", path.display())))?;
+        Ok(Self { ptr, path: path.to_path_buf() })
+    }
+
+    /// Path of the GGUF file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Context length the model was trained with
+    pub fn trained_context(&self) -> usize {
+        unsafe { ffi::nexa_llama_n_ctx_train(self.ptr.as_ptr()) }.max(0) as usize
+    }
+
+    /// Tokens in `text` by the model's own vocabulary
+    pub fn count_tokens(&self, text: &str) -> Result<usize, NexaError> {
+        let text = c_string(text)?;
+        let n = unsafe { ffi::nexa_llama_count_tokens(self.ptr.as_ptr(), text.as_ptr()) };
+        usize::try_from(n).map_err(|_| NexaError::invalid_input("llama.cpp failed to tokenize the prompt"))
+    }
+
+    /// Render `(role, content)` pairs with the model's chat template
+    ///
+    /// Models without a template get plain `role: content` lines.
+    pub fn chat_prompt(&self, messages: &[(&str, &str)]) -> Result<String, NexaError> {
+        let roles = messages.iter().map(|(role, _)| c_string(role)).collect::<Result<Vec<_>, _>>()?;
+        let contents = messages.iter().map(|(_, content)| c_string(content)).collect::<Result<Vec<_>, _>>()?;
+        let role_ptrs: Vec<*const c_char> = roles.iter().map(|s| s.as_ptr()).collect();
+        let content_ptrs: Vec<*const c_char> = contents.iter().map(|s| s.as_ptr()).collect();
+
+        let mut buf = vec![0u8; messages.iter().map(|(_, c)| c.len() + 64).sum::<usize>().max(256)];
+        loop {
+            let needed = unsafe {
+                ffi::nexa_llama_chat_prompt(
+                    self.ptr.as_ptr(),
+                    role_ptrs.as_ptr(),
+                    content_ptrs.as_ptr(),
+                    messages.len(),
+                    buf.as_mut_ptr() as *mut c_char,
+                    buf.len().min(i32::MAX as usize) as i32,
+                )
+            };
+            let Ok(needed) = usize::try_from(needed) else {
+                let mut prompt: String = messages.iter().map(|(role, content)| format!("{}: {}\n", role, content)).collect();
+                prompt.push_str("assistant:");
+                return Ok(prompt);
+            };
+            if needed <= buf.len() {
+                buf.truncate(needed);
+                return Ok(String::from_utf8_lossy(&buf).into_owned());
+            }
+            buf.resize(needed, 0);
+        }
+    }
+
+    /// Generate up to `max_tokens` tokens after `prompt`, within an `n_ctx`-token context
+    pub fn generate(&self, prompt: &str, n_ctx: usize, config: &LLMConfig) -> Result<String, NexaError> {
+        unsafe extern "C" fn collect(user: *mut c_void, piece: *const c_char, len: i32) -> c_int {
+            let out = &mut *(user as *mut Vec<u8>);
+            out.extend_from_slice(std::slice::from_raw_parts(piece as *const u8, len.max(0) as usize));
+            0
+        }
+
+        let prompt = c_string(prompt)?;
+        // Pieces can split multi-byte characters, so text is decoded once at the end
+        let mut out: Vec<u8> = Vec::new();
+        let seed = config.seed.map(|seed| seed as u32).unwrap_or(u32::MAX);
+        let result = unsafe {
+            ffi::nexa_llama_generate(
+                self.ptr.as_ptr(),
+                prompt.as_ptr(),
+                n_ctx.min(u32::MAX as usize) as u32,
+                config.max_tokens.min(i32::MAX as usize) as i32,
+                config.temperature,
+                config.top_p,
+                seed,
+                collect,
+                &mut out as *mut Vec<u8> as *mut c_void,
+            )
+        };
+        match result {
+            -1 => Err(NexaError::invalid_input("llama.cpp failed to tokenize the prompt")),
+            -2 => Err(NexaError::invalid_input(format!("Prompt does not fit the {}-token context", n_ctx))),
+            -3 => Err(NexaError::system("llama.cpp failed to create a context")),
+            n if n < 0 => Err(NexaError::system("llama.cpp failed to decode")),
+            _ => {
+                let mut text = String::from_utf8_lossy(&out).into_owned();
+                if let Some(end) = config.stop.iter().filter_map(|stop| text.find(stop.as_str())).min() {
+                    text.truncate(end);
+                }
+                Ok(text)
+            }
+        }
+    }
+}
+
+fn c_string(text: &str) -> Result<CString, NexaError> {
+    CString::new(text).map_err(|_| NexaError::invalid_input("Text sent to llama.cpp contains a NUL byte"))
+}
+
+static MODELS: OnceLock<Mutex<HashMap<PathBuf, Arc<LlamaModel>>>> = OnceLock::new();
+
+/// The model at `path`, loading it on first use
+pub fn shared_model(path: &Path, gpu_layers: u32) -> Result<Arc<LlamaModel>, NexaError> {
+    let mut models = MODELS.get_or_init(Default::default).lock();
+    if let Some(model) = models.get(path) {
+        return Ok(model.clone());
+    }
+    let started = std::time::Instant::now();
+    let model = Arc::new(LlamaModel::load(path, gpu_layers)?);
+    info!("Loaded {} in {:?}", path.display(), started.elapsed());
+    models.insert(path.to_path_buf(), model.clone());
+    Ok(model)
+}
+
+/// Complete a chat with the model named by `config.model`
+pub(super) async fn complete(config: &LLMConfig, messages: &[ChatMessage]) -> Result<String, NexaError> {
+    if messages.iter().any(|m| !m.images.is_empty()) {
+        return Err(NexaError::invalid_input("Image input is not supported for embedded models"));
+    }
+    let system = system_prompt(config, messages);
+    let chat: Vec<(String, String)> = system.into_iter()
+        .map(|content| ("system".to_string(), content))
+        .chain(turns(messages).map(|m| (m.role.as_str().to_string(), m.content.clone())))
+        .collect();
+    let config = config.clone();
+
+    tokio::task::spawn_blocking(move || {
+        let model = shared_model(Path::new(&config.model), config.gpu_layers)?;
+        let chat: Vec<(&str, &str)> = chat.iter().map(|(role, content)| (role.as_str(), content.as_str())).collect();
+        let prompt = model.chat_prompt(&chat)?;
+        let n_ctx = config.context_window.unwrap_or_else(|| DEFAULT_CONTEXT.min(model.trained_context().max(1)));
+        model.generate(&prompt, n_ctx, &config)
+    })
+    .await
+    .map_err(|e| NexaError::system(format!("Embedded generation panicked: {}", e)))?
+}
@@ -0,0 +1,345 @@
+//! Guardrails screening prompts before they are sent and responses before they are returned
+//!
+//! - Regex filters that block or redact matching text
+//! - PII redaction of emails, phone numbers, card and social security numbers
+//! - Provider moderation endpoints (OpenAI-style `/v1/moderations`)
+
+use std::sync::Arc;
+use std::time::Duration;
+use futures::future::BoxFuture;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use crate::error::NexaError;
+use super::{http_pool, request_error, status_error, ChatMessage, ChatRole, LLMClient, LLMConfig};
+
+/// When a guardrail runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    /// On user messages, before the completion is requested
+    Prompt,
+    /// On the model's reply, before it is returned
+    Response,
+}
+
+/// Outcome of screening a text
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    Allow,
+    /// Continue with this text instead
+    Redact(String),
+    /// Stop, for this reason
+    Block(String),
+}
+
+/// A check run on prompts, responses or both
+pub trait Guardrail: Send + Sync {
+    /// Name used in errors and logs
+    fn name(&self) -> &str;
+
+    /// Whether the guardrail runs at this stage
+    fn applies_to(&self, _stage: Stage) -> bool {
+        true
+    }
+
+    /// Screen one text
+    fn check<'a>(&'a self, stage: Stage, text: &'a str) -> BoxFuture<'a, Result<Verdict, NexaError>>;
+}
+
+/// What a regex guardrail does with a match
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RegexAction {
+    #[default]
+    Block,
+    /// Replace each match with this text
+    Redact(String),
+}
+
+/// Blocks or redacts text matching a pattern
+#[derive(Debug, Clone)]
+pub struct RegexGuardrail {
+    name: String,
+    patterns: Vec<Regex>,
+    action: RegexAction,
+    stages: Vec<Stage>,
+}
+
+impl RegexGuardrail {
+    pub fn new(name: impl Into<String>, pattern: &str, action: RegexAction) -> Result<Self, NexaError> {
+        let pattern = Regex::new(pattern)
+            .map_err(|e| NexaError::config(format!("Invalid guardrail pattern '{}': {}", pattern, e)))?;
+        Ok(Self { name: name.into(), patterns: vec![pattern], action, stages: Vec::new() })
+    }
+
+    /// Redacts emails, phone numbers, payment card numbers and US social security numbers
+    pub fn pii() -> Self {
+        let patterns = [
+            r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}",
+            r"\b\d{3}-\d{2}-\d{4}\b",
+            r"\b(?:\d[ -]?){13,19}\b",
+            r"\+?\d{1,3}[ .-]?\(?\d{2,4}\)?[ .-]?\d{3,4}[ .-]?\d{3,4}\b",
+        ];
+        Self {
+            name: "pii".to_string(),
+            patterns: patterns.iter().map(|p| Regex::new(p).expect("valid PII pattern")).collect(),
+            action: RegexAction::Redact("[REDACTED]".to_string()),
+            stages: Vec::new(),
+        }
+    }
+
+    /// Run only at these stages (all stages when empty)
+    pub fn at(mut self, stages: impl IntoIterator<Item = Stage>) -> Self {
+        self.stages = stages.into_iter().collect();
+        self
+    }
+}
+
+impl Guardrail for RegexGuardrail {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn applies_to(&self, stage: Stage) -> bool {
+        self.stages.is_empty() || self.stages.contains(&stage)
+    }
+
+    fn check<'a>(&'a self, _stage: Stage, text: &'a str) -> BoxFuture<'a, Result<Verdict, NexaError>> {
+        let verdict = match &self.action {
+            RegexAction::Block => match self.patterns.iter().find(|p| p.is_match(text)) {
+                Some(pattern) => Verdict::Block(format!("matched {}", pattern.as_str())),
+                None => Verdict::Allow,
+            },
+            RegexAction::Redact(replacement) => {
+                let redacted = self.patterns.iter().fold(text.to_string(), |text, pattern| {
+                    pattern.replace_all(&text, replacement.as_str()).into_owned()
+                });
+                if redacted == text { Verdict::Allow } else { Verdict::Redact(redacted) }
+            }
+        };
+        Box::pin(async move { Ok(verdict) })
+    }
+}
+
+/// Blocks text an OpenAI-style moderation endpoint flags
+#[derive(Debug, Clone)]
+pub struct ModerationGuardrail {
+    config: LLMConfig,
+    stages: Vec<Stage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModerationResponse {
+    #[serde(default)]
+    results: Vec<ModerationResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModerationResult {
+    flagged: bool,
+    #[serde(default)]
+    categories: std::collections::BTreeMap<String, bool>,
+}
+
+impl ModerationGuardrail {
+    /// Moderate with the provider in `config`, posting to `<server_url>/v1/moderations`
+    pub fn new(config: LLMConfig) -> Self {
+        Self { config, stages: Vec::new() }
+    }
+
+    /// Run only at these stages (all stages when empty)
+    pub fn at(mut self, stages: impl IntoIterator<Item = Stage>) -> Self {
+        self.stages = stages.into_iter().collect();
+        self
+    }
+
+    async fn moderate(&self, text: &str) -> Result<Verdict, NexaError> {
+        let response = http_pool::shared_client(&self.config.server_url)?
+            .post(format!("{}/v1/moderations", self.config.server_url))
+            .headers(LLMClient::build_headers(&self.config)?)
+            .timeout(Duration::from_secs(self.config.timeout_secs))
+            .json(&serde_json::json!({ "input": text }))
+            .send()
+            .await
+            .map_err(|e| request_error("Moderation request failed", e))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(status_error("Moderation request failed", status, text));
+        }
+        let body: ModerationResponse = response.json()
+            .await
+            .map_err(|e| NexaError::invalid_response(format!("Failed to parse moderation result: {}", e)))?;
+        Ok(match body.results.into_iter().find(|r| r.flagged) {
+            Some(result) => {
+                let categories: Vec<String> = result.categories.into_iter()
+                    .filter(|(_, flagged)| *flagged)
+                    .map(|(category, _)| category)
+                    .collect();
+                Verdict::Block(format!("flagged for {}", categories.join(", ")))
+            }
+            None => Verdict::Allow,
+        })
+    }
+}
+
+impl Guardrail for ModerationGuardrail {
+    fn name(&self) -> &str {
+        "moderation"
+    }
+
+    fn applies_to(&self, stage: Stage) -> bool {
+        self.stages.is_empty() || self.stages.contains(&stage)
+    }
+
+    fn check<'a>(&'a self, _stage: Stage, text: &'a str) -> BoxFuture<'a, Result<Verdict, NexaError>> {
+        Box::pin(self.moderate(text))
+    }
+}
+
+/// A guardrail as configured for an agent
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum GuardrailConfig {
+    /// Block or redact matches of a regular expression
+    Regex {
+        pattern: String,
+        #[serde(default)]
+        action: RegexAction,
+        /// Stages to run at; all when empty
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        stages: Vec<Stage>,
+    },
+    /// Redact personal data
+    Pii {
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        stages: Vec<Stage>,
+    },
+    /// Block text flagged by a provider's moderation endpoint
+    Moderation {
+        provider: Box<LLMConfig>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        stages: Vec<Stage>,
+    },
+}
+
+impl GuardrailConfig {
+    pub fn build(&self) -> Result<Arc<dyn Guardrail>, NexaError> {
+        Ok(match self {
+            Self::Regex { pattern, action, stages } => {
+                Arc::new(RegexGuardrail::new(format!("regex {}", pattern), pattern, action.clone())?.at(stages.clone()))
+            }
+            Self::Pii { stages } => Arc::new(RegexGuardrail::pii().at(stages.clone())),
+            Self::Moderation { provider, stages } => Arc::new(ModerationGuardrail::new((**provider).clone()).at(stages.clone())),
+        })
+    }
+}
+
+/// Guardrails run in order around a completion
+#[derive(Clone, Default)]
+pub struct Guardrails {
+    guardrails: Vec<Arc<dyn Guardrail>>,
+}
+
+impl std::fmt::Debug for Guardrails {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Guardrails")
+            .field("guardrails", &self.guardrails.iter().map(|g| g.name()).collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Guardrails {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_configs(configs: &[GuardrailConfig]) -> Result<Self, NexaError> {
+        Ok(Self { guardrails: configs.iter().map(GuardrailConfig::build).collect::<Result<_, _>>()? })
+    }
+
+    pub fn with(mut self, guardrail: Arc<dyn Guardrail>) -> Self {
+        self.guardrails.push(guardrail);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.guardrails.is_empty()
+    }
+
+    /// Run every guardrail for `stage` over `text`, returning the possibly redacted text
+    pub async fn screen(&self, stage: Stage, text: &str) -> Result<String, NexaError> {
+        let mut text = text.to_string();
+        for guardrail in self.guardrails.iter().filter(|g| g.applies_to(stage)) {
+            match guardrail.check(stage, &text).await? {
+                Verdict::Allow => {}
+                Verdict::Redact(redacted) => text = redacted,
+                Verdict::Block(reason) => {
+                    warn!("Guardrail '{}' blocked a {:?}: {}", guardrail.name(), stage, reason);
+                    let msg = format!("Blocked by guardrail '{}': {}", guardrail.name(), reason);
+                    return Err(match stage {
+                        Stage::Prompt => NexaError::invalid_input(msg),
+                        Stage::Response => NexaError::invalid_response(msg),
+                    });
+                }
+            }
+        }
+        Ok(text)
+    }
+
+    /// Screen the user messages of a chat
+    pub async fn screen_prompt(&self, messages: &[ChatMessage]) -> Result<Vec<ChatMessage>, NexaError> {
+        let mut screened = messages.to_vec();
+        for message in screened.iter_mut().filter(|m| m.role == ChatRole::User) {
+            message.content = self.screen(Stage::Prompt, &message.content).await?;
+        }
+        Ok(screened)
+    }
+
+    /// Screen a model's reply
+    pub async fn screen_response(&self, response: &str) -> Result<String, NexaError> {
+        self.screen(Stage::Response, response).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::test_utils;
+
+    #[tokio::test]
+    async fn test_regex_and_pii() {
+        let guardrails = Guardrails::from_configs(&[
+            GuardrailConfig::Pii { stages: vec![] },
+            GuardrailConfig::Regex {
+                pattern: "(?i)rm -rf".to_string(),
+                action: RegexAction::Block,
+                stages: vec![Stage::Response],
+            },
+        ]).unwrap();
+
+        let prompt = guardrails.screen_prompt(&[ChatMessage::user("Mail jane.doe@example.com or call 555-123-4567")]).await.unwrap();
+        assert_eq!(prompt[0].content, "Mail [REDACTED] or call [REDACTED]");
+
+        // The block pattern only runs on responses
+        assert!(guardrails.screen(Stage::Prompt, "how does rm -rf work").await.is_ok());
+        let err = guardrails.screen_response("Run RM -RF / now").await.unwrap_err();
+        assert!(matches!(err, NexaError::InvalidResponse(_)));
+    }
+
+    #[tokio::test]
+    async fn test_moderation() {
+        let (url, recorded) = test_utils::start_recording_server(serde_json::json!({
+            "results": [{ "flagged": true, "categories": { "violence": true, "hate": false } }]
+        })).await;
+        let guardrails = Guardrails::new()
+            .with(Arc::new(ModerationGuardrail::new(LLMConfig::with_openai_compatible(url, "omni-moderation-latest"))));
+
+        let err = guardrails.screen(Stage::Prompt, "something violent").await.unwrap_err();
+        assert!(err.to_string().contains("flagged for violence"));
+        assert_eq!(recorded.lock()[0].path, "/v1/moderations");
+        assert_eq!(recorded.lock()[0].body["input"], "something violent");
+    }
+}
@@ -0,0 +1,60 @@
+//! Shared HTTP clients with one connection pool per endpoint
+//!
+//! - Clients are keyed by the origin (scheme, host and port) of a base URL
+//! - Every `LLMClient` talking to the same server reuses its connections
+//! - Credentials and timeouts are applied per request, never baked into a pooled client
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::time::Duration;
+use parking_lot::Mutex;
+use reqwest::Client;
+use crate::error::NexaError;
+
+static CLIENTS: OnceLock<Mutex<HashMap<String, Client>>> = OnceLock::new();
+
+/// Pooled client for the endpoint serving `url`
+pub fn shared_client(url: &str) -> Result<Client, NexaError> {
+    let key = origin(url);
+    let mut clients = CLIENTS.get_or_init(Default::default).lock();
+    if let Some(client) = clients.get(&key) {
+        return Ok(client.clone());
+    }
+    let client = Client::builder()
+        .pool_max_idle_per_host(32)
+        .pool_idle_timeout(Duration::from_secs(90))
+        .tcp_keepalive(Duration::from_secs(60))
+        .build()
+        .map_err(NexaError::from)?;
+    clients.insert(key, client.clone());
+    Ok(client)
+}
+
+/// Number of endpoints with a pooled client
+pub fn pooled_endpoints() -> usize {
+    CLIENTS.get().map(|clients| clients.lock().len()).unwrap_or(0)
+}
+
+fn origin(url: &str) -> String {
+    match url::Url::parse(url) {
+        Ok(url) => url.origin().ascii_serialization(),
+        Err(_) => url.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_origin() {
+        assert_eq!(origin("http://localhost:1234/v1/chat/completions"), "http://localhost:1234");
+        assert_eq!(origin("https://api.groq.com/openai"), origin("https://api.groq.com:443/"));
+        assert_ne!(origin("http://localhost:1234"), origin("http://localhost:11434"));
+
+        let count = pooled_endpoints();
+        shared_client("http://pool-test.invalid:1/a").unwrap();
+        shared_client("http://pool-test.invalid:1/b").unwrap();
+        assert!(pooled_endpoints() <= count + 1);
+    }
+}
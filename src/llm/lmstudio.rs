@@ -0,0 +1,194 @@
+//! LM Studio model control: loading and unloading models on the server
+
+use std::time::Duration;
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use crate::error::NexaError;
+use super::{http_pool, request_error, status_error, LLMClient, LLMConfig, ServerType};
+
+/// A model known to LM Studio
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LMStudioModel {
+    pub id: String,
+    /// `llm`, `vlm` or `embeddings`
+    #[serde(default, rename = "type")]
+    pub kind: String,
+    /// `loaded` or `not-loaded`
+    #[serde(default)]
+    pub state: String,
+    #[serde(default)]
+    pub max_context_length: Option<usize>,
+}
+
+impl LMStudioModel {
+    pub fn is_loaded(&self) -> bool {
+        self.state == "loaded"
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelList {
+    #[serde(default)]
+    data: Vec<LMStudioModel>,
+}
+
+/// Client for the model load and unload endpoints of an LM Studio server
+#[derive(Debug, Clone)]
+pub struct LMStudioClient {
+    server_url: String,
+    http: reqwest::Client,
+    headers: HeaderMap,
+    timeout: Duration,
+}
+
+impl LMStudioClient {
+    /// Client for the server in `config`, or for a local LM Studio when `config` names another provider
+    pub fn new(config: &LLMConfig) -> Result<Self, NexaError> {
+        let config = match config.server_type {
+            ServerType::LMStudio => config.clone(),
+            _ => LLMConfig::for_server_type(ServerType::LMStudio),
+        };
+        Ok(Self {
+            http: http_pool::shared_client(&config.server_url)?,
+            headers: LLMClient::build_headers(&config)?,
+            server_url: config.server_url,
+            timeout: Duration::from_secs(config.timeout_secs),
+        })
+    }
+
+    /// Base URL of the server
+    pub fn server_url(&self) -> &str {
+        &self.server_url
+    }
+
+    /// Every model downloaded to the server, loaded or not
+    pub async fn models(&self) -> Result<Vec<LMStudioModel>, NexaError> {
+        let response = self.http.get(self.url("/api/v0/models"))
+            .headers(self.headers.clone())
+            .timeout(self.timeout)
+            .send()
+            .await
+            .map_err(|e| request_error("Failed to list LM Studio models", e))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(status_error("Listing LM Studio models failed", status, text));
+        }
+        let body: ModelList = response.json()
+            .await
+            .map_err(|e| NexaError::invalid_response(format!("Failed to parse model list: {}", e)))?;
+        Ok(body.data)
+    }
+
+    /// Models currently loaded into memory
+    pub async fn loaded(&self) -> Result<Vec<LMStudioModel>, NexaError> {
+        Ok(self.models().await?.into_iter().filter(LMStudioModel::is_loaded).collect())
+    }
+
+    /// Load a model into memory
+    ///
+    /// Loading a large model can take far longer than the request timeout, so it is not applied.
+    pub async fn load(&self, model: &str) -> Result<(), NexaError> {
+        self.post("/api/v1/models/load", serde_json::json!({ "model": model }), None, &format!("Loading {}", model)).await
+    }
+
+    /// Unload a model, freeing its memory
+    pub async fn unload(&self, model: &str) -> Result<(), NexaError> {
+        let timeout = Some(self.timeout);
+        self.post("/api/v1/models/unload", serde_json::json!({ "instance_id": model }), timeout, &format!("Unloading {}", model)).await
+    }
+
+    /// Make `model` the only loaded model, unloading the others first to free memory
+    ///
+    /// Returns the models that were unloaded.
+    pub async fn switch_to(&self, model: &str) -> Result<Vec<String>, NexaError> {
+        let loaded = self.loaded().await?;
+        if loaded.iter().any(|m| m.id == model) && loaded.len() == 1 {
+            return Ok(Vec::new());
+        }
+
+        let mut unloaded = Vec::new();
+        for other in loaded.into_iter().filter(|m| m.id != model) {
+            self.unload(&other.id).await?;
+            unloaded.push(other.id);
+        }
+        if !self.loaded().await?.iter().any(|m| m.id == model) {
+            info!("Loading {} into LM Studio", model);
+            self.load(model).await?;
+        }
+        Ok(unloaded)
+    }
+
+    async fn post(&self, path: &str, body: serde_json::Value, timeout: Option<Duration>, context: &str) -> Result<(), NexaError> {
+        let mut request = self.http.post(self.url(path))
+            .headers(self.headers.clone())
+            .json(&body);
+        if let Some(timeout) = timeout {
+            request = request.timeout(timeout);
+        }
+        let response = request.send()
+            .await
+            .map_err(|e| request_error(&format!("{} failed", context), e))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(status_error(&format!("{} failed", context), status, text));
+        }
+        Ok(())
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.server_url, path)
+    }
+}
+
+/// Whether a failed completion means the model is not loaded on the server
+pub(super) fn is_model_not_loaded(err: &NexaError) -> bool {
+    match err {
+        NexaError::NotFound(_) => true,
+        NexaError::InvalidInput(msg) => {
+            let msg = msg.to_lowercase();
+            msg.contains("model not found") || msg.contains("not loaded") || msg.contains("no models loaded")
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::test_utils;
+
+    #[tokio::test]
+    async fn test_switch_to() {
+        let (url, recorded) = test_utils::start_recording_server(serde_json::json!({
+            "data": [
+                { "id": "qwen2.5-7b-instruct", "type": "llm", "state": "loaded" },
+                { "id": "llama-3.2-3b-instruct", "type": "llm", "state": "not-loaded" },
+            ],
+        })).await;
+        let lmstudio = LMStudioClient::new(&LLMConfig::with_lmstudio_server(url)).unwrap();
+
+        let loaded = lmstudio.loaded().await.unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].id, "qwen2.5-7b-instruct");
+
+        let unloaded = lmstudio.switch_to("llama-3.2-3b-instruct").await.unwrap();
+        assert_eq!(unloaded, vec!["qwen2.5-7b-instruct"]);
+
+        let recorded = recorded.lock();
+        let unload = recorded.iter().find(|r| r.path == "/api/v1/models/unload").unwrap();
+        assert_eq!(unload.body["instance_id"], "qwen2.5-7b-instruct");
+        let load = recorded.iter().find(|r| r.path == "/api/v1/models/load").unwrap();
+        assert_eq!(load.body["model"], "llama-3.2-3b-instruct");
+    }
+
+    #[test]
+    fn test_is_model_not_loaded() {
+        assert!(is_model_not_loaded(&NexaError::not_found("LLM request failed (404 Not Found): {}")));
+        assert!(is_model_not_loaded(&NexaError::invalid_input("LLM request failed (400 Bad Request): Model not found")));
+        assert!(!is_model_not_loaded(&NexaError::invalid_input("LLM request failed (400 Bad Request): bad temperature")));
+        assert!(!is_model_not_loaded(&NexaError::unavailable("connection refused")));
+    }
+}
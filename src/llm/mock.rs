@@ -0,0 +1,165 @@
+//! Deterministic in-process LLM for tests and offline demos (`mock` feature)
+//!
+//! - `llm.server_url` names the mock, e.g. `mock://demo`; clients with the same URL share it
+//! - Replies come from the first rule matching the last user message, else the default template
+//! - Every request is recorded so tests can assert on what was sent
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, OnceLock};
+use parking_lot::Mutex;
+use super::{ChatMessage, ChatRole, LLMConfig};
+
+/// Reply used when no rule matches
+pub const DEFAULT_REPLY: &str = "Mock response to: {{prompt}}";
+
+/// Dimensions of mock embeddings
+pub const EMBEDDING_DIMENSIONS: usize = 64;
+
+/// A request the mock received
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    pub model: String,
+    pub messages: Vec<ChatMessage>,
+}
+
+#[derive(Debug, Clone)]
+struct MockRule {
+    contains: String,
+    reply: String,
+}
+
+/// Canned replies and the requests received, shared by every client with the same `server_url`
+///
+/// Reply templates substitute `{{prompt}}` (the last user message), `{{model}}`
+/// and `{{n}}` (the number of this request, from 1).
+#[derive(Debug)]
+pub struct MockLLM {
+    rules: Mutex<Vec<MockRule>>,
+    default_reply: Mutex<String>,
+    requests: Mutex<Vec<MockRequest>>,
+}
+
+static MOCKS: OnceLock<Mutex<HashMap<String, Arc<MockLLM>>>> = OnceLock::new();
+
+impl MockLLM {
+    /// The mock served at `server_url`, created on first use
+    pub fn named(server_url: &str) -> Arc<Self> {
+        MOCKS.get_or_init(Default::default)
+            .lock()
+            .entry(server_url.to_string())
+            .or_insert_with(|| Arc::new(Self {
+                rules: Mutex::new(Vec::new()),
+                default_reply: Mutex::new(DEFAULT_REPLY.to_string()),
+                requests: Mutex::new(Vec::new()),
+            }))
+            .clone()
+    }
+
+    /// Reply with `template` when the last user message contains `contains`
+    ///
+    /// Rules are tried in the order they were added.
+    pub fn reply_when(&self, contains: impl Into<String>, template: impl Into<String>) -> &Self {
+        self.rules.lock().push(MockRule { contains: contains.into(), reply: template.into() });
+        self
+    }
+
+    /// Reply with `template` when no rule matches
+    pub fn set_default_reply(&self, template: impl Into<String>) -> &Self {
+        *self.default_reply.lock() = template.into();
+        self
+    }
+
+    /// Requests received so far, oldest first
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().clone()
+    }
+
+    /// Forget the rules and recorded requests
+    pub fn reset(&self) {
+        self.rules.lock().clear();
+        *self.default_reply.lock() = DEFAULT_REPLY.to_string();
+        self.requests.lock().clear();
+    }
+
+    fn reply(&self, config: &LLMConfig, messages: &[ChatMessage]) -> String {
+        let prompt = messages.iter()
+            .rev()
+            .find(|m| m.role == ChatRole::User)
+            .map(|m| m.content.as_str())
+            .unwrap_or_default();
+        let n = {
+            let mut requests = self.requests.lock();
+            requests.push(MockRequest { model: config.model.clone(), messages: messages.to_vec() });
+            requests.len()
+        };
+        let template = self.rules.lock()
+            .iter()
+            .find(|rule| prompt.contains(&rule.contains))
+            .map(|rule| rule.reply.clone())
+            .unwrap_or_else(|| self.default_reply.lock().clone());
+        template
+            .replace("{{prompt}}", prompt)
+            .replace("{{model}}", &config.model)
+            .replace("{{n}}", &n.to_string())
+    }
+}
+
+/// Complete a chat with the mock named by `config.server_url`
+pub(super) fn complete(config: &LLMConfig, messages: &[ChatMessage]) -> String {
+    MockLLM::named(&config.server_url).reply(config, messages)
+}
+
+/// A unit vector derived from the words of `text`, so equal texts embed equally
+/// and texts sharing words are closer than unrelated ones
+pub(super) fn embed(text: &str) -> Vec<f32> {
+    let mut vector = vec![0.0f32; EMBEDDING_DIMENSIONS];
+    for word in text.split_whitespace() {
+        let mut hasher = DefaultHasher::new();
+        word.to_lowercase().hash(&mut hasher);
+        vector[(hasher.finish() % EMBEDDING_DIMENSIONS as u64) as usize] += 1.0;
+    }
+    let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|v| *v /= norm);
+    }
+    vector
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::LLMClient;
+
+    #[tokio::test]
+    async fn test_mock_replies_and_records() {
+        let config = LLMConfig::with_mock("test-mock-replies");
+        let mock = MockLLM::named(&config.server_url);
+        mock.reply_when("weather", "Sunny, says {{model}}")
+            .set_default_reply("#{{n}}: {{prompt}}");
+        let client = LLMClient::new(config).unwrap();
+
+        assert_eq!(client.complete("What is the weather?").await.unwrap(), "Sunny, says mock");
+        assert_eq!(client.complete("Hello").await.unwrap(), "#2: Hello");
+        assert_eq!(client.list_models().await.unwrap(), vec!["mock".to_string()]);
+        client.health_check().await.unwrap();
+
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].messages.last().unwrap().content, "Hello");
+
+        mock.reset();
+        assert_eq!(client.complete("Hi").await.unwrap(), "Mock response to: Hi");
+    }
+
+    #[tokio::test]
+    async fn test_mock_embeddings() {
+        let client = LLMClient::new(LLMConfig::with_mock("test-mock-embeddings")).unwrap();
+        let texts = ["red apple".to_string(), "red apple".to_string(), "blue sky".to_string()];
+        let embeddings = client.embed(&texts).await.unwrap();
+        assert_eq!(embeddings[0], embeddings[1]);
+        assert_ne!(embeddings[0], embeddings[2]);
+        assert_eq!(embeddings[0].len(), EMBEDDING_DIMENSIONS);
+    }
+}
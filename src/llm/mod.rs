@@ -1,20 +1,92 @@
 pub mod system_helper;
+pub mod degraded;
+pub mod http_pool;
+pub mod bedrock;
+pub mod cache;
+pub mod conversation;
+#[cfg(feature = "llama")]
+pub mod embedded;
+pub mod guardrails;
+pub mod lmstudio;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
+pub mod ollama;
+pub mod rate_limit;
+pub mod retry;
+pub mod router;
+pub mod streaming;
+pub mod tool_calling;
+pub mod tool_loop;
+pub mod truncation;
 #[cfg(test)]
 pub mod test_utils;
 
 pub use system_helper::*;
-
+pub use degraded::{LLMAvailability, LLMSupervisor};
+pub use cache::CacheConfig;
+pub use conversation::Conversation;
+pub use guardrails::{Guardrail, GuardrailConfig, Guardrails};
+pub use lmstudio::LMStudioClient;
+#[cfg(any(test, feature = "mock"))]
+pub use mock::MockLLM;
+pub use ollama::{OllamaClient, PullProgress};
+pub use rate_limit::RateLimit;
+pub use retry::{RetryPolicy, RetryableError};
+pub use router::{ModelEntry, ModelRequirements, ModelRouter};
+pub use streaming::CompletionStream;
+pub use tool_calling::{ToolCall, ToolCompletion};
+pub use tool_loop::{ToolLoop, ToolLoopOutcome};
+pub use truncation::TruncationStrategy;
+
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use reqwest::Client;
+use schemars::JsonSchema;
+use reqwest::header::HeaderMap;
+use reqwest::{IntoUrl, Method, RequestBuilder};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
 use std::time::Duration;
+use parking_lot::RwLock;
+use futures::{StreamExt, TryStreamExt};
+use tokio::sync::watch;
+pub use tokio_util::sync::CancellationToken;
+use crate::agent::Agent;
+use crate::config::Config;
 use crate::error::NexaError;
-use tracing::debug;
+use crate::secrets::SecretStore;
+use crate::tokens::{self, ModelPricing, ModelType, TokenManager};
+use crate::tools::ToolSpec;
+use tracing::{debug, error, info, warn, Instrument};
 
 /// Server type for LLM requests
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub enum ServerType {
     LMStudio,
     Ollama,
+    /// Anthropic messages API
+    #[serde(alias = "anthropic")]
+    Anthropic,
+    /// Google Gemini API
+    #[serde(alias = "gemini")]
+    Gemini,
+    /// AWS Bedrock Converse API
+    #[serde(alias = "bedrock")]
+    Bedrock,
+    /// Groq's OpenAI-compatible API
+    #[serde(alias = "groq")]
+    Groq,
+    /// Any OpenAI-style server (vLLM, LiteLLM, llamafile, text-generation-webui)
+    #[serde(alias = "openai_compatible", alias = "openai")]
+    OpenAICompatible,
+    /// OpenRouter, routing to many upstream providers
+    #[serde(alias = "openrouter")]
+    OpenRouter,
+    /// GGUF models run in-process by llama.cpp (`llama` feature)
+    #[serde(alias = "embedded")]
+    Embedded,
+    /// Deterministic canned replies for tests and offline demos (`mock` feature)
+    #[serde(alias = "mock")]
+    Mock,
 }
 
 impl Default for ServerType {
@@ -23,29 +95,138 @@ impl Default for ServerType {
     }
 }
 
+impl std::str::FromStr for ServerType {
+    type Err = NexaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "lmstudio" => Ok(Self::LMStudio),
+            "ollama" => Ok(Self::Ollama),
+            "anthropic" => Ok(Self::Anthropic),
+            "gemini" => Ok(Self::Gemini),
+            "bedrock" => Ok(Self::Bedrock),
+            "groq" => Ok(Self::Groq),
+            "openai" | "openai_compatible" | "openaicompatible" => Ok(Self::OpenAICompatible),
+            "openrouter" => Ok(Self::OpenRouter),
+            "embedded" => Ok(Self::Embedded),
+            "mock" => Ok(Self::Mock),
+            _ => Err(NexaError::invalid_input(format!("Unknown LLM provider '{}'", s))),
+        }
+    }
+}
+
+/// Content filtering strictness, mapped to each provider's own settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SafetyLevel {
+    /// Do not block content
+    BlockNone,
+    /// Block only content with a high probability of harm
+    BlockFew,
+    /// Block content with a medium or higher probability of harm
+    BlockSome,
+    /// Block content with a low or higher probability of harm
+    BlockMost,
+}
+
+impl SafetyLevel {
+    fn gemini_threshold(self) -> &'static str {
+        match self {
+            Self::BlockNone => "BLOCK_NONE",
+            Self::BlockFew => "BLOCK_ONLY_HIGH",
+            Self::BlockSome => "BLOCK_MEDIUM_AND_ABOVE",
+            Self::BlockMost => "BLOCK_LOW_AND_ABOVE",
+        }
+    }
+}
+
 /// Configuration for LLM client
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+#[schemars(deny_unknown_fields)]
 pub struct LLMConfig {
     /// Server URL
     pub server_url: String,
-    /// Server type (LMStudio or Ollama)
+    /// Server type (LMStudio, Ollama, Anthropic, Gemini, Bedrock, Groq, OpenAICompatible or OpenRouter)
     pub server_type: ServerType,
     /// Request timeout in seconds
+    #[schemars(range(min = 1))]
     pub timeout_secs: u64,
     /// Maximum tokens to generate
+    #[schemars(range(min = 1))]
     pub max_tokens: usize,
     /// Temperature for generation (0.0 - 1.0)
+    #[schemars(range(min = 0, max = 1))]
     pub temperature: f32,
     /// Top-p sampling
+    #[schemars(range(min = 0, max = 1))]
     pub top_p: f32,
     /// Stop sequences
     pub stop: Vec<String>,
+    /// Sampling seed for reproducible completions, where the provider supports it
+    pub seed: Option<u64>,
+    /// Penalty for tokens by how often they already appear (-2.0 - 2.0)
+    #[schemars(range(min = -2, max = 2))]
+    pub frequency_penalty: Option<f32>,
+    /// Penalty for tokens that already appear at all (-2.0 - 2.0)
+    #[schemars(range(min = -2, max = 2))]
+    pub presence_penalty: Option<f32>,
+    /// Bias added to the likelihood of token ids (-100 - 100), OpenAI-style servers only
+    pub logit_bias: BTreeMap<String, f32>,
     /// Allow CORS from specific origins (empty means allow all)
     pub allowed_origins: Vec<String>,
     /// Whether to include credentials in CORS requests
     pub allow_credentials: bool,
     /// Model name (especially important for Ollama)
     pub model: String,
+    /// Name of the secret holding the provider API key
+    pub api_key_secret: Option<String>,
+    /// System prompt sent with every completion
+    pub system_prompt: Option<String>,
+    /// Content filtering for providers that support it; provider default when unset
+    pub safety: Option<SafetyLevel>,
+    /// Cloud region for providers that need one (Bedrock)
+    pub region: Option<String>,
+    /// Times to wait out a 429 response before giving up
+    pub rate_limit_retries: u32,
+    /// Chat completions path of an OpenAICompatible server (default `/v1/chat/completions`)
+    pub chat_path: Option<String>,
+    /// Model list path of an OpenAICompatible server (default `/v1/models`)
+    pub models_path: Option<String>,
+    /// Header carrying the API key of an OpenAICompatible server (default `Authorization`)
+    ///
+    /// Keys sent in `Authorization` get a `Bearer ` prefix; other headers carry the bare key.
+    pub auth_header: Option<String>,
+    /// Site URL sent to OpenRouter as `HTTP-Referer` for app attribution
+    pub app_url: Option<String>,
+    /// App name sent to OpenRouter as `X-Title`
+    pub app_title: Option<String>,
+    /// Model used by `embed`; the completion model when unset
+    pub embedding_model: Option<String>,
+    /// Retries of timeouts and unavailable servers
+    pub retry: RetryPolicy,
+    /// Client-side limits that keep bursts under the provider's quota
+    pub rate_limit: RateLimit,
+    /// Caching of completion responses
+    pub cache: CacheConfig,
+    /// Context window in tokens; looked up from the model name when unset
+    pub context_window: Option<usize>,
+    /// How chats longer than the context window are shortened before sending
+    pub truncation: TruncationStrategy,
+    /// Requests `complete_batch` keeps in flight at once
+    #[schemars(range(min = 1))]
+    pub batch_concurrency: usize,
+    /// Refuse requests whose estimated cost in USD exceeds this
+    #[schemars(range(min = 0))]
+    pub max_cost: Option<f64>,
+    /// Send a minimal completion at startup so the model is loaded before the first task
+    pub warmup: bool,
+    /// Load the model into LM Studio, unloading any others, when a request finds it missing
+    pub auto_load: bool,
+    /// Layers of an embedded model to offload to the GPU
+    pub gpu_layers: u32,
+    /// Download images given by URL from loopback, link-local and private addresses too
+    pub allow_private_image_hosts: bool,
 }
 
 impl Default for LLMConfig {
@@ -58,13 +239,42 @@ impl Default for LLMConfig {
             temperature: 0.7,
             top_p: 0.9,
             stop: vec![],
+            seed: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            logit_bias: BTreeMap::new(),
             allowed_origins: vec![],
             allow_credentials: false,
             model: "local-model".to_string(),
+            api_key_secret: None,
+            system_prompt: None,
+            safety: None,
+            region: None,
+            rate_limit_retries: default_rate_limit_retries(),
+            chat_path: None,
+            models_path: None,
+            auth_header: None,
+            app_url: None,
+            app_title: None,
+            embedding_model: None,
+            retry: RetryPolicy::default(),
+            rate_limit: RateLimit::default(),
+            cache: CacheConfig::default(),
+            context_window: None,
+            truncation: TruncationStrategy::None,
+            batch_concurrency: default_batch_concurrency(),
+            max_cost: None,
+            warmup: true,
+            auto_load: false,
+            gpu_layers: 0,
+            allow_private_image_hosts: false,
         }
     }
 }
 
+fn default_rate_limit_retries() -> u32 { 3 }
+fn default_batch_concurrency() -> usize { 4 }
+
 impl LLMConfig {
     /// Create a new configuration with LM Studio server
     pub fn with_lmstudio_server(server_url: impl Into<String>) -> Self {
@@ -76,9 +286,35 @@ impl LLMConfig {
             temperature: 0.7,
             top_p: 0.9,
             stop: vec![],
+            seed: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            logit_bias: BTreeMap::new(),
             allowed_origins: vec![],
             allow_credentials: false,
             model: "local-model".to_string(),
+            api_key_secret: None,
+            system_prompt: None,
+            safety: None,
+            region: None,
+            rate_limit_retries: default_rate_limit_retries(),
+            chat_path: None,
+            models_path: None,
+            auth_header: None,
+            app_url: None,
+            app_title: None,
+            embedding_model: None,
+            retry: RetryPolicy::default(),
+            rate_limit: RateLimit::default(),
+            cache: CacheConfig::default(),
+            context_window: None,
+            truncation: TruncationStrategy::None,
+            batch_concurrency: default_batch_concurrency(),
+            max_cost: None,
+            warmup: true,
+            auto_load: false,
+            gpu_layers: 0,
+            allow_private_image_hosts: false,
         }
     }
 
@@ -92,12 +328,222 @@ impl LLMConfig {
             temperature: 0.7,
             top_p: 0.9,
             stop: vec![],
+            seed: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            logit_bias: BTreeMap::new(),
             allowed_origins: vec![],
             allow_credentials: false,
             model: model.into(),
+            api_key_secret: None,
+            system_prompt: None,
+            safety: None,
+            region: None,
+            rate_limit_retries: default_rate_limit_retries(),
+            chat_path: None,
+            models_path: None,
+            auth_header: None,
+            app_url: None,
+            app_title: None,
+            embedding_model: None,
+            retry: RetryPolicy::default(),
+            rate_limit: RateLimit::default(),
+            cache: CacheConfig::default(),
+            context_window: None,
+            truncation: TruncationStrategy::None,
+            batch_concurrency: default_batch_concurrency(),
+            max_cost: None,
+            warmup: true,
+            auto_load: false,
+            gpu_layers: 0,
+            allow_private_image_hosts: false,
+        }
+    }
+
+    /// Create a new configuration for the Anthropic API
+    ///
+    /// The API key is read from the `anthropic_api_key` secret.
+    pub fn with_anthropic(model: impl Into<String>) -> Self {
+        Self {
+            server_url: "https://api.anthropic.com".to_string(),
+            server_type: ServerType::Anthropic,
+            timeout_secs: 60,
+            model: model.into(),
+            api_key_secret: Some("anthropic_api_key".to_string()),
+            ..Self::default()
+        }
+    }
+
+    /// Create a new configuration for the Google Gemini API
+    ///
+    /// The API key is read from the `gemini_api_key` secret.
+    pub fn with_gemini(model: impl Into<String>) -> Self {
+        Self {
+            server_url: "https://generativelanguage.googleapis.com".to_string(),
+            server_type: ServerType::Gemini,
+            timeout_secs: 60,
+            model: model.into(),
+            api_key_secret: Some("gemini_api_key".to_string()),
+            ..Self::default()
+        }
+    }
+
+    /// Create a new configuration for AWS Bedrock in a region
+    ///
+    /// Requests are signed with `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY`
+    /// (plus `AWS_SESSION_TOKEN` if present) resolved as secrets.
+    pub fn with_bedrock(model: impl Into<String>, region: impl Into<String>) -> Self {
+        let region = region.into();
+        Self {
+            server_url: format!("https://bedrock-runtime.{}.amazonaws.com", region),
+            server_type: ServerType::Bedrock,
+            timeout_secs: 60,
+            model: model.into(),
+            region: Some(region),
+            ..Self::default()
+        }
+    }
+
+    /// Create a new configuration for Groq
+    ///
+    /// The API key is read from the `groq_api_key` secret.
+    pub fn with_groq(model: impl Into<String>) -> Self {
+        Self {
+            server_url: "https://api.groq.com/openai".to_string(),
+            server_type: ServerType::Groq,
+            model: model.into(),
+            api_key_secret: Some("groq_api_key".to_string()),
+            // Groq throttles aggressively; waiting it out beats failing the step
+            rate_limit_retries: 5,
+            ..Self::default()
+        }
+    }
+
+    /// Create a new configuration for an OpenAI-style server
+    pub fn with_openai_compatible(server_url: impl Into<String>, model: impl Into<String>) -> Self {
+        Self {
+            server_url: server_url.into(),
+            server_type: ServerType::OpenAICompatible,
+            model: model.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Create a new configuration for OpenRouter
+    ///
+    /// The API key is read from the `openrouter_api_key` secret.
+    pub fn with_openrouter(model: impl Into<String>) -> Self {
+        Self {
+            server_url: "https://openrouter.ai/api".to_string(),
+            server_type: ServerType::OpenRouter,
+            model: model.into(),
+            api_key_secret: Some("openrouter_api_key".to_string()),
+            ..Self::default()
+        }
+    }
+
+    /// Create a new configuration running a GGUF model in-process (`llama` feature)
+    pub fn with_embedded(model_path: impl Into<String>) -> Self {
+        Self {
+            server_url: String::new(),
+            server_type: ServerType::Embedded,
+            model: model_path.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Create a new configuration for the mock provider named `name` (`mock` feature)
+    pub fn with_mock(name: impl AsRef<str>) -> Self {
+        Self {
+            server_url: format!("mock://{}", name.as_ref()),
+            server_type: ServerType::Mock,
+            model: "mock".to_string(),
+            ..Self::default()
         }
     }
 
+    /// Identify the calling app to OpenRouter
+    pub fn with_app_attribution(mut self, url: impl Into<String>, title: impl Into<String>) -> Self {
+        self.app_url = Some(url.into());
+        self.app_title = Some(title.into());
+        self
+    }
+
+    /// Embed with this model instead of the completion model
+    pub fn with_embedding_model(mut self, model: impl Into<String>) -> Self {
+        self.embedding_model = Some(model.into());
+        self
+    }
+
+    /// Override the chat completions and model list paths
+    pub fn with_paths(mut self, chat_path: impl Into<String>, models_path: impl Into<String>) -> Self {
+        self.chat_path = Some(chat_path.into());
+        self.models_path = Some(models_path.into());
+        self
+    }
+
+    /// Send the API key in this header instead of `Authorization`
+    pub fn with_auth_header(mut self, header: impl Into<String>) -> Self {
+        self.auth_header = Some(header.into());
+        self
+    }
+
+    /// Path of the chat completions endpoint for OpenAI-style servers
+    fn chat_path(&self) -> &str {
+        match (self.server_type, &self.chat_path) {
+            (ServerType::OpenAICompatible, Some(path)) => path,
+            _ => "/v1/chat/completions",
+        }
+    }
+
+    /// Path of the embeddings endpoint for OpenAI-style servers, next to the chat endpoint
+    fn embeddings_path(&self) -> String {
+        let chat_path = self.chat_path();
+        match chat_path.strip_suffix("/chat/completions") {
+            Some(base) => format!("{}/embeddings", base),
+            None => "/v1/embeddings".to_string(),
+        }
+    }
+
+    /// Path listing the server's models
+    fn models_path(&self) -> &str {
+        match (self.server_type, &self.models_path) {
+            (ServerType::OpenAICompatible, Some(path)) => path,
+            (ServerType::Ollama, _) => "/api/tags",
+            (ServerType::Gemini, _) => "/v1beta/models",
+            (ServerType::Bedrock, _) => "/foundation-models",
+            _ => "/v1/models",
+        }
+    }
+
+    /// Default configuration for a provider
+    pub fn for_server_type(server_type: ServerType) -> Self {
+        match server_type {
+            ServerType::LMStudio => Self::default(),
+            ServerType::Ollama => Self::with_ollama_server("llama3.2"),
+            ServerType::Anthropic => Self::with_anthropic("claude-3-5-haiku-latest"),
+            ServerType::Gemini => Self::with_gemini("gemini-1.5-flash"),
+            ServerType::Bedrock => Self::with_bedrock("anthropic.claude-3-haiku-20240307-v1:0", "us-east-1"),
+            ServerType::Groq => Self::with_groq("llama-3.1-8b-instant"),
+            ServerType::OpenAICompatible => Self::with_openai_compatible("http://localhost:8000", "default"),
+            ServerType::OpenRouter => Self::with_openrouter("openrouter/auto"),
+            ServerType::Embedded => Self::with_embedded("models/model.gguf"),
+            ServerType::Mock => Self::with_mock("default"),
+        }
+    }
+
+    /// Set the content filtering level
+    pub fn with_safety(mut self, level: SafetyLevel) -> Self {
+        self.safety = Some(level);
+        self
+    }
+
+    /// Set the system prompt
+    pub fn with_system_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.system_prompt = Some(prompt.into());
+        self
+    }
+
     /// Set allowed CORS origins
     pub fn with_cors_origins(mut self, origins: Vec<String>) -> Self {
         self.allowed_origins = origins;
@@ -109,24 +555,314 @@ impl LLMConfig {
         self.allow_credentials = true;
         self
     }
+
+    /// Resolve the provider API key from the named secret
+    pub fn with_api_key_secret(mut self, name: impl Into<String>) -> Self {
+        self.api_key_secret = Some(name.into());
+        self
+    }
 }
 
 /// Request body for LLM API
 #[derive(Debug, Serialize)]
 struct LLMRequest {
-    messages: Vec<ChatMessage>,
+    messages: Vec<ApiMessage>,
     model: String,
     temperature: f32,
     max_tokens: Option<usize>,
     top_p: Option<f32>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     stop: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frequency_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    presence_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    logit_bias: BTreeMap<String, f32>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+}
+
+/// An image passed to a vision-capable model
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageInput {
+    /// Publicly reachable image URL
+    Url(String),
+    /// Inline image data
+    Base64 { media_type: String, data: String },
+}
+
+impl ImageInput {
+    /// Inline image from raw bytes, such as a screenshot
+    pub fn from_bytes(media_type: impl Into<String>, bytes: &[u8]) -> Self {
+        use base64::Engine;
+        Self::Base64 {
+            media_type: media_type.into(),
+            data: base64::engine::general_purpose::STANDARD.encode(bytes),
+        }
+    }
+
+    /// URL form accepted by OpenAI-style servers, using a data URL for inline images
+    fn url(&self) -> String {
+        match self {
+            Self::Url(url) => url.clone(),
+            Self::Base64 { media_type, data } => format!("data:{};base64,{}", media_type, data),
+        }
+    }
+}
+
+/// Who wrote a chat message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatRole {
+    System,
+    User,
+    Assistant,
+    /// Result of a tool call, answering an assistant message's `tool_calls`
+    Tool,
+}
+
+impl ChatRole {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::System => "system",
+            Self::User => "user",
+            Self::Assistant => "assistant",
+            Self::Tool => "tool",
+        }
+    }
+}
+
+/// A message in a multi-turn chat
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ChatMessage {
+    pub role: ChatRole,
+    pub content: String,
+    /// Images attached to a user message
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub images: Vec<ImageInput>,
+    /// Tools an assistant message asked to call
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    /// Call a tool message is the result of
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+}
+
+impl ChatMessage {
+    pub fn new(role: ChatRole, content: impl Into<String>) -> Self {
+        Self {
+            role,
+            content: content.into(),
+            images: Vec::new(),
+            tool_calls: Vec::new(),
+            tool_call_id: None,
+        }
+    }
+
+    pub fn system(content: impl Into<String>) -> Self {
+        Self::new(ChatRole::System, content)
+    }
+
+    pub fn user(content: impl Into<String>) -> Self {
+        Self::new(ChatRole::User, content)
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self::new(ChatRole::Assistant, content)
+    }
+
+    /// Result of the tool call `call_id`
+    pub fn tool_result(call_id: impl Into<String>, content: impl Into<String>) -> Self {
+        Self {
+            tool_call_id: Some(call_id.into()),
+            ..Self::new(ChatRole::Tool, content)
+        }
+    }
+
+    /// Record the tool calls an assistant message made
+    pub fn with_tool_calls(mut self, tool_calls: Vec<ToolCall>) -> Self {
+        self.tool_calls = tool_calls;
+        self
+    }
+
+    /// Attach images for a vision-capable model
+    pub fn with_images(mut self, images: impl IntoIterator<Item = ImageInput>) -> Self {
+        self.images.extend(images);
+        self
+    }
+}
+
+/// Generation parameters for a single request, overriding the client's configuration
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+#[schemars(deny_unknown_fields)]
+pub struct CompletionOptions {
+    /// Maximum tokens to generate
+    #[schemars(range(min = 1))]
+    pub max_tokens: Option<usize>,
+    /// Temperature for generation (0.0 - 1.0)
+    #[schemars(range(min = 0, max = 1))]
+    pub temperature: Option<f32>,
+    /// Top-p sampling
+    #[schemars(range(min = 0, max = 1))]
+    pub top_p: Option<f32>,
+    /// Stop sequences, replacing the configured ones
+    pub stop: Option<Vec<String>>,
+    /// Sampling seed for reproducible completions
+    pub seed: Option<u64>,
+    #[schemars(range(min = -2, max = 2))]
+    pub frequency_penalty: Option<f32>,
+    #[schemars(range(min = -2, max = 2))]
+    pub presence_penalty: Option<f32>,
+    /// Token biases, replacing the configured ones
+    pub logit_bias: Option<BTreeMap<String, f32>>,
+    /// Cost ceiling in USD, replacing the configured one
+    #[schemars(range(min = 0))]
+    pub max_cost: Option<f64>,
+    /// Seconds the whole call may take, retries included
+    #[schemars(range(min = 1))]
+    pub timeout_secs: Option<u64>,
+}
+
+impl CompletionOptions {
+    pub fn with_max_tokens(mut self, max_tokens: usize) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    pub fn with_top_p(mut self, top_p: f32) -> Self {
+        self.top_p = Some(top_p);
+        self
+    }
+
+    pub fn with_stop(mut self, stop: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.stop = Some(stop.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn with_max_cost(mut self, max_cost: f64) -> Self {
+        self.max_cost = Some(max_cost);
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout_secs = Some(timeout.as_secs().max(1));
+        self
+    }
+
+    fn apply(&self, config: &mut LLMConfig) {
+        if let Some(max_tokens) = self.max_tokens {
+            config.max_tokens = max_tokens;
+        }
+        if let Some(temperature) = self.temperature {
+            config.temperature = temperature;
+        }
+        if let Some(top_p) = self.top_p {
+            config.top_p = top_p;
+        }
+        if let Some(stop) = &self.stop {
+            config.stop = stop.clone();
+        }
+        if let Some(seed) = self.seed {
+            config.seed = Some(seed);
+        }
+        if let Some(penalty) = self.frequency_penalty {
+            config.frequency_penalty = Some(penalty);
+        }
+        if let Some(penalty) = self.presence_penalty {
+            config.presence_penalty = Some(penalty);
+        }
+        if let Some(logit_bias) = &self.logit_bias {
+            config.logit_bias = logit_bias.clone();
+        }
+        if let Some(max_cost) = self.max_cost {
+            config.max_cost = Some(max_cost);
+        }
+    }
 }
 
+/// Upper bound on what a completion costs, computed before it is sent
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CostEstimate {
+    pub prompt_tokens: usize,
+    /// Completion tokens the request may generate
+    pub max_completion_tokens: usize,
+    /// Cost in USD if the whole completion budget is used; unknown without pricing for the model
+    pub max_cost: Option<f64>,
+}
+
+/// A message in a provider request or response
 #[derive(Debug, Serialize, Deserialize)]
-struct ChatMessage {
+struct ApiMessage {
     role: String,
-    content: String,
+    content: MessageContent,
+}
+
+impl ApiMessage {
+    fn text(role: &str, text: &str) -> Self {
+        Self {
+            role: role.to_string(),
+            content: MessageContent::Text(text.to_string()),
+        }
+    }
+}
+
+/// Message content: plain text, or parts when images are attached
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum MessageContent {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+impl MessageContent {
+    fn into_text(self) -> String {
+        match self {
+            Self::Text(text) => text,
+            Self::Parts(parts) => parts.into_iter()
+                .filter_map(|part| match part {
+                    ContentPart::Text { text } => Some(text),
+                    _ => None,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ContentPart {
+    Text { text: String },
+    /// OpenAI-style image part
+    ImageUrl { image_url: ImageUrl },
+    /// Anthropic image block
+    Image { source: ImageSource },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ImageUrl {
+    url: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ImageSource {
+    Base64 { media_type: String, data: String },
+    Url { url: String },
 }
 
 /// Response from LLM API
@@ -138,7 +874,7 @@ struct LLMResponse {
 
 #[derive(Debug, Deserialize)]
 struct ChatChoice {
-    message: ChatMessage,
+    message: ApiMessage,
 }
 
 /// Token usage information
@@ -154,6 +890,11 @@ struct TokenUsage {
 struct OllamaRequest {
     model: String,
     prompt: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<String>,
+    /// Base64 images for multimodal models
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    images: Vec<String>,
     stream: bool,
     options: OllamaOptions,
 }
@@ -164,6 +905,12 @@ struct OllamaOptions {
     top_p: f32,
     num_predict: i32,
     stop: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frequency_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    presence_penalty: Option<f32>,
 }
 
 /// Response from Ollama API
@@ -173,136 +920,1184 @@ struct OllamaResponse {
     done: bool,
 }
 
+/// Response from an OpenAI-style embeddings endpoint
+#[derive(Debug, Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingData {
+    #[serde(default)]
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+/// Response from Ollama's embeddings endpoint
+#[derive(Debug, Deserialize)]
+struct OllamaEmbeddingResponse {
+    embedding: Vec<f32>,
+}
+
+/// Version sent in the `anthropic-version` header
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// Request body for the Anthropic messages API
+#[derive(Debug, Serialize)]
+struct AnthropicRequest {
+    model: String,
+    max_tokens: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<String>,
+    messages: Vec<ApiMessage>,
+    temperature: f32,
+    top_p: f32,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    stop_sequences: Vec<String>,
+}
+
+/// Response from the Anthropic messages API
+#[derive(Debug, Deserialize)]
+struct AnthropicResponse {
+    content: Vec<AnthropicContent>,
+    usage: Option<AnthropicUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnthropicContent {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    text: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnthropicUsage {
+    input_tokens: usize,
+    output_tokens: usize,
+}
+
+/// Harm categories Gemini applies safety thresholds to
+const GEMINI_HARM_CATEGORIES: [&str; 4] = [
+    "HARM_CATEGORY_HARASSMENT",
+    "HARM_CATEGORY_HATE_SPEECH",
+    "HARM_CATEGORY_SEXUALLY_EXPLICIT",
+    "HARM_CATEGORY_DANGEROUS_CONTENT",
+];
+
+/// Request body for the Gemini generateContent API
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiRequest {
+    contents: Vec<GeminiContent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system_instruction: Option<GeminiContent>,
+    generation_config: GeminiGenerationConfig,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    safety_settings: Vec<GeminiSafetySetting>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct GeminiContent {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    role: Option<String>,
+    #[serde(default)]
+    parts: Vec<GeminiPart>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct GeminiPart {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    inline_data: Option<GeminiBlob>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiBlob {
+    mime_type: String,
+    data: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiGenerationConfig {
+    temperature: f32,
+    top_p: f32,
+    max_output_tokens: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    stop_sequences: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frequency_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    presence_penalty: Option<f32>,
+}
+
+#[derive(Debug, Serialize)]
+struct GeminiSafetySetting {
+    category: &'static str,
+    threshold: &'static str,
+}
+
+/// Response from the Gemini generateContent API
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiResponse {
+    #[serde(default)]
+    candidates: Vec<GeminiCandidate>,
+    prompt_feedback: Option<GeminiPromptFeedback>,
+    usage_metadata: Option<GeminiUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiCandidate {
+    content: Option<GeminiContent>,
+    finish_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiPromptFeedback {
+    block_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiUsage {
+    #[serde(default)]
+    prompt_token_count: usize,
+    #[serde(default)]
+    candidates_token_count: usize,
+}
+
+/// Requests to a provider over the shared connection pool for its endpoint
+struct ProviderHttp {
+    client: reqwest::Client,
+    headers: HeaderMap,
+    timeout: Duration,
+}
+
+impl ProviderHttp {
+    fn get(&self, url: impl IntoUrl) -> RequestBuilder {
+        self.request(Method::GET, url)
+    }
+
+    fn post(&self, url: impl IntoUrl) -> RequestBuilder {
+        self.request(Method::POST, url)
+    }
+
+    fn request(&self, method: Method, url: impl IntoUrl) -> RequestBuilder {
+        self.client.request(method, url)
+            .headers(self.headers.clone())
+            .timeout(self.timeout)
+    }
+}
+
 /// Client for interacting with LLM server
 #[derive(Debug, Clone)]
 pub struct LLMClient {
-    config: LLMConfig,
-    client: Client,
+    config: Arc<RwLock<LLMConfig>>,
+    headers: Arc<RwLock<HeaderMap>>,
+    limiter: Arc<rate_limit::RateLimiter>,
+    cache: Arc<cache::ResponseCache>,
+    pricing: Arc<RwLock<HashMap<String, ModelPricing>>>,
+    token_manager: Arc<RwLock<Option<Arc<TokenManager>>>>,
 }
 
-impl LLMClient {
-    /// Create a new LLM client
-    pub fn new(config: LLMConfig) -> Result<Self, NexaError> {
-        let mut client_builder = Client::builder()
-            .timeout(Duration::from_secs(config.timeout_secs));
+impl LLMClient {
+    /// Create a new LLM client
+    pub fn new(config: LLMConfig) -> Result<Self, NexaError> {
+        let headers = Self::build_headers(&config)?;
+        Ok(Self {
+            config: Arc::new(RwLock::new(config)),
+            headers: Arc::new(RwLock::new(headers)),
+            limiter: Arc::default(),
+            cache: Arc::default(),
+            pricing: Arc::default(),
+            token_manager: Arc::default(),
+        })
+    }
+
+    /// Headers sent with every request to the provider, including its credentials
+    fn build_headers(config: &LLMConfig) -> Result<HeaderMap, NexaError> {
+        let mut headers = HeaderMap::new();
+
+        // Configure CORS headers
+        if !config.allowed_origins.is_empty() {
+            headers.insert(
+                reqwest::header::ORIGIN,
+                config.allowed_origins.join(",").parse().unwrap()
+            );
+            if config.allow_credentials {
+                headers.insert(
+                    reqwest::header::HeaderName::from_static("access-control-allow-credentials"),
+                    "true".parse().unwrap()
+                );
+            }
+        }
+
+        // Resolve provider credentials through the secrets store
+        if let Some(secret) = &config.api_key_secret {
+            let api_key = SecretStore::open_default().resolve(secret)?;
+            let (name, value) = match (config.server_type, &config.auth_header) {
+                (ServerType::Anthropic, _) => (reqwest::header::HeaderName::from_static("x-api-key"), api_key),
+                (ServerType::Gemini, _) => (reqwest::header::HeaderName::from_static("x-goog-api-key"), api_key),
+                (ServerType::OpenAICompatible, Some(header)) if !header.eq_ignore_ascii_case("authorization") => {
+                    let name = reqwest::header::HeaderName::from_bytes(header.as_bytes())
+                        .map_err(|e| NexaError::config(format!("Invalid auth header '{}': {}", header, e)))?;
+                    (name, api_key)
+                }
+                _ => (reqwest::header::AUTHORIZATION, format!("Bearer {}", api_key)),
+            };
+            let mut value = reqwest::header::HeaderValue::from_str(&value)
+                .map_err(|e| NexaError::config(format!("Invalid API key in secret '{}': {}", secret, e)))?;
+            value.set_sensitive(true);
+            headers.insert(name, value);
+        }
+        if config.server_type == ServerType::OpenRouter {
+            for (name, value) in [("http-referer", &config.app_url), ("x-title", &config.app_title)] {
+                if let Some(value) = value {
+                    let value = reqwest::header::HeaderValue::from_str(value)
+                        .map_err(|e| NexaError::config(format!("Invalid {} header: {}", name, e)))?;
+                    headers.insert(reqwest::header::HeaderName::from_static(name), value);
+                }
+            }
+        }
+        if matches!(config.server_type, ServerType::Anthropic) {
+            headers.insert(
+                reqwest::header::HeaderName::from_static("anthropic-version"),
+                reqwest::header::HeaderValue::from_static(ANTHROPIC_VERSION),
+            );
+        }
+
+        Ok(headers)
+    }
+
+    /// Get a snapshot of the current configuration
+    pub fn config(&self) -> LLMConfig {
+        self.config.read().clone()
+    }
+
+    /// Replace the configuration, rebuilding the request headers
+    pub fn update_config(&self, config: LLMConfig) -> Result<(), NexaError> {
+        let headers = Self::build_headers(&config)?;
+        *self.headers.write() = headers;
+        *self.config.write() = config;
+        Ok(())
+    }
+
+    /// Apply LLM settings from config updates as they arrive
+    pub fn watch_config(&self, mut rx: watch::Receiver<Config>) -> tokio::task::JoinHandle<()> {
+        let llm = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let config = rx.borrow_and_update().llm.clone();
+                match llm.update_config(config) {
+                    Ok(()) => info!("Applied LLM configuration update"),
+                    Err(e) => error!("Failed to apply LLM configuration update: {}", e),
+                }
+                if rx.changed().await.is_err() {
+                    break;
+                }
+            }
+        })
+    }
+
+    fn http(&self) -> Result<ProviderHttp, NexaError> {
+        let (server_url, timeout_secs) = {
+            let config = self.config.read();
+            (config.server_url.clone(), config.timeout_secs)
+        };
+        Ok(ProviderHttp {
+            client: http_pool::shared_client(&server_url)?,
+            headers: self.headers.read().clone(),
+            timeout: Duration::from_secs(timeout_secs),
+        })
+    }
+
+    /// Check that the LLM server is reachable and responding
+    pub async fn health_check(&self) -> Result<(), NexaError> {
+        let config = self.config();
+        if matches!(config.server_type, ServerType::Bedrock | ServerType::Embedded | ServerType::Mock) {
+            return self.list_models().await.map(|_| ());
+        }
+        let response = self.http()?
+            .get(format!("{}{}", config.server_url, config.models_path()))
+            .send()
+            .await
+            .map_err(|e| request_error("Health check failed", e))?;
+        if response.status().is_server_error() {
+            return Err(NexaError::unavailable(format!("Health check failed ({})", response.status())));
+        }
+        Ok(())
+    }
+
+    /// Send a one-token completion so the server loads the model, returning how long it took
+    ///
+    /// Bypasses the response cache and cost ceiling.
+    pub async fn warmup(&self) -> Result<Duration, NexaError> {
+        let mut config = self.config();
+        config.max_tokens = 1;
+        let started = std::time::Instant::now();
+        self.chat_once(&config, &[ChatMessage::user("Hi")]).await?;
+        Ok(started.elapsed())
+    }
+
+    /// Generate text completion
+    pub async fn complete(&self, prompt: &str) -> Result<String, NexaError> {
+        self.complete_with_images(prompt, &[]).await
+    }
+
+    /// Generate text completion with per-request generation parameters
+    pub async fn complete_with_options(&self, prompt: &str, options: &CompletionOptions) -> Result<String, NexaError> {
+        self.complete_chat_with_options(&[ChatMessage::user(prompt)], options).await
+    }
+
+    /// Complete many prompts concurrently, returning results in prompt order
+    ///
+    /// At most `batch_concurrency` requests are in flight; one failure does not stop the others.
+    pub async fn complete_batch<S: AsRef<str>>(&self, prompts: &[S]) -> Vec<Result<String, NexaError>> {
+        let concurrency = self.config.read().batch_concurrency.max(1);
+        futures::stream::iter(prompts)
+            .map(|prompt| self.complete(prompt.as_ref()))
+            .buffered(concurrency)
+            .collect()
+            .await
+    }
+
+    /// Generate a completion for a prompt about the given images
+    ///
+    /// Requires a vision-capable model. Images given by URL are downloaded for
+    /// providers that only accept inline data (Ollama, Gemini).
+    pub async fn complete_with_images(&self, prompt: &str, images: &[ImageInput]) -> Result<String, NexaError> {
+        self.complete_chat(&[ChatMessage::user(prompt).with_images(images.iter().cloned())]).await
+    }
+
+    /// Set model prices used by cost estimates, keyed by model id
+    pub fn set_pricing(&self, pricing: HashMap<String, ModelPricing>) {
+        self.pricing.write().extend(pricing);
+    }
+
+    /// Record the usage of streamed completions with `token_manager`
+    ///
+    /// Providers report usage only at the end of a stream, if at all, so tokens are counted locally.
+    pub fn set_token_manager(&self, token_manager: Arc<TokenManager>) {
+        *self.token_manager.write() = Some(token_manager);
+    }
+
+    /// Estimate what completing a prompt may cost, without sending it
+    pub fn estimate(&self, prompt: &str) -> CostEstimate {
+        self.estimate_chat(&[ChatMessage::user(prompt)], &CompletionOptions::default())
+    }
+
+    /// Estimate what a chat completion with these options may cost, without sending it
+    pub fn estimate_chat(&self, messages: &[ChatMessage], options: &CompletionOptions) -> CostEstimate {
+        let mut config = self.config();
+        options.apply(&mut config);
+        fit_context(&mut config, messages);
+        self.estimate_with(&config, messages)
+    }
+
+    fn estimate_with(&self, config: &LLMConfig, messages: &[ChatMessage]) -> CostEstimate {
+        let prompt_tokens = chat_tokens(config, messages);
+        CostEstimate {
+            prompt_tokens,
+            max_completion_tokens: config.max_tokens,
+            max_cost: self.pricing.read()
+                .get(&config.model)
+                .map(|pricing| pricing.cost(prompt_tokens, config.max_tokens)),
+        }
+    }
+
+    /// Refuse a request whose estimated cost exceeds the configured ceiling
+    fn check_cost(&self, config: &LLMConfig, messages: &[ChatMessage]) -> Result<(), NexaError> {
+        let Some(ceiling) = config.max_cost else {
+            return Ok(());
+        };
+        match self.estimate_with(config, messages).max_cost {
+            Some(cost) if cost > ceiling => Err(NexaError::invalid_input(format!(
+                "Estimated cost ${:.4} of {} exceeds the ceiling of ${:.4}", cost, config.model, ceiling
+            ))),
+            Some(_) => Ok(()),
+            None => {
+                debug!("No pricing for {}, cost ceiling not enforced", config.model);
+                Ok(())
+            }
+        }
+    }
+
+    /// Prompt tokens a chat takes up for the configured model, counted locally
+    pub fn count_tokens(&self, messages: &[ChatMessage]) -> usize {
+        chat_tokens(&self.config(), messages)
+    }
+
+    /// Generate the next assistant message of a multi-turn chat
+    ///
+    /// System messages replace the configured system prompt.
+    pub async fn complete_chat(&self, messages: &[ChatMessage]) -> Result<String, NexaError> {
+        self.complete_chat_with_options(messages, &CompletionOptions::default()).await
+    }
+
+    /// Generate the next assistant message of a chat run on behalf of `agent`
+    ///
+    /// The agent's system prompt is prepended as a system message, replacing the configured one.
+    /// Its guardrails screen the user messages before the request and the reply after it.
+    /// Logs under an `agent` span, within the trace of the task being run, if any.
+    pub async fn complete_chat_for_agent(&self, agent: &Agent, messages: &[ChatMessage]) -> Result<String, NexaError> {
+        let run = async {
+            let guardrails = Guardrails::from_configs(&agent.guardrails)?;
+            let messages: Vec<ChatMessage> = agent.system_prompt.iter()
+                .map(ChatMessage::system)
+                .chain(guardrails.screen_prompt(messages).await?)
+                .collect();
+            let response = self.complete_chat(&messages).await?;
+            guardrails.screen_response(&response).await
+        };
+        run.instrument(tracing::info_span!("agent", agent_id = %agent.id)).await
+    }
+
+    /// Generate the next assistant message of a multi-turn chat with per-request generation parameters
+    ///
+    /// When `options.timeout_secs` runs out the request is dropped, closing its connection.
+    pub async fn complete_chat_with_options(
+        &self,
+        messages: &[ChatMessage],
+        options: &CompletionOptions,
+    ) -> Result<String, NexaError> {
+        let Some(secs) = options.timeout_secs else {
+            return self.chat_with_options(messages, options).await;
+        };
+        tokio::time::timeout(Duration::from_secs(secs), self.chat_with_options(messages, options))
+            .await
+            .map_err(|_| NexaError::timeout(format!("LLM request did not finish within {}s", secs)))?
+    }
+
+    /// Like `complete_chat_with_options`, giving up as soon as `cancel` is triggered
+    ///
+    /// The in-flight request is dropped, so the provider stops generating instead of
+    /// finishing a reply nobody will read.
+    pub async fn complete_chat_cancellable(
+        &self,
+        messages: &[ChatMessage],
+        options: &CompletionOptions,
+        cancel: &CancellationToken,
+    ) -> Result<String, NexaError> {
+        tokio::select! {
+            biased;
+            _ = cancel.cancelled() => Err(NexaError::cancelled("LLM request was cancelled")),
+            result = self.complete_chat_with_options(messages, options) => result,
+        }
+    }
+
+    async fn chat_with_options(&self, messages: &[ChatMessage], options: &CompletionOptions) -> Result<String, NexaError> {
+        if turns(messages).next().is_none() {
+            return Err(NexaError::invalid_input("Chat needs at least one user message"));
+        }
+        let mut config = self.config();
+        options.apply(&mut config);
+        let messages = &truncation::fit(self, &config, messages).await;
+        fit_context(&mut config, messages);
+        self.check_cost(&config, messages)?;
+        if !config.cache.enabled {
+            return config.retry.run(|| self.chat_once(&config, messages)).await;
+        }
+
+        let key = cache::cache_key(&config, messages);
+        if let Some(response) = self.cache.get(&config.cache, &key).await {
+            debug!("Serving LLM response from cache");
+            return Ok(response);
+        }
+        let response = config.retry.run(|| self.chat_once(&config, messages)).await?;
+        self.cache.put(&config.cache, &key, &response).await;
+        Ok(response)
+    }
+
+    async fn chat_once(&self, config: &LLMConfig, messages: &[ChatMessage]) -> Result<String, NexaError> {
+        self.throttle(config, chat_tokens(config, messages)).await;
+        match config.server_type {
+            ServerType::LMStudio if config.auto_load => match self.complete_lmstudio(config, messages).await {
+                Err(e) if lmstudio::is_model_not_loaded(&e) => {
+                    warn!("{} is not loaded in LM Studio, loading it: {}", config.model, e);
+                    LMStudioClient::new(config)?.switch_to(&config.model).await?;
+                    self.complete_lmstudio(config, messages).await
+                }
+                result => result,
+            },
+            ServerType::LMStudio | ServerType::Groq | ServerType::OpenAICompatible | ServerType::OpenRouter => {
+                self.complete_lmstudio(config, messages).await
+            }
+            ServerType::Ollama => self.complete_ollama(config, messages).await,
+            ServerType::Anthropic => self.complete_anthropic(config, messages).await,
+            ServerType::Gemini => self.complete_gemini(config, messages).await,
+            ServerType::Bedrock if messages.iter().any(|m| !m.images.is_empty()) => Err(NexaError::invalid_input(
+                "Image input is not supported for Bedrock",
+            )),
+            ServerType::Bedrock => self.complete_bedrock(config, messages).await,
+            ServerType::Embedded => self.complete_embedded(config, messages).await,
+            ServerType::Mock => complete_mock(config, messages),
+        }
+    }
+
+    /// Media type and base64 data of an image, downloading it if needed
+    async fn inline_image(&self, image: &ImageInput) -> Result<(String, String), NexaError> {
+        use base64::Engine;
+        match image {
+            ImageInput::Base64 { media_type, data } => Ok((media_type.clone(), data.clone())),
+            ImageInput::Url(url) => {
+                let (timeout, allow_private) = {
+                    let config = self.config.read();
+                    (Duration::from_secs(config.timeout_secs), config.allow_private_image_hosts)
+                };
+                let parsed = url::Url::parse(url)
+                    .map_err(|e| NexaError::invalid_input(format!("Invalid image URL {}: {}", url, e)))?;
+                if !matches!(parsed.scheme(), "http" | "https") {
+                    return Err(NexaError::invalid_input(format!("Image URLs must be http or https: {}", url)));
+                }
+                let host = parsed.host_str()
+                    .ok_or_else(|| NexaError::invalid_input(format!("Image URL has no host: {}", url)))?
+                    .trim_start_matches('[')
+                    .trim_end_matches(']')
+                    .to_string();
+                let port = parsed.port_or_known_default().unwrap_or(80);
+                let addrs: Vec<std::net::SocketAddr> = tokio::net::lookup_host((host.as_str(), port))
+                    .await
+                    .map_err(|e| NexaError::invalid_input(format!("Cannot resolve image host {}: {}", host, e)))?
+                    .collect();
+                let addr = *addrs.first()
+                    .ok_or_else(|| NexaError::invalid_input(format!("Cannot resolve image host {}", host)))?;
+                if !allow_private && addrs.iter().any(|addr| !is_public_address(addr.ip())) {
+                    return Err(NexaError::forbidden(format!("Image host {} is not a public address", host)));
+                }
+
+                // Image hosts get a client without the provider's credentials, pinned to the
+                // checked address and refusing redirects, which could lead anywhere
+                let response = reqwest::Client::builder()
+                    .redirect(reqwest::redirect::Policy::none())
+                    .resolve(&host, addr)
+                    .timeout(timeout)
+                    .build()
+                    .map_err(|e| NexaError::system(format!("Failed to create HTTP client: {}", e)))?
+                    .get(parsed)
+                    .send()
+                    .await
+                    .map_err(|e| request_error("Failed to download image", e))?;
+                if !response.status().is_success() {
+                    return Err(NexaError::invalid_input(format!(
+                        "Failed to download image {}: {}", url, response.status()
+                    )));
+                }
+                if response.content_length().is_some_and(|length| length > MAX_IMAGE_BYTES as u64) {
+                    return Err(NexaError::invalid_input(format!(
+                        "Image {} is larger than {} bytes", url, MAX_IMAGE_BYTES
+                    )));
+                }
+                let media_type = response.headers()
+                    .get(reqwest::header::CONTENT_TYPE)
+                    .and_then(|v| v.to_str().ok())
+                    .unwrap_or("image/png")
+                    .to_string();
+                let mut bytes = Vec::new();
+                let mut stream = response.bytes_stream();
+                while let Some(chunk) = stream.next().await {
+                    let chunk = chunk.map_err(|e| request_error("Failed to download image", e))?;
+                    if bytes.len() + chunk.len() > MAX_IMAGE_BYTES {
+                        return Err(NexaError::invalid_input(format!(
+                            "Image {} is larger than {} bytes", url, MAX_IMAGE_BYTES
+                        )));
+                    }
+                    bytes.extend_from_slice(&chunk);
+                }
+                Ok((media_type, base64::engine::general_purpose::STANDARD.encode(bytes)))
+            }
+        }
+    }
+
+    /// List the models the server offers
+    pub async fn list_models(&self) -> Result<Vec<String>, NexaError> {
+        let config = self.config();
+        if config.server_type == ServerType::Embedded {
+            // The only model is the configured GGUF file
+            embedded_available(&config)?;
+            return Ok(vec![config.model]);
+        }
+        if config.server_type == ServerType::Mock {
+            mock_available()?;
+            return Ok(vec![config.model]);
+        }
+        let path = config.models_path();
+        let (list_key, name_key) = match config.server_type {
+            ServerType::Ollama | ServerType::Gemini => ("models", "name"),
+            ServerType::Bedrock => ("modelSummaries", "modelId"),
+            _ => ("data", "id"),
+        };
+
+        let response = if config.server_type == ServerType::Bedrock {
+            // Models are listed by the control plane, not the runtime endpoint
+            let url = config.server_url.replacen("bedrock-runtime.", "bedrock.", 1);
+            self.send_bedrock(&config, reqwest::Method::GET, format!("{}{}", url, path), Vec::new()).await?
+        } else {
+            self.http()?
+                .get(format!("{}{}", config.server_url, path))
+                .send()
+                .await
+                .map_err(|e| request_error("Failed to list models", e))?
+        };
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(status_error("Model listing failed", status, text));
+        }
+
+        let body: serde_json::Value = response.json()
+            .await
+            .map_err(|e| NexaError::invalid_response(format!("Failed to parse model list: {}", e)))?;
+        Ok(body[list_key].as_array()
+            .map(|models| models.iter()
+                .filter_map(|m| m[name_key].as_str())
+                // Gemini names models as `models/<id>`
+                .map(|name| name.trim_start_matches("models/").to_string())
+                .collect())
+            .unwrap_or_default())
+    }
+
+    /// Embed each text, returning one vector per text in the same order
+    pub async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, NexaError> {
+        let config = self.config();
+        config.retry.run(|| self.embed_once(&config, texts)).await
+    }
+
+    async fn embed_once(&self, config: &LLMConfig, texts: &[String]) -> Result<Vec<Vec<f32>>, NexaError> {
+        let model = config.embedding_model.clone().unwrap_or_else(|| config.model.clone());
+        let tokens: usize = texts.iter().map(|t| tokens::count_tokens(&model, t)).sum();
+        self.limiter.acquire(&config.rate_limit, tokens.min(u32::MAX as usize) as u32).await;
+        match config.server_type {
+            ServerType::LMStudio | ServerType::Groq | ServerType::OpenAICompatible | ServerType::OpenRouter => {
+                let request = self.http()?
+                    .post(format!("{}{}", config.server_url, config.embeddings_path()))
+                    .json(&serde_json::json!({ "model": model, "input": texts }));
+                let response = self.send_respecting_rate_limits(config, request).await?;
+                if !response.status().is_success() {
+                    let status = response.status();
+                    let text = response.text().await.unwrap_or_default();
+                    return Err(status_error("Embedding request failed", status, text));
+                }
+                let mut body: EmbeddingResponse = response.json()
+                    .await
+                    .map_err(|e| NexaError::invalid_response(format!("Failed to parse embeddings: {}", e)))?;
+                body.data.sort_by_key(|item| item.index);
+                if body.data.len() != texts.len() {
+                    return Err(NexaError::invalid_response(format!(
+                        "Expected {} embeddings, got {}", texts.len(), body.data.len()
+                    )));
+                }
+                Ok(body.data.into_iter().map(|item| item.embedding).collect())
+            }
+            ServerType::Ollama => {
+                // The embeddings endpoint takes one prompt per request
+                let mut embeddings = Vec::with_capacity(texts.len());
+                for text in texts {
+                    let response = self.http()?
+                        .post(format!("{}/api/embeddings", config.server_url))
+                        .json(&serde_json::json!({ "model": model, "prompt": text }))
+                        .send()
+                        .await
+                        .map_err(|e| request_error("Failed to send request to Ollama", e))?;
+                    if !response.status().is_success() {
+                        let status = response.status();
+                        let text = response.text().await.unwrap_or_default();
+                        return Err(status_error("Ollama embedding request failed", status, text));
+                    }
+                    let body: OllamaEmbeddingResponse = response.json()
+                        .await
+                        .map_err(|e| NexaError::invalid_response(format!("Failed to parse Ollama embedding: {}", e)))?;
+                    embeddings.push(body.embedding);
+                }
+                Ok(embeddings)
+            }
+            ServerType::Mock => embed_mock(texts),
+            ServerType::Anthropic | ServerType::Gemini | ServerType::Bedrock | ServerType::Embedded => Err(NexaError::invalid_input(format!(
+                "Embeddings are not supported for {:?}", config.server_type
+            ))),
+        }
+    }
+
+    /// Per-token prices the provider reports, by model id
+    ///
+    /// Only OpenRouter publishes pricing with its model list; other providers return an empty map.
+    pub async fn model_pricing(&self) -> Result<HashMap<String, ModelPricing>, NexaError> {
+        let config = self.config();
+        if config.server_type != ServerType::OpenRouter {
+            return Ok(HashMap::new());
+        }
+        let response = self.http()?
+            .get(format!("{}{}", config.server_url, config.models_path()))
+            .send()
+            .await
+            .map_err(|e| request_error("Failed to fetch model pricing", e))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(status_error("Model pricing lookup failed", status, text));
+        }
+
+        let body: serde_json::Value = response.json()
+            .await
+            .map_err(|e| NexaError::invalid_response(format!("Failed to parse model list: {}", e)))?;
+        // Prices are decimal strings in USD per token
+        let price = |value: &serde_json::Value| value.as_str().and_then(|p| p.parse::<f64>().ok());
+        Ok(body["data"].as_array()
+            .map(|models| models.iter()
+                .filter_map(|m| Some((m["id"].as_str()?.to_string(), ModelPricing {
+                    prompt: price(&m["pricing"]["prompt"])?,
+                    completion: price(&m["pricing"]["completion"])?,
+                })))
+                .collect())
+            .unwrap_or_default())
+    }
+
+    /// Generate a completion, yielding text as the server produces it
+    ///
+    /// LMStudio and other OpenAI-style servers stream over SSE and Ollama streams NDJSON;
+    /// other providers yield the full completion as a single chunk.
+    ///
+    /// With a token manager set, usage is recorded once the stream ends or is dropped;
+    /// streams that yielded no text are not recorded.
+    pub fn complete_stream(&self, prompt: &str) -> CompletionStream {
+        let client = self.clone();
+        let prompt = prompt.to_string();
+        let mut config = self.config();
+        fit_context(&mut config, &[ChatMessage::user(&prompt)]);
+        let prompt_tokens = chat_tokens(&config, &[ChatMessage::user(&prompt)]);
+        let model = config.model.clone();
+
+        let stream = futures::stream::once(async move {
+            // Only opening the stream is retried; chunks already yielded cannot be taken back
+            config.retry.run(|| client.open_stream(&config, &prompt)).await
+        })
+            .try_flatten()
+            .boxed();
+        let Some(token_manager) = self.token_manager.read().clone() else {
+            return stream;
+        };
+        streaming::on_finish(stream, move |text| {
+            if text.is_empty() {
+                return;
+            }
+            let completion_tokens = tokens::count_tokens(&model, &text);
+            let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+                warn!("No runtime to record {} streamed tokens of {}", completion_tokens, model);
+                return;
+            };
+            runtime.spawn(async move {
+                let metadata = HashMap::from([("streamed".to_string(), "true".to_string())]);
+                if let Err(e) = token_manager
+                    .track_usage(ModelType::Custom(model), prompt_tokens, completion_tokens, metadata)
+                    .await
+                {
+                    warn!("Failed to record streamed token usage: {}", e);
+                }
+            });
+        })
+    }
+
+    async fn open_stream(&self, config: &LLMConfig, prompt: &str) -> Result<CompletionStream, NexaError> {
+        self.throttle(config, chat_tokens(config, &[ChatMessage::user(prompt)])).await;
+        match config.server_type {
+            ServerType::LMStudio | ServerType::Groq | ServerType::OpenAICompatible | ServerType::OpenRouter => {
+                let request = self.http()?
+                    .post(format!("{}{}", config.server_url, config.chat_path()))
+                    .json(&chat_request(config, &[ChatMessage::user(prompt)], true));
+                let response = self.send_respecting_rate_limits(config, request).await?;
+                if !response.status().is_success() {
+                    let status = response.status();
+                    let text = response.text().await.unwrap_or_default();
+                    return Err(status_error("LLM stream request failed", status, text));
+                }
+                Ok(streaming::sse_content(response))
+            }
+            ServerType::Ollama => {
+                let response = self.http()?
+                    .post(format!("{}/api/generate", config.server_url))
+                    .json(&ollama_request(config, prompt, true))
+                    .send()
+                    .await
+                    .map_err(|e| request_error("Failed to send request to Ollama", e))?;
+                if !response.status().is_success() {
+                    let status = response.status();
+                    let text = response.text().await.unwrap_or_default();
+                    return Err(status_error("Ollama stream request failed", status, text));
+                }
+                Ok(streaming::ndjson_content(response))
+            }
+            ServerType::Anthropic | ServerType::Gemini | ServerType::Bedrock | ServerType::Embedded | ServerType::Mock => {
+                let text = self.chat_once(config, &[ChatMessage::user(prompt)]).await?;
+                Ok(futures::stream::once(async move { Ok(text) }).boxed())
+            }
+        }
+    }
+
+    async fn complete_lmstudio(&self, config: &LLMConfig, messages: &[ChatMessage]) -> Result<String, NexaError> {
+        let request = self.http()?
+            .post(format!("{}{}", config.server_url, config.chat_path()))
+            .json(&chat_request(config, messages, false));
+        let response = self.send_respecting_rate_limits(config, request).await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await
+                .unwrap_or_else(|_| "Failed to get error response".to_string());
+            return Err(status_error("LLM request failed", status, text));
+        }
+
+        let llm_response: LLMResponse = response.json()
+            .await
+            .map_err(|e| NexaError::invalid_response(format!("Failed to parse response: {}", e)))?;
+
+        if let Some(usage) = llm_response.usage {
+            debug!(
+                "LLM usage - Prompt: {}, Completion: {}, Total: {}",
+                usage.prompt_tokens, usage.completion_tokens, usage.total_tokens
+            );
+        }
+
+        Ok(llm_response.choices.into_iter().next()
+            .ok_or_else(|| NexaError::invalid_response("No completion choices returned"))?
+            .message.content.into_text())
+    }
+
+    /// Wait for the client-side rate limit, counting the prompt and the largest possible reply
+    async fn throttle(&self, config: &LLMConfig, prompt_tokens: usize) {
+        let tokens = prompt_tokens.saturating_add(config.max_tokens).min(u32::MAX as usize) as u32;
+        self.limiter.acquire(&config.rate_limit, tokens).await;
+    }
+
+    /// Send a request, waiting out 429 responses for as long as `retry-after` asks
+    async fn send_respecting_rate_limits(
+        &self,
+        config: &LLMConfig,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, NexaError> {
+        let mut attempt = 0;
+        loop {
+            let response = request.try_clone()
+                .ok_or_else(|| NexaError::system("Request body cannot be resent"))?
+                .send()
+                .await
+                .map_err(|e| request_error("Failed to send request", e))?;
+            if response.status() != reqwest::StatusCode::TOO_MANY_REQUESTS || attempt >= config.rate_limit_retries {
+                return Ok(response);
+            }
+
+            let delay = retry_after(response.headers())
+                .unwrap_or_else(|| Duration::from_secs(1 << attempt.min(5)))
+                .min(MAX_RETRY_AFTER);
+            attempt += 1;
+            warn!(
+                "Rate limited by {}, retrying in {:?} ({}/{})",
+                config.server_url, delay, attempt, config.rate_limit_retries
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    #[cfg(feature = "llama")]
+    async fn complete_embedded(&self, config: &LLMConfig, messages: &[ChatMessage]) -> Result<String, NexaError> {
+        embedded_available(config)?;
+        embedded::complete(config, messages).await
+    }
+
+    #[cfg(not(feature = "llama"))]
+    async fn complete_embedded(&self, _config: &LLMConfig, _messages: &[ChatMessage]) -> Result<String, NexaError> {
+        Err(NexaError::config(LLAMA_FEATURE_REQUIRED))
+    }
+
+    async fn complete_ollama(&self, config: &LLMConfig, messages: &[ChatMessage]) -> Result<String, NexaError> {
+        // A single prompt goes to the generate endpoint; history needs the chat endpoint
+        let prompt = match messages {
+            [message] if message.role == ChatRole::User => message,
+            _ => return self.chat_ollama(config, messages).await,
+        };
+        let mut request = ollama_request(config, &prompt.content, false);
+        for image in &prompt.images {
+            request.images.push(self.inline_image(image).await?.1);
+        }
+
+        let response = self.http()?
+            .post(format!("{}/api/generate", config.server_url))
+            .json(&request)
+            .send()
+            .await
+            .map_err(|e| request_error("Failed to send request to Ollama", e))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await
+                .unwrap_or_else(|_| "Failed to get error response".to_string());
+            return Err(status_error("Ollama request failed", status, text));
+        }
+
+        let ollama_response: OllamaResponse = response.json()
+            .await
+            .map_err(|e| NexaError::invalid_response(format!("Failed to parse Ollama response: {}", e)))?;
+
+        if !ollama_response.done {
+            debug!("Ollama response not marked as done, but proceeding with response");
+        }
+
+        Ok(ollama_response.response)
+    }
+
+    async fn chat_ollama(&self, config: &LLMConfig, messages: &[ChatMessage]) -> Result<String, NexaError> {
+        let mut api_messages = Vec::new();
+        if let Some(system) = system_prompt(config, messages) {
+            api_messages.push(serde_json::json!({ "role": "system", "content": system }));
+        }
+        for message in turns(messages) {
+            let mut images = Vec::new();
+            for image in &message.images {
+                images.push(self.inline_image(image).await?.1);
+            }
+            api_messages.push(serde_json::json!({
+                "role": message.role.as_str(),
+                "content": message.content,
+                "images": images,
+            }));
+        }
+        let body = serde_json::json!({
+            "model": config.model,
+            "messages": api_messages,
+            "stream": false,
+            "options": ollama_request(config, "", false).options,
+        });
+
+        let response = self.http()?
+            .post(format!("{}/api/chat", config.server_url))
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| request_error("Failed to send request to Ollama", e))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(status_error("Ollama chat request failed", status, text));
+        }
+
+        let body: serde_json::Value = response.json()
+            .await
+            .map_err(|e| NexaError::invalid_response(format!("Failed to parse Ollama response: {}", e)))?;
+        body["message"]["content"].as_str()
+            .map(String::from)
+            .ok_or_else(|| NexaError::invalid_response("Ollama chat response has no message content"))
+    }
 
-        // Configure CORS headers
-        if !config.allowed_origins.is_empty() {
-            client_builder = client_builder
-                .default_headers({
-                    let mut headers = reqwest::header::HeaderMap::new();
-                    headers.insert(
-                        reqwest::header::ORIGIN,
-                        config.allowed_origins.join(",").parse().unwrap()
-                    );
-                    if config.allow_credentials {
-                        headers.insert(
-                            reqwest::header::HeaderName::from_static("access-control-allow-credentials"),
-                            "true".parse().unwrap()
-                        );
-                    }
-                    headers
-                });
+    async fn complete_anthropic(&self, config: &LLMConfig, messages: &[ChatMessage]) -> Result<String, NexaError> {
+        let response = self.http()?
+            .post(format!("{}/v1/messages", config.server_url))
+            .json(&anthropic_request(config, messages))
+            .send()
+            .await
+            .map_err(|e| request_error("Failed to send request to Anthropic", e))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await
+                .unwrap_or_else(|_| "Failed to get error response".to_string());
+            return Err(status_error("Anthropic request failed", status, text));
         }
 
-        let client = client_builder
-            .build()
-            .map_err(|e| NexaError::system(format!("Failed to create HTTP client: {}", e)))?;
+        let anthropic_response: AnthropicResponse = response.json()
+            .await
+            .map_err(|e| NexaError::invalid_response(format!("Failed to parse Anthropic response: {}", e)))?;
 
-        Ok(Self { config, client })
-    }
+        if let Some(usage) = anthropic_response.usage {
+            debug!("Anthropic usage - Input: {}, Output: {}", usage.input_tokens, usage.output_tokens);
+        }
 
-    /// Generate text completion
-    pub async fn complete(&self, prompt: &str) -> Result<String, NexaError> {
-        match self.config.server_type {
-            ServerType::LMStudio => self.complete_lmstudio(prompt).await,
-            ServerType::Ollama => self.complete_ollama(prompt).await,
+        let text: String = anthropic_response.content.iter()
+            .filter(|block| block.kind == "text")
+            .map(|block| block.text.as_str())
+            .collect();
+        if text.is_empty() {
+            return Err(NexaError::invalid_response("No text content returned"));
         }
+        Ok(text)
     }
 
-    async fn complete_lmstudio(&self, prompt: &str) -> Result<String, NexaError> {
-        let request = LLMRequest {
-            messages: vec![ChatMessage {
-                role: "user".to_string(),
-                content: prompt.to_string(),
-            }],
-            model: self.config.model.clone(),
-            temperature: self.config.temperature,
-            max_tokens: Some(self.config.max_tokens),
-            top_p: Some(self.config.top_p),
-            stop: self.config.stop.clone(),
+    async fn complete_gemini(&self, config: &LLMConfig, messages: &[ChatMessage]) -> Result<String, NexaError> {
+        let text_content = |role: Option<&str>, text: &str| GeminiContent {
+            role: role.map(String::from),
+            parts: vec![GeminiPart { text: text.to_string(), inline_data: None }],
+        };
+        let mut contents = Vec::new();
+        for message in turns(messages) {
+            // Gemini calls the assistant "model"
+            let role = if message.role == ChatRole::Assistant { "model" } else { "user" };
+            let mut content = text_content(Some(role), &message.content);
+            for image in &message.images {
+                let (mime_type, data) = self.inline_image(image).await?;
+                content.parts.push(GeminiPart { text: String::new(), inline_data: Some(GeminiBlob { mime_type, data }) });
+            }
+            contents.push(content);
+        }
+        let request = GeminiRequest {
+            contents,
+            system_instruction: system_prompt(config, messages).map(|system| text_content(None, &system)),
+            generation_config: GeminiGenerationConfig {
+                temperature: config.temperature,
+                top_p: config.top_p,
+                max_output_tokens: config.max_tokens,
+                stop_sequences: config.stop.clone(),
+                seed: config.seed,
+                frequency_penalty: config.frequency_penalty,
+                presence_penalty: config.presence_penalty,
+            },
+            safety_settings: config.safety
+                .map(|level| GEMINI_HARM_CATEGORIES.iter()
+                    .map(|category| GeminiSafetySetting { category, threshold: level.gemini_threshold() })
+                    .collect())
+                .unwrap_or_default(),
         };
 
-        let response = self.client
-            .post(format!("{}/v1/chat/completions", self.config.server_url))
+        let response = self.http()?
+            .post(format!("{}/v1beta/models/{}:generateContent", config.server_url, config.model))
             .json(&request)
             .send()
             .await
-            .map_err(|e| NexaError::system(format!("Failed to send request: {}", e)))?;
+            .map_err(|e| request_error("Failed to send request to Gemini", e))?;
 
         if !response.status().is_success() {
             let status = response.status();
             let text = response.text().await
                 .unwrap_or_else(|_| "Failed to get error response".to_string());
-            return Err(NexaError::system(format!("LLM request failed ({}): {}", status, text)));
+            return Err(status_error("Gemini request failed", status, text));
         }
 
-        let llm_response: LLMResponse = response.json()
+        let gemini_response: GeminiResponse = response.json()
             .await
-            .map_err(|e| NexaError::system(format!("Failed to parse response: {}", e)))?;
+            .map_err(|e| NexaError::invalid_response(format!("Failed to parse Gemini response: {}", e)))?;
 
-        if let Some(usage) = llm_response.usage {
-            debug!(
-                "LLM usage - Prompt: {}, Completion: {}, Total: {}",
-                usage.prompt_tokens, usage.completion_tokens, usage.total_tokens
-            );
+        if let Some(reason) = gemini_response.prompt_feedback.and_then(|f| f.block_reason) {
+            return Err(NexaError::invalid_input(format!("Gemini blocked the prompt: {}", reason)));
+        }
+        if let Some(usage) = gemini_response.usage_metadata {
+            debug!("Gemini usage - Prompt: {}, Candidates: {}", usage.prompt_token_count, usage.candidates_token_count);
         }
 
-        Ok(llm_response.choices.first()
-            .ok_or_else(|| NexaError::system("No completion choices returned"))?
-            .message.content.clone())
+        let candidate = gemini_response.candidates.into_iter().next()
+            .ok_or_else(|| NexaError::invalid_response("No candidates returned"))?;
+        let text: String = candidate.content
+            .map(|content| content.parts.into_iter().map(|part| part.text).collect())
+            .unwrap_or_default();
+        if text.is_empty() && candidate.finish_reason.as_deref() == Some("SAFETY") {
+            return Err(NexaError::invalid_response("Gemini withheld the response for safety reasons"));
+        }
+        Ok(text)
     }
 
-    async fn complete_ollama(&self, prompt: &str) -> Result<String, NexaError> {
-        let request = OllamaRequest {
-            model: self.config.model.clone(),
-            prompt: prompt.to_string(),
-            stream: false,
-            options: OllamaOptions {
-                temperature: self.config.temperature,
-                top_p: self.config.top_p,
-                num_predict: self.config.max_tokens as i32,
-                stop: self.config.stop.clone(),
+    async fn complete_bedrock(&self, config: &LLMConfig, messages: &[ChatMessage]) -> Result<String, NexaError> {
+        let text = |text: &str| bedrock::ConverseText { text: text.to_string() };
+        let request = bedrock::ConverseRequest {
+            messages: turns(messages)
+                .map(|message| bedrock::ConverseMessage {
+                    role: message.role.as_str().to_string(),
+                    content: vec![text(&message.content)],
+                })
+                .collect(),
+            system: system_prompt(config, messages).as_deref().map(text).into_iter().collect(),
+            inference_config: bedrock::InferenceConfig {
+                max_tokens: config.max_tokens,
+                temperature: config.temperature,
+                top_p: config.top_p,
+                stop_sequences: config.stop.clone(),
             },
         };
 
-        let response = self.client
-            .post(format!("{}/api/generate", self.config.server_url))
-            .json(&request)
-            .send()
-            .await
-            .map_err(|e| NexaError::system(format!("Failed to send request to Ollama: {}", e)))?;
+        let url = format!("{}/model/{}/converse", config.server_url, bedrock::uri_encode(&config.model));
+        let response = self.send_bedrock(config, reqwest::Method::POST, url, serde_json::to_vec(&request)?).await?;
 
         if !response.status().is_success() {
             let status = response.status();
             let text = response.text().await
                 .unwrap_or_else(|_| "Failed to get error response".to_string());
-            return Err(NexaError::system(format!("Ollama request failed ({}): {}", status, text)));
+            return Err(status_error("Bedrock request failed", status, text));
         }
 
-        let ollama_response: OllamaResponse = response.json()
+        let converse_response: bedrock::ConverseResponse = response.json()
             .await
-            .map_err(|e| NexaError::system(format!("Failed to parse Ollama response: {}", e)))?;
+            .map_err(|e| NexaError::invalid_response(format!("Failed to parse Bedrock response: {}", e)))?;
 
-        if !ollama_response.done {
-            debug!("Ollama response not marked as done, but proceeding with response");
+        if let Some(usage) = converse_response.usage {
+            debug!("Bedrock usage - Input: {}, Output: {}", usage.input_tokens, usage.output_tokens);
         }
 
-        Ok(ollama_response.response)
+        Ok(converse_response.output.message.content.into_iter().map(|c| c.text).collect())
+    }
+
+    /// Send a Bedrock request, SigV4-signed unless an API key is configured
+    async fn send_bedrock(
+        &self,
+        config: &LLMConfig,
+        method: reqwest::Method,
+        url: String,
+        body: Vec<u8>,
+    ) -> Result<reqwest::Response, NexaError> {
+        let url = url::Url::parse(&url)
+            .map_err(|e| NexaError::config(format!("Invalid Bedrock URL '{}': {}", url, e)))?;
+        let mut request = self.http()?
+            .request(method.clone(), url.clone())
+            .header(reqwest::header::CONTENT_TYPE, "application/json");
+
+        if config.api_key_secret.is_none() {
+            let credentials = bedrock::AwsCredentials::resolve(&SecretStore::open_default())?;
+            let region = config.region.as_deref().unwrap_or("us-east-1");
+            let signer = bedrock::Signer { credentials: &credentials, region, service: bedrock::SERVICE };
+            let headers = signer.sign(
+                method.as_str(),
+                &url,
+                &[("content-type", "application/json")],
+                &body,
+                chrono::Utc::now(),
+            )?;
+            for (name, value) in headers {
+                request = request.header(name, value);
+            }
+        }
+
+        request.body(body)
+            .send()
+            .await
+            .map_err(|e| request_error("Failed to send request to Bedrock", e))
+    }
+
+    /// Offer tools to the model, returning its text and any tool calls it makes
+    pub async fn complete_with_tools(&self, prompt: &str, tools: &[ToolSpec]) -> Result<ToolCompletion, NexaError> {
+        self.tool_completion(&[ChatMessage::user(prompt)], tools, None).await
+    }
+
+    /// Offer tools to the model at the next turn of a chat, which may include earlier tool calls and results
+    pub async fn complete_chat_with_tools(&self, messages: &[ChatMessage], tools: &[ToolSpec]) -> Result<ToolCompletion, NexaError> {
+        self.tool_completion(messages, tools, None).await
+    }
+
+    /// Make the model call `tool`, returning the arguments it chose
+    pub async fn call_tool<A: DeserializeOwned>(&self, prompt: &str, tool: &ToolSpec) -> Result<A, NexaError> {
+        let completion = self.tool_completion(&[ChatMessage::user(prompt)], std::slice::from_ref(tool), Some(&tool.name)).await?;
+        completion.tool_calls.iter()
+            .find(|call| call.name == tool.name)
+            .ok_or_else(|| NexaError::invalid_response(format!("Model did not call tool '{}'", tool.name)))?
+            .args()
     }
 
-    /// Generate function call
-    pub async fn call_function<T: Serialize, R: for<'de> Deserialize<'de>>(
+    /// Have the model evaluate a function call, returning the result
+    ///
+    /// The model reports the result through a forced tool call named after the function.
+    /// Providers without tool calling, and models that answer in text, have the JSON
+    /// extracted from their reply instead.
+    pub async fn call_function<T: Serialize, R: DeserializeOwned>(
         &self,
         function_name: &str,
         args: &T,
@@ -310,11 +2105,24 @@ impl LLMClient {
         let prompt = format!(
             "Call function '{}' with arguments: {}. Return ONLY a valid JSON object containing the result. For example, if calculating a sum, return: {{\"sum\": 42}}",
             function_name,
-            serde_json::to_string(args)
-                .map_err(|e| NexaError::system(format!("Failed to serialize arguments: {}", e)))?
+            serde_json::to_string(args)?
         );
 
-        let response = self.complete(&prompt).await?;
+        let response = if matches!(self.config().server_type, ServerType::Gemini | ServerType::Bedrock | ServerType::Embedded | ServerType::Mock) {
+            self.complete(&prompt).await?
+        } else {
+            let tool = ToolSpec {
+                name: function_name.to_string(),
+                description: format!("Report the result of calling {}", function_name),
+                input_schema: serde_json::json!({ "type": "object" }),
+            };
+            let completion = self.tool_completion(&[ChatMessage::user(&prompt)], std::slice::from_ref(&tool), Some(function_name)).await?;
+            if let Some(call) = completion.tool_calls.iter().find(|call| call.name == function_name) {
+                return call.args();
+            }
+            completion.content
+                .ok_or_else(|| NexaError::invalid_response("Model returned neither a tool call nor text"))?
+        };
 
         // Try to extract JSON from the response if it's wrapped in code blocks
         let json_str = if response.contains("```json") {
@@ -335,7 +2143,98 @@ impl LLMClient {
         };
 
         serde_json::from_str(json_str)
-            .map_err(|e| NexaError::system(format!("Failed to parse function response: {}", e)))
+            .map_err(|e| NexaError::invalid_response(format!("Failed to parse function response: {}", e)))
+    }
+
+    async fn tool_completion(
+        &self,
+        messages: &[ChatMessage],
+        tools: &[ToolSpec],
+        force: Option<&str>,
+    ) -> Result<ToolCompletion, NexaError> {
+        let mut config = self.config();
+        fit_context(&mut config, messages);
+        config.retry.run(|| self.tool_completion_once(&config, messages, tools, force)).await
+    }
+
+    async fn tool_completion_once(
+        &self,
+        config: &LLMConfig,
+        messages: &[ChatMessage],
+        tools: &[ToolSpec],
+        force: Option<&str>,
+    ) -> Result<ToolCompletion, NexaError> {
+        self.throttle(config, chat_tokens(config, messages)).await;
+        let turns: Vec<&ChatMessage> = turns(messages).collect();
+        let response = match config.server_type {
+            ServerType::LMStudio | ServerType::Groq | ServerType::OpenAICompatible | ServerType::OpenRouter => {
+                let mut body = serde_json::to_value(chat_request(config, messages, false))?;
+                tool_calling::patch_openai_messages(&mut body["messages"], &turns, true);
+                body["tools"] = tool_calling::openai_tools(tools);
+                if let Some(name) = force {
+                    body["tool_choice"] = tool_calling::openai_tool_choice(name);
+                }
+                let request = self.http()?
+                    .post(format!("{}{}", config.server_url, config.chat_path()))
+                    .json(&body);
+                self.send_respecting_rate_limits(config, request).await?
+            }
+            ServerType::Ollama => {
+                // Ollama has no tool_choice; offering only the forced tool comes closest
+                let offered: Vec<ToolSpec> = tools.iter()
+                    .filter(|tool| force.is_none_or(|name| tool.name == name))
+                    .cloned()
+                    .collect();
+                let mut api_messages = serde_json::to_value(chat_request(config, messages, false).messages)?;
+                tool_calling::patch_openai_messages(&mut api_messages, &turns, false);
+                let body = serde_json::json!({
+                    "model": config.model,
+                    "messages": api_messages,
+                    "stream": false,
+                    "tools": tool_calling::openai_tools(&offered),
+                    "options": ollama_request(config, "", false).options,
+                });
+                self.http()?
+                    .post(format!("{}/api/chat", config.server_url))
+                    .json(&body)
+                    .send()
+                    .await
+                    .map_err(|e| request_error("Failed to send request to Ollama", e))?
+            }
+            ServerType::Anthropic => {
+                let mut body = serde_json::to_value(anthropic_request(config, messages))?;
+                body["messages"] = tool_calling::anthropic_messages(body["messages"].take(), &turns);
+                body["tools"] = tool_calling::anthropic_tools(tools);
+                if let Some(name) = force {
+                    body["tool_choice"] = tool_calling::anthropic_tool_choice(name);
+                }
+                self.http()?
+                    .post(format!("{}/v1/messages", config.server_url))
+                    .json(&body)
+                    .send()
+                    .await
+                    .map_err(|e| request_error("Failed to send request to Anthropic", e))?
+            }
+            ServerType::Gemini | ServerType::Bedrock | ServerType::Embedded | ServerType::Mock => {
+                return Err(NexaError::invalid_input(format!(
+                    "Tool calling is not supported for {:?}", config.server_type
+                )));
+            }
+        };
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(status_error("Tool call request failed", status, text));
+        }
+        let body: serde_json::Value = response.json()
+            .await
+            .map_err(|e| NexaError::invalid_response(format!("Failed to parse tool call response: {}", e)))?;
+        match config.server_type {
+            ServerType::Anthropic => tool_calling::parse_anthropic(&body),
+            ServerType::Ollama => tool_calling::parse_message(&body["message"]),
+            _ => tool_calling::parse_message(&body["choices"][0]["message"]),
+        }
     }
 
     /// Generate reasoning about a topic
@@ -352,6 +2251,253 @@ impl LLMClient {
     }
 }
 
+/// Longest wait honoured from a `retry-after` header
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// The conversation's own system prompt, else the configured one
+fn system_prompt(config: &LLMConfig, messages: &[ChatMessage]) -> Option<String> {
+    let system: Vec<&str> = messages.iter()
+        .filter(|m| m.role == ChatRole::System)
+        .map(|m| m.content.as_str())
+        .collect();
+    if system.is_empty() {
+        config.system_prompt.clone()
+    } else {
+        Some(system.join("\n\n"))
+    }
+}
+
+/// User and assistant turns, without system messages
+fn turns(messages: &[ChatMessage]) -> impl Iterator<Item = &ChatMessage> {
+    messages.iter().filter(|m| m.role != ChatRole::System)
+}
+
+/// Prompt tokens of a chat as sent, including the system prompt
+fn chat_tokens(config: &LLMConfig, messages: &[ChatMessage]) -> usize {
+    let system = system_prompt(config, messages);
+    let system = system.iter().map(|content| ("system", content.as_str()));
+    let turns = turns(messages).map(|m| (m.role.as_str(), m.content.as_str()));
+    tokens::count_chat_tokens(&config.model, system.chain(turns))
+}
+
+/// Shrink `max_tokens` so prompt and reply fit the model's context window,
+/// warning when the prompt alone does not fit
+fn fit_context(config: &mut LLMConfig, messages: &[ChatMessage]) {
+    let prompt_tokens = chat_tokens(config, messages);
+    let Some(window) = config.context_window.or_else(|| tokens::context_window(&config.model)) else {
+        return;
+    };
+    if prompt_tokens >= window {
+        warn!(
+            "Prompt of about {} tokens exceeds the {}-token context window of {}",
+            prompt_tokens, window, config.model
+        );
+    } else if prompt_tokens + config.max_tokens > window {
+        debug!("Reducing max_tokens to {} to fit the context window of {}", window - prompt_tokens, config.model);
+        config.max_tokens = window - prompt_tokens;
+    }
+}
+
+/// Chat completions body for OpenAI-style servers
+fn chat_request(config: &LLMConfig, messages: &[ChatMessage], stream: bool) -> LLMRequest {
+    let system = system_prompt(config, messages).map(|system| ApiMessage::text("system", &system));
+    let messages = system.into_iter()
+        .chain(turns(messages).map(|message| {
+            let mut api = ApiMessage::text(message.role.as_str(), &message.content);
+            if !message.images.is_empty() {
+                let images = message.images.iter()
+                    .map(|image| ContentPart::ImageUrl { image_url: ImageUrl { url: image.url() } });
+                api.content = MessageContent::Parts(
+                    std::iter::once(ContentPart::Text { text: message.content.clone() }).chain(images).collect(),
+                );
+            }
+            api
+        }))
+        .collect();
+    LLMRequest {
+        messages,
+        model: config.model.clone(),
+        temperature: config.temperature,
+        max_tokens: Some(config.max_tokens),
+        top_p: Some(config.top_p),
+        stop: config.stop.clone(),
+        seed: config.seed,
+        frequency_penalty: config.frequency_penalty,
+        presence_penalty: config.presence_penalty,
+        logit_bias: config.logit_bias.clone(),
+        stream,
+    }
+}
+
+/// Messages API body for Anthropic
+fn anthropic_request(config: &LLMConfig, messages: &[ChatMessage]) -> AnthropicRequest {
+    let system = system_prompt(config, messages);
+    let messages = turns(messages)
+        .map(|message| {
+            let mut api = ApiMessage::text(message.role.as_str(), &message.content);
+            if !message.images.is_empty() {
+                // Anthropic expects images before the text that refers to them
+                let images = message.images.iter().map(|image| ContentPart::Image {
+                    source: match image {
+                        ImageInput::Url(url) => ImageSource::Url { url: url.clone() },
+                        ImageInput::Base64 { media_type, data } => ImageSource::Base64 {
+                            media_type: media_type.clone(),
+                            data: data.clone(),
+                        },
+                    },
+                });
+                api.content = MessageContent::Parts(
+                    images.chain(std::iter::once(ContentPart::Text { text: message.content.clone() })).collect(),
+                );
+            }
+            api
+        })
+        .collect();
+    AnthropicRequest {
+        model: config.model.clone(),
+        max_tokens: config.max_tokens,
+        system,
+        messages,
+        temperature: config.temperature,
+        top_p: config.top_p,
+        stop_sequences: config.stop.clone(),
+    }
+}
+
+/// Generate body for Ollama
+fn ollama_request(config: &LLMConfig, prompt: &str, stream: bool) -> OllamaRequest {
+    OllamaRequest {
+        model: config.model.clone(),
+        prompt: prompt.to_string(),
+        system: config.system_prompt.clone(),
+        images: Vec::new(),
+        stream,
+        options: OllamaOptions {
+            temperature: config.temperature,
+            top_p: config.top_p,
+            num_predict: config.max_tokens as i32,
+            stop: config.stop.clone(),
+            seed: config.seed,
+            frequency_penalty: config.frequency_penalty,
+            presence_penalty: config.presence_penalty,
+        },
+    }
+}
+
+/// Parse `retry-after` as seconds or an HTTP date
+fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let value = headers.get(reqwest::header::RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = value.parse::<f64>() {
+        return Duration::try_from_secs_f64(secs).ok();
+    }
+    let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    (at.with_timezone(&chrono::Utc) - chrono::Utc::now()).to_std().ok()
+}
+
+const LLAMA_FEATURE_REQUIRED: &str = "Embedded models need nexa-core built with the `llama` feature";
+
+/// Check that an embedded model can run: the `llama` feature is built in and the GGUF file exists
+pub fn embedded_available(config: &LLMConfig) -> Result<(), NexaError> {
+    if !cfg!(feature = "llama") {
+        return Err(NexaError::config(LLAMA_FEATURE_REQUIRED));
+    }
+    if !std::path::Path::new(&config.model).is_file() {
+        return Err(NexaError::not_found(format!("GGUF model {} does not exist", config.model)));
+    }
+    Ok(())
+}
+
+const MOCK_FEATURE_REQUIRED: &str = "The mock provider needs nexa-core built with the `mock` feature";
+
+/// Check that the mock provider is built in
+pub fn mock_available() -> Result<(), NexaError> {
+    if !cfg!(any(test, feature = "mock")) {
+        return Err(NexaError::config(MOCK_FEATURE_REQUIRED));
+    }
+    Ok(())
+}
+
+#[cfg(any(test, feature = "mock"))]
+fn complete_mock(config: &LLMConfig, messages: &[ChatMessage]) -> Result<String, NexaError> {
+    Ok(mock::complete(config, messages))
+}
+
+#[cfg(not(any(test, feature = "mock")))]
+fn complete_mock(_config: &LLMConfig, _messages: &[ChatMessage]) -> Result<String, NexaError> {
+    Err(NexaError::config(MOCK_FEATURE_REQUIRED))
+}
+
+#[cfg(any(test, feature = "mock"))]
+fn embed_mock(texts: &[String]) -> Result<Vec<Vec<f32>>, NexaError> {
+    Ok(texts.iter().map(|text| mock::embed(text)).collect())
+}
+
+#[cfg(not(any(test, feature = "mock")))]
+fn embed_mock(_texts: &[String]) -> Result<Vec<Vec<f32>>, NexaError> {
+    Err(NexaError::config(MOCK_FEATURE_REQUIRED))
+}
+
+/// Largest image downloaded for inlining
+const MAX_IMAGE_BYTES: usize = 20 * 1024 * 1024;
+
+/// Whether an address is reachable on the public internet, rather than loopback, link-local or private
+fn is_public_address(ip: std::net::IpAddr) -> bool {
+    use std::net::IpAddr;
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            !(v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                || v4.is_multicast()
+                || v4.is_documentation()
+                || a == 0
+                // Shared address space of carrier-grade NAT, 100.64.0.0/10
+                || (a == 100 && (b & 0xc0) == 64))
+        }
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_public_address(IpAddr::V4(v4)),
+            None => {
+                let first = v6.segments()[0];
+                !(v6.is_loopback()
+                    || v6.is_unspecified()
+                    || v6.is_multicast()
+                    // Unique local, fc00::/7
+                    || (first & 0xfe00) == 0xfc00
+                    // Link-local, fe80::/10
+                    || (first & 0xffc0) == 0xfe80)
+            }
+        },
+    }
+}
+
+/// Classify a transport failure from an LLM request
+fn request_error(context: &str, e: reqwest::Error) -> NexaError {
+    if e.is_timeout() {
+        NexaError::timeout(format!("{}: {}", context, e))
+    } else if e.is_connect() || e.is_request() {
+        NexaError::unavailable(format!("{}: {}", context, e))
+    } else {
+        NexaError::system(format!("{}: {}", context, e))
+    }
+}
+
+/// Classify an unsuccessful response from an LLM server
+fn status_error(context: &str, status: reqwest::StatusCode, text: String) -> NexaError {
+    let msg = format!("{} ({}): {}", context, status, text);
+    if status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        NexaError::unavailable(msg)
+    } else if status == reqwest::StatusCode::NOT_FOUND {
+        NexaError::not_found(msg)
+    } else if status.is_client_error() {
+        NexaError::invalid_input(msg)
+    } else {
+        NexaError::system(msg)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -381,7 +2527,7 @@ mod tests {
                 assert!(contains_answer, "Response did not contain the expected answer: {}", response);
             }
             Ok(Err(e)) => {
-                if e.to_string().contains("connection refused") || e.to_string().contains("Failed to send request") {
+                if e.is_retryable() {
                     println!("Skipping test: LLM server not available");
                     return;
                 }
@@ -423,11 +2569,11 @@ mod tests {
                 assert_eq!(response.sum, 8);
             }
             Ok(Err(e)) => {
-                if e.to_string().contains("connection refused") || e.to_string().contains("Failed to send request") {
+                if e.is_retryable() {
                     println!("Skipping test: LLM server not available");
                     return;
                 }
-                if e.to_string().contains("Failed to parse function response") {
+                if matches!(e, NexaError::InvalidResponse(_)) {
                     println!("Response format was not as expected: {}", e);
                     return;
                 }
@@ -462,7 +2608,7 @@ mod tests {
                 println!("Reasoning Response: {}", response);
             }
             Ok(Err(e)) => {
-                if e.to_string().contains("connection refused") || e.to_string().contains("Failed to send request") {
+                if e.is_retryable() {
                     println!("Skipping test: LLM server not available");
                     return;
                 }
@@ -502,7 +2648,7 @@ mod tests {
         match result {
             Ok(Ok(_)) => (),
             Ok(Err(e)) => {
-                if e.to_string().contains("connection refused") || e.to_string().contains("Failed to send request") {
+                if e.is_retryable() {
                     println!("Skipping test: Custom server not available");
                     return;
                 }
@@ -537,11 +2683,11 @@ mod tests {
                 assert!(contains_rust, "Response did not contain Rust code: {}", response);
             }
             Ok(Err(e)) => {
-                if e.to_string().contains("connection refused") || e.to_string().contains("Failed to send request") {
+                if e.is_retryable() {
                     println!("Skipping test: Ollama server not available");
                     return;
                 }
-                if e.to_string().contains("model") && e.to_string().contains("not found") {
+                if matches!(e, NexaError::NotFound(_)) {
                     println!("Skipping test: Ollama model not installed");
                     return;
                 }
@@ -554,6 +2700,674 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_anthropic_completion() {
+        let (url, recorded) = test_utils::start_recording_server(serde_json::json!({
+            "id": "msg_1",
+            "type": "message",
+            "role": "assistant",
+            "content": [{ "type": "text", "text": "4" }],
+            "stop_reason": "end_turn",
+            "usage": { "input_tokens": 12, "output_tokens": 1 }
+        })).await;
+        let config = LLMConfig {
+            server_url: url,
+            api_key_secret: None,
+            ..LLMConfig::with_anthropic("claude-3-5-haiku-latest").with_system_prompt("Answer tersely")
+        };
+        let client = LLMClient::new(config).unwrap();
+
+        assert_eq!(client.complete("What is 2+2?").await.unwrap(), "4");
+
+        let request = recorded.lock()[0].clone();
+        assert_eq!(request.path, "/v1/messages");
+        assert_eq!(request.headers["anthropic-version"], ANTHROPIC_VERSION);
+        assert_eq!(request.body["system"], "Answer tersely");
+        assert_eq!(request.body["max_tokens"], 1000);
+        assert_eq!(request.body["messages"][0]["role"], "user");
+
+        let server_type: ServerType = serde_json::from_str("\"anthropic\"").unwrap();
+        assert!(matches!(server_type, ServerType::Anthropic));
+    }
+
+    #[tokio::test]
+    async fn test_gemini_completion() {
+        let (url, recorded) = test_utils::start_recording_server(serde_json::json!({
+            "candidates": [{
+                "content": { "role": "model", "parts": [{ "text": "Hello" }, { "text": " there" }] },
+                "finishReason": "STOP"
+            }],
+            "models": [{ "name": "models/gemini-1.5-flash" }, { "name": "models/gemini-1.5-pro" }],
+            "usageMetadata": { "promptTokenCount": 3, "candidatesTokenCount": 2 }
+        })).await;
+        let config = LLMConfig {
+            server_url: url,
+            api_key_secret: None,
+            ..LLMConfig::with_gemini("gemini-1.5-flash").with_safety(SafetyLevel::BlockFew)
+        };
+        let client = LLMClient::new(config).unwrap();
+
+        assert_eq!(client.complete("Say hello").await.unwrap(), "Hello there");
+        assert_eq!(client.list_models().await.unwrap(), vec!["gemini-1.5-flash", "gemini-1.5-pro"]);
+
+        let request = recorded.lock()[0].clone();
+        assert_eq!(request.path, "/v1beta/models/gemini-1.5-flash:generateContent");
+        assert_eq!(request.body["contents"][0]["parts"][0]["text"], "Say hello");
+        assert_eq!(request.body["generationConfig"]["maxOutputTokens"], 1000);
+        assert_eq!(request.body["safetySettings"].as_array().unwrap().len(), 4);
+        assert_eq!(request.body["safetySettings"][0]["threshold"], "BLOCK_ONLY_HIGH");
+        assert!(matches!("Gemini".parse::<ServerType>(), Ok(ServerType::Gemini)));
+    }
+
+    #[tokio::test]
+    async fn test_bedrock_completion() {
+        let (url, recorded) = test_utils::start_recording_server(serde_json::json!({
+            "output": { "message": { "role": "assistant", "content": [{ "text": "Bonjour" }] } },
+            "stopReason": "end_turn",
+            "usage": { "inputTokens": 5, "outputTokens": 2, "totalTokens": 7 }
+        })).await;
+        std::env::set_var("AWS_ACCESS_KEY_ID", "AKIDEXAMPLE");
+        std::env::set_var("AWS_SECRET_ACCESS_KEY", "secret");
+        let config = LLMConfig {
+            server_url: url,
+            ..LLMConfig::with_bedrock("anthropic.claude-3-haiku-20240307-v1:0", "eu-west-1")
+        };
+        let client = LLMClient::new(config).unwrap();
+
+        assert_eq!(client.complete("Say hello in French").await.unwrap(), "Bonjour");
+
+        let request = recorded.lock()[0].clone();
+        assert_eq!(request.path, "/model/anthropic.claude-3-haiku-20240307-v1%3A0/converse");
+        assert_eq!(request.body["inferenceConfig"]["maxTokens"], 1000);
+        let authorization = request.headers["authorization"].to_str().unwrap();
+        assert!(authorization.starts_with("AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/"));
+        assert!(authorization.contains("/eu-west-1/bedrock/aws4_request"));
+    }
+
+    #[tokio::test]
+    async fn test_groq_rate_limit_retry() {
+        use axum::http::StatusCode as HttpStatus;
+        use axum::response::IntoResponse;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let handler_calls = calls.clone();
+        let router = axum::Router::new().route("/openai/v1/chat/completions", axum::routing::post(move || {
+            let calls = handler_calls.clone();
+            async move {
+                if calls.fetch_add(1, Ordering::SeqCst) < 2 {
+                    return (HttpStatus::TOO_MANY_REQUESTS, [("retry-after", "0.05")], "slow down").into_response();
+                }
+                axum::Json(serde_json::json!({
+                    "choices": [{ "message": { "role": "assistant", "content": "done" } }]
+                })).into_response()
+            }
+        }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+        let config = LLMConfig {
+            server_url: format!("http://{}/openai", addr),
+            api_key_secret: None,
+            ..LLMConfig::with_groq("llama-3.1-8b-instant")
+        };
+        let client = LLMClient::new(config.clone()).unwrap();
+        assert_eq!(client.complete("hi").await.unwrap(), "done");
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        // Without retries the 429 is reported as a retryable error
+        calls.store(0, Ordering::SeqCst);
+        let client = LLMClient::new(LLMConfig { rate_limit_retries: 0, retry: RetryPolicy::none(), ..config }).unwrap();
+        assert!(client.complete("hi").await.unwrap_err().is_retryable());
+    }
+
+    #[tokio::test]
+    async fn test_openai_compatible_paths() {
+        let (url, recorded) = test_utils::start_recording_server(serde_json::json!({
+            "choices": [{ "message": { "role": "assistant", "content": "ok" } }],
+            "data": [{ "id": "mistral-7b" }]
+        })).await;
+        std::env::set_var("NEXA_TEST_LITELLM_KEY", "sk-local");
+        let config = LLMConfig::with_openai_compatible(&url, "mistral-7b")
+            .with_paths("/chat/completions", "/models")
+            .with_auth_header("api-key")
+            .with_api_key_secret("NEXA_TEST_LITELLM_KEY");
+        let client = LLMClient::new(config).unwrap();
+
+        assert_eq!(client.complete("ping").await.unwrap(), "ok");
+        assert_eq!(client.list_models().await.unwrap(), vec!["mistral-7b"]);
+
+        let requests = recorded.lock().clone();
+        assert_eq!(requests[0].path, "/chat/completions");
+        assert_eq!(requests[0].headers["api-key"], "sk-local");
+        assert!(requests[0].headers.get("authorization").is_none());
+        assert_eq!(requests[1].path, "/models");
+    }
+
+    #[tokio::test]
+    async fn test_watch_config() {
+        let reply = serde_json::json!({ "choices": [{ "message": { "role": "assistant", "content": "ok" } }] });
+        let (first_url, first) = test_utils::start_recording_server(reply.clone()).await;
+        let (second_url, second) = test_utils::start_recording_server(reply).await;
+        let config = |url: &str| Config { llm: LLMConfig::with_lmstudio_server(url), ..Config::default() };
+        let (tx, rx) = watch::channel(config(&first_url));
+        let client = LLMClient::new(config(&first_url).llm).unwrap();
+        let watcher = client.watch_config(rx);
+        assert_eq!(client.complete("ping").await.unwrap(), "ok");
+
+        // The next completion goes to the endpoint of the update
+        tx.send(config(&second_url)).unwrap();
+        for _ in 0..50 {
+            if client.config().server_url == second_url {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(client.complete("ping").await.unwrap(), "ok");
+        assert_eq!(first.lock().len(), 1);
+        assert_eq!(second.lock().len(), 1);
+        watcher.abort();
+    }
+
+    #[tokio::test]
+    async fn test_openrouter_pricing() {
+        let (url, recorded) = test_utils::start_recording_server(serde_json::json!({
+            "data": [
+                { "id": "anthropic/claude-3.5-haiku", "pricing": { "prompt": "0.0000008", "completion": "0.000004" } },
+                { "id": "broken/model", "pricing": {} }
+            ]
+        })).await;
+        std::env::set_var("NEXA_TEST_OPENROUTER_KEY", "sk-or-test");
+        let mut config = LLMConfig::with_openrouter("anthropic/claude-3.5-haiku")
+            .with_app_attribution("https://nexa.example", "Nexa")
+            .with_api_key_secret("NEXA_TEST_OPENROUTER_KEY");
+        config.server_url = url;
+        let client = LLMClient::new(config).unwrap();
+
+        let pricing = client.model_pricing().await.unwrap();
+        assert_eq!(pricing.len(), 1);
+        assert_eq!(pricing["anthropic/claude-3.5-haiku"], ModelPricing { prompt: 0.0000008, completion: 0.000004 });
+
+        let requests = recorded.lock().clone();
+        assert_eq!(requests[0].path, "/v1/models");
+        assert_eq!(requests[0].headers["http-referer"], "https://nexa.example");
+        assert_eq!(requests[0].headers["x-title"], "Nexa");
+        assert_eq!(requests[0].headers["authorization"], "Bearer sk-or-test");
+    }
+
+    #[tokio::test]
+    async fn test_complete_stream() {
+        let sse = test_utils::start_streaming_server(concat!(
+            ": keep-alive\n\n",
+            "data: {\"choices\":[{\"delta\":{\"role\":\"assistant\"}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\"Hel\"}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\"lo\"}}]}\r\n\r\n",
+            "data: [DONE]\n\n",
+        )).await;
+        let client = LLMClient::new(LLMConfig::with_lmstudio_server(sse)).unwrap();
+        let chunks: Vec<String> = client.complete_stream("hi").try_collect().await.unwrap();
+        assert_eq!(chunks, vec!["Hel", "lo"]);
+
+        let ndjson = test_utils::start_streaming_server(concat!(
+            "{\"response\":\"Hi\",\"done\":false}\n",
+            "{\"response\":\" there\",\"done\":false}\n",
+            "{\"response\":\"\",\"done\":true}\n",
+        )).await;
+        let mut config = LLMConfig::with_ollama_server("llama3.2");
+        config.server_url = ndjson;
+        let client = LLMClient::new(config).unwrap();
+        let chunks: Vec<String> = client.complete_stream("hi").try_collect().await.unwrap();
+        assert_eq!(chunks, vec!["Hi", " there"]);
+
+        // Errors surface as stream items
+        let client = LLMClient::new(LLMConfig::with_lmstudio_server("http://127.0.0.1:1")).unwrap();
+        let mut stream = client.complete_stream("hi");
+        assert!(stream.next().await.unwrap().is_err());
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_stream_usage_tracking() {
+        let sse = test_utils::start_streaming_server(concat!(
+            "data: {\"choices\":[{\"delta\":{\"content\":\"Hello\"}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\" world\"}}]}\n\n",
+            "data: [DONE]\n\n",
+        )).await;
+        let token_manager = Arc::new(TokenManager::new(Arc::new(crate::memory::MemoryManager::new())));
+        let client = LLMClient::new(LLMConfig::with_openai_compatible(sse, "gpt-4")).unwrap();
+        client.set_token_manager(token_manager.clone());
+        let model = ModelType::Custom("gpt-4".to_string());
+
+        let chunks: Vec<String> = client.complete_stream("Say hello").try_collect().await.unwrap();
+        assert_eq!(chunks.concat(), "Hello world");
+        let mut usage = token_manager.get_usage_by_model(model.clone()).await;
+        for _ in 0..50 {
+            if usage.total_tokens > 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            usage = token_manager.get_usage_by_model(model.clone()).await;
+        }
+        assert_eq!(usage.completion_tokens, tokens::count_tokens("gpt-4", "Hello world"));
+        assert_eq!(usage.prompt_tokens, client.count_tokens(&[ChatMessage::user("Say hello")]));
+
+        // A stream abandoned after its first chunk still counts what was received
+        let mut stream = client.complete_stream("Say hello");
+        assert_eq!(stream.next().await.unwrap().unwrap(), "Hello");
+        drop(stream);
+        for _ in 0..50 {
+            if token_manager.get_usage_by_model(model.clone()).await.completion_tokens > usage.completion_tokens {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let total = token_manager.get_usage_by_model(model).await;
+        assert_eq!(total.completion_tokens - usage.completion_tokens, tokens::count_tokens("gpt-4", "Hello"));
+    }
+
+    #[tokio::test]
+    async fn test_native_tool_call() {
+        let (url, recorded) = test_utils::start_recording_server(serde_json::json!({
+            "choices": [{
+                "message": {
+                    "role": "assistant",
+                    "content": null,
+                    "tool_calls": [{
+                        "id": "call_1",
+                        "type": "function",
+                        "function": { "name": "add_numbers", "arguments": "{\"x\": 5, \"y\": 3}" }
+                    }]
+                }
+            }]
+        })).await;
+        let client = LLMClient::new(LLMConfig::with_lmstudio_server(url)).unwrap();
+
+        #[derive(Debug, Deserialize, JsonSchema, PartialEq)]
+        struct AddArgs {
+            x: i32,
+            y: i32,
+        }
+
+        let tool = ToolSpec::for_args::<AddArgs>("add_numbers", "Add two numbers");
+        let args: AddArgs = client.call_tool("What is 5 + 3?", &tool).await.unwrap();
+        assert_eq!(args, AddArgs { x: 5, y: 3 });
+
+        let body = recorded.lock()[0].body.clone();
+        assert_eq!(body["tools"][0]["function"]["name"], "add_numbers");
+        assert_eq!(body["tools"][0]["function"]["parameters"]["properties"]["x"]["type"], "integer");
+        assert_eq!(body["tool_choice"]["function"]["name"], "add_numbers");
+
+        let completion = client.complete_with_tools("What is 5 + 3?", &[tool]).await.unwrap();
+        assert_eq!(completion.tool_calls.len(), 1);
+        assert!(recorded.lock()[1].body.get("tool_choice").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_embeddings() {
+        let (url, recorded) = test_utils::start_recording_server(serde_json::json!({
+            "data": [
+                { "index": 1, "embedding": [0.0, 1.0] },
+                { "index": 0, "embedding": [1.0, 0.0] }
+            ],
+            "embedding": [0.5, 0.5]
+        })).await;
+        let texts = vec!["first".to_string(), "second".to_string()];
+
+        let config = LLMConfig::with_lmstudio_server(&url).with_embedding_model("nomic-embed-text");
+        let embeddings = LLMClient::new(config).unwrap().embed(&texts).await.unwrap();
+        assert_eq!(embeddings, vec![vec![1.0, 0.0], vec![0.0, 1.0]]);
+
+        let mut config = LLMConfig::with_ollama_server("nomic-embed-text");
+        config.server_url = url;
+        let embeddings = LLMClient::new(config).unwrap().embed(&texts).await.unwrap();
+        assert_eq!(embeddings.len(), 2);
+
+        let requests = recorded.lock().clone();
+        assert_eq!(requests[0].path, "/v1/embeddings");
+        assert_eq!(requests[0].body["model"], "nomic-embed-text");
+        assert_eq!(requests[0].body["input"][1], "second");
+        assert_eq!(requests[1].path, "/api/embeddings");
+        assert_eq!(requests[2].body["prompt"], "second");
+
+        let mut config = LLMConfig::with_anthropic("claude-3-5-haiku-latest");
+        config.api_key_secret = None;
+        let anthropic = LLMClient::new(config).unwrap();
+        assert!(matches!(anthropic.embed(&texts).await, Err(NexaError::InvalidInput(_))));
+    }
+
+    #[tokio::test]
+    async fn test_complete_with_images() {
+        let (url, recorded) = test_utils::start_recording_server(serde_json::json!({
+            "choices": [{ "message": { "role": "assistant", "content": "A login form" } }],
+            "content": [{ "type": "text", "text": "A login form" }],
+            "response": "A login form",
+            "done": true
+        })).await;
+        let screenshot = ImageInput::from_bytes("image/png", b"not really a png");
+
+        let client = LLMClient::new(LLMConfig::with_lmstudio_server(&url)).unwrap();
+        let text = client.complete_with_images("Describe the screenshot", &[screenshot.clone()]).await.unwrap();
+        assert_eq!(text, "A login form");
+
+        let mut config = LLMConfig::with_anthropic("claude-3-5-haiku-latest");
+        config.server_url = url.clone();
+        config.api_key_secret = None;
+        LLMClient::new(config).unwrap()
+            .complete_with_images("Describe the screenshot", &[screenshot]).await.unwrap();
+
+        let mut config = LLMConfig::with_ollama_server("llava");
+        config.server_url = url.clone();
+        config.allow_private_image_hosts = true;
+        let image_url = ImageInput::Url(format!("{}/screenshot.png", url));
+        LLMClient::new(config).unwrap()
+            .complete_with_images("Describe the screenshot", &[image_url]).await.unwrap();
+
+        let requests = recorded.lock().clone();
+        let parts = &requests[0].body["messages"][0]["content"];
+        assert_eq!(parts[0], serde_json::json!({ "type": "text", "text": "Describe the screenshot" }));
+        assert_eq!(parts[1]["type"], "image_url");
+        assert!(parts[1]["image_url"]["url"].as_str().unwrap().starts_with("data:image/png;base64,"));
+
+        let blocks = &requests[1].body["messages"][0]["content"];
+        assert_eq!(blocks[0]["source"]["type"], "base64");
+        assert_eq!(blocks[0]["source"]["media_type"], "image/png");
+        assert_eq!(blocks[1]["type"], "text");
+
+        // Ollama only takes inline images, so the URL is downloaded first
+        assert_eq!(requests[2].path, "/screenshot.png");
+        assert!(requests[3].body["images"][0].as_str().is_some_and(|data| !data.is_empty()));
+    }
+
+    #[tokio::test]
+    async fn test_image_download_limits() {
+        let client = LLMClient::new(LLMConfig::with_ollama_server("llava")).unwrap();
+        for url in [
+            "file:///etc/passwd",
+            "ftp://example.com/cat.png",
+            "http://127.0.0.1/cat.png",
+            "http://10.0.0.7/cat.png",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://[::1]/cat.png",
+            "http://[fd00::1]/cat.png",
+            "http://[::ffff:192.168.1.1]/cat.png",
+        ] {
+            let result = client.inline_image(&ImageInput::Url(url.to_string())).await;
+            assert!(
+                matches!(result, Err(NexaError::InvalidInput(_)) | Err(NexaError::Forbidden(_))),
+                "{} was not refused: {:?}", url, result
+            );
+        }
+        assert!(is_public_address("93.184.216.34".parse().unwrap()));
+        assert!(is_public_address("2606:4700::1111".parse().unwrap()));
+        assert!(!is_public_address("100.64.0.1".parse().unwrap()));
+
+        // Too large, whether announced by content-length or only found while reading
+        let chunk = || axum::body::Bytes::from(vec![0u8; 1024 * 1024]);
+        let router = axum::Router::new()
+            .route("/announced", axum::routing::get(move || async move { vec![0u8; MAX_IMAGE_BYTES + 1] }))
+            .route("/streamed", axum::routing::get(move || async move {
+                let chunks = (0..=MAX_IMAGE_BYTES / (1024 * 1024)).map(move |_| Ok::<_, std::io::Error>(chunk()));
+                axum::body::Body::from_stream(futures::stream::iter(chunks))
+            }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+        let mut config = LLMConfig::with_ollama_server("llava");
+        config.allow_private_image_hosts = true;
+        let client = LLMClient::new(config).unwrap();
+        for path in ["announced", "streamed"] {
+            let image = ImageInput::Url(format!("http://{}/{}", addr, path));
+            let error = client.inline_image(&image).await.unwrap_err();
+            assert!(error.to_string().contains("larger than"), "{}: {}", path, error);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_complete_chat() {
+        let (url, recorded) = test_utils::start_recording_server(serde_json::json!({
+            "message": { "role": "assistant", "content": "Rome" },
+            "candidates": [{ "content": { "parts": [{ "text": "Rome" }] } }]
+        })).await;
+        let chat = [
+            ChatMessage::system("Answer in one word."),
+            ChatMessage::user("Capital of France?"),
+            ChatMessage::assistant("Paris"),
+            ChatMessage::user("And of Italy?"),
+        ];
+
+        let mut config = LLMConfig::with_ollama_server("llama3.2");
+        config.server_url = url.clone();
+        assert_eq!(LLMClient::new(config).unwrap().complete_chat(&chat).await.unwrap(), "Rome");
+
+        let mut config = LLMConfig::with_gemini("gemini-1.5-flash");
+        config.server_url = url;
+        config.api_key_secret = None;
+        assert_eq!(LLMClient::new(config).unwrap().complete_chat(&chat).await.unwrap(), "Rome");
+
+        let requests = recorded.lock().clone();
+        assert_eq!(requests[0].path, "/api/chat");
+        assert_eq!(requests[0].body["messages"][0]["role"], "system");
+        assert_eq!(requests[0].body["messages"][2]["content"], "Paris");
+        let contents = &requests[1].body["contents"];
+        assert_eq!(contents.as_array().unwrap().len(), 3);
+        assert_eq!(contents[1]["role"], "model");
+        assert_eq!(requests[1].body["systemInstruction"]["parts"][0]["text"], "Answer in one word.");
+
+        let client = LLMClient::new(LLMConfig::default()).unwrap();
+        assert!(matches!(client.complete_chat(&chat[..1]).await, Err(NexaError::InvalidInput(_))));
+    }
+
+    #[tokio::test]
+    async fn test_agent_system_prompt() {
+        let (url, recorded) = test_utils::start_recording_server(serde_json::json!({
+            "choices": [{ "message": { "role": "assistant", "content": "ok" } }]
+        })).await;
+        let client = LLMClient::new(LLMConfig::with_lmstudio_server(url).with_system_prompt("Be helpful.")).unwrap();
+        let reviewer = Agent::new("reviewer".to_string(), vec![]).with_system_prompt("You review Rust code.");
+        let chat = [ChatMessage::user("fn main() {}")];
+
+        client.complete_chat_for_agent(&reviewer, &chat).await.unwrap();
+        client.complete_chat_for_agent(&Agent::new("plain".to_string(), vec![]), &chat).await.unwrap();
+
+        let requests = recorded.lock().clone();
+        assert_eq!(requests[0].body["messages"][0]["content"], "You review Rust code.");
+        assert_eq!(requests[1].body["messages"][0]["content"], "Be helpful.");
+
+        let json = serde_json::to_value(&reviewer).unwrap();
+        let restored: Agent = serde_json::from_value(json).unwrap();
+        assert_eq!(restored.system_prompt.as_deref(), Some("You review Rust code."));
+    }
+
+    #[tokio::test]
+    async fn test_timeout_and_cancellation() {
+        let (url, _) = test_utils::start_echo_server().await;
+        let client = LLMClient::new(LLMConfig::with_lmstudio_server(url)).unwrap();
+        // The echo server takes 10ms per character
+        let slow = [ChatMessage::user("x".repeat(500))];
+
+        let started = std::time::Instant::now();
+        let options = CompletionOptions::default().with_timeout(Duration::from_secs(1));
+        let result = client.complete_chat_with_options(&slow, &options).await;
+        assert!(matches!(result, Err(NexaError::Timeout(_))));
+        assert!(started.elapsed() < Duration::from_secs(3));
+
+        let cancel = CancellationToken::new();
+        let trigger = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            trigger.cancel();
+        });
+        let result = client.complete_chat_cancellable(&slow, &CompletionOptions::default(), &cancel).await;
+        assert!(matches!(result, Err(NexaError::Cancelled(_))));
+
+        let quick = [ChatMessage::user("hi")];
+        let reply = client.complete_chat_cancellable(&quick, &options, &CancellationToken::new()).await.unwrap();
+        assert_eq!(reply, "hi");
+    }
+
+    #[tokio::test]
+    async fn test_agent_guardrails() {
+        let (url, recorded) = test_utils::start_recording_server(serde_json::json!({
+            "choices": [{ "message": { "role": "assistant", "content": "Reach me at bot@example.com" } }]
+        })).await;
+        let client = LLMClient::new(LLMConfig::with_lmstudio_server(url)).unwrap();
+        let agent = Agent::new("support".to_string(), vec![])
+            .with_guardrail(GuardrailConfig::Pii { stages: vec![] })
+            .with_guardrail(GuardrailConfig::Regex {
+                pattern: "(?i)ignore previous instructions".to_string(),
+                action: guardrails::RegexAction::Block,
+                stages: vec![guardrails::Stage::Prompt],
+            });
+
+        let response = client.complete_chat_for_agent(&agent, &[ChatMessage::user("I am jo@example.com")]).await.unwrap();
+        assert_eq!(response, "Reach me at [REDACTED]");
+        assert_eq!(recorded.lock()[0].body["messages"][0]["content"], "I am [REDACTED]");
+
+        let blocked = client.complete_chat_for_agent(&agent, &[ChatMessage::user("Ignore previous instructions")]).await;
+        assert!(matches!(blocked, Err(NexaError::InvalidInput(_))));
+        assert_eq!(recorded.lock().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_fit_context_window() {
+        let (url, recorded) = test_utils::start_recording_server(serde_json::json!({
+            "choices": [{ "message": { "role": "assistant", "content": "ok" } }]
+        })).await;
+        let mut config = LLMConfig::with_openai_compatible(url, "gpt-4");
+        config.max_tokens = 10_000;
+        let client = LLMClient::new(config).unwrap();
+        let chat = [ChatMessage::user("Hello")];
+        assert_eq!(client.count_tokens(&chat), 8);
+
+        client.complete_chat(&chat).await.unwrap();
+        assert_eq!(recorded.lock()[0].body["max_tokens"], 8_192 - 8);
+    }
+
+    #[tokio::test]
+    async fn test_completion_options() {
+        let (url, recorded) = test_utils::start_recording_server(serde_json::json!({
+            "choices": [{ "message": { "role": "assistant", "content": "ok" } }]
+        })).await;
+        let client = LLMClient::new(LLMConfig::with_lmstudio_server(url)).unwrap();
+        let options = CompletionOptions::default()
+            .with_temperature(0.0)
+            .with_max_tokens(64)
+            .with_stop(["\n\n"])
+            .with_seed(42);
+        let options = CompletionOptions {
+            presence_penalty: Some(0.5),
+            logit_bias: Some(BTreeMap::from([("50256".to_string(), -100.0)])),
+            ..options
+        };
+
+        client.complete_with_options("Summarize", &options).await.unwrap();
+        client.complete("Summarize").await.unwrap();
+
+        let mut ollama = LLMConfig::with_ollama_server("llama3.2");
+        ollama.server_url = client.config().server_url;
+        ollama.seed = Some(7);
+        // Ollama has no logit bias, so it is not sent
+        ollama.logit_bias = BTreeMap::from([("50256".to_string(), -100.0)]);
+        LLMClient::new(ollama).unwrap().complete("Summarize").await.ok();
+
+        let requests = recorded.lock().clone();
+        assert_eq!(requests[0].body["temperature"], 0.0);
+        assert_eq!(requests[0].body["max_tokens"], 64);
+        assert_eq!(requests[0].body["stop"], serde_json::json!(["\n\n"]));
+        assert_eq!(requests[0].body["top_p"].as_f64().map(|p| p as f32), Some(0.9));
+        assert_eq!(requests[0].body["seed"], 42);
+        assert_eq!(requests[0].body["presence_penalty"], 0.5);
+        assert_eq!(requests[0].body["logit_bias"]["50256"], -100.0);
+        assert!(requests[0].body.get("frequency_penalty").is_none());
+        // The client's configuration is left unchanged
+        assert_eq!(requests[1].body["max_tokens"], 1000);
+        assert!(requests[1].body.get("seed").is_none());
+        assert_eq!(requests[2].body["options"]["seed"], 7);
+        assert!(requests[2].body.get("logit_bias").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_complete_batch() {
+        let (url, peak) = test_utils::start_echo_server().await;
+        let mut config = LLMConfig::with_lmstudio_server(url);
+        config.batch_concurrency = 3;
+        let client = LLMClient::new(config).unwrap();
+
+        client.complete("").await.unwrap();
+
+        // Later prompts finish first, but results keep prompt order
+        let prompts = ["cccccccccc", "bbbbbbbb", "aaaaaa", "dd", "e"];
+        let results = client.complete_batch(&prompts).await;
+        let results: Vec<String> = results.into_iter().map(Result::unwrap).collect();
+        assert_eq!(results, prompts);
+        // Requests overlap, up to the configured concurrency
+        assert_eq!(peak.load(std::sync::atomic::Ordering::SeqCst), 3);
+
+        let offline = LLMClient::new(LLMConfig {
+            retry: RetryPolicy::none(),
+            ..LLMConfig::with_lmstudio_server("http://127.0.0.1:1")
+        }).unwrap();
+        assert!(offline.complete_batch(&["x", "y"]).await.iter().all(Result::is_err));
+    }
+
+    #[tokio::test]
+    async fn test_cost_estimate() {
+        let (url, recorded) = test_utils::start_recording_server(serde_json::json!({
+            "choices": [{ "message": { "role": "assistant", "content": "ok" } }]
+        })).await;
+        let client = LLMClient::new(LLMConfig::with_openai_compatible(url, "gpt-4")).unwrap();
+        assert_eq!(client.estimate("Hello").max_cost, None);
+
+        client.set_pricing(HashMap::from([(
+            "gpt-4".to_string(),
+            ModelPricing { prompt: 0.00003, completion: 0.00006 },
+        )]));
+        let estimate = client.estimate("Hello");
+        assert_eq!((estimate.prompt_tokens, estimate.max_completion_tokens), (8, 1000));
+        assert!((estimate.max_cost.unwrap() - 0.06024).abs() < 1e-9);
+
+        // Steps over their ceiling are refused before anything is sent
+        let cheap = CompletionOptions::default().with_max_cost(0.01);
+        let refused = client.complete_with_options("Hello", &cheap).await;
+        assert!(matches!(refused, Err(NexaError::InvalidInput(_))));
+        assert!(recorded.lock().is_empty());
+        client.complete_with_options("Hello", &cheap.with_max_tokens(100)).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_cached_completion() {
+        let (url, recorded) = test_utils::start_recording_server(serde_json::json!({
+            "choices": [{ "message": { "role": "assistant", "content": "4" } }]
+        })).await;
+        let mut config = LLMConfig::with_lmstudio_server(url);
+        config.temperature = 0.0;
+        config.cache.enabled = true;
+        let client = LLMClient::new(config).unwrap();
+
+        assert_eq!(client.complete("2 + 2?").await.unwrap(), "4");
+        assert_eq!(client.complete("2 + 2?").await.unwrap(), "4");
+        assert_eq!(recorded.lock().len(), 1);
+
+        client.complete("3 + 1?").await.unwrap();
+        assert_eq!(recorded.lock().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_embedded_unavailable() {
+        assert!(matches!("embedded".parse::<ServerType>(), Ok(ServerType::Embedded)));
+        let client = LLMClient::new(LLMConfig::with_embedded("/nonexistent/model.gguf")).unwrap();
+
+        let err = client.complete("Hi").await.unwrap_err();
+        if cfg!(feature = "llama") {
+            assert!(matches!(err, NexaError::NotFound(_)));
+        } else {
+            assert!(matches!(err, NexaError::Config(_)));
+        }
+        assert!(client.health_check().await.is_err());
+    }
+
     #[tokio::test]
     async fn test_ollama_function_call() {
         let config = LLMConfig::with_ollama_server("qwen2.5-coder:7b");
@@ -583,15 +3397,15 @@ mod tests {
                 assert_eq!(response.sum, 8);
             }
             Ok(Err(e)) => {
-                if e.to_string().contains("connection refused") || e.to_string().contains("Failed to send request") {
+                if e.is_retryable() {
                     println!("Skipping test: Ollama server not available");
                     return;
                 }
-                if e.to_string().contains("model") && e.to_string().contains("not found") {
+                if matches!(e, NexaError::NotFound(_)) {
                     println!("Skipping test: Ollama model not installed");
                     return;
                 }
-                if e.to_string().contains("Failed to parse function response") {
+                if matches!(e, NexaError::InvalidResponse(_)) {
                     println!("Response format was not as expected: {}", e);
                     return;
                 }
@@ -0,0 +1,274 @@
+//! Ollama model management: pulling, deleting and inspecting models on the server
+
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use futures::stream::{BoxStream, StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use crate::error::NexaError;
+use super::{http_pool, request_error, status_error, streaming, LLMConfig, ServerType};
+
+/// Progress events of a model pull, in the order the server reports them
+pub type PullStream = BoxStream<'static, Result<PullProgress, NexaError>>;
+
+/// One progress event of a model pull
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PullProgress {
+    /// Phase of the pull, e.g. `pulling manifest`, `verifying sha256 digest` or `success`
+    pub status: String,
+    /// Layer being downloaded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digest: Option<String>,
+    /// Size of the layer in bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
+    /// Bytes of the layer downloaded so far
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed: Option<u64>,
+}
+
+impl PullProgress {
+    /// Fraction of the current layer downloaded, when the server reports sizes
+    pub fn fraction(&self) -> Option<f64> {
+        match (self.completed, self.total) {
+            (Some(completed), Some(total)) if total > 0 => Some(completed as f64 / total as f64),
+            _ => None,
+        }
+    }
+
+    /// Whether this is the final event of a successful pull
+    pub fn is_success(&self) -> bool {
+        self.status == "success"
+    }
+}
+
+/// Format and size details of a model
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelSummary {
+    #[serde(default)]
+    pub format: String,
+    #[serde(default)]
+    pub family: String,
+    #[serde(default)]
+    pub parameter_size: String,
+    #[serde(default)]
+    pub quantization_level: String,
+}
+
+/// What the server knows about an installed model
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelDetails {
+    #[serde(default)]
+    pub modelfile: String,
+    #[serde(default)]
+    pub parameters: String,
+    #[serde(default)]
+    pub template: String,
+    #[serde(default)]
+    pub details: ModelSummary,
+}
+
+/// A model currently loaded into memory
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunningModel {
+    pub name: String,
+    /// Total size in bytes
+    #[serde(default)]
+    pub size: u64,
+    /// Bytes held in GPU memory
+    #[serde(default)]
+    pub size_vram: u64,
+    /// When the server unloads the model if it stays idle
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub details: ModelSummary,
+}
+
+#[derive(Debug, Deserialize)]
+struct PullEvent {
+    status: Option<String>,
+    digest: Option<String>,
+    total: Option<u64>,
+    completed: Option<u64>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RunningModels {
+    #[serde(default)]
+    models: Vec<RunningModel>,
+}
+
+/// Client for the model management endpoints of an Ollama server
+#[derive(Debug, Clone)]
+pub struct OllamaClient {
+    server_url: String,
+    http: reqwest::Client,
+    timeout: Duration,
+}
+
+impl OllamaClient {
+    /// Client for the server in `config`, or for a local Ollama when `config` names another provider
+    pub fn new(config: &LLMConfig) -> Result<Self, NexaError> {
+        let config = match config.server_type {
+            ServerType::Ollama => config.clone(),
+            _ => LLMConfig::for_server_type(ServerType::Ollama),
+        };
+        Ok(Self {
+            http: http_pool::shared_client(&config.server_url)?,
+            server_url: config.server_url,
+            timeout: Duration::from_secs(config.timeout_secs),
+        })
+    }
+
+    /// Base URL of the server
+    pub fn server_url(&self) -> &str {
+        &self.server_url
+    }
+
+    /// Download a model, yielding progress until the server reports `success`
+    ///
+    /// Downloads can take far longer than the request timeout, so only connecting is bounded by it.
+    pub async fn pull(&self, model: &str) -> Result<PullStream, NexaError> {
+        let response = tokio::time::timeout(
+            self.timeout,
+            self.http.post(self.url("/api/pull"))
+                .json(&serde_json::json!({ "model": model, "stream": true }))
+                .send(),
+        )
+        .await
+        .map_err(|_| NexaError::timeout(format!("Pulling {} timed out waiting for Ollama", model)))?
+        .map_err(|e| request_error("Failed to send pull request to Ollama", e))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(status_error("Ollama pull failed", status, text));
+        }
+
+        Ok(streaming::lines(response)
+            .try_filter(|line| futures::future::ready(!line.is_empty()))
+            .and_then(|line| async move {
+                let event: PullEvent = serde_json::from_str(&line)
+                    .map_err(|e| NexaError::invalid_response(format!("Invalid pull progress: {}", e)))?;
+                match (event.error, event.status) {
+                    (Some(error), _) => Err(NexaError::unavailable(format!("Ollama pull failed: {}", error))),
+                    (None, Some(status)) => Ok(PullProgress {
+                        status,
+                        digest: event.digest,
+                        total: event.total,
+                        completed: event.completed,
+                    }),
+                    (None, None) => Err(NexaError::invalid_response(format!("Invalid pull progress: {}", line))),
+                }
+            })
+            .boxed())
+    }
+
+    /// Remove a model from the server
+    pub async fn delete(&self, model: &str) -> Result<(), NexaError> {
+        let response = self.http.delete(self.url("/api/delete"))
+            .timeout(self.timeout)
+            .json(&serde_json::json!({ "model": model }))
+            .send()
+            .await
+            .map_err(|e| request_error("Failed to send delete request to Ollama", e))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(status_error(&format!("Deleting {} failed", model), status, text));
+        }
+        Ok(())
+    }
+
+    /// Modelfile, parameters, template and format of an installed model
+    pub async fn show(&self, model: &str) -> Result<ModelDetails, NexaError> {
+        let response = self.http.post(self.url("/api/show"))
+            .timeout(self.timeout)
+            .json(&serde_json::json!({ "model": model }))
+            .send()
+            .await
+            .map_err(|e| request_error("Failed to send show request to Ollama", e))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(status_error(&format!("Showing {} failed", model), status, text));
+        }
+        response.json()
+            .await
+            .map_err(|e| NexaError::invalid_response(format!("Failed to parse model details: {}", e)))
+    }
+
+    /// Models currently loaded into memory
+    pub async fn running(&self) -> Result<Vec<RunningModel>, NexaError> {
+        let response = self.http.get(self.url("/api/ps"))
+            .timeout(self.timeout)
+            .send()
+            .await
+            .map_err(|e| request_error("Failed to list running Ollama models", e))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(status_error("Listing running models failed", status, text));
+        }
+        let body: RunningModels = response.json()
+            .await
+            .map_err(|e| NexaError::invalid_response(format!("Failed to parse running models: {}", e)))?;
+        Ok(body.models)
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.server_url, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::test_utils;
+
+    fn client(server_url: String) -> OllamaClient {
+        let mut config = LLMConfig::with_ollama_server("llama3.2");
+        config.server_url = server_url;
+        OllamaClient::new(&config).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_pull_progress() {
+        let url = test_utils::start_streaming_server(concat!(
+            "{\"status\":\"pulling manifest\"}\n",
+            "{\"status\":\"pulling abc\",\"digest\":\"sha256:abc\",\"total\":200,\"completed\":50}\n",
+            "{\"status\":\"success\"}\n",
+        )).await;
+        let events: Vec<PullProgress> = client(url).pull("llama3.2").await.unwrap().try_collect().await.unwrap();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[1].fraction(), Some(0.25));
+        assert!(events[2].is_success());
+
+        let url = test_utils::start_streaming_server("{\"error\":\"pull model manifest: file does not exist\"}\n").await;
+        let result: Result<Vec<PullProgress>, _> = client(url).pull("missing").await.unwrap().try_collect().await;
+        assert!(matches!(result, Err(NexaError::Unavailable(_))));
+    }
+
+    #[tokio::test]
+    async fn test_show_and_running() {
+        let (url, recorded) = test_utils::start_recording_server(serde_json::json!({
+            "modelfile": "FROM llama3.2",
+            "details": { "family": "llama", "parameter_size": "3.2B", "quantization_level": "Q4_K_M" },
+            "models": [{ "name": "llama3.2:latest", "size": 3000, "size_vram": 2000 }],
+        })).await;
+        let ollama = client(url);
+
+        let details = ollama.show("llama3.2").await.unwrap();
+        assert_eq!(details.details.parameter_size, "3.2B");
+        let running = ollama.running().await.unwrap();
+        assert_eq!(running[0].name, "llama3.2:latest");
+        assert_eq!(running[0].size_vram, 2000);
+        ollama.delete("llama3.2").await.unwrap();
+
+        let recorded = recorded.lock();
+        assert_eq!(recorded[0].path, "/api/show");
+        assert_eq!(recorded[0].body["model"], "llama3.2");
+        assert_eq!(recorded[1].path, "/api/ps");
+        assert_eq!((recorded[2].method.as_str(), recorded[2].path.as_str()), ("DELETE", "/api/delete"));
+    }
+}
@@ -0,0 +1,99 @@
+//! Client-side token buckets that keep requests under a provider's rate limits
+
+use std::time::{Duration, Instant};
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+/// Requests and tokens a client may send to its provider per minute
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+#[schemars(deny_unknown_fields)]
+pub struct RateLimit {
+    /// Requests per minute; unlimited when unset
+    #[schemars(range(min = 1))]
+    pub requests_per_minute: Option<u32>,
+    /// Prompt and completion tokens per minute; unlimited when unset
+    #[schemars(range(min = 1))]
+    pub tokens_per_minute: Option<u32>,
+}
+
+/// A bucket holding up to a minute's allowance, refilled continuously
+#[derive(Debug)]
+struct Bucket {
+    available: f64,
+    refilled: Instant,
+}
+
+impl Bucket {
+    /// Take `cost` from the bucket, returning how long to wait before using it
+    ///
+    /// The balance may go negative, so concurrent callers queue up behind each other.
+    fn take(bucket: &mut Option<Self>, per_minute: u32, cost: u32, now: Instant) -> Duration {
+        let capacity = per_minute.max(1) as f64;
+        let per_second = capacity / 60.0;
+        let bucket = bucket.get_or_insert(Self { available: capacity, refilled: now });
+        let elapsed = now.saturating_duration_since(bucket.refilled).as_secs_f64();
+        bucket.available = (bucket.available + elapsed * per_second).min(capacity);
+        bucket.refilled = now;
+
+        // Requests larger than the bucket wait for a full one instead of forever
+        bucket.available -= (cost as f64).min(capacity);
+        if bucket.available >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-bucket.available / per_second)
+        }
+    }
+}
+
+/// Request and token buckets shared by clones of an `LLMClient`
+#[derive(Debug, Default)]
+pub(super) struct RateLimiter {
+    requests: Mutex<Option<Bucket>>,
+    tokens: Mutex<Option<Bucket>>,
+}
+
+impl RateLimiter {
+    /// Wait until a request using about `tokens` tokens fits within `limit`
+    pub async fn acquire(&self, limit: &RateLimit, tokens: u32) {
+        let now = Instant::now();
+        let mut wait = Duration::ZERO;
+        if let Some(per_minute) = limit.requests_per_minute {
+            wait = wait.max(Bucket::take(&mut self.requests.lock(), per_minute, 1, now));
+        }
+        if let Some(per_minute) = limit.tokens_per_minute {
+            wait = wait.max(Bucket::take(&mut self.tokens.lock(), per_minute, tokens, now));
+        }
+        if !wait.is_zero() {
+            debug!("Client-side rate limit reached, delaying request by {:?}", wait);
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket() {
+        let start = Instant::now();
+        let mut bucket = None;
+
+        // A full minute's allowance is available as a burst
+        for _ in 0..60 {
+            assert_eq!(Bucket::take(&mut bucket, 60, 1, start), Duration::ZERO);
+        }
+        // Then callers queue up at the refill rate
+        assert_eq!(Bucket::take(&mut bucket, 60, 1, start), Duration::from_secs(1));
+        assert_eq!(Bucket::take(&mut bucket, 60, 1, start), Duration::from_secs(2));
+        assert_eq!(Bucket::take(&mut bucket, 60, 1, start + Duration::from_secs(3)), Duration::ZERO);
+
+        // Oversized requests wait for a full bucket
+        let mut tokens = None;
+        assert_eq!(Bucket::take(&mut tokens, 600, 10_000, start), Duration::ZERO);
+        assert_eq!(Bucket::take(&mut tokens, 600, 300, start), Duration::from_secs(30));
+    }
+}
@@ -0,0 +1,134 @@
+//! Retries of transient LLM failures with exponential backoff and jitter
+
+use std::future::Future;
+use std::time::Duration;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use crate::error::NexaError;
+use tracing::warn;
+
+/// Failure classes a retry policy can retry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RetryableError {
+    /// The request timed out
+    Timeout,
+    /// The server refused or dropped the connection, or answered with a 5xx or 429
+    Unavailable,
+}
+
+/// How `LLMClient` retries failed requests
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+#[schemars(deny_unknown_fields)]
+pub struct RetryPolicy {
+    /// Attempts per request, including the first; 1 disables retries
+    #[schemars(range(min = 1))]
+    pub max_attempts: u32,
+    /// Delay before the first retry in milliseconds, doubled for each further retry
+    pub initial_backoff_ms: u64,
+    /// Upper bound on the delay between attempts in milliseconds
+    pub max_backoff_ms: u64,
+    /// Fraction of each delay that is randomized (0.0 - 1.0)
+    #[schemars(range(min = 0, max = 1))]
+    pub jitter: f64,
+    /// Failure classes to retry
+    pub retry_on: Vec<RetryableError>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff_ms: 500,
+            max_backoff_ms: 10_000,
+            jitter: 0.2,
+            retry_on: vec![RetryableError::Timeout, RetryableError::Unavailable],
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that makes a single attempt
+    pub fn none() -> Self {
+        Self { max_attempts: 1, ..Self::default() }
+    }
+
+    /// Whether the policy retries this error
+    pub fn retries(&self, error: &NexaError) -> bool {
+        let class = match error {
+            NexaError::Timeout(_) => RetryableError::Timeout,
+            NexaError::Unavailable(_) => RetryableError::Unavailable,
+            _ => return false,
+        };
+        self.retry_on.contains(&class)
+    }
+
+    /// Delay before retry number `retry`, counting from 1
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let base = self.initial_backoff_ms
+            .saturating_mul(1u64 << retry.saturating_sub(1).min(20))
+            .min(self.max_backoff_ms) as f64;
+        // A random UUID is a cheap source of randomness for spreading out retries
+        let random = (uuid::Uuid::new_v4().as_u128() % 1_000_000) as f64 / 1_000_000.0;
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        Duration::from_millis((base * (1.0 - jitter * random)) as u64)
+    }
+
+    /// Run `op` until it succeeds, fails with an error the policy does not retry, or runs out of attempts
+    pub async fn run<T, F, Fut>(&self, mut op: F) -> Result<T, NexaError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, NexaError>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match op().await {
+                Err(e) if attempt < self.max_attempts && self.retries(&e) => {
+                    let delay = self.backoff(attempt);
+                    warn!("LLM request failed ({}), retrying in {:?} ({}/{})", e, delay, attempt, self.max_attempts - 1);
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[tokio::test]
+    async fn test_retry_policy() {
+        let policy = RetryPolicy { initial_backoff_ms: 1, jitter: 0.0, ..RetryPolicy::default() };
+        assert_eq!(policy.backoff(1), Duration::from_millis(1));
+        assert_eq!(policy.backoff(3), Duration::from_millis(4));
+        assert_eq!(RetryPolicy { jitter: 0.0, ..RetryPolicy::default() }.backoff(10), Duration::from_secs(10));
+
+        // Transient failures are retried until an attempt succeeds
+        let attempts = AtomicU32::new(0);
+        let result = policy.run(|| async {
+            match attempts.fetch_add(1, Ordering::SeqCst) {
+                0 => Err(NexaError::unavailable("connection refused")),
+                1 => Err(NexaError::timeout("read timed out")),
+                _ => Ok("done"),
+            }
+        }).await;
+        assert_eq!(result.unwrap(), "done");
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+        // Other errors, and classes the policy leaves out, fail immediately
+        let attempts = AtomicU32::new(0);
+        let only_timeouts = RetryPolicy { retry_on: vec![RetryableError::Timeout], ..policy.clone() };
+        let result: Result<(), _> = only_timeouts.run(|| async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(NexaError::unavailable("503"))
+        }).await;
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+        assert!(!policy.retries(&NexaError::invalid_input("bad request")));
+    }
+}
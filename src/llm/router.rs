@@ -0,0 +1,238 @@
+//! Model routing by capability and cost
+//!
+//! - Models are registered with what they can do and what they cost
+//! - A task states its requirements instead of naming a model
+//! - The cheapest model meeting the requirements within the budget is chosen
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use crate::error::NexaError;
+use crate::tokens::{self, ModelPricing};
+use super::{LLMClient, LLMConfig};
+
+/// A model the router can choose, with the provider settings that serve it
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct ModelEntry {
+    /// Provider and model, as in the `llm` section
+    pub provider: LLMConfig,
+    /// Supports native tool calling
+    #[serde(default)]
+    pub tools: bool,
+    /// Accepts images
+    #[serde(default)]
+    pub vision: bool,
+    /// Context window in tokens; taken from the provider or model name when unset
+    #[serde(default)]
+    pub context_window: Option<usize>,
+    /// Price in USD per token; models without pricing are treated as free, like local servers
+    #[serde(default)]
+    pub pricing: Option<ModelPricing>,
+}
+
+impl ModelEntry {
+    pub fn new(provider: LLMConfig) -> Self {
+        Self { provider, tools: false, vision: false, context_window: None, pricing: None }
+    }
+
+    pub fn with_tools(mut self) -> Self {
+        self.tools = true;
+        self
+    }
+
+    pub fn with_vision(mut self) -> Self {
+        self.vision = true;
+        self
+    }
+
+    pub fn with_pricing(mut self, pricing: ModelPricing) -> Self {
+        self.pricing = Some(pricing);
+        self
+    }
+
+    /// Model name as sent to the provider
+    pub fn model(&self) -> &str {
+        &self.provider.model
+    }
+
+    /// Context window in tokens, if known
+    pub fn context_window(&self) -> Option<usize> {
+        self.context_window
+            .or(self.provider.context_window)
+            .or_else(|| tokens::context_window(&self.provider.model))
+    }
+
+    /// Upper bound of the cost in USD of a request with these requirements
+    pub fn estimated_cost(&self, requirements: &ModelRequirements) -> f64 {
+        let completion_tokens = requirements.completion_tokens.unwrap_or(self.provider.max_tokens);
+        self.pricing
+            .map(|pricing| pricing.cost(requirements.prompt_tokens, completion_tokens))
+            .unwrap_or(0.0)
+    }
+
+    /// Whether the model has every capability the requirements ask for
+    pub fn satisfies(&self, requirements: &ModelRequirements) -> bool {
+        (!requirements.tools || self.tools)
+            && (!requirements.vision || self.vision)
+            && (requirements.min_context == 0 || self.context_window().is_some_and(|w| w >= requirements.min_context))
+    }
+}
+
+/// What a task needs from a model
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct ModelRequirements {
+    /// Needs native tool calling
+    pub tools: bool,
+    /// Needs image input
+    pub vision: bool,
+    /// Smallest acceptable context window in tokens
+    pub min_context: usize,
+    /// Most the request may cost in USD
+    pub max_cost: Option<f64>,
+    /// Expected prompt size in tokens, used to estimate cost
+    pub prompt_tokens: usize,
+    /// Expected reply size in tokens; each model's `max_tokens` when unset
+    pub completion_tokens: Option<usize>,
+}
+
+impl ModelRequirements {
+    pub fn with_tools(mut self) -> Self {
+        self.tools = true;
+        self
+    }
+
+    pub fn with_vision(mut self) -> Self {
+        self.vision = true;
+        self
+    }
+
+    pub fn with_min_context(mut self, tokens: usize) -> Self {
+        self.min_context = tokens;
+        self
+    }
+
+    pub fn with_max_cost(mut self, max_cost: f64) -> Self {
+        self.max_cost = Some(max_cost);
+        self
+    }
+
+    /// Estimate cost for a prompt of this many tokens
+    pub fn with_prompt_tokens(mut self, tokens: usize) -> Self {
+        self.prompt_tokens = tokens;
+        self
+    }
+}
+
+/// Picks the cheapest registered model that meets a task's requirements
+#[derive(Debug, Clone, Default)]
+pub struct ModelRouter {
+    models: Vec<ModelEntry>,
+}
+
+impl ModelRouter {
+    pub fn new(models: Vec<ModelEntry>) -> Self {
+        Self { models }
+    }
+
+    /// Register another model
+    pub fn register(&mut self, entry: ModelEntry) {
+        self.models.push(entry);
+    }
+
+    /// Registered models, in registration order
+    pub fn models(&self) -> &[ModelEntry] {
+        &self.models
+    }
+
+    /// Models meeting the requirements within budget with their estimated cost, cheapest first
+    ///
+    /// Models of equal cost keep their registration order.
+    pub fn candidates(&self, requirements: &ModelRequirements) -> Vec<(&ModelEntry, f64)> {
+        let mut candidates: Vec<(&ModelEntry, f64)> = self.models.iter()
+            .filter(|entry| entry.satisfies(requirements))
+            .map(|entry| (entry, entry.estimated_cost(requirements)))
+            .filter(|(_, cost)| !requirements.max_cost.is_some_and(|max| *cost > max))
+            .collect();
+        candidates.sort_by(|a, b| a.1.total_cmp(&b.1));
+        candidates
+    }
+
+    /// The cheapest model meeting the requirements within budget
+    pub fn route(&self, requirements: &ModelRequirements) -> Result<&ModelEntry, NexaError> {
+        if let Some((entry, _)) = self.candidates(requirements).into_iter().next() {
+            return Ok(entry);
+        }
+        if self.models.iter().any(|entry| entry.satisfies(requirements)) {
+            return Err(NexaError::invalid_input(format!(
+                "Every model meeting the requirements exceeds the budget of ${:.4}",
+                requirements.max_cost.unwrap_or_default()
+            )));
+        }
+        Err(NexaError::not_found(format!("No registered model meets the requirements {:?}", requirements)))
+    }
+
+    /// A client for the model chosen for the requirements
+    pub fn client(&self, requirements: &ModelRequirements) -> Result<LLMClient, NexaError> {
+        let entry = self.route(requirements)?;
+        let mut config = entry.provider.clone();
+        config.context_window = entry.context_window();
+        let client = LLMClient::new(config)?;
+        if let Some(pricing) = entry.pricing {
+            client.set_pricing([(entry.model().to_string(), pricing)].into_iter().collect());
+        }
+        Ok(client)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn router() -> ModelRouter {
+        ModelRouter::new(vec![
+            ModelEntry::new(LLMConfig::with_ollama_server("llama3")),
+            ModelEntry::new(LLMConfig::with_openrouter("openai/gpt-4o-mini"))
+                .with_tools()
+                .with_vision()
+                .with_pricing(ModelPricing { prompt: 0.15e-6, completion: 0.6e-6 }),
+            ModelEntry::new(LLMConfig::with_anthropic("claude-3-5-sonnet-latest"))
+                .with_tools()
+                .with_vision()
+                .with_pricing(ModelPricing { prompt: 3e-6, completion: 15e-6 }),
+        ])
+    }
+
+    #[test]
+    fn test_route_cheapest_eligible() {
+        let router = router();
+        assert_eq!(router.route(&ModelRequirements::default()).unwrap().model(), "llama3");
+
+        let tools = ModelRequirements::default().with_tools();
+        assert_eq!(router.route(&tools).unwrap().model(), "openai/gpt-4o-mini");
+
+        // llama3 has an 8k window
+        let long = ModelRequirements::default().with_min_context(100_000);
+        assert_eq!(router.route(&long).unwrap().model(), "openai/gpt-4o-mini");
+        let longer = ModelRequirements::default().with_min_context(150_000);
+        assert_eq!(router.route(&longer).unwrap().model(), "claude-3-5-sonnet-latest");
+
+        let candidates = router.candidates(&tools.clone().with_prompt_tokens(1000));
+        assert_eq!(candidates.len(), 2);
+        assert!(candidates[0].1 < candidates[1].1);
+    }
+
+    #[test]
+    fn test_route_budget() {
+        let router = router();
+        let requirements = ModelRequirements::default()
+            .with_tools()
+            .with_min_context(150_000)
+            .with_prompt_tokens(10_000)
+            .with_max_cost(0.01);
+        assert!(matches!(router.route(&requirements), Err(NexaError::InvalidInput(_))));
+
+        let requirements = ModelRequirements::default().with_min_context(5_000_000);
+        assert!(matches!(router.route(&requirements), Err(NexaError::NotFound(_))));
+    }
+}
@@ -0,0 +1,138 @@
+//! Decoding of streamed completions: SSE for OpenAI-style servers, NDJSON for Ollama
+
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use serde::Deserialize;
+use crate::error::NexaError;
+
+/// A stream of completion text chunks
+pub type CompletionStream = BoxStream<'static, Result<String, NexaError>>;
+
+/// Split a response body into lines, without their line endings
+pub(super) fn lines(response: reqwest::Response) -> CompletionStream {
+    let bytes = response.bytes_stream().boxed();
+    stream::unfold((bytes, Vec::new(), false), |(mut bytes, mut buf, mut eof)| async move {
+        loop {
+            if let Some(pos) = buf.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = buf.drain(..=pos).collect();
+                let line = String::from_utf8_lossy(&line).trim_end().to_string();
+                return Some((Ok(line), (bytes, buf, eof)));
+            }
+            if eof {
+                if buf.is_empty() {
+                    return None;
+                }
+                let line = String::from_utf8_lossy(&buf).trim_end().to_string();
+                buf.clear();
+                return Some((Ok(line), (bytes, buf, eof)));
+            }
+            match bytes.next().await {
+                Some(Ok(chunk)) => buf.extend_from_slice(&chunk),
+                Some(Err(e)) => {
+                    return Some((Err(NexaError::unavailable(format!("Stream interrupted: {}", e))), (bytes, buf, true)));
+                }
+                None => eof = true,
+            }
+        }
+    })
+    .boxed()
+}
+
+/// Pass `stream` through, calling `report` with all the text it yielded once it
+/// ends or is dropped, so abandoned streams are accounted for too
+pub(super) fn on_finish(stream: CompletionStream, report: impl FnOnce(String) + Send + 'static) -> CompletionStream {
+    struct Report<F: FnOnce(String)> {
+        text: String,
+        report: Option<F>,
+    }
+
+    impl<F: FnOnce(String)> Drop for Report<F> {
+        fn drop(&mut self) {
+            if let Some(report) = self.report.take() {
+                report(std::mem::take(&mut self.text));
+            }
+        }
+    }
+
+    let report = Report { text: String::new(), report: Some(report) };
+    stream::unfold((stream, report), |(mut stream, mut report)| async move {
+        let item = stream.next().await?;
+        if let Ok(chunk) = &item {
+            report.text.push_str(chunk);
+        }
+        Some((item, (stream, report)))
+    })
+    .boxed()
+}
+
+#[derive(Debug, Deserialize)]
+struct SseChunk {
+    #[serde(default)]
+    choices: Vec<SseChoice>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SseChoice {
+    #[serde(default)]
+    delta: SseDelta,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct SseDelta {
+    content: Option<String>,
+}
+
+/// Text deltas from an OpenAI-style `text/event-stream` body, ending at `[DONE]`
+pub(super) fn sse_content(response: reqwest::Response) -> CompletionStream {
+    lines(response)
+        .try_take_while(|line| futures::future::ready(Ok(sse_data(line) != Some("[DONE]"))))
+        .try_filter_map(|line| async move {
+            // Comments, event names and blank separators carry no text
+            let Some(data) = sse_data(&line) else {
+                return Ok(None);
+            };
+            let chunk: SseChunk = serde_json::from_str(data)
+                .map_err(|e| NexaError::invalid_response(format!("Invalid stream chunk: {}", e)))?;
+            Ok(chunk.choices.into_iter()
+                .next()
+                .and_then(|choice| choice.delta.content)
+                .filter(|content| !content.is_empty()))
+        })
+        .boxed()
+}
+
+fn sse_data(line: &str) -> Option<&str> {
+    line.strip_prefix("data:").map(str::trim)
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaChunk {
+    #[serde(default)]
+    response: String,
+    #[serde(default)]
+    done: bool,
+    error: Option<String>,
+}
+
+/// Text chunks from an Ollama NDJSON body, ending at the chunk marked `done`
+pub(super) fn ndjson_content(response: reqwest::Response) -> CompletionStream {
+    lines(response)
+        .try_filter(|line| futures::future::ready(!line.is_empty()))
+        .and_then(|line| async move {
+            let chunk: OllamaChunk = serde_json::from_str(&line)
+                .map_err(|e| NexaError::invalid_response(format!("Invalid Ollama stream chunk: {}", e)))?;
+            match chunk.error {
+                Some(error) => Err(NexaError::unavailable(format!("Ollama stream failed: {}", error))),
+                None => Ok(chunk),
+            }
+        })
+        // Keep the final chunk, then stop
+        .scan(false, |finished, chunk| {
+            let item = (!*finished).then(|| {
+                *finished = chunk.as_ref().map(|c| c.done).unwrap_or(true);
+                chunk
+            });
+            futures::future::ready(item)
+        })
+        .try_filter_map(|chunk| futures::future::ready(Ok((!chunk.response.is_empty()).then_some(chunk.response))))
+        .boxed()
+}
@@ -3,7 +3,7 @@ use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock;
 use crate::error::NexaError;
 use crate::agent::Task;
-use crate::llm::{LLMClient, LLMConfig};
+use crate::llm::{ChatMessage, LLMClient, LLMConfig, ToolLoop, ToolLoopOutcome};
 use crate::mcp::ServerControl;
 use tracing::info;
 use chrono::{DateTime, Utc};
@@ -107,7 +107,7 @@ impl SystemHelper {
         };
 
         let task_details: TaskDetails = serde_json::from_str(json_str)
-            .map_err(|e| NexaError::system(format!("Failed to parse task details: {}", e)))?;
+            .map_err(|e| NexaError::invalid_response(format!("Failed to parse task details: {}", e)))?;
 
         // Create task
         let task = Task::new(
@@ -196,6 +196,11 @@ impl SystemHelper {
         self.llm.reason(prompt, Some(&context)).await
     }
 
+    /// Carry out a request with the server's registered tools, letting the model call them until it answers
+    pub async fn run_with_tools(&self, request: &str, limits: &ToolLoop) -> Result<ToolLoopOutcome, NexaError> {
+        self.llm.run_tools(&[ChatMessage::user(request)], self.server.tools(), limits).await
+    }
+
     /// Add a task template
     pub async fn add_task_template(&self, template: String) -> Result<(), NexaError> {
         let mut templates = self.task_templates.write().await;
@@ -251,7 +256,7 @@ impl SystemHelper {
         };
 
         let tasks: Vec<SystemTaskRequest> = serde_json::from_str(json_str)
-            .map_err(|e| NexaError::system(format!("Failed to parse task suggestions: {}", e)))?;
+            .map_err(|e| NexaError::invalid_response(format!("Failed to parse task suggestions: {}", e)))?;
 
         Ok(tasks)
     }
@@ -330,11 +335,11 @@ mod tests {
                 }
             }
             Err(e) => {
-                if e.to_string().contains("Failed to parse task suggestions") {
+                if matches!(e, NexaError::InvalidResponse(_)) {
                     println!("Skipping test: LLM response was not in expected format");
                     return;
                 }
-                if e.to_string().contains("connection refused") || e.to_string().contains("Failed to send request") {
+                if e.is_retryable() {
                     println!("Skipping test: LLM server not available");
                     return;
                 }
@@ -4,6 +4,8 @@ use hyper::service::{make_service_fn, service_fn};
 use serde_json::json;
 use std::convert::Infallible;
 use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 
 pub async fn start_mock_server() -> SocketAddr {
     let addr = SocketAddr::from(([127, 0, 0, 1], 0));
@@ -54,4 +56,112 @@ async fn mock_llm_handler(req: Request<Body>) -> Result<Response<Body>, Infallib
             .unwrap()
     };
     Ok(response)
-} 
\ No newline at end of file
+} 
+/// A request captured by `start_recording_server`
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    pub headers: axum::http::HeaderMap,
+    pub body: serde_json::Value,
+}
+
+/// Serve `response` as JSON for every request, recording what was sent
+pub async fn start_recording_server(
+    response: serde_json::Value,
+) -> (String, std::sync::Arc<parking_lot::Mutex<Vec<RecordedRequest>>>) {
+    use axum::extract::State;
+    use axum::http::{HeaderMap, Method, Uri};
+    use std::sync::Arc;
+
+    type Recorded = Arc<parking_lot::Mutex<Vec<RecordedRequest>>>;
+    async fn record(
+        State((recorded, response)): State<(Recorded, serde_json::Value)>,
+        method: Method,
+        uri: Uri,
+        headers: HeaderMap,
+        body: String,
+    ) -> axum::Json<serde_json::Value> {
+        recorded.lock().push(RecordedRequest {
+            method: method.to_string(),
+            path: uri.to_string(),
+            headers,
+            body: serde_json::from_str(&body).unwrap_or(serde_json::Value::Null),
+        });
+        axum::Json(response)
+    }
+
+    let recorded = Recorded::default();
+    let router = axum::Router::new()
+        .fallback(record)
+        .with_state((recorded.clone(), response));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+    (format!("http://{}", addr), recorded)
+}
+
+/// Serve `body` verbatim for every request, sent in small chunks like a streaming server
+pub async fn start_streaming_server(body: &'static str) -> String {
+    async fn stream(axum::extract::State(body): axum::extract::State<&'static str>) -> axum::body::Body {
+        let chunks = body.as_bytes().chunks(7).map(|chunk| Ok::<_, Infallible>(chunk.to_vec()));
+        axum::body::Body::from_stream(futures::stream::iter(chunks))
+    }
+
+    let router = axum::Router::new().fallback(stream).with_state(body);
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+    format!("http://{}", addr)
+}
+
+/// Answer chat completions with the last message's content, after a delay of
+/// 10ms per character so longer prompts finish later
+///
+/// Also returns the most requests the server had in flight at once.
+pub async fn start_echo_server() -> (String, Arc<AtomicUsize>) {
+    #[derive(Default)]
+    struct InFlight {
+        current: AtomicUsize,
+        peak: Arc<AtomicUsize>,
+    }
+
+    /// Counts a request in flight until dropped, even if the client gives up on it
+    struct Request(Arc<InFlight>);
+
+    impl Request {
+        fn start(in_flight: &Arc<InFlight>) -> Self {
+            let current = in_flight.current.fetch_add(1, Ordering::SeqCst) + 1;
+            in_flight.peak.fetch_max(current, Ordering::SeqCst);
+            Self(in_flight.clone())
+        }
+    }
+
+    impl Drop for Request {
+        fn drop(&mut self) {
+            self.0.current.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    async fn echo(
+        axum::extract::State(in_flight): axum::extract::State<Arc<InFlight>>,
+        axum::Json(body): axum::Json<serde_json::Value>,
+    ) -> axum::Json<serde_json::Value> {
+        let _request = Request::start(&in_flight);
+        let content = body["messages"].as_array()
+            .and_then(|messages| messages.last())
+            .and_then(|message| message["content"].as_str())
+            .unwrap_or_default()
+            .to_string();
+        tokio::time::sleep(std::time::Duration::from_millis(10 * content.len() as u64)).await;
+        axum::Json(json!({ "choices": [{ "message": { "role": "assistant", "content": content } }] }))
+    }
+
+    let in_flight = Arc::new(InFlight::default());
+    let peak = in_flight.peak.clone();
+    let router = axum::Router::new().fallback(echo).with_state(in_flight);
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+    (format!("http://{}", addr), peak)
+}
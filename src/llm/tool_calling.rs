@@ -0,0 +1,249 @@
+//! Native tool calling: tool definitions and tool calls in each provider's wire format
+//!
+//! - OpenAI-style `tools` / `tool_calls`, also used by LMStudio, Groq and OpenRouter
+//! - Ollama `/api/chat` tools, whose arguments arrive as objects
+//! - Anthropic `tool_use` content blocks
+//! - Tool results sent back as `tool` messages, or `tool_result` blocks for Anthropic
+
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use crate::error::NexaError;
+use crate::tools::ToolSpec;
+use super::{ChatMessage, ChatRole};
+
+/// A tool invocation requested by the model
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ToolCall {
+    /// Provider-assigned id, generated when the provider has none
+    pub id: String,
+    pub name: String,
+    pub arguments: Value,
+}
+
+impl ToolCall {
+    /// Deserialize the arguments into the tool's argument type
+    pub fn args<A: DeserializeOwned>(&self) -> Result<A, NexaError> {
+        serde_json::from_value(self.arguments.clone())
+            .map_err(|e| NexaError::invalid_response(format!("Invalid arguments for tool '{}': {}", self.name, e)))
+    }
+}
+
+/// Model reply to a prompt offered tools
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ToolCompletion {
+    /// Text the model produced alongside or instead of tool calls
+    pub content: Option<String>,
+    pub tool_calls: Vec<ToolCall>,
+}
+
+/// `tools` entries for OpenAI-style and Ollama requests
+pub(super) fn openai_tools(tools: &[ToolSpec]) -> Value {
+    tools.iter()
+        .map(|tool| json!({
+            "type": "function",
+            "function": {
+                "name": tool.name,
+                "description": tool.description,
+                "parameters": tool.input_schema,
+            },
+        }))
+        .collect()
+}
+
+/// `tool_choice` forcing an OpenAI-style model to call one tool
+pub(super) fn openai_tool_choice(name: &str) -> Value {
+    json!({ "type": "function", "function": { "name": name } })
+}
+
+/// `tools` entries for Anthropic requests, whose format matches `ToolSpec`
+pub(super) fn anthropic_tools(tools: &[ToolSpec]) -> Value {
+    tools.iter()
+        .map(|tool| json!({
+            "name": tool.name,
+            "description": tool.description,
+            "input_schema": tool.input_schema,
+        }))
+        .collect()
+}
+
+/// `tool_choice` forcing an Anthropic model to call one tool
+pub(super) fn anthropic_tool_choice(name: &str) -> Value {
+    json!({ "type": "tool", "name": name })
+}
+
+/// Tool calls in an OpenAI-style or Ollama chat `message`
+pub(super) fn parse_message(message: &Value) -> Result<ToolCompletion, NexaError> {
+    let tool_calls = message["tool_calls"].as_array()
+        .map(|calls| calls.iter().map(parse_function_call).collect::<Result<Vec<_>, _>>())
+        .transpose()?
+        .unwrap_or_default();
+    Ok(ToolCompletion {
+        content: message["content"].as_str().filter(|c| !c.is_empty()).map(String::from),
+        tool_calls,
+    })
+}
+
+/// Add tool calls and tool result ids to OpenAI-style or Ollama request `messages`,
+/// whose last entries correspond to `turns`
+///
+/// OpenAI-style servers take arguments as a JSON string, Ollama as an object.
+pub(super) fn patch_openai_messages(api_messages: &mut Value, turns: &[&ChatMessage], string_arguments: bool) {
+    let Some(api_messages) = api_messages.as_array_mut() else {
+        return;
+    };
+    let offset = api_messages.len().saturating_sub(turns.len());
+    for (api, message) in api_messages[offset..].iter_mut().zip(turns) {
+        if let Some(id) = &message.tool_call_id {
+            api["tool_call_id"] = json!(id);
+        }
+        if !message.tool_calls.is_empty() {
+            api["tool_calls"] = message.tool_calls.iter()
+                .map(|call| json!({
+                    "id": call.id,
+                    "type": "function",
+                    "function": {
+                        "name": call.name,
+                        "arguments": if string_arguments { json!(call.arguments.to_string()) } else { call.arguments.clone() },
+                    },
+                }))
+                .collect();
+        }
+    }
+}
+
+/// Anthropic request `messages` for `turns`, with tool calls as `tool_use` blocks and
+/// consecutive tool results merged into one user message of `tool_result` blocks
+pub(super) fn anthropic_messages(api_messages: Value, turns: &[&ChatMessage]) -> Value {
+    let mut merged: Vec<Value> = Vec::new();
+    for (mut api, message) in api_messages.as_array().cloned().unwrap_or_default().into_iter().zip(turns) {
+        if message.role == ChatRole::Tool {
+            let block = json!({
+                "type": "tool_result",
+                "tool_use_id": message.tool_call_id.clone().unwrap_or_default(),
+                "content": message.content,
+            });
+            match merged.last_mut() {
+                Some(last) if last["role"] == "user" && last["content"][0]["type"] == "tool_result" => {
+                    if let Some(blocks) = last["content"].as_array_mut() {
+                        blocks.push(block);
+                    }
+                }
+                _ => merged.push(json!({ "role": "user", "content": [block] })),
+            }
+            continue;
+        }
+        if !message.tool_calls.is_empty() {
+            let text = (!message.content.is_empty()).then(|| json!({ "type": "text", "text": message.content }));
+            let calls = message.tool_calls.iter()
+                .map(|call| json!({ "type": "tool_use", "id": call.id, "name": call.name, "input": call.arguments }));
+            api["content"] = text.into_iter().chain(calls).collect();
+        }
+        merged.push(api);
+    }
+    Value::Array(merged)
+}
+
+fn parse_function_call(call: &Value) -> Result<ToolCall, NexaError> {
+    let function = &call["function"];
+    let name = function["name"].as_str()
+        .ok_or_else(|| NexaError::invalid_response("Tool call without a function name"))?;
+    // OpenAI sends arguments as a JSON string, Ollama as an object
+    let arguments = match &function["arguments"] {
+        Value::String(raw) => serde_json::from_str(raw)
+            .map_err(|e| NexaError::invalid_response(format!("Tool '{}' arguments are not JSON: {}", name, e)))?,
+        Value::Null => json!({}),
+        arguments => arguments.clone(),
+    };
+    Ok(ToolCall {
+        id: call["id"].as_str().map(String::from).unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+        name: name.to_string(),
+        arguments,
+    })
+}
+
+/// Text and `tool_use` blocks in an Anthropic messages response
+pub(super) fn parse_anthropic(body: &Value) -> Result<ToolCompletion, NexaError> {
+    let mut completion = ToolCompletion::default();
+    for block in body["content"].as_array().into_iter().flatten() {
+        match block["type"].as_str() {
+            Some("text") => {
+                let text = block["text"].as_str().unwrap_or_default();
+                completion.content.get_or_insert_with(String::new).push_str(text);
+            }
+            Some("tool_use") => completion.tool_calls.push(ToolCall {
+                id: block["id"].as_str().unwrap_or_default().to_string(),
+                name: block["name"].as_str()
+                    .ok_or_else(|| NexaError::invalid_response("Tool use block without a name"))?
+                    .to_string(),
+                arguments: block["input"].clone(),
+            }),
+            _ => {}
+        }
+    }
+    Ok(completion)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tool_calls() {
+        let openai = parse_message(&json!({
+            "content": null,
+            "tool_calls": [{
+                "id": "call_1",
+                "type": "function",
+                "function": { "name": "add", "arguments": "{\"x\": 5, \"y\": 3}" },
+            }],
+        })).unwrap();
+        let ollama = parse_message(&json!({
+            "content": "",
+            "tool_calls": [{ "function": { "name": "add", "arguments": { "x": 5, "y": 3 } } }],
+        })).unwrap();
+        let anthropic = parse_anthropic(&json!({
+            "content": [
+                { "type": "text", "text": "Adding." },
+                { "type": "tool_use", "id": "toolu_1", "name": "add", "input": { "x": 5, "y": 3 } },
+            ],
+        })).unwrap();
+
+        assert_eq!(openai.content, None);
+        assert_eq!(openai.tool_calls[0].id, "call_1");
+        assert_eq!(ollama.tool_calls[0].arguments, openai.tool_calls[0].arguments);
+        assert_eq!(anthropic.content.as_deref(), Some("Adding."));
+        assert_eq!(anthropic.tool_calls[0].arguments, json!({ "x": 5, "y": 3 }));
+        assert!(parse_message(&json!({
+            "tool_calls": [{ "function": { "name": "add", "arguments": "not json" } }],
+        })).is_err());
+    }
+
+    #[test]
+    fn test_tool_result_messages() {
+        let call = ToolCall { id: "call_1".to_string(), name: "add".to_string(), arguments: json!({ "x": 1 }) };
+        let other = ToolCall { id: "call_2".to_string(), ..call.clone() };
+        let chat = [
+            ChatMessage::user("Add"),
+            ChatMessage::assistant("").with_tool_calls(vec![call, other]),
+            ChatMessage::tool_result("call_1", "2"),
+            ChatMessage::tool_result("call_2", "3"),
+        ];
+        let turns: Vec<&ChatMessage> = chat.iter().collect();
+        let plain: Value = chat.iter().map(|m| json!({ "role": m.role.as_str(), "content": m.content })).collect();
+
+        let mut openai = json!([{ "role": "system", "content": "Be exact." }]);
+        openai.as_array_mut().unwrap().extend(plain.as_array().unwrap().iter().cloned());
+        patch_openai_messages(&mut openai, &turns, true);
+        assert_eq!(openai[2]["tool_calls"][0]["function"]["arguments"], "{\"x\":1}");
+        assert_eq!(openai[3]["role"], "tool");
+        assert_eq!(openai[4]["tool_call_id"], "call_2");
+
+        let anthropic = anthropic_messages(plain, &turns);
+        assert_eq!(anthropic.as_array().unwrap().len(), 3);
+        assert_eq!(anthropic[1]["content"][0]["type"], "tool_use");
+        assert_eq!(anthropic[2]["role"], "user");
+        assert_eq!(anthropic[2]["content"][1]["tool_use_id"], "call_2");
+    }
+}
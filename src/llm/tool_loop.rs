@@ -0,0 +1,189 @@
+//! Agentic loop: let the model call tools until it gives a final answer
+//!
+//! - Tool calls of one turn run concurrently, up to a bound
+//! - Results, including tool errors, are sent back as tool messages for the model to act on
+//! - The loop stops after a maximum number of model turns
+
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::debug;
+use crate::error::NexaError;
+use crate::tools::ToolRegistry;
+use super::{ChatMessage, LLMClient, ToolCall};
+
+/// Limits of a tool loop
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolLoop {
+    /// Model turns before giving up on a final answer
+    pub max_iterations: usize,
+    /// Tool calls run at once
+    pub concurrency: usize,
+}
+
+impl Default for ToolLoop {
+    fn default() -> Self {
+        Self { max_iterations: 10, concurrency: 4 }
+    }
+}
+
+impl ToolLoop {
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = max_iterations.max(1);
+        self
+    }
+
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+}
+
+/// Final answer of a tool loop and how it was reached
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolLoopOutcome {
+    /// The model's final answer
+    pub content: String,
+    /// The whole chat, including tool calls and results
+    pub messages: Vec<ChatMessage>,
+    /// Model turns taken
+    pub iterations: usize,
+    /// Tool calls run
+    pub tool_calls: usize,
+}
+
+impl LLMClient {
+    /// Answer a chat, running the tools in `tools` the model calls along the way
+    pub async fn run_tools(
+        &self,
+        messages: &[ChatMessage],
+        tools: &ToolRegistry,
+        limits: &ToolLoop,
+    ) -> Result<ToolLoopOutcome, NexaError> {
+        let specs = tools.list();
+        let mut messages = messages.to_vec();
+        let mut tool_calls = 0;
+        for iteration in 1..=limits.max_iterations {
+            let completion = self.complete_chat_with_tools(&messages, &specs).await?;
+            if completion.tool_calls.is_empty() {
+                return Ok(ToolLoopOutcome {
+                    content: completion.content.unwrap_or_default(),
+                    messages,
+                    iterations: iteration,
+                    tool_calls,
+                });
+            }
+
+            debug!("Running {} tool call(s) at iteration {}", completion.tool_calls.len(), iteration);
+            tool_calls += completion.tool_calls.len();
+            let calls = completion.tool_calls.clone();
+            messages.push(ChatMessage::assistant(completion.content.unwrap_or_default()).with_tool_calls(completion.tool_calls));
+            // Results keep the order of the calls
+            let results: Vec<ChatMessage> = futures::stream::iter(calls)
+                .map(|call| run_call(tools, call))
+                .buffered(limits.concurrency.max(1))
+                .collect()
+                .await;
+            messages.extend(results);
+        }
+        Err(NexaError::invalid_response(format!(
+            "Model was still calling tools after {} iterations", limits.max_iterations
+        )))
+    }
+}
+
+/// Run one call, reporting failures to the model instead of ending the loop
+async fn run_call(tools: &ToolRegistry, call: ToolCall) -> ChatMessage {
+    let content = match tools.call(&call.name, call.arguments).await {
+        Ok(Value::String(text)) => text,
+        Ok(value) => value.to_string(),
+        Err(e) => serde_json::json!({ "error": e.to_string() }).to_string(),
+    };
+    ChatMessage::tool_result(call.id, content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use futures::future::BoxFuture;
+    use crate::llm::{test_utils, ChatRole, LLMConfig};
+    use crate::tools::{Tool, ToolSpec};
+
+    /// Echoes after a delay, counting the most calls it had running at once
+    struct Slow {
+        spec: ToolSpec,
+        running: AtomicUsize,
+        peak: Arc<AtomicUsize>,
+    }
+
+    impl Tool for Slow {
+        fn spec(&self) -> &ToolSpec {
+            &self.spec
+        }
+
+        fn call(&self, args: Value) -> BoxFuture<'_, Result<Value, NexaError>> {
+            Box::pin(async move {
+                let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+                self.peak.fetch_max(running, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                self.running.fetch_sub(1, Ordering::SeqCst);
+                Ok(serde_json::json!({ "echo": args["text"] }))
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_parallel_tool_calls() {
+        let tools = ToolRegistry::new();
+        let peak = Arc::new(AtomicUsize::new(0));
+        tools.register(Arc::new(Slow {
+            spec: ToolSpec {
+                name: "slow".to_string(),
+                description: "Echo after a delay".to_string(),
+                input_schema: serde_json::json!({ "type": "object" }),
+            },
+            running: AtomicUsize::new(0),
+            peak: peak.clone(),
+        })).unwrap();
+        let (url, recorded) = test_utils::start_recording_server(serde_json::json!({
+            "choices": [{ "message": { "role": "assistant", "content": null, "tool_calls": [
+                { "id": "a", "type": "function", "function": { "name": "slow", "arguments": "{\"text\": \"a\"}" } },
+                { "id": "b", "type": "function", "function": { "name": "slow", "arguments": "{\"text\": \"b\"}" } },
+                { "id": "c", "type": "function", "function": { "name": "missing", "arguments": "{}" } },
+            ] } }]
+        })).await;
+        let client = LLMClient::new(LLMConfig::with_lmstudio_server(url)).unwrap();
+
+        // The server always asks for more tools, so the loop hits its limit
+        let result = client.run_tools(&[ChatMessage::user("Go")], &tools, &ToolLoop::default().with_max_iterations(2)).await;
+        assert!(matches!(result, Err(NexaError::InvalidResponse(_))));
+        // The two calls of each round run at the same time
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+
+        let requests = recorded.lock().clone();
+        assert_eq!(requests.len(), 2);
+        let sent = requests[1].body["messages"].as_array().unwrap();
+        let roles: Vec<&str> = sent.iter().map(|m| m["role"].as_str().unwrap()).collect();
+        assert_eq!(roles, vec!["user", "assistant", "tool", "tool", "tool"]);
+        assert_eq!(sent[1]["tool_calls"][1]["id"], "b");
+        assert_eq!(sent[2]["tool_call_id"], "a");
+        assert_eq!(sent[3]["content"], "{\"echo\":\"b\"}");
+        assert!(sent[4]["content"].as_str().unwrap().contains("Unknown tool"));
+    }
+
+    #[tokio::test]
+    async fn test_final_answer() {
+        let (url, _) = test_utils::start_recording_server(serde_json::json!({
+            "choices": [{ "message": { "role": "assistant", "content": "Done." } }]
+        })).await;
+        let client = LLMClient::new(LLMConfig::with_lmstudio_server(url)).unwrap();
+        let outcome = client.run_tools(&[ChatMessage::user("Hi")], &ToolRegistry::new(), &ToolLoop::default()).await.unwrap();
+        assert_eq!(outcome.content, "Done.");
+        assert_eq!(outcome.iterations, 1);
+        assert_eq!(outcome.tool_calls, 0);
+        assert_eq!(outcome.messages.last().unwrap().role, ChatRole::User);
+    }
+}
@@ -0,0 +1,190 @@
+//! Fitting long chats into the model's context window
+//!
+//! - The prompt budget is the window minus room for the reply (`max_tokens`, at most half the window)
+//! - Chats within budget are sent unchanged, whatever the strategy
+//! - Strategies never drop the latest message; if that alone does not fit, the chat is sent as is
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+use crate::error::NexaError;
+use crate::tokens;
+use super::{chat_tokens, ChatMessage, ChatRole, LLMClient, LLMConfig};
+
+/// What to do with a chat that does not fit the context window
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TruncationStrategy {
+    /// Send the chat as is and let the provider reject it
+    #[default]
+    None,
+    /// Drop the oldest user and assistant messages, keeping system messages
+    DropOldest,
+    /// Keep only the system messages and the latest user message
+    KeepSystem,
+    /// Replace the middle of the chat with a summary written by the model,
+    /// keeping the first user message and the most recent turns
+    SummarizeMiddle,
+}
+
+/// Tokens the prompt may take up, or `None` when the model's window is unknown
+pub(super) fn prompt_budget(config: &LLMConfig) -> Option<usize> {
+    let window = config.context_window.or_else(|| tokens::context_window(&config.model))?;
+    Some(window - config.max_tokens.min(window / 2))
+}
+
+/// Apply the configured strategy when `messages` exceed the prompt budget
+pub(super) async fn fit(client: &LLMClient, config: &LLMConfig, messages: &[ChatMessage]) -> Vec<ChatMessage> {
+    let Some(budget) = prompt_budget(config) else {
+        return messages.to_vec();
+    };
+    let tokens = chat_tokens(config, messages);
+    if config.truncation == TruncationStrategy::None || tokens <= budget {
+        return messages.to_vec();
+    }
+    debug!("Chat of about {} tokens exceeds the {}-token budget, applying {:?}", tokens, budget, config.truncation);
+    match config.truncation {
+        TruncationStrategy::None => messages.to_vec(),
+        TruncationStrategy::DropOldest => drop_oldest(config, messages, budget),
+        TruncationStrategy::KeepSystem => keep_system(messages),
+        TruncationStrategy::SummarizeMiddle => match summarize_middle(client, config, messages, budget).await {
+            Ok(messages) => drop_oldest(config, &messages, budget),
+            Err(e) => {
+                warn!("Failed to summarize the chat, dropping the oldest messages instead: {}", e);
+                drop_oldest(config, messages, budget)
+            }
+        },
+    }
+}
+
+/// Drop turns from the front until the chat fits, so that it starts with a user message
+pub(super) fn drop_oldest(config: &LLMConfig, messages: &[ChatMessage], budget: usize) -> Vec<ChatMessage> {
+    let (system, turns) = split(messages);
+    let mut start = 0;
+    while start + 1 < turns.len() {
+        let chat: Vec<ChatMessage> = system.iter().chain(&turns[start..]).cloned().collect();
+        if chat_tokens(config, &chat) <= budget && turns[start].role == ChatRole::User {
+            break;
+        }
+        start += 1;
+    }
+    system.into_iter().chain(turns[start..].iter().cloned()).collect()
+}
+
+/// System messages and everything from the latest user message on
+pub(super) fn keep_system(messages: &[ChatMessage]) -> Vec<ChatMessage> {
+    let (system, turns) = split(messages);
+    let last_user = turns.iter().rposition(|m| m.role == ChatRole::User).unwrap_or(turns.len().saturating_sub(1));
+    system.into_iter().chain(turns[last_user..].iter().cloned()).collect()
+}
+
+const SUMMARY_PROMPT: &str = "Summarize the conversation below for the assistant who will continue it. \
+    Keep facts, decisions, open questions and anything the user asked to remember. Reply with the summary only.";
+
+/// Summarize the turns between the first user message and the most recent turns that fit half the budget
+async fn summarize_middle(
+    client: &LLMClient,
+    config: &LLMConfig,
+    messages: &[ChatMessage],
+    budget: usize,
+) -> Result<Vec<ChatMessage>, NexaError> {
+    let (system, turns) = split(messages);
+    let head = usize::from(turns.first().is_some_and(|m| m.role == ChatRole::User));
+    let mut tail = turns.len().saturating_sub(1).max(head);
+    while tail > head + 1 {
+        let chat: Vec<ChatMessage> = system.iter().chain(&turns[..head]).chain(&turns[tail - 1..]).cloned().collect();
+        if chat_tokens(config, &chat) > budget / 2 {
+            break;
+        }
+        tail -= 1;
+    }
+    let middle = &turns[head..tail];
+    if middle.is_empty() {
+        return Err(NexaError::invalid_input("Nothing between the first and latest turns to summarize"));
+    }
+
+    let transcript: String = middle.iter()
+        .map(|m| format!("{}: {}\n\n", m.role.as_str(), m.content))
+        .collect();
+    let mut summary_config = config.clone();
+    summary_config.max_tokens = (budget / 4).max(1);
+    let request = [ChatMessage::system(SUMMARY_PROMPT), ChatMessage::user(transcript)];
+    let request = drop_oldest(&summary_config, &request, prompt_budget(&summary_config).unwrap_or(usize::MAX));
+    let summary = config.retry.run(|| client.chat_once(&summary_config, &request)).await?;
+    debug!("Summarized {} messages into about {} tokens", middle.len(), tokens::count_tokens(&config.model, &summary));
+
+    Ok(system.into_iter()
+        .chain(std::iter::once(ChatMessage::system(format!("Summary of the earlier conversation:\n{}", summary.trim()))))
+        .chain(turns[..head].iter().cloned())
+        .chain(turns[tail..].iter().cloned())
+        .collect())
+}
+
+fn split(messages: &[ChatMessage]) -> (Vec<ChatMessage>, Vec<ChatMessage>) {
+    messages.iter().cloned().partition(|m| m.role == ChatRole::System)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::test_utils;
+
+    fn chat() -> Vec<ChatMessage> {
+        let mut chat = vec![ChatMessage::system("You are terse.")];
+        for i in 0..10 {
+            chat.push(ChatMessage::user(format!("Question {} {}", i, "padding ".repeat(20))));
+            chat.push(ChatMessage::assistant(format!("Answer {} {}", i, "padding ".repeat(20))));
+        }
+        chat.push(ChatMessage::user("Final question"));
+        chat
+    }
+
+    fn config(strategy: TruncationStrategy) -> LLMConfig {
+        let mut config = LLMConfig::with_openai_compatible("http://127.0.0.1:1", "gpt-4");
+        config.context_window = Some(400);
+        config.max_tokens = 100;
+        config.truncation = strategy;
+        config
+    }
+
+    #[test]
+    fn test_drop_oldest_and_keep_system() {
+        let config = config(TruncationStrategy::DropOldest);
+        let budget = prompt_budget(&config).unwrap();
+        assert_eq!(budget, 300);
+
+        let fitted = drop_oldest(&config, &chat(), budget);
+        assert!(chat_tokens(&config, &fitted) <= budget);
+        assert!(fitted.len() < chat().len());
+        assert_eq!(fitted[0].content, "You are terse.");
+        assert_eq!(fitted[1].role, ChatRole::User);
+        assert_eq!(fitted.last().unwrap().content, "Final question");
+
+        let fitted = keep_system(&chat());
+        let contents: Vec<&str> = fitted.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, vec!["You are terse.", "Final question"]);
+    }
+
+    #[tokio::test]
+    async fn test_summarize_middle() {
+        let (url, recorded) = test_utils::start_recording_server(serde_json::json!({
+            "choices": [{ "message": { "role": "assistant", "content": "They asked ten questions." } }]
+        })).await;
+        let mut config = config(TruncationStrategy::SummarizeMiddle);
+        config.server_url = url;
+        let client = LLMClient::new(config).unwrap();
+
+        client.complete_chat(&chat()).await.unwrap();
+        let requests = recorded.lock().clone();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].body["messages"][0]["content"].as_str().unwrap().starts_with("Summarize"));
+
+        let sent = requests[1].body["messages"].as_array().unwrap();
+        let system = sent[0]["content"].as_str().unwrap();
+        assert!(system.starts_with("You are terse."));
+        assert!(system.ends_with("They asked ten questions."));
+        assert!(sent[1]["content"].as_str().unwrap().starts_with("Question 0"));
+        assert_eq!(sent.last().unwrap()["content"], "Final question");
+        assert!(sent.len() < chat().len());
+    }
+}
@@ -0,0 +1,205 @@
+//! Logging
+//!
+//! Tracing setup with runtime-adjustable filters:
+//! - Global subscriber initialization
+//! - Per-module level directives (e.g. `nexa_core::mcp::server=debug`)
+//! - Live filter reloads without restarting the daemon
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::OnceLock;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tracing_subscriber::{fmt as tracing_fmt, reload, EnvFilter, Registry};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use crate::error::NexaError;
+use tracing::info;
+
+/// Target name that refers to the default level for all modules
+pub const DEFAULT_TARGET: &str = "default";
+
+const LEVELS: [&str; 6] = ["trace", "debug", "info", "warn", "error", "off"];
+
+/// Default level plus per-target overrides
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LogLevels {
+    pub default: String,
+    pub targets: BTreeMap<String, String>,
+}
+
+impl LogLevels {
+    pub fn new(default: impl Into<String>) -> Self {
+        Self {
+            default: default.into(),
+            targets: BTreeMap::new(),
+        }
+    }
+
+    /// Set the level for a target, or the default level for `DEFAULT_TARGET`
+    pub fn set(&mut self, target: &str, level: &str) -> Result<(), NexaError> {
+        let level = level.to_lowercase();
+        if !LEVELS.contains(&level.as_str()) {
+            return Err(NexaError::invalid_input(format!(
+                "Invalid log level '{}', expected one of: {}", level, LEVELS.join(", ")
+            )));
+        }
+        let target = normalize_target(target)?;
+        if target == DEFAULT_TARGET {
+            self.default = level;
+        } else {
+            self.targets.insert(target, level);
+        }
+        Ok(())
+    }
+
+    /// Remove the override for a target so it falls back to the default level
+    pub fn reset(&mut self, target: &str) -> Result<bool, NexaError> {
+        Ok(self.targets.remove(&normalize_target(target)?).is_some())
+    }
+
+    /// Build an `EnvFilter` from these levels
+    pub fn to_filter(&self) -> Result<EnvFilter, NexaError> {
+        EnvFilter::try_new(self.to_string())
+            .map_err(|e| NexaError::invalid_input(format!("Invalid log filter: {}", e)))
+    }
+}
+
+impl fmt::Display for LogLevels {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.default)?;
+        for (target, level) in &self.targets {
+            write!(f, ",{}={}", target, level)?;
+        }
+        Ok(())
+    }
+}
+
+/// Map a module path given by the user to a tracing target
+///
+/// Paths relative to the crate (`mcp::server`) are prefixed with `nexa_core`.
+fn normalize_target(target: &str) -> Result<String, NexaError> {
+    let target = target.trim();
+    let valid = !target.is_empty()
+        && target.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':' || c == '-');
+    if !valid {
+        return Err(NexaError::invalid_input(format!("Invalid log target '{}'", target)));
+    }
+    if target == DEFAULT_TARGET || target.starts_with("nexa_core") || !is_crate_module(target) {
+        return Ok(target.to_string());
+    }
+    Ok(format!("nexa_core::{}", target))
+}
+
+/// Whether `target` is under one of the crate's top-level modules, as declared in lib.rs
+fn is_crate_module(target: &str) -> bool {
+    let root = target.split("::").next().unwrap_or(target);
+    include_str!("../lib.rs").lines()
+        .filter_map(|line| line.trim().strip_prefix("pub mod "))
+        .map(|rest| rest.trim_end_matches(|c: char| !(c.is_ascii_alphanumeric() || c == '_')))
+        .any(|module| module == root)
+}
+
+struct LogState {
+    levels: Mutex<LogLevels>,
+    handle: reload::Handle<EnvFilter, Registry>,
+}
+
+static STATE: OnceLock<LogState> = OnceLock::new();
+
+/// Install the global subscriber with a reloadable filter
+///
+/// `RUST_LOG` takes precedence over `default_level` when set.
+pub fn init(default_level: &str) -> Result<(), NexaError> {
+    init_with_writer(default_level, std::io::stdout)
+}
+
+/// Like `init`, but log to stderr, keeping stdout free for protocol output
+pub fn init_to_stderr(default_level: &str) -> Result<(), NexaError> {
+    init_with_writer(default_level, std::io::stderr)
+}
+
+fn init_with_writer<W>(default_level: &str, writer: W) -> Result<(), NexaError>
+where
+    W: for<'a> tracing_subscriber::fmt::MakeWriter<'a> + Send + Sync + 'static,
+{
+    let levels = LogLevels::new(std::env::var("RUST_LOG").unwrap_or_else(|_| default_level.to_string()));
+    let (filter, handle) = reload::Layer::new(levels.to_filter()?);
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_fmt::layer().with_writer(writer))
+        .try_init()
+        .map_err(|e| NexaError::system(format!("Failed to initialize logging: {}", e)))?;
+
+    STATE.set(LogState { levels: Mutex::new(levels), handle })
+        .map_err(|_| NexaError::system("Logging already initialized"))
+}
+
+fn state() -> Result<&'static LogState, NexaError> {
+    STATE.get().ok_or_else(|| NexaError::system("Logging is not initialized in this process"))
+}
+
+/// Current log levels of this process
+pub fn current_levels() -> Result<LogLevels, NexaError> {
+    Ok(state()?.levels.lock().clone())
+}
+
+/// Set the log level for a target and apply it immediately
+pub fn set_level(target: &str, level: &str) -> Result<LogLevels, NexaError> {
+    let state = state()?;
+    let mut levels = state.levels.lock();
+    let mut updated = levels.clone();
+    updated.set(target, level)?;
+    apply(state, &updated)?;
+    *levels = updated;
+    info!("Log filter set to {}", levels);
+    Ok(levels.clone())
+}
+
+/// Remove a target override and apply the change immediately
+pub fn reset_level(target: &str) -> Result<LogLevels, NexaError> {
+    let state = state()?;
+    let mut levels = state.levels.lock();
+    let mut updated = levels.clone();
+    if updated.reset(target)? {
+        apply(state, &updated)?;
+        *levels = updated;
+        info!("Log filter set to {}", levels);
+    }
+    Ok(levels.clone())
+}
+
+fn apply(state: &LogState, levels: &LogLevels) -> Result<(), NexaError> {
+    let filter = levels.to_filter()?;
+    state.handle.reload(filter)
+        .map_err(|e| NexaError::system(format!("Failed to reload log filter: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_levels() {
+        let mut levels = LogLevels::new("info");
+        levels.set("mcp::server", "DEBUG").unwrap();
+        levels.set("hyper", "warn").unwrap();
+        assert_eq!(levels.to_string(), "info,hyper=warn,nexa_core::mcp::server=debug");
+        assert!(levels.to_filter().is_ok());
+
+        levels.set(DEFAULT_TARGET, "trace").unwrap();
+        assert!(levels.reset("nexa_core::mcp::server").unwrap());
+        assert_eq!(levels.to_string(), "trace,hyper=warn");
+
+        // Every module declared in lib.rs is the crate's, external crates are left alone
+        for module in ["backup", "discovery", "tools", "events", "kubernetes", "plugins", "scripting", "containers"] {
+            assert_eq!(normalize_target(module).unwrap(), format!("nexa_core::{}", module));
+        }
+        assert_eq!(normalize_target("backup::scheduler").unwrap(), "nexa_core::backup::scheduler");
+        assert_eq!(normalize_target("tower_http::trace").unwrap(), "tower_http::trace");
+
+        assert!(matches!(levels.set("mcp", "loud"), Err(NexaError::InvalidInput(_))));
+        assert!(matches!(levels.set("mcp server", "info"), Err(NexaError::InvalidInput(_))));
+    }
+}
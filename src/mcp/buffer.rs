@@ -1,10 +1,15 @@
-use tokio::sync::{mpsc, broadcast};
-use std::collections::VecDeque;
+use tokio::sync::{mpsc, broadcast, Notify};
+use tokio_util::time::DelayQueue;
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
 use std::sync::Arc;
-use parking_lot::RwLock;
-use tracing::{debug, error};
+use parking_lot::{Mutex, RwLock};
+use tracing::{debug, error, warn};
 use serde::{Serialize, Deserialize};
 use std::time::{Duration, SystemTime};
+use crate::error::NexaError;
+use crate::mcp::metrics::MetricsCollector;
+use crate::mcp::trace::TraceContext;
 
 /// Message priority levels
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Hash)]
@@ -32,6 +37,120 @@ pub struct BufferedMessage {
     pub max_attempts: u32,
     /// Optional delay before processing
     pub delay_until: Option<SystemTime>,
+    /// Dot-separated subject, such as `tasks.assignment`, for subscription filters
+    #[serde(default)]
+    pub topic: Option<String>,
+    /// Trace of the request the message was created for
+    #[serde(flatten)]
+    pub trace: Option<TraceContext>,
+}
+
+impl BufferedMessage {
+    /// Set the topic of the message
+    pub fn with_topic(mut self, topic: impl Into<String>) -> Self {
+        self.topic = Some(topic.into());
+        self
+    }
+
+    /// When the message became, or becomes, available for delivery
+    pub fn ready_at(&self) -> SystemTime {
+        self.delay_until.map_or(self.created_at, |delay_until| delay_until.max(self.created_at))
+    }
+}
+
+/// Topic patterns a subscription receives messages for
+///
+/// Patterns match topics segment by segment, where `*` matches any one segment and a
+/// trailing `>` any remaining ones: `tasks.*` matches `tasks.assignment`, `tasks.>` also
+/// matches `tasks.assignment.retry`. Messages without a topic match no pattern.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TopicFilter {
+    patterns: Vec<String>,
+}
+
+impl TopicFilter {
+    pub fn new<I, S>(patterns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self { patterns: patterns.into_iter().map(Into::into).collect() }
+    }
+
+    /// Whether a message with this topic passes the filter
+    pub fn matches(&self, topic: Option<&str>) -> bool {
+        let Some(topic) = topic else { return false };
+        self.patterns.iter().any(|pattern| {
+            let mut segments = topic.split('.');
+            for part in pattern.split('.') {
+                match (part, segments.next()) {
+                    (">", Some(_)) => return true,
+                    ("*", Some(_)) => {}
+                    (part, Some(segment)) if part == segment => {}
+                    _ => return false,
+                }
+            }
+            segments.next().is_none()
+        })
+    }
+}
+
+/// Receiver of the buffer's messages that pass a `TopicFilter`
+#[derive(Debug)]
+pub struct Subscription {
+    receiver: broadcast::Receiver<BufferedMessage>,
+    filter: TopicFilter,
+}
+
+impl Subscription {
+    /// Wait for the next matching message
+    pub async fn recv(&mut self) -> Result<BufferedMessage, broadcast::error::RecvError> {
+        loop {
+            let msg = self.receiver.recv().await?;
+            if self.filter.matches(msg.topic.as_deref()) {
+                return Ok(msg);
+            }
+        }
+    }
+
+    pub fn filter(&self) -> &TopicFilter {
+        &self.filter
+    }
+}
+
+/// What `publish` does when the buffer or the message's priority queue is full
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// Fail with `PublishError::Full`
+    #[default]
+    Reject,
+    /// Wait until a message is popped or expires
+    Wait,
+    /// Write the message to this directory, and queue it once there is room
+    Spill(PathBuf),
+}
+
+/// Why a message could not be published
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum PublishError {
+    #[error("Message of {size} bytes exceeds the maximum of {max}")]
+    TooLarge { size: usize, max: usize },
+    #[error("{0:?} priority queue is full")]
+    Full(Priority),
+    #[error("Failed to spill message to disk: {0}")]
+    Spill(String),
+    #[error("Message buffer is closed")]
+    Closed,
+}
+
+impl From<PublishError> for NexaError {
+    fn from(e: PublishError) -> Self {
+        match e {
+            PublishError::TooLarge { .. } => NexaError::invalid_input(e.to_string()),
+            PublishError::Full(_) => NexaError::unavailable(e.to_string()),
+            PublishError::Spill(_) | PublishError::Closed => NexaError::system(e.to_string()),
+        }
+    }
 }
 
 /// Configuration for the message buffer
@@ -39,6 +158,10 @@ pub struct BufferedMessage {
 pub struct BufferConfig {
     /// Maximum buffer capacity
     pub capacity: usize,
+    /// Maximum messages of each priority, queued or scheduled
+    pub queue_capacity: usize,
+    /// What to do with messages published while full
+    pub overflow: OverflowPolicy,
     /// Maximum message size in bytes
     pub max_message_size: usize,
     /// Default message TTL
@@ -47,16 +170,26 @@ pub struct BufferConfig {
     pub max_attempts: u32,
     /// Cleanup interval
     pub cleanup_interval: Duration,
+    /// How long a published message id is remembered, so that republishing it is
+    /// ignored unless its attempts went up; zero disables deduplication
+    pub dedup_window: Duration,
+    /// Wait after which a queued message is promoted one priority level, for each
+    /// multiple of it; `None`, the default, keeps strict priority order
+    pub aging_threshold: Option<Duration>,
 }
 
 impl Default for BufferConfig {
     fn default() -> Self {
         Self {
             capacity: 10000,
+            queue_capacity: 10000,
+            overflow: OverflowPolicy::Reject,
             max_message_size: 1024 * 1024, // 1MB
             message_ttl: Duration::from_secs(3600), // 1 hour
             max_attempts: 3,
             cleanup_interval: Duration::from_secs(60),
+            dedup_window: Duration::from_secs(300),
+            aging_threshold: None,
         }
     }
 }
@@ -74,6 +207,112 @@ pub struct MessageBuffer {
     sub_tx: broadcast::Sender<BufferedMessage>,
     /// Current buffer size
     size: Arc<RwLock<usize>>,
+    /// Messages waiting for their `delay_until`
+    scheduled: Arc<RwLock<HashMap<uuid::Uuid, BufferedMessage>>>,
+    /// Recently published message ids, with when and at which attempt
+    seen: Arc<RwLock<HashMap<uuid::Uuid, (SystemTime, u32)>>>,
+    /// Room left in the queues
+    slots: Arc<Slots>,
+    /// Where saturation is recorded
+    metrics: Option<Arc<MetricsCollector>>,
+}
+
+/// Accounting of the messages held per priority, including spilled ones waiting for room
+#[derive(Debug)]
+struct Slots {
+    capacity: usize,
+    queue_capacity: usize,
+    /// Messages accepted and not yet popped or expired, per priority
+    held: Mutex<[usize; 4]>,
+    /// Spilled message files per priority, oldest first
+    spilled: Mutex<Vec<VecDeque<PathBuf>>>,
+    /// Signalled whenever a slot is released
+    space: Notify,
+    pub_tx: mpsc::Sender<BufferedMessage>,
+}
+
+impl Slots {
+    fn try_reserve(&self, priority: Priority) -> bool {
+        let mut held = self.held.lock();
+        let total: usize = held.iter().sum();
+        if total >= self.capacity || held[priority as usize] >= self.queue_capacity {
+            return false;
+        }
+        held[priority as usize] += 1;
+        true
+    }
+
+    /// Give back `count` slots of `priority`, moving spilled messages into them
+    fn release(&self, priority: usize, count: usize) {
+        if count == 0 {
+            return;
+        }
+        {
+            let mut held = self.held.lock();
+            held[priority] = held[priority].saturating_sub(count);
+        }
+        self.refill(priority);
+        self.space.notify_waiters();
+    }
+
+    /// Queue spilled messages of `priority` while there is room
+    fn refill(&self, priority: usize) {
+        loop {
+            let Some(path) = self.spilled.lock()[priority].front().cloned() else { return };
+            let level = [Priority::Low, Priority::Normal, Priority::High, Priority::Critical][priority];
+            if !self.try_reserve(level) {
+                return;
+            }
+            self.spilled.lock()[priority].pop_front();
+            let msg = std::fs::read(&path).ok().and_then(|data| serde_json::from_slice::<BufferedMessage>(&data).ok());
+            let _ = std::fs::remove_file(&path);
+            let queued = match msg {
+                Some(msg) => self.pub_tx.try_send(msg).is_ok(),
+                None => false,
+            };
+            if !queued {
+                warn!("Dropped spilled message {}", path.display());
+                self.held.lock()[priority] -= 1;
+            }
+        }
+    }
+
+    fn has_spilled(&self, priority: Priority) -> bool {
+        !self.spilled.lock()[priority as usize].is_empty()
+    }
+
+    /// Write a message to `dir`, to be queued once there is room
+    fn spill(&self, dir: &std::path::Path, msg: &BufferedMessage) -> Result<(), PublishError> {
+        let path = dir.join(format!("{}.json", msg.id));
+        let data = serde_json::to_vec(msg).map_err(|e| PublishError::Spill(e.to_string()))?;
+        std::fs::write(&path, data).map_err(|e| PublishError::Spill(e.to_string()))?;
+        self.spilled.lock()[msg.priority as usize].push_back(path);
+        Ok(())
+    }
+
+    /// Index the messages spilled by an earlier run
+    fn load_spilled(&self, dir: &std::path::Path) {
+        if let Err(e) = std::fs::create_dir_all(dir) {
+            error!("Failed to create spill directory {}: {}", dir.display(), e);
+            return;
+        }
+        let mut found: Vec<(SystemTime, Priority, PathBuf)> = std::fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|path| {
+                let msg: BufferedMessage = serde_json::from_slice(&std::fs::read(&path).ok()?).ok()?;
+                Some((msg.created_at, msg.priority, path))
+            })
+            .collect();
+        found.sort();
+        let mut spilled = self.spilled.lock();
+        for (_, priority, path) in found {
+            spilled[priority as usize].push_back(path);
+        }
+    }
 }
 
 impl MessageBuffer {
@@ -91,13 +330,51 @@ impl MessageBuffer {
             VecDeque::with_capacity(config.capacity), // Critical
         ]));
         
+        let slots = Arc::new(Slots {
+            capacity: config.capacity,
+            queue_capacity: config.queue_capacity,
+            held: Mutex::new([0; 4]),
+            spilled: Mutex::new(vec![VecDeque::new(); 4]),
+            space: Notify::new(),
+            pub_tx: pub_tx.clone(),
+        });
+        if let OverflowPolicy::Spill(dir) = &config.overflow {
+            slots.load_spilled(dir);
+            for priority in 0..4 {
+                slots.refill(priority);
+            }
+        }
+
         let size = Arc::new(RwLock::new(0usize));
+        let scheduled = Arc::new(RwLock::new(HashMap::new()));
+        let seen: Arc<RwLock<HashMap<uuid::Uuid, (SystemTime, u32)>>> = Arc::new(RwLock::new(HashMap::new()));
         let queues_clone = queues.clone();
         let size_clone = size.clone();
+        let scheduled_clone = scheduled.clone();
         
-        // Start message processor
+        // Start message processor, holding delayed messages in a timer wheel until due
         tokio::spawn(async move {
-            while let Some(msg) = pub_rx.recv().await {
+            let mut timers = DelayQueue::new();
+            loop {
+                let msg = tokio::select! {
+                    msg = pub_rx.recv() => {
+                        let Some(msg) = msg else { break };
+                        match msg.ready_at().duration_since(SystemTime::now()) {
+                            Ok(delay) if !delay.is_zero() => {
+                                scheduled_clone.write().insert(msg.id, msg.clone());
+                                timers.insert(msg, delay);
+                                continue;
+                            }
+                            _ => msg,
+                        }
+                    }
+                    Some(expired) = std::future::poll_fn(|cx| timers.poll_expired(cx)), if !timers.is_empty() => {
+                        let msg = expired.into_inner();
+                        scheduled_clone.write().remove(&msg.id);
+                        msg
+                    }
+                };
+
                 let priority = msg.priority as usize;
                 {
                     let mut queues = queues_clone.write();
@@ -116,6 +393,9 @@ impl MessageBuffer {
         let size_cleanup = size.clone();
         let cleanup_interval = config.cleanup_interval;
         let message_ttl = config.message_ttl;
+        let seen_cleanup = seen.clone();
+        let slots_cleanup = slots.clone();
+        let dedup_window = config.dedup_window;
         tokio::spawn(async move {
             let mut interval = tokio::time::interval(cleanup_interval);
             loop {
@@ -125,15 +405,17 @@ impl MessageBuffer {
                 {
                     let mut qs = queues_cleanup.write();
                     let mut size_ref = size_cleanup.write();
-                    for queue in qs.iter_mut() {
+                    for (priority, queue) in qs.iter_mut().enumerate() {
                         let initial = queue.len();
                         queue.retain(|msg| {
-                            msg.created_at.elapsed().map_or(false, |elapsed| elapsed < message_ttl)
+                            msg.ready_at().elapsed().map_or(false, |elapsed| elapsed < message_ttl)
                         });
                         total_removed += initial - queue.len();
+                        slots_cleanup.release(priority, initial - queue.len());
                     }
                     *size_ref = (*size_ref).saturating_sub(total_removed);
                 }
+                forget_seen(&seen_cleanup, dedup_window);
                 if total_removed > 0 {
                     debug!("Cleaned up {} expired messages", total_removed);
                 }
@@ -146,34 +428,135 @@ impl MessageBuffer {
             pub_tx,
             sub_tx,
             size,
+            scheduled,
+            seen,
+            slots,
+            metrics: None,
         }
     }
+
+    /// Record saturation events in `metrics`
+    pub fn with_metrics(mut self, metrics: Arc<MetricsCollector>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
     
     /// Get a subscriber for receiving messages
     pub fn subscribe(&self) -> broadcast::Receiver<BufferedMessage> {
         self.sub_tx.subscribe()
     }
+
+    /// Get a subscriber for the messages whose topic passes `filter`
+    pub fn subscribe_filtered(&self, filter: TopicFilter) -> Subscription {
+        Subscription { receiver: self.sub_tx.subscribe(), filter }
+    }
     
     /// Publish a message to the buffer
-    pub async fn publish(&self, msg: BufferedMessage) -> Result<(), String> {
+    ///
+    /// When full, the message is rejected, waits for room or is spilled to disk
+    /// according to `BufferConfig::overflow`.
+    pub async fn publish(&self, msg: BufferedMessage) -> Result<(), PublishError> {
         // Check message size
         if msg.payload.len() > self.config.max_message_size {
-            return Err("Message exceeds maximum size".to_string());
+            return Err(PublishError::TooLarge { size: msg.payload.len(), max: self.config.max_message_size });
+        }
+
+        if self.is_duplicate(&msg) {
+            debug!("Ignoring duplicate message {}", msg.id);
+            return Ok(());
         }
 
         // Check buffer capacity
-        if self.len() >= self.config.capacity {
-            return Err("Buffer is full".to_string());
+        let spill_dir = match &self.config.overflow {
+            OverflowPolicy::Spill(dir) => Some(dir),
+            _ => None,
+        };
+        // Spilled messages of the same priority go first
+        let behind_spilled = spill_dir.is_some() && self.slots.has_spilled(msg.priority);
+        if behind_spilled || !self.slots.try_reserve(msg.priority) {
+            self.record_saturation(msg.priority).await;
+            match (&self.config.overflow, spill_dir) {
+                (_, Some(dir)) => {
+                    self.slots.spill(dir, &msg)?;
+                    self.remember(&msg);
+                    return Ok(());
+                }
+                (OverflowPolicy::Wait, _) => self.wait_for_room(msg.priority).await,
+                _ => return Err(PublishError::Full(msg.priority)),
+            }
+        }
+
+        // Ignore messages already published, such as resends after a reconnect
+        if !self.remember(&msg) {
+            debug!("Ignoring duplicate message {}", msg.id);
+            self.slots.release(msg.priority as usize, 1);
+            return Ok(());
         }
 
         // Publish to channel
+        let (id, priority) = (msg.id, msg.priority);
         if let Err(e) = self.pub_tx.send(msg).await {
             error!("Failed to publish message: {}", e);
-            return Err("Failed to publish message".to_string());
+            self.seen.write().remove(&id);
+            self.slots.release(priority as usize, 1);
+            return Err(PublishError::Closed);
         }
 
         Ok(())
     }
+
+    async fn wait_for_room(&self, priority: Priority) {
+        loop {
+            let space = self.slots.space.notified();
+            tokio::pin!(space);
+            space.as_mut().enable();
+            if self.slots.try_reserve(priority) {
+                return;
+            }
+            space.await;
+        }
+    }
+
+    async fn record_saturation(&self, priority: Priority) {
+        debug!("{:?} priority queue is saturated", priority);
+        if let Some(metrics) = &self.metrics {
+            metrics.record_saturation().await;
+        }
+    }
+
+    /// Whether the message was published within the deduplication window
+    fn is_duplicate(&self, msg: &BufferedMessage) -> bool {
+        if self.config.dedup_window.is_zero() {
+            return false;
+        }
+        self.seen.read().get(&msg.id).is_some_and(|(at, attempts)| {
+            msg.attempts <= *attempts
+                && at.elapsed().is_ok_and(|age| age < self.config.dedup_window)
+        })
+    }
+
+    /// Record the message as published, returning false if it is a duplicate
+    fn remember(&self, msg: &BufferedMessage) -> bool {
+        if self.config.dedup_window.is_zero() {
+            return true;
+        }
+        let mut seen = self.seen.write();
+        let now = SystemTime::now();
+        let duplicate = seen.get(&msg.id).is_some_and(|(at, attempts)| {
+            msg.attempts <= *attempts
+                && now.duration_since(*at).is_ok_and(|age| age < self.config.dedup_window)
+        });
+        if !duplicate {
+            seen.insert(msg.id, (now, msg.attempts));
+        }
+        !duplicate
+    }
+
+    /// Publish a message to be delivered once `delay` has passed
+    pub async fn publish_delayed(&self, mut msg: BufferedMessage, delay: Duration) -> Result<(), PublishError> {
+        msg.delay_until = Some(SystemTime::now() + delay);
+        self.publish(msg).await
+    }
     
     /// Pop a message from the specified priority queue
     pub fn pop(&self, priority: Priority) -> Option<BufferedMessage> {
@@ -181,6 +564,7 @@ impl MessageBuffer {
         let mut size = self.size.write();
         if let Some(msg) = queues[priority as usize].pop_front() {
             *size = size.saturating_sub(1);
+            self.slots.release(priority as usize, 1);
             Some(msg)
         } else {
             None
@@ -188,16 +572,47 @@ impl MessageBuffer {
     }
     
     /// Pop the highest priority message available
+    ///
+    /// With aging, priority is that of the message raised by its wait, so lower
+    /// priorities are not starved by sustained higher priority traffic. Among equal
+    /// priorities the oldest message goes first.
     pub fn pop_any(&self) -> Option<BufferedMessage> {
+        self.pop_any_at(SystemTime::now())
+    }
+
+    /// Pop the highest priority message available, aged as of `now`
+    fn pop_any_at(&self, now: SystemTime) -> Option<BufferedMessage> {
         let mut queues = self.queues.write();
+        let (level, _, _) = queues.iter()
+            .enumerate()
+            .filter_map(|(level, queue)| queue.front().map(|msg| (level, self.aged_level(msg, now), msg.ready_at())))
+            .max_by(|a, b| a.1.cmp(&b.1).then(b.2.cmp(&a.2)).then(a.0.cmp(&b.0)))?;
+        let msg = queues[level].pop_front()?;
         let mut size = self.size.write();
-        for queue in queues.iter_mut().rev() {  // Start from highest priority
-            if let Some(msg) = queue.pop_front() {
-                *size = size.saturating_sub(1);
-                return Some(msg);
+        *size = size.saturating_sub(1);
+        self.slots.release(level, 1);
+        Some(msg)
+    }
+
+    /// Priority level of a message once aged
+    fn aged_level(&self, msg: &BufferedMessage, now: SystemTime) -> usize {
+        let level = msg.priority as usize;
+        match self.config.aging_threshold.filter(|threshold| !threshold.is_zero()) {
+            Some(threshold) => {
+                let waited = now.duration_since(msg.ready_at()).unwrap_or_default();
+                let promotions = (waited.as_nanos() / threshold.as_nanos()) as usize;
+                level.saturating_add(promotions).min(Priority::Critical as usize)
             }
+            None => level,
         }
-        None
+    }
+    
+    /// Copy all queued messages, highest priority first, then the scheduled ones
+    pub fn snapshot(&self) -> Vec<BufferedMessage> {
+        let queues = self.queues.read();
+        let mut scheduled: Vec<_> = self.scheduled.read().values().cloned().collect();
+        scheduled.sort_by_key(|msg| msg.ready_at());
+        queues.iter().rev().flat_map(|q| q.iter().cloned()).chain(scheduled).collect()
     }
     
     /// Clean up expired messages
@@ -208,18 +623,20 @@ impl MessageBuffer {
         {
             let mut queues = self.queues.write();
             let mut size = self.size.write();
-            for queue in queues.iter_mut() {
+            for (priority, queue) in queues.iter_mut().enumerate() {
                 let initial_len = queue.len();
                 queue.retain(|msg| {
-                    match msg.created_at.elapsed() {
+                    match msg.ready_at().elapsed() {
                         Ok(elapsed) => elapsed < self.config.message_ttl,
                         Err(_) => false,
                     }
                 });
                 total_removed += initial_len - queue.len();
+                self.slots.release(priority, initial_len - queue.len());
             }
             *size = size.saturating_sub(total_removed);
         }
+        forget_seen(&self.seen, self.config.dedup_window);
         
         if total_removed > 0 {
             debug!("Cleaned up {} expired messages", total_removed);
@@ -236,12 +653,27 @@ impl MessageBuffer {
         self.len() == 0
     }
 
+    /// Number of messages waiting for their `delay_until`
+    pub fn scheduled(&self) -> usize {
+        self.scheduled.read().len()
+    }
+
+    /// Number of messages spilled to disk waiting for room
+    pub fn spilled(&self) -> usize {
+        self.slots.spilled.lock().iter().map(VecDeque::len).sum()
+    }
+
     pub async fn cleanup_expired(&mut self) {
         let _now = SystemTime::now();
         // TODO: Implement cleanup logic
     }
 }
 
+/// Drop the ids published longer than `window` ago
+fn forget_seen(seen: &RwLock<HashMap<uuid::Uuid, (SystemTime, u32)>>, window: Duration) {
+    seen.write().retain(|_, (at, _)| at.elapsed().is_ok_and(|age| age < window));
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -259,6 +691,8 @@ mod tests {
             attempts: 0,
             max_attempts: 3,
             delay_until: None,
+            topic: None,
+            trace: None,
         };
 
         // Test publish
@@ -285,6 +719,8 @@ mod tests {
             attempts: 0,
             max_attempts: 3,
             delay_until: None,
+            topic: None,
+            trace: None,
         };
 
         let low_msg = BufferedMessage {
@@ -295,6 +731,8 @@ mod tests {
             attempts: 0,
             max_attempts: 3,
             delay_until: None,
+            topic: None,
+            trace: None,
         };
 
         // Publish messages in reverse priority order
@@ -314,6 +752,237 @@ mod tests {
         assert_eq!(received.priority, Priority::Low);
     }
 
+    fn message(priority: Priority) -> BufferedMessage {
+        message_at(priority, SystemTime::now())
+    }
+
+    fn message_at(priority: Priority, created_at: SystemTime) -> BufferedMessage {
+        BufferedMessage {
+            id: Uuid::new_v4(),
+            payload: vec![priority as u8],
+            priority,
+            created_at,
+            attempts: 0,
+            max_attempts: 3,
+            delay_until: None,
+            topic: None,
+            trace: None,
+        }
+    }
+
+    async fn wait_for_len(buffer: &MessageBuffer, len: usize) {
+        while buffer.len() < len {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    }
+
+    async fn pop_order(aging_threshold: Option<Duration>) -> Vec<Priority> {
+        let buffer = MessageBuffer::new(BufferConfig { aging_threshold, ..Default::default() });
+        let now = SystemTime::now();
+        buffer.publish(message_at(Priority::Low, now - Duration::from_millis(250))).await.unwrap();
+        buffer.publish(message_at(Priority::Critical, now)).await.unwrap();
+        buffer.publish(message_at(Priority::High, now)).await.unwrap();
+        wait_for_len(&buffer, 3).await;
+        std::iter::from_fn(|| buffer.pop_any_at(now)).map(|msg| msg.priority).collect()
+    }
+
+    #[tokio::test]
+    async fn test_priority_aging() {
+        assert_eq!(BufferConfig::default().aging_threshold, None);
+
+        // Without aging the low priority message waits behind newer traffic
+        assert_eq!(pop_order(None).await, vec![Priority::Critical, Priority::High, Priority::Low]);
+
+        // Having waited two thresholds it counts as High, and goes before the newer High
+        let order = pop_order(Some(Duration::from_millis(100))).await;
+        assert_eq!(order, vec![Priority::Critical, Priority::Low, Priority::High]);
+    }
+
+    #[tokio::test]
+    async fn test_aging_fairness() {
+        // A steady stream of High messages, one popped per publish every 5ms, never drains
+        let buffer = MessageBuffer::new(BufferConfig {
+            aging_threshold: Some(Duration::from_millis(20)),
+            ..Default::default()
+        });
+        let start = SystemTime::now();
+        buffer.publish(message_at(Priority::Low, start)).await.unwrap();
+        buffer.publish(message_at(Priority::High, start)).await.unwrap();
+        wait_for_len(&buffer, 2).await;
+
+        let mut popped = 0;
+        let low_served_at = loop {
+            let now = start + Duration::from_millis(5) * (popped + 1);
+            buffer.publish(message_at(Priority::High, now)).await.unwrap();
+            wait_for_len(&buffer, 3).await;
+            let msg = buffer.pop_any_at(now).unwrap();
+            if msg.priority == Priority::Low {
+                break now;
+            }
+            popped += 1;
+            assert!(popped <= 100, "Low priority message starved");
+        };
+        // Two thresholds raise it to High, and it is older than every High queued
+        assert_eq!(low_served_at.duration_since(start).unwrap(), Duration::from_millis(40));
+        assert_eq!(popped, 7);
+    }
+
+    #[tokio::test]
+    async fn test_delayed_delivery() {
+        let buffer = MessageBuffer::new(BufferConfig::default());
+        let mut subscriber = buffer.subscribe();
+        let later = message(Priority::Critical);
+        buffer.publish_delayed(later.clone(), Duration::from_millis(200)).await.unwrap();
+        let now = message(Priority::Low);
+        buffer.publish(now.clone()).await.unwrap();
+        wait_for_len(&buffer, 1).await;
+
+        // Only the due message can be popped; the delayed one waits
+        assert_eq!(buffer.scheduled(), 1);
+        assert_eq!(buffer.snapshot().len(), 2);
+        assert_eq!(buffer.pop_any().unwrap().id, now.id);
+        assert!(buffer.pop_any().is_none());
+        assert_eq!(subscriber.recv().await.unwrap().id, now.id);
+
+        let released = tokio::time::timeout(Duration::from_secs(2), subscriber.recv()).await.unwrap().unwrap();
+        assert_eq!(released.id, later.id);
+        assert!(released.ready_at().elapsed().is_ok());
+        assert_eq!(buffer.scheduled(), 0);
+        assert_eq!(buffer.pop(Priority::Critical).unwrap().id, later.id);
+    }
+
+    #[tokio::test]
+    async fn test_deduplication() {
+        let buffer = MessageBuffer::new(BufferConfig {
+            dedup_window: Duration::from_millis(200),
+            ..Default::default()
+        });
+        let msg = message(Priority::High);
+        buffer.publish(msg.clone()).await.unwrap();
+        buffer.publish(msg.clone()).await.unwrap();
+        wait_for_len(&buffer, 1).await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(buffer.len(), 1);
+
+        // A retry with more attempts is a new delivery
+        let mut retry = buffer.pop_any().unwrap();
+        retry.attempts += 1;
+        buffer.publish(retry.clone()).await.unwrap();
+        buffer.publish(retry.clone()).await.unwrap();
+        wait_for_len(&buffer, 1).await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(buffer.len(), 1);
+        assert!(buffer.pop_any().is_some());
+
+        // Once the window has passed the id is forgotten
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        buffer.publish(retry).await.unwrap();
+        wait_for_len(&buffer, 1).await;
+    }
+
+    #[tokio::test]
+    async fn test_overflow_reject() {
+        let metrics = Arc::new(MetricsCollector::new());
+        let buffer = MessageBuffer::new(BufferConfig { queue_capacity: 2, ..Default::default() })
+            .with_metrics(metrics.clone());
+        buffer.publish(message(Priority::Low)).await.unwrap();
+        buffer.publish(message(Priority::Low)).await.unwrap();
+        assert_eq!(buffer.publish(message(Priority::Low)).await, Err(PublishError::Full(Priority::Low)));
+        assert_eq!(metrics.get_metrics().await.saturation_count, 1);
+
+        // Other priorities have their own room, and popping makes some
+        buffer.publish(message(Priority::High)).await.unwrap();
+        wait_for_len(&buffer, 3).await;
+        buffer.pop(Priority::Low).unwrap();
+        buffer.publish(message(Priority::Low)).await.unwrap();
+
+        let too_large = BufferedMessage { payload: vec![0; 2 * 1024 * 1024], ..message(Priority::Low) };
+        assert!(matches!(buffer.publish(too_large).await, Err(PublishError::TooLarge { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_overflow_wait() {
+        let buffer = Arc::new(MessageBuffer::new(BufferConfig {
+            queue_capacity: 1,
+            overflow: OverflowPolicy::Wait,
+            ..Default::default()
+        }));
+        let first = message(Priority::Normal);
+        buffer.publish(first.clone()).await.unwrap();
+        let waiting = tokio::spawn({
+            let buffer = buffer.clone();
+            async move { buffer.publish(message(Priority::Normal)).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiting.is_finished());
+
+        wait_for_len(&buffer, 1).await;
+        assert_eq!(buffer.pop_any().unwrap().id, first.id);
+        tokio::time::timeout(Duration::from_secs(1), waiting).await.unwrap().unwrap().unwrap();
+        wait_for_len(&buffer, 1).await;
+    }
+
+    #[tokio::test]
+    async fn test_overflow_spill() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = BufferConfig {
+            queue_capacity: 1,
+            overflow: OverflowPolicy::Spill(dir.path().to_path_buf()),
+            ..Default::default()
+        };
+        let buffer = MessageBuffer::new(config.clone());
+        let messages: Vec<_> = (0..3).map(|_| message(Priority::Normal)).collect();
+        for msg in &messages {
+            buffer.publish(msg.clone()).await.unwrap();
+        }
+        assert_eq!(buffer.spilled(), 2);
+
+        // Popping makes room for the oldest spilled message
+        wait_for_len(&buffer, 1).await;
+        assert_eq!(buffer.pop_any().unwrap().id, messages[0].id);
+        assert_eq!(buffer.spilled(), 1);
+        wait_for_len(&buffer, 1).await;
+
+        // What is left on disk is picked up by the next buffer
+        drop(buffer);
+        let restarted = MessageBuffer::new(config);
+        assert_eq!(restarted.spilled(), 0);
+        wait_for_len(&restarted, 1).await;
+        assert_eq!(restarted.pop_any().unwrap().id, messages[2].id);
+    }
+
+    #[test]
+    fn test_topic_filter() {
+        let filter = TopicFilter::new(["tasks.*", "agents.>", "metrics"]);
+        assert!(filter.matches(Some("tasks.assignment")));
+        assert!(!filter.matches(Some("tasks.assignment.retry")));
+        assert!(!filter.matches(Some("tasks")));
+        assert!(filter.matches(Some("agents.status")));
+        assert!(filter.matches(Some("agents.status.busy")));
+        assert!(!filter.matches(Some("agents")));
+        assert!(filter.matches(Some("metrics")));
+        assert!(!filter.matches(Some("metrics.cpu")));
+        assert!(!filter.matches(None));
+    }
+
+    #[tokio::test]
+    async fn test_filtered_subscription() {
+        let buffer = MessageBuffer::new(BufferConfig::default());
+        let mut tasks = buffer.subscribe_filtered(TopicFilter::new(["tasks.>"]));
+        let mut everything = buffer.subscribe();
+
+        let untopiced = message(Priority::High);
+        let status = message(Priority::High).with_topic("agents.status");
+        let assignment = message(Priority::Low).with_topic("tasks.assignment");
+        for msg in [&untopiced, &status, &assignment] {
+            buffer.publish(msg.clone()).await.unwrap();
+        }
+
+        assert_eq!(tasks.recv().await.unwrap().id, assignment.id);
+        assert_eq!(everything.recv().await.unwrap().id, untopiced.id);
+        assert_eq!(everything.recv().await.unwrap().id, status.id);
+    }
+
     #[tokio::test]
     async fn test_cleanup() {
         let buffer = MessageBuffer::new(BufferConfig {
@@ -330,6 +999,8 @@ mod tests {
             attempts: 0,
             max_attempts: 3,
             delay_until: None,
+            topic: None,
+            trace: None,
         };
 
         assert!(buffer.publish(msg).await.is_ok());
@@ -0,0 +1,449 @@
+//! Client for third-party Model Context Protocol servers
+//!
+//! - Connects over stdio, launching the server as a subprocess, or over WebSocket
+//! - Discovers the server's tools and registers them as `<server>__<tool>`
+//! - Calls are plain tools to agents, so tool loops can use them like built-in ones
+
+use std::collections::{BTreeMap, HashMap};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use futures::future::BoxFuture;
+use futures::{SinkExt, StreamExt};
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, Command};
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::protocol::Message;
+use tracing::{debug, info, warn};
+use crate::error::NexaError;
+use crate::tools::{Tool, ToolRegistry, ToolSpec};
+use super::jsonrpc::{JsonRpcError, JsonRpcRequest, JsonRpcResponse, METHOD_NOT_FOUND, PROTOCOL_VERSIONS};
+
+/// An MCP server whose tools agents may use
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct McpServerConfig {
+    /// Prefix of the server's tool names
+    pub name: String,
+    /// Program to launch, speaking MCP on its stdin and stdout
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    /// Arguments of `command`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    /// Extra environment variables of `command`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// WebSocket URL of a running server, instead of `command`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Seconds to wait for each response
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_timeout_secs() -> u64 {
+    60
+}
+
+impl McpServerConfig {
+    /// Server launched as `command args...`
+    pub fn stdio(name: impl Into<String>, command: impl Into<String>, args: &[&str]) -> Self {
+        Self {
+            name: name.into(),
+            command: Some(command.into()),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            env: BTreeMap::new(),
+            url: None,
+            timeout_secs: default_timeout_secs(),
+        }
+    }
+
+    /// Server listening at a WebSocket URL
+    pub fn websocket(name: impl Into<String>, url: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            command: None,
+            args: Vec::new(),
+            env: BTreeMap::new(),
+            url: Some(url.into()),
+            timeout_secs: default_timeout_secs(),
+        }
+    }
+}
+
+type Pending = Arc<Mutex<HashMap<u64, oneshot::Sender<JsonRpcResponse>>>>;
+
+/// Open transport to a server; dropping it closes the connection and stops the subprocess
+struct Connection {
+    outgoing: mpsc::UnboundedSender<String>,
+    pending: Pending,
+    next_id: AtomicU64,
+    tasks: Vec<JoinHandle<()>>,
+    _child: Option<Child>,
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+/// Connection to an MCP server, shared by the tools registered from it
+#[derive(Clone)]
+pub struct McpClient {
+    name: String,
+    timeout: Duration,
+    connection: Arc<Connection>,
+    server_info: Value,
+}
+
+impl std::fmt::Debug for McpClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("McpClient")
+            .field("name", &self.name)
+            .field("server_info", &self.server_info)
+            .finish()
+    }
+}
+
+impl McpClient {
+    /// Connect and complete the MCP handshake
+    pub async fn connect(config: &McpServerConfig) -> Result<Self, NexaError> {
+        let connection = match (&config.command, &config.url) {
+            (Some(command), None) => open_stdio(config, command)?,
+            (None, Some(url)) => open_websocket(url).await?,
+            _ => {
+                return Err(NexaError::config(format!(
+                    "MCP server '{}' needs either a command or a url", config.name
+                )))
+            }
+        };
+        let mut client = Self {
+            name: config.name.clone(),
+            timeout: Duration::from_secs(config.timeout_secs),
+            connection: Arc::new(connection),
+            server_info: Value::Null,
+        };
+
+        let result = client.request("initialize", json!({
+            "protocolVersion": PROTOCOL_VERSIONS[0],
+            "capabilities": {},
+            "clientInfo": { "name": "nexa", "version": env!("CARGO_PKG_VERSION") },
+        })).await?;
+        client.server_info = result.get("serverInfo").cloned().unwrap_or(Value::Null);
+        client.notify("notifications/initialized")?;
+        info!("Connected to MCP server '{}' ({})", client.name, client.server_info);
+        Ok(client)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// `serverInfo` the server reported during the handshake
+    pub fn server_info(&self) -> &Value {
+        &self.server_info
+    }
+
+    /// Send a request and wait for its result
+    pub async fn request(&self, method: &str, params: Value) -> Result<Value, NexaError> {
+        let id = self.connection.next_id.fetch_add(1, Ordering::SeqCst);
+        let (tx, rx) = oneshot::channel();
+        self.connection.pending.lock().insert(id, tx);
+        self.send(&JsonRpcRequest::new(id, method, params))?;
+
+        match tokio::time::timeout(self.timeout, rx).await {
+            Ok(Ok(response)) => response.into_result(),
+            Ok(Err(_)) => Err(NexaError::unavailable(format!("MCP server '{}' closed the connection", self.name))),
+            Err(_) => {
+                self.connection.pending.lock().remove(&id);
+                Err(NexaError::timeout(format!(
+                    "MCP server '{}' did not answer {} within {}s", self.name, method, self.timeout.as_secs()
+                )))
+            }
+        }
+    }
+
+    fn notify(&self, method: &str) -> Result<(), NexaError> {
+        self.send(&JsonRpcRequest::notification(method, None))
+    }
+
+    fn send(&self, message: &JsonRpcRequest) -> Result<(), NexaError> {
+        self.connection.outgoing.send(serde_json::to_string(message)?)
+            .map_err(|_| NexaError::unavailable(format!("MCP server '{}' closed the connection", self.name)))
+    }
+
+    /// Tools the server offers, under their own names
+    pub async fn list_tools(&self) -> Result<Vec<ToolSpec>, NexaError> {
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match &cursor {
+                Some(cursor) => json!({ "cursor": cursor }),
+                None => json!({}),
+            };
+            let result = self.request("tools/list", params).await?;
+            for tool in result["tools"].as_array().into_iter().flatten() {
+                let Some(name) = tool["name"].as_str() else {
+                    warn!("MCP server '{}' listed a tool without a name", self.name);
+                    continue;
+                };
+                tools.push(ToolSpec {
+                    name: name.to_string(),
+                    description: tool["description"].as_str().unwrap_or_default().to_string(),
+                    input_schema: tool.get("inputSchema").cloned().unwrap_or_else(|| json!({ "type": "object" })),
+                });
+            }
+            match result["nextCursor"].as_str() {
+                Some(next) => cursor = Some(next.to_string()),
+                None => return Ok(tools),
+            }
+        }
+    }
+
+    /// Call a tool by the server's name for it
+    ///
+    /// Returns the structured content when the server provides it, otherwise the text
+    /// content, or the raw content items when some are not text.
+    pub async fn call_tool(&self, name: &str, args: Value) -> Result<Value, NexaError> {
+        let result = self.request("tools/call", json!({ "name": name, "arguments": args })).await?;
+        let content = result["content"].as_array().cloned().unwrap_or_default();
+        let texts: Vec<&str> = content.iter()
+            .filter(|item| item["type"] == "text")
+            .filter_map(|item| item["text"].as_str())
+            .collect();
+        if result["isError"].as_bool().unwrap_or(false) {
+            return Err(NexaError::system(format!(
+                "Tool '{}' of MCP server '{}' failed: {}", name, self.name, texts.join("\n")
+            )));
+        }
+        if let Some(structured) = result.get("structuredContent") {
+            return Ok(structured.clone());
+        }
+        if texts.len() == content.len() {
+            return Ok(Value::String(texts.join("\n")));
+        }
+        Ok(Value::Array(content))
+    }
+
+    /// Register the server's tools as `<server>__<tool>`, returning the registered names
+    pub async fn register_tools(&self, registry: &ToolRegistry) -> Result<Vec<String>, NexaError> {
+        let mut names = Vec::new();
+        for spec in self.list_tools().await? {
+            let tool = McpTool {
+                spec: ToolSpec { name: format!("{}__{}", self.name, spec.name), ..spec.clone() },
+                remote: spec.name,
+                client: self.clone(),
+            };
+            let name = tool.spec.name.clone();
+            registry.register(Arc::new(tool))?;
+            names.push(name);
+        }
+        debug!("Registered {} tool(s) of MCP server '{}'", names.len(), self.name);
+        Ok(names)
+    }
+}
+
+/// A tool of an MCP server
+struct McpTool {
+    spec: ToolSpec,
+    remote: String,
+    client: McpClient,
+}
+
+impl Tool for McpTool {
+    fn spec(&self) -> &ToolSpec {
+        &self.spec
+    }
+
+    fn call(&self, args: Value) -> BoxFuture<'_, Result<Value, NexaError>> {
+        Box::pin(self.client.call_tool(&self.remote, args))
+    }
+}
+
+fn open_stdio(config: &McpServerConfig, command: &str) -> Result<Connection, NexaError> {
+    let mut child = Command::new(command)
+        .args(&config.args)
+        .envs(&config.env)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| NexaError::config(format!("Failed to launch MCP server '{}': {}", config.name, e)))?;
+    let (Some(mut stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+        return Err(NexaError::system(format!("MCP server '{}' has no stdio pipes", config.name)));
+    };
+
+    let (outgoing, mut rx) = mpsc::unbounded_channel::<String>();
+    let pending = Pending::default();
+    let writer = tokio::spawn(async move {
+        while let Some(line) = rx.recv().await {
+            let written = async {
+                stdin.write_all(line.as_bytes()).await?;
+                stdin.write_all(b"\n").await?;
+                stdin.flush().await
+            };
+            if let Err(e) = written.await {
+                warn!("Failed to write to MCP server: {}", e);
+                break;
+            }
+        }
+    });
+    let reader = {
+        let (pending, outgoing) = (pending.clone(), outgoing.clone());
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stdout).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                on_message(&line, &pending, &outgoing);
+            }
+            pending.lock().clear();
+        })
+    };
+    Ok(Connection { outgoing, pending, next_id: AtomicU64::new(1), tasks: vec![writer, reader], _child: Some(child) })
+}
+
+async fn open_websocket(url: &str) -> Result<Connection, NexaError> {
+    let (socket, _) = tokio_tungstenite::connect_async(url).await?;
+    let (mut sink, mut stream) = socket.split();
+
+    let (outgoing, mut rx) = mpsc::unbounded_channel::<String>();
+    let pending = Pending::default();
+    let writer = tokio::spawn(async move {
+        while let Some(text) = rx.recv().await {
+            if let Err(e) = sink.send(Message::Text(text)).await {
+                warn!("Failed to write to MCP server: {}", e);
+                break;
+            }
+        }
+        let _ = sink.close().await;
+    });
+    let reader = {
+        let (pending, outgoing) = (pending.clone(), outgoing.clone());
+        tokio::spawn(async move {
+            while let Some(Ok(message)) = stream.next().await {
+                match message {
+                    Message::Text(text) => on_message(&text, &pending, &outgoing),
+                    Message::Close(_) => break,
+                    _ => {}
+                }
+            }
+            pending.lock().clear();
+        })
+    };
+    Ok(Connection { outgoing, pending, next_id: AtomicU64::new(1), tasks: vec![writer, reader], _child: None })
+}
+
+/// Route a response to its caller and answer requests from the server
+fn on_message(text: &str, pending: &Pending, outgoing: &mpsc::UnboundedSender<String>) {
+    let Ok(message) = serde_json::from_str::<Value>(text) else {
+        // Servers may print other output; only JSON-RPC matters
+        debug!("Ignoring non-JSON output of MCP server: {}", text);
+        return;
+    };
+    if let Some(method) = message.get("method").and_then(Value::as_str) {
+        let Some(id) = message.get("id").cloned() else {
+            debug!("MCP server notification {}", method);
+            return;
+        };
+        let reply = match method {
+            "ping" => JsonRpcResponse::success(id, json!({})),
+            method => JsonRpcResponse::failure(id, JsonRpcError::new(METHOD_NOT_FOUND, format!("Unsupported method '{}'", method))),
+        };
+        if let Ok(reply) = serde_json::to_string(&reply) {
+            let _ = outgoing.send(reply);
+        }
+        return;
+    }
+
+    match serde_json::from_value::<JsonRpcResponse>(message) {
+        Ok(response) => match response.id.as_u64().and_then(|id| pending.lock().remove(&id)) {
+            Some(caller) => {
+                let _ = caller.send(response);
+            }
+            None => debug!("Ignoring MCP response to unknown request {}", response.id),
+        },
+        Err(e) => warn!("Invalid message from MCP server: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::jsonrpc::McpHandler;
+    use crate::mcp::registry::AgentRegistry;
+
+    struct Reverse(ToolSpec);
+
+    impl Tool for Reverse {
+        fn spec(&self) -> &ToolSpec {
+            &self.0
+        }
+
+        fn call(&self, args: Value) -> BoxFuture<'_, Result<Value, NexaError>> {
+            Box::pin(async move { Ok(Value::String(args["text"].as_str().unwrap_or_default().chars().rev().collect())) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_websocket_server_tools() {
+        // A nexa MCP server with one tool
+        let remote = ToolRegistry::new();
+        remote.register(Arc::new(Reverse(ToolSpec {
+            name: "reverse".to_string(),
+            description: "Reverse text".to_string(),
+            input_schema: json!({ "type": "object", "properties": { "text": { "type": "string" } }, "required": ["text"] }),
+        }))).unwrap();
+        let handler = McpHandler::new(remote, AgentRegistry::new());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut socket = tokio_tungstenite::accept_async(socket).await.unwrap();
+            while let Some(Ok(Message::Text(text))) = socket.next().await {
+                if let Some(reply) = handler.handle_text(&text).await {
+                    socket.send(Message::Text(reply)).await.unwrap();
+                }
+            }
+        });
+
+        let client = McpClient::connect(&McpServerConfig::websocket("text", format!("ws://{}", addr))).await.unwrap();
+        assert_eq!(client.server_info()["name"], "nexa");
+        let tools = ToolRegistry::new();
+        assert_eq!(client.register_tools(&tools).await.unwrap(), vec!["text__reverse"]);
+        assert_eq!(tools.list()[0].input_schema["required"][0], "text");
+        assert_eq!(tools.call("text__reverse", json!({ "text": "abc" })).await.unwrap(), "cba");
+        // Failures on the server come back as errors
+        assert!(matches!(client.call_tool("reverse", json!({})).await, Err(NexaError::System(_))));
+        assert!(matches!(client.call_tool("missing", json!({})).await, Err(NexaError::InvalidInput(_))));
+    }
+
+    #[tokio::test]
+    async fn test_stdio_server() {
+        // Answers initialize, skips the initialized notification, then lists tools and answers a call
+        let script = r#"
+            read line; echo '{"jsonrpc":"2.0","id":1,"result":{"protocolVersion":"2024-11-05","capabilities":{},"serverInfo":{"name":"sh"}}}'
+            read line
+            read line; echo 'starting up'; echo '{"jsonrpc":"2.0","id":2,"result":{"tools":[{"name":"date","description":"Today","inputSchema":{"type":"object"}}]}}'
+            read line; echo '{"jsonrpc":"2.0","id":3,"result":{"content":[{"type":"text","text":"2026-10-15"}],"structuredContent":{"date":"2026-10-15"}}}'
+            read line
+        "#;
+        let client = McpClient::connect(&McpServerConfig::stdio("clock", "sh", &["-c", script])).await.unwrap();
+        assert_eq!(client.server_info()["name"], "sh");
+        let tools = ToolRegistry::new();
+        client.register_tools(&tools).await.unwrap();
+        assert_eq!(tools.call("clock__date", json!({})).await.unwrap(), json!({ "date": "2026-10-15" }));
+
+        let err = McpClient::connect(&McpServerConfig { url: None, command: None, ..McpServerConfig::websocket("none", "") }).await.unwrap_err();
+        assert!(err.is_user_error());
+    }
+}
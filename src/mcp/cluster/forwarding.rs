@@ -0,0 +1,167 @@
+//! Message Forwarding
+//!
+//! Delivers a buffered message to an agent connected to another node, over the same
+//! TCP port as registry replication:
+//! - The sender opens a connection to a member and asks it to deliver the message
+//! - The member hands it to its delivery hook and answers with the outcome
+//! - Members without the agent answer that it is not connected, and the next is tried
+//! - Where the agent was last found is tried first
+//! - Requests and answers are authenticated with the cluster secret like replication
+//!   frames, so only cluster members can reach agents this way
+
+use super::replication::{PeerConnection, PeerMessage};
+use crate::error::NexaError;
+use crate::mcp::buffer::BufferedMessage;
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time;
+
+/// Delivers a message to an agent connected to this node, returning its response
+///
+/// Fails with a not found error when the agent is not connected here.
+pub type Delivery = Arc<dyn Fn(String, BufferedMessage) -> BoxFuture<'static, Result<serde_json::Value, NexaError>> + Send + Sync>;
+
+/// Answer of a node asked to deliver a message
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub(super) enum ForwardOutcome {
+    Delivered { response: serde_json::Value },
+    /// The agent is not connected to the node
+    NotConnected,
+    Failed { code: String, message: String },
+}
+
+impl ForwardOutcome {
+    /// Outcome of handing a message to the delivery hook, if there is one
+    pub(super) async fn deliver(delivery: Option<Delivery>, agent_id: String, message: BufferedMessage) -> Self {
+        let Some(delivery) = delivery else { return Self::NotConnected };
+        match delivery(agent_id, message).await {
+            Ok(response) => Self::Delivered { response },
+            Err(NexaError::NotFound(_)) => Self::NotConnected,
+            Err(e) => Self::Failed { code: e.code().to_string(), message: e.message() },
+        }
+    }
+}
+
+/// Ask the node listening on `addr` to deliver `message` to `agent_id`
+pub(super) async fn forward_to(
+    addr: SocketAddr,
+    secret: &str,
+    agent_id: &str,
+    message: &BufferedMessage,
+    timeout: Duration,
+) -> Result<ForwardOutcome, NexaError> {
+    let exchange = async {
+        let mut connection = PeerConnection::connect(addr, secret).await?;
+        connection.send(&PeerMessage::Forward { agent_id: agent_id.to_string(), message: message.clone() }).await?;
+        match connection.recv().await? {
+            Some(PeerMessage::Forwarded { outcome }) => Ok(outcome),
+            Some(_) => Err(NexaError::cluster(format!("{} did not answer the forwarded message", addr))),
+            None => Err(NexaError::cluster(format!("{} closed the connection without answering", addr))),
+        }
+    };
+    time::timeout(timeout, exchange).await
+        .map_err(|_| NexaError::timeout(format!("Forwarding to {} timed out after {:?}", addr, timeout)))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::buffer::Priority;
+    use crate::mcp::cluster::{ClusterConfig, ClusterManager};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::SystemTime;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpStream;
+
+    async fn node() -> (ClusterManager, SocketAddr) {
+        node_with_secret("test-secret").await
+    }
+
+    async fn node_with_secret(secret: &str) -> (ClusterManager, SocketAddr) {
+        let config = ClusterConfig {
+            probe_interval: Duration::from_millis(50),
+            probe_timeout: Duration::from_millis(20),
+            secret: Some(secret.to_string()),
+            ..Default::default()
+        };
+        let manager = ClusterManager::new("127.0.0.1:0".parse().unwrap(), Some(config));
+        let addr = manager.start_gossip("127.0.0.1:0".parse().unwrap()).await.unwrap();
+        (manager, addr)
+    }
+
+    fn message() -> BufferedMessage {
+        BufferedMessage {
+            id: uuid::Uuid::new_v4(),
+            payload: b"hello".to_vec(),
+            priority: Priority::Normal,
+            created_at: SystemTime::now(),
+            attempts: 0,
+            max_attempts: 3,
+            delay_until: None,
+            topic: None,
+            trace: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_forward_message() {
+        let (local, _) = node().await;
+        let (remote, remote_addr) = node().await;
+        remote.set_delivery(Arc::new(|agent_id, message| Box::pin(async move {
+            match agent_id.as_str() {
+                "remote-agent" => Ok(serde_json::json!({ "echo": message.payload })),
+                "broken-agent" => Err(NexaError::unavailable("Agent 'broken-agent' disconnected")),
+                _ => Err(NexaError::not_found(format!("Agent '{}' is not connected", agent_id))),
+            }
+        }))).await;
+        local.join_cluster(remote_addr).await.unwrap();
+
+        let message = message();
+
+        // The response comes back from the node the agent is connected to
+        let response = local.forward("remote-agent", &message).await.unwrap();
+        assert_eq!(response["echo"], serde_json::json!(b"hello".to_vec()));
+
+        // Errors of the delivering node come back as they were
+        let err = local.forward("broken-agent", &message).await.unwrap_err();
+        assert!(matches!(err, NexaError::Unavailable(_)));
+
+        let err = local.forward("ghost", &message).await.unwrap_err();
+        assert!(matches!(err, NexaError::NotFound(_)));
+
+        local.stop().await.unwrap();
+        remote.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_forward_needs_secret() {
+        let (outsider, _) = node_with_secret("wrong-secret").await;
+        let (remote, remote_addr) = node().await;
+        let delivered = Arc::new(AtomicUsize::new(0));
+        let count = delivered.clone();
+        remote.set_delivery(Arc::new(move |_, _| {
+            count.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async { Ok(serde_json::json!({ "delivered": true })) })
+        })).await;
+        outsider.join_cluster(remote_addr).await.unwrap();
+
+        // A node with another secret is refused, as is a bare request
+        let err = outsider.forward("remote-agent", &message()).await.unwrap_err();
+        assert!(matches!(err, NexaError::NotFound(_)));
+        let mut stream = TcpStream::connect(remote_addr).await.unwrap();
+        let request = PeerMessage::Forward { agent_id: "remote-agent".to_string(), message: message() };
+        stream.write_all(format!("{}\n", serde_json::to_string(&request).unwrap()).as_bytes()).await.unwrap();
+        let mut answer = String::new();
+        let mut reader = BufReader::new(stream);
+        while reader.read_line(&mut answer).await.is_ok_and(|read| read > 0) {}
+        assert!(!answer.contains("delivered"));
+        assert_eq!(delivered.load(Ordering::SeqCst), 0);
+
+        outsider.stop().await.unwrap();
+        remote.stop().await.unwrap();
+    }
+}
@@ -0,0 +1,508 @@
+//! Gossip Membership
+//!
+//! SWIM-style discovery and failure detection over UDP:
+//! - Nodes join by contacting any member, which answers with the whole membership
+//! - Each interval one member is probed, directly and then through other members
+//! - Members missing a probe are suspected, and removed when the suspicion times out
+//! - Membership updates ride along on every message until enough members heard them
+//! - Members refute suspicion by raising their incarnation number
+
+use super::types::*;
+use super::manager::ClusterManager;
+use crate::error::NexaError;
+use parking_lot::Mutex;
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
+use tokio::net::UdpSocket;
+use tokio::sync::{oneshot, RwLock};
+use tokio::time::{self, Duration};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+/// Membership updates carried by one message
+const MAX_PIGGYBACK: usize = 8;
+
+/// Largest datagram read
+const MAX_DATAGRAM: usize = 65_507;
+
+/// Liveness of a member as gossiped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MemberState {
+    Alive,
+    /// Missed a probe; removed unless it refutes the suspicion in time
+    Suspect,
+    /// Failed or left; removed from the membership
+    Dead,
+}
+
+/// A cluster member as known through gossip
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Member {
+    pub node: Node,
+    /// Address the member gossips on
+    pub gossip_addr: SocketAddr,
+    pub state: MemberState,
+    /// Raised by the member itself to refute suspicion
+    pub incarnation: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum GossipMessage {
+    Ping { seq: u64, updates: Vec<Member> },
+    Ack { seq: u64, updates: Vec<Member> },
+    /// Asks the receiver to probe `target` and acknowledge `seq` if it answers
+    PingReq { seq: u64, target: SocketAddr, updates: Vec<Member> },
+    Join { member: Member },
+    /// Answers `Join` with the whole membership
+    Welcome { members: Vec<Member> },
+}
+
+#[derive(Debug)]
+struct Entry {
+    member: Member,
+    suspected_at: Option<Instant>,
+}
+
+/// An update waiting to be piggybacked
+#[derive(Debug)]
+struct Broadcast {
+    member: Member,
+    remaining: usize,
+}
+
+/// Gossip membership of one node
+#[derive(Clone)]
+pub struct Gossip {
+    manager: ClusterManager,
+    socket: Arc<UdpSocket>,
+    local: Arc<RwLock<Member>>,
+    members: Arc<RwLock<HashMap<Uuid, Entry>>>,
+    /// Incarnation each removed member was declared dead at, so stale gossip cannot revive it
+    dead: Arc<RwLock<HashMap<Uuid, u64>>>,
+    broadcasts: Arc<Mutex<Vec<Broadcast>>>,
+    acks: Arc<Mutex<HashMap<u64, oneshot::Sender<()>>>>,
+    welcome: Arc<Mutex<Option<oneshot::Sender<()>>>>,
+    seq: Arc<AtomicU64>,
+    tasks: Arc<Mutex<Vec<tokio::task::JoinHandle<()>>>>,
+}
+
+impl Gossip {
+    /// Bind `addr` and start gossiping for the manager's node
+    pub async fn start(manager: ClusterManager, addr: SocketAddr) -> Result<Self, NexaError> {
+        let socket = UdpSocket::bind(addr).await
+            .map_err(|e| NexaError::cluster(format!("Failed to bind gossip socket {}: {}", addr, e)))?;
+        let gossip_addr = socket.local_addr()?;
+        let local = Member {
+            node: manager.node.read().await.clone(),
+            gossip_addr,
+            state: MemberState::Alive,
+            incarnation: 0,
+        };
+        let gossip = Self {
+            manager,
+            socket: Arc::new(socket),
+            local: Arc::new(RwLock::new(local)),
+            members: Arc::new(RwLock::new(HashMap::new())),
+            dead: Arc::new(RwLock::new(HashMap::new())),
+            broadcasts: Arc::new(Mutex::new(Vec::new())),
+            acks: Arc::new(Mutex::new(HashMap::new())),
+            welcome: Arc::new(Mutex::new(None)),
+            seq: Arc::new(AtomicU64::new(0)),
+            tasks: Arc::new(Mutex::new(Vec::new())),
+        };
+
+        let receiver = gossip.clone();
+        let prober = gossip.clone();
+        gossip.tasks.lock().extend([
+            tokio::spawn(async move { receiver.receive().await }),
+            tokio::spawn(async move { prober.probe_loop().await }),
+        ]);
+        info!("Gossiping on {}", gossip_addr);
+        Ok(gossip)
+    }
+
+    /// Address gossip listens on
+    pub fn local_addr(&self) -> Result<SocketAddr, NexaError> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    /// Join the cluster through any member, waiting for it to send the membership
+    pub async fn join(&self, seed: SocketAddr) -> Result<(), NexaError> {
+        let (tx, rx) = oneshot::channel();
+        *self.welcome.lock() = Some(tx);
+        let member = self.local.read().await.clone();
+        self.send(seed, &GossipMessage::Join { member }).await?;
+        let timeout = self.manager.config.read().await.node_timeout;
+        match time::timeout(timeout, rx).await {
+            Ok(Ok(())) => {
+                info!("Joined cluster through {}", seed);
+                Ok(())
+            }
+            _ => Err(NexaError::cluster(format!("No answer from seed {}", seed))),
+        }
+    }
+
+    /// Members currently believed alive or suspected, excluding this node
+    pub async fn members(&self) -> Vec<Member> {
+        self.members.read().await.values().map(|entry| entry.member.clone()).collect()
+    }
+
+    /// Tell the members this node is leaving, then stop gossiping
+    pub async fn leave(&self) {
+        let mut left = self.local.read().await.clone();
+        left.state = MemberState::Dead;
+        left.incarnation += 1;
+        let addrs: Vec<SocketAddr> = self.members.read().await.values().map(|e| e.member.gossip_addr).collect();
+        let message = GossipMessage::Ping { seq: self.next_seq(), updates: vec![left] };
+        for addr in addrs {
+            let _ = self.send(addr, &message).await;
+        }
+        self.stop();
+    }
+
+    /// Stop gossiping without telling the members, which will detect it as a failure
+    pub fn stop(&self) {
+        for task in self.tasks.lock().drain(..) {
+            task.abort();
+        }
+    }
+
+    async fn receive(&self) {
+        let mut buf = vec![0u8; MAX_DATAGRAM];
+        loop {
+            let (len, from) = match self.socket.recv_from(&mut buf).await {
+                Ok(received) => received,
+                Err(e) => {
+                    warn!("Gossip receive error: {}", e);
+                    continue;
+                }
+            };
+            match serde_json::from_slice(&buf[..len]) {
+                Ok(message) => self.handle(message, from).await,
+                Err(e) => debug!("Ignoring malformed gossip from {}: {}", from, e),
+            }
+        }
+    }
+
+    async fn handle(&self, message: GossipMessage, from: SocketAddr) {
+        match message {
+            GossipMessage::Ping { seq, updates } => {
+                self.merge(updates).await;
+                let ack = GossipMessage::Ack { seq, updates: self.piggyback() };
+                let _ = self.send(from, &ack).await;
+            }
+            GossipMessage::Ack { seq, updates } => {
+                self.merge(updates).await;
+                if let Some(waiter) = self.acks.lock().remove(&seq) {
+                    let _ = waiter.send(());
+                }
+            }
+            GossipMessage::PingReq { seq, target, updates } => {
+                self.merge(updates).await;
+                let gossip = self.clone();
+                tokio::spawn(async move {
+                    let timeout = gossip.manager.config.read().await.probe_timeout;
+                    if gossip.ping(target, timeout).await {
+                        let ack = GossipMessage::Ack { seq, updates: gossip.piggyback() };
+                        let _ = gossip.send(from, &ack).await;
+                    }
+                });
+            }
+            GossipMessage::Join { mut member } => {
+                member.state = MemberState::Alive;
+                self.merge(vec![member]).await;
+                let mut members = self.members().await;
+                members.push(self.local.read().await.clone());
+                let _ = self.send(from, &GossipMessage::Welcome { members }).await;
+            }
+            GossipMessage::Welcome { members } => {
+                self.merge(members).await;
+                if let Some(waiter) = self.welcome.lock().take() {
+                    let _ = waiter.send(());
+                }
+            }
+        }
+    }
+
+    /// Probe one member per interval and expire suspicions
+    async fn probe_loop(&self) {
+        loop {
+            let config = self.manager.config.read().await.clone();
+            let started = Instant::now();
+            if let Some(target) = self.pick_target().await {
+                if !self.probe(&target, &config).await {
+                    self.suspect(target).await;
+                }
+            }
+            self.expire_suspects(config.suspect_timeout).await;
+            time::sleep(config.probe_interval.saturating_sub(started.elapsed())).await;
+        }
+    }
+
+    /// Ping a member, asking others to ping it when it does not answer in time
+    async fn probe(&self, target: &Member, config: &ClusterConfig) -> bool {
+        let seq = self.next_seq();
+        let (tx, mut rx) = oneshot::channel();
+        self.acks.lock().insert(seq, tx);
+        let ping = GossipMessage::Ping { seq, updates: self.piggyback() };
+        let _ = self.send(target.gossip_addr, &ping).await;
+        if time::timeout(config.probe_timeout, &mut rx).await.is_ok() {
+            return true;
+        }
+
+        let helpers: Vec<SocketAddr> = {
+            let members = self.members.read().await;
+            let mut helpers: Vec<SocketAddr> = members.values()
+                .filter(|e| e.member.node.id != target.node.id && e.member.state == MemberState::Alive)
+                .map(|e| e.member.gossip_addr)
+                .collect();
+            helpers.shuffle(&mut rand::thread_rng());
+            helpers.truncate(config.indirect_probes);
+            helpers
+        };
+        for helper in helpers {
+            let request = GossipMessage::PingReq { seq, target: target.gossip_addr, updates: self.piggyback() };
+            let _ = self.send(helper, &request).await;
+        }
+        let remaining = config.probe_interval.saturating_sub(config.probe_timeout).max(config.probe_timeout);
+        let answered = time::timeout(remaining, rx).await.is_ok();
+        self.acks.lock().remove(&seq);
+        answered
+    }
+
+    /// Ping an address directly, waiting up to `timeout` for the ack
+    async fn ping(&self, addr: SocketAddr, timeout: Duration) -> bool {
+        let seq = self.next_seq();
+        let (tx, rx) = oneshot::channel();
+        self.acks.lock().insert(seq, tx);
+        let ping = GossipMessage::Ping { seq, updates: self.piggyback() };
+        let answered = self.send(addr, &ping).await.is_ok() && time::timeout(timeout, rx).await.is_ok();
+        self.acks.lock().remove(&seq);
+        answered
+    }
+
+    async fn pick_target(&self) -> Option<Member> {
+        let members = self.members.read().await;
+        let candidates: Vec<&Entry> = members.values().collect();
+        candidates.choose(&mut rand::thread_rng()).map(|entry| entry.member.clone())
+    }
+
+    async fn suspect(&self, target: Member) {
+        let mut members = self.members.write().await;
+        let Some(entry) = members.get_mut(&target.node.id) else { return };
+        if entry.member.state != MemberState::Alive || entry.member.incarnation != target.incarnation {
+            return;
+        }
+        debug!("Suspecting member {} at {}", target.node.id, target.gossip_addr);
+        entry.member.state = MemberState::Suspect;
+        entry.suspected_at = Some(Instant::now());
+        let member = entry.member.clone();
+        drop(members);
+        self.enqueue(member.clone()).await;
+        self.manager.set_node_health(member.node.id, NodeHealth::Unhealthy).await;
+    }
+
+    async fn expire_suspects(&self, timeout: Duration) {
+        let expired: Vec<Member> = self.members.read().await.values()
+            .filter(|e| e.suspected_at.is_some_and(|at| at.elapsed() >= timeout))
+            .map(|e| Member { state: MemberState::Dead, ..e.member.clone() })
+            .collect();
+        for member in expired {
+            warn!("Member {} at {} failed", member.node.id, member.gossip_addr);
+            self.remove(&member, Some("failed to answer probes")).await;
+            self.enqueue(member).await;
+        }
+    }
+
+    /// Apply gossiped updates, keeping the newest incarnation of each member
+    async fn merge(&self, updates: Vec<Member>) {
+        let local_id = self.local.read().await.node.id;
+        for update in updates {
+            if update.node.id == local_id {
+                self.refute(&update).await;
+                continue;
+            }
+            if self.dead.read().await.get(&update.node.id).is_some_and(|&inc| update.incarnation <= inc) {
+                continue;
+            }
+
+            let mut members = self.members.write().await;
+            let known = members.get(&update.node.id).map(|e| (e.member.state, e.member.incarnation));
+            let newer = match (known, update.state) {
+                (None, MemberState::Dead) => false,
+                (None, _) => true,
+                (Some((_, inc)), MemberState::Alive) => update.incarnation > inc,
+                (Some((state, inc)), MemberState::Suspect) => {
+                    update.incarnation > inc || (update.incarnation == inc && state == MemberState::Alive)
+                }
+                (Some((_, inc)), MemberState::Dead) => update.incarnation >= inc,
+            };
+            if !newer {
+                continue;
+            }
+
+            match update.state {
+                MemberState::Dead => {
+                    drop(members);
+                    // Members leaving declare themselves dead with a raised incarnation
+                    let left = known.is_some_and(|(_, inc)| update.incarnation > inc);
+                    self.remove(&update, (!left).then_some("declared dead by a member")).await;
+                }
+                state => {
+                    let suspected_at = (state == MemberState::Suspect).then(Instant::now);
+                    let joined = known.is_none();
+                    members.insert(update.node.id, Entry { member: update.clone(), suspected_at });
+                    drop(members);
+                    if joined {
+                        info!("Member {} joined at {}", update.node.id, update.gossip_addr);
+                        self.manager.apply_membership(MembershipChange::Join {
+                            node: update.node.clone(),
+                            timestamp: SystemTime::now(),
+                        }).await;
+                    }
+                    let health = if state == MemberState::Alive { NodeHealth::Healthy } else { NodeHealth::Unhealthy };
+                    self.manager.set_node_health(update.node.id, health).await;
+                }
+            }
+            self.enqueue(update).await;
+        }
+    }
+
+    /// Answer gossip that this node is suspected or dead by raising its incarnation
+    async fn refute(&self, update: &Member) {
+        let mut local = self.local.write().await;
+        if update.state == MemberState::Alive || update.incarnation < local.incarnation {
+            return;
+        }
+        local.incarnation = update.incarnation + 1;
+        debug!("Refuting suspicion with incarnation {}", local.incarnation);
+        let member = local.clone();
+        drop(local);
+        self.enqueue(member).await;
+    }
+
+    /// Remove a member that failed for `reason`, or that left when there is none
+    async fn remove(&self, member: &Member, reason: Option<&str>) {
+        self.members.write().await.remove(&member.node.id);
+        self.dead.write().await.insert(member.node.id, member.incarnation);
+        let change = match reason {
+            Some(reason) => MembershipChange::Remove {
+                node_id: member.node.id,
+                reason: reason.to_string(),
+                timestamp: SystemTime::now(),
+            },
+            None => MembershipChange::Leave { node_id: member.node.id, timestamp: SystemTime::now() },
+        };
+        self.manager.apply_membership(change).await;
+    }
+
+    /// Queue an update to piggyback, sent about `3 * log2(n + 1)` times
+    async fn enqueue(&self, member: Member) {
+        let size = self.members.read().await.len() + 1;
+        let remaining = 3 * (usize::BITS - size.leading_zeros()) as usize;
+        let mut broadcasts = self.broadcasts.lock();
+        broadcasts.retain(|b| b.member.node.id != member.node.id);
+        broadcasts.push(Broadcast { member, remaining });
+    }
+
+    /// Updates to attach to the next message, least sent first
+    fn piggyback(&self) -> Vec<Member> {
+        let mut broadcasts = self.broadcasts.lock();
+        broadcasts.sort_by_key(|b| std::cmp::Reverse(b.remaining));
+        let updates = broadcasts.iter_mut()
+            .take(MAX_PIGGYBACK)
+            .map(|b| {
+                b.remaining -= 1;
+                b.member.clone()
+            })
+            .collect();
+        broadcasts.retain(|b| b.remaining > 0);
+        updates
+    }
+
+    async fn send(&self, addr: SocketAddr, message: &GossipMessage) -> Result<(), NexaError> {
+        let bytes = serde_json::to_vec(message)?;
+        self.socket.send_to(&bytes, addr).await?;
+        Ok(())
+    }
+
+    fn next_seq(&self) -> u64 {
+        self.seq.fetch_add(1, Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> ClusterConfig {
+        ClusterConfig {
+            probe_interval: Duration::from_millis(50),
+            probe_timeout: Duration::from_millis(20),
+            suspect_timeout: Duration::from_millis(150),
+            ..Default::default()
+        }
+    }
+
+    async fn node() -> (ClusterManager, Gossip) {
+        let manager = ClusterManager::new("127.0.0.1:0".parse().unwrap(), Some(config()));
+        let gossip = Gossip::start(manager.clone(), "127.0.0.1:0".parse().unwrap()).await.unwrap();
+        (manager, gossip)
+    }
+
+    async fn wait_for(mut done: impl FnMut() -> bool) {
+        for _ in 0..100 {
+            if done() {
+                return;
+            }
+            time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("Gossip did not converge");
+    }
+
+    #[tokio::test]
+    async fn test_join_and_failure_detection() {
+        let (a, gossip_a) = node().await;
+        let (b, gossip_b) = node().await;
+        let (c, gossip_c) = node().await;
+        let mut changes = a.subscribe();
+
+        // Each node joins through a different member, and all learn of each other
+        gossip_b.join(gossip_a.local_addr().unwrap()).await.unwrap();
+        gossip_c.join(gossip_b.local_addr().unwrap()).await.unwrap();
+        wait_for(|| [&a, &b, &c].iter().all(|m| m.nodes.len() == 2)).await;
+        let c_id = c.node.read().await.id;
+        assert!(a.nodes.contains_key(&c_id));
+        assert!(matches!(
+            changes.recv().await.unwrap(),
+            ClusterMessage::MembershipChange(MembershipChange::Join { .. })
+        ));
+
+        // A node that stops answering is removed everywhere
+        gossip_c.stop();
+        wait_for(|| !a.nodes.contains_key(&c_id) && !b.nodes.contains_key(&c_id)).await;
+        assert_eq!(gossip_a.members().await.len(), 1);
+
+        // A node that leaves is removed without waiting for a timeout
+        let b_id = b.node.read().await.id;
+        gossip_b.leave().await;
+        wait_for(|| !a.nodes.contains_key(&b_id)).await;
+        gossip_a.stop();
+    }
+
+    #[tokio::test]
+    async fn test_join_unreachable_seed() {
+        let (_, gossip) = node().await;
+        let silent = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let mut manager_config = config();
+        manager_config.node_timeout = Duration::from_millis(100);
+        *gossip.manager.config.write().await = manager_config;
+        assert!(gossip.join(silent.local_addr().unwrap()).await.is_err());
+        gossip.stop();
+    }
+}
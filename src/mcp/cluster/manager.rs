@@ -5,12 +5,19 @@
 //! - Leader election
 //! - State replication
 //! - Health monitoring
+//! - Membership through gossip
+//! - Registry replication from the leader
+//! - Message forwarding to agents on other nodes
 
 use super::types::*;
+use super::forwarding::{self, Delivery, ForwardOutcome};
+use super::gossip::{Gossip, MemberState};
+use super::replication::Replicator;
+use crate::mcp::registry::AgentRegistry;
 use crate::error::NexaError;
 use dashmap::DashMap;
 use tokio::sync::{RwLock, broadcast, mpsc};
-use tracing::{error, info};
+use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 use std::time::SystemTime;
 use std::net::SocketAddr;
@@ -18,6 +25,7 @@ use tokio::time::{self, Duration};
 use rand::Rng;
 use crate::mcp::buffer::BufferedMessage;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 /// Internal message types for cluster tasks
 #[derive(Debug)]
@@ -47,6 +55,18 @@ pub struct ClusterManager {
     task_tx: mpsc::Sender<ClusterTask>,
     /// Shutdown signal
     shutdown: Arc<tokio::sync::Notify>,
+    /// Gossip membership, once started
+    gossip: Arc<RwLock<Option<Gossip>>>,
+    /// Registry replicated across the cluster, if any
+    registry: Arc<RwLock<Option<AgentRegistry>>>,
+    /// Registry replication and forwarded message delivery, started with gossip
+    replicator: Arc<RwLock<Option<Replicator>>>,
+    /// Delivers messages forwarded to agents connected to this node
+    delivery: Arc<RwLock<Option<Delivery>>>,
+    /// Node each agent was last reached on through forwarding
+    agent_locations: Arc<DashMap<String, Uuid>>,
+    /// Whether the node is outside quorum and read-only
+    fenced: Arc<AtomicBool>,
 }
 
 // Explicitly implement Send and Sync since all fields are Send + Sync
@@ -95,6 +115,12 @@ impl ClusterManager {
             message_tx,
             task_tx,
             shutdown: Arc::new(tokio::sync::Notify::new()),
+            gossip: Arc::new(RwLock::new(None)),
+            registry: Arc::new(RwLock::new(None)),
+            replicator: Arc::new(RwLock::new(None)),
+            delivery: Arc::new(RwLock::new(None)),
+            agent_locations: Arc::new(DashMap::new()),
+            fenced: Arc::new(AtomicBool::new(false)),
         };
 
         // Start task processor
@@ -112,8 +138,8 @@ impl ClusterManager {
             match task {
                 ClusterTask::SendHeartbeat => {
                     let (term, node_id) = {
-                        let node_guard = self.node.read().await;
                         let state_guard = self.state.read().await;
+                        let node_guard = self.node.read().await;
                         if node_guard.role != NodeRole::Leader {
                             continue;
                         }
@@ -131,11 +157,10 @@ impl ClusterManager {
                 }
                 ClusterTask::CheckHealth => {
                     let _health = self.check_node_health().await;
-                    let _node_guard = self.node.read().await;
-                    let state_guard = self.state.read().await;
-                    let term = state_guard.term;
-                    let state = state_guard.clone();
-                    
+                    // Broadcasting writes the state, so the read lock is released first
+                    let state = self.state.read().await.clone();
+                    let term = state.term;
+
                     let message = ClusterMessage::StateSync {
                         term,
                         state,
@@ -176,38 +201,228 @@ impl ClusterManager {
         self.start_heartbeat().await?;
         self.start_health_monitor().await?;
         self.start_election_monitor().await?;
+        self.check_quorum().await;
+
+        let (gossip_addr, seeds) = {
+            let config = self.config.read().await;
+            (config.gossip_addr, config.seeds.clone())
+        };
+        if let Some(addr) = gossip_addr {
+            self.start_gossip(addr).await?;
+            for seed in seeds {
+                match self.join_cluster(seed).await {
+                    Ok(()) => break,
+                    Err(e) => warn!("Failed to join cluster through {}: {}", seed, e),
+                }
+            }
+        }
         
         Ok(())
     }
 
+    /// Start gossiping membership on `addr`, returning the address bound
+    pub async fn start_gossip(&self, addr: SocketAddr) -> Result<SocketAddr, NexaError> {
+        let mut gossip = self.gossip.write().await;
+        if let Some(running) = gossip.as_ref() {
+            return running.local_addr();
+        }
+        let started = Gossip::start(self.clone(), addr).await?;
+        let bound = started.local_addr()?;
+        let registry = self.registry.read().await.clone();
+        match self.config.read().await.secret.clone() {
+            Some(secret) => match Replicator::start(self.clone(), started.clone(), registry, secret).await {
+                Ok(replicator) => *self.replicator.write().await = Some(replicator),
+                Err(e) => {
+                    started.stop();
+                    return Err(e);
+                }
+            },
+            None => warn!("No cluster secret set; registry replication and message forwarding are off"),
+        }
+        *gossip = Some(started);
+        Ok(bound)
+    }
+
+    /// Address gossip is bound to, if it is started
+    pub async fn gossip_addr(&self) -> Option<SocketAddr> {
+        self.gossip.read().await.as_ref().and_then(|gossip| gossip.local_addr().ok())
+    }
+
+    /// Replicate `registry` to the members while leading, and from the leader otherwise
+    ///
+    /// Takes effect when gossip starts; replication shares the gossip port, over TCP.
+    pub async fn replicate(&self, registry: AgentRegistry) {
+        *self.registry.write().await = Some(registry);
+    }
+
+    /// Deliver messages forwarded by other nodes to agents connected to this one
+    pub async fn set_delivery(&self, delivery: Delivery) {
+        *self.delivery.write().await = Some(delivery);
+    }
+
+    pub(crate) async fn delivery(&self) -> Option<Delivery> {
+        self.delivery.read().await.clone()
+    }
+
+    /// Deliver `message` to `agent_id` through the node it is connected to, returning its response
+    ///
+    /// Members are asked in turn, starting with where the agent was last reached, until
+    /// one has it connected. Fails with a not found error when none has.
+    pub async fn forward(&self, agent_id: &str, message: &BufferedMessage) -> Result<serde_json::Value, NexaError> {
+        let gossip = self.gossip.read().await.clone()
+            .ok_or_else(|| NexaError::cluster("Gossip is not started"))?;
+        let (timeout, secret) = {
+            let config = self.config.read().await;
+            (config.node_timeout, config.secret.clone())
+        };
+        let secret = secret.ok_or_else(|| NexaError::cluster("Forwarding needs a cluster secret"))?;
+        let mut members: Vec<_> = gossip.members().await.into_iter()
+            .filter(|m| m.state != MemberState::Dead)
+            .collect();
+        if let Some(last) = self.agent_locations.get(agent_id).map(|node| *node) {
+            members.sort_by_key(|m| m.node.id != last);
+        }
+
+        for member in members {
+            match forwarding::forward_to(member.gossip_addr, &secret, agent_id, message, timeout).await {
+                Ok(ForwardOutcome::Delivered { response }) => {
+                    self.agent_locations.insert(agent_id.to_string(), member.node.id);
+                    return Ok(response);
+                }
+                Ok(ForwardOutcome::NotConnected) => continue,
+                Ok(ForwardOutcome::Failed { code, message }) => {
+                    return Err(NexaError::from_code(&code, format!("Node {}: {}", member.node.id, message)));
+                }
+                Err(e) => debug!("Failed to forward message {} to {}: {}", message.id, member.node.id, e),
+            }
+        }
+        self.agent_locations.remove(agent_id);
+        Err(NexaError::not_found(format!("Agent '{}' is not connected to any node", agent_id)))
+    }
+
+    /// Subscribe to cluster messages, including membership changes
+    pub fn subscribe(&self) -> broadcast::Receiver<ClusterMessage> {
+        self.message_tx.subscribe()
+    }
+
+    /// Apply a membership change learned through gossip
+    pub(crate) async fn apply_membership(&self, change: MembershipChange) {
+        match &change {
+            MembershipChange::Join { node, .. } => {
+                info!("Node {} joined the cluster at {}", node.id, node.addr);
+                self.nodes.insert(node.id, node.clone());
+                self.state.write().await.nodes.insert(node.id, node.clone());
+            }
+            MembershipChange::Leave { node_id, .. } | MembershipChange::Remove { node_id, .. } => {
+                if let MembershipChange::Remove { reason, .. } = &change {
+                    warn!("Node {} removed from the cluster: {}", node_id, reason);
+                } else {
+                    info!("Node {} left the cluster", node_id);
+                }
+                self.nodes.remove(node_id);
+                let mut state = self.state.write().await;
+                state.nodes.remove(node_id);
+                if state.leader_id == Some(*node_id) {
+                    // Elect a new leader without waiting for the heartbeat timeout
+                    state.leader_id = None;
+                    let mut node = self.node.write().await;
+                    if node.role != NodeRole::Leader {
+                        node.last_heartbeat = SystemTime::UNIX_EPOCH;
+                    }
+                }
+            }
+        }
+        let _ = self.message_tx.send(ClusterMessage::MembershipChange(change));
+        self.check_quorum().await;
+    }
+
+    /// Healthy members this node sees, itself included
+    pub fn healthy_members(&self) -> usize {
+        1 + self.nodes.iter().filter(|node| node.health == NodeHealth::Healthy).count()
+    }
+
+    /// Whether this node sees at least `min_quorum_size` healthy members
+    pub async fn has_quorum(&self) -> bool {
+        self.healthy_members() >= self.config.read().await.min_quorum_size
+    }
+
+    /// Whether this node is outside quorum, as of the last membership change
+    pub fn is_fenced(&self) -> bool {
+        self.fenced.load(Ordering::SeqCst)
+    }
+
+    /// Fence or unfence this node as it loses or regains quorum
+    ///
+    /// A fenced node is `Degraded` and steps down if it leads, so the side of a
+    /// partition without quorum cannot keep acting as leader.
+    async fn check_quorum(&self) {
+        let members = self.healthy_members();
+        let required = self.config.read().await.min_quorum_size;
+        let fenced = members < required;
+        if self.fenced.swap(fenced, Ordering::SeqCst) == fenced {
+            return;
+        }
+
+        let mut state = self.state.write().await;
+        let mut node = self.node.write().await;
+        if fenced {
+            warn!("Lost quorum with {} of {} required members; fencing this node", members, required);
+            node.health = NodeHealth::Degraded;
+            if node.role == NodeRole::Leader {
+                node.role = NodeRole::Follower;
+                state.leader_id = None;
+            }
+        } else {
+            info!("Regained quorum with {} of {} required members", members, required);
+            node.health = NodeHealth::Healthy;
+        }
+        drop(node);
+        drop(state);
+        let _ = self.message_tx.send(ClusterMessage::QuorumChanged { has_quorum: !fenced, members, required });
+    }
+
+    /// Record the health of a member as seen through gossip
+    pub(crate) async fn set_node_health(&self, node_id: Uuid, health: NodeHealth) {
+        if let Some(mut node) = self.nodes.get_mut(&node_id) {
+            node.health = health;
+        }
+        if let Some(node) = self.state.write().await.nodes.get_mut(&node_id) {
+            node.health = health;
+        }
+        self.check_quorum().await;
+    }
+
     /// Stop cluster manager
     pub async fn stop(&self) -> Result<(), NexaError> {
         info!("Stopping cluster manager");
         let _ = self.task_tx.send(ClusterTask::Shutdown).await;
         self.shutdown.notify_waiters();
+        if let Some(replicator) = self.replicator.write().await.take() {
+            replicator.stop();
+        }
+        if let Some(gossip) = self.gossip.write().await.take() {
+            gossip.stop();
+        }
         Ok(())
     }
 
-    /// Join an existing cluster
+    /// Join an existing cluster through the gossip address of any member
     pub async fn join_cluster(&self, seed_addr: SocketAddr) -> Result<(), NexaError> {
         info!("Joining cluster via seed node: {}", seed_addr);
-        
-        // Connect to seed node
-        let node = self.node.read().await;
-        let _join_message = ClusterMessage::MembershipChange(MembershipChange::Join {
-            node: node.clone(),
-            timestamp: SystemTime::now(),
-        });
-        
-        // TODO: Send join message to seed node
-        // TODO: Wait for cluster state sync
-        
-        Ok(())
+        let gossip = self.gossip.read().await.clone()
+            .ok_or_else(|| NexaError::cluster("Gossip is not started; set gossip_addr to join a cluster"))?;
+        gossip.join(seed_addr).await
     }
 
     /// Leave cluster gracefully
     pub async fn leave_cluster(&self) -> Result<(), NexaError> {
         info!("Leaving cluster gracefully");
+        if let Some(replicator) = self.replicator.write().await.take() {
+            replicator.stop();
+        }
+        if let Some(gossip) = self.gossip.write().await.take() {
+            gossip.leave().await;
+        }
         
         let node = self.node.read().await;
         let leave_message = ClusterMessage::MembershipChange(MembershipChange::Leave {
@@ -260,8 +475,8 @@ impl ClusterManager {
 
     /// Handle incoming vote
     pub async fn handle_vote(&self, term: u64, voter_id: Uuid, granted: bool) -> Result<(), NexaError> {
-        let mut node_guard = self.node.write().await;
         let mut state_guard = self.state.write().await;
+        let mut node_guard = self.node.write().await;
 
         if term != state_guard.term {
             return Ok(());
@@ -272,7 +487,7 @@ impl ClusterManager {
             
             // Check if we have quorum
             let votes = self.votes.len();
-            if votes >= state_guard.quorum_size {
+            if votes >= state_guard.quorum_size && !self.is_fenced() {
                 node_guard.role = NodeRole::Leader;
                 state_guard.leader_id = Some(node_guard.id);
             }
@@ -417,6 +632,42 @@ mod tests {
         assert_eq!(node_guard.health, NodeHealth::Healthy);
     }
     
+    #[tokio::test]
+    async fn test_quorum_fencing() {
+        let addr = SocketAddr::from_str("127.0.0.1:8080").unwrap();
+        let config = ClusterConfig { min_quorum_size: 2, ..Default::default() };
+        let manager = ClusterManager::new(addr, Some(config));
+        let mut peer = manager.node.read().await.clone();
+        peer.id = Uuid::new_v4();
+        manager.apply_membership(MembershipChange::Join { node: peer.clone(), timestamp: SystemTime::now() }).await;
+        assert!(manager.has_quorum().await);
+        assert!(!manager.is_fenced());
+        manager.node.write().await.role = NodeRole::Leader;
+
+        // Losing sight of the peer leaves this node alone, below quorum
+        let mut messages = manager.subscribe();
+        manager.set_node_health(peer.id, NodeHealth::Unhealthy).await;
+        assert!(manager.is_fenced());
+        {
+            let node_guard = manager.node.read().await;
+            assert_eq!(node_guard.role, NodeRole::Follower);
+            assert_eq!(node_guard.health, NodeHealth::Degraded);
+        }
+        assert!(matches!(
+            messages.recv().await.unwrap(),
+            ClusterMessage::QuorumChanged { has_quorum: false, members: 1, required: 2 }
+        ));
+
+        // A fenced node cannot win an election
+        manager.handle_vote(0, Uuid::new_v4(), true).await.unwrap();
+        manager.handle_vote(0, Uuid::new_v4(), true).await.unwrap();
+        assert_eq!(manager.node.read().await.role, NodeRole::Follower);
+
+        manager.set_node_health(peer.id, NodeHealth::Healthy).await;
+        assert!(!manager.is_fenced());
+        assert_eq!(manager.node.read().await.health, NodeHealth::Healthy);
+    }
+
     #[tokio::test]
     async fn test_election_process() {
         let addr = SocketAddr::from_str("127.0.0.1:8080").unwrap();
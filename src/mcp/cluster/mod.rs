@@ -1,13 +1,17 @@
 //! Cluster Management Module
 //! 
 //! Provides distributed cluster management:
-//! - Node discovery and coordination
+//! - Node discovery and failure detection by gossip
 //! - Leader election using Raft consensus
-//! - State replication and synchronization
+//! - Agent registry replication from the leader
+//! - Message forwarding to agents connected to other nodes
 //! - Health monitoring and failure detection
 
 mod types;
 mod manager;
+mod gossip;
+mod replication;
+mod forwarding;
 
 // Re-export commonly used types
 pub use types::{
@@ -15,4 +19,6 @@ pub use types::{
     ClusterState, ClusterConfig, ClusterMessage,
     MembershipChange,
 };
-pub use manager::ClusterManager; 
\ No newline at end of file
+pub use manager::ClusterManager;
+pub use gossip::{Gossip, Member, MemberState};
+pub use forwarding::Delivery; 
\ No newline at end of file
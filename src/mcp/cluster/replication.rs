@@ -0,0 +1,444 @@
+//! Registry Replication
+//!
+//! The leader streams its agent registry to every gossip member over TCP, on the
+//! port the member gossips on:
+//! - Each stream opens with the leader's agents and tasks, replacing the follower's
+//! - Every registry change follows as it happens
+//! - Streams that break or fall behind are reopened with a new snapshot on the next probe
+//! - Followers only follow the leader they know, or when they know none, a gossip member
+//!   at their term or later; never while leading themselves
+//!
+//! The same listener answers messages forwarded to agents connected to this node.
+//! Every frame on it is authenticated with the cluster secret, and frames are capped
+//! at `MAX_FRAME` bytes.
+
+use super::forwarding::ForwardOutcome;
+use super::gossip::Gossip;
+use super::manager::ClusterManager;
+use super::types::NodeRole;
+use crate::error::NexaError;
+use crate::mcp::buffer::BufferedMessage;
+use crate::mcp::trace;
+use crate::mcp::registry::{AgentRegistry, RegistryEvent, RegistrySnapshot};
+use futures::{SinkExt, StreamExt};
+use hmac::{Hmac, Mac};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tokio::time;
+use tokio_util::codec::{Framed, LinesCodec};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+/// Longest frame read from a peer, in bytes; a registry snapshot must fit in one
+pub(super) const MAX_FRAME: usize = 16 * 1024 * 1024;
+
+/// One line exchanged between nodes
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub(super) enum PeerMessage {
+    /// Opens a replication stream with the leader's registry
+    Sync { leader_id: Uuid, term: u64, snapshot: RegistrySnapshot },
+    Change { event: RegistryEvent },
+    /// Asks for a message to be delivered to an agent connected to the receiver
+    Forward { agent_id: String, message: BufferedMessage },
+    /// Answers `Forward`
+    Forwarded { outcome: ForwardOutcome },
+}
+
+/// An authenticated connection between two nodes
+///
+/// The accepting node opens with a random challenge. Every frame after it is the hex
+/// HMAC-SHA256, under the cluster secret, of the challenge, the frame's direction and
+/// sequence number and its JSON, followed by a space and the JSON. Frames can thus be
+/// neither forged without the secret nor replayed on another connection or out of order.
+pub(super) struct PeerConnection {
+    frames: Framed<TcpStream, LinesCodec>,
+    secret: Vec<u8>,
+    challenge: String,
+    accepting: bool,
+    sent: u64,
+    received: u64,
+}
+
+impl PeerConnection {
+    /// Open a connection to the node listening on `addr`
+    pub(super) async fn connect(addr: SocketAddr, secret: &str) -> Result<Self, NexaError> {
+        let stream = TcpStream::connect(addr).await?;
+        let mut frames = Framed::new(stream, LinesCodec::new_with_max_length(MAX_FRAME));
+        let challenge = match frames.next().await {
+            Some(line) => line.map_err(frame_error)?,
+            None => return Err(NexaError::cluster(format!("{} closed the connection without a challenge", addr))),
+        };
+        Ok(Self { frames, secret: secret.as_bytes().to_vec(), challenge, accepting: false, sent: 0, received: 0 })
+    }
+
+    /// Answer a connection from another node with a fresh challenge
+    pub(super) async fn accept(stream: TcpStream, secret: &str) -> Result<Self, NexaError> {
+        let mut frames = Framed::new(stream, LinesCodec::new_with_max_length(MAX_FRAME));
+        let challenge = hex::encode(rand::random::<[u8; 16]>());
+        frames.send(challenge.as_str()).await.map_err(frame_error)?;
+        Ok(Self { frames, secret: secret.as_bytes().to_vec(), challenge, accepting: true, sent: 0, received: 0 })
+    }
+
+    pub(super) async fn send(&mut self, message: &PeerMessage) -> Result<(), NexaError> {
+        let body = serde_json::to_string(message)?;
+        let tag = hex::encode(self.mac(self.accepting, self.sent, &body).finalize().into_bytes());
+        self.sent += 1;
+        self.frames.send(format!("{} {}", tag, body)).await.map_err(frame_error)
+    }
+
+    /// Next message from the peer, or `None` once it closed the connection
+    ///
+    /// Fails on frames that are too long or do not carry a valid tag.
+    pub(super) async fn recv(&mut self) -> Result<Option<PeerMessage>, NexaError> {
+        let Some(line) = self.frames.next().await else { return Ok(None) };
+        let line = line.map_err(frame_error)?;
+        let (tag, body) = line.split_once(' ')
+            .ok_or_else(|| NexaError::unauthorized("Peer frame carries no authentication tag"))?;
+        let tag = hex::decode(tag)
+            .map_err(|_| NexaError::unauthorized("Peer frame carries a malformed authentication tag"))?;
+        self.mac(!self.accepting, self.received, body).verify_slice(&tag)
+            .map_err(|_| NexaError::unauthorized("Peer frame failed authentication"))?;
+        self.received += 1;
+        Ok(Some(serde_json::from_str(body)?))
+    }
+
+    fn mac(&self, from_acceptor: bool, seq: u64, body: &str) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC takes keys of any length");
+        mac.update(self.challenge.as_bytes());
+        mac.update(&[from_acceptor as u8]);
+        mac.update(&seq.to_be_bytes());
+        mac.update(body.as_bytes());
+        mac
+    }
+}
+
+fn frame_error(e: tokio_util::codec::LinesCodecError) -> NexaError {
+    match e {
+        tokio_util::codec::LinesCodecError::MaxLineLengthExceeded => {
+            NexaError::cluster(format!("Peer frame is longer than {} bytes", MAX_FRAME))
+        }
+        tokio_util::codec::LinesCodecError::Io(e) => e.into(),
+    }
+}
+
+/// Replicates the registry of one node, and answers forwarded messages
+#[derive(Clone)]
+pub(crate) struct Replicator {
+    manager: ClusterManager,
+    gossip: Gossip,
+    /// Cluster secret authenticating every frame
+    secret: String,
+    /// Registry to replicate, if any
+    registry: Option<AgentRegistry>,
+    /// Stream to each follower while leading, by node id
+    streams: Arc<Mutex<HashMap<Uuid, JoinHandle<()>>>>,
+    /// Streams received from leaders
+    followed: Arc<Mutex<Vec<JoinHandle<()>>>>,
+    tasks: Arc<Mutex<Vec<JoinHandle<()>>>>,
+}
+
+impl Replicator {
+    /// Listen for the leader on the gossip port and stream to the members while leading
+    pub(crate) async fn start(
+        manager: ClusterManager,
+        gossip: Gossip,
+        registry: Option<AgentRegistry>,
+        secret: String,
+    ) -> Result<Self, NexaError> {
+        let addr = gossip.local_addr()?;
+        let listener = TcpListener::bind(addr).await
+            .map_err(|e| NexaError::cluster(format!("Failed to bind replication listener {}: {}", addr, e)))?;
+        let replicator = Self {
+            manager,
+            gossip,
+            secret,
+            registry,
+            streams: Arc::new(Mutex::new(HashMap::new())),
+            followed: Arc::new(Mutex::new(Vec::new())),
+            tasks: Arc::new(Mutex::new(Vec::new())),
+        };
+
+        let acceptor = replicator.clone();
+        let leader = replicator.clone();
+        replicator.tasks.lock().extend([
+            tokio::spawn(async move { acceptor.accept(listener).await }),
+            tokio::spawn(async move { leader.lead().await }),
+        ]);
+        Ok(replicator)
+    }
+
+    /// Stop replicating in both directions
+    pub(crate) fn stop(&self) {
+        for task in self.tasks.lock().drain(..) {
+            task.abort();
+        }
+        for (_, stream) in self.streams.lock().drain() {
+            stream.abort();
+        }
+        for stream in self.followed.lock().drain(..) {
+            stream.abort();
+        }
+    }
+
+    async fn accept(&self, listener: TcpListener) {
+        loop {
+            let (stream, from) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    warn!("Replication accept error: {}", e);
+                    continue;
+                }
+            };
+            let peer = self.clone();
+            let mut followed = self.followed.lock();
+            followed.retain(|stream| !stream.is_finished());
+            followed.push(tokio::spawn(async move {
+                if let Err(e) = peer.serve(stream).await {
+                    debug!("Connection from {} ended: {}", from, e);
+                }
+            }));
+        }
+    }
+
+    /// Follow a replication stream, or answer a forwarded message
+    async fn serve(&self, stream: TcpStream) -> Result<(), NexaError> {
+        let mut connection = PeerConnection::accept(stream, &self.secret).await?;
+        let Some(message) = connection.recv().await? else { return Ok(()) };
+        match message {
+            PeerMessage::Sync { leader_id, term, snapshot } => self.follow(leader_id, term, snapshot, connection).await,
+            PeerMessage::Forward { agent_id, message } => {
+                let delivery = self.manager.delivery().await;
+                let outcome = trace::scope(message.trace.clone(), ForwardOutcome::deliver(delivery, agent_id, message)).await;
+                connection.send(&PeerMessage::Forwarded { outcome }).await
+            }
+            _ => Err(NexaError::cluster("Connection did not open with a snapshot or a forwarded message")),
+        }
+    }
+
+    /// Apply a stream from the leader to the registry
+    async fn follow(
+        &self,
+        leader_id: Uuid,
+        term: u64,
+        snapshot: RegistrySnapshot,
+        mut connection: PeerConnection,
+    ) -> Result<(), NexaError> {
+        let Some(registry) = &self.registry else {
+            return Err(NexaError::cluster(format!("No registry to replicate from {}", leader_id)));
+        };
+        self.recognize(leader_id, term).await?;
+
+        info!("Replicating {} agents and {} tasks from leader {}", snapshot.agents.len(), snapshot.tasks.len(), leader_id);
+        registry.restore(snapshot).await;
+        while let Some(message) = connection.recv().await? {
+            if self.manager.state.read().await.leader_id != Some(leader_id) {
+                return Err(NexaError::cluster(format!("{} is no longer the leader", leader_id)));
+            }
+            match message {
+                PeerMessage::Change { event } => registry.apply(event).await,
+                _ => return Err(NexaError::cluster("Unexpected message in replication stream")),
+            }
+        }
+        Ok(())
+    }
+
+    /// Accept `leader_id` as the leader at `term`, or refuse to follow it
+    ///
+    /// The known leader is followed at its term or later. Without a known leader, a gossip
+    /// member claiming this node's term or a later one is recorded as the leader.
+    async fn recognize(&self, leader_id: Uuid, term: u64) -> Result<(), NexaError> {
+        if self.manager.node.read().await.role == NodeRole::Leader {
+            return Err(NexaError::cluster(format!("Refusing to follow {} while leading", leader_id)));
+        }
+        let member = self.gossip.members().await.iter().any(|m| m.node.id == leader_id);
+        let mut state = self.manager.state.write().await;
+        if term < state.term {
+            return Err(NexaError::cluster(format!("Leader {} is at term {}, behind term {}", leader_id, term, state.term)));
+        }
+        match state.leader_id {
+            Some(known) if known == leader_id => {}
+            Some(known) => {
+                return Err(NexaError::cluster(format!("Refusing to follow {} while {} leads", leader_id, known)));
+            }
+            None if member => {
+                info!("Following leader {} at term {}", leader_id, term);
+                state.leader_id = Some(leader_id);
+            }
+            None => return Err(NexaError::cluster(format!("Leader {} is not a cluster member", leader_id))),
+        }
+        state.term = term;
+        Ok(())
+    }
+
+    /// Keep a stream open to every member while this node leads
+    async fn lead(&self) {
+        let interval = self.manager.config.read().await.probe_interval;
+        let mut ticker = time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let leading = self.registry.is_some() && self.manager.node.read().await.role == NodeRole::Leader;
+            let members = if leading { self.gossip.members().await } else { Vec::new() };
+
+            let mut streams = self.streams.lock();
+            streams.retain(|id, stream| {
+                let keep = !stream.is_finished() && members.iter().any(|m| m.node.id == *id);
+                if !keep {
+                    stream.abort();
+                }
+                keep
+            });
+            for member in members {
+                if streams.contains_key(&member.node.id) {
+                    continue;
+                }
+                let replicator = self.clone();
+                let addr = member.gossip_addr;
+                streams.insert(member.node.id, tokio::spawn(async move {
+                    if let Err(e) = replicator.replicate_to(addr).await {
+                        debug!("Replication stream to {} ended: {}", addr, e);
+                    }
+                }));
+            }
+        }
+    }
+
+    /// Stream the registry to the follower at `addr` until it breaks
+    async fn replicate_to(&self, addr: SocketAddr) -> Result<(), NexaError> {
+        let Some(registry) = &self.registry else { return Ok(()) };
+        let mut connection = PeerConnection::connect(addr, &self.secret).await?;
+        let (snapshot, mut changes) = registry.subscribe().await;
+        let leader_id = self.manager.node.read().await.id;
+        let term = self.manager.state.read().await.term;
+        connection.send(&PeerMessage::Sync { leader_id, term, snapshot }).await?;
+        loop {
+            let event = match changes.recv().await {
+                Ok(event) => event,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    return Err(NexaError::cluster(format!("Follower fell {} changes behind", skipped)));
+                }
+                Err(broadcast::error::RecvError::Closed) => return Ok(()),
+            };
+            connection.send(&PeerMessage::Change { event }).await?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::{Agent, Task};
+    use crate::mcp::cluster::ClusterConfig;
+    use std::future::Future;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::time::Duration;
+
+    async fn node() -> (ClusterManager, AgentRegistry, SocketAddr) {
+        let config = ClusterConfig {
+            probe_interval: Duration::from_millis(50),
+            probe_timeout: Duration::from_millis(20),
+            min_quorum_size: 2,
+            secret: Some("test-secret".to_string()),
+            ..Default::default()
+        };
+        let manager = ClusterManager::new("127.0.0.1:0".parse().unwrap(), Some(config));
+        let registry = AgentRegistry::new();
+        manager.replicate(registry.clone()).await;
+        let addr = manager.start_gossip("127.0.0.1:0".parse().unwrap()).await.unwrap();
+        (manager, registry, addr)
+    }
+
+    async fn eventually<F, Fut>(mut done: F)
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = bool>,
+    {
+        for _ in 0..100 {
+            if done().await {
+                return;
+            }
+            time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("Registry was not replicated");
+    }
+
+    #[tokio::test]
+    async fn test_replicate_registry() {
+        let (leader, registry, leader_addr) = node().await;
+        leader.node.write().await.role = NodeRole::Leader;
+        leader.state.write().await.term = 1;
+        let existing = Agent::new("existing".to_string(), vec!["review".to_string()]);
+        registry.register(existing.clone()).await.unwrap();
+
+        // A follower joining gets the registry as it is, replacing its own
+        let (follower, replica, _) = node().await;
+        let stale = Agent::new("stale".to_string(), vec![]);
+        replica.register(stale.clone()).await.unwrap();
+        follower.join_cluster(leader_addr).await.unwrap();
+        eventually(|| async { replica.get_agent(&existing.id).await.is_ok() }).await;
+        assert!(replica.get_agent(&stale.id).await.is_err());
+        assert_eq!(replica.find_by_capability("review").await[0].id, existing.id);
+
+        // Then every change as it happens, tasks included
+        let task = Task::new("Review".to_string(), String::new(), Vec::new(), Vec::new(), None, 60, 1);
+        registry.add_task(task.clone()).await.unwrap();
+        registry.assign_task(&task.id, &existing.id).await.unwrap();
+        eventually(|| async {
+            replica.get_task(&task.id).await.is_ok_and(|t| t.assigned_agent.as_deref() == Some(existing.id.as_str()))
+        }).await;
+        registry.deregister(&existing.id).await.unwrap();
+        eventually(|| async { replica.list_agents().await.is_empty() }).await;
+
+        leader.stop().await.unwrap();
+        follower.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_replication_authentication() {
+        let (follower, replica, addr) = node().await;
+        let known = Agent::new("known".to_string(), vec![]);
+        replica.register(known.clone()).await.unwrap();
+        let (leader_id, other_id) = (Uuid::new_v4(), Uuid::new_v4());
+        follower.state.write().await.leader_id = Some(leader_id);
+        let sync = |leader_id| PeerMessage::Sync { leader_id, term: 99, snapshot: RegistrySnapshot::default() };
+
+        // Without the secret, or with another one, frames are refused
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let mut line = serde_json::to_string(&sync(leader_id)).unwrap();
+        line.push('\n');
+        stream.write_all(line.as_bytes()).await.unwrap();
+        let mut wrong = PeerConnection::connect(addr, "wrong-secret").await.unwrap();
+        wrong.send(&sync(leader_id)).await.unwrap();
+
+        // With it, only the known leader is followed
+        let mut other = PeerConnection::connect(addr, "test-secret").await.unwrap();
+        other.send(&sync(other_id)).await.unwrap();
+
+        // Frames longer than the cap are refused before they are buffered whole
+        let mut flood = TcpStream::connect(addr).await.unwrap();
+        let _ = flood.write_all(&vec![b'a'; MAX_FRAME + 1]).await;
+
+        // Each is dropped once refused
+        for refused in [stream, wrong.frames.into_inner(), other.frames.into_inner(), flood] {
+            let mut refused = BufReader::new(refused);
+            let mut rest = String::new();
+            while refused.read_line(&mut rest).await.is_ok_and(|read| read > 0) {}
+        }
+        assert!(replica.get_agent(&known.id).await.is_ok());
+        assert_eq!(follower.state.read().await.term, 0);
+
+        let mut leader = PeerConnection::connect(addr, "test-secret").await.unwrap();
+        leader.send(&sync(leader_id)).await.unwrap();
+        eventually(|| async { replica.get_agent(&known.id).await.is_err() }).await;
+        assert_eq!(follower.state.read().await.term, 99);
+
+        follower.stop().await.unwrap();
+    }
+}
@@ -115,7 +115,8 @@ pub struct ClusterConfig {
     pub heartbeat_interval: Duration,
     /// Election timeout range (min, max)
     pub election_timeout: (Duration, Duration),
-    /// Minimum nodes for quorum
+    /// Minimum nodes for quorum; a node seeing fewer healthy members, itself
+    /// included, is fenced: it steps down and rejects task assignments
     pub min_quorum_size: usize,
     /// Node failure timeout
     pub node_timeout: Duration,
@@ -123,6 +124,44 @@ pub struct ClusterConfig {
     pub replication_factor: usize,
     /// Cluster name/ID
     pub cluster_id: String,
+    /// UDP address to gossip membership on; gossip is off when unset
+    #[serde(default)]
+    pub gossip_addr: Option<SocketAddr>,
+    /// Gossip addresses of members to join through, tried in order
+    #[serde(default)]
+    pub seeds: Vec<SocketAddr>,
+    /// Interval between gossip probes
+    #[serde(default = "default_probe_interval")]
+    pub probe_interval: Duration,
+    /// Time a probed member has to acknowledge
+    #[serde(default = "default_probe_timeout")]
+    pub probe_timeout: Duration,
+    /// Time a suspected member has to refute the suspicion before removal
+    #[serde(default = "default_suspect_timeout")]
+    pub suspect_timeout: Duration,
+    /// Members asked to probe a member that missed a direct probe
+    #[serde(default = "default_indirect_probes")]
+    pub indirect_probes: usize,
+    /// Shared secret authenticating every frame between nodes; registry
+    /// replication and message forwarding are off without it
+    #[serde(default)]
+    pub secret: Option<String>,
+}
+
+fn default_probe_interval() -> Duration {
+    Duration::from_secs(1)
+}
+
+fn default_probe_timeout() -> Duration {
+    Duration::from_millis(300)
+}
+
+fn default_suspect_timeout() -> Duration {
+    Duration::from_secs(5)
+}
+
+fn default_indirect_probes() -> usize {
+    3
 }
 
 impl Default for ClusterConfig {
@@ -134,6 +173,13 @@ impl Default for ClusterConfig {
             node_timeout: Duration::from_secs(5),
             replication_factor: 3,
             cluster_id: "nexa-cluster".to_string(),
+            gossip_addr: None,
+            seeds: Vec::new(),
+            probe_interval: default_probe_interval(),
+            probe_timeout: default_probe_timeout(),
+            suspect_timeout: default_suspect_timeout(),
+            indirect_probes: default_indirect_probes(),
+            secret: None,
         }
     }
 }
@@ -160,4 +206,10 @@ pub enum ClusterMessage {
         term: u64,
         state: ClusterState,
     },
+    /// The local node gained or lost sight of a quorum of members, itself included
+    QuorumChanged {
+        has_quorum: bool,
+        members: usize,
+        required: usize,
+    },
 } 
\ No newline at end of file
@@ -1,7 +1,7 @@
 use tokio::sync::mpsc;
 use tracing::{debug, error};
 use crate::error::NexaError;
-use crate::mcp::buffer::MessageBuffer;
+use crate::mcp::buffer::{BufferedMessage, MessageBuffer};
 use crate::mcp::cluster::ClusterManager;
 use crate::mcp::processor::{MessageProcessor, ProcessorConfig};
 use std::sync::Arc;
@@ -170,14 +170,13 @@ impl ClusterProcessor {
             })
         };
 
-        // Wait for shutdown signal
-        tokio::select! {
-            _ = shutdown_rx.recv() => {
-                debug!("Shutting down cluster processor");
-                sync_task.abort();
-                redistribution_task.abort();
-            }
-        }
+        // Stop background tasks on shutdown without blocking the caller
+        tokio::spawn(async move {
+            let _ = shutdown_rx.recv().await;
+            debug!("Shutting down cluster processor");
+            sync_task.abort();
+            redistribution_task.abort();
+        });
 
         Ok(())
     }
@@ -190,6 +189,20 @@ impl ClusterProcessor {
         self.processor.stop().await
     }
 
+    /// Cluster manager the processor distributes messages through
+    pub fn manager(&self) -> &Arc<ClusterManager> {
+        &self.manager
+    }
+
+    /// Deliver `message` to `agent_id` through whichever node it is connected to
+    ///
+    /// Returns the response of the agent's node; callers need not know where the agent is.
+    pub async fn forward(&self, agent_id: &str, message: &BufferedMessage) -> Result<serde_json::Value, NexaError> {
+        let response = self.manager.forward(agent_id, message).await?;
+        debug!("Forwarded message {} to agent {}", message.id, agent_id);
+        Ok(response)
+    }
+
     /// Sync messages across the cluster
     async fn sync_messages(
         buffer: Arc<MessageBuffer>,
@@ -0,0 +1,156 @@
+//! Message Encoding
+//!
+//! How MCP messages are framed on connections that can carry binary frames:
+//! - JSON text frames, the default, readable in any proxy or debugger
+//! - CBOR binary frames, when both sides agree on them in the handshake
+//! - CBOR binary frames compressed with zstd above `COMPRESSION_THRESHOLD` bytes,
+//!   when both sides agree on `cbor+zstd`
+//!
+//! A binary frame is a flags byte followed by the CBOR document, itself zstd-compressed
+//! under the `ZSTD` flag. Frames with unknown flags are refused.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::error::NexaError;
+
+/// Flags of a binary frame with a plain CBOR document
+const PLAIN: u8 = 0;
+
+/// Flag of a binary frame with a zstd-compressed CBOR document
+const ZSTD: u8 = 0x01;
+
+/// CBOR documents larger than this are compressed on `cbor+zstd` connections
+pub const COMPRESSION_THRESHOLD: usize = 1024;
+
+/// Largest document a compressed frame may expand to
+const MAX_DECOMPRESSED: usize = 16 * 1024 * 1024;
+
+/// zstd level, favouring speed as messages are compressed on every send
+const ZSTD_LEVEL: i32 = 3;
+
+/// Encoding of the messages of a connection
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    #[default]
+    Json,
+    Cbor,
+    /// CBOR, compressed with zstd above `COMPRESSION_THRESHOLD`
+    #[serde(rename = "cbor+zstd")]
+    CborZstd,
+}
+
+impl Encoding {
+    /// Encodings of connections carrying text frames only
+    pub const TEXT: &'static [Encoding] = &[Encoding::Json];
+
+    /// Encodings of connections carrying binary frames too
+    pub const ALL: &'static [Encoding] = &[Encoding::Json, Encoding::Cbor, Encoding::CborZstd];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Cbor => "cbor",
+            Self::CborZstd => "cbor+zstd",
+        }
+    }
+
+    /// The first of the `offered` encoding names that is `supported`, or JSON
+    pub fn negotiate(offered: &[String], supported: &[Encoding]) -> Self {
+        offered.iter()
+            .find_map(|name| supported.iter().copied().find(|e| e.name() == name))
+            .unwrap_or_default()
+    }
+}
+
+/// Encode a message as a binary frame, compressing documents above the threshold if `compress`
+pub fn encode_binary(message: &Value, compress: bool) -> Result<Vec<u8>, NexaError> {
+    let mut document = Vec::new();
+    ciborium::into_writer(message, &mut document)
+        .map_err(|e| NexaError::invalid_input(format!("Failed to encode CBOR message: {}", e)))?;
+    if compress && document.len() > COMPRESSION_THRESHOLD {
+        let compressed = zstd::bulk::compress(&document, ZSTD_LEVEL)
+            .map_err(|e| NexaError::system(format!("Failed to compress message: {}", e)))?;
+        let mut frame = Vec::with_capacity(compressed.len() + 1);
+        frame.push(ZSTD);
+        frame.extend_from_slice(&compressed);
+        return Ok(frame);
+    }
+    let mut frame = Vec::with_capacity(document.len() + 1);
+    frame.push(PLAIN);
+    frame.extend_from_slice(&document);
+    Ok(frame)
+}
+
+/// Decode a message from a binary frame
+pub fn decode_binary(frame: &[u8]) -> Result<Value, NexaError> {
+    match frame.split_first() {
+        Some((&PLAIN, document)) => decode_cbor(document),
+        Some((&ZSTD, compressed)) => {
+            let document = zstd::bulk::decompress(compressed, MAX_DECOMPRESSED)
+                .map_err(|e| NexaError::invalid_input(format!("Invalid compressed message: {}", e)))?;
+            decode_cbor(&document)
+        }
+        Some((flags, _)) => Err(NexaError::invalid_input(format!("Unsupported binary frame flags {:#04x}", flags))),
+        None => Err(NexaError::invalid_input("Empty binary frame")),
+    }
+}
+
+fn decode_cbor(document: &[u8]) -> Result<Value, NexaError> {
+    ciborium::from_reader(document)
+        .map_err(|e| NexaError::invalid_input(format!("Invalid CBOR message: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_binary_frames() {
+        let message = serde_json::json!({ "TaskAssignment": { "agent_id": "a-1", "task": { "payload": [1, 2, 3] } } });
+        let frame = encode_binary(&message, false).unwrap();
+        assert!(frame.len() < message.to_string().len());
+        assert_eq!(decode_binary(&frame).unwrap(), message);
+
+        assert!(decode_binary(&[]).is_err());
+        let mut flagged = frame;
+        flagged[0] = 0x02;
+        assert!(decode_binary(&flagged).unwrap_err().to_string().contains("0x02"));
+    }
+
+    #[test]
+    fn test_compressed_frames() {
+        // Below the threshold, frames stay plain even when compression is on
+        let small = serde_json::json!({ "StatusUpdate": { "agent_id": "a-1", "status": "Busy" } });
+        let frame = encode_binary(&small, true).unwrap();
+        assert_eq!(frame[0], PLAIN);
+        assert_eq!(decode_binary(&frame).unwrap(), small);
+
+        let large = serde_json::json!({ "TaskAssignment": {
+            "agent_id": "a-1",
+            "task": { "description": "summarize the quarterly report ".repeat(100) },
+        } });
+        let plain = encode_binary(&large, false).unwrap();
+        assert!(plain.len() > COMPRESSION_THRESHOLD);
+        assert_eq!(plain[0], PLAIN);
+        let compressed = encode_binary(&large, true).unwrap();
+        assert_eq!(compressed[0], ZSTD);
+        assert!(compressed.len() < plain.len() / 4);
+        assert_eq!(decode_binary(&compressed).unwrap(), large);
+
+        // Corrupt data and documents expanding past the limit are refused
+        assert!(decode_binary(&[ZSTD, 1, 2, 3]).is_err());
+        let bomb = zstd::bulk::compress(&vec![0u8; MAX_DECOMPRESSED + 1], ZSTD_LEVEL).unwrap();
+        assert!(decode_binary(&[&[ZSTD][..], &bomb].concat()).is_err());
+    }
+
+    #[test]
+    fn test_negotiate_encoding() {
+        let offered = vec!["msgpack".to_string(), "cbor".to_string(), "json".to_string()];
+        assert_eq!(Encoding::negotiate(&offered, Encoding::ALL), Encoding::Cbor);
+        let offered = vec!["cbor+zstd".to_string(), "cbor".to_string()];
+        assert_eq!(Encoding::negotiate(&offered, Encoding::ALL), Encoding::CborZstd);
+        assert_eq!(Encoding::negotiate(&offered, Encoding::TEXT), Encoding::Json);
+        assert_eq!(Encoding::negotiate(&[], Encoding::ALL), Encoding::Json);
+    }
+}
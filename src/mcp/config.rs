@@ -1,7 +1,9 @@
 use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::time::Duration;
 use crate::error::NexaError;
+use crate::mcp::loadbalancer::Strategy;
 use std::fs;
 use tracing::debug;
 use uuid;
@@ -62,6 +64,30 @@ pub struct LoadBalancerConfig {
 
     #[serde(default = "default_max_connection_lifetime_secs")]
     pub max_connection_lifetime_secs: u64,
+
+    /// How backends are picked
+    #[serde(default)]
+    pub strategy: Strategy,
+
+    #[serde(default)]
+    pub backends: Vec<BackendConfig>,
+
+    /// Consecutive failures after which a backend is ejected
+    #[serde(default = "default_eject_after_failures")]
+    pub eject_after_failures: u32,
+
+    /// How long an ejected backend gets no traffic before a probe request is let through
+    #[serde(default = "default_ejection_secs")]
+    pub ejection_secs: u64,
+}
+
+/// A backend to balance across
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackendConfig {
+    pub addr: SocketAddr,
+    /// Share of requests relative to the other backends, under weighted round-robin
+    #[serde(default = "default_backend_weight")]
+    pub weight: u32,
 }
 
 impl Default for LoadBalancerConfig {
@@ -74,6 +100,10 @@ impl Default for LoadBalancerConfig {
             max_connection_lifetime_secs: 3600,
             max_pool_size: 100,
             min_pool_size: 10,
+            strategy: Strategy::default(),
+            backends: Vec::new(),
+            eject_after_failures: default_eject_after_failures(),
+            ejection_secs: default_ejection_secs(),
         }
     }
 }
@@ -195,6 +225,18 @@ fn default_max_connection_lifetime_secs() -> u64 {
     300
 }
 
+fn default_backend_weight() -> u32 {
+    1
+}
+
+fn default_eject_after_failures() -> u32 {
+    3
+}
+
+fn default_ejection_secs() -> u64 {
+    30
+}
+
 impl Default for ServerConfig {
     fn default() -> Self {
         Self {
@@ -222,6 +264,10 @@ impl Default for ServerConfig {
                 retry_delay_ms: default_retry_delay_ms(),
                 health_check_interval_ms: default_health_check_interval_ms(),
                 max_connection_lifetime_secs: default_max_connection_lifetime_secs(),
+                strategy: Strategy::default(),
+                backends: Vec::new(),
+                eject_after_failures: default_eject_after_failures(),
+                ejection_secs: default_ejection_secs(),
             },
         }
     }
@@ -343,9 +389,3 @@ impl ServerConfig {
         Ok(())
     }
 }
-
-impl From<serde_yaml::Error> for NexaError {
-    fn from(err: serde_yaml::Error) -> Self {
-        NexaError::config(format!("YAML error: {}", err))
-    }
-} 
\ No newline at end of file
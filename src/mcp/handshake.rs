@@ -0,0 +1,74 @@
+//! Protocol version negotiation
+//!
+//! Agents open with `ClientHello`, listing the protocol versions and capabilities they
+//! support; the server answers `ServerHello` with the highest version both support and
+//! the capabilities they share. Agents that skip the handshake are served as version 1,
+//! unless the server requires it. Connections that can carry binary frames also agree
+//! on the encoding of the messages that follow, JSON unless the agent offers another.
+
+use serde::{Deserialize, Serialize};
+use crate::error::NexaError;
+use crate::mcp::codec::Encoding;
+
+/// Protocol version spoken by this server
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// Oldest protocol version still served, the one of agents that send no `ClientHello`
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+/// Capability of agents that answer `Ping` with `Pong`; only they are monitored for liveness
+pub const HEARTBEAT_CAPABILITY: &str = "heartbeat";
+
+/// Capabilities this server offers
+pub const SERVER_CAPABILITIES: &[&str] = &["agent_query", "events", "heartbeat", "mcp", "task_assignment"];
+
+/// Outcome of a successful handshake
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Handshake {
+    /// Version both sides speak
+    pub version: u32,
+    /// Capabilities both sides support
+    pub capabilities: Vec<String>,
+    /// Encoding of the messages after the handshake
+    #[serde(default)]
+    pub encoding: Encoding,
+}
+
+/// Agree on the highest version in `versions` that the server speaks
+///
+/// Fails with an invalid input error naming the supported range when there is none.
+pub fn negotiate(versions: &[u32], capabilities: &[String]) -> Result<Handshake, NexaError> {
+    let version = versions.iter()
+        .copied()
+        .filter(|v| (MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(v))
+        .max()
+        .ok_or_else(|| NexaError::invalid_input(format!(
+            "No supported protocol version in {:?}; this server speaks {} to {}",
+            versions, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION,
+        )))?;
+    let capabilities = capabilities.iter()
+        .filter(|c| SERVER_CAPABILITIES.contains(&c.as_str()))
+        .cloned()
+        .collect();
+    Ok(Handshake { version, capabilities, encoding: Encoding::Json })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate() {
+        let capabilities = vec!["events".to_string(), "telepathy".to_string()];
+        let handshake = negotiate(&[1, 2, 7], &capabilities).unwrap();
+        assert_eq!(handshake.version, PROTOCOL_VERSION);
+        assert_eq!(handshake.capabilities, vec!["events"]);
+
+        // Older agents are downgraded to the version they speak
+        assert_eq!(negotiate(&[1], &[]).unwrap().version, 1);
+
+        let err = negotiate(&[0, 9], &[]).unwrap_err();
+        assert!(err.is_user_error());
+        assert!(err.to_string().contains("1 to 2"));
+    }
+}
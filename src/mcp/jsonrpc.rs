@@ -0,0 +1,471 @@
+//! Model Context Protocol over JSON-RPC 2.0
+//!
+//! Lets MCP hosts such as Claude Desktop use nexa:
+//! - `initialize` and `ping`, with protocol version negotiation
+//! - `tools/list` and `tools/call` over the server's tool registry
+//! - `resources/list`, `resources/templates/list` and `resources/read` for agents and tasks
+//! - `prompts/list` and `prompts/get` for the system prompts of agents
+//!
+//! The handler is transport independent; it takes and returns JSON-RPC messages.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::debug;
+use crate::agent::Agent;
+use crate::error::NexaError;
+use crate::tools::ToolRegistry;
+use super::registry::AgentRegistry;
+
+/// Protocol revisions this server speaks, newest first
+pub const PROTOCOL_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const INTERNAL_ERROR: i64 = -32603;
+/// MCP code for a `resources/read` of an unknown URI
+pub const RESOURCE_NOT_FOUND: i64 = -32002;
+
+/// A JSON-RPC request, or a notification when it has no `id`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonRpcRequest {
+    pub jsonrpc: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Value>,
+    pub method: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub params: Option<Value>,
+}
+
+impl JsonRpcRequest {
+    pub fn new(id: impl Into<Value>, method: impl Into<String>, params: Value) -> Self {
+        Self { jsonrpc: "2.0".to_string(), id: Some(id.into()), method: method.into(), params: Some(params) }
+    }
+
+    pub fn notification(method: impl Into<String>, params: Option<Value>) -> Self {
+        Self { jsonrpc: "2.0".to_string(), id: None, method: method.into(), params }
+    }
+}
+
+/// A JSON-RPC response carrying either a result or an error
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonRpcResponse {
+    pub jsonrpc: String,
+    pub id: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<JsonRpcError>,
+}
+
+impl JsonRpcResponse {
+    pub fn success(id: Value, result: Value) -> Self {
+        Self { jsonrpc: "2.0".to_string(), id, result: Some(result), error: None }
+    }
+
+    pub fn failure(id: Value, error: JsonRpcError) -> Self {
+        Self { jsonrpc: "2.0".to_string(), id, result: None, error: Some(error) }
+    }
+
+    /// The result, or the error converted to a `NexaError`
+    pub fn into_result(self) -> Result<Value, NexaError> {
+        match self.error {
+            Some(error) => Err(error.into()),
+            None => Ok(self.result.unwrap_or(Value::Null)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonRpcError {
+    pub code: i64,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl JsonRpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self { code, message: message.into(), data: None }
+    }
+}
+
+impl From<NexaError> for JsonRpcError {
+    fn from(e: NexaError) -> Self {
+        let code = if e.is_user_error() { INVALID_PARAMS } else { INTERNAL_ERROR };
+        Self { code, message: e.message(), data: Some(json!({ "code": e.code() })) }
+    }
+}
+
+impl From<JsonRpcError> for NexaError {
+    fn from(e: JsonRpcError) -> Self {
+        match e.code {
+            INVALID_PARAMS | INVALID_REQUEST => NexaError::invalid_input(e.message),
+            METHOD_NOT_FOUND | RESOURCE_NOT_FOUND => NexaError::not_found(e.message),
+            _ => NexaError::protocol(format!("{} (code {})", e.message, e.code)),
+        }
+    }
+}
+
+/// Whether a message looks like JSON-RPC rather than the native WebSocket protocol
+pub fn is_jsonrpc(message: &Value) -> bool {
+    match message {
+        Value::Array(batch) => batch.first().is_some_and(|m| m.get("jsonrpc").is_some()),
+        message => message.get("jsonrpc").is_some(),
+    }
+}
+
+/// Serves MCP requests from the server's tools and agent registry
+#[derive(Debug, Clone)]
+pub struct McpHandler {
+    tools: ToolRegistry,
+    registry: AgentRegistry,
+}
+
+impl McpHandler {
+    pub fn new(tools: ToolRegistry, registry: AgentRegistry) -> Self {
+        Self { tools, registry }
+    }
+
+    /// Handle a message as text, returning the text to send back, if any
+    pub async fn handle_text(&self, text: &str) -> Option<String> {
+        let reply = match serde_json::from_str::<Value>(text) {
+            Ok(message) => self.handle(message).await?,
+            Err(e) => json!(JsonRpcResponse::failure(Value::Null, JsonRpcError::new(PARSE_ERROR, e.to_string()))),
+        };
+        Some(reply.to_string())
+    }
+
+    /// Handle a request, notification or batch; notifications get no reply
+    pub async fn handle(&self, message: Value) -> Option<Value> {
+        match message {
+            Value::Array(batch) if batch.is_empty() => {
+                Some(json!(JsonRpcResponse::failure(Value::Null, JsonRpcError::new(INVALID_REQUEST, "Empty batch"))))
+            }
+            Value::Array(batch) => {
+                let mut replies = Vec::new();
+                for message in batch {
+                    if let Some(reply) = self.handle_one(message).await {
+                        replies.push(json!(reply));
+                    }
+                }
+                (!replies.is_empty()).then_some(Value::Array(replies))
+            }
+            message => self.handle_one(message).await.map(|reply| json!(reply)),
+        }
+    }
+
+    async fn handle_one(&self, message: Value) -> Option<JsonRpcResponse> {
+        let id = message.get("id").cloned().unwrap_or(Value::Null);
+        match serde_json::from_value::<JsonRpcRequest>(message) {
+            Ok(request) => self.handle_request(request).await,
+            Err(e) => Some(JsonRpcResponse::failure(id, JsonRpcError::new(INVALID_REQUEST, e.to_string()))),
+        }
+    }
+
+    /// Handle a parsed request; notifications get no response
+    pub async fn handle_request(&self, request: JsonRpcRequest) -> Option<JsonRpcResponse> {
+        let params = request.params.unwrap_or(Value::Null);
+        let Some(id) = request.id else {
+            debug!("MCP notification {}", request.method);
+            return None;
+        };
+        let response = match self.dispatch(&request.method, params).await {
+            Ok(result) => JsonRpcResponse::success(id, result),
+            Err(error) => JsonRpcResponse::failure(id, error),
+        };
+        Some(response)
+    }
+
+    async fn dispatch(&self, method: &str, params: Value) -> Result<Value, JsonRpcError> {
+        debug!("MCP request {}", method);
+        match method {
+            "initialize" => Ok(initialize(&params)),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(self.list_tools()),
+            "tools/call" => self.call_tool(&params).await,
+            "resources/list" => Ok(self.list_resources().await),
+            "resources/templates/list" => Ok(list_resource_templates()),
+            "resources/read" => self.read_resource(&params).await,
+            "prompts/list" => Ok(self.list_prompts().await),
+            "prompts/get" => self.get_prompt(&params).await,
+            method => Err(JsonRpcError::new(METHOD_NOT_FOUND, format!("Unknown method '{}'", method))),
+        }
+    }
+
+    fn list_tools(&self) -> Value {
+        let tools: Vec<Value> = self.tools.list().into_iter()
+            .map(|spec| json!({
+                "name": spec.name,
+                "description": spec.description,
+                "inputSchema": spec.input_schema,
+            }))
+            .collect();
+        json!({ "tools": tools })
+    }
+
+    async fn call_tool(&self, params: &Value) -> Result<Value, JsonRpcError> {
+        let name = required_str(params, "name")?;
+        if self.tools.get(name).is_none() {
+            return Err(JsonRpcError::new(INVALID_PARAMS, format!("Unknown tool '{}'", name)));
+        }
+        let args = params.get("arguments").cloned().unwrap_or_else(|| json!({}));
+        // Failures of the tool itself are results, so the model can see them
+        let (text, is_error) = match self.tools.call(name, args).await {
+            Ok(Value::String(text)) => (text, false),
+            Ok(value) => (value.to_string(), false),
+            Err(e) => (e.to_string(), true),
+        };
+        Ok(json!({
+            "content": [{ "type": "text", "text": text }],
+            "isError": is_error,
+        }))
+    }
+
+    async fn list_resources(&self) -> Value {
+        let mut resources = vec![
+            json!({
+                "uri": "nexa://agents",
+                "name": "agents",
+                "description": "All registered agents",
+                "mimeType": "application/json",
+            }),
+            json!({
+                "uri": "nexa://tasks",
+                "name": "tasks",
+                "description": "All tasks",
+                "mimeType": "application/json",
+            }),
+        ];
+        for agent in self.registry.list_agents().await {
+            resources.push(json!({
+                "uri": format!("nexa://agents/{}", agent.id),
+                "name": agent.name,
+                "description": format!("Agent {} ({:?})", agent.id, agent.status),
+                "mimeType": "application/json",
+            }));
+        }
+        json!({ "resources": resources })
+    }
+
+    async fn read_resource(&self, params: &Value) -> Result<Value, JsonRpcError> {
+        let uri = required_str(params, "uri")?;
+        let not_found = || JsonRpcError::new(RESOURCE_NOT_FOUND, format!("Unknown resource '{}'", uri));
+        let path = uri.strip_prefix("nexa://").ok_or_else(not_found)?;
+        let contents = match path.split_once('/') {
+            None if path == "agents" => json!(self.registry.list_agents().await),
+            None if path == "tasks" => json!(self.registry.list_tasks().await?),
+            Some(("agents", id)) => json!(self.registry.get_agent(id).await.map_err(|_| not_found())?),
+            Some(("tasks", id)) => json!(self.registry.get_task(id).await.map_err(|_| not_found())?),
+            _ => return Err(not_found()),
+        };
+        Ok(json!({
+            "contents": [{
+                "uri": uri,
+                "mimeType": "application/json",
+                "text": serde_json::to_string_pretty(&contents).map_err(NexaError::from)?,
+            }]
+        }))
+    }
+
+    async fn list_prompts(&self) -> Value {
+        let prompts: Vec<Value> = self.registry.list_agents().await.into_iter()
+            .filter(|agent| agent.system_prompt.is_some())
+            .map(|agent| json!({
+                "name": agent.name,
+                "description": format!("Act as agent {}", agent.id),
+                "arguments": [{
+                    "name": "input",
+                    "description": "Request for the agent",
+                    "required": false,
+                }],
+            }))
+            .collect();
+        json!({ "prompts": prompts })
+    }
+
+    async fn get_prompt(&self, params: &Value) -> Result<Value, JsonRpcError> {
+        let name = required_str(params, "name")?;
+        let agent = self.find_agent(name).await
+            .filter(|agent| agent.system_prompt.is_some())
+            .ok_or_else(|| JsonRpcError::new(INVALID_PARAMS, format!("Unknown prompt '{}'", name)))?;
+        let mut text = agent.system_prompt.clone().unwrap_or_default();
+        if let Some(input) = params.pointer("/arguments/input").and_then(Value::as_str) {
+            text = format!("{}\n\n{}", text, input);
+        }
+        Ok(json!({
+            "description": format!("Act as agent {}", agent.id),
+            "messages": [{ "role": "user", "content": { "type": "text", "text": text } }],
+        }))
+    }
+
+    /// Agent with this name, or this id
+    async fn find_agent(&self, name: &str) -> Option<Agent> {
+        let agents = self.registry.list_agents().await;
+        agents.iter().find(|a| a.name == name).or_else(|| agents.iter().find(|a| a.id == name)).cloned()
+    }
+}
+
+fn initialize(params: &Value) -> Value {
+    let requested = params.get("protocolVersion").and_then(Value::as_str);
+    let version = requested
+        .filter(|v| PROTOCOL_VERSIONS.contains(v))
+        .unwrap_or(PROTOCOL_VERSIONS[0]);
+    json!({
+        "protocolVersion": version,
+        "capabilities": {
+            "tools": { "listChanged": false },
+            "resources": { "subscribe": false, "listChanged": false },
+            "prompts": { "listChanged": false },
+        },
+        "serverInfo": {
+            "name": "nexa",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+    })
+}
+
+fn list_resource_templates() -> Value {
+    json!({
+        "resourceTemplates": [
+            {
+                "uriTemplate": "nexa://agents/{id}",
+                "name": "agent",
+                "description": "A registered agent",
+                "mimeType": "application/json",
+            },
+            {
+                "uriTemplate": "nexa://tasks/{id}",
+                "name": "task",
+                "description": "A task",
+                "mimeType": "application/json",
+            },
+        ]
+    })
+}
+
+fn required_str<'a>(params: &'a Value, field: &str) -> Result<&'a str, JsonRpcError> {
+    params.get(field)
+        .and_then(Value::as_str)
+        .ok_or_else(|| JsonRpcError::new(INVALID_PARAMS, format!("Missing '{}' parameter", field)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use futures::future::BoxFuture;
+    use crate::agent::AgentStatus;
+    use crate::tools::{Tool, ToolSpec};
+
+    struct Upper(ToolSpec);
+
+    impl Tool for Upper {
+        fn spec(&self) -> &ToolSpec {
+            &self.0
+        }
+
+        fn call(&self, args: Value) -> BoxFuture<'_, Result<Value, NexaError>> {
+            Box::pin(async move { Ok(Value::String(args["text"].as_str().unwrap_or_default().to_uppercase())) })
+        }
+    }
+
+    async fn handler() -> McpHandler {
+        let tools = ToolRegistry::new();
+        tools.register(Arc::new(Upper(ToolSpec {
+            name: "upper".to_string(),
+            description: "Uppercase text".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": { "text": { "type": "string" } },
+                "required": ["text"],
+            }),
+        }))).unwrap();
+        let registry = AgentRegistry::new();
+        registry.register(Agent {
+            id: "agent-1".to_string(),
+            name: "reviewer".to_string(),
+            capabilities: vec!["review".to_string()],
+            status: AgentStatus::Idle,
+            current_task: None,
+            last_heartbeat: chrono::Utc::now(),
+            system_prompt: Some("You review code.".to_string()),
+            guardrails: Vec::new(),
+        }).await.unwrap();
+        McpHandler::new(tools, registry)
+    }
+
+    async fn call(handler: &McpHandler, method: &str, params: Value) -> JsonRpcResponse {
+        handler.handle_request(JsonRpcRequest::new(1, method, params)).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_initialize_and_tools() {
+        let handler = handler().await;
+        let result = call(&handler, "initialize", json!({ "protocolVersion": "2024-11-05" })).await.into_result().unwrap();
+        assert_eq!(result["protocolVersion"], "2024-11-05");
+        assert_eq!(result["serverInfo"]["name"], "nexa");
+        let result = call(&handler, "initialize", json!({ "protocolVersion": "1999-01-01" })).await.into_result().unwrap();
+        assert_eq!(result["protocolVersion"], PROTOCOL_VERSIONS[0]);
+        assert!(handler.handle_request(JsonRpcRequest::notification("notifications/initialized", None)).await.is_none());
+
+        let result = call(&handler, "tools/list", json!({})).await.into_result().unwrap();
+        assert_eq!(result["tools"][0]["name"], "upper");
+        assert_eq!(result["tools"][0]["inputSchema"]["required"][0], "text");
+
+        let result = call(&handler, "tools/call", json!({ "name": "upper", "arguments": { "text": "hi" } })).await;
+        let result = result.into_result().unwrap();
+        assert_eq!(result["content"][0]["text"], "HI");
+        assert_eq!(result["isError"], false);
+
+        let result = call(&handler, "tools/call", json!({ "name": "upper", "arguments": {} })).await.into_result().unwrap();
+        assert_eq!(result["isError"], true);
+
+        let error = call(&handler, "tools/call", json!({ "name": "missing" })).await.error.unwrap();
+        assert_eq!(error.code, INVALID_PARAMS);
+        let error = call(&handler, "sampling/createMessage", json!({})).await.error.unwrap();
+        assert_eq!(error.code, METHOD_NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_resources_and_prompts() {
+        let handler = handler().await;
+        let result = call(&handler, "resources/list", json!({})).await.into_result().unwrap();
+        let uris: Vec<&str> = result["resources"].as_array().unwrap().iter().map(|r| r["uri"].as_str().unwrap()).collect();
+        assert_eq!(uris, vec!["nexa://agents", "nexa://tasks", "nexa://agents/agent-1"]);
+
+        let result = call(&handler, "resources/read", json!({ "uri": "nexa://agents/agent-1" })).await.into_result().unwrap();
+        let agent: Agent = serde_json::from_str(result["contents"][0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(agent.name, "reviewer");
+        let error = call(&handler, "resources/read", json!({ "uri": "nexa://agents/nobody" })).await.error.unwrap();
+        assert_eq!(error.code, RESOURCE_NOT_FOUND);
+
+        let result = call(&handler, "prompts/list", json!({})).await.into_result().unwrap();
+        assert_eq!(result["prompts"][0]["name"], "reviewer");
+        let result = call(&handler, "prompts/get", json!({ "name": "reviewer", "arguments": { "input": "Check main.rs" } })).await;
+        let text = result.into_result().unwrap()["messages"][0]["content"]["text"].as_str().unwrap().to_string();
+        assert_eq!(text, "You review code.\n\nCheck main.rs");
+    }
+
+    #[tokio::test]
+    async fn test_text_and_batches() {
+        let handler = handler().await;
+        let reply: Value = serde_json::from_str(&handler.handle_text("{not json").await.unwrap()).unwrap();
+        assert_eq!(reply["error"]["code"], PARSE_ERROR);
+        assert!(handler.handle_text(r#"{"jsonrpc":"2.0","method":"notifications/cancelled"}"#).await.is_none());
+
+        let batch = json!([
+            { "jsonrpc": "2.0", "id": 1, "method": "ping" },
+            { "jsonrpc": "2.0", "method": "notifications/initialized" },
+            { "jsonrpc": "2.0", "id": 2 },
+        ]);
+        assert!(is_jsonrpc(&batch));
+        let replies = handler.handle(batch).await.unwrap();
+        assert_eq!(replies.as_array().unwrap().len(), 2);
+        assert_eq!(replies[0]["result"], json!({}));
+        assert_eq!(replies[1]["id"], 2);
+        assert_eq!(replies[1]["error"]["code"], INVALID_REQUEST);
+        assert!(!is_jsonrpc(&json!({ "type": "RegisterAgent" })));
+    }
+}
@@ -1,11 +1,239 @@
 use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use tokio::sync::{RwLock, Semaphore};
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use tokio::net::TcpStream;
 use std::net::SocketAddr;
-use std::time::{Duration, SystemTime};
-use tracing::{debug, error, warn};
+use std::time::{Duration, Instant, SystemTime};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tracing::{debug, error, info, warn};
 use crate::error::NexaError;
+use crate::mcp::config::{BackendConfig, LoadBalancerConfig};
+use crate::mcp::metrics::MetricsCollector;
+
+/// Points on the consistent-hashing ring per unit of backend weight
+const VIRTUAL_NODES: u32 = 64;
+
+/// Built-in ways of picking a backend, selected by `load_balancer.strategy`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Strategy {
+    /// Each backend in turn
+    #[default]
+    RoundRobin,
+    /// The backend with the fewest open connections
+    LeastConnections,
+    /// Each backend in turn, as often as its weight
+    WeightedRoundRobin,
+    /// The same backend for the same key, such as an agent id, while the backends are unchanged
+    ConsistentHash,
+}
+
+impl Strategy {
+    /// A fresh instance of the strategy
+    pub fn build(self) -> Arc<dyn BalancingStrategy> {
+        match self {
+            Strategy::RoundRobin => Arc::new(RoundRobin::default()),
+            Strategy::LeastConnections => Arc::new(LeastConnections),
+            Strategy::WeightedRoundRobin => Arc::new(WeightedRoundRobin::default()),
+            Strategy::ConsistentHash => Arc::new(ConsistentHash::default()),
+        }
+    }
+}
+
+/// A backend as a strategy sees it when picking
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backend {
+    pub addr: SocketAddr,
+    pub weight: u32,
+    /// Connections handed out and not yet released
+    pub active: usize,
+    pub health: BackendHealth,
+}
+
+/// Whether a backend gets traffic
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackendHealth {
+    Healthy,
+    /// Failed; gets no traffic until its ejection ends
+    Ejected,
+    /// Ejection ended; one probe request decides whether it is healthy again
+    HalfOpen,
+}
+
+/// Failures and ejection of a backend
+#[derive(Debug, Default)]
+struct Ejection {
+    /// Consecutive failures
+    failures: u32,
+    /// When an ejected backend is probed next, or a probe is given up on
+    until: Option<Instant>,
+}
+
+/// Picks the backend for a request
+///
+/// Implement this for strategies beyond the built-in ones and install it with
+/// `LoadBalancer::set_strategy`.
+pub trait BalancingStrategy: Send + Sync + std::fmt::Debug {
+    /// Name the strategy's metrics are reported under
+    fn name(&self) -> &str;
+
+    /// Pick one of `backends`, which is never empty; `key` identifies the requester, if known
+    fn select(&self, backends: &[Backend], key: Option<&str>) -> SocketAddr;
+}
+
+#[derive(Debug, Default)]
+pub struct RoundRobin {
+    next: AtomicUsize,
+}
+
+impl BalancingStrategy for RoundRobin {
+    fn name(&self) -> &str {
+        "round_robin"
+    }
+
+    fn select(&self, backends: &[Backend], _key: Option<&str>) -> SocketAddr {
+        backends[self.next.fetch_add(1, Ordering::Relaxed) % backends.len()].addr
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct LeastConnections;
+
+impl BalancingStrategy for LeastConnections {
+    fn name(&self) -> &str {
+        "least_connections"
+    }
+
+    fn select(&self, backends: &[Backend], _key: Option<&str>) -> SocketAddr {
+        // Ties go to the first backend added
+        backends.iter().min_by_key(|b| b.active).map(|b| b.addr).unwrap_or(backends[0].addr)
+    }
+}
+
+/// Smooth weighted round-robin: a backend of weight 3 next to one of weight 1 is
+/// picked three times in every four, interleaved rather than in a burst
+#[derive(Debug, Default)]
+pub struct WeightedRoundRobin {
+    current: parking_lot::Mutex<HashMap<SocketAddr, i64>>,
+}
+
+impl BalancingStrategy for WeightedRoundRobin {
+    fn name(&self) -> &str {
+        "weighted_round_robin"
+    }
+
+    fn select(&self, backends: &[Backend], _key: Option<&str>) -> SocketAddr {
+        let mut current = self.current.lock();
+        current.retain(|addr, _| backends.iter().any(|b| b.addr == *addr));
+        let total: i64 = backends.iter().map(|b| b.weight as i64).sum();
+        let mut best: Option<(SocketAddr, i64)> = None;
+        for backend in backends {
+            let weight = current.entry(backend.addr).or_insert(0);
+            *weight += backend.weight as i64;
+            if best.is_none_or(|(_, w)| *weight > w) {
+                best = Some((backend.addr, *weight));
+            }
+        }
+        let (addr, _) = best.unwrap_or((backends[0].addr, 0));
+        if let Some(weight) = current.get_mut(&addr) {
+            *weight -= total;
+        }
+        addr
+    }
+}
+
+/// Hashes keys onto a ring of backends, so adding or removing a backend only
+/// moves the keys of its neighbours; requests without a key go round-robin
+#[derive(Debug, Default)]
+pub struct ConsistentHash {
+    ring: parking_lot::Mutex<Ring>,
+    fallback: RoundRobin,
+}
+
+#[derive(Debug, Default)]
+struct Ring {
+    /// Backends and weights the ring was built from
+    members: Vec<(SocketAddr, u32)>,
+    points: BTreeMap<u64, SocketAddr>,
+}
+
+impl BalancingStrategy for ConsistentHash {
+    fn name(&self) -> &str {
+        "consistent_hash"
+    }
+
+    fn select(&self, backends: &[Backend], key: Option<&str>) -> SocketAddr {
+        let Some(key) = key else { return self.fallback.select(backends, None) };
+        let mut ring = self.ring.lock();
+        let members: Vec<(SocketAddr, u32)> = backends.iter().map(|b| (b.addr, b.weight)).collect();
+        if ring.members != members {
+            let mut points = BTreeMap::new();
+            for &(addr, weight) in &members {
+                for replica in 0..VIRTUAL_NODES * weight.max(1) {
+                    points.insert(ring_hash(format!("{}-{}", addr, replica).as_bytes()), addr);
+                }
+            }
+            *ring = Ring { members, points };
+        }
+        let hash = ring_hash(key.as_bytes());
+        ring.points.range(hash..).next()
+            .or_else(|| ring.points.iter().next())
+            .map(|(_, addr)| *addr)
+            .unwrap_or(backends[0].addr)
+    }
+}
+
+/// 64-bit FNV-1a with the MurmurHash3 finalizer, so keys differing in their last
+/// characters spread over the whole ring; stable across processes unlike `DefaultHasher`
+fn ring_hash(bytes: &[u8]) -> u64 {
+    let mut hash = bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3));
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    hash ^ (hash >> 33)
+}
+
+/// How evenly a strategy spread its picks
+#[derive(Debug, Clone, Serialize)]
+pub struct StrategyMetrics {
+    pub strategy: String,
+    pub selections: u64,
+    /// Picks per backend
+    pub by_backend: HashMap<SocketAddr, u64>,
+    /// Coefficient of variation of picks per unit of weight across the backends;
+    /// 0 is a spread exactly in proportion to weight
+    pub imbalance: f64,
+}
+
+#[derive(Debug, Default)]
+struct Distribution {
+    by_backend: HashMap<SocketAddr, u64>,
+}
+
+impl Distribution {
+    fn metrics(&self, strategy: &str, backends: &[Backend]) -> StrategyMetrics {
+        let per_weight: Vec<f64> = backends.iter()
+            .map(|b| *self.by_backend.get(&b.addr).unwrap_or(&0) as f64 / b.weight.max(1) as f64)
+            .collect();
+        let mean = per_weight.iter().sum::<f64>() / per_weight.len().max(1) as f64;
+        let imbalance = if mean == 0.0 {
+            0.0
+        } else {
+            let variance = per_weight.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / per_weight.len() as f64;
+            variance.sqrt() / mean
+        };
+        StrategyMetrics {
+            strategy: strategy.to_string(),
+            selections: self.by_backend.values().sum(),
+            by_backend: self.by_backend.clone(),
+            imbalance,
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct ConnectionStats {
@@ -57,6 +285,15 @@ pub struct ConnectionPool {
 
 pub struct LoadBalancer {
     pools: Arc<RwLock<HashMap<SocketAddr, Arc<RwLock<ConnectionPool>>>>>,
+    /// Backends to balance across, in the order added
+    backends: Arc<parking_lot::RwLock<Vec<Backend>>>,
+    strategy: Arc<parking_lot::RwLock<Arc<dyn BalancingStrategy>>>,
+    /// Picks of each strategy used, by strategy name
+    distributions: Arc<parking_lot::Mutex<HashMap<String, Distribution>>>,
+    /// Locked after `backends`
+    ejections: Arc<parking_lot::Mutex<HashMap<SocketAddr, Ejection>>>,
+    eject_after: u32,
+    ejection: Duration,
     max_retries: usize,
     retry_delay: Duration,
     health_check_interval: Duration,
@@ -164,6 +401,12 @@ impl LoadBalancer {
     ) -> Self {
         Self {
             pools: Arc::new(RwLock::new(HashMap::new())),
+            backends: Arc::new(parking_lot::RwLock::new(Vec::new())),
+            strategy: Arc::new(parking_lot::RwLock::new(Strategy::default().build())),
+            distributions: Arc::new(parking_lot::Mutex::new(HashMap::new())),
+            ejections: Arc::new(parking_lot::Mutex::new(HashMap::new())),
+            eject_after: 3,
+            ejection: Duration::from_secs(30),
             max_retries,
             retry_delay,
             health_check_interval,
@@ -171,13 +414,198 @@ impl LoadBalancer {
         }
     }
 
+    /// Eject backends after `failures` consecutive failures, for `duration` before probing them
+    pub fn with_ejection(mut self, failures: u32, duration: Duration) -> Self {
+        self.eject_after = failures.max(1);
+        self.ejection = duration;
+        self
+    }
+
+    /// Load balancer with the strategy and backends of `config`
+    pub fn from_config(config: &LoadBalancerConfig) -> Self {
+        let balancer = Self::new(
+            config.max_retries,
+            Duration::from_millis(config.retry_delay_ms),
+            Duration::from_millis(config.health_check_interval_ms),
+            Duration::from_millis(config.connection_timeout_ms),
+        ).with_ejection(config.eject_after_failures, Duration::from_secs(config.ejection_secs));
+        balancer.set_strategy(config.strategy.build());
+        for BackendConfig { addr, weight } in &config.backends {
+            balancer.add_backend(*addr, *weight);
+        }
+        balancer
+    }
+
+    /// Replace the strategy backends are picked with; metrics of earlier strategies are kept
+    pub fn set_strategy(&self, strategy: Arc<dyn BalancingStrategy>) {
+        *self.strategy.write() = strategy;
+    }
+
+    /// Add a backend, or change its weight; a weight of 0 counts as 1
+    pub fn add_backend(&self, addr: SocketAddr, weight: u32) {
+        let mut backends = self.backends.write();
+        match backends.iter_mut().find(|b| b.addr == addr) {
+            Some(backend) => backend.weight = weight.max(1),
+            None => backends.push(Backend { addr, weight: weight.max(1), active: 0, health: BackendHealth::Healthy }),
+        }
+    }
+
+    pub fn remove_backend(&self, addr: SocketAddr) {
+        self.backends.write().retain(|b| b.addr != addr);
+        self.ejections.lock().remove(&addr);
+    }
+
+    pub fn backends(&self) -> Vec<Backend> {
+        self.backends.read().clone()
+    }
+
+    /// Pick a healthy backend with the current strategy; `key`, such as an agent id,
+    /// keeps requests on one backend under consistent hashing
+    ///
+    /// An ejected backend whose ejection ended is picked first, as the probe deciding
+    /// whether it gets traffic again.
+    pub fn select(&self, key: Option<&str>) -> Result<SocketAddr, NexaError> {
+        if let Some(addr) = self.next_probe() {
+            return Ok(addr);
+        }
+        let backends: Vec<Backend> = self.backends.read().iter()
+            .filter(|b| b.health == BackendHealth::Healthy)
+            .cloned()
+            .collect();
+        if backends.is_empty() {
+            return Err(NexaError::unavailable("No healthy backends to balance across"));
+        }
+        let strategy = self.strategy.read().clone();
+        let addr = strategy.select(&backends, key);
+        let mut distributions = self.distributions.lock();
+        let distribution = distributions.entry(strategy.name().to_string()).or_default();
+        *distribution.by_backend.entry(addr).or_insert(0) += 1;
+        Ok(addr)
+    }
+
+    /// Pick a backend and take a connection to it; release it with `release_connection`
+    ///
+    /// The outcome counts towards the backend's health.
+    pub async fn connect(&self, key: Option<&str>) -> Result<(SocketAddr, TcpStream), NexaError> {
+        let addr = self.select(key)?;
+        match self.get_connection(addr).await {
+            Ok(stream) => {
+                self.record_success(addr);
+                Ok((addr, stream))
+            }
+            Err(e) => {
+                self.record_failure(addr);
+                Err(e)
+            }
+        }
+    }
+
+    /// Move an ejected backend due for a probe to half-open, returning it
+    fn next_probe(&self) -> Option<SocketAddr> {
+        let now = Instant::now();
+        let mut backends = self.backends.write();
+        let mut ejections = self.ejections.lock();
+        let backend = backends.iter_mut().find(|b| {
+            b.health != BackendHealth::Healthy
+                && ejections.get(&b.addr).and_then(|e| e.until).is_none_or(|until| until <= now)
+        })?;
+        backend.health = BackendHealth::HalfOpen;
+        // A probe not reported back within the ejection is tried again
+        ejections.entry(backend.addr).or_default().until = Some(now + self.ejection);
+        debug!("Probing ejected backend {}", backend.addr);
+        Some(backend.addr)
+    }
+
+    /// Count a successful request, restoring a half-open backend
+    pub fn record_success(&self, addr: SocketAddr) {
+        let mut backends = self.backends.write();
+        let mut ejections = self.ejections.lock();
+        let Some(backend) = backends.iter_mut().find(|b| b.addr == addr) else { return };
+        if backend.health == BackendHealth::HalfOpen {
+            info!("Backend {} recovered", addr);
+            backend.health = BackendHealth::Healthy;
+        }
+        if backend.health == BackendHealth::Healthy {
+            ejections.remove(&addr);
+        }
+    }
+
+    /// Count a failed request, ejecting the backend after too many in a row or a failed probe
+    pub fn record_failure(&self, addr: SocketAddr) {
+        let mut backends = self.backends.write();
+        let mut ejections = self.ejections.lock();
+        let Some(backend) = backends.iter_mut().find(|b| b.addr == addr) else { return };
+        let ejection = ejections.entry(addr).or_default();
+        ejection.failures += 1;
+        if backend.health == BackendHealth::HalfOpen || ejection.failures >= self.eject_after {
+            Self::eject(backend, ejection, self.ejection);
+        }
+    }
+
+    fn eject(backend: &mut Backend, ejection: &mut Ejection, duration: Duration) {
+        if backend.health != BackendHealth::Ejected {
+            warn!("Ejecting backend {} after {} failures", backend.addr, ejection.failures);
+        }
+        backend.health = BackendHealth::Ejected;
+        ejection.until = Some(Instant::now() + duration);
+    }
+
+    /// Follow the node and agent health reported to `metrics`
+    ///
+    /// Unhealthy targets are ejected at once; an ejected target reported healthy is
+    /// probed with the next request.
+    pub fn watch_health(&self, metrics: &MetricsCollector) -> tokio::task::JoinHandle<()> {
+        let mut reports = metrics.subscribe_health();
+        let balancer = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let report = match reports.recv().await {
+                    Ok(report) => report,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("Load balancer skipped {} health reports", skipped);
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                let mut backends = balancer.backends.write();
+                let mut ejections = balancer.ejections.lock();
+                let Some(backend) = backends.iter_mut().find(|b| b.addr == report.addr) else { continue };
+                let ejection = ejections.entry(report.addr).or_default();
+                if !report.healthy {
+                    Self::eject(backend, ejection, balancer.ejection);
+                } else if backend.health == BackendHealth::Ejected {
+                    ejection.until = Some(Instant::now());
+                }
+            }
+        })
+    }
+
+    /// Distribution of each strategy used, over the current backends
+    pub fn metrics(&self) -> Vec<StrategyMetrics> {
+        let backends = self.backends.read().clone();
+        let mut metrics: Vec<StrategyMetrics> = self.distributions.lock().iter()
+            .map(|(name, distribution)| distribution.metrics(name, &backends))
+            .collect();
+        metrics.sort_by(|a, b| a.strategy.cmp(&b.strategy));
+        metrics
+    }
+
+    fn track_active(&self, addr: SocketAddr, opened: bool) {
+        if let Some(backend) = self.backends.write().iter_mut().find(|b| b.addr == addr) {
+            backend.active = if opened { backend.active + 1 } else { backend.active.saturating_sub(1) };
+        }
+    }
+
     pub async fn get_connection(&self, addr: SocketAddr) -> Result<TcpStream, NexaError> {
         let mut retries = 0;
         let mut last_error = None;
 
         while retries < self.max_retries {
             match self.try_get_connection(addr).await {
-                Ok(stream) => return Ok(stream),
+                Ok(stream) => {
+                    self.track_active(addr, true);
+                    return Ok(stream);
+                }
                 Err(e) => {
                     last_error = Some(e);
                     retries += 1;
@@ -216,6 +644,7 @@ impl LoadBalancer {
     }
 
     pub async fn release_connection(&self, addr: SocketAddr, stream: TcpStream) {
+        self.track_active(addr, false);
         if let Some(pool) = self.pools.read().await.get(&addr) {
             let mut pool = pool.write().await;
             pool.release(addr, stream).await;
@@ -239,6 +668,7 @@ impl LoadBalancer {
             let mut pool = pool.write().await;
             if let Err(e) = self.check_pool_health(&mut pool, *addr).await {
                 error!("Health check failed for {}: {}", addr, e);
+                self.record_failure(*addr);
             }
         }
     }
@@ -265,10 +695,116 @@ impl Clone for LoadBalancer {
     fn clone(&self) -> Self {
         Self {
             pools: self.pools.clone(),
+            backends: self.backends.clone(),
+            strategy: self.strategy.clone(),
+            distributions: self.distributions.clone(),
+            ejections: self.ejections.clone(),
+            eject_after: self.eject_after,
+            ejection: self.ejection,
             max_retries: self.max_retries,
             retry_delay: self.retry_delay,
             health_check_interval: self.health_check_interval,
             connection_timeout: self.connection_timeout,
         }
     }
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn balancer(strategy: Strategy, weights: &[u32]) -> (LoadBalancer, Vec<SocketAddr>) {
+        let config = LoadBalancerConfig {
+            strategy,
+            backends: weights.iter().enumerate()
+                .map(|(i, weight)| BackendConfig { addr: format!("127.0.0.1:{}", 9000 + i).parse().unwrap(), weight: *weight })
+                .collect(),
+            ..Default::default()
+        };
+        let balancer = LoadBalancer::from_config(&config);
+        let addrs = config.backends.iter().map(|b| b.addr).collect();
+        (balancer, addrs)
+    }
+
+    #[test]
+    fn test_strategies() {
+        let (lb, addrs) = balancer(Strategy::RoundRobin, &[1, 1, 1]);
+        let picks: Vec<_> = (0..6).map(|_| lb.select(None).unwrap()).collect();
+        assert_eq!(picks[..3], addrs[..]);
+        assert_eq!(picks[3..], addrs[..]);
+        assert_eq!(lb.metrics()[0].imbalance, 0.0);
+
+        let (lb, addrs) = balancer(Strategy::WeightedRoundRobin, &[3, 1]);
+        let picks: Vec<_> = (0..4).map(|_| lb.select(None).unwrap()).collect();
+        assert_eq!(picks, vec![addrs[0], addrs[0], addrs[1], addrs[0]]);
+        assert_eq!(lb.metrics()[0].imbalance, 0.0);
+
+        let (lb, addrs) = balancer(Strategy::LeastConnections, &[1, 1]);
+        lb.track_active(addrs[0], true);
+        assert_eq!(lb.select(None).unwrap(), addrs[1]);
+        lb.track_active(addrs[1], true);
+        lb.track_active(addrs[1], true);
+        assert_eq!(lb.select(None).unwrap(), addrs[0]);
+    }
+
+    #[tokio::test]
+    async fn test_ejection() {
+        let (lb, addrs) = balancer(Strategy::RoundRobin, &[1, 1]);
+        let lb = lb.with_ejection(2, Duration::from_millis(50));
+        lb.record_failure(addrs[0]);
+        assert_eq!(lb.backends()[0].health, BackendHealth::Healthy);
+        lb.record_failure(addrs[0]);
+        assert_eq!(lb.backends()[0].health, BackendHealth::Ejected);
+        assert!((0..4).all(|_| lb.select(None).unwrap() == addrs[1]));
+
+        // Once the ejection ends one request probes the backend, and its failure ejects it again
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(lb.select(None).unwrap(), addrs[0]);
+        assert_eq!(lb.backends()[0].health, BackendHealth::HalfOpen);
+        assert_eq!(lb.select(None).unwrap(), addrs[1]);
+        lb.record_failure(addrs[0]);
+        assert_eq!(lb.backends()[0].health, BackendHealth::Ejected);
+
+        // A successful probe restores it
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(lb.select(None).unwrap(), addrs[0]);
+        lb.record_success(addrs[0]);
+        assert_eq!(lb.backends()[0].health, BackendHealth::Healthy);
+
+        // Health reported to the metrics collector ejects without waiting for failures
+        let metrics = MetricsCollector::new();
+        let watch = lb.watch_health(&metrics);
+        metrics.record_target_health(addrs[1], false);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(lb.backends()[1].health, BackendHealth::Ejected);
+        assert!((0..4).all(|_| lb.select(None).unwrap() == addrs[0]));
+        metrics.record_target_health(addrs[1], true);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(lb.select(None).unwrap(), addrs[1]);
+        watch.abort();
+    }
+
+    #[test]
+    fn test_consistent_hash() {
+        let (lb, addrs) = balancer(Strategy::ConsistentHash, &[1, 1, 1, 1]);
+        let agents: Vec<String> = (0..200).map(|i| format!("agent-{}", i)).collect();
+        let before: Vec<_> = agents.iter().map(|id| lb.select(Some(id)).unwrap()).collect();
+        // Sticky per agent
+        assert!(agents.iter().zip(&before).all(|(id, addr)| lb.select(Some(id)).unwrap() == *addr));
+        assert!(addrs.iter().all(|addr| before.contains(addr)));
+
+        // Removing a backend only moves the agents it had
+        lb.remove_backend(addrs[3]);
+        for (id, addr) in agents.iter().zip(&before) {
+            let after = lb.select(Some(id)).unwrap();
+            if *addr != addrs[3] {
+                assert_eq!(after, *addr);
+            }
+        }
+
+        let metrics = lb.metrics();
+        assert_eq!(metrics[0].strategy, "consistent_hash");
+        assert_eq!(metrics[0].selections, 600);
+        assert!(LoadBalancer::new(1, Duration::ZERO, Duration::ZERO, Duration::ZERO).select(None).is_err());
+    }
+}
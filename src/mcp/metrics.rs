@@ -1,6 +1,7 @@
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{broadcast, RwLock};
 use std::collections::HashMap;
+use std::net::SocketAddr;
 use std::time::{Duration, SystemTime, Instant};
 use crate::mcp::buffer::Priority;
 use serde::Serialize;
@@ -18,6 +19,8 @@ pub struct MessageMetrics {
     pub failed_count: u64,
     /// Retry count
     pub retry_count: u64,
+    /// Publishes that found their queue full
+    pub saturation_count: u64,
     /// Current queue sizes by priority
     pub queue_sizes: HashMap<Priority, usize>,
     /// Messages processed per second
@@ -45,6 +48,7 @@ impl Default for MessageMetrics {
             avg_processing_time,
             failed_count: 0,
             retry_count: 0,
+            saturation_count: 0,
             queue_sizes,
             throughput: 0.0,
             last_updated: SystemTime::now(),
@@ -52,6 +56,17 @@ impl Default for MessageMetrics {
     }
 }
 
+/// Health of a node or agent that traffic is balanced to
+#[derive(Debug, Clone, Serialize)]
+pub struct TargetHealth {
+    pub addr: SocketAddr,
+    pub healthy: bool,
+    pub timestamp: SystemTime,
+}
+
+/// Health reports buffered for each subscriber
+const HEALTH_CAPACITY: usize = 256;
+
 /// Metrics collector for message processing
 #[derive(Debug)]
 pub struct MetricsCollector {
@@ -63,6 +78,8 @@ pub struct MetricsCollector {
     last_throughput_calc: Arc<RwLock<Instant>>,
     /// Messages processed since last calculation
     messages_since_last_calc: Arc<RwLock<u64>>,
+    /// Health reports of balanced targets
+    health: broadcast::Sender<TargetHealth>,
 }
 
 impl Default for MetricsCollector {
@@ -78,9 +95,20 @@ impl MetricsCollector {
             processing_times: Arc::new(RwLock::new(HashMap::new())),
             last_throughput_calc: Arc::new(RwLock::new(Instant::now())),
             messages_since_last_calc: Arc::new(RwLock::new(0)),
+            health: broadcast::channel(HEALTH_CAPACITY).0,
         }
     }
 
+    /// Report the health of a node or agent to load balancers subscribed with `subscribe_health`
+    pub fn record_target_health(&self, addr: SocketAddr, healthy: bool) {
+        let _ = self.health.send(TargetHealth { addr, healthy, timestamp: SystemTime::now() });
+    }
+
+    /// Receive every health report made from now on
+    pub fn subscribe_health(&self) -> broadcast::Receiver<TargetHealth> {
+        self.health.subscribe()
+    }
+
     /// Record a successful message processing
     pub async fn record_success(&self, priority: Priority, processing_time: Duration) {
         let mut metrics = self.metrics.write().await;
@@ -131,6 +159,13 @@ impl MetricsCollector {
         metrics.last_updated = SystemTime::now();
     }
 
+    /// Record a publish to a full queue
+    pub async fn record_saturation(&self) {
+        let mut metrics = self.metrics.write().await;
+        metrics.saturation_count += 1;
+        metrics.last_updated = SystemTime::now();
+    }
+
     /// Update queue sizes
     pub async fn update_queue_sizes(&self, sizes: HashMap<Priority, usize>) {
         let mut metrics = self.metrics.write().await;
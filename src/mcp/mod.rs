@@ -10,43 +10,142 @@ pub mod registry;
 pub mod server;
 pub mod protocol;
 pub mod tokens;
+#[cfg(feature = "cluster")]
 pub mod cluster;
 pub mod config;
 pub mod loadbalancer;
 pub mod buffer;
 pub mod processor;
+#[cfg(feature = "cluster")]
 pub mod cluster_processor;
 pub mod metrics;
+pub mod jsonrpc;
+pub mod stdio;
+pub mod client;
+pub mod handshake;
+pub mod codec;
+pub mod trace;
 
 use std::path::PathBuf;
 use std::time::Duration;
 use serde::{Serialize, Deserialize};
 use uuid::Uuid;
-use crate::agent::{Agent, Task, AgentStatus};
+use crate::agent::{Agent, Task, AgentStatus, TaskStatus};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::collections::HashMap;
+use crate::config::{Config, ConfigService};
+use crate::api::ApiServer;
+use crate::backup::BackupManager;
+use crate::discovery::Discovery;
+use crate::events::{EventBus, EventKind, JsonlSink, WebhookSink, EVENT_LOG_FILE};
+use crate::tools::ToolRegistry;
+use crate::kubernetes::LeaseElector;
+#[cfg(feature = "plugins")]
+use crate::plugins::PluginHost;
+#[cfg(feature = "scripting")]
+use crate::scripting::{ScriptRunner, ScriptTool};
+#[cfg(feature = "containers")]
+use crate::containers::{ContainerRunner, ContainerTool};
 use crate::error::NexaError;
+use crate::llm::{LLMClient, LLMSupervisor};
+use crate::recovery::{StateJournal, STATE_FILE};
 use crate::mcp::server::{Server, ServerState};
 use crate::monitoring::{
     MonitoringSystem, SystemMetrics, SystemHealth, SystemAlert, AlertLevel
 };
 use crate::memory::{MemoryManager, MemoryStats, ResourceType};
 use tokio::sync::{RwLock, broadcast};
-use tracing::{debug, error, info};
-use chrono::Utc;
+use tracing::{debug, error, info, warn};
+use chrono::{DateTime, Utc};
 use crate::tokens::{TokenManager, ModelType, TokenUsage};
-use crate::mcp::buffer::{MessageBuffer, BufferConfig, Priority, BufferedMessage};
+use crate::mcp::buffer::{MessageBuffer, BufferConfig, Priority, BufferedMessage, Subscription, TopicFilter};
 use crate::mcp::processor::{MessageProcessor, ProcessorConfig};
+use crate::mcp::registry::RegistryEvent;
+use crate::mcp::trace::TraceContext;
+use crate::mcp::codec::Encoding;
+#[cfg(feature = "cluster")]
 use crate::mcp::cluster_processor::{ClusterProcessor, ClusterProcessorConfig};
 use crate::mcp::metrics::{MetricsCollector, AlertChecker, AlertThresholds};
+#[cfg(feature = "cluster")]
 use std::net::SocketAddr;
 
+#[cfg(feature = "cluster")]
 pub use cluster::{ClusterManager, ClusterConfig, Node, NodeRole};
 
+/// Topic of the buffered messages carrying scheduled task assignments
+pub const ASSIGNMENT_TOPIC: &str = "tasks.assignment";
+
+/// Wait before retrying a scheduled assignment the server could not take
+const ASSIGNMENT_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// How often a drain checks on the work in flight
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Stage of a drain
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DrainPhase {
+    /// Waiting for the work in flight to finish
+    Draining,
+    /// No work is left; the server can be stopped
+    Drained,
+    /// The timeout passed with work still in flight
+    TimedOut,
+}
+
+/// Progress of draining the server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DrainStatus {
+    pub phase: DrainPhase,
+    pub started_at: DateTime<Utc>,
+    /// Unfinished tasks assigned to agents connected to this server
+    pub in_flight_tasks: usize,
+    pub active_connections: u32,
+}
+
+/// Whether one part of the server is up, as reported by the probes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ComponentStatus {
+    pub name: String,
+    pub ready: bool,
+    /// Why the component is not ready
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl ComponentStatus {
+    fn new(name: &str, problem: Option<String>) -> Self {
+        Self { name: name.to_string(), ready: problem.is_none(), detail: problem }
+    }
+}
+
+/// File written and removed in the runtime directory to check it is writable
+const STORAGE_PROBE_FILE: &str = ".probe";
+
 #[derive(Debug, Serialize, Deserialize)]
 pub enum MCPMessage {
+    /// Opens a connection, offering the protocol versions and capabilities of an agent
+    ClientHello {
+        versions: Vec<u32>,
+        #[serde(default)]
+        capabilities: Vec<String>,
+        /// Message encodings the agent supports, by preference
+        #[serde(default)]
+        encodings: Vec<String>,
+    },
+    /// Answers `ClientHello` with the version, capabilities and encoding agreed on
+    ServerHello {
+        version: u32,
+        capabilities: Vec<String>,
+        #[serde(default)]
+        encoding: Encoding,
+    },
     RegisterAgent {
         agent: Agent,
+        /// Auth token issued to the agent by the registry
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        token: Option<String>,
     },
     DeregisterAgent {
         agent_id: String,
@@ -54,28 +153,56 @@ pub enum MCPMessage {
     TaskAssignment {
         task: Task,
         agent_id: String,
+        /// Trace of the request that made the assignment
+        #[serde(flatten)]
+        trace: Option<TraceContext>,
     },
     StatusUpdate {
         agent_id: String,
         status: AgentStatus,
+        /// Trace of the assignment the agent is working on, if any
+        #[serde(flatten)]
+        trace: Option<TraceContext>,
     },
+    /// Finds agents by capability, or by an expression like `rust & review | python`
     AgentQuery {
         capability: String,
     },
     AgentResponse {
         agents: Vec<Agent>,
     },
+    /// Checks that an agent is alive; agents that negotiated heartbeats answer with `Pong`
+    Ping {
+        sent_at: DateTime<Utc>,
+    },
+    Pong {
+        agent_id: String,
+    },
     Error {
         code: u32,
         message: String,
     },
 }
 
+impl MCPMessage {
+    /// Trace the message belongs to, if it carries one
+    pub fn trace(&self) -> Option<&TraceContext> {
+        match self {
+            Self::TaskAssignment { trace, .. } | Self::StatusUpdate { trace, .. } => trace.as_ref(),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct MCPConnection {
     pub id: String,
     pub agent: Option<Agent>,
     pub active_connections: Arc<RwLock<u32>>,
+    /// Outcome of the connection's handshake, if it made one
+    pub handshake: Arc<RwLock<Option<handshake::Handshake>>>,
+    /// Encodings the transport of the connection can carry
+    pub encodings: &'static [Encoding],
 }
 
 // Explicitly implement Send and Sync since all fields are Send + Sync
@@ -88,12 +215,30 @@ impl MCPConnection {
             id: Uuid::new_v4().to_string(),
             agent: None,
             active_connections: Arc::new(RwLock::new(0)),
+            handshake: Arc::new(RwLock::new(None)),
+            encodings: Encoding::TEXT,
         }
     }
 
+    /// Let the connection agree on any of `encodings` in its handshake
+    pub fn with_encodings(mut self, encodings: &'static [Encoding]) -> Self {
+        self.encodings = encodings;
+        self
+    }
+
+    /// Protocol version of the connection; agents that made no handshake speak the oldest
+    pub async fn protocol_version(&self) -> u32 {
+        self.handshake.read().await.as_ref().map_or(handshake::MIN_PROTOCOL_VERSION, |h| h.version)
+    }
+
+    /// Encoding of the connection's messages; JSON until a handshake agrees on another
+    pub async fn encoding(&self) -> Encoding {
+        self.handshake.read().await.as_ref().map_or(Encoding::Json, |h| h.encoding)
+    }
+
     pub async fn handle_message(&self, message: MCPMessage) -> Result<serde_json::Value, NexaError> {
         match message {
-            MCPMessage::StatusUpdate { agent_id, status } => {
+            MCPMessage::StatusUpdate { agent_id, status, .. } => {
                 info!("Status update from {}: {:?}", agent_id, status);
                 Ok(serde_json::json!({
                     "code": 200,
@@ -123,9 +268,39 @@ pub struct ServerControl {
     server_handle: Arc<RwLock<Option<tokio::task::JoinHandle<Result<(), NexaError>>>>>,
     message_buffer: Arc<MessageBuffer>,
     message_processor: Arc<RwLock<Option<MessageProcessor>>>,
+    #[cfg(feature = "cluster")]
     cluster_processor: Arc<RwLock<Option<ClusterProcessor>>>,
+    #[cfg(feature = "cluster")]
+    quorum_handle: Arc<RwLock<Option<tokio::task::JoinHandle<()>>>>,
+    /// Whether the node is inside cluster quorum; always true without clustering
+    quorum: Arc<AtomicBool>,
     metrics_collector: Arc<MetricsCollector>,
     alert_checker: Arc<AlertChecker>,
+    config_service: ConfigService,
+    journal_handle: Arc<RwLock<Option<tokio::task::JoinHandle<()>>>>,
+    llm_supervisor: Arc<RwLock<Option<LLMSupervisor>>>,
+    llm_tasks: Arc<RwLock<Vec<tokio::task::JoinHandle<()>>>>,
+    /// Subsystems following configuration changes while the server runs
+    config_watches: Arc<RwLock<Vec<tokio::task::JoinHandle<()>>>>,
+    api_handle: Arc<RwLock<Option<tokio::task::JoinHandle<()>>>>,
+    backup_handle: Arc<RwLock<Option<tokio::task::JoinHandle<()>>>>,
+    dispatch_handle: Arc<RwLock<Option<tokio::task::JoinHandle<()>>>>,
+    heartbeat_handle: Arc<RwLock<Option<tokio::task::JoinHandle<()>>>>,
+    tools: ToolRegistry,
+    mcp_tools: Arc<RwLock<Vec<String>>>,
+    events: EventBus,
+    event_sinks: Arc<RwLock<Vec<tokio::task::JoinHandle<()>>>>,
+    leader: Arc<AtomicBool>,
+    shutting_down: Arc<AtomicBool>,
+    /// Whether `start` has completed, until the server is stopped
+    started: Arc<AtomicBool>,
+    /// Progress of the current or last drain, until resumed
+    drain: Arc<RwLock<Option<DrainStatus>>>,
+    drain_handle: Arc<RwLock<Option<tokio::task::JoinHandle<()>>>>,
+    elector: Arc<RwLock<Option<LeaseElector>>>,
+    election_handle: Arc<RwLock<Option<tokio::task::JoinHandle<()>>>>,
+    #[cfg(feature = "plugins")]
+    plugin_host: Arc<RwLock<Option<PluginHost>>>,
     pid_file: PathBuf,
     socket_path: PathBuf,
 }
@@ -142,9 +317,35 @@ impl Clone for ServerControl {
             server_handle: self.server_handle.clone(),
             message_buffer: self.message_buffer.clone(),
             message_processor: self.message_processor.clone(),
+            #[cfg(feature = "cluster")]
             cluster_processor: self.cluster_processor.clone(),
+            #[cfg(feature = "cluster")]
+            quorum_handle: self.quorum_handle.clone(),
+            quorum: self.quorum.clone(),
             metrics_collector: self.metrics_collector.clone(),
             alert_checker: self.alert_checker.clone(),
+            config_service: self.config_service.clone(),
+            journal_handle: self.journal_handle.clone(),
+            llm_supervisor: self.llm_supervisor.clone(),
+            llm_tasks: self.llm_tasks.clone(),
+            config_watches: self.config_watches.clone(),
+            api_handle: self.api_handle.clone(),
+            backup_handle: self.backup_handle.clone(),
+            dispatch_handle: self.dispatch_handle.clone(),
+            heartbeat_handle: self.heartbeat_handle.clone(),
+            tools: self.tools.clone(),
+            mcp_tools: self.mcp_tools.clone(),
+            events: self.events.clone(),
+            event_sinks: self.event_sinks.clone(),
+            leader: self.leader.clone(),
+            shutting_down: self.shutting_down.clone(),
+            started: self.started.clone(),
+            drain: self.drain.clone(),
+            drain_handle: self.drain_handle.clone(),
+            elector: self.elector.clone(),
+            election_handle: self.election_handle.clone(),
+            #[cfg(feature = "plugins")]
+            plugin_host: self.plugin_host.clone(),
             pid_file: self.pid_file.clone(),
             socket_path: self.socket_path.clone(),
         }
@@ -156,19 +357,22 @@ impl ServerControl {
         let memory_manager = Arc::new(MemoryManager::new());
         let token_manager = Arc::new(TokenManager::new(memory_manager.clone()));
         let monitoring = Arc::new(MonitoringSystem::new(memory_manager.clone(), token_manager.clone()));
-        let message_buffer = Arc::new(MessageBuffer::new(BufferConfig::default()));
-        let message_processor = Arc::new(RwLock::new(None));
-        let cluster_processor = Arc::new(RwLock::new(None));
         let metrics_collector = Arc::new(MetricsCollector::new());
+        let message_buffer = Arc::new(MessageBuffer::new(BufferConfig::default()).with_metrics(metrics_collector.clone()));
+        let message_processor = Arc::new(RwLock::new(None));
         let alert_checker = Arc::new(AlertChecker::new(
             AlertThresholds::default(),
             metrics_collector.clone(),
         ));
+        let server = Arc::new(Server::new(pid_file.clone(), socket_path.clone()));
+
+        // Subsystems follow configuration changes once started
+        let config_service = ConfigService::new(Config::default());
 
         Self {
-            pid_file: pid_file.clone(),
-            socket_path: socket_path.clone(),
-            server: Arc::new(Server::new(pid_file, socket_path)),
+            pid_file,
+            socket_path,
+            server,
             server_handle: Arc::new(RwLock::new(None)),
             registry: registry::AgentRegistry::new(),
             protocol: protocol::ProtocolHandler::new(),
@@ -177,10 +381,409 @@ impl ServerControl {
             monitoring,
             message_buffer,
             message_processor,
-            cluster_processor,
+            #[cfg(feature = "cluster")]
+            cluster_processor: Arc::new(RwLock::new(None)),
+            #[cfg(feature = "cluster")]
+            quorum_handle: Arc::new(RwLock::new(None)),
+            quorum: Arc::new(AtomicBool::new(true)),
             metrics_collector,
             alert_checker,
+            config_service,
+            journal_handle: Arc::new(RwLock::new(None)),
+            llm_supervisor: Arc::new(RwLock::new(None)),
+            llm_tasks: Arc::new(RwLock::new(Vec::new())),
+            config_watches: Arc::new(RwLock::new(Vec::new())),
+            api_handle: Arc::new(RwLock::new(None)),
+            backup_handle: Arc::new(RwLock::new(None)),
+            dispatch_handle: Arc::new(RwLock::new(None)),
+            heartbeat_handle: Arc::new(RwLock::new(None)),
+            tools: ToolRegistry::new(),
+            mcp_tools: Arc::new(RwLock::new(Vec::new())),
+            events: EventBus::new(),
+            event_sinks: Arc::new(RwLock::new(Vec::new())),
+            leader: Arc::new(AtomicBool::new(true)),
+            shutting_down: Arc::new(AtomicBool::new(false)),
+            started: Arc::new(AtomicBool::new(false)),
+            drain: Arc::new(RwLock::new(None)),
+            drain_handle: Arc::new(RwLock::new(None)),
+            elector: Arc::new(RwLock::new(None)),
+            election_handle: Arc::new(RwLock::new(None)),
+            #[cfg(feature = "plugins")]
+            plugin_host: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// Get the directory holding the PID file and other runtime files
+    pub fn runtime_dir(&self) -> PathBuf {
+        self.pid_file.parent().map(PathBuf::from).unwrap_or_default()
+    }
+
+    /// Bus carrying lifecycle events to subscribers and sinks
+    pub fn events(&self) -> &EventBus {
+        &self.events
+    }
+
+    /// Get the path of the runtime state journal
+    pub fn state_file(&self) -> PathBuf {
+        self.runtime_dir().join(STATE_FILE)
+    }
+
+    /// Get the configuration service subsystems are subscribed to
+    pub fn config_service(&self) -> &ConfigService {
+        &self.config_service
+    }
+
+    /// Whether this replica does leader-only work; always true outside Kubernetes mode
+    pub fn is_leader(&self) -> bool {
+        self.leader.load(Ordering::SeqCst)
+    }
+
+    /// Whether this node is inside cluster quorum and may assign tasks
+    ///
+    /// A node outside quorum is read-only until it sees enough members again.
+    pub fn has_quorum(&self) -> bool {
+        self.quorum.load(Ordering::SeqCst)
+    }
+
+    fn ensure_quorum(&self) -> Result<(), NexaError> {
+        if self.has_quorum() {
+            Ok(())
+        } else {
+            Err(NexaError::unavailable("This node is outside cluster quorum and read-only"))
+        }
+    }
+
+    /// Refuse new work outside quorum and while draining
+    async fn ensure_accepting(&self) -> Result<(), NexaError> {
+        self.ensure_quorum()?;
+        if self.server.get_state().await == ServerState::Maintenance {
+            return Err(NexaError::unavailable("The server is draining and takes no new task assignments"));
+        }
+        Ok(())
+    }
+
+    /// Drain the server ahead of maintenance, such as a rolling upgrade
+    ///
+    /// New connections and task assignments are refused from now on, and the server
+    /// reports not ready. Returns at once; `drain_status` follows the tasks of the
+    /// connected agents until they finish, or `timeout` passes. Draining again
+    /// returns the drain under way.
+    pub async fn drain(&self, timeout: Duration) -> Result<DrainStatus, NexaError> {
+        self.server.enter_maintenance().await?;
+        let mut drain = self.drain.write().await;
+        if let Some(status) = drain.as_ref().filter(|status| status.phase == DrainPhase::Draining) {
+            return Ok(status.clone());
+        }
+        let status = DrainStatus {
+            phase: DrainPhase::Draining,
+            started_at: Utc::now(),
+            in_flight_tasks: self.in_flight_tasks().await,
+            active_connections: self.server.get_active_connections().await,
+        };
+        info!("Draining {} tasks in flight, within {:?}", status.in_flight_tasks, timeout);
+        *drain = Some(status.clone());
+        let server = self.clone();
+        *self.drain_handle.write().await = Some(tokio::spawn(async move { server.watch_drain(timeout).await }));
+        Ok(status)
+    }
+
+    /// Progress of the current or last drain, if the server has not resumed since
+    pub async fn drain_status(&self) -> Option<DrainStatus> {
+        self.drain.read().await.clone()
+    }
+
+    /// Accept connections and task assignments again, ending any drain
+    ///
+    /// Returns the drain that was ended, if any.
+    pub async fn resume(&self) -> Result<Option<DrainStatus>, NexaError> {
+        if let Some(handle) = self.drain_handle.write().await.take() {
+            handle.abort();
+        }
+        self.server.resume().await?;
+        Ok(self.drain.write().await.take())
+    }
+
+    /// Unfinished tasks assigned to agents connected to this server
+    async fn in_flight_tasks(&self) -> usize {
+        let connected = self.server.connected_agents().await;
+        self.registry.list_tasks().await.unwrap_or_default().iter()
+            .filter(|task| matches!(task.status, TaskStatus::Pending | TaskStatus::InProgress))
+            .filter(|task| task.assigned_agent.as_ref().is_some_and(|agent| connected.contains(agent)))
+            .count()
+    }
+
+    /// Follow a drain until nothing is in flight or `timeout` passes
+    async fn watch_drain(&self, timeout: Duration) {
+        let deadline = tokio::time::Instant::now() + timeout;
+        let mut ticker = tokio::time::interval(DRAIN_POLL_INTERVAL);
+        loop {
+            ticker.tick().await;
+            let in_flight = self.in_flight_tasks().await;
+            let active_connections = self.server.get_active_connections().await;
+            let mut drain = self.drain.write().await;
+            let Some(status) = drain.as_mut() else { return };
+            if in_flight != status.in_flight_tasks {
+                info!("Draining: {} tasks in flight", in_flight);
+            }
+            status.in_flight_tasks = in_flight;
+            status.active_connections = active_connections;
+            if in_flight == 0 {
+                status.phase = DrainPhase::Drained;
+                info!("Drained; {} connections still open", active_connections);
+                return;
+            }
+            if tokio::time::Instant::now() >= deadline {
+                status.phase = DrainPhase::TimedOut;
+                warn!("Drain timed out after {:?} with {} tasks in flight", timeout, in_flight);
+                return;
+            }
+        }
+    }
+
+    /// Track the quorum of a cluster manager, alerting as it is lost and regained
+    #[cfg(feature = "cluster")]
+    async fn spawn_quorum_watch(&self, manager: &ClusterManager) -> tokio::task::JoinHandle<()> {
+        let mut messages = manager.subscribe();
+        self.quorum.store(manager.has_quorum().await, Ordering::SeqCst);
+        let server = self.clone();
+        tokio::spawn(async move {
+            loop {
+                match messages.recv().await {
+                    Ok(cluster::ClusterMessage::QuorumChanged { has_quorum, members, required }) => {
+                        server.quorum.store(has_quorum, Ordering::SeqCst);
+                        let (level, message) = if has_quorum {
+                            (AlertLevel::Info, format!("Cluster quorum regained with {} of {} members", members, required))
+                        } else {
+                            (AlertLevel::Critical, format!(
+                                "Cluster quorum lost with {} of {} members; task assignments are fenced",
+                                members, required,
+                            ))
+                        };
+                        server.monitoring.raise_alert(level, message, HashMap::new()).await;
+                    }
+                    Ok(_) => {}
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("Quorum watch skipped {} cluster messages", skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        })
+    }
+
+    /// Whether the server is not shutting down and every component is ready
+    pub async fn is_ready(&self) -> bool {
+        self.components().await.iter().all(|component| component.ready)
+    }
+
+    /// Whether startup has completed, so the other probes are worth asking
+    pub fn is_started(&self) -> bool {
+        self.started.load(Ordering::SeqCst)
+    }
+
+    /// Status of what serving traffic depends on: the MCP server, the message processor and storage
+    pub async fn components(&self) -> Vec<ComponentStatus> {
+        let state = self.server.get_state().await;
+        let server = if self.shutting_down.load(Ordering::SeqCst) {
+            Some("shutting down".to_string())
+        } else {
+            (state != ServerState::Running).then(|| format!("server state is {}", state))
+        };
+        let running = self.message_processor.read().await.as_ref().is_some_and(MessageProcessor::is_running);
+        let processor = (!running).then(|| "not running".to_string());
+        let probe = self.runtime_dir().join(STORAGE_PROBE_FILE);
+        let storage = match tokio::fs::write(&probe, b"").await {
+            Ok(()) => tokio::fs::remove_file(&probe).await.err(),
+            Err(e) => Some(e),
+        };
+        let storage = storage.map(|e| format!("{:?} is not writable: {}", self.runtime_dir(), e));
+        vec![
+            ComponentStatus::new("mcp_server", server),
+            ComponentStatus::new("message_processor", processor),
+            ComponentStatus::new("storage", storage),
+        ]
+    }
+
+    /// Report not ready from now on, so traffic drains before the server stops
+    pub fn begin_shutdown(&self) {
+        self.shutting_down.store(true, Ordering::SeqCst);
+    }
+
+    /// Get the tools available to agents
+    pub fn tools(&self) -> &ToolRegistry {
+        &self.tools
+    }
+
+    /// Assign a task to an agent and push the assignment to the agent's connection
+    ///
+    /// Agents connected to another cluster node get it through that node. Returns whether
+    /// the agent was connected to receive it; the assignment is recorded either way.
+    pub async fn assign_task(&self, task_id: &str, agent_id: &str) -> Result<bool, NexaError> {
+        self.ensure_accepting().await?;
+        self.registry.assign_task(task_id, agent_id).await?;
+        let task = self.registry.get_task(task_id).await?;
+        let message = MCPMessage::TaskAssignment { task, agent_id: agent_id.to_string(), trace: TraceContext::current_child() };
+        let result = self.server.send_to_agent(agent_id, &message).await;
+        #[cfg(feature = "cluster")]
+        let result = match (result, self.cluster_processor.read().await.as_ref()) {
+            (Err(NexaError::NotFound(_)), Some(processor)) => {
+                processor.forward(agent_id, &self.assignment_message(&message)?).await.map(|_| ())
+            }
+            (result, _) => result,
+        };
+        match result {
+            Ok(()) => Ok(true),
+            Err(e) => {
+                debug!("Task {} assigned but not delivered: {}", task_id, e);
+                Ok(false)
+            }
+        }
+    }
+
+    /// Move an unfinished task to another agent, pushing the assignment as with `assign_task`
+    ///
+    /// Interrupted tasks go back to pending for their new agent.
+    pub async fn reassign_task(&self, task_id: &str, agent_id: &str) -> Result<bool, NexaError> {
+        self.ensure_accepting().await?;
+        let task = self.registry.get_task(task_id).await?;
+        if registry::is_finished(task.status) {
+            return Err(NexaError::invalid_input(format!("Task {} is already {:?}", task_id, task.status)));
+        }
+        self.registry.get_agent(agent_id).await?;
+        self.registry.unassign_task(task_id).await?;
+        if task.status == TaskStatus::Interrupted {
+            self.registry.set_task_status(task_id, TaskStatus::Pending).await?;
+        }
+        self.assign_task(task_id, agent_id).await
+    }
+
+    /// Assign a task to an agent once `delay` has passed
+    ///
+    /// The assignment waits in the message buffer, and is made and pushed to the
+    /// agent when it comes due, as with `assign_task`.
+    pub async fn schedule_task_assignment(&self, task_id: &str, agent_id: &str, delay: Duration) -> Result<(), NexaError> {
+        self.ensure_accepting().await?;
+        let task = self.registry.get_task(task_id).await?;
+        self.registry.get_agent(agent_id).await?;
+        let message = MCPMessage::TaskAssignment { task, agent_id: agent_id.to_string(), trace: TraceContext::current_child() };
+        let msg = self.assignment_message(&message)?;
+        Ok(self.message_buffer.publish_delayed(msg, delay).await?)
+    }
+
+    /// Wrap a task assignment for the message buffer, or for another node
+    fn assignment_message(&self, message: &MCPMessage) -> Result<BufferedMessage, NexaError> {
+        Ok(BufferedMessage {
+            id: Uuid::new_v4(),
+            payload: serde_json::to_vec(message)?,
+            priority: Priority::Normal,
+            created_at: std::time::SystemTime::now(),
+            attempts: 0,
+            max_attempts: self.message_buffer.config.max_attempts,
+            delay_until: None,
+            topic: Some(ASSIGNMENT_TOPIC.to_string()),
+            trace: message.trace().cloned(),
+        })
+    }
+
+    /// Make the task assignments released by the message buffer
+    fn spawn_assignment_dispatcher(&self) -> tokio::task::JoinHandle<()> {
+        let server = self.clone();
+        let mut messages = self.message_buffer.subscribe_filtered(TopicFilter::new([ASSIGNMENT_TOPIC]));
+        tokio::spawn(async move {
+            loop {
+                match messages.recv().await {
+                    Ok(msg) => {
+                        if let Ok(MCPMessage::TaskAssignment { task, agent_id, .. }) = serde_json::from_slice(&msg.payload) {
+                            let result = trace::scope(msg.trace.clone(), server.assign_task(&task.id, &agent_id)).await;
+                            match result {
+                                Ok(_) => {}
+                                // Held back while draining or outside quorum, and journaled meanwhile
+                                Err(NexaError::Unavailable(reason)) => {
+                                    debug!("Retrying assignment of task {} later: {}", task.id, reason);
+                                    if let Err(e) = server.message_buffer.publish_delayed(msg, ASSIGNMENT_RETRY_DELAY).await {
+                                        error!("Failed to requeue assignment of task {}: {}", task.id, e);
+                                    }
+                                }
+                                Err(e) => error!("Failed to make scheduled assignment of task {}: {}", task.id, e),
+                            }
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("Assignment dispatcher skipped {} messages", skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        })
+    }
+
+    /// Ping agents that negotiated heartbeats, taking those that stop answering offline
+    fn spawn_heartbeat_monitor(&self) -> tokio::task::JoinHandle<()> {
+        let server = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let Ok(config) = server.server.get_config().await else { break };
+                tokio::time::sleep(config.heartbeat_interval).await;
+                server.check_heartbeats(config.heartbeat_interval * config.missed_heartbeats).await;
+            }
+        })
+    }
+
+    /// Take agents silent for longer than `timeout` offline and ping the others
+    async fn check_heartbeats(&self, timeout: Duration) {
+        let now = Utc::now();
+        for agent_id in self.server.heartbeat_agents().await {
+            let Ok(agent) = self.registry.get_agent(&agent_id).await else { continue };
+            let silent = (now - agent.last_heartbeat).to_std().unwrap_or_default();
+            if silent > timeout {
+                if let Err(e) = self.take_offline(&agent_id, silent).await {
+                    error!("Failed to take agent {} offline: {}", agent_id, e);
+                }
+            } else if let Err(e) = self.server.send_to_agent(&agent_id, &MCPMessage::Ping { sent_at: now }).await {
+                debug!("Failed to ping agent {}: {}", agent_id, e);
+            }
+        }
+    }
+
+    /// Mark an agent offline, requeue its tasks and raise an `AgentOffline` event
+    async fn take_offline(&self, agent_id: &str, silent: Duration) -> Result<(), NexaError> {
+        self.registry.update_status(agent_id, AgentStatus::Offline).await?;
+        self.server.unlink_agent(agent_id).await;
+        let requeued = self.registry.requeue_tasks(agent_id).await?;
+        warn!("Agent {} missed its heartbeats for {:?}; requeued {} task(s)", agent_id, silent, requeued.len());
+        self.events.publish(EventKind::AgentOffline, serde_json::json!({
+            "agent_id": agent_id,
+            "silent_secs": silent.as_secs(),
+            "requeued_tasks": requeued,
+        }));
+        Ok(())
+    }
+
+    /// Revoke an agent's auth token, deregistering the agent if it is registered
+    pub async fn revoke_agent_token(&self, agent_id: &str) -> Result<(), NexaError> {
+        self.registry.revoke_token(agent_id).await?;
+        if self.registry.get_agent(agent_id).await.is_ok() {
+            self.registry.deregister(agent_id).await?;
+            self.server.unlink_agent(agent_id).await;
+            info!("Deregistered agent {} after revoking its token", agent_id);
         }
+        Ok(())
+    }
+
+    /// Model Context Protocol handler serving this server's tools, agents and tasks
+    pub fn mcp_handler(&self) -> jsonrpc::McpHandler {
+        jsonrpc::McpHandler::new(self.tools.clone(), self.registry.clone())
+    }
+
+    /// Get the plugin host started with the server, if any
+    #[cfg(feature = "plugins")]
+    pub async fn plugin_host(&self) -> Option<PluginHost> {
+        self.plugin_host.read().await.clone()
+    }
+
+    /// Get the LLM supervisor started with the server, if any
+    pub async fn llm_supervisor(&self) -> Option<LLMSupervisor> {
+        self.llm_supervisor.read().await.clone()
     }
 
     pub async fn start(&self, addr: Option<&str>) -> Result<(), NexaError> {
@@ -190,6 +793,15 @@ impl ServerControl {
             return Err(NexaError::system("Server is already running"));
         }
 
+        // Seed subsystems from the loaded configuration, then follow its changes
+        let current = self.config_service.current();
+        self.monitoring.apply_config(&current.monitoring);
+        self.server.apply_config(&current).await;
+        *self.config_watches.write().await = vec![
+            self.monitoring.watch_config(self.config_service.subscribe()),
+            self.server.watch_config(self.config_service.subscribe()),
+        ];
+
         // Configure server bind address if provided
         if let Some(addr) = addr {
             let mut config = self.server.get_config().await?;
@@ -198,6 +810,16 @@ impl ServerControl {
             debug!("Set server bind address to {}", addr);
         }
 
+        // The transport is fixed for as long as the server runs
+        let mut config = self.server.get_config().await?;
+        config.transport = self.config_service.current().server.transport;
+        self.server.set_config(config).await?;
+
+        self.shutting_down.store(false, Ordering::SeqCst);
+        self.start_event_sinks().await;
+        self.server.set_mcp_handler(Some(self.mcp_handler())).await;
+        self.server.set_registry(Some(self.registry.clone())).await;
+
         // Start server first
         let server = self.server.clone();
         server.start().await?;
@@ -223,11 +845,21 @@ impl ServerControl {
 
         // Store the handle for the main server task
         let server_clone = self.server.clone();
+        let events = self.events.clone();
         let server_handle = tokio::spawn(async move {
+            let mut previous = ServerState::Running;
             // Keep checking server state and handle any necessary maintenance
             loop {
-                match server_clone.get_state().await {
-                    ServerState::Running => {
+                let state = server_clone.get_state().await;
+                if state != previous {
+                    events.publish(EventKind::ServerStateChanged, serde_json::json!({
+                        "from": previous.to_string(),
+                        "to": state.to_string(),
+                    }));
+                    previous = state.clone();
+                }
+                match state {
+                    ServerState::Running | ServerState::Maintenance => {
                         // Server is running normally, perform health check
                         server_clone.check_health().await;
                         tokio::time::sleep(Duration::from_secs(1)).await;
@@ -262,46 +894,355 @@ impl ServerControl {
         );
         processor.start().await?;
         *self.message_processor.write().await = Some(processor);
+        *self.dispatch_handle.write().await = Some(self.spawn_assignment_dispatcher());
+        *self.heartbeat_handle.write().await = Some(self.spawn_heartbeat_monitor());
 
-        // Start cluster processor if clustering is enabled
         let server_config = self.server.get_config().await?;
-        let cluster_config = Some(ClusterConfig {
-            min_quorum_size: 1,
-            heartbeat_interval: server_config.health_check_interval,
-            election_timeout: (
-                server_config.connection_timeout,
-                server_config.connection_timeout * 2
-            ),
-            // Add other fields as needed
-            ..Default::default()
-        });
 
-        if let Some(config) = cluster_config {
-            let bind_addr = addr
-                .and_then(|a| a.parse::<SocketAddr>().ok())
-                .unwrap_or_else(|| "127.0.0.1:0".parse().unwrap());
+        // Start cluster processor if clustering is enabled
+        #[cfg(feature = "cluster")]
+        {
+            let settings = self.config_service.current().cluster;
+            // Resolved through the secrets store, like provider credentials
+            let secret = settings.auth_secret.as_deref()
+                .map(|name| crate::secrets::SecretStore::open_default().resolve(name))
+                .transpose()?;
+            let cluster_config = Some(ClusterConfig {
+                min_quorum_size: settings.min_quorum_size.max(1),
+                gossip_addr: settings.gossip_addr,
+                seeds: settings.seeds,
+                secret,
+                heartbeat_interval: server_config.health_check_interval,
+                election_timeout: (
+                    server_config.connection_timeout,
+                    server_config.connection_timeout * 2
+                ),
+                // Add other fields as needed
+                ..Default::default()
+            });
+
+            if let Some(config) = cluster_config {
+                let bind_addr = addr
+                    .and_then(|a| a.parse::<SocketAddr>().ok())
+                    .unwrap_or_else(|| "127.0.0.1:0".parse().unwrap());
                 
-            let mut cluster_processor = ClusterProcessor::new(
-                ClusterProcessorConfig::default(),
-                self.message_buffer.clone(),
-                Arc::new(ClusterManager::new(bind_addr, Some(config))),
-            );
-            cluster_processor.start().await?;
-            *self.cluster_processor.write().await = Some(cluster_processor);
+                let manager = ClusterManager::new(bind_addr, Some(config));
+                manager.replicate(self.registry.clone()).await;
+                let server = self.server.clone();
+                manager.set_delivery(Arc::new(move |agent_id, msg| {
+                    let server = server.clone();
+                    Box::pin(async move {
+                        let message: MCPMessage = serde_json::from_slice(&msg.payload)?;
+                        server.send_to_agent(&agent_id, &message).await?;
+                        Ok(serde_json::json!({ "delivered": true }))
+                    })
+                })).await;
+                *self.quorum_handle.write().await = Some(self.spawn_quorum_watch(&manager).await);
+                manager.start().await?;
+                let mut cluster_processor = ClusterProcessor::new(
+                    ClusterProcessorConfig::default(),
+                    self.message_buffer.clone(),
+                    Arc::new(manager),
+                );
+                cluster_processor.start().await?;
+                *self.cluster_processor.write().await = Some(cluster_processor);
+            }
         }
 
         // Start message cleanup task
         self.start_message_cleanup().await;
 
+        // Journal runtime state for crash recovery
+        let journal = StateJournal::spawn(self.clone(), self.state_file(), Duration::from_secs(5));
+        *self.journal_handle.write().await = Some(journal);
+
+        // Only the Lease holder does leader-only work when running in Kubernetes
+        let kubernetes = self.config_service.current().kubernetes;
+        if kubernetes.enabled {
+            let elector = LeaseElector::in_cluster(&kubernetes)?;
+            info!("Competing for lease {} as {}", kubernetes.lease_name, elector.identity());
+            self.leader.store(false, Ordering::SeqCst);
+            let handle = elector.clone().spawn(self.leader.clone(), Duration::from_secs(kubernetes.renew_interval.max(1)));
+            *self.elector.write().await = Some(elector);
+            *self.election_handle.write().await = Some(handle);
+        }
+
+        // Snapshot persisted data on a schedule
+        let backup_config = self.config_service.current().backup;
+        if backup_config.enabled {
+            let backups = BackupManager::new(self.runtime_dir())
+                .with_config_path(Config::get_config_path())
+                .with_retention(backup_config.retention);
+            let handle = backups.start_schedule(self.clone(), Duration::from_secs(backup_config.interval.max(1)));
+            *self.backup_handle.write().await = Some(handle);
+        }
+
+        // Supervise LLM providers, queueing work while they are unavailable
+        let config = self.config_service.current();
+        let warmup = config.llm.warmup;
+        let llm = LLMClient::new(config.llm)?;
+        llm.set_token_manager(self.token_manager.clone());
+        let config_watch = llm.watch_config(self.config_service.subscribe());
+        let pricing = self.load_model_pricing(llm.clone());
+        let mut providers = vec![llm];
+        for fallback in config.llm_fallbacks {
+            let fallback = LLMClient::new(fallback)?;
+            fallback.set_token_manager(self.token_manager.clone());
+            providers.push(fallback);
+        }
+        let supervisor = LLMSupervisor::new(providers).with_monitoring(self.monitoring.clone());
+        let health_checks = supervisor.start_health_checks(server_config.health_check_interval);
+        let mut llm_tasks = vec![config_watch, health_checks, pricing];
+        if warmup {
+            let supervisor = supervisor.clone();
+            llm_tasks.push(tokio::spawn(async move { supervisor.warmup().await }));
+        }
+        *self.llm_tasks.write().await = llm_tasks;
+        *self.llm_supervisor.write().await = Some(supervisor);
+
+        self.load_tools().await?;
+
+        // Serve the REST API; the WebSocket server keeps running without it
+        let api_config = self.config_service.current().api;
+        let mut api_addr = None;
+        if api_config.enabled {
+            let api = ApiServer::new(self.clone()).with_auto_port(server_config.auto_port);
+            match api.start(&api_config.bind_addr).await {
+                Ok((addr, handle)) => {
+                    api_addr = Some(addr);
+                    *self.api_handle.write().await = Some(handle);
+                }
+                Err(e) => error!("REST API disabled: {}", e),
+            }
+        }
+
+        // Advertise the addresses actually bound, which may be ephemeral
+        if let Some(ws_addr) = self.server.get_bound_addr().await {
+            let discovery = Discovery {
+                pid: std::process::id(),
+                ws_addr,
+                api_addr,
+                profile: self.config_service.profile(),
+                started_at: Utc::now(),
+            };
+            if let Err(e) = discovery.write(&self.runtime_dir()) {
+                error!("Failed to write discovery file: {}", e);
+            }
+        }
+
+        self.events.publish(EventKind::ServerStarted, serde_json::json!({
+            "ws_addr": self.server.get_bound_addr().await,
+            "api_addr": api_addr,
+            "profile": self.config_service.profile(),
+        }));
+        self.started.store(true, Ordering::SeqCst);
         info!("Server startup completed successfully");
         Ok(())
     }
 
+    /// Attach the configured sinks and the relay of changes to the bus, replacing any from a previous start
+    async fn start_event_sinks(&self) {
+        let config = self.config_service.current().events;
+        let mut sinks = self.event_sinks.write().await;
+        for handle in sinks.drain(..) {
+            handle.abort();
+        }
+        if config.log {
+            sinks.push(self.events.add_sink(Arc::new(JsonlSink::new(self.runtime_dir().join(EVENT_LOG_FILE)))));
+        }
+        for webhook in &config.webhooks {
+            sinks.push(self.events.add_sink(Arc::new(WebhookSink::from_config(webhook))));
+        }
+        sinks.push(self.spawn_event_relay().await);
+        let websocket = config.websocket.then(|| self.events.clone());
+        self.server.set_event_bus(websocket).await;
+    }
+
+    /// Publish registry changes and alerts on the event bus as they happen
+    async fn spawn_event_relay(&self) -> tokio::task::JoinHandle<()> {
+        let events = self.events.clone();
+        // Subscribe before spawning so no change made after this call is missed
+        let (_, mut changes) = self.registry.subscribe().await;
+        let mut alerts = self.monitoring.subscribe_alerts();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    change = changes.recv() => match change {
+                        Ok(change) => {
+                            let kind = match change {
+                                RegistryEvent::Added { .. } | RegistryEvent::Removed { .. } | RegistryEvent::StatusChanged { .. } => {
+                                    EventKind::AgentStatusChanged
+                                }
+                                RegistryEvent::TaskUpdated { .. } | RegistryEvent::TaskRemoved { .. } => EventKind::TaskProgress,
+                            };
+                            events.publish(kind, serde_json::json!(change));
+                        }
+                        Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                            warn!("Event relay skipped {} registry changes", skipped);
+                        }
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    },
+                    alert = alerts.recv() => match alert {
+                        Ok(alert) => {
+                            events.publish(EventKind::Alert, serde_json::json!(alert));
+                        }
+                        Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                            warn!("Event relay skipped {} alerts", skipped);
+                        }
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    },
+                }
+            }
+        })
+    }
+
+    /// Register the tools of enabled features (plugins, scripts and containers) and of MCP servers
+    ///
+    /// Tools that need an LLM get the supervisor if the server has started one.
+    pub async fn load_tools(&self) -> Result<(), NexaError> {
+        self.connect_mcp_servers().await;
+
+        // Register tools from WASM plugins
+        #[cfg(feature = "plugins")]
+        self.load_plugins().await;
+
+        // Scripts read files from their own directory, away from secrets and state
+        #[cfg(feature = "scripting")]
+        if self.tools.get("script").is_none() {
+            let mut runner = ScriptRunner::new(self.runtime_dir().join("scripts"));
+            if let Some(llm) = self.llm_supervisor().await {
+                runner = runner.with_llm(llm);
+            }
+            self.tools.register(Arc::new(ScriptTool::new(runner)))?;
+        }
+
+        // Let agents run code in isolated containers
+        #[cfg(feature = "containers")]
+        if self.tools.get("run_container").is_none() {
+            match ContainerRunner::connect(self.config_service.current().containers) {
+                Ok(runner) => self.tools.register(Arc::new(ContainerTool::new(runner)))?,
+                Err(e) => error!("run_container tool disabled: {}", e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Register the tools of the configured MCP servers; servers that fail are skipped
+    async fn connect_mcp_servers(&self) {
+        let mut registered = self.mcp_tools.write().await;
+        if !registered.is_empty() {
+            return;
+        }
+        for config in self.config_service.current().mcp_servers {
+            let tools = match client::McpClient::connect(&config).await {
+                Ok(client) => client.register_tools(&self.tools).await,
+                Err(e) => Err(e),
+            };
+            match tools {
+                Ok(tools) => registered.extend(tools),
+                Err(e) => error!("Tools of MCP server '{}' disabled: {}", config.name, e),
+            }
+        }
+    }
+
+    #[cfg(feature = "plugins")]
+    async fn load_plugins(&self) {
+        let config = self.config_service.current().plugins;
+        if !config.enabled {
+            return;
+        }
+        let dir = config.dir.unwrap_or_else(|| self.runtime_dir().join("plugins"));
+        let host = match PluginHost::new(dir) {
+            Ok(host) => match self.llm_supervisor().await {
+                Some(llm) => host.with_llm(llm),
+                None => host,
+            },
+            Err(e) => {
+                error!("Plugins disabled: {}", e);
+                return;
+            }
+        };
+        match host.load_all(&self.tools) {
+            Ok(loaded) if !loaded.is_empty() => info!("Loaded {} plugin(s)", loaded.len()),
+            Ok(_) => {}
+            Err(e) => error!("Failed to load plugins: {}", e),
+        }
+        *self.plugin_host.write().await = Some(host);
+    }
+
     pub async fn stop(&self) -> Result<(), NexaError> {
+        self.begin_shutdown();
+        self.started.store(false, Ordering::SeqCst);
+        // Published first so sinks deliver it while the rest shuts down
+        self.events.publish(EventKind::ServerStopped, serde_json::json!({}));
+        Discovery::remove(&self.runtime_dir());
+
+        // Hand leadership to another replica
+        if let Some(handle) = self.election_handle.write().await.take() {
+            handle.abort();
+        }
+        if let Some(elector) = self.elector.write().await.take() {
+            if let Err(e) = elector.release().await {
+                error!("Failed to release lease: {}", e);
+            }
+            self.leader.store(false, Ordering::SeqCst);
+        }
+
+        // Stop the REST API
+        if let Some(handle) = self.api_handle.write().await.take() {
+            handle.abort();
+        }
+
+        // Stop scheduled backups
+        if let Some(handle) = self.backup_handle.write().await.take() {
+            handle.abort();
+        }
+        if let Some(handle) = self.dispatch_handle.write().await.take() {
+            handle.abort();
+        }
+        if let Some(handle) = self.heartbeat_handle.write().await.take() {
+            handle.abort();
+        }
+        if let Some(handle) = self.drain_handle.write().await.take() {
+            handle.abort();
+        }
+
+        // Stop LLM supervision
+        for handle in self.llm_tasks.write().await.drain(..) {
+            handle.abort();
+        }
+        for handle in self.config_watches.write().await.drain(..) {
+            handle.abort();
+        }
+        self.llm_supervisor.write().await.take();
+
+        // Disconnect MCP servers, stopping the ones launched as subprocesses
+        for name in self.mcp_tools.write().await.drain(..) {
+            self.tools.unregister(&name);
+        }
+
+        if let Err(e) = self.registry.compact().await {
+            error!("Failed to compact the agent registry: {}", e);
+        }
+
+        // Stop journaling and record a clean shutdown
+        if let Some(journal) = self.journal_handle.write().await.take() {
+            journal.abort();
+            if let Err(e) = StateJournal::mark_clean_shutdown(&self.state_file()) {
+                error!("Failed to record clean shutdown: {}", e);
+            }
+        }
+
         // Stop cluster processor
+        #[cfg(feature = "cluster")]
         if let Some(mut processor) = self.cluster_processor.write().await.take() {
+            processor.manager().stop().await?;
             processor.stop().await?;
         }
+        #[cfg(feature = "cluster")]
+        if let Some(handle) = self.quorum_handle.write().await.take() {
+            handle.abort();
+        }
 
         // Stop message processor
         if let Some(mut processor) = self.message_processor.write().await.take() {
@@ -359,6 +1300,10 @@ impl ServerControl {
         Err(NexaError::system("Server failed to stop within timeout"))
     }
 
+    pub async fn get_server_config(&self) -> Result<server::ServerConfig, NexaError> {
+        self.server.get_config().await
+    }
+
     pub async fn get_bound_addr(&self) -> Result<std::net::SocketAddr, NexaError> {
         self.server.get_bound_addr().await
             .ok_or_else(|| NexaError::system("Server address not available"))
@@ -376,6 +1321,14 @@ impl ServerControl {
                 let active_connections = self.server.get_active_connections().await;
                 let bound_addr = self.server.get_bound_addr().await;
 
+                if !self.has_quorum() {
+                    return Ok(SystemHealth {
+                        is_healthy: false,
+                        message: "Outside cluster quorum, read-only".to_string(),
+                        timestamp: Utc::now(),
+                    });
+                }
+
                 Ok(SystemHealth {
                     is_healthy: bound_addr.is_some() && active_connections < 1000,
                     message: format!(
@@ -416,6 +1369,13 @@ impl ServerControl {
                         timestamp: Utc::now(),
                     });
                 }
+                if !self.has_quorum() {
+                    alerts.push(SystemAlert {
+                        level: AlertLevel::Critical,
+                        message: "Outside cluster quorum; task assignments are fenced".to_string(),
+                        timestamp: Utc::now(),
+                    });
+                }
             }
             state => {
                 alerts.push(SystemAlert {
@@ -442,6 +1402,9 @@ impl ServerControl {
             token_cost: 0.0,
             active_agents: active_connections,
             error_count: 0,
+            llm_requests_by_provider: self.llm_supervisor().await
+                .map(|llm| llm.served_by())
+                .unwrap_or_default(),
             timestamp: Utc::now(),
         })
     }
@@ -453,7 +1416,8 @@ impl ServerControl {
 
     /// Track agent resource allocation
     pub async fn track_agent_resources(&self, agent_id: &str, resource_type: ResourceType, size: usize) -> Result<(), NexaError> {
-        let metadata = HashMap::new();
+        let mut metadata = HashMap::new();
+        metadata.insert("agent_id".to_string(), agent_id.to_string());
         self.memory_manager.allocate(
             format!("agent-{}-{:?}", agent_id, resource_type),
             resource_type,
@@ -462,6 +1426,26 @@ impl ServerControl {
         ).await
     }
 
+    /// Release resource allocations held by agents that are no longer registered
+    pub async fn release_orphaned_allocations(&self) -> Result<usize, NexaError> {
+        let agents: Vec<String> = self.registry.list_agents().await
+            .into_iter()
+            .map(|a| a.id)
+            .collect();
+        let mut released = 0;
+        for (id, record) in self.memory_manager.get_allocations().await {
+            let owner = record.metadata.get("agent_id");
+            if owner.is_some_and(|owner| !agents.contains(owner)) {
+                self.memory_manager.deallocate(&id).await?;
+                released += 1;
+            }
+        }
+        if released > 0 {
+            info!("Released {} orphaned allocations", released);
+        }
+        Ok(released)
+    }
+
     /// Track token usage for an agent
     pub async fn track_agent_token_usage(
         &self,
@@ -470,6 +1454,18 @@ impl ServerControl {
         prompt_tokens: usize,
         completion_tokens: usize,
     ) -> Result<(), NexaError> {
+        if let Some(limit) = self.token_manager.model_limit(&model) {
+            let total = prompt_tokens + completion_tokens;
+            if total > limit {
+                self.events.publish(EventKind::BudgetExceeded, serde_json::json!({
+                    "agent_id": agent_id,
+                    "model": format!("{:?}", model),
+                    "tokens": total,
+                    "limit": limit,
+                }));
+            }
+        }
+
         let mut metadata = HashMap::new();
         metadata.insert("agent_id".to_string(), agent_id.to_string());
         
@@ -478,6 +1474,36 @@ impl ServerControl {
             .await
     }
 
+    /// Fetch provider-reported model prices in the background so costs reflect them
+    fn load_model_pricing(&self, llm: LLMClient) -> tokio::task::JoinHandle<()> {
+        let token_manager = self.token_manager.clone();
+        tokio::spawn(async move {
+            match llm.model_pricing().await {
+                Ok(pricing) if !pricing.is_empty() => {
+                    info!("Loaded pricing for {} model(s)", pricing.len());
+                    llm.set_pricing(pricing.clone());
+                    token_manager.load_pricing(pricing);
+                }
+                Ok(_) => {}
+                Err(e) => warn!("Failed to load model pricing: {}", e),
+            }
+        })
+    }
+
+    /// Update an agent's status, publishing an event when it fails
+    pub async fn update_agent_status(&self, agent_id: &str, status: AgentStatus) -> Result<(), NexaError> {
+        self.registry.update_status(agent_id, status).await?;
+        if status == AgentStatus::Error {
+            self.events.publish(EventKind::AgentFailed, serde_json::json!({ "agent_id": agent_id }));
+        }
+        Ok(())
+    }
+
+    /// Get token usage recorded since the server started
+    pub async fn total_token_usage(&self) -> TokenUsage {
+        self.token_manager.get_usage_since(chrono::DateTime::<Utc>::MIN_UTC).await
+    }
+
     /// Get token usage for an agent
     pub async fn get_agent_token_usage(&self, _agent_id: &str, since: Option<chrono::DateTime<chrono::Utc>>) -> TokenUsage {
         match since {
@@ -492,8 +1518,7 @@ impl ServerControl {
 
     /// Publish a message to the buffer
     pub async fn publish_message(&self, msg: BufferedMessage) -> Result<(), NexaError> {
-        self.message_buffer.publish(msg).await
-            .map_err(|e| NexaError::system(format!("Failed to publish message: {}", e)))
+        Ok(self.message_buffer.publish(msg).await?)
     }
 
     /// Subscribe to messages
@@ -501,6 +1526,22 @@ impl ServerControl {
         self.message_buffer.subscribe()
     }
 
+    /// Subscribe to the messages whose topic matches one of `patterns`
+    ///
+    /// See `TopicFilter` for the pattern syntax.
+    pub fn subscribe_to_topics<I, S>(&self, patterns: I) -> Subscription
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.message_buffer.subscribe_filtered(TopicFilter::new(patterns))
+    }
+
+    /// Get a copy of all messages waiting in the buffer
+    pub fn pending_messages(&self) -> Vec<BufferedMessage> {
+        self.message_buffer.snapshot()
+    }
+
     /// Get the next message with the specified priority
     pub fn get_next_message(&self, priority: Priority) -> Option<BufferedMessage> {
         self.message_buffer.pop(priority)
@@ -560,8 +1601,36 @@ impl ServerControl {
 mod tests {
     use super::*;
     use std::time::{Duration, SystemTime};
+    use crate::agent::TaskStatus;
     use crate::memory::ResourceType;
 
+    #[tokio::test]
+    async fn test_config_watch_starts_with_server() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = ServerControl::new(dir.path().join("nexa.pid"), dir.path().join("nexa.sock"));
+        let mut config = Config::default();
+        config.runtime_dir = dir.path().to_path_buf();
+        config.api.enabled = false;
+        config.backup.enabled = false;
+        config.monitoring.cpu_threshold = 42.0;
+        server.config_service().update(config.clone());
+
+        // Seeded from the loaded configuration, not the defaults
+        server.start(Some("127.0.0.1:0")).await.unwrap();
+        assert_eq!(server.monitoring.cpu_threshold(), 42.0);
+
+        config.monitoring.cpu_threshold = 55.0;
+        server.config_service().update(config);
+        for _ in 0..50 {
+            if server.monitoring.cpu_threshold() == 55.0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(server.monitoring.cpu_threshold(), 55.0);
+        server.stop().await.unwrap();
+    }
+
     #[tokio::test]
     async fn test_server_control() {
         // Set up temporary paths for test
@@ -628,6 +1697,319 @@ mod tests {
         assert_eq!(usage.total_tokens, 150);
     }
 
+    #[tokio::test]
+    async fn test_agent_failed_event() {
+        let server = ServerControl::new(PathBuf::new(), PathBuf::new());
+        let mut events = server.events().subscribe();
+        let agent = Agent::new("worker".to_string(), vec![]);
+        server.registry.register(agent.clone()).await.unwrap();
+
+        server.update_agent_status(&agent.id, AgentStatus::Busy).await.unwrap();
+        server.update_agent_status(&agent.id, AgentStatus::Error).await.unwrap();
+
+        let event = events.recv().await.unwrap();
+        assert_eq!(event.kind, EventKind::AgentFailed);
+        assert_eq!(event.data["agent_id"], agent.id);
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_scheduled_task_assignment() {
+        let server = ServerControl::new(PathBuf::new(), PathBuf::new());
+        let dispatcher = server.spawn_assignment_dispatcher();
+        let agent = Agent::new("worker".to_string(), vec![]);
+        server.registry.register(agent.clone()).await.unwrap();
+        let task = Task::new("Later".to_string(), String::new(), Vec::new(), Vec::new(), None, 60, 1);
+        server.registry.add_task(task.clone()).await.unwrap();
+        assert!(server.schedule_task_assignment("missing", &agent.id, Duration::ZERO).await.is_err());
+        let (outbox, mut inbox) = tokio::sync::mpsc::unbounded_channel();
+        server.server.link_agent(&agent.id, outbox, false).await;
+
+        let trace = TraceContext::new();
+        trace.clone().scope(server.schedule_task_assignment(&task.id, &agent.id, Duration::from_millis(200))).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(server.registry.get_task(&task.id).await.unwrap().assigned_agent, None);
+
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        assert_eq!(server.registry.get_task(&task.id).await.unwrap().assigned_agent, Some(agent.id));
+
+        // The agent gets the assignment in the trace it was scheduled in
+        let assignment = inbox.try_recv().unwrap();
+        assert_eq!(assignment["TaskAssignment"]["trace_id"], trace.trace_id.as_str());
+        assert_ne!(assignment["TaskAssignment"]["span_id"], trace.span_id.as_str());
+        dispatcher.abort();
+    }
+
+    #[tokio::test]
+    async fn test_drain() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = ServerControl::new(dir.path().join("nexa.pid"), dir.path().join("nexa.sock"));
+        server.server.set_config(server::ServerConfig::new().with_bind_addr("127.0.0.1:0".to_string())).await.unwrap();
+        server.server.start().await.unwrap();
+        while server.server.get_state().await != ServerState::Running {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let url = format!("ws://{}", server.get_bound_addr().await.unwrap());
+
+        let agent = Agent::new("worker".to_string(), vec![]);
+        server.registry.register(agent.clone()).await.unwrap();
+        let (outbox, _inbox) = tokio::sync::mpsc::unbounded_channel();
+        server.server.link_agent(&agent.id, outbox, false).await;
+        let mut task = Task::new("Work".to_string(), String::new(), Vec::new(), Vec::new(), None, 60, 1);
+        task.status = TaskStatus::InProgress;
+        server.registry.add_task(task.clone()).await.unwrap();
+        assert!(server.assign_task(&task.id, &agent.id).await.unwrap());
+        let mut task = server.registry.get_task(&task.id).await.unwrap();
+
+        let status = server.drain(Duration::from_secs(10)).await.unwrap();
+        assert_eq!(status.phase, DrainPhase::Draining);
+        assert_eq!(status.in_flight_tasks, 1);
+        assert!(!server.is_ready().await);
+
+        // No new connections or assignments while draining
+        assert!(tokio_tungstenite::connect_async(url.as_str()).await.is_err());
+        let err = server.assign_task(&task.id, &agent.id).await.unwrap_err();
+        assert!(matches!(err, NexaError::Unavailable(_)));
+
+        // Drained once the task in flight finishes
+        task.status = TaskStatus::Completed;
+        server.registry.update_task(task.clone()).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(600)).await;
+        assert_eq!(server.drain_status().await.unwrap().phase, DrainPhase::Drained);
+
+        let ended = server.resume().await.unwrap().unwrap();
+        assert_eq!(ended.in_flight_tasks, 0);
+        assert!(server.drain_status().await.is_none());
+        assert!(tokio_tungstenite::connect_async(url.as_str()).await.is_ok());
+
+        // Work still in flight when the timeout passes is reported
+        task.status = TaskStatus::InProgress;
+        server.registry.update_task(task).await.unwrap();
+        server.drain(Duration::ZERO).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(600)).await;
+        let status = server.drain_status().await.unwrap();
+        assert_eq!(status.phase, DrainPhase::TimedOut);
+        assert_eq!(status.in_flight_tasks, 1);
+
+        server.server.stop().await.unwrap();
+    }
+
+    #[cfg(feature = "cluster")]
+    #[tokio::test]
+    async fn test_quorum_fencing() {
+        let server = ServerControl::new(PathBuf::new(), PathBuf::new());
+        let config = ClusterConfig { min_quorum_size: 2, ..Default::default() };
+        let manager = ClusterManager::new("127.0.0.1:0".parse().unwrap(), Some(config));
+        let mut peer = manager.node.read().await.clone();
+        peer.id = Uuid::new_v4();
+        manager.apply_membership(cluster::MembershipChange::Join { node: peer.clone(), timestamp: SystemTime::now() }).await;
+        let watch = server.spawn_quorum_watch(&manager).await;
+        assert!(server.has_quorum());
+
+        let agent = Agent::new("worker".to_string(), vec![]);
+        server.registry.register(agent.clone()).await.unwrap();
+        let task = Task::new("Work".to_string(), String::new(), Vec::new(), Vec::new(), None, 60, 1);
+        server.registry.add_task(task.clone()).await.unwrap();
+
+        manager.apply_membership(cluster::MembershipChange::Remove {
+            node_id: peer.id,
+            reason: "partitioned".to_string(),
+            timestamp: SystemTime::now(),
+        }).await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!server.has_quorum());
+        let err = server.assign_task(&task.id, &agent.id).await.unwrap_err();
+        assert_eq!(err.http_status(), 503);
+        assert_eq!(server.registry.get_task(&task.id).await.unwrap().assigned_agent, None);
+        let alerts = server.monitoring.get_recent_alerts(Utc::now() - chrono::Duration::minutes(1)).await;
+        assert!(alerts.iter().any(|alert| alert.level == AlertLevel::Critical));
+        watch.abort();
+    }
+
+    #[cfg(feature = "cluster")]
+    #[tokio::test]
+    async fn test_quorum_from_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = ServerControl::new(dir.path().join("nexa.pid"), dir.path().join("nexa.sock"));
+        let mut config = Config::default();
+        config.runtime_dir = dir.path().to_path_buf();
+        config.api.enabled = false;
+        config.backup.enabled = false;
+        config.cluster.min_quorum_size = 2;
+        server.config_service().update(config);
+        server.start(Some("127.0.0.1:0")).await.unwrap();
+
+        // Alone, the node cannot reach a quorum of two and is fenced
+        assert!(!server.has_quorum());
+        let agent = Agent::new("worker".to_string(), vec![]);
+        server.registry.register(agent.clone()).await.unwrap();
+        let task = Task::new("Work".to_string(), String::new(), Vec::new(), Vec::new(), None, 60, 1);
+        server.registry.add_task(task.clone()).await.unwrap();
+        let err = server.assign_task(&task.id, &agent.id).await.unwrap_err();
+        assert_eq!(err.http_status(), 503);
+        assert_eq!(server.registry.get_task(&task.id).await.unwrap().assigned_agent, None);
+        server.stop().await.unwrap();
+    }
+
+    #[cfg(feature = "cluster")]
+    async fn cluster_node(dir: &std::path::Path, seeds: Vec<SocketAddr>) -> ServerControl {
+        let server = ServerControl::new(dir.join("nexa.pid"), dir.join("nexa.sock"));
+        let mut config = Config::default();
+        config.runtime_dir = dir.to_path_buf();
+        config.api.enabled = false;
+        config.backup.enabled = false;
+        config.cluster.min_quorum_size = 2;
+        config.cluster.gossip_addr = Some("127.0.0.1:0".parse().unwrap());
+        config.cluster.seeds = seeds;
+        std::env::set_var("NEXA_TEST_CLUSTER_SECRET", "cluster-secret");
+        config.cluster.auth_secret = Some("NEXA_TEST_CLUSTER_SECRET".to_string());
+        server.config_service().update(config);
+        server.start(Some("127.0.0.1:0")).await.unwrap();
+        server
+    }
+
+    #[cfg(feature = "cluster")]
+    async fn cluster_manager(server: &ServerControl) -> Arc<ClusterManager> {
+        server.cluster_processor.read().await.as_ref().unwrap().manager().clone()
+    }
+
+    #[cfg(feature = "cluster")]
+    #[tokio::test]
+    async fn test_cluster_discovery() {
+        let (first_dir, second_dir) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let first = cluster_node(first_dir.path(), Vec::new()).await;
+        let seed = cluster_manager(&first).await.gossip_addr().await.unwrap();
+        assert!(!first.has_quorum());
+
+        // Joining through the first node's gossip address makes a quorum of two on both
+        let second = cluster_node(second_dir.path(), vec![seed]).await;
+        for _ in 0..100 {
+            if first.has_quorum() && second.has_quorum() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert!(first.has_quorum() && second.has_quorum());
+        second.stop().await.unwrap();
+        first.stop().await.unwrap();
+    }
+
+    #[cfg(feature = "cluster")]
+    #[tokio::test]
+    async fn test_cluster_replication() {
+        let (first_dir, second_dir) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let first = cluster_node(first_dir.path(), Vec::new()).await;
+        let leader = cluster_manager(&first).await;
+        leader.state.write().await.term = 1;
+        leader.node.write().await.role = cluster::NodeRole::Leader;
+        let agent = Agent::new("worker".to_string(), vec!["review".to_string()]);
+        first.registry.register(agent.clone()).await.unwrap();
+
+        // With the secret resolved from the environment, the joining node follows the leader's registry
+        let seed = leader.gossip_addr().await.unwrap();
+        let second = cluster_node(second_dir.path(), vec![seed]).await;
+        for _ in 0..100 {
+            if second.registry.get_agent(&agent.id).await.is_ok() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert_eq!(second.registry.get_agent(&agent.id).await.unwrap().name, "worker");
+        second.stop().await.unwrap();
+        first.stop().await.unwrap();
+    }
+
+    #[cfg(feature = "cluster")]
+    #[tokio::test]
+    async fn test_cluster_forwarding() {
+        let (first_dir, second_dir) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let first = cluster_node(first_dir.path(), Vec::new()).await;
+        let seed = cluster_manager(&first).await.gossip_addr().await.unwrap();
+        let second = cluster_node(second_dir.path(), vec![seed]).await;
+        for _ in 0..100 {
+            if first.has_quorum() && second.has_quorum() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        // The agent is connected to the second node only
+        let agent = Agent::new("worker".to_string(), vec![]);
+        first.registry.register(agent.clone()).await.unwrap();
+        let (outbox, mut inbox) = tokio::sync::mpsc::unbounded_channel();
+        second.server.link_agent(&agent.id, outbox, false).await;
+        let task = Task::new("Work".to_string(), String::new(), Vec::new(), Vec::new(), None, 60, 1);
+        first.registry.add_task(task.clone()).await.unwrap();
+
+        // Assigning on the first node reaches it through the second
+        assert!(first.assign_task(&task.id, &agent.id).await.unwrap());
+        let assignment = tokio::time::timeout(Duration::from_secs(5), inbox.recv()).await.unwrap().unwrap();
+        assert_eq!(assignment["TaskAssignment"]["task"]["id"], task.id.as_str());
+        assert_eq!(assignment["TaskAssignment"]["agent_id"], agent.id.as_str());
+        second.stop().await.unwrap();
+        first.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_event_relay() {
+        let server = ServerControl::new(PathBuf::new(), PathBuf::new());
+        let mut events = server.events().subscribe();
+        let relay = server.spawn_event_relay().await;
+
+        let agent = Agent::new("worker".to_string(), vec![]);
+        server.registry.register(agent.clone()).await.unwrap();
+        server.registry.update_status(&agent.id, AgentStatus::Busy).await.unwrap();
+
+        let added = events.recv().await.unwrap();
+        assert_eq!(added.kind, EventKind::AgentStatusChanged);
+        assert_eq!(added.data["change"], "added");
+        let busy = events.recv().await.unwrap();
+        assert_eq!(busy.data["change"], "status_changed");
+        assert_eq!(busy.data["to"], "Busy");
+
+        server.monitoring.raise_alert(AlertLevel::Warning, "Disk filling up".to_string(), HashMap::new()).await;
+        let alert = events.recv().await.unwrap();
+        assert_eq!(alert.kind, EventKind::Alert);
+        assert_eq!(alert.data["message"], "Disk filling up");
+        relay.abort();
+    }
+
+    #[tokio::test]
+    async fn test_missed_heartbeats() {
+        let server = ServerControl::new(PathBuf::new(), PathBuf::new());
+        let mut events = server.events().subscribe();
+        let mut silent = Agent::new("silent".to_string(), vec![]);
+        silent.last_heartbeat = Utc::now() - chrono::Duration::seconds(60);
+        let live = Agent::new("live".to_string(), vec![]);
+        for agent in [&silent, &live] {
+            server.registry.register(agent.clone()).await.unwrap();
+        }
+        let mut task = Task::new("Work".to_string(), String::new(), Vec::new(), Vec::new(), None, 60, 1);
+        task.status = TaskStatus::InProgress;
+        server.registry.add_task(task.clone()).await.unwrap();
+        server.registry.assign_task(&task.id, &silent.id).await.unwrap();
+
+        let (silent_outbox, _silent_inbox) = tokio::sync::mpsc::unbounded_channel();
+        let (live_outbox, mut live_inbox) = tokio::sync::mpsc::unbounded_channel();
+        server.server.link_agent(&silent.id, silent_outbox, true).await;
+        server.server.link_agent(&live.id, live_outbox, true).await;
+        server.check_heartbeats(Duration::from_secs(30)).await;
+
+        // The live agent is pinged
+        assert!(live_inbox.try_recv().unwrap().get("Ping").is_some());
+        assert_eq!(server.server.heartbeat_agents().await, vec![live.id.clone()]);
+
+        // The silent one is taken offline and its task requeued
+        assert_eq!(server.registry.get_agent(&silent.id).await.unwrap().status, AgentStatus::Offline);
+        let requeued = server.registry.get_task(&task.id).await.unwrap();
+        assert_eq!(requeued.status, TaskStatus::Pending);
+        assert_eq!(requeued.assigned_agent, None);
+        let event = events.recv().await.unwrap();
+        assert_eq!(event.kind, EventKind::AgentOffline);
+        assert_eq!(event.data["agent_id"], silent.id);
+        assert_eq!(event.data["requeued_tasks"], serde_json::json!([task.id]));
+    }
+
     #[tokio::test]
     async fn test_message_buffer() {
         let server = ServerControl::new(PathBuf::new(), PathBuf::new());
@@ -641,6 +2023,8 @@ mod tests {
             attempts: 0,
             max_attempts: 3,
             delay_until: None,
+            topic: None,
+            trace: None,
         };
         
         // Test publish
@@ -662,6 +2046,8 @@ mod tests {
             attempts: 0,
             max_attempts: 3,
             delay_until: None,
+            topic: None,
+            trace: None,
         };
         
         let mut subscriber = server.subscribe_to_messages();
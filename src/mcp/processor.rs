@@ -2,6 +2,7 @@ use std::time::{Duration, SystemTime};
 use tracing::{debug, error, info};
 use crate::error::NexaError;
 use crate::mcp::buffer::{BufferedMessage, MessageBuffer, Priority};
+use crate::mcp::trace;
 use std::sync::Arc;
 use tokio::sync::{mpsc, watch};
 
@@ -122,7 +123,7 @@ impl MessageProcessor {
                 _ = tokio::time::sleep(Duration::from_millis(100)) => {
                     // Try to get next message, starting with highest priority
                     if let Some(msg) = buffer.pop_any() {
-                        match Self::process_message(msg.clone()).await {
+                        match trace::scope(msg.trace.clone(), Self::process_message(msg.clone())).await {
                             ProcessingResult::Success => {
                                 debug!("Worker {} successfully processed message {}", worker_id, msg.id);
                             }
@@ -223,6 +224,8 @@ mod tests {
                 attempts: 0,
                 max_attempts: 3,
                 delay_until: None,
+                topic: None,
+                trace: None,
             },
             BufferedMessage {
                 id: Uuid::new_v4(),
@@ -232,6 +235,8 @@ mod tests {
                 attempts: 0,
                 max_attempts: 3,
                 delay_until: None,
+                topic: None,
+                trace: None,
             },
         ];
 
@@ -186,6 +186,8 @@ mod tests {
                 status: AgentStatus::Idle,
                 current_task: None,
                 last_heartbeat: Utc::now(),
+                system_prompt: None,
+                guardrails: Vec::new(),
             },
         };
 
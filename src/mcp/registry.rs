@@ -1,40 +1,376 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::sync::Arc;
-use tokio::sync::RwLock;
-use crate::agent::{Agent, Task, AgentStatus};
+use chrono::{DateTime, Utc};
+use futures::stream::{self, BoxStream, StreamExt};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::{broadcast, RwLock};
+use tracing::{error, warn};
+use crate::agent::{Agent, Task, AgentStatus, TaskStatus};
 use crate::error::NexaError;
+use crate::migrations::{self, DataKind};
+
+/// Journal of registry changes since the last compaction, relative to the runtime directory
+pub const REGISTRY_JOURNAL_FILE: &str = "agents.jsonl";
+
+/// Journal records after which the registry is compacted into its snapshot
+const COMPACT_AFTER: usize = 1000;
+
+/// Changes buffered for each watcher before it falls behind
+pub const WATCH_CAPACITY: usize = 256;
+
+/// A change to the registered agents or their tasks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum RegistryEvent {
+    /// An agent registered, or was restored from a previous run
+    Added { agent: Agent },
+    Removed { agent_id: String },
+    StatusChanged { agent_id: String, from: AgentStatus, to: AgentStatus },
+    /// A task was added, assigned, requeued or otherwise updated
+    TaskUpdated { task: Task },
+    TaskRemoved { task_id: String },
+}
+
+/// The agents and tasks of a registry at one point
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RegistrySnapshot {
+    pub agents: Vec<Agent>,
+    pub tasks: Vec<Task>,
+}
+
+/// How an agent last connected to the server
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AgentConnection {
+    /// Protocol version agreed in the handshake
+    pub protocol_version: u32,
+    /// Capabilities agreed in the handshake
+    pub capabilities: Vec<String>,
+    pub connected_at: DateTime<Utc>,
+}
 
 /// Registry for managing connected agents
 #[derive(Debug, Clone)]
 pub struct AgentRegistry {
     agents: Arc<RwLock<HashMap<String, Agent>>>,
+    /// Ids of the agents having each capability, locked after `agents`
+    index: Arc<RwLock<HashMap<String, HashSet<String>>>>,
     tasks: Arc<RwLock<HashMap<String, Task>>>,
+    /// SHA-256 of the auth token issued for each agent id
+    tokens: Arc<RwLock<HashMap<String, Vec<u8>>>>,
+    /// Connection of each agent registered over the server, locked after `index`
+    connections: Arc<RwLock<HashMap<String, AgentConnection>>>,
+    /// Files the registry is persisted to, once opened
+    store: Arc<parking_lot::Mutex<Option<RegistryStore>>>,
+    /// File the tasks are persisted to, once opened; locked under `tasks`
+    task_store: Arc<parking_lot::Mutex<Option<TaskStore>>>,
+    /// Changes sent to watchers, under the lock of what changed so they arrive in order
+    changes: broadcast::Sender<RegistryEvent>,
 }
 
 impl AgentRegistry {
     pub fn new() -> Self {
         Self {
             agents: Arc::new(RwLock::new(HashMap::new())),
+            index: Arc::new(RwLock::new(HashMap::new())),
             tasks: Arc::new(RwLock::new(HashMap::new())),
+            tokens: Arc::new(RwLock::new(HashMap::new())),
+            connections: Arc::new(RwLock::new(HashMap::new())),
+            store: Arc::new(parking_lot::Mutex::new(None)),
+            task_store: Arc::new(parking_lot::Mutex::new(None)),
+            changes: broadcast::channel(WATCH_CAPACITY).0,
+        }
+    }
+
+    /// Stream the registered agents as `Added` changes and the tasks as `TaskUpdated`,
+    /// then every change as it happens
+    ///
+    /// The stream ends if the watcher falls more than `WATCH_CAPACITY` changes behind;
+    /// watching again starts over from the current agents.
+    pub async fn watch(&self) -> BoxStream<'static, RegistryEvent> {
+        let (snapshot, rx) = self.subscribe().await;
+        let current: Vec<RegistryEvent> = snapshot.agents.into_iter()
+            .map(|agent| RegistryEvent::Added { agent })
+            .chain(snapshot.tasks.into_iter().map(|task| RegistryEvent::TaskUpdated { task }))
+            .collect();
+        let changes = stream::unfold(rx, |mut rx| async move {
+            match rx.recv().await {
+                Ok(event) => Some((event, rx)),
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("Registry watcher fell {} changes behind, ending its stream", skipped);
+                    None
+                }
+                Err(broadcast::error::RecvError::Closed) => None,
+            }
+        });
+        stream::iter(current).chain(changes).boxed()
+    }
+
+    /// The current agents and tasks, and a receiver of every change made after them
+    pub(crate) async fn subscribe(&self) -> (RegistrySnapshot, broadcast::Receiver<RegistryEvent>) {
+        // Same order as `assign_task`
+        let tasks = self.tasks.read().await;
+        let agents = self.agents.read().await;
+        let rx = self.changes.subscribe();
+        let snapshot = RegistrySnapshot {
+            agents: agents.values().cloned().collect(),
+            tasks: tasks.values().cloned().collect(),
+        };
+        (snapshot, rx)
+    }
+
+    /// Replace the agents and tasks with a snapshot replicated from another node
+    ///
+    /// Replicated agents have no connection to this node; they keep the status
+    /// the other node knew them by.
+    #[cfg(feature = "cluster")]
+    pub(crate) async fn restore(&self, snapshot: RegistrySnapshot) {
+        let mut tasks = self.tasks.write().await;
+        let mut agents = self.agents.write().await;
+        let mut index = self.index.write().await;
+        let mut connections = self.connections.write().await;
+        for agent_id in agents.keys() {
+            let _ = self.changes.send(RegistryEvent::Removed { agent_id: agent_id.clone() });
+        }
+        for task_id in tasks.keys() {
+            let _ = self.changes.send(RegistryEvent::TaskRemoved { task_id: task_id.clone() });
+        }
+        agents.clear();
+        index.clear();
+        connections.clear();
+        tasks.clear();
+        for agent in snapshot.agents {
+            index_agent(&mut index, &agent);
+            let _ = self.changes.send(RegistryEvent::Added { agent: agent.clone() });
+            agents.insert(agent.id.clone(), agent);
+        }
+        for task in snapshot.tasks {
+            let _ = self.changes.send(RegistryEvent::TaskUpdated { task: task.clone() });
+            tasks.insert(task.id.clone(), task);
+        }
+        self.persist_tasks(&tasks);
+        if let Some(store) = self.store.lock().as_mut() {
+            if let Err(e) = store.compact(stored_agents(&agents, &connections)) {
+                error!("Failed to persist the agent registry: {}", e);
+            }
+        }
+    }
+
+    /// Apply a change replicated from another node
+    #[cfg(feature = "cluster")]
+    pub(crate) async fn apply(&self, event: RegistryEvent) {
+        let result = match event {
+            RegistryEvent::Added { agent } => {
+                // The other node accepted the registration, so it replaces any known agent
+                let mut agents = self.agents.write().await;
+                let mut index = self.index.write().await;
+                if let Some(known) = agents.get(&agent.id) {
+                    unindex_agent(&mut index, known);
+                }
+                index_agent(&mut index, &agent);
+                let mut connections = self.connections.write().await;
+                connections.remove(&agent.id);
+                agents.insert(agent.id.clone(), agent.clone());
+                let _ = self.changes.send(RegistryEvent::Added { agent: agent.clone() });
+                self.journal(JournalRecord::Register { agent, connection: None }, &agents, &connections);
+                Ok(())
+            }
+            RegistryEvent::Removed { agent_id } => self.deregister(&agent_id).await,
+            RegistryEvent::StatusChanged { agent_id, to, .. } => self.update_status(&agent_id, to).await,
+            RegistryEvent::TaskUpdated { task } => self.update_task(task).await,
+            RegistryEvent::TaskRemoved { task_id } => self.remove_task(&task_id).await,
+        };
+        if let Err(e) = result {
+            tracing::debug!("Skipping replicated change: {}", e);
+        }
+    }
+
+    /// Persist the registry in `runtime_dir`, first restoring the agents and tasks persisted there
+    ///
+    /// Restored agents are `Offline` until they register again, and tasks that were in
+    /// progress are `Interrupted`. Tasks already known, as recovered from a crash, are kept.
+    /// Returns how many agents were restored.
+    pub async fn open(&self, runtime_dir: &Path) -> Result<usize, NexaError> {
+        let task_store = TaskStore { path: runtime_dir.join(DataKind::Tasks.file_name()) };
+        let stored_tasks = task_store.load()?;
+        {
+            let mut tasks = self.tasks.write().await;
+            for mut task in stored_tasks {
+                if tasks.contains_key(&task.id) {
+                    continue;
+                }
+                if task.status == TaskStatus::InProgress {
+                    task.status = TaskStatus::Interrupted;
+                }
+                let _ = self.changes.send(RegistryEvent::TaskUpdated { task: task.clone() });
+                tasks.insert(task.id.clone(), task);
+            }
+            task_store.save(&tasks)?;
+            *self.task_store.lock() = Some(task_store);
+        }
+
+        let mut store = RegistryStore {
+            snapshot: runtime_dir.join(DataKind::Agents.file_name()),
+            journal: runtime_dir.join(REGISTRY_JOURNAL_FILE),
+            records: 0,
+        };
+        let stored = store.load()?;
+
+        let mut agents = self.agents.write().await;
+        let mut index = self.index.write().await;
+        let mut connections = self.connections.write().await;
+        let mut restored = 0;
+        for StoredAgent { mut agent, connection } in stored {
+            if agents.contains_key(&agent.id) {
+                continue;
+            }
+            agent.status = AgentStatus::Offline;
+            index_agent(&mut index, &agent);
+            if let Some(connection) = connection {
+                connections.insert(agent.id.clone(), connection);
+            }
+            let _ = self.changes.send(RegistryEvent::Added { agent: agent.clone() });
+            agents.insert(agent.id.clone(), agent);
+            restored += 1;
+        }
+        store.compact(stored_agents(&agents, &connections))?;
+        *self.store.lock() = Some(store);
+        Ok(restored)
+    }
+
+    /// Rewrite the persisted registry as a snapshot of the current agents, emptying the journal
+    ///
+    /// Does nothing until the registry is opened.
+    pub async fn compact(&self) -> Result<(), NexaError> {
+        let agents = self.agents.read().await;
+        let connections = self.connections.read().await;
+        match self.store.lock().as_mut() {
+            Some(store) => store.compact(stored_agents(&agents, &connections)),
+            None => Ok(()),
+        }
+    }
+
+    /// Rewrite the persisted tasks, while holding the lock of `tasks`
+    fn persist_tasks(&self, tasks: &HashMap<String, Task>) {
+        if let Some(store) = self.task_store.lock().as_ref() {
+            if let Err(e) = store.save(tasks) {
+                error!("Failed to persist tasks: {}", e);
+            }
+        }
+    }
+
+    /// Journal a change, compacting once the journal grows long
+    fn journal(&self, record: JournalRecord, agents: &HashMap<String, Agent>, connections: &HashMap<String, AgentConnection>) {
+        let mut store = self.store.lock();
+        let Some(store) = store.as_mut() else { return };
+        let result = store.append(&record).and_then(|()| {
+            if store.records >= COMPACT_AFTER {
+                store.compact(stored_agents(agents, connections))
+            } else {
+                Ok(())
+            }
+        });
+        if let Err(e) = result {
+            error!("Failed to persist the agent registry: {}", e);
+        }
+    }
+
+    /// Issue the auth token an agent registers with
+    ///
+    /// Only the token's hash is kept, so it cannot be shown again; an agent that already
+    /// has one gets a new token with `rotate_token`.
+    pub async fn issue_token(&self, agent_id: &str) -> Result<String, NexaError> {
+        let mut tokens = self.tokens.write().await;
+        if tokens.contains_key(agent_id) {
+            return Err(NexaError::agent(format!("Agent {} already has a token", agent_id)));
+        }
+        let token = new_token();
+        tokens.insert(agent_id.to_string(), hash_token(&token));
+        Ok(token)
+    }
+
+    /// Replace an agent's token, invalidating the old one
+    pub async fn rotate_token(&self, agent_id: &str) -> Result<String, NexaError> {
+        let mut tokens = self.tokens.write().await;
+        let hash = tokens.get_mut(agent_id)
+            .ok_or_else(|| NexaError::not_found(format!("No token issued for agent {}", agent_id)))?;
+        let token = new_token();
+        *hash = hash_token(&token);
+        Ok(token)
+    }
+
+    /// Revoke an agent's token; it can no longer register with it
+    pub async fn revoke_token(&self, agent_id: &str) -> Result<(), NexaError> {
+        self.tokens.write().await.remove(agent_id)
+            .map(|_| ())
+            .ok_or_else(|| NexaError::not_found(format!("No token issued for agent {}", agent_id)))
+    }
+
+    /// Whether a token was issued for the agent
+    pub async fn has_token(&self, agent_id: &str) -> bool {
+        self.tokens.read().await.contains_key(agent_id)
+    }
+
+    /// Check the token an agent presents
+    ///
+    /// Agents with an issued token must present it. Agents without one only pass when
+    /// tokens are not `required`.
+    pub async fn verify_token(&self, agent_id: &str, token: Option<&str>, required: bool) -> Result<(), NexaError> {
+        let tokens = self.tokens.read().await;
+        match (tokens.get(agent_id), token) {
+            (Some(hash), Some(token)) if *hash == hash_token(token) => Ok(()),
+            (Some(_), _) => Err(NexaError::unauthorized(format!("Invalid token for agent {}", agent_id))),
+            (None, _) if required => Err(NexaError::unauthorized(format!("No token issued for agent {}", agent_id))),
+            (None, _) => Ok(()),
         }
     }
 
     /// Register a new agent
     pub async fn register(&self, agent: Agent) -> Result<(), NexaError> {
+        self.insert(agent, None).await
+    }
+
+    /// Register an agent connecting over the server, recording its connection
+    pub async fn register_connected(&self, agent: Agent, connection: AgentConnection) -> Result<(), NexaError> {
+        self.insert(agent, Some(connection)).await
+    }
+
+    async fn insert(&self, agent: Agent, connection: Option<AgentConnection>) -> Result<(), NexaError> {
         let mut agents = self.agents.write().await;
-        if agents.contains_key(&agent.id) {
-            return Err(NexaError::agent("Agent already registered"));
+        let mut index = self.index.write().await;
+        // Offline agents, such as those restored from a previous run, may register again
+        match agents.get(&agent.id) {
+            Some(known) if known.status != AgentStatus::Offline => {
+                return Err(NexaError::agent("Agent already registered"));
+            }
+            Some(known) => unindex_agent(&mut index, known),
+            None => {}
         }
-        agents.insert(agent.id.clone(), agent);
+        index_agent(&mut index, &agent);
+        let mut connections = self.connections.write().await;
+        match &connection {
+            Some(connection) => connections.insert(agent.id.clone(), connection.clone()),
+            None => connections.remove(&agent.id),
+        };
+        agents.insert(agent.id.clone(), agent.clone());
+        let _ = self.changes.send(RegistryEvent::Added { agent: agent.clone() });
+        self.journal(JournalRecord::Register { agent, connection }, &agents, &connections);
         Ok(())
     }
 
     /// Deregister an agent
     pub async fn deregister(&self, agent_id: &str) -> Result<(), NexaError> {
         let mut agents = self.agents.write().await;
-        if agents.remove(agent_id).is_none() {
-            return Err(NexaError::agent("Agent not found"));
-        }
+        let agent = agents.remove(agent_id).ok_or_else(|| NexaError::not_found("Agent not found"))?;
+        unindex_agent(&mut *self.index.write().await, &agent);
+        let mut connections = self.connections.write().await;
+        connections.remove(agent_id);
+        let _ = self.changes.send(RegistryEvent::Removed { agent_id: agent_id.to_string() });
+        self.journal(JournalRecord::Deregister { agent_id: agent_id.to_string() }, &agents, &connections);
         Ok(())
     }
 
@@ -44,18 +380,33 @@ impl AgentRegistry {
         agents
             .get(agent_id)
             .cloned()
-            .ok_or_else(|| NexaError::agent("Agent not found"))
+            .ok_or_else(|| NexaError::not_found("Agent not found"))
     }
 
     /// Update agent status
     pub async fn update_status(&self, agent_id: &str, status: AgentStatus) -> Result<(), NexaError> {
         let mut agents = self.agents.write().await;
-        if let Some(agent) = agents.get_mut(agent_id) {
-            agent.status = status;
-            Ok(())
-        } else {
-            Err(NexaError::agent("Agent not found"))
+        let agent = agents.get_mut(agent_id).ok_or_else(|| NexaError::not_found("Agent not found"))?;
+        let from = std::mem::replace(&mut agent.status, status);
+        if from != status {
+            let _ = self.changes.send(RegistryEvent::StatusChanged { agent_id: agent_id.to_string(), from, to: status });
         }
+        let connections = self.connections.read().await;
+        self.journal(JournalRecord::Status { agent_id: agent_id.to_string(), status }, &agents, &connections);
+        Ok(())
+    }
+
+    /// How an agent last connected, if it registered over the server
+    pub async fn connection(&self, agent_id: &str) -> Option<AgentConnection> {
+        self.connections.read().await.get(agent_id).cloned()
+    }
+
+    /// Record that an agent was heard from
+    pub async fn touch(&self, agent_id: &str) -> Result<(), NexaError> {
+        let mut agents = self.agents.write().await;
+        agents.get_mut(agent_id)
+            .map(Agent::update_heartbeat)
+            .ok_or_else(|| NexaError::not_found("Agent not found"))
     }
 
     /// List all registered agents
@@ -67,22 +418,53 @@ impl AgentRegistry {
     /// Find agents by capability
     pub async fn find_by_capability(&self, capability: &str) -> Vec<Agent> {
         let agents = self.agents.read().await;
-        agents
-            .values()
-            .filter(|agent| agent.has_capability(capability))
-            .cloned()
+        let index = self.index.read().await;
+        index
+            .get(capability)
+            .into_iter()
+            .flatten()
+            .filter_map(|id| agents.get(id).cloned())
             .collect()
     }
 
+    /// Find agents matching a capability expression
+    pub async fn query(&self, query: &CapabilityQuery) -> Vec<Agent> {
+        let agents = self.agents.read().await;
+        let index = self.index.read().await;
+        let mut matched = HashSet::new();
+        for all in &query.alternatives {
+            let mut sets: Vec<&HashSet<String>> = Vec::with_capacity(all.len());
+            for capability in all {
+                match index.get(capability) {
+                    Some(ids) => sets.push(ids),
+                    None => break,
+                }
+            }
+            if sets.len() < all.len() {
+                continue;
+            }
+            // Intersect from the rarest capability
+            sets.sort_by_key(|ids| ids.len());
+            let Some((rarest, rest)) = sets.split_first() else { continue };
+            matched.extend(rarest.iter().filter(|id| rest.iter().all(|ids| ids.contains(*id))));
+        }
+        matched.into_iter().filter_map(|id| agents.get(id).cloned()).collect()
+    }
+
     pub async fn add_task(&self, task: Task) -> Result<(), NexaError> {
         let mut tasks = self.tasks.write().await;
+        let _ = self.changes.send(RegistryEvent::TaskUpdated { task: task.clone() });
         tasks.insert(task.id.clone(), task);
+        self.persist_tasks(&tasks);
         Ok(())
     }
 
     pub async fn remove_task(&self, id: &str) -> Result<(), NexaError> {
         let mut tasks = self.tasks.write().await;
-        tasks.remove(id);
+        if tasks.remove(id).is_some() {
+            let _ = self.changes.send(RegistryEvent::TaskRemoved { task_id: id.to_string() });
+            self.persist_tasks(&tasks);
+        }
         Ok(())
     }
 
@@ -91,7 +473,7 @@ impl AgentRegistry {
         tasks
             .get(id)
             .cloned()
-            .ok_or_else(|| NexaError::system(format!("Task not found: {}", id)))
+            .ok_or_else(|| NexaError::not_found(format!("Task not found: {}", id)))
     }
 
     pub async fn list_tasks(&self) -> Result<Vec<Task>, NexaError> {
@@ -101,24 +483,54 @@ impl AgentRegistry {
 
     pub async fn update_task(&self, task: Task) -> Result<(), NexaError> {
         let mut tasks = self.tasks.write().await;
+        let _ = self.changes.send(RegistryEvent::TaskUpdated { task: task.clone() });
         tasks.insert(task.id.clone(), task);
+        self.persist_tasks(&tasks);
         Ok(())
     }
 
+    /// Move a task on to `status`, freeing its agent once the task is finished
+    ///
+    /// Tasks already completed, failed or cancelled cannot change status again.
+    pub async fn set_task_status(&self, task_id: &str, status: TaskStatus) -> Result<Task, NexaError> {
+        let mut tasks = self.tasks.write().await;
+        let mut agents = self.agents.write().await;
+
+        let task = tasks
+            .get_mut(task_id)
+            .ok_or_else(|| NexaError::not_found(format!("Task not found: {}", task_id)))?;
+        if is_finished(task.status) {
+            return Err(NexaError::invalid_input(format!("Task {} is already {:?}", task_id, task.status)));
+        }
+        task.status = status;
+        if is_finished(status) {
+            let agent = task.assigned_agent.as_ref().and_then(|agent_id| agents.get_mut(agent_id));
+            if let Some(agent) = agent.filter(|agent| agent.current_task.as_deref() == Some(task_id)) {
+                agent.current_task = None;
+            }
+        }
+        let task = task.clone();
+        let _ = self.changes.send(RegistryEvent::TaskUpdated { task: task.clone() });
+        self.persist_tasks(&tasks);
+        Ok(task)
+    }
+
     pub async fn assign_task(&self, task_id: &str, agent_id: &str) -> Result<(), NexaError> {
         let mut tasks = self.tasks.write().await;
         let mut agents = self.agents.write().await;
 
         let task = tasks
             .get_mut(task_id)
-            .ok_or_else(|| NexaError::system(format!("Task not found: {}", task_id)))?;
+            .ok_or_else(|| NexaError::not_found(format!("Task not found: {}", task_id)))?;
 
         let agent = agents
             .get_mut(agent_id)
-            .ok_or_else(|| NexaError::system(format!("Agent not found: {}", agent_id)))?;
+            .ok_or_else(|| NexaError::not_found(format!("Agent not found: {}", agent_id)))?;
 
         task.assigned_agent = Some(agent_id.to_string());
         agent.current_task = Some(task_id.to_string());
+        let _ = self.changes.send(RegistryEvent::TaskUpdated { task: task.clone() });
+        self.persist_tasks(&tasks);
 
         Ok(())
     }
@@ -129,7 +541,7 @@ impl AgentRegistry {
 
         let task = tasks
             .get_mut(task_id)
-            .ok_or_else(|| NexaError::system(format!("Task not found: {}", task_id)))?;
+            .ok_or_else(|| NexaError::not_found(format!("Task not found: {}", task_id)))?;
 
         if let Some(agent_id) = &task.assigned_agent {
             if let Some(agent) = agents.get_mut(agent_id) {
@@ -138,8 +550,228 @@ impl AgentRegistry {
         }
 
         task.assigned_agent = None;
+        let _ = self.changes.send(RegistryEvent::TaskUpdated { task: task.clone() });
+        self.persist_tasks(&tasks);
         Ok(())
     }
+
+    /// Put the unfinished tasks of an agent back to pending and unassigned
+    ///
+    /// Returns the ids of the requeued tasks.
+    pub async fn requeue_tasks(&self, agent_id: &str) -> Result<Vec<String>, NexaError> {
+        let mut tasks = self.tasks.write().await;
+        let mut agents = self.agents.write().await;
+
+        let mut requeued = Vec::new();
+        for task in tasks.values_mut() {
+            let unfinished = matches!(task.status, TaskStatus::Pending | TaskStatus::InProgress | TaskStatus::Interrupted);
+            if task.assigned_agent.as_deref() == Some(agent_id) && unfinished {
+                task.assigned_agent = None;
+                task.status = TaskStatus::Pending;
+                requeued.push(task.id.clone());
+                let _ = self.changes.send(RegistryEvent::TaskUpdated { task: task.clone() });
+            }
+        }
+        if let Some(agent) = agents.get_mut(agent_id) {
+            agent.current_task = None;
+        }
+        if !requeued.is_empty() {
+            self.persist_tasks(&tasks);
+        }
+        Ok(requeued)
+    }
+}
+
+/// Capability expression agents are queried with
+///
+/// `&` joins capabilities an agent needs all of and binds tighter than `|`, which joins
+/// alternatives: `rust & review | python` matches agents with both `rust` and `review`,
+/// or with `python`. A single capability name is the simplest expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapabilityQuery {
+    alternatives: Vec<Vec<String>>,
+}
+
+impl CapabilityQuery {
+    /// Query for agents having one capability
+    pub fn capability(capability: impl Into<String>) -> Self {
+        Self { alternatives: vec![vec![capability.into()]] }
+    }
+}
+
+impl FromStr for CapabilityQuery {
+    type Err = NexaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let alternatives = s.split('|')
+            .map(|all| all.split('&').map(|c| c.trim().to_string()).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        if alternatives.iter().flatten().any(String::is_empty) {
+            return Err(NexaError::invalid_input(format!("Invalid capability expression '{}'", s)));
+        }
+        Ok(Self { alternatives })
+    }
+}
+
+fn index_agent(index: &mut HashMap<String, HashSet<String>>, agent: &Agent) {
+    for capability in &agent.capabilities {
+        index.entry(capability.clone()).or_default().insert(agent.id.clone());
+    }
+}
+
+fn unindex_agent(index: &mut HashMap<String, HashSet<String>>, agent: &Agent) {
+    for capability in &agent.capabilities {
+        if let Some(ids) = index.get_mut(capability) {
+            ids.remove(&agent.id);
+            if ids.is_empty() {
+                index.remove(capability);
+            }
+        }
+    }
+}
+
+/// A registered agent as persisted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredAgent {
+    agent: Agent,
+    #[serde(default)]
+    connection: Option<AgentConnection>,
+}
+
+fn stored_agents(agents: &HashMap<String, Agent>, connections: &HashMap<String, AgentConnection>) -> Vec<StoredAgent> {
+    agents.values()
+        .map(|agent| StoredAgent { agent: agent.clone(), connection: connections.get(&agent.id).cloned() })
+        .collect()
+}
+
+/// A change to the registry, as journaled
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum JournalRecord {
+    Register { agent: Agent, connection: Option<AgentConnection> },
+    Status { agent_id: String, status: AgentStatus },
+    Deregister { agent_id: String },
+}
+
+/// Snapshot of the registry in `agents.json`, and a journal of the changes since
+#[derive(Debug)]
+struct RegistryStore {
+    snapshot: PathBuf,
+    journal: PathBuf,
+    /// Records journaled since the last compaction
+    records: usize,
+}
+
+impl RegistryStore {
+    /// The persisted agents, replaying the journal over the snapshot
+    fn load(&self) -> Result<Vec<StoredAgent>, NexaError> {
+        let mut agents = HashMap::new();
+        if self.snapshot.exists() {
+            let data = migrations::read_versioned(DataKind::Agents, &self.snapshot)?;
+            let stored: Vec<StoredAgent> = serde_json::from_value(data)
+                .map_err(|e| NexaError::config(format!("Failed to parse {:?}: {}", self.snapshot, e)))?;
+            agents.extend(stored.into_iter().map(|stored| (stored.agent.id.clone(), stored)));
+        }
+        if self.journal.exists() {
+            let contents = fs::read_to_string(&self.journal)
+                .map_err(|e| NexaError::system(format!("Failed to read {:?}: {}", self.journal, e)))?;
+            for (number, line) in contents.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+                match serde_json::from_str(line) {
+                    Ok(JournalRecord::Register { agent, connection }) => {
+                        agents.insert(agent.id.clone(), StoredAgent { agent, connection });
+                    }
+                    Ok(JournalRecord::Status { agent_id, status }) => {
+                        if let Some(stored) = agents.get_mut(&agent_id) {
+                            stored.agent.status = status;
+                        }
+                    }
+                    Ok(JournalRecord::Deregister { agent_id }) => {
+                        agents.remove(&agent_id);
+                    }
+                    // A crash can leave the last record half written
+                    Err(e) => warn!("Skipping unreadable record {} of {:?}: {}", number + 1, self.journal, e),
+                }
+            }
+        }
+        Ok(agents.into_values().collect())
+    }
+
+    fn append(&mut self, record: &JournalRecord) -> Result<(), NexaError> {
+        let line = serde_json::to_string(record)?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.journal)
+            .map_err(|e| NexaError::system(format!("Failed to open {:?}: {}", self.journal, e)))?;
+        writeln!(file, "{}", line)
+            .map_err(|e| NexaError::system(format!("Failed to write {:?}: {}", self.journal, e)))?;
+        self.records += 1;
+        Ok(())
+    }
+
+    /// Replace the snapshot and empty the journal; replaying the journal over the new
+    /// snapshot changes nothing, so a crash in between loses nothing
+    fn compact(&mut self, agents: Vec<StoredAgent>) -> Result<(), NexaError> {
+        migrations::write_versioned(DataKind::Agents, &self.snapshot, serde_json::to_value(agents)?)?;
+        if self.journal.exists() {
+            fs::remove_file(&self.journal)
+                .map_err(|e| NexaError::system(format!("Failed to remove {:?}: {}", self.journal, e)))?;
+        }
+        self.records = 0;
+        Ok(())
+    }
+}
+
+/// Check that every record of a registry journal parses, returning how many it holds;
+/// only the last record may be unreadable, as a crash can leave it half written
+pub fn check_journal(path: &Path) -> Result<usize, NexaError> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| NexaError::system(format!("Failed to read {:?}: {}", path, e)))?;
+    let lines: Vec<&str> = contents.lines().filter(|line| !line.trim().is_empty()).collect();
+    for (number, line) in lines.iter().enumerate() {
+        if let Err(e) = serde_json::from_str::<JournalRecord>(line) {
+            if number + 1 < lines.len() {
+                return Err(NexaError::config(format!("Record {} of {:?} is unreadable: {}", number + 1, path, e)));
+            }
+        }
+    }
+    Ok(lines.len())
+}
+
+/// Tasks in `tasks.json`, rewritten whole on every change
+#[derive(Debug)]
+struct TaskStore {
+    path: PathBuf,
+}
+
+impl TaskStore {
+    fn load(&self) -> Result<Vec<Task>, NexaError> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        let data = migrations::read_versioned(DataKind::Tasks, &self.path)?;
+        serde_json::from_value(data)
+            .map_err(|e| NexaError::config(format!("Failed to parse {:?}: {}", self.path, e)))
+    }
+
+    fn save(&self, tasks: &HashMap<String, Task>) -> Result<(), NexaError> {
+        let mut tasks: Vec<&Task> = tasks.values().collect();
+        tasks.sort_by(|a, b| (a.created_at, &a.id).cmp(&(b.created_at, &b.id)));
+        migrations::write_versioned(DataKind::Tasks, &self.path, serde_json::to_value(tasks)?)
+    }
+}
+
+/// Whether a task reached a status it does not leave
+pub fn is_finished(status: TaskStatus) -> bool {
+    matches!(status, TaskStatus::Completed | TaskStatus::Failed | TaskStatus::Cancelled)
+}
+
+fn new_token() -> String {
+    format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple())
+}
+
+fn hash_token(token: &str) -> Vec<u8> {
+    Sha256::digest(token.as_bytes()).to_vec()
 }
 
 #[cfg(test)]
@@ -157,9 +789,176 @@ mod tests {
             status: AgentStatus::Idle,
             current_task: None,
             last_heartbeat: Utc::now(),
+            system_prompt: None,
+            guardrails: Vec::new(),
         };
 
         assert!(registry.register(agent.clone()).await.is_ok());
         assert!(registry.deregister("test-1").await.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_capability_query() {
+        let registry = AgentRegistry::new();
+        let agent = |name: &str, capabilities: &[&str]| {
+            Agent::new(name.to_string(), capabilities.iter().map(|c| c.to_string()).collect())
+        };
+        let rust = agent("rust", &["rust", "review"]);
+        let python = agent("python", &["python", "review"]);
+        let writer = agent("writer", &["docs"]);
+        for agent in [&rust, &python, &writer] {
+            registry.register(agent.clone()).await.unwrap();
+        }
+        let names = |agents: Vec<Agent>| {
+            let mut names: Vec<String> = agents.into_iter().map(|a| a.name).collect();
+            names.sort();
+            names
+        };
+
+        assert_eq!(names(registry.find_by_capability("review").await), vec!["python", "rust"]);
+        assert!(registry.find_by_capability("go").await.is_empty());
+        let query = |s: &str| s.parse::<CapabilityQuery>().unwrap();
+        assert_eq!(query("review"), CapabilityQuery::capability("review"));
+        assert_eq!(names(registry.query(&query("review & rust")).await), vec!["rust"]);
+        assert_eq!(names(registry.query(&query("rust & review | docs")).await), vec!["rust", "writer"]);
+        assert_eq!(names(registry.query(&query("go & review | python")).await), vec!["python"]);
+        assert!("review &".parse::<CapabilityQuery>().unwrap_err().is_user_error());
+
+        // Deregistered agents leave the index
+        registry.deregister(&rust.id).await.unwrap();
+        assert_eq!(names(registry.query(&query("review")).await), vec!["python"]);
+        assert!(registry.query(&query("rust")).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_persistence() {
+        let dir = tempfile::tempdir().unwrap();
+        let registry = AgentRegistry::new();
+        assert_eq!(registry.open(dir.path()).await.unwrap(), 0);
+        let connected = Agent::new("connected".to_string(), vec!["review".to_string()]);
+        let local = Agent::new("local".to_string(), vec![]);
+        let gone = Agent::new("gone".to_string(), vec![]);
+        let connection = AgentConnection {
+            protocol_version: 2,
+            capabilities: vec!["heartbeat".to_string()],
+            connected_at: Utc::now(),
+        };
+        registry.register_connected(connected.clone(), connection.clone()).await.unwrap();
+        registry.register(local.clone()).await.unwrap();
+        registry.register(gone.clone()).await.unwrap();
+        registry.update_status(&local.id, AgentStatus::Busy).await.unwrap();
+        registry.deregister(&gone.id).await.unwrap();
+        assert!(dir.path().join(REGISTRY_JOURNAL_FILE).exists());
+
+        // A restart restores the agents offline, compacting the journal into the snapshot
+        let restarted = AgentRegistry::new();
+        assert_eq!(restarted.open(dir.path()).await.unwrap(), 2);
+        assert!(!dir.path().join(REGISTRY_JOURNAL_FILE).exists());
+        assert!(dir.path().join("agents.json").exists());
+        assert!(restarted.get_agent(&gone.id).await.is_err());
+        for agent in [&connected, &local] {
+            assert_eq!(restarted.get_agent(&agent.id).await.unwrap().status, AgentStatus::Offline);
+        }
+        assert_eq!(restarted.connection(&connected.id).await, Some(connection));
+        assert_eq!(restarted.find_by_capability("review").await.len(), 1);
+
+        // Restored agents may register again, but only while offline
+        restarted.register(local.clone()).await.unwrap();
+        assert!(restarted.register(local.clone()).await.is_err());
+        assert_eq!(restarted.get_agent(&local.id).await.unwrap().status, local.status);
+
+        restarted.compact().await.unwrap();
+        assert!(!dir.path().join(REGISTRY_JOURNAL_FILE).exists());
+        let reopened = AgentRegistry::new();
+        assert_eq!(reopened.open(dir.path()).await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_task_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let registry = AgentRegistry::new();
+        registry.open(dir.path()).await.unwrap();
+        let agent = Agent::new("worker".to_string(), vec![]);
+        registry.register(agent.clone()).await.unwrap();
+        let task = |title: &str| Task::new(title.to_string(), String::new(), Vec::new(), Vec::new(), None, 60, 1);
+        let (running, done, dropped) = (task("running"), task("done"), task("dropped"));
+        for task in [&running, &done, &dropped] {
+            registry.add_task(task.clone()).await.unwrap();
+        }
+        registry.assign_task(&running.id, &agent.id).await.unwrap();
+        registry.set_task_status(&running.id, TaskStatus::InProgress).await.unwrap();
+        registry.assign_task(&done.id, &agent.id).await.unwrap();
+        registry.set_task_status(&done.id, TaskStatus::Completed).await.unwrap();
+        registry.remove_task(&dropped.id).await.unwrap();
+
+        // Finishing a task frees its agent, and finished tasks stay finished
+        assert_eq!(registry.get_agent(&agent.id).await.unwrap().current_task, None);
+        let err = registry.set_task_status(&done.id, TaskStatus::Cancelled).await.unwrap_err();
+        assert!(matches!(err, NexaError::InvalidInput(_)));
+
+        // A restart restores the tasks, those in progress as interrupted
+        let restarted = AgentRegistry::new();
+        restarted.open(dir.path()).await.unwrap();
+        assert_eq!(restarted.list_tasks().await.unwrap().len(), 2);
+        let interrupted = restarted.get_task(&running.id).await.unwrap();
+        assert_eq!(interrupted.status, TaskStatus::Interrupted);
+        assert_eq!(interrupted.assigned_agent, Some(agent.id.clone()));
+        assert_eq!(restarted.get_task(&done.id).await.unwrap().status, TaskStatus::Completed);
+        assert!(restarted.get_task(&dropped.id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_watch() {
+        let registry = AgentRegistry::new();
+        let existing = Agent::new("existing".to_string(), vec![]);
+        let joining = Agent::new("joining".to_string(), vec![]);
+        registry.register(existing.clone()).await.unwrap();
+
+        let mut changes = registry.watch().await;
+        registry.register(joining.clone()).await.unwrap();
+        registry.update_status(&joining.id, AgentStatus::Busy).await.unwrap();
+        // Unchanged statuses are not reported
+        registry.update_status(&joining.id, AgentStatus::Busy).await.unwrap();
+        registry.deregister(&existing.id).await.unwrap();
+
+        assert!(matches!(changes.next().await, Some(RegistryEvent::Added { agent }) if agent.id == existing.id));
+        assert!(matches!(changes.next().await, Some(RegistryEvent::Added { agent }) if agent.id == joining.id));
+        assert!(matches!(
+            changes.next().await,
+            Some(RegistryEvent::StatusChanged { agent_id, to: AgentStatus::Busy, .. }) if agent_id == joining.id
+        ));
+        assert!(matches!(changes.next().await, Some(RegistryEvent::Removed { agent_id }) if agent_id == existing.id));
+
+        // Watchers that fall too far behind are ended
+        for _ in 0..WATCH_CAPACITY {
+            registry.update_status(&joining.id, AgentStatus::Idle).await.unwrap();
+            registry.update_status(&joining.id, AgentStatus::Busy).await.unwrap();
+        }
+        assert!(changes.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_agent_tokens() {
+        let registry = AgentRegistry::new();
+        assert!(registry.verify_token("agent-1", None, false).await.is_ok());
+        assert!(registry.verify_token("agent-1", None, true).await.is_err());
+
+        let token = registry.issue_token("agent-1").await.unwrap();
+        assert!(registry.issue_token("agent-1").await.is_err());
+        assert!(registry.verify_token("agent-1", Some(&token), true).await.is_ok());
+        // Once issued, the token is needed even when tokens are optional
+        let err = registry.verify_token("agent-1", None, false).await.unwrap_err();
+        assert_eq!(err.http_status(), 401);
+        assert!(registry.verify_token("agent-1", Some("guess"), false).await.is_err());
+
+        let rotated = registry.rotate_token("agent-1").await.unwrap();
+        assert_ne!(rotated, token);
+        assert!(registry.verify_token("agent-1", Some(&token), true).await.is_err());
+        assert!(registry.verify_token("agent-1", Some(&rotated), true).await.is_ok());
+
+        registry.revoke_token("agent-1").await.unwrap();
+        assert!(!registry.has_token("agent-1").await);
+        assert!(registry.verify_token("agent-1", Some(&rotated), true).await.is_err());
+        assert!(registry.rotate_token("agent-1").await.is_err());
+    }
 }
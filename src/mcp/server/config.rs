@@ -1,7 +1,19 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use std::time::Duration;
 
+/// Protocol agents connect with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    /// WebSocket, also answering Model Context Protocol requests
+    #[default]
+    WebSocket,
+    /// gRPC bidirectional streams (requires the `grpc` feature)
+    Grpc,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServerConfig {
     /// Server bind address
@@ -20,6 +32,31 @@ pub struct ServerConfig {
     pub log_level: String,
     /// Enable metrics collection
     pub enable_metrics: bool,
+    /// Fall back to an ephemeral port when the configured one is busy
+    pub auto_port: bool,
+    /// Protocol agents connect with
+    #[serde(default)]
+    pub transport: Transport,
+    /// Refuse messages from connections that have not made a `ClientHello` handshake
+    #[serde(default)]
+    pub require_handshake: bool,
+    /// Refuse to register agents that were not issued a token
+    #[serde(default)]
+    pub require_agent_token: bool,
+    /// Interval between pings to agents that negotiated heartbeats
+    #[serde(default = "default_heartbeat_interval")]
+    pub heartbeat_interval: Duration,
+    /// Heartbeats an agent may miss before it is taken offline
+    #[serde(default = "default_missed_heartbeats")]
+    pub missed_heartbeats: u32,
+}
+
+fn default_heartbeat_interval() -> Duration {
+    Duration::from_secs(15)
+}
+
+fn default_missed_heartbeats() -> u32 {
+    3
 }
 
 impl Default for ServerConfig {
@@ -33,6 +70,12 @@ impl Default for ServerConfig {
             runtime_dir: PathBuf::from("/tmp"),
             log_level: "info".to_string(),
             enable_metrics: true,
+            auto_port: true,
+            transport: Transport::default(),
+            require_handshake: false,
+            require_agent_token: false,
+            heartbeat_interval: default_heartbeat_interval(),
+            missed_heartbeats: default_missed_heartbeats(),
         }
     }
 }
@@ -77,6 +120,32 @@ impl ServerConfig {
         self
     }
 
+    pub fn with_auto_port(mut self, enabled: bool) -> Self {
+        self.auto_port = enabled;
+        self
+    }
+
+    pub fn with_transport(mut self, transport: Transport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    pub fn with_require_handshake(mut self, required: bool) -> Self {
+        self.require_handshake = required;
+        self
+    }
+
+    pub fn with_require_agent_token(mut self, required: bool) -> Self {
+        self.require_agent_token = required;
+        self
+    }
+
+    pub fn with_heartbeat(mut self, interval: Duration, missed: u32) -> Self {
+        self.heartbeat_interval = interval;
+        self.missed_heartbeats = missed;
+        self
+    }
+
     pub fn with_metrics_enabled(mut self, enabled: bool) -> Self {
         self.enable_metrics = enabled;
         self
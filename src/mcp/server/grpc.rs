@@ -0,0 +1,280 @@
+//! gRPC transport for agent communication
+//!
+//! Serves `nexa.mcp.AgentTransport/Exchange` from `proto/agent_transport.proto`:
+//! - One bidirectional stream per agent, carrying the messages of the WebSocket protocol
+//! - Each `Envelope` holds one JSON message, so both transports share their handling
+//! - Events are forwarded on the stream like they are to WebSocket clients
+//!
+//! Built directly on HTTP/2 with hand-written framing, since the only message type
+//! is a single string field.
+
+use std::net::SocketAddr;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use h2::server::SendResponse;
+use h2::{RecvStream, SendStream};
+use http::{HeaderMap, HeaderValue, Request, Response};
+use tokio::net::TcpStream;
+use tracing::{debug, error};
+use crate::error::NexaError;
+use crate::mcp::MCPConnection;
+use super::{next_event, Server};
+
+/// Path of the `Exchange` method
+pub const EXCHANGE_PATH: &str = "/nexa.mcp.AgentTransport/Exchange";
+
+const GRPC_OK: &str = "0";
+const GRPC_INVALID_ARGUMENT: &str = "3";
+const GRPC_UNIMPLEMENTED: &str = "12";
+
+/// Serve the gRPC calls of one HTTP/2 connection
+pub(super) async fn serve_connection(server: Server, socket: TcpStream, addr: SocketAddr) -> Result<(), NexaError> {
+    let mut connection = h2::server::handshake(socket).await.map_err(h2_error)?;
+    while let Some(call) = connection.accept().await {
+        let (request, respond) = call.map_err(h2_error)?;
+        let server = server.clone();
+        tokio::spawn(async move {
+            if let Err(e) = exchange(&server, request, respond).await {
+                error!("gRPC stream error for {}: {}", addr, e);
+            }
+        });
+    }
+    Ok(())
+}
+
+async fn exchange(
+    server: &Server,
+    request: Request<RecvStream>,
+    mut respond: SendResponse<Bytes>,
+) -> Result<(), NexaError> {
+    if request.uri().path() != EXCHANGE_PATH {
+        return finish_early(&mut respond, GRPC_UNIMPLEMENTED, &format!("Unknown method {}", request.uri().path()));
+    }
+    let encoding = request.headers().get("grpc-encoding").and_then(|v| v.to_str().ok()).unwrap_or("identity");
+    if encoding != "identity" {
+        return finish_early(&mut respond, GRPC_UNIMPLEMENTED, &format!("Unsupported encoding {}", encoding));
+    }
+
+    let response = Response::builder()
+        .header("content-type", "application/grpc")
+        .body(())
+        .map_err(|e| NexaError::server(e.to_string()))?;
+    let mut send = respond.send_response(response, false).map_err(h2_error)?;
+    let mut body = request.into_body();
+    let connection = MCPConnection::new();
+    let (outbox, mut inbox) = tokio::sync::mpsc::unbounded_channel();
+    let mut events = server.events.read().await.as_ref().map(|bus| bus.subscribe());
+    let mut buffer = BytesMut::new();
+
+    let status = loop {
+        tokio::select! {
+            data = body.data() => {
+                let Some(data) = data else { break (GRPC_OK, String::new()) };
+                let data = data.map_err(h2_error)?;
+                let _ = body.flow_control().release_capacity(data.len());
+                buffer.extend_from_slice(&data);
+                match next_messages(&mut buffer) {
+                    Ok(messages) => {
+                        for message in messages {
+                            let reply = match serde_json::from_str(&message) {
+                                Ok(message) => server.reply(&connection, &outbox, message).await,
+                                Err(e) => Some(serde_json::json!({ "error": format!("Invalid JSON: {}", e) })),
+                            };
+                            if let Some(reply) = reply {
+                                send_message(&mut send, &reply.to_string())?;
+                            }
+                        }
+                    }
+                    Err(e) => break (GRPC_INVALID_ARGUMENT, e.message()),
+                }
+            }
+            Some(message) = inbox.recv() => send_message(&mut send, &message.to_string())?,
+            Some(event) = next_event(&mut events) => {
+                let text = serde_json::json!({ "type": "event", "event": event }).to_string();
+                send_message(&mut send, &text)?;
+            }
+        }
+    };
+    server.unlink(&outbox).await;
+    debug!("gRPC exchange finished with status {}", status.0);
+
+    let mut trailers = HeaderMap::new();
+    trailers.insert("grpc-status", HeaderValue::from_static(status.0));
+    if !status.1.is_empty() {
+        if let Ok(message) = HeaderValue::from_str(&status.1) {
+            trailers.insert("grpc-message", message);
+        }
+    }
+    send.send_trailers(trailers).map_err(h2_error)
+}
+
+/// Answer with a status and no messages
+fn finish_early(respond: &mut SendResponse<Bytes>, status: &'static str, message: &str) -> Result<(), NexaError> {
+    let mut response = Response::builder()
+        .header("content-type", "application/grpc")
+        .header("grpc-status", status);
+    if let Ok(message) = HeaderValue::from_str(message) {
+        response = response.header("grpc-message", message);
+    }
+    let response = response.body(()).map_err(|e| NexaError::server(e.to_string()))?;
+    respond.send_response(response, true).map_err(h2_error)?;
+    Ok(())
+}
+
+fn send_message(send: &mut SendStream<Bytes>, json: &str) -> Result<(), NexaError> {
+    send.send_data(encode_message(json), false).map_err(h2_error)
+}
+
+/// Frame an `Envelope { json }` as a gRPC message
+pub fn encode_message(json: &str) -> Bytes {
+    let mut envelope = BytesMut::new();
+    if !json.is_empty() {
+        envelope.put_u8(0x0a); // field 1, length-delimited
+        put_varint(&mut envelope, json.len() as u64);
+        envelope.put_slice(json.as_bytes());
+    }
+    let mut frame = BytesMut::with_capacity(5 + envelope.len());
+    frame.put_u8(0); // uncompressed
+    frame.put_u32(envelope.len() as u32);
+    frame.put_slice(&envelope);
+    frame.freeze()
+}
+
+/// Take the complete gRPC messages from `buffer`, returning the JSON of each `Envelope`
+pub fn next_messages(buffer: &mut BytesMut) -> Result<Vec<String>, NexaError> {
+    let mut messages = Vec::new();
+    while buffer.len() >= 5 {
+        let compressed = buffer[0];
+        let len = u32::from_be_bytes([buffer[1], buffer[2], buffer[3], buffer[4]]) as usize;
+        if buffer.len() < 5 + len {
+            break;
+        }
+        if compressed != 0 {
+            return Err(NexaError::invalid_input("Compressed messages are not supported"));
+        }
+        buffer.advance(5);
+        let envelope = buffer.split_to(len).freeze();
+        messages.push(decode_envelope(envelope)?);
+    }
+    Ok(messages)
+}
+
+/// The `json` field of an `Envelope`, skipping unknown fields
+fn decode_envelope(mut bytes: Bytes) -> Result<String, NexaError> {
+    let invalid = || NexaError::invalid_input("Malformed Envelope");
+    let mut json = String::new();
+    while bytes.has_remaining() {
+        let key = get_varint(&mut bytes).ok_or_else(invalid)?;
+        let skip = match key & 0x7 {
+            0 => {
+                get_varint(&mut bytes).ok_or_else(invalid)?;
+                0
+            }
+            1 => 8,
+            2 => get_varint(&mut bytes).ok_or_else(invalid)? as usize,
+            5 => 4,
+            _ => return Err(invalid()),
+        };
+        if bytes.remaining() < skip {
+            return Err(invalid());
+        }
+        let field = bytes.split_to(skip);
+        if key == 0x0a {
+            json = String::from_utf8(field.to_vec()).map_err(|_| invalid())?;
+        }
+    }
+    Ok(json)
+}
+
+fn put_varint(buffer: &mut BytesMut, mut value: u64) {
+    while value >= 0x80 {
+        buffer.put_u8((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    buffer.put_u8(value as u8);
+}
+
+fn get_varint(bytes: &mut Bytes) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        if !bytes.has_remaining() {
+            return None;
+        }
+        let byte = bytes.get_u8();
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+fn h2_error(e: h2::Error) -> NexaError {
+    match e.get_io() {
+        Some(io) => NexaError::Io(std::io::Error::new(io.kind(), io.to_string())),
+        None => NexaError::server(format!("HTTP/2 error: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::server::{ServerConfig, Transport};
+
+    #[test]
+    fn test_framing() {
+        let json = format!("{{\"text\":\"{}\"}}", "x".repeat(300));
+        let mut buffer = BytesMut::new();
+        buffer.extend_from_slice(&encode_message(&json));
+        buffer.extend_from_slice(&encode_message("{}")[..4]);
+        assert_eq!(next_messages(&mut buffer).unwrap(), vec![json]);
+        // The partial second message waits for the rest
+        assert_eq!(buffer.len(), 4);
+
+        // Unknown fields are skipped
+        let mut buffer = BytesMut::from(&[0, 0, 0, 0, 6, 0x10, 0x01, 0x0a, 0x02, b'{', b'}'][..]);
+        assert_eq!(next_messages(&mut buffer).unwrap(), vec!["{}"]);
+        let mut buffer = BytesMut::from(&[1, 0, 0, 0, 0][..]);
+        assert!(next_messages(&mut buffer).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_exchange() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = Server::new(dir.path().join("nexa.pid"), dir.path().join("nexa.sock"));
+        server.set_config(ServerConfig::new()
+            .with_bind_addr("127.0.0.1:0".to_string())
+            .with_transport(Transport::Grpc)).await.unwrap();
+        server.start().await.unwrap();
+        let addr = server.get_bound_addr().await.unwrap();
+
+        let socket = TcpStream::connect(addr).await.unwrap();
+        let (client, connection) = h2::client::handshake(socket).await.unwrap();
+        tokio::spawn(connection);
+        let mut client = client.ready().await.unwrap();
+        let request = Request::post(format!("http://{}{}", addr, EXCHANGE_PATH))
+            .header("content-type", "application/grpc")
+            .header("te", "trailers")
+            .body(())
+            .unwrap();
+        let (response, mut send) = client.send_request(request, false).unwrap();
+        let message = serde_json::json!({ "StatusUpdate": { "agent_id": "agent-1", "status": "Busy" } });
+        send.send_data(encode_message(&message.to_string()), false).unwrap();
+        send.send_data(encode_message("not json"), true).unwrap();
+
+        let response = response.await.unwrap();
+        assert_eq!(response.headers()["content-type"], "application/grpc");
+        let mut body = response.into_body();
+        let mut buffer = BytesMut::new();
+        while let Some(data) = body.data().await {
+            buffer.extend_from_slice(&data.unwrap());
+        }
+        let replies: Vec<serde_json::Value> = next_messages(&mut buffer).unwrap().iter()
+            .map(|reply| serde_json::from_str(reply).unwrap())
+            .collect();
+        assert_eq!(replies[0]["code"], 200);
+        assert!(replies[1]["error"].as_str().unwrap().starts_with("Invalid JSON"));
+        let trailers = body.trailers().await.unwrap().unwrap();
+        assert_eq!(trailers["grpc-status"], "0");
+        server.stop().await.unwrap();
+    }
+}
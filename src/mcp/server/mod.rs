@@ -1,19 +1,30 @@
 mod config;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+mod sse;
 
-pub use config::ServerConfig;
+pub use config::{ServerConfig, Transport};
 
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::{Duration, SystemTime};
-use tokio::sync::{RwLock, Notify};
+use tokio::sync::{RwLock, Notify, watch};
 use tokio::net::{TcpListener, TcpStream};
 use std::net::SocketAddr;
-use tracing::{error, info, debug};
+use tracing::{error, info, debug, warn};
 use tokio_tungstenite::{WebSocketStream, tungstenite::protocol::Message};
 use futures::stream::{SplitStream, SplitSink};
-use futures::StreamExt;
+use futures::{SinkExt, StreamExt};
+use crate::config::Config;
 use crate::error::NexaError;
+use crate::events::{Event, EventBus};
+use crate::mcp::jsonrpc::{self, JsonRpcError, JsonRpcResponse, McpHandler};
+use crate::mcp::codec::{self, Encoding};
+use crate::mcp::{handshake, trace, MCPConnection, MCPMessage};
+use crate::mcp::registry::{AgentConnection, AgentRegistry};
+use tokio::sync::mpsc;
+use futures::future::BoxFuture;
 use serde_json;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -71,6 +82,17 @@ struct ServerInternalState {
     shutdown_requested: bool,
 }
 
+/// Messages pushed to one client connection, whatever its transport
+type Outbox = mpsc::UnboundedSender<serde_json::Value>;
+
+/// Connection an agent registered over
+#[derive(Debug, Clone)]
+struct Link {
+    outbox: Outbox,
+    /// Whether the connection negotiated heartbeats
+    heartbeat: bool,
+}
+
 #[derive(Clone, Debug)]
 pub struct Server {
     pid_file: PathBuf,
@@ -82,16 +104,23 @@ pub struct Server {
     server_handle: Arc<RwLock<Option<tokio::task::JoinHandle<Result<(), NexaError>>>>>,
     ready_notify: Arc<Notify>,
     metrics: Arc<RwLock<ServerMetrics>>,
-    health_check_interval: Duration,
-    max_connections: u32,
-    connection_timeout: Duration,
     connected_clients: Arc<RwLock<HashMap<SocketAddr, SystemTime>>>,
     config: Arc<RwLock<ServerConfig>>,
+    /// Health check interval, so the server loop learns of changes as they are made
+    health_interval: Arc<watch::Sender<Duration>>,
+    events: Arc<RwLock<Option<EventBus>>>,
+    mcp: Arc<RwLock<Option<McpHandler>>>,
+    registry: Arc<RwLock<Option<AgentRegistry>>>,
+    /// Connection of each agent registered over it, by agent id
+    links: Arc<RwLock<HashMap<String, Link>>>,
+    sessions: Arc<RwLock<HashMap<String, sse::Session>>>,
 }
 
 impl Server {
     pub fn new(pid_file: PathBuf, socket_path: PathBuf) -> Self {
         let (shutdown_tx, _) = tokio::sync::broadcast::channel(16);
+        let config = ServerConfig::default();
+        let (health_interval, _) = watch::channel(config.health_check_interval);
         
         Self {
             pid_file,
@@ -113,28 +142,149 @@ impl Server {
                 last_error: None,
                 uptime: Duration::from_secs(0),
             })),
-            health_check_interval: Duration::from_secs(30),
-            max_connections: 1000,
-            connection_timeout: Duration::from_secs(30),
             connected_clients: Arc::new(RwLock::new(HashMap::new())),
-            config: Arc::new(RwLock::new(ServerConfig::default())),
+            config: Arc::new(RwLock::new(config)),
+            health_interval: Arc::new(health_interval),
+            events: Arc::new(RwLock::new(None)),
+            mcp: Arc::new(RwLock::new(None)),
+            registry: Arc::new(RwLock::new(None)),
+            links: Arc::new(RwLock::new(HashMap::new())),
+            sessions: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
+    /// Forward events from this bus to every WebSocket client, or stop forwarding with `None`
+    pub async fn set_event_bus(&self, events: Option<EventBus>) {
+        *self.events.write().await = events;
+    }
+
+    /// Answer JSON-RPC messages from WebSocket clients as Model Context Protocol requests
+    pub async fn set_mcp_handler(&self, handler: Option<McpHandler>) {
+        *self.mcp.write().await = handler;
+    }
+
+    /// Register agents that connect in this registry, or only acknowledge them with `None`
+    pub async fn set_registry(&self, registry: Option<AgentRegistry>) {
+        *self.registry.write().await = registry;
+    }
+
+    /// Push a message to the connection an agent registered over
+    pub async fn send_to_agent(&self, agent_id: &str, message: &MCPMessage) -> Result<(), NexaError> {
+        let outbox = self.links.read().await.get(agent_id).map(|link| link.outbox.clone())
+            .ok_or_else(|| NexaError::not_found(format!("Agent '{}' is not connected", agent_id)))?;
+        outbox.send(serde_json::json!(message))
+            .map_err(|_| NexaError::unavailable(format!("Agent '{}' disconnected", agent_id)))
+    }
+
+    /// Push messages for an agent to `outbox`, pinging it when `heartbeat` is set
+    pub(crate) async fn link_agent(&self, agent_id: &str, outbox: Outbox, heartbeat: bool) {
+        self.links.write().await.insert(agent_id.to_string(), Link { outbox, heartbeat });
+    }
+
+    /// Connected agents that negotiated heartbeats
+    pub async fn heartbeat_agents(&self) -> Vec<String> {
+        self.links.read().await.iter()
+            .filter(|(_, link)| link.heartbeat)
+            .map(|(agent_id, _)| agent_id.clone())
+            .collect()
+    }
+
+    /// Agents registered over a connection to this server
+    pub async fn connected_agents(&self) -> Vec<String> {
+        self.links.read().await.keys().cloned().collect()
+    }
+
+    /// Stop pushing messages to an agent
+    pub async fn unlink_agent(&self, agent_id: &str) {
+        self.links.write().await.remove(agent_id);
+    }
+
+    /// Forget the agents registered over a closed connection
+    async fn unlink(&self, outbox: &Outbox) {
+        self.links.write().await.retain(|_, link| !link.outbox.same_channel(outbox));
+    }
+
     pub async fn get_config(&self) -> Result<ServerConfig, NexaError> {
         let config = self.config.read().await;
         Ok(config.clone())
     }
 
     pub async fn set_config(&self, config: ServerConfig) -> Result<(), NexaError> {
+        self.set_health_interval(config.health_check_interval);
         *self.config.write().await = config;
         Ok(())
     }
 
+    /// Tell the server loop about a new health check interval, if it changed
+    fn set_health_interval(&self, interval: Duration) {
+        self.health_interval.send_if_modified(|current| {
+            let changed = *current != interval;
+            *current = interval;
+            changed
+        });
+    }
+
+    /// Apply connection limits and intervals from a configuration
+    pub async fn apply_config(&self, update: &Config) {
+        let mut config = self.config.write().await;
+        config.max_connections = update.server.max_connections;
+        config.connection_timeout = Duration::from_secs(update.server.connection_timeout);
+        config.health_check_interval = Duration::from_secs(update.monitoring.health_check_interval.max(1));
+        config.auto_port = update.server.auto_port;
+        config.require_handshake = update.server.require_handshake;
+        config.require_agent_token = update.server.require_agent_token;
+        config.heartbeat_interval = Duration::from_secs(update.server.heartbeat_interval.max(1));
+        config.missed_heartbeats = update.server.missed_heartbeats.max(1);
+        self.set_health_interval(config.health_check_interval);
+        debug!("Applied server config update: max_connections={}", config.max_connections);
+    }
+
+    /// Apply connection limits and intervals from config updates as they arrive
+    pub fn watch_config(&self, mut rx: watch::Receiver<Config>) -> tokio::task::JoinHandle<()> {
+        let server = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let update = rx.borrow_and_update().clone();
+                server.apply_config(&update).await;
+                if rx.changed().await.is_err() {
+                    break;
+                }
+            }
+        })
+    }
+
     pub async fn get_state(&self) -> ServerState {
         self.state.read().await.state.clone()
     }
 
+    /// Refuse new connections until `resume`, keeping the open ones
+    pub async fn enter_maintenance(&self) -> Result<(), NexaError> {
+        let mut state = self.state.write().await;
+        match state.state {
+            ServerState::Running => {
+                state.state = ServerState::Maintenance;
+                info!("Entering maintenance, refusing new connections");
+                Ok(())
+            }
+            ServerState::Maintenance => Ok(()),
+            ref other => Err(NexaError::unavailable(format!("Cannot enter maintenance while {}", other))),
+        }
+    }
+
+    /// Accept connections again after maintenance
+    pub async fn resume(&self) -> Result<(), NexaError> {
+        let mut state = self.state.write().await;
+        match state.state {
+            ServerState::Maintenance => {
+                state.state = ServerState::Running;
+                info!("Leaving maintenance, accepting connections");
+                Ok(())
+            }
+            ServerState::Running => Ok(()),
+            ref other => Err(NexaError::unavailable(format!("Cannot resume while {}", other))),
+        }
+    }
+
     pub async fn get_bound_addr(&self) -> Option<std::net::SocketAddr> {
         *self.bound_addr.read().await
     }
@@ -145,6 +295,9 @@ impl Server {
 
     pub async fn start(&self) -> Result<(), NexaError> {
         debug!("Starting server initialization");
+        if cfg!(not(feature = "grpc")) && self.config.read().await.transport == Transport::Grpc {
+            return Err(NexaError::config("The gRPC transport requires the `grpc` feature"));
+        }
         let mut state = self.state.write().await;
         if state.state != ServerState::Stopped {
             return Err(NexaError::server("Server is not in stopped state"));
@@ -157,8 +310,17 @@ impl Server {
         let config = self.config.read().await;
         let bind_addr = &config.bind_addr;
         debug!("Attempting to bind to {}", bind_addr);
-        let listener = TcpListener::bind(bind_addr).await
-            .map_err(|e| NexaError::server(format!("Failed to bind to {}: {}", bind_addr, e)))?;
+        let listener = match TcpListener::bind(bind_addr).await {
+            Ok(listener) => listener,
+            Err(e) if e.kind() == std::io::ErrorKind::AddrInUse && config.auto_port => {
+                let host = bind_addr.rsplit_once(':').map(|(host, _)| host).unwrap_or(bind_addr);
+                warn!("{} is in use, binding to an ephemeral port", bind_addr);
+                TcpListener::bind(format!("{}:0", host)).await
+                    .map_err(|e| NexaError::server(format!("Failed to bind to {}:0: {}", host, e)))?
+            }
+            Err(e) => return Err(NexaError::server(format!("Failed to bind to {}: {}", bind_addr, e))),
+        };
+        drop(config);
         
         let local_addr = listener.local_addr()?;
         *self.bound_addr.write().await = Some(local_addr);
@@ -169,7 +331,6 @@ impl Server {
         let handle = tokio::spawn(async move {
             info!("Server starting on {}", local_addr);
             
-            let mut interval = tokio::time::interval(server.health_check_interval);
             let mut shutdown_rx = server.shutdown_tx.subscribe();
             debug!("Server loop initialized");
             
@@ -198,6 +359,10 @@ impl Server {
                                         debug!("Rejecting connection during shutdown");
                                         continue;
                                     }
+                                    if state.state == ServerState::Maintenance {
+                                        debug!("Rejecting connection from {} during maintenance", addr);
+                                        continue;
+                                    }
                                     drop(state);
                                     
                                     // Handle connection in a separate task
@@ -228,6 +393,11 @@ impl Server {
                 debug!("Accept loop exited");
             });
             
+            // One ticker across iterations, rebuilt only when the interval changes,
+            // so a health check is due every interval whatever else wakes the loop
+            let mut health_interval = server.health_interval.subscribe();
+            let mut health_check = health_ticker(*health_interval.borrow_and_update());
+
             // Main server loop
             loop {
                 tokio::select! {
@@ -281,7 +451,12 @@ impl Server {
                         debug!("Server loop exited");
                         break;
                     }
-                    _ = interval.tick() => {
+                    Ok(()) = health_interval.changed() => {
+                        let interval = *health_interval.borrow_and_update();
+                        debug!("Health check interval changed to {:?}", interval);
+                        health_check = health_ticker(interval);
+                    }
+                    _ = health_check.tick() => {
                         let state = server.state.read().await;
                         if state.shutdown_requested {
                             debug!("Skipping health check during shutdown");
@@ -291,11 +466,12 @@ impl Server {
                         
                         // Perform health check
                         let now = SystemTime::now();
+                        let connection_timeout = server.config.read().await.connection_timeout;
                         {
                             let mut clients = server.connected_clients.write().await;
                             clients.retain(|_, last_seen| {
                                 now.duration_since(*last_seen)
-                                    .map(|duration| duration < server.connection_timeout)
+                                    .map(|duration| duration < connection_timeout)
                                     .unwrap_or(false)
                             });
                             
@@ -339,7 +515,7 @@ impl Server {
                     debug!("Server is already in the process of stopping");
                     return Ok(());
                 }
-                ServerState::Running => {
+                ServerState::Running | ServerState::Maintenance => {
                     debug!("Server is running, proceeding with shutdown");
                     state.state = ServerState::Stopping;
                     state.shutdown_requested = true;
@@ -430,16 +606,31 @@ impl Server {
     pub async fn handle_connection(&self, socket: TcpStream, addr: SocketAddr) -> Result<(), NexaError> {
         let active_conns = *self.active_connections.read().await;
         
-        if active_conns >= self.max_connections {
+        if active_conns >= self.config.read().await.max_connections {
             return Err(NexaError::server("Maximum connections reached"));
         }
 
         // Configure socket
         socket.set_nodelay(true)?;
-        
-        // Upgrade to WebSocket
-        let ws_stream = tokio_tungstenite::accept_async(socket).await?;
-        let (write, read) = ws_stream.split();
+
+        let (transport, timeout) = {
+            let config = self.config.read().await;
+            (config.transport, config.connection_timeout)
+        };
+        let session: BoxFuture<'static, Result<(), NexaError>> = match transport {
+            Transport::WebSocket if !is_websocket_upgrade(&socket, timeout).await? => {
+                Box::pin(sse::serve_connection(self.clone(), socket))
+            }
+            Transport::WebSocket => {
+                let (write, read) = tokio_tungstenite::accept_async(socket).await?.split();
+                let server = self.clone();
+                Box::pin(async move { server.process_connection(read, write, addr).await })
+            }
+            #[cfg(feature = "grpc")]
+            Transport::Grpc => Box::pin(grpc::serve_connection(self.clone(), socket, addr)),
+            #[cfg(not(feature = "grpc"))]
+            Transport::Grpc => return Err(NexaError::config("The gRPC transport requires the `grpc` feature")),
+        };
         
         // Update metrics and state
         {
@@ -453,7 +644,7 @@ impl Server {
         // Spawn connection handler
         let server = self.clone();
         tokio::spawn(async move {
-            if let Err(e) = server.process_connection(read, write, addr).await {
+            if let Err(e) = session.await {
                 error!("Connection error for {}: {}", addr, e);
             }
             
@@ -473,15 +664,29 @@ impl Server {
         mut write: SplitSink<WebSocketStream<TcpStream>, Message>,
         addr: SocketAddr,
     ) -> Result<(), NexaError> {
-        while let Some(msg) = read.next().await {
-            match msg {
-                Ok(msg) => {
+        let connection = MCPConnection::new().with_encodings(Encoding::ALL);
+        let (outbox, mut inbox) = mpsc::unbounded_channel();
+        let mut events = self.events.read().await.as_ref().map(EventBus::subscribe);
+        let result = loop {
+            tokio::select! {
+                msg = read.next() => {
+                    let Some(msg) = msg else { break Ok(()) };
                     match msg {
-                        Message::Text(text) => {
-                            match serde_json::from_str(&text) {
+                        Ok(msg) => {
+                            let message = match msg {
+                                Message::Text(text) => serde_json::from_str(&text).map_err(NexaError::from),
+                                Message::Binary(frame) => codec::decode_binary(&frame),
+                                Message::Close(_) => break Ok(()),
+                                _ => continue,
+                            };
+                            match message {
                                 Ok(message) => {
-                                    if let Err(e) = Server::handle_client_message(&message, &mut write).await {
-                                        error!("Failed to handle message from {}: {}", addr, e);
+                                    // The reply to a handshake is in the encoding it replaces
+                                    let encoding = connection.encoding().await;
+                                    let Some(reply) = self.reply(&connection, &outbox, message).await else { continue };
+                                    if let Err(e) = send_encoded(&mut write, encoding, &reply).await {
+                                        error!("Failed to send reply to {}: {}", addr, e);
+                                        break Ok(());
                                     }
                                 }
                                 Err(e) => {
@@ -489,35 +694,163 @@ impl Server {
                                 }
                             }
                         }
-                        Message::Close(_) => break,
-                        _ => {}
+                        Err(e) => {
+                            error!("WebSocket error from {}: {}", addr, e);
+                            break Ok(());
+                        }
                     }
                 }
-                Err(e) => {
-                    error!("WebSocket error from {}: {}", addr, e);
-                    break;
+                Some(message) = inbox.recv() => {
+                    if let Err(e) = send_encoded(&mut write, connection.encoding().await, &message).await {
+                        error!("Failed to send message to {}: {}", addr, e);
+                        break Ok(());
+                    }
+                }
+                Some(event) = next_event(&mut events) => {
+                    let event = serde_json::json!({ "type": "event", "event": event });
+                    if let Err(e) = send_encoded(&mut write, connection.encoding().await, &event).await {
+                        error!("Failed to send event to {}: {}", addr, e);
+                        break Ok(());
+                    }
                 }
             }
+        };
+        self.unlink(&outbox).await;
+        result
+    }
+
+    /// Reply to a client message, whatever the transport
+    ///
+    /// JSON-RPC messages are Model Context Protocol requests; anything else is an `MCPMessage`.
+    /// Agents registering are linked to `outbox`, where task assignments are pushed.
+    async fn reply(&self, connection: &MCPConnection, outbox: &Outbox, message: serde_json::Value) -> Option<serde_json::Value> {
+        if jsonrpc::is_jsonrpc(&message) {
+            let handler = self.mcp.read().await.clone();
+            return match handler {
+                Some(handler) => handler.handle(message).await,
+                None => Some(serde_json::json!(JsonRpcResponse::failure(
+                    message.get("id").cloned().unwrap_or_default(),
+                    JsonRpcError::new(jsonrpc::METHOD_NOT_FOUND, "MCP is not enabled on this server"),
+                ))),
+            };
         }
-        Ok(())
+        let reply = match serde_json::from_value::<MCPMessage>(message) {
+            Ok(MCPMessage::ClientHello { versions, capabilities, encodings }) => {
+                Ok(Self::hello(connection, &versions, &capabilities, &encodings).await)
+            }
+            Ok(_) if self.config.read().await.require_handshake && connection.handshake.read().await.is_none() => {
+                Ok(serde_json::json!(MCPMessage::Error {
+                    code: 426,
+                    message: "Open the connection with a ClientHello".to_string(),
+                }))
+            }
+            Ok(MCPMessage::Pong { agent_id }) => {
+                self.pong(outbox, &agent_id).await;
+                return None;
+            }
+            Ok(message) => {
+                let trace = message.trace().cloned();
+                trace::scope(trace, async {
+                    match self.registry.read().await.clone() {
+                        Some(registry) => self.handle_agent_message(&registry, connection, outbox, message).await,
+                        None => connection.handle_message(message).await,
+                    }
+                }).await
+            }
+            Err(e) => Err(NexaError::invalid_input(format!("Invalid message: {}", e))),
+        };
+        Some(reply.unwrap_or_else(|e| serde_json::json!(MCPMessage::Error {
+            code: u32::from(e.http_status()),
+            message: e.message(),
+        })))
     }
 
-    async fn handle_client_message(
-        _message: &serde_json::Value,
-        _write: &mut SplitSink<WebSocketStream<TcpStream>, Message>,
-    ) -> Result<(), NexaError> {
-        // TODO: Implement message handling
-        Ok(())
+    /// Answer a `ClientHello`, recording the handshake on the connection
+    async fn hello(connection: &MCPConnection, versions: &[u32], capabilities: &[String], encodings: &[String]) -> serde_json::Value {
+        match handshake::negotiate(versions, capabilities) {
+            Ok(mut agreed) => {
+                agreed.encoding = Encoding::negotiate(encodings, connection.encodings);
+                debug!("Connection {} speaks protocol version {} in {}", connection.id, agreed.version, agreed.encoding.name());
+                let reply = MCPMessage::ServerHello {
+                    version: agreed.version,
+                    capabilities: agreed.capabilities.clone(),
+                    encoding: agreed.encoding,
+                };
+                *connection.handshake.write().await = Some(agreed);
+                serde_json::json!(reply)
+            }
+            Err(e) => serde_json::json!(MCPMessage::Error { code: 426, message: e.message() }),
+        }
+    }
+
+    /// Record a heartbeat from an agent registered over the connection of `outbox`
+    async fn pong(&self, outbox: &Outbox, agent_id: &str) {
+        let linked = self.links.read().await.get(agent_id).is_some_and(|link| link.outbox.same_channel(outbox));
+        let registry = self.registry.read().await.clone();
+        match registry {
+            Some(registry) if linked => {
+                if let Err(e) = registry.touch(agent_id).await {
+                    debug!("Ignoring pong from {}: {}", agent_id, e);
+                }
+            }
+            _ => debug!("Ignoring pong for agent {} not registered over this connection", agent_id),
+        }
+    }
+
+    async fn handle_agent_message(
+        &self,
+        registry: &AgentRegistry,
+        connection: &MCPConnection,
+        outbox: &Outbox,
+        message: MCPMessage,
+    ) -> Result<serde_json::Value, NexaError> {
+        let (agent_id, action) = match message {
+            MCPMessage::RegisterAgent { mut agent, token } => {
+                let agent_id = agent.id.clone();
+                let required = self.config.read().await.require_agent_token;
+                registry.verify_token(&agent_id, token.as_deref(), required).await?;
+                agent.update_heartbeat();
+                let agreed = connection.handshake.read().await.clone();
+                let heartbeat = agreed.as_ref()
+                    .is_some_and(|h| h.capabilities.iter().any(|c| c == handshake::HEARTBEAT_CAPABILITY));
+                registry.register_connected(agent, AgentConnection {
+                    protocol_version: agreed.as_ref().map_or(handshake::MIN_PROTOCOL_VERSION, |h| h.version),
+                    capabilities: agreed.map(|h| h.capabilities).unwrap_or_default(),
+                    connected_at: chrono::Utc::now(),
+                }).await?;
+                self.link_agent(&agent_id, outbox.clone(), heartbeat).await;
+                (agent_id, "registered")
+            }
+            MCPMessage::DeregisterAgent { agent_id } => {
+                registry.deregister(&agent_id).await?;
+                self.links.write().await.remove(&agent_id);
+                (agent_id, "deregistered")
+            }
+            MCPMessage::StatusUpdate { agent_id, status, .. } => {
+                registry.update_status(&agent_id, status).await?;
+                (agent_id, "updated")
+            }
+            MCPMessage::AgentQuery { capability } => {
+                let agents = registry.query(&capability.parse()?).await;
+                return Ok(serde_json::json!(MCPMessage::AgentResponse { agents }));
+            }
+            message => return connection.handle_message(message).await,
+        };
+        Ok(serde_json::json!({
+            "code": 200,
+            "message": format!("Agent {} {}", agent_id, action),
+        }))
     }
 
     pub async fn check_health(&self) {
         let now = SystemTime::now();
+        let connection_timeout = self.config.read().await.connection_timeout;
         let mut clients = self.connected_clients.write().await;
         
         // Remove stale connections
         clients.retain(|_, last_seen| {
             now.duration_since(*last_seen)
-                .map(|duration| duration < self.connection_timeout)
+                .map(|duration| duration < connection_timeout)
                 .unwrap_or(false)
         });
         
@@ -530,6 +863,65 @@ impl Server {
     }
 }
 
+/// Ticker firing every `interval`, the first time one interval from now
+fn health_ticker(interval: Duration) -> tokio::time::Interval {
+    let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    ticker
+}
+
+/// Send a message over a WebSocket in `encoding`
+async fn send_encoded(
+    write: &mut SplitSink<WebSocketStream<TcpStream>, Message>,
+    encoding: Encoding,
+    message: &serde_json::Value,
+) -> Result<(), NexaError> {
+    let frame = match encoding {
+        Encoding::Json => Message::Text(message.to_string()),
+        Encoding::Cbor => Message::Binary(codec::encode_binary(message, false)?),
+        Encoding::CborZstd => Message::Binary(codec::encode_binary(message, true)?),
+    };
+    write.send(frame).await.map_err(|e| NexaError::from(Box::new(e)))
+}
+
+/// Whether the request on `socket` asks for a WebSocket upgrade, peeking at its headers
+async fn is_websocket_upgrade(socket: &TcpStream, timeout: Duration) -> Result<bool, NexaError> {
+    let peek = async {
+        let mut buf = [0u8; 4096];
+        loop {
+            let n = socket.peek(&mut buf).await?;
+            let head = &buf[..n];
+            if n == 0 || n == buf.len() || head.windows(4).any(|w| w == b"\r\n\r\n") {
+                let head = String::from_utf8_lossy(head).to_ascii_lowercase();
+                return Ok(head.lines().any(|line| line.starts_with("upgrade:") && line.contains("websocket")));
+            }
+            // The rest of the headers has not arrived yet
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    };
+    tokio::time::timeout(timeout, peek).await
+        .map_err(|_| NexaError::timeout("Client sent no request headers"))?
+}
+
+/// Next event for a client, waiting forever when forwarding is off
+async fn next_event(events: &mut Option<tokio::sync::broadcast::Receiver<Event>>) -> Option<Event> {
+    let Some(rx) = events else {
+        return std::future::pending().await;
+    };
+    loop {
+        match rx.recv().await {
+            Ok(event) => return Some(event),
+            Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                warn!("WebSocket client skipped {} event(s)", skipped);
+            }
+            Err(tokio::sync::broadcast::error::RecvError::Closed) => {
+                *events = None;
+                return None;
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -550,4 +942,162 @@ mod tests {
         assert!(server.stop().await.is_ok());
         assert_eq!(server.get_state().await, ServerState::Stopped);
     }
-} 
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_health_check_interval_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = Server::new(dir.path().join("nexa.pid"), dir.path().join("nexa.sock"));
+        let config = ServerConfig::new()
+            .with_bind_addr("127.0.0.1:0".to_string())
+            .with_connection_timeout(Duration::ZERO)
+            .with_health_check_interval(Duration::from_secs(3600));
+        server.set_config(config.clone()).await.unwrap();
+        server.start().await.unwrap();
+        let stale: SocketAddr = "127.0.0.1:9".parse().unwrap();
+        server.connected_clients.write().await.insert(stale, SystemTime::UNIX_EPOCH);
+
+        // The running loop picks the new interval up without waiting out the old one
+        server.set_config(config.with_health_check_interval(Duration::from_millis(20))).await.unwrap();
+        for _ in 0..50 {
+            if server.connected_clients.read().await.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(server.connected_clients.read().await.is_empty());
+        server.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_agent_token() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = Server::new(dir.path().join("nexa.pid"), dir.path().join("nexa.sock"));
+        server.set_config(ServerConfig::new().with_require_agent_token(true)).await.unwrap();
+        let registry = AgentRegistry::new();
+        server.set_registry(Some(registry.clone())).await;
+        let (outbox, _inbox) = mpsc::unbounded_channel();
+        let connection = MCPConnection::new();
+        let agent = crate::agent::Agent::new("remote".to_string(), Vec::new());
+        let register = |token: Option<&str>| serde_json::json!(MCPMessage::RegisterAgent {
+            agent: agent.clone(),
+            token: token.map(str::to_string),
+        });
+
+        let reply = server.reply(&connection, &outbox, register(None)).await.unwrap();
+        assert_eq!(reply["Error"]["code"], 401);
+        let token = registry.issue_token(&agent.id).await.unwrap();
+        let reply = server.reply(&connection, &outbox, register(Some("wrong"))).await.unwrap();
+        assert_eq!(reply["Error"]["code"], 401);
+        let reply = server.reply(&connection, &outbox, register(Some(&token))).await.unwrap();
+        assert_eq!(reply["code"], 200);
+        assert!(registry.get_agent(&agent.id).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_handshake() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = Server::new(dir.path().join("nexa.pid"), dir.path().join("nexa.sock"));
+        server.set_config(ServerConfig::new().with_require_handshake(true)).await.unwrap();
+        let (outbox, _inbox) = mpsc::unbounded_channel();
+        let connection = MCPConnection::new();
+        let status = serde_json::json!({ "StatusUpdate": { "agent_id": "agent-1", "status": "Busy" } });
+
+        // Without a handshake the connection is refused
+        let reply = server.reply(&connection, &outbox, status.clone()).await.unwrap();
+        assert_eq!(reply["Error"]["code"], 426);
+
+        let hello = serde_json::json!({ "ClientHello": { "versions": [99] } });
+        let reply = server.reply(&connection, &outbox, hello).await.unwrap();
+        assert_eq!(reply["Error"]["code"], 426);
+        assert_eq!(connection.protocol_version().await, handshake::MIN_PROTOCOL_VERSION);
+
+        let hello = serde_json::json!({ "ClientHello": { "versions": [1, 2], "capabilities": ["events"] } });
+        let reply = server.reply(&connection, &outbox, hello).await.unwrap();
+        assert_eq!(reply["ServerHello"]["version"], handshake::PROTOCOL_VERSION);
+        assert_eq!(reply["ServerHello"]["capabilities"], serde_json::json!(["events"]));
+        assert_eq!(connection.protocol_version().await, handshake::PROTOCOL_VERSION);
+        let reply = server.reply(&connection, &outbox, status).await.unwrap();
+        assert_eq!(reply["code"], 200);
+
+        // Unknown message types are answered, not fatal
+        let reply = server.reply(&connection, &outbox, serde_json::json!({ "Teleport": {} })).await.unwrap();
+        assert_eq!(reply["Error"]["code"], 400);
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_links() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = Server::new(dir.path().join("nexa.pid"), dir.path().join("nexa.sock"));
+        let registry = AgentRegistry::new();
+        server.set_registry(Some(registry.clone())).await;
+        let mut agent = crate::agent::Agent::new("remote".to_string(), Vec::new());
+        agent.last_heartbeat = chrono::Utc::now() - chrono::Duration::hours(1);
+        let legacy = crate::agent::Agent::new("legacy".to_string(), Vec::new());
+
+        let (outbox, _inbox) = mpsc::unbounded_channel();
+        let connection = MCPConnection::new();
+        let hello = serde_json::json!({ "ClientHello": { "versions": [2], "capabilities": ["heartbeat"] } });
+        server.reply(&connection, &outbox, hello).await.unwrap();
+        let register = serde_json::json!(MCPMessage::RegisterAgent { agent: agent.clone(), token: None });
+        assert_eq!(server.reply(&connection, &outbox, register).await.unwrap()["code"], 200);
+        // Registering counts as a heartbeat
+        let registered = registry.get_agent(&agent.id).await.unwrap().last_heartbeat;
+        assert!(registered > agent.last_heartbeat);
+
+        // Agents that did not negotiate heartbeats are not monitored
+        let (legacy_outbox, _legacy_inbox) = mpsc::unbounded_channel();
+        let register = serde_json::json!(MCPMessage::RegisterAgent { agent: legacy.clone(), token: None });
+        server.reply(&MCPConnection::new(), &legacy_outbox, register).await.unwrap();
+        assert_eq!(server.heartbeat_agents().await, vec![agent.id.clone()]);
+
+        // Pongs are not answered, and only count over the agent's own connection
+        let pong = serde_json::json!(MCPMessage::Pong { agent_id: agent.id.clone() });
+        assert!(server.reply(&MCPConnection::new(), &legacy_outbox, pong.clone()).await.is_none());
+        assert_eq!(registry.get_agent(&agent.id).await.unwrap().last_heartbeat, registered);
+        assert!(server.reply(&connection, &outbox, pong).await.is_none());
+        assert!(registry.get_agent(&agent.id).await.unwrap().last_heartbeat > registered);
+    }
+
+    #[tokio::test]
+    async fn test_binary_encoding() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = Server::new(dir.path().join("nexa.pid"), dir.path().join("nexa.sock"));
+        server.set_config(ServerConfig::new().with_bind_addr("127.0.0.1:0".to_string())).await.unwrap();
+        server.start().await.unwrap();
+        let url = format!("ws://{}", server.get_bound_addr().await.unwrap());
+        let (mut socket, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+
+        // The handshake itself is answered in JSON
+        let hello = serde_json::json!({ "ClientHello": { "versions": [2], "encodings": ["msgpack", "cbor"] } });
+        socket.send(Message::Text(hello.to_string())).await.unwrap();
+        let Some(Ok(Message::Text(reply))) = socket.next().await else { panic!("Expected a text frame") };
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&reply).unwrap()["ServerHello"]["encoding"], "cbor");
+
+        // Then messages go both ways as CBOR
+        let status = serde_json::json!({ "StatusUpdate": { "agent_id": "agent-1", "status": "Busy" } });
+        socket.send(Message::Binary(codec::encode_binary(&status, false).unwrap())).await.unwrap();
+        let Some(Ok(Message::Binary(reply))) = socket.next().await else { panic!("Expected a binary frame") };
+        assert_eq!(codec::decode_binary(&reply).unwrap()["code"], 200);
+
+        // With compression agreed on, large messages go both ways compressed
+        let url = format!("ws://{}", server.get_bound_addr().await.unwrap());
+        let (mut socket, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        let hello = serde_json::json!({ "ClientHello": { "versions": [2], "encodings": ["cbor+zstd"] } });
+        socket.send(Message::Text(hello.to_string())).await.unwrap();
+        let Some(Ok(Message::Text(reply))) = socket.next().await else { panic!("Expected a text frame") };
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&reply).unwrap()["ServerHello"]["encoding"], "cbor+zstd");
+
+        let agent_id = "agent-".repeat(500);
+        let status = serde_json::json!({ "StatusUpdate": { "agent_id": agent_id, "status": "Busy" } });
+        let frame = codec::encode_binary(&status, true).unwrap();
+        assert_eq!(frame[0], 0x01);
+        socket.send(Message::Binary(frame)).await.unwrap();
+        let Some(Ok(Message::Binary(reply))) = socket.next().await else { panic!("Expected a binary frame") };
+        assert_eq!(reply[0], 0x01);
+        let reply = codec::decode_binary(&reply).unwrap();
+        assert_eq!(reply["code"], 200);
+        assert!(reply["message"].as_str().unwrap().contains(&agent_id));
+
+        server.stop().await.unwrap();
+    }
+}
@@ -0,0 +1,197 @@
+//! HTTP fallback transport for networks that block WebSocket upgrades
+//!
+//! Served on the WebSocket port to requests that do not ask for an upgrade:
+//! - `GET /sse` opens a session and streams server messages as Server-Sent Events,
+//!   starting with an `endpoint` event holding the URL to post to
+//! - `POST /messages?session_id=...` takes one client message; its reply arrives on the stream
+//!
+//! Messages are the same JSON as on the WebSocket, including MCP requests, so this is
+//! also the HTTP+SSE transport of the Model Context Protocol.
+
+use std::convert::Infallible;
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::sse::{Event as SseEvent, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use futures::Stream;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto::Builder;
+use hyper_util::service::TowerToHyperService;
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tracing::debug;
+use crate::api::ApiError;
+use crate::error::NexaError;
+use crate::mcp::MCPConnection;
+use super::{next_event, Outbox, Server};
+
+/// An open event stream and the connection state of its client
+#[derive(Debug, Clone)]
+pub(super) struct Session {
+    connection: MCPConnection,
+    outbox: Outbox,
+}
+
+#[derive(Debug, Deserialize)]
+struct SessionQuery {
+    session_id: String,
+}
+
+/// Serve the HTTP requests of one connection
+pub(super) async fn serve_connection(server: Server, socket: TcpStream) -> Result<(), NexaError> {
+    let app = Router::new()
+        .route("/sse", get(open_session))
+        .route("/messages", post(post_message))
+        .with_state(server);
+    Builder::new(TokioExecutor::new())
+        .serve_connection(TokioIo::new(socket), TowerToHyperService::new(app))
+        .await
+        .map_err(|e| NexaError::server(format!("HTTP connection error: {}", e)))
+}
+
+async fn open_session(State(server): State<Server>) -> Sse<impl Stream<Item = Result<SseEvent, Infallible>>> {
+    let id = uuid::Uuid::new_v4().to_string();
+    let (outbox, inbox) = mpsc::unbounded_channel();
+    server.sessions.write().await.insert(id.clone(), Session { connection: MCPConnection::new(), outbox: outbox.clone() });
+    debug!("Opened SSE session {}", id);
+
+    // Forward events until the client goes away
+    let mut events = server.events.read().await.as_ref().map(|bus| bus.subscribe());
+    let forward = outbox.clone();
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = forward.closed() => break,
+                Some(event) = next_event(&mut events) => {
+                    let _ = forward.send(serde_json::json!({ "type": "event", "event": event }));
+                }
+            }
+        }
+    });
+
+    let endpoint = SseEvent::default().event("endpoint").data(format!("/messages?session_id={}", id));
+    let guard = SessionGuard { server, id, outbox };
+    let messages = futures::stream::unfold((inbox, guard), |(mut inbox, guard)| async move {
+        let message = inbox.recv().await?;
+        Some((Ok(SseEvent::default().event("message").data(message.to_string())), (inbox, guard)))
+    });
+    let stream = futures::StreamExt::chain(futures::stream::once(async move { Ok(endpoint) }), messages);
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+async fn post_message(
+    State(server): State<Server>,
+    Query(query): Query<SessionQuery>,
+    Json(message): Json<Value>,
+) -> Response {
+    let session = server.sessions.read().await.get(&query.session_id).cloned();
+    let Some(session) = session else {
+        return ApiError(NexaError::not_found(format!("Unknown session '{}'", query.session_id))).into_response();
+    };
+    if let Some(reply) = server.reply(&session.connection, &session.outbox, message).await {
+        let _ = session.outbox.send(reply);
+    }
+    StatusCode::ACCEPTED.into_response()
+}
+
+/// Closes the session when its event stream is dropped
+struct SessionGuard {
+    server: Server,
+    id: String,
+    outbox: Outbox,
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        let (server, id, outbox) = (self.server.clone(), std::mem::take(&mut self.id), self.outbox.clone());
+        tokio::spawn(async move {
+            server.sessions.write().await.remove(&id);
+            server.unlink(&outbox).await;
+            debug!("Closed SSE session {}", id);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+    use crate::agent::{Agent, Task};
+    use crate::mcp::server::ServerConfig;
+    use crate::mcp::registry::AgentRegistry;
+    use crate::mcp::MCPMessage;
+
+    /// Reads `(event, data)` pairs from an SSE response
+    struct Events {
+        body: futures::stream::BoxStream<'static, reqwest::Result<Vec<u8>>>,
+        buffer: String,
+    }
+
+    impl Events {
+        async fn next(&mut self) -> (String, String) {
+            loop {
+                if let Some(end) = self.buffer.find("\n\n") {
+                    let block: String = self.buffer.drain(..end + 2).collect();
+                    let field = |name: &str| block.lines()
+                        .find_map(|line| line.strip_prefix(name))
+                        .map(|value| value.trim().to_string());
+                    match (field("event:"), field("data:")) {
+                        (Some(event), Some(data)) => return (event, data),
+                        _ => continue, // keep-alive comment
+                    }
+                }
+                let chunk = self.body.next().await.unwrap().unwrap();
+                self.buffer.push_str(&String::from_utf8_lossy(&chunk));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_register_and_receive_assignment() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = Server::new(dir.path().join("nexa.pid"), dir.path().join("nexa.sock"));
+        server.set_config(ServerConfig::new().with_bind_addr("127.0.0.1:0".to_string())).await.unwrap();
+        let registry = AgentRegistry::new();
+        server.set_registry(Some(registry.clone())).await;
+        server.start().await.unwrap();
+        let base = format!("http://{}", server.get_bound_addr().await.unwrap());
+
+        let response = reqwest::get(format!("{}/sse", base)).await.unwrap();
+        assert_eq!(response.headers()["content-type"], "text/event-stream");
+        let mut events = Events { body: response.bytes_stream().map(|chunk| chunk.map(|b| b.to_vec())).boxed(), buffer: String::new() };
+        let (event, endpoint) = events.next().await;
+        assert_eq!(event, "endpoint");
+
+        let agent = Agent::new("remote".to_string(), vec!["review".to_string()]);
+        let client = reqwest::Client::new();
+        let response = client.post(format!("{}{}", base, endpoint))
+            .json(&MCPMessage::RegisterAgent { agent: agent.clone(), token: None })
+            .send().await.unwrap();
+        assert_eq!(response.status().as_u16(), StatusCode::ACCEPTED.as_u16());
+        let (_, reply) = events.next().await;
+        assert_eq!(serde_json::from_str::<Value>(&reply).unwrap()["code"], 200);
+        assert_eq!(registry.get_agent(&agent.id).await.unwrap().name, "remote");
+
+        let task = Task::new("Review".to_string(), String::new(), Vec::new(), Vec::new(), None, 60, 1);
+        server.send_to_agent(&agent.id, &MCPMessage::TaskAssignment { task: task.clone(), agent_id: agent.id.clone(), trace: None }).await.unwrap();
+        let (_, assignment) = events.next().await;
+        assert_eq!(serde_json::from_str::<Value>(&assignment).unwrap()["TaskAssignment"]["task"]["id"], task.id);
+
+        let response = client.post(format!("{}/messages?session_id=nope", base)).json(&serde_json::json!({})).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), StatusCode::NOT_FOUND.as_u16());
+
+        // WebSocket clients still connect on the same port
+        let ws_url = base.replace("http://", "ws://");
+        assert!(tokio_tungstenite::connect_async(ws_url).await.is_ok());
+
+        // Closing the stream disconnects the agent
+        drop(events);
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        assert!(server.send_to_agent(&agent.id, &MCPMessage::DeregisterAgent { agent_id: agent.id.clone() }).await.is_err());
+        server.stop().await.unwrap();
+    }
+}
@@ -0,0 +1,174 @@
+//! Model Context Protocol over stdio
+//!
+//! Newline-delimited JSON-RPC for MCP hosts that launch servers as subprocesses:
+//! - Served in process by an `McpHandler`, requests running concurrently
+//! - Or relayed to the WebSocket server of a running daemon, sharing its agents and tools
+//!
+//! Nothing else may be written to stdout while serving; logs go to stderr.
+
+use futures::{SinkExt, StreamExt};
+use serde_json::Value;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::protocol::Message;
+use tracing::debug;
+use crate::error::NexaError;
+use super::jsonrpc::{is_jsonrpc, McpHandler};
+
+/// Answer the messages read from `reader` with `handler`, until it ends
+///
+/// Replies may be written in a different order than the requests arrived.
+pub async fn serve<R, W>(handler: &McpHandler, reader: R, mut writer: W) -> Result<(), NexaError>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+    let mut lines = reader.lines();
+    loop {
+        tokio::select! {
+            line = lines.next_line() => {
+                let Some(line) = line? else { break };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let handler = handler.clone();
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    if let Some(reply) = handler.handle_text(&line).await {
+                        let _ = tx.send(reply);
+                    }
+                });
+            }
+            Some(reply) = rx.recv() => write_line(&mut writer, &reply).await?,
+        }
+    }
+
+    // Finish the requests still running
+    drop(tx);
+    while let Some(reply) = rx.recv().await {
+        write_line(&mut writer, &reply).await?;
+    }
+    debug!("MCP stdio input closed");
+    Ok(())
+}
+
+/// Relay messages between `reader`/`writer` and the WebSocket server at `url`
+///
+/// Only JSON-RPC replies are passed back; other WebSocket traffic such as events is dropped.
+pub async fn relay<R, W>(url: &str, reader: R, mut writer: W) -> Result<(), NexaError>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let (socket, _) = tokio_tungstenite::connect_async(url).await?;
+    let (mut sink, mut stream) = socket.split();
+    let mut lines = reader.lines();
+    let mut pending = 0usize;
+    let mut input_closed = false;
+    loop {
+        tokio::select! {
+            line = lines.next_line(), if !input_closed => match line? {
+                Some(line) if line.trim().is_empty() => {}
+                Some(line) => {
+                    pending += usize::from(expects_reply(&line));
+                    sink.send(Message::Text(line)).await?;
+                }
+                None => input_closed = true,
+            },
+            message = stream.next() => match message {
+                Some(Ok(Message::Text(text))) => {
+                    if serde_json::from_str::<Value>(&text).is_ok_and(|m| is_jsonrpc(&m)) {
+                        pending = pending.saturating_sub(1);
+                        write_line(&mut writer, &text).await?;
+                    }
+                }
+                Some(Ok(Message::Close(_))) | None => break,
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Err(e.into()),
+            },
+        }
+        if input_closed && pending == 0 {
+            break;
+        }
+    }
+    let _ = sink.close().await;
+    Ok(())
+}
+
+/// Whether the server answers this message; notifications and unparseable lines aside
+fn expects_reply(line: &str) -> bool {
+    match serde_json::from_str::<Value>(line) {
+        Ok(Value::Array(batch)) => batch.iter().any(|m| m.get("id").is_some_and(|id| !id.is_null())),
+        Ok(message) => message.get("id").is_some_and(|id| !id.is_null()),
+        Err(_) => false,
+    }
+}
+
+async fn write_line<W: AsyncWrite + Unpin>(writer: &mut W, text: &str) -> Result<(), NexaError> {
+    writer.write_all(text.as_bytes()).await?;
+    writer.write_all(b"\n").await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tokio::io::BufReader;
+    use crate::mcp::registry::AgentRegistry;
+    use crate::tools::ToolRegistry;
+
+    fn replies(output: &[u8]) -> Vec<Value> {
+        let mut replies: Vec<Value> = String::from_utf8_lossy(output).lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        replies.sort_by_key(|r| r["id"].as_i64());
+        replies
+    }
+
+    const INPUT: &str = concat!(
+        r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2024-11-05"}}"#, "\n",
+        r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#, "\n",
+        "\n",
+        r#"{"jsonrpc":"2.0","id":2,"method":"tools/list"}"#, "\n",
+    );
+
+    #[tokio::test]
+    async fn test_serve() {
+        let handler = McpHandler::new(ToolRegistry::new(), AgentRegistry::new());
+        let mut output = Vec::new();
+        serve(&handler, BufReader::new(INPUT.as_bytes()), &mut output).await.unwrap();
+
+        let replies = replies(&output);
+        assert_eq!(replies.len(), 2);
+        assert_eq!(replies[0]["result"]["protocolVersion"], "2024-11-05");
+        assert_eq!(replies[1]["result"]["tools"], json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_relay() {
+        // A WebSocket server that also pushes an event, which must not reach the host
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut socket = tokio_tungstenite::accept_async(socket).await.unwrap();
+            let handler = McpHandler::new(ToolRegistry::new(), AgentRegistry::new());
+            socket.send(Message::Text(json!({ "type": "event", "event": {} }).to_string())).await.unwrap();
+            while let Some(Ok(Message::Text(text))) = socket.next().await {
+                if let Some(reply) = handler.handle_text(&text).await {
+                    socket.send(Message::Text(reply)).await.unwrap();
+                }
+            }
+        });
+
+        let mut output = Vec::new();
+        relay(&format!("ws://{}", addr), BufReader::new(INPUT.as_bytes()), &mut output).await.unwrap();
+        let replies = replies(&output);
+        assert_eq!(replies.len(), 2);
+        assert_eq!(replies[0]["result"]["serverInfo"]["name"], "nexa");
+        assert_eq!(replies[1]["id"], 2);
+    }
+}
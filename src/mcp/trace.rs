@@ -0,0 +1,108 @@
+//! Distributed Tracing
+//!
+//! Trace context carried by MCP messages, so a run can be followed end to end in logs:
+//! - API requests open a trace, or continue the one of a W3C `traceparent` header
+//! - Messages created while handling one carry its trace and a span of their own
+//! - Whoever handles a message, on this node or another, logs within its trace
+
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use tracing::Instrument;
+use uuid::Uuid;
+
+tokio::task_local! {
+    static CURRENT: TraceContext;
+}
+
+/// Trace and span a message or request belongs to, as lowercase hex
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceContext {
+    /// Shared by everything done for one originating request
+    pub trace_id: String,
+    /// This step of the trace
+    pub span_id: String,
+}
+
+impl TraceContext {
+    /// Open a new trace
+    pub fn new() -> Self {
+        Self { trace_id: Uuid::new_v4().simple().to_string(), span_id: new_span_id() }
+    }
+
+    /// A new span in the same trace
+    pub fn child(&self) -> Self {
+        Self { trace_id: self.trace_id.clone(), span_id: new_span_id() }
+    }
+
+    /// Context of the trace being handled by this task, if any
+    pub fn current() -> Option<Self> {
+        CURRENT.try_with(Clone::clone).ok()
+    }
+
+    /// A new span in the current trace, for a message created while handling it
+    pub fn current_child() -> Option<Self> {
+        Self::current().map(|trace| trace.child())
+    }
+
+    /// Parse a W3C `traceparent` header, `00-<trace id>-<parent span id>-<flags>`
+    pub fn from_traceparent(header: &str) -> Option<Self> {
+        let mut parts = header.trim().split('-');
+        let (version, trace_id, span_id) = (parts.next()?, parts.next()?, parts.next()?);
+        parts.next()?;
+        let hex = |s: &str, len: usize| s.len() == len && s.bytes().all(|b| b.is_ascii_hexdigit());
+        if !hex(version, 2) || !hex(trace_id, 32) || !hex(span_id, 16) || trace_id.bytes().all(|b| b == b'0') {
+            return None;
+        }
+        Some(Self { trace_id: trace_id.to_ascii_lowercase(), span_id: span_id.to_ascii_lowercase() })
+    }
+
+    /// Format as a W3C `traceparent` header
+    pub fn traceparent(&self) -> String {
+        format!("00-{}-{}-01", self.trace_id, self.span_id)
+    }
+
+    /// Run `future` within this trace, logging under a span carrying its ids
+    pub async fn scope<F: Future>(self, future: F) -> F::Output {
+        let span = tracing::info_span!("trace", trace_id = %self.trace_id, span_id = %self.span_id);
+        CURRENT.scope(self, future.instrument(span)).await
+    }
+}
+
+impl Default for TraceContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Run `future` within `trace` when there is one
+pub async fn scope<F: Future>(trace: Option<TraceContext>, future: F) -> F::Output {
+    match trace {
+        Some(trace) => trace.scope(future).await,
+        None => future.await,
+    }
+}
+
+fn new_span_id() -> String {
+    Uuid::new_v4().simple().to_string()[..16].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_trace_context() {
+        let parent = TraceContext::from_traceparent("00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01").unwrap();
+        assert_eq!(parent.trace_id, "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(parent.traceparent(), "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01");
+        assert!(TraceContext::from_traceparent("00-00000000000000000000000000000000-00f067aa0ba902b7-01").is_none());
+        assert!(TraceContext::from_traceparent("not a traceparent").is_none());
+
+        // Messages created within a trace get spans of their own in it
+        assert!(TraceContext::current().is_none());
+        let child = parent.clone().scope(async { TraceContext::current_child().unwrap() }).await;
+        assert_eq!(child.trace_id, parent.trace_id);
+        assert_ne!(child.span_id, parent.span_id);
+        assert_eq!(child.span_id.len(), 16);
+    }
+}
@@ -14,7 +14,8 @@ use crate::error::NexaError;
 use serde::{Serialize, Deserialize};
 
 /// Memory usage statistics
-#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "api-docs", derive(utoipa::ToSchema))]
 pub struct MemoryStats {
     pub total_used: usize,
     pub total_allocated: usize,
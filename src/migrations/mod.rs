@@ -0,0 +1,334 @@
+//! Data Format Migrations
+//!
+//! Keeps on-disk data readable across releases:
+//! - Versioned envelopes for agents, workflows and tasks
+//! - Versioned configuration files
+//! - Detection of legacy (unversioned) layouts
+//! - Backups before every migration
+//! - Hard failure on formats newer than this build understands
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde_json::{json, Value};
+use crate::error::NexaError;
+use tracing::{debug, info};
+
+/// Current on-disk format version
+pub const CURRENT_VERSION: u32 = 1;
+
+/// Kind of persisted data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DataKind {
+    Agents,
+    Workflows,
+    Tasks,
+    Config,
+}
+
+impl DataKind {
+    /// File name used for this kind of data in the data directory
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            DataKind::Agents => "agents.json",
+            DataKind::Workflows => "workflows.json",
+            DataKind::Tasks => "tasks.json",
+            DataKind::Config => "config.yml",
+        }
+    }
+}
+
+impl fmt::Display for DataKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DataKind::Agents => write!(f, "agents"),
+            DataKind::Workflows => write!(f, "workflows"),
+            DataKind::Tasks => write!(f, "tasks"),
+            DataKind::Config => write!(f, "config"),
+        }
+    }
+}
+
+/// A single migration step from `from` to `from + 1`
+#[derive(Clone)]
+pub struct Migration {
+    pub kind: DataKind,
+    pub from: u32,
+    pub apply: fn(Value) -> Result<Value, NexaError>,
+}
+
+impl fmt::Debug for Migration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Migration")
+            .field("kind", &self.kind)
+            .field("from", &self.from)
+            .finish()
+    }
+}
+
+/// Record of a file that was migrated
+#[derive(Debug, Clone)]
+pub struct MigrationRecord {
+    pub kind: DataKind,
+    pub path: PathBuf,
+    pub from: u32,
+    pub to: u32,
+    pub backup: PathBuf,
+}
+
+/// Detects and upgrades older on-disk formats
+#[derive(Debug, Clone)]
+pub struct Migrator {
+    data_dir: PathBuf,
+    config_path: Option<PathBuf>,
+    migrations: Vec<Migration>,
+}
+
+impl Migrator {
+    /// Create a migrator for the given data directory with the built-in migrations
+    pub fn new(data_dir: impl Into<PathBuf>) -> Self {
+        let mut migrations = Vec::new();
+        for kind in [DataKind::Agents, DataKind::Workflows, DataKind::Tasks] {
+            migrations.push(Migration { kind, from: 0, apply: wrap_legacy_json });
+        }
+        migrations.push(Migration { kind: DataKind::Config, from: 0, apply: stamp_config_version });
+
+        Self {
+            data_dir: data_dir.into(),
+            config_path: None,
+            migrations,
+        }
+    }
+
+    /// Also migrate the configuration file at the given path
+    pub fn with_config_path(mut self, path: PathBuf) -> Self {
+        self.config_path = Some(path);
+        self
+    }
+
+    /// Register an additional migration step
+    pub fn with_migration(mut self, migration: Migration) -> Self {
+        self.migrations.push(migration);
+        self
+    }
+
+    /// Kinds of data whose files are older than the current version
+    pub fn pending(&self) -> Result<Vec<DataKind>, NexaError> {
+        let mut files: Vec<(DataKind, PathBuf)> = [DataKind::Agents, DataKind::Workflows, DataKind::Tasks]
+            .into_iter()
+            .map(|kind| (kind, self.data_dir.join(kind.file_name())))
+            .collect();
+        if let Some(path) = &self.config_path {
+            files.push((DataKind::Config, path.clone()));
+        }
+
+        let mut pending = Vec::new();
+        for (kind, path) in files {
+            if path.exists() && detect_version(kind, &read_value(kind, &path)?)? < CURRENT_VERSION {
+                pending.push(kind);
+            }
+        }
+        Ok(pending)
+    }
+
+    /// Migrate every known data file to the current version
+    pub fn run(&self) -> Result<Vec<MigrationRecord>, NexaError> {
+        let mut records = Vec::new();
+        for kind in [DataKind::Agents, DataKind::Workflows, DataKind::Tasks] {
+            let path = self.data_dir.join(kind.file_name());
+            if let Some(record) = self.migrate_file(kind, &path)? {
+                records.push(record);
+            }
+        }
+        if let Some(path) = &self.config_path {
+            if let Some(record) = self.migrate_file(DataKind::Config, path)? {
+                records.push(record);
+            }
+        }
+        Ok(records)
+    }
+
+    /// Migrate a single file, returning a record if it was changed
+    pub fn migrate_file(&self, kind: DataKind, path: &Path) -> Result<Option<MigrationRecord>, NexaError> {
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let mut value = read_value(kind, path)?;
+        let from = detect_version(kind, &value)?;
+        if from > CURRENT_VERSION {
+            return Err(NexaError::config(format!(
+                "{} data at {:?} has format version {}, but this build only supports up to version {}",
+                kind, path, from, CURRENT_VERSION
+            )));
+        }
+        if from == CURRENT_VERSION {
+            debug!("{} data at {:?} is up to date", kind, path);
+            return Ok(None);
+        }
+
+        let mut version = from;
+        while version < CURRENT_VERSION {
+            let migration = self.migrations.iter()
+                .find(|m| m.kind == kind && m.from == version)
+                .ok_or_else(|| NexaError::config(format!("No migration for {} from version {}", kind, version)))?;
+            value = (migration.apply)(value)?;
+            version += 1;
+        }
+
+        let backup = backup_path(path, from);
+        fs::copy(path, &backup)
+            .map_err(|e| NexaError::config(format!("Failed to back up {:?}: {}", path, e)))?;
+        write_value(kind, path, &value)?;
+
+        info!("Migrated {} data at {:?} from version {} to {} (backup at {:?})", kind, path, from, version, backup);
+        Ok(Some(MigrationRecord {
+            kind,
+            path: path.to_path_buf(),
+            from,
+            to: version,
+            backup,
+        }))
+    }
+}
+
+/// Read the payload of a versioned JSON data file
+pub fn read_versioned(kind: DataKind, path: &Path) -> Result<Value, NexaError> {
+    let mut value = read_value(kind, path)?;
+    match detect_version(kind, &value)? {
+        CURRENT_VERSION => Ok(value.get_mut("data").map(Value::take).unwrap_or(Value::Null)),
+        version => Err(NexaError::config(format!(
+            "{} data at {:?} has format version {}, expected {}", kind, path, version, CURRENT_VERSION
+        ))),
+    }
+}
+
+/// Parse a data file and return its format version, failing on versions newer than this build
+pub fn check_file(kind: DataKind, path: &Path) -> Result<u32, NexaError> {
+    let version = detect_version(kind, &read_value(kind, path)?)?;
+    if version > CURRENT_VERSION {
+        return Err(NexaError::config(format!(
+            "{} data at {:?} has format version {}, but this build only supports up to version {}",
+            kind, path, version, CURRENT_VERSION
+        )));
+    }
+    Ok(version)
+}
+
+/// Write a payload into a versioned JSON data file
+pub fn write_versioned(kind: DataKind, path: &Path, data: Value) -> Result<(), NexaError> {
+    write_value(kind, path, &json!({ "version": CURRENT_VERSION, "data": data }))
+}
+
+/// Detect the format version of a parsed data file
+pub fn detect_version(kind: DataKind, value: &Value) -> Result<u32, NexaError> {
+    let version = match kind {
+        DataKind::Config => value.get("version"),
+        // Legacy JSON files were bare arrays/objects without an envelope
+        _ => value.as_object()
+            .filter(|o| o.contains_key("data"))
+            .and_then(|o| o.get("version")),
+    };
+    match version {
+        None => Ok(0),
+        Some(v) => v.as_u64()
+            .map(|v| v as u32)
+            .ok_or_else(|| NexaError::config(format!("Invalid {} format version: {}", kind, v))),
+    }
+}
+
+/// 0 -> 1: wrap a bare JSON document in a versioned envelope
+fn wrap_legacy_json(value: Value) -> Result<Value, NexaError> {
+    Ok(json!({ "version": 1, "data": value }))
+}
+
+/// 0 -> 1: add a version key to the configuration file
+fn stamp_config_version(value: Value) -> Result<Value, NexaError> {
+    match value {
+        Value::Object(mut map) => {
+            map.insert("version".to_string(), json!(1));
+            Ok(Value::Object(map))
+        }
+        Value::Null => Ok(json!({ "version": 1 })),
+        _ => Err(NexaError::config("Configuration file is not a mapping")),
+    }
+}
+
+fn read_value(kind: DataKind, path: &Path) -> Result<Value, NexaError> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| NexaError::config(format!("Failed to read {:?}: {}", path, e)))?;
+    match kind {
+        DataKind::Config => serde_yaml::from_str(&contents)
+            .map_err(|e| NexaError::config(format!("Failed to parse {:?}: {}", path, e))),
+        _ => serde_json::from_str(&contents)
+            .map_err(|e| NexaError::config(format!("Failed to parse {:?}: {}", path, e))),
+    }
+}
+
+fn write_value(kind: DataKind, path: &Path, value: &Value) -> Result<(), NexaError> {
+    let contents = match kind {
+        DataKind::Config => serde_yaml::to_string(value)
+            .map_err(|e| NexaError::config(format!("Failed to serialize {:?}: {}", path, e)))?,
+        _ => serde_json::to_string_pretty(value)?,
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| NexaError::config(format!("Failed to create directory {:?}: {}", parent, e)))?;
+    }
+    fs::write(path, contents)
+        .map_err(|e| NexaError::config(format!("Failed to write {:?}: {}", path, e)))
+}
+
+fn backup_path(path: &Path, version: u32) -> PathBuf {
+    let file_name = path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    path.with_file_name(format!("{}.v{}.bak", file_name, version))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrates_legacy_json_with_backup() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join(DataKind::Agents.file_name());
+        fs::write(&path, r#"[{"id": "agent-1"}]"#).unwrap();
+
+        assert_eq!(Migrator::new(temp_dir.path()).pending().unwrap(), vec![DataKind::Agents]);
+        let records = Migrator::new(temp_dir.path()).run().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].from, 0);
+        assert_eq!(records[0].to, CURRENT_VERSION);
+        assert!(records[0].backup.exists());
+
+        let data = read_versioned(DataKind::Agents, &path).unwrap();
+        assert_eq!(data[0]["id"], "agent-1");
+
+        // Already migrated files are left alone
+        assert!(Migrator::new(temp_dir.path()).run().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_migrates_config() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("config.yml");
+        fs::write(&path, "server:\n  host: 127.0.0.1\n  port: 8080\n").unwrap();
+
+        let records = Migrator::new(temp_dir.path()).with_config_path(path.clone()).run().unwrap();
+        assert_eq!(records.len(), 1);
+        assert!(crate::config::Config::validate_file(&path).unwrap().is_empty());
+        assert_eq!(crate::config::Config::load(&path).unwrap().version, CURRENT_VERSION);
+    }
+
+    #[test]
+    fn test_rejects_future_version() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join(DataKind::Tasks.file_name());
+        fs::write(&path, r#"{"version": 99, "data": []}"#).unwrap();
+
+        assert!(Migrator::new(temp_dir.path()).run().is_err());
+        assert!(!backup_path(&path, 99).exists());
+    }
+}
@@ -3,24 +3,25 @@
 //! This module provides real-time monitoring and metrics tracking:
 //! - Resource utilization monitoring
 //! - Performance metrics
-//! - Health checks
+//! - Health checks, including probes of the LLM providers
 //! - Alert system
 //! - Metrics aggregation
 
 use std::sync::Arc;
-use tokio::sync::RwLock;
-use std::collections::HashMap;
+use tokio::sync::{broadcast, RwLock, watch};
+use std::collections::{BTreeMap, HashMap};
 use chrono::{DateTime, Utc};
+use crate::config::{Config, MonitoringConfig};
 use crate::error::NexaError;
 use crate::memory::MemoryManager;
 use crate::tokens::{TokenManager, TokenUsage};
 use serde::{Serialize, Deserialize};
 use std::time::Duration;
 use sysinfo::System;
-use utoipa;
 use tracing::debug;
 
-#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "api-docs", derive(utoipa::ToSchema))]
 pub struct SystemMetrics {
     pub cpu_usage: f64,
     pub memory_used: usize,
@@ -30,6 +31,9 @@ pub struct SystemMetrics {
     pub token_cost: f64,
     pub active_agents: u32,
     pub error_count: usize,
+    /// Completed LLM requests per provider, keyed by `<server type>/<model>`
+    #[serde(default)]
+    pub llm_requests_by_provider: BTreeMap<String, u64>,
     pub timestamp: chrono::DateTime<chrono::Utc>,
 }
 
@@ -44,26 +48,44 @@ impl Default for SystemMetrics {
             token_cost: 0.0,
             active_agents: 0,
             error_count: 0,
+            llm_requests_by_provider: BTreeMap::new(),
             timestamp: Utc::now(),
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "api-docs", derive(utoipa::ToSchema))]
 pub struct SystemHealth {
     pub is_healthy: bool,
     pub message: String,
     pub timestamp: DateTime<Utc>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+/// Latest health probe of an LLM provider
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "api-docs", derive(utoipa::ToSchema))]
+pub struct ProviderHealth {
+    /// `<server type>/<model>`
+    pub provider: String,
+    pub server_url: String,
+    pub available: bool,
+    /// Round trip of the probe in milliseconds
+    pub latency_ms: u64,
+    pub error: Option<String>,
+    pub checked_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "api-docs", derive(utoipa::ToSchema))]
 pub struct SystemAlert {
     pub level: AlertLevel,
     pub message: String,
     pub timestamp: DateTime<Utc>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "api-docs", derive(utoipa::ToSchema))]
 pub enum AlertLevel {
     Info,
     Warning,
@@ -82,7 +104,8 @@ impl std::fmt::Display for AlertLevel {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "api-docs", derive(utoipa::ToSchema))]
 pub enum ResourceType {
     Memory,
     CPU,
@@ -90,7 +113,8 @@ pub enum ResourceType {
     Storage,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "api-docs", derive(utoipa::ToSchema))]
 pub struct Resource {
     pub name: String,
     pub resource_type: ResourceType,
@@ -102,15 +126,20 @@ pub struct Resource {
 pub struct MonitoringSystem {
     memory_manager: Arc<MemoryManager>,
     token_manager: Arc<TokenManager>,
-    cpu_threshold: f64,
-    memory_threshold: f64,
+    cpu_threshold: Arc<parking_lot::RwLock<f64>>,
+    memory_threshold: Arc<parking_lot::RwLock<f64>>,
+    check_interval: Arc<parking_lot::RwLock<Duration>>,
     metrics_history: Arc<RwLock<Vec<SystemMetrics>>>,
     health_status: Arc<RwLock<SystemHealth>>,
     alerts: Arc<RwLock<Vec<SystemAlert>>>,
     resources: Arc<RwLock<HashMap<String, Resource>>>,
+    provider_health: Arc<RwLock<BTreeMap<String, ProviderHealth>>>,
+    /// Alerts sent to subscribers as they are raised
+    raised: broadcast::Sender<SystemAlert>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "api-docs", derive(utoipa::ToSchema))]
 pub struct SystemStatus {
     pub metrics: SystemMetrics,
     pub health: SystemHealth,
@@ -123,8 +152,9 @@ impl MonitoringSystem {
         let system = Self {
             memory_manager,
             token_manager,
-            cpu_threshold: 80.0,
-            memory_threshold: 90.0,
+            cpu_threshold: Arc::new(parking_lot::RwLock::new(80.0)),
+            memory_threshold: Arc::new(parking_lot::RwLock::new(90.0)),
+            check_interval: Arc::new(parking_lot::RwLock::new(Duration::from_secs(30))),
             metrics_history: Arc::new(RwLock::new(Vec::new())),
             health_status: Arc::new(RwLock::new(SystemHealth {
                 is_healthy: true,
@@ -133,10 +163,12 @@ impl MonitoringSystem {
             })),
             alerts: Arc::new(RwLock::new(Vec::new())),
             resources: Arc::new(RwLock::new(HashMap::new())),
+            provider_health: Arc::new(RwLock::new(BTreeMap::new())),
+            raised: broadcast::channel(100).0,
         };
         
         debug!("Initialized monitoring system with thresholds - CPU: {}, Memory: {}", 
-            system.cpu_threshold, system.memory_threshold);
+            system.cpu_threshold(), system.memory_threshold());
         
         system
     }
@@ -164,6 +196,7 @@ impl MonitoringSystem {
             token_cost: token_usage.cost,
             active_agents,
             error_count: 0,
+            llm_requests_by_provider: BTreeMap::new(),
             timestamp: Utc::now(),
         };
 
@@ -189,8 +222,10 @@ impl MonitoringSystem {
             metrics.memory_used as f64 / metrics.memory_allocated as f64 * 100.0
         };
 
-        let is_healthy = metrics.cpu_usage <= self.cpu_threshold && 
-                        memory_percentage <= self.memory_threshold;
+        let cpu_threshold = self.cpu_threshold();
+        let memory_threshold = self.memory_threshold();
+        let is_healthy = metrics.cpu_usage <= cpu_threshold && 
+                        memory_percentage <= memory_threshold;
         
         let message = if is_healthy {
             "System healthy".to_string()
@@ -205,9 +240,9 @@ impl MonitoringSystem {
         debug!(
             "Health check metrics - CPU: {:.1}% (threshold: {:.1}%), Memory: {:.1}% (threshold: {:.1}%)",
             metrics.cpu_usage,
-            self.cpu_threshold,
+            cpu_threshold,
             memory_percentage,
-            self.memory_threshold
+            memory_threshold
         );
 
         let health = SystemHealth {
@@ -228,6 +263,8 @@ impl MonitoringSystem {
             message,
             timestamp: chrono::Utc::now(),
         };
+        // Sending only fails when nobody is subscribed
+        let _ = self.raised.send(alert.clone());
         
         let mut alerts = self.alerts.write().await;
         alerts.push(alert);
@@ -238,6 +275,28 @@ impl MonitoringSystem {
         }
     }
 
+    /// Receive every alert raised from now on
+    pub fn subscribe_alerts(&self) -> broadcast::Receiver<SystemAlert> {
+        self.raised.subscribe()
+    }
+
+    /// Record an LLM provider probe, alerting when the provider becomes unavailable
+    pub async fn record_provider_health(&self, health: ProviderHealth) {
+        let previous = self.provider_health.write().await.insert(health.provider.clone(), health.clone());
+        if !health.available && previous.is_none_or(|p| p.available) {
+            let message = format!(
+                "LLM provider {} unavailable: {}",
+                health.provider, health.error.as_deref().unwrap_or("unknown error")
+            );
+            self.raise_alert(AlertLevel::Warning, message, HashMap::new()).await;
+        }
+    }
+
+    /// Latest probe of each LLM provider
+    pub async fn provider_health(&self) -> Vec<ProviderHealth> {
+        self.provider_health.read().await.values().cloned().collect()
+    }
+
     /// Get recent alerts
     pub async fn get_recent_alerts(&self, since: DateTime<Utc>) -> Vec<SystemAlert> {
         let alerts = self.alerts.read().await;
@@ -260,24 +319,10 @@ impl MonitoringSystem {
 
     /// Start background monitoring
     pub async fn start_monitoring(&self, interval: Duration) -> Result<(), NexaError> {
-        let metrics_history = self.metrics_history.clone();
-        let health_status = self.health_status.clone();
-        let alerts = self.alerts.clone();
-        let memory_manager = self.memory_manager.clone();
-        let token_manager = self.token_manager.clone();
+        *self.check_interval.write() = interval;
+        let monitor = self.clone();
 
         tokio::spawn(async move {
-            let monitor = MonitoringSystem {
-                cpu_threshold: 80.0,
-                memory_threshold: 80.0,
-                memory_manager,
-                token_manager,
-                metrics_history,
-                health_status,
-                alerts,
-                resources: Arc::new(RwLock::new(HashMap::new())),
-            };
-
             loop {
                 if let Err(e) = monitor.check_health().await {
                     let mut metadata = HashMap::new();
@@ -288,6 +333,7 @@ impl MonitoringSystem {
                         metadata,
                     ).await;
                 }
+                let interval = *monitor.check_interval.read();
                 tokio::time::sleep(interval).await;
             }
         });
@@ -295,17 +341,40 @@ impl MonitoringSystem {
         Ok(())
     }
 
+    /// Apply thresholds and intervals from a monitoring configuration
+    pub fn apply_config(&self, config: &MonitoringConfig) {
+        self.set_cpu_threshold(config.cpu_threshold);
+        self.set_memory_threshold(config.memory_threshold);
+        *self.check_interval.write() = Duration::from_secs(config.health_check_interval.max(1));
+    }
+
+    /// Apply monitoring settings from config updates as they arrive
+    pub fn watch_config(&self, mut rx: watch::Receiver<Config>) -> tokio::task::JoinHandle<()> {
+        let monitor = self.clone();
+        tokio::spawn(async move {
+            loop {
+                monitor.apply_config(&rx.borrow_and_update().monitoring);
+                if rx.changed().await.is_err() {
+                    break;
+                }
+            }
+        })
+    }
+
     pub fn get_alerts(&self, metrics: &SystemMetrics) -> Vec<SystemAlert> {
         let mut alerts = Vec::new();
 
+        let cpu_threshold = self.cpu_threshold();
+        let memory_threshold = self.memory_threshold();
+
         // Check CPU usage
-        if metrics.cpu_usage > self.cpu_threshold {
+        if metrics.cpu_usage > cpu_threshold {
             alerts.push(SystemAlert {
                 level: AlertLevel::Critical,
                 message: format!("CPU usage critical: {:.1}%", metrics.cpu_usage),
                 timestamp: Utc::now(),
             });
-        } else if metrics.cpu_usage > self.cpu_threshold * 0.8 {
+        } else if metrics.cpu_usage > cpu_threshold * 0.8 {
             alerts.push(SystemAlert {
                 level: AlertLevel::Warning,
                 message: format!("CPU usage high: {:.1}%", metrics.cpu_usage),
@@ -315,13 +384,13 @@ impl MonitoringSystem {
 
         // Check memory usage
         let memory_usage_percent = (metrics.memory_used as f64 / metrics.memory_allocated as f64) * 100.0;
-        if memory_usage_percent > self.memory_threshold {
+        if memory_usage_percent > memory_threshold {
             alerts.push(SystemAlert {
                 level: AlertLevel::Critical,
                 message: format!("Memory usage critical: {:.1}%", memory_usage_percent),
                 timestamp: Utc::now(),
             });
-        } else if memory_usage_percent > self.memory_threshold * 0.8 {
+        } else if memory_usage_percent > memory_threshold * 0.8 {
             alerts.push(SystemAlert {
                 level: AlertLevel::Warning,
                 message: format!("Memory usage high: {:.1}%", memory_usage_percent),
@@ -342,15 +411,25 @@ impl MonitoringSystem {
     }
 
     /// Set CPU usage threshold (percentage)
-    pub fn set_cpu_threshold(&mut self, threshold: f64) {
+    pub fn set_cpu_threshold(&self, threshold: f64) {
         debug!("Setting CPU threshold to {}", threshold);
-        self.cpu_threshold = threshold;
+        *self.cpu_threshold.write() = threshold;
     }
 
     /// Set memory usage threshold (percentage)
-    pub fn set_memory_threshold(&mut self, threshold: f64) {
+    pub fn set_memory_threshold(&self, threshold: f64) {
         debug!("Setting memory threshold to {}", threshold);
-        self.memory_threshold = threshold;
+        *self.memory_threshold.write() = threshold;
+    }
+
+    /// Get CPU usage threshold (percentage)
+    pub fn cpu_threshold(&self) -> f64 {
+        *self.cpu_threshold.read()
+    }
+
+    /// Get memory usage threshold (percentage)
+    pub fn memory_threshold(&self) -> f64 {
+        *self.memory_threshold.read()
     }
 
     pub async fn allocate(&self, name: String, resource_type: ResourceType, size: usize, _metadata: HashMap<String, String>) {
@@ -444,6 +523,23 @@ mod tests {
         assert!(!metrics.is_empty());
     }
 
+    #[tokio::test]
+    async fn test_config_subscription() {
+        let monitoring = MonitoringSystem::default();
+        let service = crate::config::ConfigService::new(Config::default());
+        let handle = monitoring.watch_config(service.subscribe());
+
+        let mut config = service.current();
+        config.monitoring.cpu_threshold = 42.0;
+        config.monitoring.memory_threshold = 55.0;
+        service.update(config);
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(monitoring.cpu_threshold(), 42.0);
+        assert_eq!(monitoring.memory_threshold(), 55.0);
+        handle.abort();
+    }
+
     #[tokio::test]
     async fn test_resource_allocation() {
         let memory_manager = Arc::new(MemoryManager::new());
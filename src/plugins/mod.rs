@@ -0,0 +1,434 @@
+//! WASM Plugins
+//!
+//! Loads `.wasm` modules from the plugins directory and registers their tools:
+//! - One sandboxed wasmtime instance per plugin, with a fuel limit per call
+//! - Tools declared in the plugin manifest, added to the `ToolRegistry`
+//! - Host API for logging, reading the plugin's resources, calling the LLM and emitting events
+//!
+//! # Plugin ABI
+//!
+//! Strings cross the boundary as UTF-8 JSON. A `(ptr, len)` pair returned
+//! as a single `i64` is packed as `ptr << 32 | len`.
+//!
+//! A plugin exports:
+//! - `memory`
+//! - `nexa_alloc(len: i32) -> i32` - allocate `len` bytes for the host to write into
+//! - `nexa_manifest() -> i64` - a `PluginManifest`
+//! - `nexa_call(name_ptr, name_len, args_ptr, args_len: i32) -> i64` - a `CallResult`
+//!
+//! The host provides, in the `nexa` import module:
+//! - `log(level, ptr, len: i32)` - level 0-4 is trace to error
+//! - `emit_event(ptr, len: i32) -> i32` - a JSON event, returns 0 on success
+//! - `read_resource(ptr, len: i32) -> i64` - a `CallResult` with a file from the plugin's resource directory
+//! - `llm_complete(ptr, len: i32) -> i64` - a `CallResult` with the completion of a prompt
+
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use futures::future::BoxFuture;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::broadcast;
+use wasmtime::{Caller, Engine, Instance, Linker, Module, Store};
+use crate::error::NexaError;
+use crate::llm::LLMSupervisor;
+use crate::tools::{Tool, ToolRegistry, ToolSpec};
+use tracing::{debug, error, info, trace, warn};
+
+/// Fuel available to a single plugin call
+pub const DEFAULT_FUEL: u64 = 1_000_000_000;
+
+/// What a plugin declares about itself
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PluginManifest {
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub tools: Vec<ToolSpec>,
+}
+
+/// Result envelope passed across the plugin boundary
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CallResult {
+    Ok(Value),
+    Error(String),
+}
+
+/// Event emitted by a plugin
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginEvent {
+    pub plugin: String,
+    pub event: Value,
+}
+
+struct HostState {
+    plugin: String,
+    resource_dir: PathBuf,
+    llm: Option<LLMSupervisor>,
+    events: broadcast::Sender<PluginEvent>,
+    runtime: tokio::runtime::Handle,
+    /// Set while the manifest is read on an async worker, where blocking is not allowed
+    loading: bool,
+}
+
+struct PluginInstance {
+    store: Store<HostState>,
+    instance: Instance,
+}
+
+/// Loads plugins and keeps their instances alive
+#[derive(Clone)]
+pub struct PluginHost {
+    engine: Engine,
+    dir: PathBuf,
+    llm: Option<LLMSupervisor>,
+    fuel: u64,
+    events: broadcast::Sender<PluginEvent>,
+}
+
+impl std::fmt::Debug for PluginHost {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PluginHost")
+            .field("dir", &self.dir)
+            .field("fuel", &self.fuel)
+            .finish()
+    }
+}
+
+impl PluginHost {
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Self, NexaError> {
+        let mut config = wasmtime::Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config)
+            .map_err(|e| NexaError::system(format!("Failed to create WASM engine: {}", e)))?;
+        let (events, _) = broadcast::channel(256);
+        Ok(Self {
+            engine,
+            dir: dir.into(),
+            llm: None,
+            fuel: DEFAULT_FUEL,
+            events,
+        })
+    }
+
+    /// LLM used by the `llm_complete` host call
+    pub fn with_llm(mut self, llm: LLMSupervisor) -> Self {
+        self.llm = Some(llm);
+        self
+    }
+
+    /// Fuel available to a single plugin call
+    pub fn with_fuel(mut self, fuel: u64) -> Self {
+        self.fuel = fuel;
+        self
+    }
+
+    /// Subscribe to events emitted by plugins
+    pub fn subscribe_events(&self) -> broadcast::Receiver<PluginEvent> {
+        self.events.subscribe()
+    }
+
+    /// Load every `.wasm` file in the plugins directory and register its tools
+    ///
+    /// A plugin that fails to load is logged and skipped.
+    pub fn load_all(&self, registry: &ToolRegistry) -> Result<Vec<PluginManifest>, NexaError> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                debug!("No plugins directory at {:?}", self.dir);
+                return Ok(Vec::new());
+            }
+            Err(e) => return Err(NexaError::system(format!("Failed to read plugins directory {:?}: {}", self.dir, e))),
+        };
+
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "wasm"))
+            .collect();
+        paths.sort();
+
+        let mut loaded = Vec::new();
+        for path in paths {
+            match self.load(&path, registry) {
+                Ok(manifest) => loaded.push(manifest),
+                Err(e) => error!("Failed to load plugin {:?}: {}", path, e),
+            }
+        }
+        Ok(loaded)
+    }
+
+    /// Load a single plugin and register its tools
+    pub fn load(&self, path: &Path, registry: &ToolRegistry) -> Result<PluginManifest, NexaError> {
+        let bytes = fs::read(path)
+            .map_err(|e| NexaError::system(format!("Failed to read {:?}: {}", path, e)))?;
+        let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        self.load_bytes(&stem, &bytes, registry)
+    }
+
+    /// Load a plugin from WASM (or WAT) bytes and register its tools
+    pub fn load_bytes(&self, name: &str, bytes: &[u8], registry: &ToolRegistry) -> Result<PluginManifest, NexaError> {
+        let module = Module::new(&self.engine, bytes)
+            .map_err(|e| NexaError::invalid_input(format!("Invalid plugin module '{}': {}", name, e)))?;
+        let state = HostState {
+            plugin: name.to_string(),
+            resource_dir: self.dir.join(name),
+            llm: self.llm.clone(),
+            events: self.events.clone(),
+            runtime: tokio::runtime::Handle::try_current()
+                .map_err(|_| NexaError::system("Plugins must be loaded inside a Tokio runtime"))?,
+            loading: true,
+        };
+        let mut store = Store::new(&self.engine, state);
+        store.set_fuel(self.fuel).map_err(plugin_error)?;
+
+        let linker = host_linker(&self.engine)?;
+        let instance = linker.instantiate(&mut store, &module).map_err(plugin_error)?;
+        let mut plugin = PluginInstance { store, instance };
+
+        let manifest_fn = plugin.instance
+            .get_typed_func::<(), i64>(&mut plugin.store, "nexa_manifest")
+            .map_err(plugin_error)?;
+        let packed = manifest_fn.call(&mut plugin.store, ()).map_err(plugin_error)?;
+        let manifest: PluginManifest = serde_json::from_slice(&read_packed(&mut plugin, packed)?)
+            .map_err(|e| NexaError::invalid_response(format!("Invalid manifest from plugin '{}': {}", name, e)))?;
+        plugin.store.data_mut().loading = false;
+
+        let plugin = Arc::new(Mutex::new(plugin));
+        for spec in &manifest.tools {
+            registry.register(Arc::new(PluginTool {
+                spec: spec.clone(),
+                plugin: plugin.clone(),
+                fuel: self.fuel,
+            }))?;
+        }
+
+        info!("Loaded plugin {} v{} with {} tool(s)", manifest.name, manifest.version, manifest.tools.len());
+        Ok(manifest)
+    }
+}
+
+/// A tool implemented by a plugin
+struct PluginTool {
+    spec: ToolSpec,
+    plugin: Arc<Mutex<PluginInstance>>,
+    fuel: u64,
+}
+
+impl Tool for PluginTool {
+    fn spec(&self) -> &ToolSpec {
+        &self.spec
+    }
+
+    fn call(&self, args: Value) -> BoxFuture<'_, Result<Value, NexaError>> {
+        let plugin = self.plugin.clone();
+        let name = self.spec.name.clone();
+        let fuel = self.fuel;
+        Box::pin(async move {
+            // Plugin code and blocking host calls run off the async workers
+            tokio::task::spawn_blocking(move || {
+                let mut plugin = plugin.lock();
+                call_plugin(&mut plugin, &name, &args, fuel)
+            })
+            .await
+            .map_err(|e| NexaError::system(format!("Plugin call panicked: {}", e)))?
+        })
+    }
+}
+
+fn call_plugin(plugin: &mut PluginInstance, name: &str, args: &Value, fuel: u64) -> Result<Value, NexaError> {
+    plugin.store.set_fuel(fuel).map_err(plugin_error)?;
+    let (name_ptr, name_len) = write_guest(plugin, name.as_bytes())?;
+    let (args_ptr, args_len) = write_guest(plugin, serde_json::to_string(args)?.as_bytes())?;
+
+    let call = plugin.instance
+        .get_typed_func::<(i32, i32, i32, i32), i64>(&mut plugin.store, "nexa_call")
+        .map_err(plugin_error)?;
+    let packed = call.call(&mut plugin.store, (name_ptr, name_len, args_ptr, args_len))
+        .map_err(plugin_error)?;
+
+    match serde_json::from_slice(&read_packed(plugin, packed)?)
+        .map_err(|e| NexaError::invalid_response(format!("Invalid result from tool '{}': {}", name, e)))?
+    {
+        CallResult::Ok(value) => Ok(value),
+        CallResult::Error(message) => Err(NexaError::agent(format!("Tool '{}' failed: {}", name, message))),
+    }
+}
+
+fn host_linker(engine: &Engine) -> Result<Linker<HostState>, NexaError> {
+    let mut linker = Linker::new(engine);
+
+    linker.func_wrap("nexa", "log", |mut caller: Caller<'_, HostState>, level: i32, ptr: i32, len: i32| {
+        let message = read_caller_string(&mut caller, ptr, len).unwrap_or_default();
+        let plugin = &caller.data().plugin;
+        match level {
+            0 => trace!("[plugin {}] {}", plugin, message),
+            1 => debug!("[plugin {}] {}", plugin, message),
+            2 => info!("[plugin {}] {}", plugin, message),
+            3 => warn!("[plugin {}] {}", plugin, message),
+            _ => error!("[plugin {}] {}", plugin, message),
+        }
+    }).map_err(plugin_error)?;
+
+    linker.func_wrap("nexa", "emit_event", |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| -> i32 {
+        let Some(event) = read_caller_string(&mut caller, ptr, len)
+            .and_then(|s| serde_json::from_str::<Value>(&s).ok())
+        else {
+            return -1;
+        };
+        let state = caller.data();
+        debug!("Plugin {} emitted event {}", state.plugin, event);
+        // Nobody may be listening; that is not the plugin's problem
+        let _ = state.events.send(PluginEvent { plugin: state.plugin.clone(), event });
+        0
+    }).map_err(plugin_error)?;
+
+    linker.func_wrap("nexa", "read_resource", |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| -> wasmtime::Result<i64> {
+        let result = match read_caller_string(&mut caller, ptr, len) {
+            Some(path) => read_resource(&caller.data().resource_dir, &path),
+            None => CallResult::Error("Invalid resource path".to_string()),
+        };
+        write_result(&mut caller, &result)
+    }).map_err(plugin_error)?;
+
+    linker.func_wrap("nexa", "llm_complete", |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| -> wasmtime::Result<i64> {
+        let result = match (read_caller_string(&mut caller, ptr, len), caller.data().llm.clone()) {
+            (None, _) => CallResult::Error("Invalid prompt".to_string()),
+            (Some(_), None) => CallResult::Error("No LLM is available to plugins".to_string()),
+            (Some(_), Some(_)) if caller.data().loading => {
+                CallResult::Error("llm_complete is not available while the plugin loads".to_string())
+            }
+            (Some(prompt), Some(llm)) => match caller.data().runtime.block_on(llm.complete(&prompt)) {
+                Ok(text) => CallResult::Ok(Value::String(text)),
+                Err(e) => CallResult::Error(e.to_string()),
+            },
+        };
+        write_result(&mut caller, &result)
+    }).map_err(plugin_error)?;
+
+    Ok(linker)
+}
+
+fn read_resource(resource_dir: &Path, path: &str) -> CallResult {
+    let relative = Path::new(path);
+    if !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+        return CallResult::Error(format!("Resource path '{}' must stay inside the plugin directory", path));
+    }
+    match fs::read_to_string(resource_dir.join(relative)) {
+        Ok(contents) => CallResult::Ok(Value::String(contents)),
+        Err(e) => CallResult::Error(format!("Failed to read resource '{}': {}", path, e)),
+    }
+}
+
+fn read_caller_string(caller: &mut Caller<'_, HostState>, ptr: i32, len: i32) -> Option<String> {
+    let memory = caller.get_export("memory")?.into_memory()?;
+    let data = memory.data(&caller);
+    let start = usize::try_from(ptr).ok()?;
+    let bytes = data.get(start..start.checked_add(usize::try_from(len).ok()?)?)?;
+    String::from_utf8(bytes.to_vec()).ok()
+}
+
+fn write_result(caller: &mut Caller<'_, HostState>, result: &CallResult) -> wasmtime::Result<i64> {
+    let bytes = serde_json::to_vec(result)?;
+    let alloc = caller.get_export("nexa_alloc")
+        .and_then(|e| e.into_func())
+        .ok_or_else(|| wasmtime::Error::msg("plugin does not export nexa_alloc"))?
+        .typed::<i32, i32>(&caller)?;
+    let ptr = alloc.call(&mut *caller, bytes.len() as i32)?;
+    let memory = caller.get_export("memory")
+        .and_then(|e| e.into_memory())
+        .ok_or_else(|| wasmtime::Error::msg("plugin does not export memory"))?;
+    memory.write(&mut *caller, ptr as usize, &bytes)?;
+    Ok(pack(ptr, bytes.len() as i32))
+}
+
+fn write_guest(plugin: &mut PluginInstance, bytes: &[u8]) -> Result<(i32, i32), NexaError> {
+    let alloc = plugin.instance
+        .get_typed_func::<i32, i32>(&mut plugin.store, "nexa_alloc")
+        .map_err(plugin_error)?;
+    let ptr = alloc.call(&mut plugin.store, bytes.len() as i32).map_err(plugin_error)?;
+    let memory = plugin.instance.get_memory(&mut plugin.store, "memory")
+        .ok_or_else(|| NexaError::invalid_response("Plugin does not export memory"))?;
+    memory.write(&mut plugin.store, ptr as usize, bytes).map_err(plugin_error)?;
+    Ok((ptr, bytes.len() as i32))
+}
+
+fn read_packed(plugin: &mut PluginInstance, packed: i64) -> Result<Vec<u8>, NexaError> {
+    let (ptr, len) = unpack(packed);
+    let memory = plugin.instance.get_memory(&mut plugin.store, "memory")
+        .ok_or_else(|| NexaError::invalid_response("Plugin does not export memory"))?;
+    let mut buf = vec![0; len];
+    memory.read(&plugin.store, ptr, &mut buf).map_err(plugin_error)?;
+    Ok(buf)
+}
+
+fn pack(ptr: i32, len: i32) -> i64 {
+    ((ptr as u32 as i64) << 32) | len as u32 as i64
+}
+
+fn unpack(packed: i64) -> (usize, usize) {
+    ((packed as u64 >> 32) as usize, (packed as u64 & 0xffff_ffff) as usize)
+}
+
+fn plugin_error(e: impl std::fmt::Display) -> NexaError {
+    NexaError::agent(format!("Plugin error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Echoes `{"ok": <args>}` by wrapping the arguments in place, and reads a resource
+    const ECHO_PLUGIN: &str = r#"
+        (module
+          (import "nexa" "read_resource" (func $read_resource (param i32 i32) (result i64)))
+          (memory (export "memory") 1)
+          (global $next (mut i32) (i32.const 1024))
+          (data (i32.const 0) "{\"name\":\"echo\",\"version\":\"1.0.0\",\"tools\":[{\"name\":\"echo\",\"description\":\"Echo\"},{\"name\":\"readme\",\"description\":\"Read\"}]}")
+          (data (i32.const 512) "README")
+          (func (export "nexa_alloc") (param $len i32) (result i32)
+            (local $ptr i32)
+            (local.set $ptr (global.get $next))
+            (global.set $next (i32.add (global.get $next) (local.get $len)))
+            (local.get $ptr))
+          (func (export "nexa_manifest") (result i64)
+            (i64.const 119))
+          (func (export "nexa_call") (param $name i32) (param $name_len i32) (param $args i32) (param $args_len i32) (result i64)
+            (local $out i32)
+            ;; tool "readme" (6 bytes) reads the README resource
+            (if (i32.eq (local.get $name_len) (i32.const 6))
+              (then (return (call $read_resource (i32.const 512) (i32.const 6)))))
+            ;; {"ok": <args>}
+            (local.set $out (global.get $next))
+            (global.set $next (i32.add (global.get $next) (i32.add (local.get $args_len) (i32.const 7))))
+            (i64.store (local.get $out) (i64.const 0x3a226b6f227b))
+            (memory.copy (i32.add (local.get $out) (i32.const 6)) (local.get $args) (local.get $args_len))
+            (i32.store8 (i32.add (local.get $out) (i32.add (local.get $args_len) (i32.const 6))) (i32.const 125))
+            (i64.or
+              (i64.shl (i64.extend_i32_u (local.get $out)) (i64.const 32))
+              (i64.extend_i32_u (i32.add (local.get $args_len) (i32.const 7))))))
+    "#;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_plugin_tools() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(temp_dir.path().join("echo")).unwrap();
+        fs::write(temp_dir.path().join("echo").join("README"), "plugin docs").unwrap();
+        fs::write(temp_dir.path().join("echo.wasm"), ECHO_PLUGIN).unwrap();
+        fs::write(temp_dir.path().join("broken.wasm"), "not wasm").unwrap();
+
+        let registry = ToolRegistry::new();
+        let loaded = PluginHost::new(temp_dir.path()).unwrap().load_all(&registry).unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].name, "echo");
+        assert_eq!(registry.list().len(), 2);
+
+        let result = registry.call("echo", json!({ "text": "hi" })).await.unwrap();
+        assert_eq!(result, json!({ "text": "hi" }));
+        assert_eq!(registry.call("readme", json!({})).await.unwrap(), "plugin docs");
+
+        assert!(matches!(read_resource(temp_dir.path(), "../secret"), CallResult::Error(_)));
+    }
+}
@@ -0,0 +1,300 @@
+//! Crash Recovery
+//!
+//! Reconciles state left behind by a previous run:
+//! - Runtime state journal written while the server runs
+//! - Stale PID and state file cleanup
+//! - Interrupted task detection
+//! - Orphaned memory allocation release
+//! - Requeueing of undelivered messages
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use nix::libc;
+use crate::agent::{Task, TaskStatus};
+use crate::error::NexaError;
+use crate::llm::{LLMAvailability, LLMSupervisor};
+use crate::mcp::ServerControl;
+use crate::mcp::buffer::BufferedMessage;
+use tracing::{debug, error, info, warn};
+
+/// Name of the runtime state file in the runtime directory
+pub const STATE_FILE: &str = "nexa.state";
+
+/// Snapshot of runtime state persisted while the server runs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuntimeState {
+    /// PID of the process that wrote the snapshot
+    pub pid: u32,
+    /// Time the snapshot was written
+    pub updated_at: DateTime<Utc>,
+    /// Set when the server shut down cleanly
+    pub clean_shutdown: bool,
+    /// Tasks known to the registry
+    pub tasks: Vec<Task>,
+    /// Messages still waiting in the buffer
+    pub pending_messages: Vec<BufferedMessage>,
+    /// LLM provider availability, if the server supervises providers
+    #[serde(default)]
+    pub llm_availability: Option<LLMAvailability>,
+    /// LLM requests queued while providers are unavailable
+    #[serde(default)]
+    pub queued_llm_requests: usize,
+    /// Config profile the server was started with
+    #[serde(default)]
+    pub profile: Option<String>,
+}
+
+/// Writes runtime state snapshots for crash recovery
+pub struct StateJournal;
+
+impl StateJournal {
+    /// Capture the current runtime state of a server
+    pub async fn snapshot(server: &ServerControl) -> Result<RuntimeState, NexaError> {
+        let llm = server.llm_supervisor().await;
+        Ok(RuntimeState {
+            pid: std::process::id(),
+            updated_at: Utc::now(),
+            clean_shutdown: false,
+            tasks: server.registry.list_tasks().await?,
+            pending_messages: server.pending_messages(),
+            llm_availability: llm.as_ref().map(LLMSupervisor::availability),
+            queued_llm_requests: llm.as_ref().map_or(0, LLMSupervisor::queued),
+            profile: server.config_service().profile(),
+        })
+    }
+
+    /// Write a state snapshot to disk
+    pub fn save(path: &Path, state: &RuntimeState) -> Result<(), NexaError> {
+        let contents = serde_json::to_string(state)?;
+        let tmp = path.with_extension("state.tmp");
+        fs::write(&tmp, contents)
+            .map_err(|e| NexaError::system(format!("Failed to write state file: {}", e)))?;
+        fs::rename(&tmp, path)
+            .map_err(|e| NexaError::system(format!("Failed to replace state file: {}", e)))
+    }
+
+    /// Read a state snapshot from disk
+    pub fn load(path: &Path) -> Result<Option<RuntimeState>, NexaError> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let contents = fs::read_to_string(path)
+            .map_err(|e| NexaError::system(format!("Failed to read state file: {}", e)))?;
+        serde_json::from_str(&contents)
+            .map(Some)
+            .map_err(|e| NexaError::system(format!("Failed to parse state file: {}", e)))
+    }
+
+    /// Mark the last snapshot as belonging to a clean shutdown
+    pub fn mark_clean_shutdown(path: &Path) -> Result<(), NexaError> {
+        if let Some(mut state) = Self::load(path)? {
+            state.clean_shutdown = true;
+            state.updated_at = Utc::now();
+            Self::save(path, &state)?;
+        }
+        Ok(())
+    }
+
+    /// Periodically snapshot a server's state until the task is aborted
+    pub fn spawn(server: ServerControl, path: PathBuf, interval: Duration) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            loop {
+                interval.tick().await;
+                let result = match Self::snapshot(&server).await {
+                    Ok(state) => Self::save(&path, &state),
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = result {
+                    error!("Failed to write runtime state: {}", e);
+                }
+            }
+        })
+    }
+}
+
+/// Summary of the actions taken by crash recovery
+#[derive(Debug, Clone, Default)]
+pub struct RecoveryReport {
+    /// PID from a stale PID file that was removed
+    pub stale_pid: Option<i32>,
+    /// Whether the previous run ended without a clean shutdown
+    pub crashed: bool,
+    /// Tasks marked interrupted
+    pub interrupted_tasks: Vec<String>,
+    /// Orphaned memory allocations released
+    pub released_allocations: usize,
+    /// Messages requeued into the buffer
+    pub requeued_messages: usize,
+    /// Messages dropped because they ran out of delivery attempts
+    pub dropped_messages: usize,
+}
+
+impl RecoveryReport {
+    /// Whether recovery changed anything
+    pub fn is_empty(&self) -> bool {
+        self.stale_pid.is_none()
+            && !self.crashed
+            && self.released_allocations == 0
+    }
+}
+
+/// Reconciles state from a previous run at startup
+#[derive(Debug, Clone)]
+pub struct CrashRecovery {
+    pid_file: PathBuf,
+    state_file: PathBuf,
+}
+
+impl CrashRecovery {
+    pub fn new(runtime_dir: &Path, pid_file: PathBuf) -> Self {
+        Self {
+            pid_file,
+            state_file: runtime_dir.join(STATE_FILE),
+        }
+    }
+
+    /// Recover state from a previous run into the given server
+    pub async fn recover(&self, server: &ServerControl) -> Result<RecoveryReport, NexaError> {
+        let mut report = RecoveryReport {
+            stale_pid: self.remove_stale_pid_file(),
+            ..Default::default()
+        };
+
+        let state = match StateJournal::load(&self.state_file) {
+            Ok(state) => state,
+            Err(e) => {
+                // A corrupt journal must not block startup
+                warn!("Discarding unreadable runtime state: {}", e);
+                let _ = fs::remove_file(&self.state_file);
+                None
+            }
+        };
+
+        if let Some(state) = state.filter(|s| !s.clean_shutdown && !is_process_alive(s.pid as i32)) {
+            info!("Recovering from unclean shutdown of PID {} at {}", state.pid, state.updated_at);
+            report.crashed = true;
+
+            for mut task in state.tasks {
+                if task.status == TaskStatus::InProgress {
+                    task.status = TaskStatus::Interrupted;
+                    report.interrupted_tasks.push(task.id.clone());
+                }
+                server.registry.add_task(task).await?;
+            }
+
+            for mut msg in state.pending_messages {
+                msg.attempts += 1;
+                if msg.attempts >= msg.max_attempts {
+                    report.dropped_messages += 1;
+                    continue;
+                }
+                server.publish_message(msg).await?;
+                report.requeued_messages += 1;
+            }
+
+            fs::remove_file(&self.state_file)
+                .map_err(|e| NexaError::system(format!("Failed to remove state file: {}", e)))?;
+        }
+
+        report.released_allocations = server.release_orphaned_allocations().await?;
+        debug!("Crash recovery finished: {:?}", report);
+        Ok(report)
+    }
+
+    fn remove_stale_pid_file(&self) -> Option<i32> {
+        let pid = fs::read_to_string(&self.pid_file).ok()?.trim().parse::<i32>().ok()?;
+        if pid as u32 == std::process::id() || is_process_alive(pid) {
+            return None;
+        }
+        match fs::remove_file(&self.pid_file) {
+            Ok(()) => {
+                info!("Removed stale PID file from PID {}", pid);
+                Some(pid)
+            }
+            Err(e) => {
+                error!("Failed to remove stale PID file: {}", e);
+                None
+            }
+        }
+    }
+}
+
+fn is_process_alive(pid: i32) -> bool {
+    pid > 0 && unsafe { libc::kill(pid, 0) } == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::buffer::Priority;
+    use std::time::SystemTime;
+
+    #[tokio::test]
+    async fn test_recover_after_crash() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let pid_file = temp_dir.path().join("nexa.pid");
+        fs::write(&pid_file, i32::MAX.to_string()).unwrap();
+
+        let mut running = Task::new("running".to_string(), String::new(), vec![], vec![], None, 0, 0);
+        running.status = TaskStatus::InProgress;
+        let pending = Task::new("pending".to_string(), String::new(), vec![], vec![], None, 0, 0);
+        let message = |attempts| BufferedMessage {
+            id: uuid::Uuid::new_v4(),
+            payload: vec![1],
+            priority: Priority::Normal,
+            created_at: SystemTime::now(),
+            attempts,
+            max_attempts: 3,
+            delay_until: None,
+            topic: None,
+            trace: None,
+        };
+        let state = RuntimeState {
+            pid: i32::MAX as u32,
+            updated_at: Utc::now(),
+            clean_shutdown: false,
+            tasks: vec![running.clone(), pending.clone()],
+            pending_messages: vec![message(0), message(2)],
+            llm_availability: None,
+            queued_llm_requests: 0,
+            profile: None,
+        };
+        StateJournal::save(&temp_dir.path().join(STATE_FILE), &state).unwrap();
+
+        let server = ServerControl::new(pid_file.clone(), temp_dir.path().join("nexa.sock"));
+        let report = CrashRecovery::new(temp_dir.path(), pid_file.clone())
+            .recover(&server)
+            .await
+            .unwrap();
+
+        assert_eq!(report.stale_pid, Some(i32::MAX));
+        assert!(report.crashed);
+        assert_eq!(report.interrupted_tasks, vec![running.id.clone()]);
+        assert_eq!(report.requeued_messages, 1);
+        assert_eq!(report.dropped_messages, 1);
+        assert!(!pid_file.exists());
+        assert!(!temp_dir.path().join(STATE_FILE).exists());
+
+        assert_eq!(server.registry.get_task(&running.id).await.unwrap().status, TaskStatus::Interrupted);
+        assert_eq!(server.registry.get_task(&pending.id).await.unwrap().status, TaskStatus::Pending);
+    }
+
+    #[tokio::test]
+    async fn test_clean_shutdown_is_not_recovered() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let pid_file = temp_dir.path().join("nexa.pid");
+        let state_file = temp_dir.path().join(STATE_FILE);
+
+        let server = ServerControl::new(pid_file.clone(), temp_dir.path().join("nexa.sock"));
+        StateJournal::save(&state_file, &StateJournal::snapshot(&server).await.unwrap()).unwrap();
+        StateJournal::mark_clean_shutdown(&state_file).unwrap();
+
+        let report = CrashRecovery::new(temp_dir.path(), pid_file).recover(&server).await.unwrap();
+        assert!(!report.crashed);
+        assert!(report.is_empty());
+    }
+}
@@ -0,0 +1,217 @@
+//! Embedded Scripting
+//!
+//! Rhai scripts for glue logic between LLM steps:
+//! - Previous step outputs available as the `outputs` map
+//! - Helpers for JSON, HTTP GET, reading files from a work directory and calling the LLM
+//! - Operation limit so a runaway script cannot stall the server
+//! - Exposed to agents as the `script` tool
+//!
+//! The script's last expression is its result:
+//!
+//! ```text
+//! let answer = parse_json(outputs.classify);
+//! if answer.score > 0.5 { llm("Summarize: " + outputs.fetch) } else { "skipped" }
+//! ```
+
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use futures::future::BoxFuture;
+use rhai::{Dynamic, Engine, EvalAltResult, Scope};
+use serde_json::Value;
+use crate::error::NexaError;
+use crate::llm::LLMSupervisor;
+use crate::tools::{Tool, ToolSpec};
+use tracing::debug;
+
+/// Operations a single script may execute
+pub const DEFAULT_MAX_OPERATIONS: u64 = 1_000_000;
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+/// Runs Rhai scripts with access to step outputs and host helpers
+#[derive(Clone)]
+pub struct ScriptRunner {
+    work_dir: PathBuf,
+    llm: Option<LLMSupervisor>,
+    http: reqwest::Client,
+    max_operations: u64,
+}
+
+impl std::fmt::Debug for ScriptRunner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ScriptRunner")
+            .field("work_dir", &self.work_dir)
+            .field("max_operations", &self.max_operations)
+            .finish()
+    }
+}
+
+impl ScriptRunner {
+    /// Create a runner whose `read_file` helper is confined to `work_dir`
+    pub fn new(work_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            work_dir: work_dir.into(),
+            llm: None,
+            http: reqwest::Client::new(),
+            max_operations: DEFAULT_MAX_OPERATIONS,
+        }
+    }
+
+    /// LLM used by the `llm` helper
+    pub fn with_llm(mut self, llm: LLMSupervisor) -> Self {
+        self.llm = Some(llm);
+        self
+    }
+
+    /// Operations a single script may execute
+    pub fn with_max_operations(mut self, max_operations: u64) -> Self {
+        self.max_operations = max_operations;
+        self
+    }
+
+    /// Evaluate a script with the outputs of previous steps
+    pub async fn run(&self, script: &str, outputs: Value) -> Result<Value, NexaError> {
+        let runner = self.clone();
+        let script = script.to_string();
+        let runtime = tokio::runtime::Handle::current();
+        // Helpers block on async calls, so scripts run off the async workers
+        tokio::task::spawn_blocking(move || runner.eval(&script, outputs, runtime))
+            .await
+            .map_err(|e| NexaError::system(format!("Script task panicked: {}", e)))?
+    }
+
+    fn eval(&self, script: &str, outputs: Value, runtime: tokio::runtime::Handle) -> Result<Value, NexaError> {
+        let engine = self.engine(runtime);
+        let mut scope = Scope::new();
+        scope.push_dynamic("outputs", to_dynamic(&outputs).map_err(script_error)?);
+
+        let result: Dynamic = engine.eval_with_scope(&mut scope, script).map_err(script_error)?;
+        debug!("Script returned {}", result.type_name());
+        rhai::serde::from_dynamic(&result).map_err(script_error)
+    }
+
+    fn engine(&self, runtime: tokio::runtime::Handle) -> Engine {
+        let mut engine = Engine::new();
+        engine.set_max_operations(self.max_operations);
+
+        engine.register_fn("parse_json", |text: &str| -> ScriptResult<Dynamic> {
+            let value: Value = serde_json::from_str(text).map_err(|e| format!("Invalid JSON: {}", e))?;
+            to_dynamic(&value)
+        });
+        engine.register_fn("to_json", |value: Dynamic| -> ScriptResult<String> {
+            let value: Value = rhai::serde::from_dynamic(&value)?;
+            Ok(value.to_string())
+        });
+
+        let work_dir = self.work_dir.clone();
+        engine.register_fn("read_file", move |path: &str| -> ScriptResult<String> {
+            let relative = Path::new(path);
+            if !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+                return Err(format!("Path '{}' must stay inside the work directory", path).into());
+            }
+            std::fs::read_to_string(work_dir.join(relative))
+                .map_err(|e| format!("Failed to read '{}': {}", path, e).into())
+        });
+
+        let http = self.http.clone();
+        let http_runtime = runtime.clone();
+        engine.register_fn("http_get", move |url: &str| -> ScriptResult<String> {
+            http_runtime.block_on(async {
+                let response = http.get(url).send().await.map_err(|e| format!("GET {} failed: {}", url, e))?;
+                let status = response.status();
+                let body = response.text().await.map_err(|e| format!("GET {} failed: {}", url, e))?;
+                if status.is_success() {
+                    Ok(body)
+                } else {
+                    Err(format!("GET {} returned {}", url, status).into())
+                }
+            })
+        });
+
+        let llm = self.llm.clone();
+        engine.register_fn("llm", move |prompt: &str| -> ScriptResult<String> {
+            let llm = llm.as_ref().ok_or("No LLM is available to scripts")?;
+            runtime.block_on(llm.complete(prompt)).map_err(|e| e.to_string().into())
+        });
+
+        engine
+    }
+}
+
+/// The `script` tool: runs a Rhai script against step outputs
+pub struct ScriptTool {
+    spec: ToolSpec,
+    runner: Arc<ScriptRunner>,
+}
+
+impl ScriptTool {
+    pub fn new(runner: ScriptRunner) -> Self {
+        Self {
+            spec: ToolSpec {
+                name: "script".to_string(),
+                description: "Run a Rhai script; previous step outputs are available as `outputs`".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "script": { "type": "string" },
+                        "outputs": { "type": "object" },
+                    },
+                    "required": ["script"],
+                }),
+            },
+            runner: Arc::new(runner),
+        }
+    }
+}
+
+impl Tool for ScriptTool {
+    fn spec(&self) -> &ToolSpec {
+        &self.spec
+    }
+
+    fn call(&self, args: Value) -> BoxFuture<'_, Result<Value, NexaError>> {
+        Box::pin(async move {
+            let script = args["script"].as_str().unwrap_or_default();
+            let outputs = args.get("outputs").cloned().unwrap_or_else(|| serde_json::json!({}));
+            self.runner.run(script, outputs).await
+        })
+    }
+}
+
+fn to_dynamic(value: &Value) -> ScriptResult<Dynamic> {
+    rhai::serde::to_dynamic(value)
+}
+
+fn script_error(e: Box<EvalAltResult>) -> NexaError {
+    NexaError::invalid_input(format!("Script error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_script_runner() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::fs::write(temp_dir.path().join("notes.txt"), "from disk").unwrap();
+        let runner = ScriptRunner::new(temp_dir.path()).with_max_operations(10_000);
+
+        let outputs = json!({ "classify": "{\"label\": \"bug\", \"score\": 0.9}" });
+        let script = r#"
+            let parsed = parse_json(outputs.classify);
+            #{ label: parsed.label, urgent: parsed.score > 0.5, notes: read_file("notes.txt") }
+        "#;
+        let result = runner.run(script, outputs).await.unwrap();
+        assert_eq!(result, json!({ "label": "bug", "urgent": true, "notes": "from disk" }));
+
+        assert!(runner.run(r#"read_file("../etc/passwd")"#, json!({})).await.is_err());
+        assert!(runner.run("loop { }", json!({})).await.is_err());
+        assert!(runner.run(r#"llm("hi")"#, json!({})).await.is_err());
+
+        let registry = crate::tools::ToolRegistry::new();
+        registry.register(Arc::new(ScriptTool::new(runner))).unwrap();
+        let result = registry.call("script", json!({ "script": "40 + 2" })).await.unwrap();
+        assert_eq!(result, 42);
+    }
+}
@@ -0,0 +1,258 @@
+//! Secrets Management
+//!
+//! Provides storage for provider credentials:
+//! - Encrypted secrets file in the user's data directory
+//! - Key file in the user's config directory, apart from the data, with owner-only permissions
+//! - One location for the daemon, the CLI and the LLM clients of the same user
+//! - Credential resolution with mounted secret files and environment variable fallback
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use chacha20poly1305::{ChaCha20Poly1305, Key, KeyInit, Nonce};
+use chacha20poly1305::aead::{Aead, AeadCore, OsRng};
+use crate::error::NexaError;
+use tracing::debug;
+
+/// Environment variable naming a directory of mounted secret files (e.g. a Kubernetes Secret volume)
+pub const SECRETS_DIR_ENV: &str = "NEXA_SECRETS_DIR";
+
+const SECRETS_FILE: &str = "nexa-secrets.enc";
+const KEY_FILE: &str = "nexa-secrets.key";
+const NONCE_LEN: usize = 12;
+
+/// Encrypted key/value store for API keys and other credentials
+#[derive(Debug, Clone)]
+pub struct SecretStore {
+    secrets_path: PathBuf,
+    key_path: PathBuf,
+}
+
+impl SecretStore {
+    /// Create a store keeping its secrets in `data_dir` and its key in `key_dir`
+    pub fn new(data_dir: impl AsRef<Path>, key_dir: impl AsRef<Path>) -> Self {
+        Self {
+            secrets_path: data_dir.as_ref().join(SECRETS_FILE),
+            key_path: key_dir.as_ref().join(KEY_FILE),
+        }
+    }
+
+    /// Open the current user's store, shared by every part of the daemon and the CLI
+    pub fn open_default() -> Self {
+        Self::new(default_data_dir(), default_key_dir())
+    }
+
+    /// Store a secret, replacing any existing value
+    pub fn set(&self, name: &str, value: &str) -> Result<(), NexaError> {
+        if name.is_empty() {
+            return Err(NexaError::config("Secret name cannot be empty"));
+        }
+        let mut secrets = self.load()?;
+        secrets.insert(name.to_string(), value.to_string());
+        self.save(&secrets)
+    }
+
+    /// Get a secret by name
+    pub fn get(&self, name: &str) -> Result<Option<String>, NexaError> {
+        Ok(self.load()?.remove(name))
+    }
+
+    /// Remove a secret, returning whether it existed
+    pub fn remove(&self, name: &str) -> Result<bool, NexaError> {
+        let mut secrets = self.load()?;
+        let existed = secrets.remove(name).is_some();
+        if existed {
+            self.save(&secrets)?;
+        }
+        Ok(existed)
+    }
+
+    /// List the names of all stored secrets
+    pub fn list(&self) -> Result<Vec<String>, NexaError> {
+        Ok(self.load()?.into_keys().collect())
+    }
+
+    /// Resolve a credential by name, falling back to a file of the same name in
+    /// the mounted secrets directory, then to an environment variable
+    pub fn resolve(&self, name: &str) -> Result<String, NexaError> {
+        if let Some(value) = self.get(name)? {
+            return Ok(value);
+        }
+        if let Some(value) = read_mounted(name) {
+            return Ok(value);
+        }
+        std::env::var(name)
+            .map_err(|_| NexaError::config(format!("Secret '{}' not found in store or environment", name)))
+    }
+
+    fn load(&self) -> Result<BTreeMap<String, String>, NexaError> {
+        if !self.secrets_path.exists() {
+            return Ok(BTreeMap::new());
+        }
+
+        let data = fs::read(&self.secrets_path)
+            .map_err(|e| NexaError::config(format!("Failed to read secrets file: {}", e)))?;
+        if data.len() < NONCE_LEN {
+            return Err(NexaError::config("Secrets file is corrupted"));
+        }
+
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        let plaintext = self.cipher()?
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| NexaError::config("Failed to decrypt secrets file"))?;
+
+        serde_json::from_slice(&plaintext)
+            .map_err(|e| NexaError::config(format!("Failed to parse secrets file: {}", e)))
+    }
+
+    fn save(&self, secrets: &BTreeMap<String, String>) -> Result<(), NexaError> {
+        let plaintext = serde_json::to_vec(secrets)?;
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self.cipher()?
+            .encrypt(&nonce, plaintext.as_slice())
+            .map_err(|_| NexaError::config("Failed to encrypt secrets"))?;
+
+        let mut data = nonce.to_vec();
+        data.extend_from_slice(&ciphertext);
+        write_private(&self.secrets_path, &data)?;
+        debug!("Saved {} secrets to {:?}", secrets.len(), self.secrets_path);
+        Ok(())
+    }
+
+    fn cipher(&self) -> Result<ChaCha20Poly1305, NexaError> {
+        let key = if self.key_path.exists() {
+            let key = fs::read(&self.key_path)
+                .map_err(|e| NexaError::config(format!("Failed to read secrets key: {}", e)))?;
+            if key.len() != 32 {
+                return Err(NexaError::config("Secrets key has invalid length"));
+            }
+            key
+        } else {
+            let key = ChaCha20Poly1305::generate_key(&mut OsRng).to_vec();
+            write_private(&self.key_path, &key)?;
+            debug!("Generated new secrets key at {:?}", self.key_path);
+            key
+        };
+
+        Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+    }
+}
+
+/// Directory of the secrets file: `$XDG_DATA_HOME/nexa`, or `~/.local/share/nexa`
+pub fn default_data_dir() -> PathBuf {
+    xdg_dir("XDG_DATA_HOME", ".local/share")
+}
+
+/// Directory of the secrets key: `$XDG_CONFIG_HOME/nexa`, or `~/.config/nexa`
+pub fn default_key_dir() -> PathBuf {
+    xdg_dir("XDG_CONFIG_HOME", ".config")
+}
+
+fn xdg_dir(var: &str, fallback: &str) -> PathBuf {
+    let base = std::env::var_os(var)
+        .filter(|dir| Path::new(dir).is_absolute())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| {
+            let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
+            PathBuf::from(home).join(fallback)
+        });
+    base.join("nexa")
+}
+
+/// Write a file that only the current user can read, in a directory only they can enter
+fn write_private(path: &Path, data: &[u8]) -> Result<(), NexaError> {
+    let mut file = fs::OpenOptions::new();
+    file.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt};
+        if let Some(parent) = path.parent() {
+            fs::DirBuilder::new().recursive(true).mode(0o700).create(parent)
+                .map_err(|e| NexaError::config(format!("Failed to create secrets directory: {}", e)))?;
+            fs::set_permissions(parent, fs::Permissions::from_mode(0o700))
+                .map_err(|e| NexaError::config(format!("Failed to set permissions on {:?}: {}", parent, e)))?;
+        }
+        file.mode(0o600);
+        // Tighten files created before, as `mode` only applies to new ones
+        if path.exists() {
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+                .map_err(|e| NexaError::config(format!("Failed to set permissions on {:?}: {}", path, e)))?;
+        }
+    }
+    #[cfg(not(unix))]
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| NexaError::config(format!("Failed to create secrets directory: {}", e)))?;
+    }
+
+    file.open(path)
+        .and_then(|mut file| file.write_all(data))
+        .map_err(|e| NexaError::config(format!("Failed to write {:?}: {}", path, e)))
+}
+
+fn read_mounted(name: &str) -> Option<String> {
+    let dir = std::env::var_os(SECRETS_DIR_ENV)?;
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        return None;
+    }
+    let value = fs::read_to_string(Path::new(&dir).join(name)).ok()?;
+    Some(value.trim_end_matches(['\r', '\n']).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_roundtrip() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let store = SecretStore::new(temp_dir.path().join("data"), temp_dir.path().join("config"));
+
+        store.set("OPENAI_API_KEY", "sk-test").unwrap();
+        assert_eq!(store.get("OPENAI_API_KEY").unwrap(), Some("sk-test".to_string()));
+        assert_eq!(store.list().unwrap(), vec!["OPENAI_API_KEY".to_string()]);
+
+        // Values must not be stored in plain text
+        let raw = fs::read(temp_dir.path().join("data").join(SECRETS_FILE)).unwrap();
+        assert!(!String::from_utf8_lossy(&raw).contains("sk-test"));
+
+        // The key lives apart from the data, readable by its owner only
+        assert!(!temp_dir.path().join("data").join(KEY_FILE).exists());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = |path: PathBuf| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+            assert_eq!(mode(temp_dir.path().join("config").join(KEY_FILE)), 0o600);
+            assert_eq!(mode(temp_dir.path().join("config")), 0o700);
+            assert_eq!(mode(temp_dir.path().join("data").join(SECRETS_FILE)), 0o600);
+        }
+
+        assert!(store.remove("OPENAI_API_KEY").unwrap());
+        assert!(!store.remove("OPENAI_API_KEY").unwrap());
+        assert_eq!(store.get("OPENAI_API_KEY").unwrap(), None);
+    }
+
+    #[test]
+    fn test_resolve_falls_back_to_env() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let store = SecretStore::new(temp_dir.path(), temp_dir.path());
+
+        std::env::set_var("NEXA_TEST_SECRET_FALLBACK", "from-env");
+        assert_eq!(store.resolve("NEXA_TEST_SECRET_FALLBACK").unwrap(), "from-env");
+
+        // Mounted secret files take precedence over the environment
+        let mounted = temp_dir.path().join("mounted");
+        fs::create_dir_all(&mounted).unwrap();
+        fs::write(mounted.join("NEXA_TEST_SECRET_FALLBACK"), "from-file\n").unwrap();
+        std::env::set_var(SECRETS_DIR_ENV, &mounted);
+        assert_eq!(store.resolve("NEXA_TEST_SECRET_FALLBACK").unwrap(), "from-file");
+        std::env::remove_var(SECRETS_DIR_ENV);
+
+        store.set("NEXA_TEST_SECRET_FALLBACK", "from-store").unwrap();
+        assert_eq!(store.resolve("NEXA_TEST_SECRET_FALLBACK").unwrap(), "from-store");
+
+        assert!(store.resolve("NEXA_TEST_SECRET_MISSING").is_err());
+    }
+}
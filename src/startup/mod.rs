@@ -0,0 +1,342 @@
+//! Startup Management
+//!
+//! Runs preflight checks before the server starts:
+//! - Runtime directory permissions
+//! - Port availability
+//! - Stale PID file detection
+//! - Storage integrity
+//! - LLM server reachability
+
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+use serde::Serialize;
+use tokio::net::TcpListener;
+use nix::libc;
+use crate::config::Config;
+use crate::llm::{LLMConfig, ServerType};
+use crate::mcp::registry::{check_journal, REGISTRY_JOURNAL_FILE};
+use crate::migrations::{self, DataKind};
+use crate::recovery::{StateJournal, STATE_FILE};
+use crate::secrets::SecretStore;
+use tracing::debug;
+
+/// Outcome of a single preflight check
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum CheckStatus {
+    Passed,
+    Warning(String),
+    Failed(String),
+}
+
+/// Named result of a preflight check
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckResult {
+    pub name: String,
+    pub status: CheckStatus,
+}
+
+/// Results of all preflight checks
+#[derive(Debug, Clone, Serialize)]
+pub struct PreflightReport {
+    pub checks: Vec<CheckResult>,
+}
+
+impl PreflightReport {
+    /// Whether startup may proceed (no check failed)
+    pub fn is_go(&self) -> bool {
+        !self.checks.iter().any(|c| matches!(c.status, CheckStatus::Failed(_)))
+    }
+
+    /// Number of checks that passed with a warning
+    pub fn warnings(&self) -> usize {
+        self.checks.iter().filter(|c| matches!(c.status, CheckStatus::Warning(_))).count()
+    }
+
+    /// Get the status of a check by name
+    pub fn status(&self, name: &str) -> Option<&CheckStatus> {
+        self.checks.iter().find(|c| c.name == name).map(|c| &c.status)
+    }
+}
+
+impl fmt::Display for PreflightReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            match &check.status {
+                CheckStatus::Passed => writeln!(f, "  [ OK ] {}", check.name)?,
+                CheckStatus::Warning(msg) => writeln!(f, "  [WARN] {}: {}", check.name, msg)?,
+                CheckStatus::Failed(msg) => writeln!(f, "  [FAIL] {}: {}", check.name, msg)?,
+            }
+        }
+        if self.is_go() {
+            write!(f, "Preflight: GO ({} warning(s))", self.warnings())
+        } else {
+            write!(f, "Preflight: NO-GO")
+        }
+    }
+}
+
+/// Runs preflight checks for `nexa start`
+#[derive(Debug, Clone)]
+pub struct StartupManager {
+    runtime_dir: PathBuf,
+    pid_file: PathBuf,
+    bind_addr: String,
+    auto_port: bool,
+    config_path: Option<PathBuf>,
+    llm_config: Option<LLMConfig>,
+}
+
+impl StartupManager {
+    pub fn new(runtime_dir: PathBuf, pid_file: PathBuf) -> Self {
+        Self {
+            runtime_dir,
+            pid_file,
+            bind_addr: "0.0.0.0:8080".to_string(),
+            auto_port: false,
+            config_path: None,
+            llm_config: None,
+        }
+    }
+
+    /// Address the server will bind to
+    pub fn with_bind_addr(mut self, addr: impl Into<String>) -> Self {
+        self.bind_addr = addr.into();
+        self
+    }
+
+    /// Whether the server falls back to an ephemeral port when the address is busy
+    pub fn with_auto_port(mut self, enabled: bool) -> Self {
+        self.auto_port = enabled;
+        self
+    }
+
+    /// Config file to check for integrity
+    pub fn with_config_path(mut self, path: PathBuf) -> Self {
+        self.config_path = Some(path);
+        self
+    }
+
+    /// LLM server to check for reachability
+    pub fn with_llm_config(mut self, config: LLMConfig) -> Self {
+        self.llm_config = Some(config);
+        self
+    }
+
+    /// Run all preflight checks
+    pub async fn run_preflight(&self) -> PreflightReport {
+        let mut checks = vec![
+            self.check("runtime_dir", self.check_runtime_dir()),
+            self.check("port", self.check_port().await),
+            self.check("pid_file", self.check_pid_file()),
+            self.check("storage", self.check_storage()),
+        ];
+        if let Some(llm) = &self.llm_config {
+            checks.push(self.check("llm_server", Self::check_llm(llm).await));
+        }
+        PreflightReport { checks }
+    }
+
+    fn check(&self, name: &str, status: CheckStatus) -> CheckResult {
+        debug!("Preflight check {}: {:?}", name, status);
+        CheckResult { name: name.to_string(), status }
+    }
+
+    fn check_runtime_dir(&self) -> CheckStatus {
+        if let Err(e) = fs::create_dir_all(&self.runtime_dir) {
+            return CheckStatus::Failed(format!("Cannot create {:?}: {}", self.runtime_dir, e));
+        }
+        let probe = self.runtime_dir.join(format!(".nexa-preflight-{}", std::process::id()));
+        match fs::write(&probe, b"ok") {
+            Ok(()) => {
+                let _ = fs::remove_file(&probe);
+                CheckStatus::Passed
+            }
+            Err(e) => CheckStatus::Failed(format!("{:?} is not writable: {}", self.runtime_dir, e)),
+        }
+    }
+
+    async fn check_port(&self) -> CheckStatus {
+        match TcpListener::bind(&self.bind_addr).await {
+            Ok(_) => CheckStatus::Passed,
+            Err(e) if e.kind() == std::io::ErrorKind::AddrInUse && self.auto_port => CheckStatus::Warning(
+                format!("{} is in use, an ephemeral port will be used", self.bind_addr)
+            ),
+            Err(e) => CheckStatus::Failed(format!("Cannot bind {}: {}", self.bind_addr, e)),
+        }
+    }
+
+    fn check_pid_file(&self) -> CheckStatus {
+        let contents = match fs::read_to_string(&self.pid_file) {
+            Ok(contents) => contents,
+            Err(_) => return CheckStatus::Passed,
+        };
+        match contents.trim().parse::<i32>() {
+            // Zero and negative PIDs would signal process groups, so they count as unreadable
+            Ok(pid) if pid <= 0 => CheckStatus::Warning(format!("Unreadable PID file {:?} will be replaced", self.pid_file)),
+            Ok(pid) if pid as u32 == std::process::id() => CheckStatus::Passed,
+            Ok(pid) if unsafe { libc::kill(pid, 0) } == 0 => {
+                CheckStatus::Failed(format!("Server already running with PID {}", pid))
+            }
+            Ok(pid) => CheckStatus::Warning(format!("Stale PID file from PID {} will be replaced", pid)),
+            Err(_) => CheckStatus::Warning(format!("Unreadable PID file {:?} will be replaced", self.pid_file)),
+        }
+    }
+
+    fn check_storage(&self) -> CheckStatus {
+        if let Some(path) = self.config_path.as_ref().filter(|p| p.exists()) {
+            match Config::validate_file(path) {
+                Ok(issues) if !issues.is_empty() => {
+                    return CheckStatus::Failed(format!("{:?} has {} configuration error(s)", path, issues.len()));
+                }
+                Err(e) => return CheckStatus::Failed(e.to_string()),
+                Ok(_) => {}
+            }
+        }
+        for kind in [DataKind::Agents, DataKind::Workflows, DataKind::Tasks] {
+            let path = self.runtime_dir.join(kind.file_name());
+            if path.exists() {
+                if let Err(e) = migrations::check_file(kind, &path) {
+                    return CheckStatus::Failed(format!("{:?} is corrupt: {}", path, e));
+                }
+            }
+        }
+        let journal = self.runtime_dir.join(REGISTRY_JOURNAL_FILE);
+        if journal.exists() {
+            if let Err(e) = check_journal(&journal) {
+                return CheckStatus::Failed(format!("{:?} is corrupt: {}", journal, e));
+            }
+        }
+        let state_file = self.runtime_dir.join(STATE_FILE);
+        if let Err(e) = StateJournal::load(&state_file) {
+            return CheckStatus::Failed(format!("{:?} is corrupt: {}", state_file, e));
+        }
+        match SecretStore::open_default().list() {
+            Ok(_) => CheckStatus::Passed,
+            Err(e) => CheckStatus::Failed(format!("Secrets store unreadable: {}", e)),
+        }
+    }
+
+    async fn check_llm(config: &LLMConfig) -> CheckStatus {
+        if config.server_type == ServerType::Embedded {
+            return match crate::llm::embedded_available(config) {
+                Ok(()) => CheckStatus::Passed,
+                Err(e) => CheckStatus::Failed(e.to_string()),
+            };
+        }
+        if config.server_type == ServerType::Mock {
+            return match crate::llm::mock_available() {
+                Ok(()) => CheckStatus::Passed,
+                Err(e) => CheckStatus::Failed(e.to_string()),
+            };
+        }
+        let client = match crate::llm::http_pool::shared_client(&config.server_url) {
+            Ok(client) => client,
+            Err(e) => return CheckStatus::Warning(format!("Failed to create HTTP client: {}", e)),
+        };
+        match client.get(&config.server_url).timeout(Duration::from_secs(2)).send().await {
+            Ok(_) => CheckStatus::Passed,
+            Err(e) => CheckStatus::Warning(format!("LLM server unreachable at {}: {}", config.server_url, e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_preflight_go() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let manager = StartupManager::new(temp_dir.path().to_path_buf(), temp_dir.path().join("nexa.pid"))
+            .with_bind_addr("127.0.0.1:0")
+            .with_llm_config(LLMConfig {
+                server_url: "http://127.0.0.1:9".to_string(),
+                ..LLMConfig::default()
+            });
+
+        let report = manager.run_preflight().await;
+        assert!(report.is_go());
+        assert_eq!(report.status("runtime_dir"), Some(&CheckStatus::Passed));
+        assert_eq!(report.status("port"), Some(&CheckStatus::Passed));
+        assert!(matches!(report.status("llm_server"), Some(CheckStatus::Warning(_))));
+    }
+
+    #[tokio::test]
+    async fn test_preflight_no_go() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let config_path = temp_dir.path().join("config.yml");
+        fs::write(&config_path, "bogus: true\n").unwrap();
+
+        let manager = StartupManager::new(temp_dir.path().to_path_buf(), temp_dir.path().join("nexa.pid"))
+            .with_bind_addr(addr.to_string())
+            .with_config_path(config_path);
+
+        let report = manager.run_preflight().await;
+        assert!(!report.is_go());
+        assert!(matches!(report.status("port"), Some(CheckStatus::Failed(_))));
+        assert!(matches!(report.status("storage"), Some(CheckStatus::Failed(_))));
+
+        // A busy port is only a warning when an ephemeral port can be used
+        let report = manager.with_auto_port(true).run_preflight().await;
+        assert!(matches!(report.status("port"), Some(CheckStatus::Warning(_))));
+    }
+
+    #[tokio::test]
+    async fn test_stale_pid_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let pid_file = temp_dir.path().join("nexa.pid");
+        fs::write(&pid_file, i32::MAX.to_string()).unwrap();
+
+        let manager = StartupManager::new(temp_dir.path().to_path_buf(), pid_file)
+            .with_bind_addr("127.0.0.1:0");
+
+        let report = manager.run_preflight().await;
+        assert!(report.is_go());
+        assert!(matches!(report.status("pid_file"), Some(CheckStatus::Warning(_))));
+
+        for pid in ["0", "-1", "-42"] {
+            fs::write(&manager.pid_file, pid).unwrap();
+            let report = manager.run_preflight().await;
+            assert!(report.is_go(), "PID {} blocked startup", pid);
+            assert!(matches!(report.status("pid_file"), Some(CheckStatus::Warning(w)) if w.contains("Unreadable")));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_corrupt_data_files() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let manager = StartupManager::new(temp_dir.path().to_path_buf(), temp_dir.path().join("nexa.pid"))
+            .with_bind_addr("127.0.0.1:0");
+        let storage = |report: &PreflightReport| report.status("storage").cloned();
+
+        // Legacy files still awaiting migration pass, as does a half written last journal record
+        fs::write(temp_dir.path().join("agents.json"), "[]").unwrap();
+        fs::write(temp_dir.path().join("tasks.json"), r#"{"version": 1, "data": []}"#).unwrap();
+        fs::write(temp_dir.path().join(REGISTRY_JOURNAL_FILE), "{\"op\": \"deregister\", \"agent_id\": \"a\"}\n{\"op\":").unwrap();
+        assert_eq!(storage(&manager.run_preflight().await), Some(CheckStatus::Passed));
+
+        let corrupt = [
+            ("agents.json", "[{\"id\":"),
+            ("workflows.json", r#"{"version": 2, "data": []}"#),
+            (REGISTRY_JOURNAL_FILE, "{\"op\":\n{\"op\": \"deregister\", \"agent_id\": \"a\"}\n"),
+            (STATE_FILE, "{\"tasks\": ["),
+        ];
+        for (file, contents) in corrupt {
+            let path = temp_dir.path().join(file);
+            let previous = fs::read_to_string(&path).ok();
+            fs::write(&path, contents).unwrap();
+            let report = manager.run_preflight().await;
+            assert!(!report.is_go());
+            assert!(matches!(storage(&report), Some(CheckStatus::Failed(e)) if e.contains(file)), "{} passed", file);
+            match previous {
+                Some(previous) => fs::write(&path, previous).unwrap(),
+                None => fs::remove_file(&path).unwrap(),
+            }
+        }
+    }
+}
@@ -3,9 +3,14 @@
 //! This module provides token usage tracking and management:
 //! - Token consumption monitoring
 //! - Rate limiting
-//! - Cost tracking
+//! - Cost tracking, from provider-reported pricing where available
+//! - Local prompt token counting before dispatch
 //! - Usage analytics
 
+pub mod tokenizer;
+
+pub use tokenizer::{context_window, count_chat_tokens, count_tokens, TokenizerFamily};
+
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use std::collections::HashMap;
@@ -13,6 +18,7 @@ use chrono::{DateTime, Utc};
 use crate::error::NexaError;
 use crate::memory::{MemoryManager, ResourceType};
 use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
 
 #[derive(Debug, Clone, Hash, Eq, PartialEq)]
 pub enum ModelType {
@@ -23,7 +29,21 @@ pub enum ModelType {
     Custom(String),
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+impl ModelType {
+    /// Model id used to pick a tokenizer
+    pub fn model_id(&self) -> &str {
+        match self {
+            Self::GPT4 => "gpt-4",
+            Self::GPT35 => "gpt-3.5-turbo",
+            Self::Claude2 => "claude-2",
+            Self::Claude3 => "claude-3",
+            Self::Custom(id) => id,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "api-docs", derive(utoipa::ToSchema))]
 pub struct TokenUsage {
     pub prompt_tokens: usize,
     pub completion_tokens: usize,
@@ -42,6 +62,20 @@ impl Default for TokenUsage {
     }
 }
 
+/// Price of a model in USD per token
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize, JsonSchema)]
+pub struct ModelPricing {
+    pub prompt: f64,
+    pub completion: f64,
+}
+
+impl ModelPricing {
+    /// Cost of an interaction in USD
+    pub fn cost(&self, prompt_tokens: usize, completion_tokens: usize) -> f64 {
+        prompt_tokens as f64 * self.prompt + completion_tokens as f64 * self.completion
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct UsageRecord {
     pub model: ModelType,
@@ -54,6 +88,7 @@ pub struct UsageRecord {
 pub struct TokenManager {
     usage_records: Arc<RwLock<Vec<UsageRecord>>>,
     model_limits: HashMap<ModelType, usize>,
+    pricing: parking_lot::RwLock<HashMap<ModelType, ModelPricing>>,
     memory_manager: Arc<MemoryManager>,
 }
 
@@ -62,6 +97,7 @@ impl TokenManager {
         Self {
             usage_records: Arc::new(RwLock::new(Vec::new())),
             model_limits: HashMap::new(),
+            pricing: parking_lot::RwLock::new(HashMap::new()),
             memory_manager,
         }
     }
@@ -71,6 +107,42 @@ impl TokenManager {
         self.model_limits.insert(model, limit);
     }
 
+    /// Token limit configured for a model
+    pub fn model_limit(&self, model: &ModelType) -> Option<usize> {
+        self.model_limits.get(model).copied()
+    }
+
+    /// Set the price of a model, replacing the built-in example rates
+    pub fn set_model_pricing(&self, model: ModelType, pricing: ModelPricing) {
+        self.pricing.write().insert(model, pricing);
+    }
+
+    /// Set prices for models identified by provider model id
+    pub fn load_pricing(&self, pricing: HashMap<String, ModelPricing>) {
+        self.pricing.write().extend(pricing.into_iter().map(|(id, p)| (ModelType::Custom(id), p)));
+    }
+
+    /// Price configured for a model
+    pub fn model_pricing(&self, model: &ModelType) -> Option<ModelPricing> {
+        self.pricing.read().get(model).copied()
+    }
+
+    /// Count the prompt's tokens and check the request against the model's limit
+    ///
+    /// Returns the prompt token count, so requests can be rejected before they are sent.
+    pub fn check_request(&self, model: &ModelType, prompt: &str, max_tokens: usize) -> Result<usize, NexaError> {
+        let prompt_tokens = count_tokens(model.model_id(), prompt);
+        if let Some(limit) = self.model_limits.get(model) {
+            if prompt_tokens + max_tokens > *limit {
+                return Err(NexaError::invalid_input(format!(
+                    "Request for {:?} needs up to {} tokens ({} prompt + {} max_tokens), exceeding its limit of {}",
+                    model, prompt_tokens + max_tokens, prompt_tokens, max_tokens, limit
+                )));
+            }
+        }
+        Ok(prompt_tokens)
+    }
+
     /// Track token usage for a model interaction
     pub async fn track_usage(
         &self,
@@ -90,11 +162,12 @@ impl TokenManager {
             }
         }
 
-        // Calculate cost (example rates)
-        let cost = match model {
-            ModelType::GPT4 => (prompt_tokens as f64 * 0.03 + completion_tokens as f64 * 0.06) / 1000.0,
-            ModelType::GPT35 => (prompt_tokens as f64 * 0.001 + completion_tokens as f64 * 0.002) / 1000.0,
-            _ => 0.0,
+        // Calculate cost, falling back to example rates without known pricing
+        let cost = match (self.model_pricing(&model), &model) {
+            (Some(pricing), _) => pricing.cost(prompt_tokens, completion_tokens),
+            (None, ModelType::GPT4) => (prompt_tokens as f64 * 0.03 + completion_tokens as f64 * 0.06) / 1000.0,
+            (None, ModelType::GPT35) => (prompt_tokens as f64 * 0.001 + completion_tokens as f64 * 0.002) / 1000.0,
+            (None, _) => 0.0,
         };
 
         let usage = TokenUsage {
@@ -200,4 +273,28 @@ mod tests {
         assert_eq!(usage.prompt_tokens, 100);
         assert_eq!(usage.completion_tokens, 50);
     }
+
+    #[tokio::test]
+    async fn test_pricing_cost() {
+        let token_manager = TokenManager::new(Arc::new(MemoryManager::new()));
+        let model = ModelType::Custom("anthropic/claude-3.5-haiku".to_string());
+        token_manager.load_pricing(HashMap::from([(
+            "anthropic/claude-3.5-haiku".to_string(),
+            ModelPricing { prompt: 0.000001, completion: 0.000005 },
+        )]));
+
+        token_manager.track_usage(model.clone(), 1000, 200, HashMap::new()).await.unwrap();
+        let usage = token_manager.get_usage_by_model(model).await;
+        assert!((usage.cost - 0.002).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_check_request() {
+        let mut token_manager = TokenManager::new(Arc::new(MemoryManager::new()));
+        token_manager.set_model_limit(ModelType::GPT4, 100);
+
+        assert_eq!(token_manager.check_request(&ModelType::GPT4, "Hello there", 50).unwrap(), 2);
+        assert!(token_manager.check_request(&ModelType::GPT4, "Hello there", 99).is_err());
+        assert!(token_manager.check_request(&ModelType::GPT35, "Hello there", 1000).is_ok());
+    }
 } 
\ No newline at end of file
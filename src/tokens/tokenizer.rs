@@ -0,0 +1,188 @@
+//! Local token counting
+//!
+//! Splits text the way tiktoken-style BPE tokenizers pre-tokenize it (words
+//! with their leading space, digit groups of up to three, punctuation runs,
+//! whitespace) and sizes each piece by the typical merge length of the model
+//! family's vocabulary. Counts are estimates that track the provider's
+//! closely for English prose and code, without shipping vocabulary files.
+
+/// Vocabulary family of a model, which determines how text is split into tokens
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenizerFamily {
+    /// GPT-4 and GPT-3.5 (`cl100k_base`)
+    Cl100k,
+    /// GPT-4o and o-series (`o200k_base`)
+    O200k,
+    Claude,
+    /// Llama 3 and models sharing its vocabulary
+    Llama,
+    Gemini,
+    /// Models with smaller vocabularies (Mistral, Llama 2, unknown local models)
+    Generic,
+}
+
+impl TokenizerFamily {
+    /// Family of a provider model id
+    pub fn for_model(model: &str) -> Self {
+        let model = model.to_lowercase();
+        let model = model.rsplit('/').next().unwrap_or(&model);
+        if model.starts_with("gpt-4o") || model.starts_with("o1") || model.starts_with("o3") || model.starts_with("o4") {
+            Self::O200k
+        } else if model.starts_with("gpt-") || model.contains("text-embedding") {
+            Self::Cl100k
+        } else if model.contains("claude") {
+            Self::Claude
+        } else if model.contains("gemini") || model.contains("gemma") {
+            Self::Gemini
+        } else if model.contains("llama3") || model.contains("llama-3") || model.contains("qwen") {
+            Self::Llama
+        } else {
+            Self::Generic
+        }
+    }
+
+    /// Longest word piece the vocabulary usually encodes as a single token
+    fn merge_len(self) -> usize {
+        match self {
+            Self::O200k | Self::Gemini => 7,
+            Self::Cl100k | Self::Llama => 6,
+            Self::Claude => 5,
+            Self::Generic => 4,
+        }
+    }
+}
+
+/// Tokens `text` takes up for `model`
+pub fn count_tokens(model: &str, text: &str) -> usize {
+    let family = TokenizerFamily::for_model(model);
+    pieces(text).map(|piece| piece_tokens(family, piece)).sum()
+}
+
+/// Tokens a chat takes up, including per-message framing and the reply primer
+pub fn count_chat_tokens<'a>(model: &str, messages: impl IntoIterator<Item = (&'a str, &'a str)>) -> usize {
+    messages.into_iter()
+        .map(|(role, content)| 3 + count_tokens(model, role) + count_tokens(model, content))
+        .sum::<usize>()
+        + 3
+}
+
+/// Context window of well-known models, in tokens
+pub fn context_window(model: &str) -> Option<usize> {
+    let model = model.to_lowercase();
+    let model = model.rsplit('/').next().unwrap_or(&model);
+    let window = match model {
+        m if m.starts_with("gpt-4o") || m.starts_with("gpt-4-turbo") || m.starts_with("gpt-4.1") => 128_000,
+        m if m.starts_with("o1") || m.starts_with("o3") || m.starts_with("o4") => 200_000,
+        m if m.starts_with("gpt-4-32k") => 32_768,
+        m if m.starts_with("gpt-4") => 8_192,
+        m if m.starts_with("gpt-3.5-turbo") => 16_385,
+        m if m.contains("claude") => 200_000,
+        m if m.contains("gemini-1.5-pro") => 2_097_152,
+        m if m.contains("gemini") => 1_048_576,
+        m if m.contains("llama3.1") || m.contains("llama3.2") || m.contains("llama-3.1") || m.contains("llama-3.2") => 131_072,
+        m if m.contains("llama3") || m.contains("llama-3") => 8_192,
+        m if m.contains("mistral") || m.contains("mixtral") || m.contains("qwen") => 32_768,
+        _ => return None,
+    };
+    Some(window)
+}
+
+/// Split text into pre-tokenizer pieces
+fn pieces(text: &str) -> impl Iterator<Item = &str> {
+    let mut rest = text;
+    std::iter::from_fn(move || {
+        let first = rest.chars().next()?;
+        let start = first.len_utf8();
+        let mut end = start;
+        let chars = rest[start..].char_indices().map(|(i, c)| (i + start, c));
+        // A single leading space attaches to a following word or punctuation, but not to numbers
+        let kind = match first {
+            ' ' => rest[start..].chars().next()
+                .filter(|c| !c.is_whitespace() && !c.is_ascii_digit())
+                .map(kind_of)
+                .unwrap_or(Kind::Space),
+            c => kind_of(c),
+        };
+        let mut digits = usize::from(first.is_ascii_digit());
+        for (i, c) in chars {
+            let same = match kind {
+                Kind::Word => c.is_alphabetic() && !is_cjk(c),
+                Kind::Number => c.is_ascii_digit() && digits < 3,
+                Kind::Space => c.is_whitespace() && c != '\n',
+                Kind::Punct => !c.is_alphanumeric() && !c.is_whitespace(),
+                Kind::Single => false,
+            };
+            if !same {
+                break;
+            }
+            digits += usize::from(c.is_ascii_digit());
+            end = i + c.len_utf8();
+        }
+        let (piece, tail) = rest.split_at(end);
+        rest = tail;
+        Some(piece)
+    })
+}
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Word,
+    Number,
+    Space,
+    Punct,
+    Single,
+}
+
+fn kind_of(c: char) -> Kind {
+    match c {
+        c if is_cjk(c) || c == '\n' => Kind::Single,
+        c if c.is_ascii_digit() => Kind::Number,
+        c if c.is_alphabetic() => Kind::Word,
+        c if c.is_whitespace() => Kind::Space,
+        _ => Kind::Punct,
+    }
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32, 0x3040..=0x30FF | 0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xAC00..=0xD7AF | 0xF900..=0xFAFF)
+}
+
+fn piece_tokens(family: TokenizerFamily, piece: &str) -> usize {
+    let first = piece.chars().next().unwrap_or(' ');
+    if is_cjk(first) {
+        return 1;
+    }
+    if first.is_whitespace() && piece.trim().is_empty() {
+        return 1;
+    }
+    // Non-ASCII letters fall back to byte-level merges of about two bytes each
+    let len = if piece.is_ascii() { piece.len() } else { piece.len().div_ceil(2) };
+    let merge_len = match kind_of(piece.trim_start().chars().next().unwrap_or(first)) {
+        Kind::Punct => 3,
+        _ => family.merge_len(),
+    };
+    len.div_ceil(merge_len).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_tokens() {
+        assert_eq!(pieces("Hello, world! 12345").collect::<Vec<_>>(), vec!["Hello", ",", " world", "!", " ", "123", "45"]);
+        assert_eq!(count_tokens("gpt-4", ""), 0);
+        // "The quick brown fox jumps over the lazy dog." is 10 tokens in cl100k
+        assert_eq!(count_tokens("gpt-4", "The quick brown fox jumps over the lazy dog."), 10);
+        assert_eq!(count_tokens("gpt-4o", "你好世界"), 4);
+        assert!(count_tokens("mistral", "internationalization") > count_tokens("gpt-4o", "internationalization"));
+
+        assert_eq!(TokenizerFamily::for_model("openai/gpt-4o-mini"), TokenizerFamily::O200k);
+        assert_eq!(TokenizerFamily::for_model("claude-3-5-sonnet-20241022"), TokenizerFamily::Claude);
+        assert_eq!(TokenizerFamily::for_model("llama3.2"), TokenizerFamily::Llama);
+        assert_eq!(context_window("gpt-4"), Some(8_192));
+        assert_eq!(context_window("meta-llama/llama-3.1-70b-instruct"), Some(131_072));
+        assert_eq!(context_window("local-model"), None);
+        assert_eq!(count_chat_tokens("gpt-4", [("user", "Hello")]), 8);
+    }
+}
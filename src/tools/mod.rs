@@ -0,0 +1,166 @@
+//! Tool Registry
+//!
+//! Capabilities agents can invoke by name:
+//! - Tool specs with a JSON Schema for their arguments
+//! - Registration from built-in code and plugins
+//! - Invocation with argument validation against the schema
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use futures::future::BoxFuture;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::error::NexaError;
+use tracing::debug;
+
+/// Description of a tool
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolSpec {
+    /// Unique tool name
+    pub name: String,
+    /// What the tool does, shown to models and users
+    pub description: String,
+    /// JSON Schema of the tool arguments
+    #[serde(default = "default_input_schema")]
+    pub input_schema: Value,
+}
+
+impl ToolSpec {
+    /// Spec whose argument schema is generated from the argument type
+    pub fn for_args<A: schemars::JsonSchema>(name: impl Into<String>, description: impl Into<String>) -> Self {
+        let mut input_schema = serde_json::to_value(schemars::schema_for!(A)).unwrap_or_else(|_| default_input_schema());
+        if let Some(schema) = input_schema.as_object_mut() {
+            // Providers expect a bare object schema
+            schema.remove("$schema");
+            schema.remove("title");
+        }
+        Self {
+            name: name.into(),
+            description: description.into(),
+            input_schema,
+        }
+    }
+}
+
+fn default_input_schema() -> Value {
+    serde_json::json!({ "type": "object" })
+}
+
+/// A capability that can be invoked with JSON arguments
+pub trait Tool: Send + Sync {
+    /// Name, description and argument schema
+    fn spec(&self) -> &ToolSpec;
+
+    /// Run the tool
+    fn call(&self, args: Value) -> BoxFuture<'_, Result<Value, NexaError>>;
+}
+
+/// Tools available to agents, keyed by name
+#[derive(Clone, Default)]
+pub struct ToolRegistry {
+    tools: Arc<RwLock<BTreeMap<String, Arc<dyn Tool>>>>,
+}
+
+impl std::fmt::Debug for ToolRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ToolRegistry")
+            .field("tools", &self.tools.read().keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl ToolRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a tool; names must be unique
+    pub fn register(&self, tool: Arc<dyn Tool>) -> Result<(), NexaError> {
+        let name = tool.spec().name.clone();
+        let mut tools = self.tools.write();
+        if tools.contains_key(&name) {
+            return Err(NexaError::invalid_input(format!("Tool '{}' is already registered", name)));
+        }
+        debug!("Registered tool {}", name);
+        tools.insert(name, tool);
+        Ok(())
+    }
+
+    /// Remove a tool, returning whether it was registered
+    pub fn unregister(&self, name: &str) -> bool {
+        self.tools.write().remove(name).is_some()
+    }
+
+    /// Look up a tool by name
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Tool>> {
+        self.tools.read().get(name).cloned()
+    }
+
+    /// Specs of all registered tools, sorted by name
+    pub fn list(&self) -> Vec<ToolSpec> {
+        self.tools.read().values().map(|t| t.spec().clone()).collect()
+    }
+
+    /// Validate arguments against the tool's schema and invoke it
+    pub async fn call(&self, name: &str, args: Value) -> Result<Value, NexaError> {
+        let tool = self.get(name)
+            .ok_or_else(|| NexaError::not_found(format!("Unknown tool '{}'", name)))?;
+
+        let schema = jsonschema::JSONSchema::compile(&tool.spec().input_schema)
+            .map_err(|e| NexaError::system(format!("Tool '{}' has an invalid schema: {}", name, e)))?;
+        if let Err(errors) = schema.validate(&args) {
+            let issues: Vec<String> = errors.map(|e| format!("{} (at {})", e, e.instance_path)).collect();
+            return Err(NexaError::invalid_input(format!(
+                "Invalid arguments for tool '{}': {}", name, issues.join("; ")
+            )));
+        }
+
+        tool.call(args).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    struct Echo(ToolSpec);
+
+    impl Tool for Echo {
+        fn spec(&self) -> &ToolSpec {
+            &self.0
+        }
+
+        fn call(&self, args: Value) -> BoxFuture<'_, Result<Value, NexaError>> {
+            Box::pin(async move { Ok(args) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tool_registry() {
+        let registry = ToolRegistry::new();
+        let echo = Arc::new(Echo(ToolSpec {
+            name: "echo".to_string(),
+            description: "Returns its arguments".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": { "text": { "type": "string" } },
+                "required": ["text"],
+            }),
+        }));
+        registry.register(echo.clone()).unwrap();
+        assert!(registry.register(echo).is_err());
+        assert_eq!(registry.list().len(), 1);
+
+        let result = registry.call("echo", json!({ "text": "hi" })).await.unwrap();
+        assert_eq!(result["text"], "hi");
+
+        let err = registry.call("echo", json!({ "text": 5 })).await.unwrap_err();
+        assert!(matches!(err, NexaError::InvalidInput(_)));
+        assert!(registry.call("missing", json!({})).await.is_err());
+
+        assert!(registry.unregister("echo"));
+        assert!(registry.list().is_empty());
+    }
+}
@@ -10,15 +10,11 @@ pub fn hello_world() -> &'static str {
 }
 
 pub async fn create_ws_server(addr: &str) -> Result<TcpListener, NexaError> {
-    TcpListener::bind(addr)
-        .await
-        .map_err(|e| NexaError::system(&e.to_string()))
+    Ok(TcpListener::bind(addr).await?)
 }
 
 pub async fn handle_ws_connection(stream: TcpStream) -> Result<(), NexaError> {
-    let ws_stream = accept_async(stream)
-        .await
-        .map_err(|e| NexaError::system(&e.to_string()))?;
+    let ws_stream = accept_async(stream).await?;
     
     debug!("New WebSocket connection established");
     handle_ws_messages(ws_stream).await
@@ -36,9 +32,7 @@ async fn handle_ws_messages(mut ws_stream: WebSocketStream<TcpStream>) -> Result
                             "code": 200,
                             "message": "Message received"
                         });
-                        ws_stream.send(Message::Text(response.to_string()))
-                            .await
-                            .map_err(|e| NexaError::system(&e.to_string()))?;
+                        ws_stream.send(Message::Text(response.to_string())).await?;
                     }
                     Message::Close(_) => {
                         debug!("Client initiated close");
@@ -51,15 +45,13 @@ async fn handle_ws_messages(mut ws_stream: WebSocketStream<TcpStream>) -> Result
                             "code": 400,
                             "message": "Unsupported message type"
                         });
-                        ws_stream.send(Message::Text(response.to_string()))
-                            .await
-                            .map_err(|e| NexaError::system(&e.to_string()))?;
+                        ws_stream.send(Message::Text(response.to_string())).await?;
                     }
                 }
             }
             Err(e) => {
                 error!("Error receiving message: {}", e);
-                return Err(NexaError::system(&e.to_string()));
+                return Err(e.into());
             }
         }
     }
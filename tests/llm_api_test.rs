@@ -1 +1,32 @@
-// This file is intentionally empty until we implement the tests 
\ No newline at end of file
+// Runs against the mock provider: cargo test --features mock --test llm_api_test
+#![cfg(feature = "mock")]
+
+use nexa_core::llm::{ChatMessage, Conversation, LLMClient, LLMConfig, MockLLM};
+
+#[tokio::test]
+async fn test_completion_against_mock() {
+    let config = LLMConfig::with_mock("llm-api-test");
+    MockLLM::named(&config.server_url).reply_when("capital of France", "Paris");
+    let client = LLMClient::new(config.clone()).unwrap();
+
+    assert_eq!(client.complete("What is the capital of France?").await.unwrap(), "Paris");
+    assert_eq!(
+        client.complete_chat(&[ChatMessage::system("Be brief."), ChatMessage::user("Hi")]).await.unwrap(),
+        "Mock response to: Hi"
+    );
+
+    let requests = MockLLM::named(&config.server_url).requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[1].messages[0].content, "Be brief.");
+}
+
+#[tokio::test]
+async fn test_conversation_against_mock() {
+    let config = LLMConfig::with_mock("llm-api-conversation");
+    MockLLM::named(&config.server_url).set_default_reply("Reply {{n}}");
+    let client = LLMClient::new(config).unwrap();
+
+    let mut conversation = Conversation::new();
+    assert_eq!(conversation.send(&client, ChatMessage::user("one")).await.unwrap(), "Reply 1");
+    assert_eq!(conversation.send(&client, ChatMessage::user("two")).await.unwrap(), "Reply 2");
+}
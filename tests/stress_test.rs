@@ -22,6 +22,7 @@ proptest! {
             node_timeout: Duration::from_secs(5),
             replication_factor: 3,
             cluster_id: Uuid::new_v4().to_string(),
+            ..Default::default()
         };
 
         // Validate configuration
@@ -43,6 +44,7 @@ async fn test_cluster_config() -> Result<(), NexaError> {
         node_timeout: Duration::from_secs(5),
         replication_factor: 3,
         cluster_id: Uuid::new_v4().to_string(),
+        ..Default::default()
     };
 
     // Validate configuration